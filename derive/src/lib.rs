@@ -0,0 +1,256 @@
+//! Derive macro for `fog_math::schema::SchemaType`.
+//!
+//! The derived validator follows the type's serde representation: structs
+//! become maps and enums become fog-pack enums. A field is optional if it
+//! or its struct has `default`, or if it has `skip_serializing_if`.
+//! Skipped fields and renames are honored. Flattened fields are left out,
+//! as they hold whatever keys the schema doesn't describe. Any other serde
+//! attribute is an error, since the schema would no longer match what serde
+//! writes.
+//!
+//! Doc comments become validator comments wherever fog-pack has room for
+//! one. They're part of the schema hash, so editing one needs a new schema
+//! version.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    parse_macro_input, spanned::Spanned, Attribute, Data, DeriveInput, Error, Expr, Fields, Lit,
+    LitStr, Meta,
+};
+
+#[proc_macro_derive(SchemaType)]
+pub fn derive_schema_type(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let ident = &input.ident;
+    let name = ident.to_string();
+    let doc = docs(&input.attrs);
+    let container = SerdeAttrs::parse(&input.attrs)?;
+    let body = match &input.data {
+        Data::Struct(s) => match &s.fields {
+            Fields::Named(_) => map(&s.fields, &doc, container.optional)?,
+            _ => {
+                return Err(Error::new(
+                    input.span(),
+                    "only structs with named fields are supported",
+                ))
+            }
+        },
+        Data::Enum(e) => {
+            let mut variants = Vec::new();
+            for v in &e.variants {
+                let serde = SerdeAttrs::parse(&v.attrs)?;
+                if serde.skip {
+                    continue;
+                }
+                let tag = serde.rename.unwrap_or_else(|| v.ident.to_string());
+                let doc = docs(&v.attrs);
+                let payload = match &v.fields {
+                    Fields::Unit => quote!(None),
+                    Fields::Named(_) => {
+                        let map = map(&v.fields, &doc, false)?;
+                        quote!(Some(#map))
+                    }
+                    Fields::Unnamed(f) if f.unnamed.len() == 1 => {
+                        let ty = &f.unnamed[0].ty;
+                        quote!(Some(::fog_math::schema::commented(
+                            <#ty as ::fog_math::schema::SchemaType>::validator(types),
+                            #doc,
+                        )))
+                    }
+                    Fields::Unnamed(f) => {
+                        return Err(Error::new(
+                            f.span(),
+                            "tuple variants with several fields aren't supported",
+                        ))
+                    }
+                };
+                variants.push(quote!(.insert(#tag, #payload)));
+            }
+            quote!(::fog_pack::validator::EnumValidator::new() #(#variants)* .build())
+        }
+        Data::Union(_) => return Err(Error::new(input.span(), "unions aren't supported")),
+    };
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::fog_math::schema::SchemaType for #ident #ty_generics #where_clause {
+            fn validator(
+                types: &mut ::std::collections::BTreeMap<::std::string::String, ::fog_pack::validator::Validator>,
+            ) -> ::fog_pack::validator::Validator {
+                ::fog_math::schema::named(types, #name, |types| #body)
+            }
+        }
+    })
+}
+
+/// Build a map validator for a set of named fields. `all_optional` is set
+/// when the struct itself has `#[serde(default)]`.
+fn map(fields: &Fields, doc: &str, all_optional: bool) -> syn::Result<TokenStream> {
+    let mut adds = Vec::new();
+    for f in fields {
+        let serde = SerdeAttrs::parse(&f.attrs)?;
+        if serde.skip {
+            continue;
+        }
+        let key = match serde.rename {
+            Some(r) => r,
+            None => f.ident.as_ref().unwrap().to_string(),
+        };
+        let ty = &f.ty;
+        let doc = docs(&f.attrs);
+        let add = if all_optional || serde.optional {
+            quote!(opt_add)
+        } else {
+            quote!(req_add)
+        };
+        adds.push(quote! {
+            .#add(#key, ::fog_math::schema::commented(
+                <#ty as ::fog_math::schema::SchemaType>::validator(types),
+                #doc,
+            ))
+        });
+    }
+    Ok(quote! {
+        ::fog_pack::validator::MapValidator::new().comment(#doc) #(#adds)* .build()
+    })
+}
+
+/// Join the doc comments on an item into a single string.
+fn docs(attrs: &[Attribute]) -> String {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|a| a.path().is_ident("doc"))
+        .filter_map(|a| match &a.meta {
+            Meta::NameValue(nv) => match &nv.value {
+                Expr::Lit(l) => match &l.lit {
+                    Lit::Str(s) => Some(s.value()),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+    // Strip the single leading space each `///` line carries.
+    let lines: Vec<&str> = lines
+        .iter()
+        .map(|l| l.strip_prefix(' ').unwrap_or(l))
+        .collect();
+    lines.join("\n").trim().to_string()
+}
+
+/// The serde attributes that change the serialized shape of a type, field
+/// or variant.
+#[derive(Default)]
+struct SerdeAttrs {
+    rename: Option<String>,
+    skip: bool,
+    optional: bool,
+}
+
+impl SerdeAttrs {
+    /// Read the serde attributes on an item, failing on any that aren't
+    /// modeled here.
+    fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut out = Self::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("serde")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    out.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("skip") || meta.path.is_ident("flatten") {
+                    out.skip = true;
+                } else if meta.path.is_ident("skip_serializing_if") || meta.path.is_ident("default")
+                {
+                    out.optional = true;
+                    if meta.input.peek(syn::Token![=]) {
+                        meta.value()?.parse::<LitStr>()?;
+                    }
+                } else if meta.path.is_ident("remote") {
+                    // Only names the type serde implements for; the shape
+                    // is still described by the fields.
+                    meta.value()?.parse::<LitStr>()?;
+                } else {
+                    let name = meta
+                        .path
+                        .get_ident()
+                        .map_or_else(|| "?".to_string(), |i| i.to_string());
+                    return Err(meta.error(format!(
+                        "`#[serde({})]` isn't supported by `SchemaType`",
+                        name
+                    )));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn unsupported_serde() {
+        let err = expand(parse_quote! {
+            #[serde(rename_all = "lowercase")]
+            enum Shape {
+                Square,
+                Circle,
+            }
+        })
+        .unwrap_err();
+        assert!(err.to_string().contains("rename_all"));
+        for attr in [
+            quote!(#[serde(untagged)]),
+            quote!(#[serde(tag = "t")]),
+            quote!(#[serde(transparent)]),
+        ] {
+            let input = parse_quote! {
+                #attr
+                struct S {
+                    a: u8,
+                }
+            };
+            assert!(expand(input).is_err());
+        }
+        for attr in [
+            quote!(#[serde(skip_serializing)]),
+            quote!(#[serde(skip_deserializing)]),
+            quote!(#[serde(with = "m")]),
+        ] {
+            let input = parse_quote! {
+                struct S {
+                    #attr
+                    a: u8,
+                }
+            };
+            assert!(expand(input).is_err());
+        }
+    }
+
+    #[test]
+    fn supported_serde() {
+        let input = parse_quote! {
+            #[serde(default, remote = "Self")]
+            struct S {
+                #[serde(rename = "b", skip_serializing_if = "Option::is_none")]
+                a: Option<u8>,
+                #[serde(skip)]
+                c: u8,
+                #[serde(flatten)]
+                d: u8,
+                #[serde(default = "one")]
+                e: u8,
+            }
+        };
+        assert!(expand(input).is_ok());
+    }
+}
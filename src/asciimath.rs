@@ -0,0 +1,1091 @@
+//! Conversion between AsciiMath and fog-math elements.
+//!
+//! AsciiMath has no notion of a syntax error, so parsing always succeeds.
+//! Anything that isn't a known symbol is treated as an identifier, number, or
+//! operator, just like the reference implementation does.
+
+use crate::math::*;
+use crate::tree::{into_elem, into_vec, op_char};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Sym {
+    /// An identifier.
+    Id(&'static str),
+    /// An upright single-character identifier.
+    IdNormal(char),
+    /// An operator.
+    Op(char),
+    /// An operator that takes scripts as limits.
+    Large(char),
+    /// A function name that takes scripts as limits.
+    LimitFunc(&'static str),
+    /// Left bracket. The invisible bracket has no character.
+    Left(Option<char>),
+    /// Right bracket. The invisible bracket has no character.
+    Right(Option<char>),
+    /// Horizontal space, in em.
+    Space(f32),
+    /// Commands taking one argument.
+    Sqrt,
+    Text,
+    Font(Variant),
+    /// Overscript or underscript: character, accent, and whether it goes
+    /// under.
+    Accent(char, bool, bool),
+    /// Brackets placed around an argument.
+    Fence(char, char),
+    /// Commands taking two arguments.
+    Frac,
+    Root,
+    Overset,
+    Underset,
+}
+
+/// The AsciiMath symbol table. The first entry for a given output is the one
+/// used when writing.
+const SYMBOLS: &[(&str, Sym)] = &[
+    // Operators
+    ("+", Sym::Op('+')),
+    ("-", Sym::Op('−')),
+    ("*", Sym::Op('⋅')),
+    ("**", Sym::Op('∗')),
+    ("***", Sym::Op('⋆')),
+    ("//", Sym::Op('/')),
+    ("\\\\", Sym::Op('\\')),
+    ("setminus", Sym::Op('\\')),
+    ("xx", Sym::Op('×')),
+    ("|><", Sym::Op('⋉')),
+    ("><|", Sym::Op('⋊')),
+    ("|><|", Sym::Op('⋈')),
+    ("-:", Sym::Op('÷')),
+    ("divide", Sym::Op('÷')),
+    ("@", Sym::Op('∘')),
+    ("o+", Sym::Op('⊕')),
+    ("ox", Sym::Op('⊗')),
+    ("o.", Sym::Op('⊙')),
+    ("sum", Sym::Large('∑')),
+    ("prod", Sym::Large('∏')),
+    ("^^", Sym::Op('∧')),
+    ("^^^", Sym::Large('⋀')),
+    ("vv", Sym::Op('∨')),
+    ("vvv", Sym::Large('⋁')),
+    ("nn", Sym::Op('∩')),
+    ("nnn", Sym::Large('⋂')),
+    ("uu", Sym::Op('∪')),
+    ("uuu", Sym::Large('⋃')),
+    // Relations
+    ("=", Sym::Op('=')),
+    ("!=", Sym::Op('≠')),
+    (":=", Sym::Op('≔')),
+    ("<", Sym::Op('<')),
+    ("lt", Sym::Op('<')),
+    (">", Sym::Op('>')),
+    ("gt", Sym::Op('>')),
+    ("<=", Sym::Op('≤')),
+    ("le", Sym::Op('≤')),
+    (">=", Sym::Op('≥')),
+    ("ge", Sym::Op('≥')),
+    ("-<", Sym::Op('≺')),
+    (">-", Sym::Op('≻')),
+    ("-<=", Sym::Op('⪯')),
+    (">-=", Sym::Op('⪰')),
+    ("in", Sym::Op('∈')),
+    ("!in", Sym::Op('∉')),
+    ("sub", Sym::Op('⊂')),
+    ("sup", Sym::Op('⊃')),
+    ("sube", Sym::Op('⊆')),
+    ("supe", Sym::Op('⊇')),
+    ("-=", Sym::Op('≡')),
+    ("~=", Sym::Op('≅')),
+    ("~~", Sym::Op('≈')),
+    ("~", Sym::Op('∼')),
+    ("prop", Sym::Op('∝')),
+    // Logic
+    ("and", Sym::Id("and")),
+    ("or", Sym::Id("or")),
+    ("not", Sym::Op('¬')),
+    ("=>", Sym::Op('⇒')),
+    ("if", Sym::Id("if")),
+    ("<=>", Sym::Op('⇔')),
+    ("iff", Sym::Op('⇔')),
+    ("AA", Sym::Op('∀')),
+    ("EE", Sym::Op('∃')),
+    ("_|_", Sym::Op('⊥')),
+    ("TT", Sym::Op('⊤')),
+    ("|--", Sym::Op('⊢')),
+    ("|==", Sym::Op('⊨')),
+    // Miscellaneous
+    ("int", Sym::Op('∫')),
+    ("oint", Sym::Op('∮')),
+    ("del", Sym::Op('∂')),
+    ("grad", Sym::Op('∇')),
+    ("+-", Sym::Op('±')),
+    ("-+", Sym::Op('∓')),
+    ("O/", Sym::IdNormal('∅')),
+    ("oo", Sym::IdNormal('∞')),
+    ("aleph", Sym::IdNormal('ℵ')),
+    ("/_", Sym::Op('∠')),
+    (":.", Sym::Op('∴')),
+    (":'", Sym::Op('∵')),
+    ("|...|", Sym::Op('…')),
+    ("...", Sym::Op('…')),
+    ("|cdots|", Sym::Op('⋯')),
+    ("cdots", Sym::Op('⋯')),
+    ("vdots", Sym::Op('⋮')),
+    ("ddots", Sym::Op('⋱')),
+    ("|quad|", Sym::Space(1.0)),
+    ("quad", Sym::Space(1.0)),
+    ("qquad", Sym::Space(2.0)),
+    ("\\ ", Sym::Space(0.25)),
+    ("diamond", Sym::Op('⋄')),
+    ("square", Sym::Op('□')),
+    ("|__", Sym::Op('⌊')),
+    ("__|", Sym::Op('⌋')),
+    ("|~", Sym::Op('⌈')),
+    ("~|", Sym::Op('⌉')),
+    ("CC", Sym::IdNormal('ℂ')),
+    ("NN", Sym::IdNormal('ℕ')),
+    ("QQ", Sym::IdNormal('ℚ')),
+    ("RR", Sym::IdNormal('ℝ')),
+    ("ZZ", Sym::IdNormal('ℤ')),
+    ("'", Sym::Op('′')),
+    ("prime", Sym::Op('′')),
+    // Arrows
+    ("uarr", Sym::Op('↑')),
+    ("darr", Sym::Op('↓')),
+    ("->", Sym::Op('→')),
+    ("rarr", Sym::Op('→')),
+    ("to", Sym::Op('→')),
+    (">->", Sym::Op('↣')),
+    ("->>", Sym::Op('↠')),
+    (">->>", Sym::Op('⤖')),
+    ("|->", Sym::Op('↦')),
+    ("larr", Sym::Op('←')),
+    ("harr", Sym::Op('↔')),
+    ("rArr", Sym::Op('⇒')),
+    ("lArr", Sym::Op('⇐')),
+    ("hArr", Sym::Op('⇔')),
+    // Brackets
+    ("(", Sym::Left(Some('('))),
+    (")", Sym::Right(Some(')'))),
+    ("[", Sym::Left(Some('['))),
+    ("]", Sym::Right(Some(']'))),
+    ("{", Sym::Left(Some('{'))),
+    ("}", Sym::Right(Some('}'))),
+    ("(:", Sym::Left(Some('⟨'))),
+    (":)", Sym::Right(Some('⟩'))),
+    ("<<", Sym::Left(Some('⟨'))),
+    (">>", Sym::Right(Some('⟩'))),
+    ("{:", Sym::Left(None)),
+    (":}", Sym::Right(None)),
+    // Greek
+    ("alpha", Sym::Id("α")),
+    ("beta", Sym::Id("β")),
+    ("gamma", Sym::Id("γ")),
+    ("Gamma", Sym::IdNormal('Γ')),
+    ("delta", Sym::Id("δ")),
+    ("Delta", Sym::IdNormal('Δ')),
+    ("epsilon", Sym::Id("ε")),
+    ("epsi", Sym::Id("ε")),
+    ("varepsilon", Sym::Id("ɛ")),
+    ("zeta", Sym::Id("ζ")),
+    ("eta", Sym::Id("η")),
+    ("theta", Sym::Id("θ")),
+    ("Theta", Sym::IdNormal('Θ')),
+    ("vartheta", Sym::Id("ϑ")),
+    ("iota", Sym::Id("ι")),
+    ("kappa", Sym::Id("κ")),
+    ("lambda", Sym::Id("λ")),
+    ("Lambda", Sym::IdNormal('Λ')),
+    ("mu", Sym::Id("μ")),
+    ("nu", Sym::Id("ν")),
+    ("xi", Sym::Id("ξ")),
+    ("Xi", Sym::IdNormal('Ξ')),
+    ("pi", Sym::Id("π")),
+    ("Pi", Sym::IdNormal('Π')),
+    ("rho", Sym::Id("ρ")),
+    ("sigma", Sym::Id("σ")),
+    ("Sigma", Sym::IdNormal('Σ')),
+    ("tau", Sym::Id("τ")),
+    ("upsilon", Sym::Id("υ")),
+    ("phi", Sym::Id("ϕ")),
+    ("Phi", Sym::IdNormal('Φ')),
+    ("varphi", Sym::Id("φ")),
+    ("chi", Sym::Id("χ")),
+    ("psi", Sym::Id("ψ")),
+    ("Psi", Sym::IdNormal('Ψ')),
+    ("omega", Sym::Id("ω")),
+    ("Omega", Sym::IdNormal('Ω')),
+    // Functions
+    ("sin", Sym::Id("sin")),
+    ("cos", Sym::Id("cos")),
+    ("tan", Sym::Id("tan")),
+    ("sec", Sym::Id("sec")),
+    ("csc", Sym::Id("csc")),
+    ("cot", Sym::Id("cot")),
+    ("arcsin", Sym::Id("arcsin")),
+    ("arccos", Sym::Id("arccos")),
+    ("arctan", Sym::Id("arctan")),
+    ("sinh", Sym::Id("sinh")),
+    ("cosh", Sym::Id("cosh")),
+    ("tanh", Sym::Id("tanh")),
+    ("sech", Sym::Id("sech")),
+    ("csch", Sym::Id("csch")),
+    ("coth", Sym::Id("coth")),
+    ("exp", Sym::Id("exp")),
+    ("log", Sym::Id("log")),
+    ("ln", Sym::Id("ln")),
+    ("det", Sym::Id("det")),
+    ("dim", Sym::Id("dim")),
+    ("mod", Sym::Id("mod")),
+    ("gcd", Sym::Id("gcd")),
+    ("lcm", Sym::Id("lcm")),
+    ("lub", Sym::Id("lub")),
+    ("glb", Sym::Id("glb")),
+    ("lim", Sym::LimitFunc("lim")),
+    ("Lim", Sym::LimitFunc("Lim")),
+    ("min", Sym::LimitFunc("min")),
+    ("max", Sym::LimitFunc("max")),
+    // Commands
+    ("sqrt", Sym::Sqrt),
+    ("text", Sym::Text),
+    ("bb", Sym::Font(Variant::Bold)),
+    ("mathbf", Sym::Font(Variant::Bold)),
+    ("bbb", Sym::Font(Variant::DoubleStruck)),
+    ("mathbb", Sym::Font(Variant::DoubleStruck)),
+    ("cc", Sym::Font(Variant::Script)),
+    ("mathcal", Sym::Font(Variant::Script)),
+    ("tt", Sym::Font(Variant::Monospace)),
+    ("mathtt", Sym::Font(Variant::Monospace)),
+    ("fr", Sym::Font(Variant::Fraktur)),
+    ("mathfrak", Sym::Font(Variant::Fraktur)),
+    ("sf", Sym::Font(Variant::SansSerif)),
+    ("mathsf", Sym::Font(Variant::SansSerif)),
+    ("hat", Sym::Accent('^', true, false)),
+    ("bar", Sym::Accent('¯', true, false)),
+    ("overline", Sym::Accent('¯', true, false)),
+    ("vec", Sym::Accent('→', true, false)),
+    ("dot", Sym::Accent('˙', true, false)),
+    ("ddot", Sym::Accent('¨', true, false)),
+    ("tilde", Sym::Accent('~', true, false)),
+    ("overarc", Sym::Accent('⌢', true, false)),
+    ("ul", Sym::Accent('_', false, true)),
+    ("underline", Sym::Accent('_', false, true)),
+    ("obrace", Sym::Accent('⏞', false, false)),
+    ("overbrace", Sym::Accent('⏞', false, false)),
+    ("ubrace", Sym::Accent('⏟', false, true)),
+    ("underbrace", Sym::Accent('⏟', false, true)),
+    ("abs", Sym::Fence('|', '|')),
+    ("norm", Sym::Fence('‖', '‖')),
+    ("floor", Sym::Fence('⌊', '⌋')),
+    ("ceil", Sym::Fence('⌈', '⌉')),
+    ("frac", Sym::Frac),
+    ("root", Sym::Root),
+    ("stackrel", Sym::Overset),
+    ("overset", Sym::Overset),
+    ("underset", Sym::Underset),
+];
+
+/// Parse AsciiMath into an element.
+pub fn parse(src: &str) -> Element {
+    let mut p = Parser { src, pos: 0 };
+    let mut out = Vec::new();
+    loop {
+        out.extend(p.expr_list().into_iter().map(|n| n.e));
+        // Unmatched right brackets are kept as plain operators.
+        match p.next() {
+            Tok::Sym(_, Sym::Right(Some(c))) => out.push(Element::op(c)),
+            Tok::Eof => break,
+            _ => (),
+        }
+    }
+    into_elem(out)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Tok<'a> {
+    Sym(&'a str, Sym),
+    Num(&'a str),
+    Char(char),
+    Quoted(&'a str),
+    Sup,
+    Sub,
+    Div,
+    Eof,
+}
+
+/// A parsed expression. Bracketed groups also hold their unbracketed
+/// contents, for use as command arguments and in matrices.
+struct Node {
+    e: Element,
+    group: Option<Group>,
+}
+
+struct Group {
+    open: Option<char>,
+    close: Option<char>,
+    inner: Vec<Node>,
+}
+
+impl Node {
+    fn new(e: Element) -> Self {
+        Self { e, group: None }
+    }
+
+    /// Get the element, with any outer brackets removed.
+    fn arg(self) -> Element {
+        match self.group {
+            Some(g) => into_elem(g.inner.into_iter().map(|n| n.e).collect()),
+            None => self.e,
+        }
+    }
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Tok<'a> {
+        let rest = self.src[self.pos..].trim_start();
+        self.pos = self.src.len() - rest.len();
+        let Some(c) = rest.chars().next() else {
+            return Tok::Eof;
+        };
+        if c == '"' {
+            let body = &rest[1..];
+            let len = body.find('"').unwrap_or(body.len());
+            self.pos += 1 + len + usize::from(len < body.len());
+            return Tok::Quoted(&body[..len]);
+        }
+        if c.is_ascii_digit() || (c == '.' && rest[1..].starts_with(|c: char| c.is_ascii_digit())) {
+            let mut len = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let frac = &rest[len..];
+            if frac.starts_with('.') && frac[1..].starts_with(|c: char| c.is_ascii_digit()) {
+                len += 1 + frac[1..]
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(frac.len() - 1);
+            }
+            self.pos += len;
+            return Tok::Num(&rest[..len]);
+        }
+        if let Some(&(s, sym)) = SYMBOLS
+            .iter()
+            .filter(|(s, _)| rest.starts_with(s))
+            .max_by_key(|(s, _)| s.len())
+        {
+            // Single-character infix operators lose to longer symbols.
+            self.pos += s.len();
+            return Tok::Sym(s, sym);
+        }
+        self.pos += c.len_utf8();
+        match c {
+            '^' => Tok::Sup,
+            '_' => Tok::Sub,
+            '/' => Tok::Div,
+            c => Tok::Char(c),
+        }
+    }
+
+    fn peek(&mut self) -> Tok<'a> {
+        let pos = self.pos;
+        let t = self.next();
+        self.pos = pos;
+        t
+    }
+
+    /// Parse expressions until a right bracket or the end of input.
+    fn expr_list(&mut self) -> Vec<Node> {
+        let mut out = Vec::new();
+        loop {
+            match self.peek() {
+                Tok::Eof | Tok::Sym(_, Sym::Right(_)) => return out,
+                _ => (),
+            }
+            let num = self.intermediate();
+            if self.peek() == Tok::Div {
+                self.next();
+                let den = self.intermediate();
+                out.push(Node::new(Element::frac(num.arg(), den.arg())));
+            } else {
+                out.push(num);
+            }
+        }
+    }
+
+    /// Parse a simple expression with optional scripts.
+    fn intermediate(&mut self) -> Node {
+        let (base, limits) = self.simple();
+        let mut sub = None;
+        let mut sup = None;
+        if self.peek() == Tok::Sub {
+            self.next();
+            sub = Some(self.simple().0.arg());
+        }
+        if self.peek() == Tok::Sup {
+            self.next();
+            sup = Some(self.simple().0.arg());
+        }
+        if sub.is_none() && sup.is_none() {
+            return base;
+        }
+        let base = base.e;
+        Node::new(match (sub, sup, limits) {
+            (Some(sub), None, false) => Element::sub(base, sub),
+            (None, Some(sup), false) => Element::sup(base, sup),
+            (Some(sub), Some(sup), false) => Element::sub_sup(base, sub, sup),
+            (Some(sub), None, true) => Element::under(base, sub),
+            (None, Some(sup), true) => Element::over(base, sup),
+            (Some(sub), Some(sup), true) => Element::under_over(base, sub, sup),
+            (None, None, _) => unreachable!(),
+        })
+    }
+
+    /// Parse a simple expression. Also returns whether scripts on it should be
+    /// placed as limits.
+    fn simple(&mut self) -> (Node, bool) {
+        let node = match self.next() {
+            Tok::Eof => Node::new(Element::row([])),
+            Tok::Num(n) => Node::new(Element::num(n)),
+            Tok::Quoted(t) => Node::new(Element::text(t)),
+            Tok::Sup => Node::new(Element::op('^')),
+            Tok::Sub => Node::new(Element::op('_')),
+            Tok::Div => Node::new(Element::op('/')),
+            Tok::Char(c) if c.is_alphabetic() => Node::new(Element::id(c.to_string())),
+            Tok::Char(c) => Node::new(Element::op(c)),
+            Tok::Sym(_, sym) => match sym {
+                Sym::Id(t) => Node::new(Element::id(t)),
+                Sym::IdNormal(c) => Node::new(Element::id_normal(c.to_string())),
+                Sym::Op(c) => Node::new(Element::op(c)),
+                Sym::Large(c) => return (Node::new(Element::op(c)), true),
+                Sym::LimitFunc(t) => return (Node::new(Element::id(t)), true),
+                Sym::Space(0.25) => Node::new(Element::text("\u{a0}")),
+                Sym::Space(w) => Node::new(Element::space(Space::width(Length::Em(w)))),
+                Sym::Left(open) => self.group(open),
+                Sym::Right(c) => Node::new(match c {
+                    Some(c) => Element::op(c),
+                    None => Element::row([]),
+                }),
+                Sym::Sqrt => Node::new(Element::sqrt(self.simple().0.arg())),
+                Sym::Text => {
+                    let rest = &self.src[self.pos..];
+                    let trimmed = rest.trim_start();
+                    match trimmed.chars().next() {
+                        Some(open @ ('(' | '[' | '{')) => {
+                            let close = match open {
+                                '(' => ')',
+                                '[' => ']',
+                                _ => '}',
+                            };
+                            let body = &trimmed[1..];
+                            let len = body.find(close).unwrap_or(body.len());
+                            self.pos += rest.len() - trimmed.len()
+                                + 1
+                                + len
+                                + usize::from(len < body.len());
+                            Node::new(Element::text(&body[..len]))
+                        }
+                        _ => Node::new(Element::id("text")),
+                    }
+                }
+                Sym::Font(v) => Node::new(self.simple().0.arg().variant(v)),
+                Sym::Accent(c, accent, under) => {
+                    let base = self.simple().0.arg();
+                    let mark = Element::op(c);
+                    let limits = matches!(c, '⏞' | '⏟');
+                    let e = match (accent, under) {
+                        (true, false) => Element::over_accent(base, mark),
+                        (false, false) => Element::over(base, mark),
+                        (true, true) => Element::under_accent(base, mark),
+                        (false, true) => Element::under(base, mark),
+                    };
+                    return (Node::new(e), limits);
+                }
+                Sym::Fence(open, close) => {
+                    let mut elems = vec![Element::op(open)];
+                    elems.extend(into_vec(self.simple().0.arg()));
+                    elems.push(Element::op(close));
+                    Node::new(Element::row(elems))
+                }
+                Sym::Frac => {
+                    let num = self.simple().0.arg();
+                    Node::new(Element::frac(num, self.simple().0.arg()))
+                }
+                Sym::Root => {
+                    let index = self.simple().0.arg();
+                    Node::new(Element::root(self.simple().0.arg(), index))
+                }
+                Sym::Overset => {
+                    let over = self.simple().0.arg();
+                    Node::new(Element::over(self.simple().0.arg(), over))
+                }
+                Sym::Underset => {
+                    let under = self.simple().0.arg();
+                    Node::new(Element::under(self.simple().0.arg(), under))
+                }
+            },
+        };
+        (node, false)
+    }
+
+    /// Parse a bracketed group, after the left bracket.
+    fn group(&mut self, open: Option<char>) -> Node {
+        let inner = self.expr_list();
+        let close = match self.next() {
+            Tok::Sym(_, Sym::Right(c)) => c,
+            _ => None,
+        };
+        let mut elems = Vec::new();
+        elems.extend(open.map(Element::op));
+        if let Some(table) = matrix(&inner) {
+            elems.push(table);
+        } else {
+            elems.extend(inner.iter().map(|n| n.e.clone()));
+        }
+        elems.extend(close.map(Element::op));
+        let e = if open.is_none() && close.is_none() {
+            into_elem(elems)
+        } else {
+            Element::row(elems)
+        };
+        Node {
+            e,
+            group: Some(Group { open, close, inner }),
+        }
+    }
+}
+
+fn is_comma(e: &Element) -> bool {
+    matches!(e.elem(), MathElement::Op(','))
+}
+
+/// Check if the contents of a bracketed group form a matrix: two or more
+/// bracketed rows separated by commas, with the same number of columns.
+fn matrix(inner: &[Node]) -> Option<Element> {
+    if inner.len() < 3 {
+        return None;
+    }
+    let mut rows = Vec::new();
+    for (i, node) in inner.iter().enumerate() {
+        if i % 2 == 1 {
+            if !is_comma(&node.e) {
+                return None;
+            }
+            continue;
+        }
+        let g = node.group.as_ref()?;
+        if g.open.is_none() || g.close.is_none() {
+            return None;
+        }
+        let cells: Vec<TableCell> = g
+            .inner
+            .split(|n| is_comma(&n.e))
+            .map(|c| TableCell::new(c.iter().map(|n| n.e.clone())))
+            .collect();
+        rows.push(TableRow::new(cells));
+    }
+    if inner.len().is_multiple_of(2) {
+        return None;
+    }
+    let cols = rows[0].cells.len();
+    rows.iter()
+        .all(|r| r.cells.len() == cols)
+        .then(|| Element::table(rows))
+}
+
+impl Element {
+    /// Write the element out as AsciiMath. Elements with no AsciiMath
+    /// equivalent, like prescripts and phantoms, are approximated.
+    pub fn to_asciimath(&self) -> String {
+        let mut w = Writer::default();
+        w.items(self);
+        w.out
+    }
+}
+
+/// Find the AsciiMath input for a symbol.
+fn lookup(f: impl Fn(Sym) -> bool) -> Option<&'static str> {
+    SYMBOLS.iter().find(|(_, sym)| f(*sym)).map(|(s, _)| *s)
+}
+
+fn op_str(c: char) -> Option<&'static str> {
+    lookup(
+        |s| matches!(s, Sym::Op(t) | Sym::Large(t) | Sym::Left(Some(t)) | Sym::Right(Some(t)) if t == c),
+    )
+}
+
+fn font(e: &Element) -> Option<&'static str> {
+    let v = e.attributes()?.variant?;
+    lookup(|s| s == Sym::Font(v))
+}
+
+/// Get the brackets around a row, if it starts and ends with them.
+fn fences(elems: &[Element]) -> Option<(&'static str, &'static str)> {
+    let open = match elems.first()?.elem() {
+        MathElement::Op(c) => lookup(|s| s == Sym::Left(Some(*c)))?,
+        _ => return None,
+    };
+    let close = match elems.last()?.elem() {
+        MathElement::Op(c) => lookup(|s| s == Sym::Right(Some(*c)))?,
+        _ => return None,
+    };
+    (elems.len() >= 2).then_some((open, close))
+}
+
+/// Get the accent command for an over- or underscript.
+fn accent(e: &Element) -> Option<&'static str> {
+    let (mark, accent, under) = match e.elem() {
+        MathElement::Over { over, accent, .. } => (over, *accent, false),
+        MathElement::Under {
+            under,
+            accent_under,
+            ..
+        } => (under, *accent_under, true),
+        _ => return None,
+    };
+    let c = op_char(mark)?;
+    lookup(|s| s == Sym::Accent(c, accent, under))
+}
+
+/// Check if scripts on this element are written as limits.
+fn takes_limits(e: &Element) -> bool {
+    match e.elem() {
+        MathElement::Id { t, .. } => lookup(|s| matches!(s, Sym::LimitFunc(n) if n == t)).is_some(),
+        MathElement::Over { .. } | MathElement::Under { .. } => {
+            matches!(accent(e), Some("obrace" | "ubrace"))
+        }
+        _ => op_char(e).is_some_and(|c| lookup(|s| s == Sym::Large(c)).is_some()),
+    }
+}
+
+/// Check if the element is written as a single AsciiMath simple expression.
+fn is_simple(e: &Element) -> bool {
+    if font(e).is_some() {
+        return true;
+    }
+    match e.elem() {
+        MathElement::Op(_) | MathElement::Oper(_) | MathElement::ResolvedOper(_) => true,
+        MathElement::Text(_) | MathElement::Str(_) | MathElement::Err(_) => true,
+        MathElement::Id { .. } | MathElement::Num(_) | MathElement::Space(_) => true,
+        MathElement::Sqrt(_) | MathElement::Root { .. } | MathElement::Table { .. } => true,
+        MathElement::Row(elems) => fences(elems).is_some(),
+        MathElement::Over { base, .. } | MathElement::Under { base, .. } => {
+            accent(e).is_some() || !takes_limits(base)
+        }
+        MathElement::UnderOver { base, .. } => !takes_limits(base),
+        _ => false,
+    }
+}
+
+/// Quote a piece of text.
+fn text(t: &str) -> String {
+    if t.contains('"') {
+        format!("text({})", t)
+    } else {
+        format!("\"{}\"", t)
+    }
+}
+
+#[derive(Default)]
+struct Writer {
+    out: String,
+    /// The last token written.
+    last: String,
+}
+
+impl Writer {
+    /// Write a token, separating it from the previous one if they would
+    /// otherwise run together into a word or a different symbol.
+    fn push(&mut self, s: &str) {
+        if !self.last.is_empty() {
+            let joined = format!("{}{}", self.last, s);
+            let mut p = Parser {
+                src: &joined,
+                pos: 0,
+            };
+            p.next();
+            let words = self.last.ends_with(|c: char| c.is_alphanumeric())
+                && s.starts_with(|c: char| c.is_alphanumeric());
+            if words || p.pos != self.last.len() {
+                self.out.push(' ');
+            }
+        }
+        self.out.push_str(s);
+        self.last = s.to_string();
+    }
+
+    /// Write the contents of an element, without any grouping.
+    fn items(&mut self, e: &Element) {
+        match e.elem() {
+            MathElement::Row(elems) if font(e).is_none() && fences(elems).is_none() => {
+                elems.iter().for_each(|e| self.element(e))
+            }
+            _ => self.element(e),
+        }
+    }
+
+    /// Write a command argument or script, which loses its outer brackets
+    /// when read back.
+    fn arg(&mut self, e: &Element) {
+        let fenced = matches!(e.elem(), MathElement::Row(_)) && font(e).is_none();
+        if is_simple(e) && !fenced {
+            self.element(e);
+        } else {
+            self.push("(");
+            self.items(e);
+            self.push(")");
+        }
+    }
+
+    /// Write the base of a script.
+    fn base(&mut self, e: &Element) {
+        if is_simple(e) {
+            self.element(e);
+        } else {
+            self.push("{:");
+            self.items(e);
+            self.push(":}");
+        }
+    }
+
+    fn element(&mut self, e: &Element) {
+        if let Some(cmd) = font(e) {
+            let mut inner = e.clone();
+            inner.attributes_mut().variant = None;
+            self.push(cmd);
+            self.arg(&inner);
+            return;
+        }
+        match e.elem() {
+            MathElement::Op(_) | MathElement::Oper(_) | MathElement::ResolvedOper(_) => {
+                let c = op_char(e).unwrap();
+                match op_str(c) {
+                    Some(s) => self.push(s),
+                    None => self.push(&c.to_string()),
+                }
+            }
+            MathElement::Text(t) if t == "\u{a0}" => self.push("\\ "),
+            MathElement::Text(t) | MathElement::Str(t) => self.text(t),
+            MathElement::Err(t) | MathElement::Unknown { tag: t, .. } => {
+                self.push(&format!("color(red)({})", text(t)))
+            }
+            MathElement::Id { t, normal } => {
+                let mut chars = t.chars();
+                let name = match (chars.next(), chars.next()) {
+                    (Some(c), None) if *normal => lookup(|s| s == Sym::IdNormal(c))
+                        .or_else(|| lookup(|s| matches!(s, Sym::Id(n) if n == t))),
+                    _ => lookup(|s| matches!(s, Sym::Id(n) | Sym::LimitFunc(n) if n == t)),
+                };
+                match name {
+                    Some(name) => self.push(name),
+                    None if t.chars().count() == 1 => self.push(t),
+                    None => self.text(t),
+                }
+            }
+            MathElement::Num(t) => self.push(t),
+            MathElement::Space(s) => match s.width {
+                Some(Length::Em(w)) if w >= 1.5 => self.push("qquad"),
+                Some(Length::Em(w)) if w >= 0.75 => self.push("quad"),
+                _ => self.push("\\ "),
+            },
+            MathElement::Row(elems) => match fences(elems) {
+                Some((open, close)) => {
+                    let inner = &elems[1..elems.len() - 1];
+                    match inner {
+                        [t] if matches!(t.elem(), MathElement::Table { .. }) => {
+                            self.table(t, open, close)
+                        }
+                        _ => {
+                            self.push(open);
+                            inner.iter().for_each(|e| self.element(e));
+                            self.push(close);
+                        }
+                    }
+                }
+                None => {
+                    self.push("{:");
+                    elems.iter().for_each(|e| self.element(e));
+                    self.push(":}");
+                }
+            },
+            MathElement::Phantom(elems) | MathElement::Padding(Padding { elems, .. }) => {
+                self.push("{:");
+                elems.iter().for_each(|e| self.element(e));
+                self.push(":}");
+            }
+            MathElement::Frac { num, den, .. } => {
+                self.arg(num);
+                self.push("/");
+                self.arg(den);
+            }
+            MathElement::Sqrt(base) => {
+                self.push("sqrt");
+                self.arg(base);
+            }
+            MathElement::Root { base, index } => {
+                self.push("root");
+                self.arg(index);
+                self.arg(base);
+            }
+            MathElement::Sup { base, sup } => {
+                self.base(base);
+                self.push("^");
+                self.arg(sup);
+            }
+            MathElement::Sub { base, sub } => {
+                self.base(base);
+                self.push("_");
+                self.arg(sub);
+            }
+            MathElement::SubSup { base, sub, sup } => {
+                self.base(base);
+                self.push("_");
+                self.arg(sub);
+                self.push("^");
+                self.arg(sup);
+            }
+            MathElement::Over { base, over, .. } => {
+                if let Some(cmd) = accent(e) {
+                    self.push(cmd);
+                    self.arg(base);
+                } else if takes_limits(base) {
+                    self.base(base);
+                    self.push("^");
+                    self.arg(over);
+                } else {
+                    self.push("overset");
+                    self.arg(over);
+                    self.arg(base);
+                }
+            }
+            MathElement::Under { base, under, .. } => {
+                if let Some(cmd) = accent(e) {
+                    self.push(cmd);
+                    self.arg(base);
+                } else if takes_limits(base) {
+                    self.base(base);
+                    self.push("_");
+                    self.arg(under);
+                } else {
+                    self.push("underset");
+                    self.arg(under);
+                    self.arg(base);
+                }
+            }
+            MathElement::UnderOver {
+                base, under, over, ..
+            } => {
+                if takes_limits(base) {
+                    self.base(base);
+                    self.push("_");
+                    self.arg(under);
+                    self.push("^");
+                    self.arg(over);
+                } else {
+                    self.push("underset");
+                    self.arg(under);
+                    self.push("overset");
+                    self.arg(over);
+                    self.arg(base);
+                }
+            }
+            MathElement::MultiScript { base, post, pre } => {
+                self.push("{:");
+                for p in pre.iter() {
+                    self.push("{::}");
+                    self.scripts(p);
+                }
+                self.base(base);
+                for p in post.iter() {
+                    self.scripts(p);
+                }
+                self.push(":}");
+            }
+            MathElement::Table { .. } => self.table(e, "{:", ":}"),
+        }
+    }
+
+    /// Write one pair of multiscripts.
+    fn scripts(&mut self, p: &Pair) {
+        self.push("_");
+        self.arg(&p.sub);
+        self.push("^");
+        self.arg(&p.sup);
+    }
+
+    fn text(&mut self, t: &str) {
+        self.push(&text(t));
+    }
+
+    /// Write a table as a matrix inside the given brackets. Each row is
+    /// written with the same brackets, or parentheses if they're invisible.
+    fn table(&mut self, e: &Element, open: &str, close: &str) {
+        let MathElement::Table { rows } = e.elem() else {
+            return;
+        };
+        let (row_open, row_close) = if open == "{:" {
+            ("(", ")")
+        } else {
+            (open, close)
+        };
+        self.push(open);
+        for (i, row) in rows.iter().enumerate() {
+            if i > 0 {
+                self.push(",");
+            }
+            self.push(row_open);
+            for (j, cell) in row.cells.iter().enumerate() {
+                if j > 0 {
+                    self.push(",");
+                }
+                cell.elems.iter().for_each(|e| self.element(e));
+            }
+            self.push(row_close);
+        }
+        self.push(close);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(s: &str) -> Element {
+        Element::row(s.chars().map(|c| match c {
+            '=' | '+' => Element::op(c),
+            c if c.is_ascii_digit() => Element::num(c.to_string()),
+            c => Element::id(c.to_string()),
+        }))
+    }
+
+    #[test]
+    fn scripts_and_limits() {
+        assert_eq!(
+            parse("sum_(i=1)^n i^2"),
+            Element::row([
+                Element::under_over(Element::op('∑'), ids("i=1"), Element::id("n")),
+                Element::sup(Element::id("i"), Element::num("2")),
+            ])
+        );
+        assert_eq!(
+            parse("x_1^(2n)"),
+            Element::sub_sup(Element::id("x"), Element::num("1"), ids("2n"))
+        );
+    }
+
+    #[test]
+    fn fractions() {
+        assert_eq!(
+            parse("a/b"),
+            Element::frac(Element::id("a"), Element::id("b"))
+        );
+        assert_eq!(
+            parse("(a+b)/c^2"),
+            Element::frac(
+                ids("a+b"),
+                Element::sup(Element::id("c"), Element::num("2"))
+            )
+        );
+        assert_eq!(
+            parse("frac(1)(2) 1/2"),
+            Element::row([
+                Element::frac(Element::num("1"), Element::num("2")),
+                Element::frac(Element::num("1"), Element::num("2")),
+            ])
+        );
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(parse("sqrt x"), Element::sqrt(Element::id("x")));
+        assert_eq!(
+            parse("root(3)(x+1)"),
+            Element::root(ids("x+1"), Element::num("3"))
+        );
+        assert_eq!(
+            parse("bb x hat y"),
+            Element::row([
+                Element::id("x").variant(Variant::Bold),
+                Element::over_accent(Element::id("y"), Element::op('^')),
+            ])
+        );
+        assert_eq!(
+            parse("abs(x) \"if\" text(x > 0)"),
+            Element::row([
+                Element::row([Element::op('|'), Element::id("x"), Element::op('|')]),
+                Element::text("if"),
+                Element::text("x > 0"),
+            ])
+        );
+    }
+
+    #[test]
+    fn matrices() {
+        assert_eq!(
+            parse("[[a,b],[c,d]]"),
+            Element::row([
+                Element::op('['),
+                Element::matrix([
+                    [Element::id("a"), Element::id("b")],
+                    [Element::id("c"), Element::id("d")],
+                ]),
+                Element::op(']'),
+            ])
+        );
+        // Mismatched column counts make it a plain list.
+        assert!(matches!(
+            parse("[(a,b),(c)]").elem(),
+            MathElement::Row(elems) if elems.len() == 5
+        ));
+    }
+
+    #[test]
+    fn write() {
+        let e = Element::row([
+            Element::op('∫'),
+            Element::frac(ids("a+b"), Element::id("c")),
+            Element::op('−'),
+            Element::sqrt(Element::id("α")),
+        ]);
+        assert_eq!(e.to_asciimath(), "int(a+b)/c-sqrt alpha");
+        assert_eq!(parse("2x dx").to_asciimath(), "2 x d x");
+        assert_eq!(
+            Element::row([Element::op('<'), Element::op('=')]).to_asciimath(),
+            "< ="
+        );
+    }
+
+    #[test]
+    fn asciimath_roundtrip() {
+        for src in [
+            "sum_(i=1)^n i^2",
+            "a/b",
+            "sqrt x",
+            "[[a,b],[c,d]]",
+            "((1,0),(0,1))",
+            "{:(x,\"if\" x >= 0),(-x,\"otherwise\"):}",
+            "(a+b)/(c-d) = x^(2n)/y_1",
+            "lim_(x->0) (sin x)/x = 1",
+            "int_0^1 f(x) dx",
+            "root(3)(x+1) + sqrt(x^2+y^2)",
+            "bb x + cc F + bbb R",
+            "hat x vec v ubrace(a+b)_n",
+            "abs(x) <= 1 quad AA x in RR",
+            "overset(def)(=) underset(k)(max)",
+            "x^2^3 {:a/b:}_i",
+            "(: a, b :) text(a \"b\")",
+        ] {
+            let e = parse(src);
+            assert_eq!(
+                parse(&e.to_asciimath()),
+                e,
+                "{} -> {}",
+                src,
+                e.to_asciimath()
+            );
+        }
+    }
+}
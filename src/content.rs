@@ -0,0 +1,1913 @@
+//! Content MathML, which describes the meaning of an expression instead of
+//! its layout.
+//!
+//! [`Content`] trees follow Strict Content MathML: everything is built from
+//! identifiers, numbers, strings, and symbols from content dictionaries,
+//! combined by application and binding. Parsing also accepts the common
+//! non-strict forms, like `<plus/>` and `<int>` with `<lowlimit>`, and
+//! rewrites them into their strict equivalents. Trees can be rendered as
+//! presentation [`Element`]s with [`Content::to_element`].
+
+use fog_pack::types::Value;
+use serde::{Deserialize, Serialize};
+
+use crate::math::*;
+use crate::mathml::{WriteOptions, NAMESPACE};
+use crate::tree::{fenced, into_elem, into_vec, list, op_char};
+use crate::xml::{self, XmlError, XmlNode};
+
+/// A Content MathML expression.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Content {
+    /// An identifier, like a variable name.
+    Ci(String),
+    /// A number.
+    Cn {
+        t: String,
+        /// The number type, like `integer` or `double`, if one was given.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        kind: Option<String>,
+    },
+    /// A string literal.
+    Cs(String),
+    /// A symbol, defined by a content dictionary.
+    Csymbol {
+        /// The content dictionary, like `arith1`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cd: Option<String>,
+        name: String,
+    },
+    /// A function applied to its arguments.
+    Apply {
+        head: Box<Content>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        args: Vec<Content>,
+    },
+    /// A binding, like a quantifier or lambda, over some variables.
+    Bind {
+        head: Box<Content>,
+        bvars: Vec<String>,
+        body: Box<Content>,
+    },
+    /// An error message, for input that couldn't be converted.
+    Err(String),
+}
+
+impl Content {
+    /// Create an identifier.
+    pub fn ci(t: impl Into<String>) -> Self {
+        Self::Ci(t.into())
+    }
+
+    /// Create a number with no type.
+    pub fn cn(t: impl Into<String>) -> Self {
+        Self::Cn {
+            t: t.into(),
+            kind: None,
+        }
+    }
+
+    /// Create a string literal.
+    pub fn cs(t: impl Into<String>) -> Self {
+        Self::Cs(t.into())
+    }
+
+    /// Create a symbol from a content dictionary.
+    pub fn csymbol(cd: impl Into<String>, name: impl Into<String>) -> Self {
+        Self::Csymbol {
+            cd: Some(cd.into()),
+            name: name.into(),
+        }
+    }
+
+    /// Apply a function to some arguments.
+    pub fn apply(head: Content, args: impl IntoIterator<Item = Content>) -> Self {
+        Self::Apply {
+            head: Box::new(head),
+            args: args.into_iter().collect(),
+        }
+    }
+
+    /// Bind variables within a body.
+    pub fn bind(
+        head: Content,
+        bvars: impl IntoIterator<Item = impl Into<String>>,
+        body: Content,
+    ) -> Self {
+        Self::Bind {
+            head: Box::new(head),
+            bvars: bvars.into_iter().map(Into::into).collect(),
+            body: Box::new(body),
+        }
+    }
+
+    /// Create an error message.
+    pub fn err(t: impl Into<String>) -> Self {
+        Self::Err(t.into())
+    }
+
+    /// Get the content dictionary and name, if this is a symbol.
+    fn symbol(&self) -> Option<(&str, &str)> {
+        match self {
+            Content::Csymbol { cd, name } => Some((cd.as_deref().unwrap_or(""), name)),
+            _ => None,
+        }
+    }
+}
+
+/// Non-strict operator elements, with the content dictionary symbols they
+/// stand for.
+const NON_STRICT: &[(&str, &str, &str)] = &[
+    ("plus", "arith1", "plus"),
+    ("minus", "arith1", "minus"),
+    ("times", "arith1", "times"),
+    ("divide", "arith1", "divide"),
+    ("power", "arith1", "power"),
+    ("abs", "arith1", "abs"),
+    ("root", "arith1", "root"),
+    ("sum", "arith1", "sum"),
+    ("product", "arith1", "product"),
+    ("gcd", "arith1", "gcd"),
+    ("lcm", "arith1", "lcm"),
+    ("factorial", "integer1", "factorial"),
+    ("quotient", "integer1", "quotient"),
+    ("rem", "integer1", "remainder"),
+    ("max", "minmax1", "max"),
+    ("min", "minmax1", "min"),
+    ("floor", "rounding1", "floor"),
+    ("ceiling", "rounding1", "ceiling"),
+    ("conjugate", "complex1", "conjugate"),
+    ("eq", "relation1", "eq"),
+    ("neq", "relation1", "neq"),
+    ("lt", "relation1", "lt"),
+    ("gt", "relation1", "gt"),
+    ("leq", "relation1", "leq"),
+    ("geq", "relation1", "geq"),
+    ("approx", "relation1", "approx"),
+    ("and", "logic1", "and"),
+    ("or", "logic1", "or"),
+    ("xor", "logic1", "xor"),
+    ("not", "logic1", "not"),
+    ("implies", "logic1", "implies"),
+    ("equivalent", "logic1", "equivalent"),
+    ("true", "logic1", "true"),
+    ("false", "logic1", "false"),
+    ("forall", "quant1", "forall"),
+    ("exists", "quant1", "exists"),
+    ("lambda", "fns1", "lambda"),
+    ("compose", "fns1", "left_compose"),
+    ("in", "set1", "in"),
+    ("notin", "set1", "notin"),
+    ("subset", "set1", "subset"),
+    ("prsubset", "set1", "prsubset"),
+    ("union", "set1", "union"),
+    ("intersect", "set1", "intersect"),
+    ("setdiff", "set1", "setdiff"),
+    ("emptyset", "set1", "emptyset"),
+    ("set", "set1", "set"),
+    ("list", "list1", "list"),
+    ("interval", "interval1", "interval_cc"),
+    ("vector", "linalg2", "vector"),
+    ("matrix", "linalg2", "matrix"),
+    ("matrixrow", "linalg2", "matrixrow"),
+    ("int", "calculus1", "int"),
+    ("diff", "calculus1", "diff"),
+    ("partialdiff", "calculus1", "partialdiff"),
+    ("limit", "limit1", "limit"),
+    ("sin", "transc1", "sin"),
+    ("cos", "transc1", "cos"),
+    ("tan", "transc1", "tan"),
+    ("sec", "transc1", "sec"),
+    ("csc", "transc1", "csc"),
+    ("cot", "transc1", "cot"),
+    ("sinh", "transc1", "sinh"),
+    ("cosh", "transc1", "cosh"),
+    ("tanh", "transc1", "tanh"),
+    ("arcsin", "transc1", "arcsin"),
+    ("arccos", "transc1", "arccos"),
+    ("arctan", "transc1", "arctan"),
+    ("exp", "transc1", "exp"),
+    ("ln", "transc1", "ln"),
+    ("log", "transc1", "log"),
+    ("pi", "nums1", "pi"),
+    ("exponentiale", "nums1", "e"),
+    ("imaginaryi", "nums1", "i"),
+    ("infinity", "nums1", "infinity"),
+    ("integers", "setname1", "Z"),
+    ("reals", "setname1", "R"),
+    ("rationals", "setname1", "Q"),
+    ("naturalnumbers", "setname1", "N"),
+    ("complexes", "setname1", "C"),
+];
+
+/// Closures of non-strict intervals.
+const INTERVALS: &[(&str, &str)] = &[
+    ("closed", "interval_cc"),
+    ("open", "interval_oo"),
+    ("open-closed", "interval_oc"),
+    ("closed-open", "interval_co"),
+];
+
+/// Operator precedence, from loosest to tightest.
+const LOWEST: u8 = 0;
+const IMPLIES: u8 = 1;
+const OR: u8 = 2;
+const AND: u8 = 3;
+const RELATION: u8 = 4;
+const SET: u8 = 5;
+const SUM: u8 = 6;
+const PRODUCT: u8 = 7;
+const PREFIX: u8 = 8;
+const POWER: u8 = 9;
+const ATOM: u8 = 10;
+
+/// How an applied symbol is presented.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Notation {
+    /// Infix operator with its precedence, and whether it's associative.
+    Infix(char, u8, bool),
+    /// Prefix operator with its precedence.
+    Prefix(char, u8),
+    Postfix(char),
+    /// Function written by name, like `sin`.
+    Func(&'static str),
+    /// Constant, written as an identifier.
+    Const(&'static str),
+    /// Arguments surrounded by brackets and separated by commas.
+    Fence(char, char),
+    Times,
+    /// `minus`, which is also written as a prefix with one argument.
+    Minus,
+    Frac,
+    /// A fraction of two integers, which keeps `nums1` in a `cd` data
+    /// attribute to tell it apart from a division.
+    Rational,
+    Power,
+    Root,
+    Conjugate,
+    Matrix,
+    /// Large operator, like a sum, over a range or set.
+    Big(char),
+    Int,
+    DefInt,
+    Limit,
+    Diff,
+    Quantifier(char),
+    Lambda,
+}
+
+/// Notations for content dictionary symbols.
+const NOTATIONS: &[(&str, &str, Notation)] = &[
+    ("arith1", "plus", Notation::Infix('+', SUM, true)),
+    ("arith1", "minus", Notation::Minus),
+    ("arith1", "unary_minus", Notation::Prefix('−', PREFIX)),
+    ("arith1", "times", Notation::Times),
+    ("arith1", "divide", Notation::Frac),
+    ("nums1", "rational", Notation::Rational),
+    ("arith1", "power", Notation::Power),
+    ("arith1", "root", Notation::Root),
+    ("arith1", "abs", Notation::Fence('|', '|')),
+    ("arith1", "sum", Notation::Big('∑')),
+    ("arith1", "product", Notation::Big('∏')),
+    ("arith1", "gcd", Notation::Func("gcd")),
+    ("arith1", "lcm", Notation::Func("lcm")),
+    ("integer1", "factorial", Notation::Postfix('!')),
+    ("integer1", "quotient", Notation::Func("quotient")),
+    ("integer1", "remainder", Notation::Func("rem")),
+    ("minmax1", "max", Notation::Func("max")),
+    ("minmax1", "min", Notation::Func("min")),
+    ("rounding1", "floor", Notation::Fence('⌊', '⌋')),
+    ("rounding1", "ceiling", Notation::Fence('⌈', '⌉')),
+    ("complex1", "conjugate", Notation::Conjugate),
+    ("relation1", "eq", Notation::Infix('=', RELATION, false)),
+    ("relation1", "neq", Notation::Infix('≠', RELATION, false)),
+    ("relation1", "lt", Notation::Infix('<', RELATION, false)),
+    ("relation1", "gt", Notation::Infix('>', RELATION, false)),
+    ("relation1", "leq", Notation::Infix('≤', RELATION, false)),
+    ("relation1", "geq", Notation::Infix('≥', RELATION, false)),
+    ("relation1", "approx", Notation::Infix('≈', RELATION, false)),
+    ("logic1", "and", Notation::Infix('∧', AND, true)),
+    ("logic1", "or", Notation::Infix('∨', OR, true)),
+    ("logic1", "xor", Notation::Infix('⊻', OR, true)),
+    ("logic1", "not", Notation::Prefix('¬', PREFIX)),
+    ("logic1", "implies", Notation::Infix('⇒', IMPLIES, false)),
+    ("logic1", "equivalent", Notation::Infix('⇔', IMPLIES, false)),
+    ("logic1", "true", Notation::Const("true")),
+    ("logic1", "false", Notation::Const("false")),
+    ("quant1", "forall", Notation::Quantifier('∀')),
+    ("quant1", "exists", Notation::Quantifier('∃')),
+    ("fns1", "lambda", Notation::Lambda),
+    ("fns1", "left_compose", Notation::Infix('∘', PRODUCT, true)),
+    ("set1", "in", Notation::Infix('∈', RELATION, false)),
+    ("set1", "notin", Notation::Infix('∉', RELATION, false)),
+    ("set1", "subset", Notation::Infix('⊆', RELATION, false)),
+    ("set1", "prsubset", Notation::Infix('⊂', RELATION, false)),
+    ("set1", "union", Notation::Infix('∪', SET, true)),
+    ("set1", "intersect", Notation::Infix('∩', SET, true)),
+    ("set1", "setdiff", Notation::Infix('∖', SET, false)),
+    ("set1", "emptyset", Notation::Const("∅")),
+    ("set1", "set", Notation::Fence('{', '}')),
+    ("list1", "list", Notation::Fence('[', ']')),
+    ("interval1", "interval_cc", Notation::Fence('[', ']')),
+    ("interval1", "interval_oo", Notation::Fence('(', ')')),
+    ("interval1", "interval_oc", Notation::Fence('(', ']')),
+    ("interval1", "interval_co", Notation::Fence('[', ')')),
+    ("interval1", "interval", Notation::Fence('[', ']')),
+    ("interval1", "oriented_interval", Notation::Fence('[', ']')),
+    ("interval1", "integer_interval", Notation::Fence('[', ']')),
+    ("linalg2", "vector", Notation::Fence('(', ')')),
+    ("linalg2", "matrix", Notation::Matrix),
+    ("calculus1", "int", Notation::Int),
+    ("calculus1", "defint", Notation::DefInt),
+    ("calculus1", "diff", Notation::Diff),
+    ("limit1", "limit", Notation::Limit),
+    ("transc1", "sin", Notation::Func("sin")),
+    ("transc1", "cos", Notation::Func("cos")),
+    ("transc1", "tan", Notation::Func("tan")),
+    ("transc1", "sec", Notation::Func("sec")),
+    ("transc1", "csc", Notation::Func("csc")),
+    ("transc1", "cot", Notation::Func("cot")),
+    ("transc1", "sinh", Notation::Func("sinh")),
+    ("transc1", "cosh", Notation::Func("cosh")),
+    ("transc1", "tanh", Notation::Func("tanh")),
+    ("transc1", "arcsin", Notation::Func("arcsin")),
+    ("transc1", "arccos", Notation::Func("arccos")),
+    ("transc1", "arctan", Notation::Func("arctan")),
+    ("transc1", "exp", Notation::Func("exp")),
+    ("transc1", "ln", Notation::Func("ln")),
+    ("transc1", "log", Notation::Func("log")),
+    ("nums1", "pi", Notation::Const("π")),
+    ("nums1", "e", Notation::Const("e")),
+    ("nums1", "i", Notation::Const("i")),
+    ("nums1", "infinity", Notation::Const("∞")),
+    ("setname1", "Z", Notation::Const("ℤ")),
+    ("setname1", "R", Notation::Const("ℝ")),
+    ("setname1", "Q", Notation::Const("ℚ")),
+    ("setname1", "N", Notation::Const("ℕ")),
+    ("setname1", "C", Notation::Const("ℂ")),
+];
+
+fn notation(c: &Content) -> Option<Notation> {
+    let (cd, name) = c.symbol()?;
+    NOTATIONS
+        .iter()
+        .find(|(d, n, _)| *n == name && (cd.is_empty() || *d == cd))
+        .map(|(.., n)| *n)
+}
+
+/// Parse a Content MathML document. The root may be a `<math>` element or a
+/// single content element.
+///
+/// Only XML syntax errors are returned as errors. Unsupported or malformed
+/// content becomes [`Content::Err`] nodes within the returned tree.
+pub fn parse(src: &str) -> Result<Content, XmlError> {
+    let root = xml::parse(src)?;
+    Ok(convert(&root))
+}
+
+/// Convert a single Content MathML element.
+fn convert(node: &XmlNode) -> Content {
+    let name = node.local();
+    match name {
+        "math" => match node.elems().next() {
+            Some(e) => convert(e),
+            None => Content::err("<math> has no content"),
+        },
+        "semantics" => {
+            // Prefer a content annotation over the presentation markup.
+            let annotation = node.elems().find(|e| {
+                e.local() == "annotation-xml"
+                    && matches!(
+                        e.attr("encoding"),
+                        Some("MathML-Content" | "application/mathml-content+xml")
+                    )
+            });
+            match annotation.and_then(|a| a.elems().next()) {
+                Some(e) => convert(e),
+                None => match node.elems().next() {
+                    Some(e) => convert(e),
+                    None => Content::err("<semantics> has no content"),
+                },
+            }
+        }
+        "ci" => Content::ci(node.text().trim()),
+        "cn" => number(node),
+        "cs" => Content::cs(node.text()),
+        "csymbol" => Content::Csymbol {
+            cd: node.attr("cd").map(String::from),
+            name: node.text().trim().to_string(),
+        },
+        "apply" => apply(node),
+        "bind" => {
+            let mut elems = node.elems();
+            let Some(head) = elems.next() else {
+                return Content::err("<bind> has no head");
+            };
+            let mut bvars = Vec::new();
+            let mut body = None;
+            for e in elems {
+                match e.local() {
+                    "bvar" => bvars.push(bvar(e)),
+                    _ => body = Some(convert(e)),
+                }
+            }
+            match body {
+                Some(body) => Content::bind(convert(head), bvars, body),
+                None => Content::err("<bind> has no body"),
+            }
+        }
+        "cerror" => match node.elems().find(|e| e.local() == "cs") {
+            Some(cs) => Content::err(cs.text()),
+            None => Content::err(node.text().trim()),
+        },
+        _ => match NON_STRICT.iter().find(|(n, ..)| *n == name) {
+            Some(&(_, cd, name)) => {
+                let name = match node.attr("closure") {
+                    Some(c) => INTERVALS
+                        .iter()
+                        .find(|(n, _)| *n == c)
+                        .map_or(name, |(_, s)| *s),
+                    None => name,
+                };
+                let sym = Content::csymbol(cd, name);
+                // Containers like <set> and <interval> hold their arguments.
+                if node.elems().next().is_some() {
+                    Content::apply(sym, node.elems().map(convert))
+                } else {
+                    sym
+                }
+            }
+            None => Content::err(format!("unsupported Content MathML element <{}>", name)),
+        },
+    }
+}
+
+/// Convert a number, including the non-strict forms split by `<sep/>`.
+fn number(node: &XmlNode) -> Content {
+    let kind = node.attr("type");
+    let parts: Vec<String> = node
+        .children
+        .split(|c| matches!(c, xml::XmlChild::Elem(e) if e.local() == "sep"))
+        .map(|part| {
+            part.iter()
+                .filter_map(|c| match c {
+                    xml::XmlChild::Text(t) => Some(t.as_str()),
+                    _ => None,
+                })
+                .collect::<String>()
+                .trim()
+                .to_string()
+        })
+        .collect();
+    match (kind, parts.as_slice()) {
+        (Some("rational"), [n, d]) => Content::apply(
+            Content::csymbol("nums1", "rational"),
+            [Content::cn(n), Content::cn(d)],
+        ),
+        (Some("complex-cartesian"), [re, im]) => Content::apply(
+            Content::csymbol("complex1", "complex_cartesian"),
+            [Content::cn(re), Content::cn(im)],
+        ),
+        (Some("e-notation"), [m, e]) => Content::Cn {
+            t: format!("{}e{}", m, e),
+            kind: Some("double".into()),
+        },
+        (kind, [t]) => Content::Cn {
+            t: t.clone(),
+            kind: kind.map(String::from),
+        },
+        _ => Content::err("<cn> has an unsupported number of parts"),
+    }
+}
+
+/// Get the variable name in a `<bvar>`.
+fn bvar(node: &XmlNode) -> String {
+    node.elems()
+        .find(|e| e.local() == "ci")
+        .map_or_else(|| node.text(), |ci| ci.text())
+        .trim()
+        .to_string()
+}
+
+/// Convert an application, rewriting the non-strict qualifiers.
+fn apply(node: &XmlNode) -> Content {
+    let mut elems = node.elems();
+    let Some(head) = elems.next() else {
+        return Content::err("<apply> has no head");
+    };
+    let head = convert(head);
+    let mut bvars = Vec::new();
+    let mut lower = None;
+    let mut upper = None;
+    let mut degree = None;
+    let mut domain = None;
+    let mut args = Vec::new();
+    for e in elems {
+        let first = || {
+            e.elems()
+                .next()
+                .map_or_else(|| Content::cn(e.text().trim()), convert)
+        };
+        match e.local() {
+            "bvar" => bvars.push(bvar(e)),
+            "lowlimit" => lower = Some(first()),
+            "uplimit" => upper = Some(first()),
+            "degree" | "logbase" => degree = Some(first()),
+            "domainofapplication" => domain = Some(first()),
+            "condition" => {
+                // A limit's condition says what the variable tends to.
+                if let Some(target) = e
+                    .find("tendsto")
+                    .and(e.find("apply"))
+                    .and_then(|a| a.elems().nth(2))
+                {
+                    lower = Some(convert(target));
+                }
+            }
+            "interval" if !bvars.is_empty() && domain.is_none() => domain = Some(convert(e)),
+            _ => args.push(convert(e)),
+        }
+    }
+    let name = head.symbol().map_or("", |(_, n)| n);
+    if bvars.is_empty() {
+        return match (name, args.len()) {
+            ("minus", 1) => Content::apply(Content::csymbol("arith1", "unary_minus"), args),
+            ("root", _) => {
+                args.push(degree.unwrap_or_else(|| Content::cn("2")));
+                Content::apply(head, args)
+            }
+            ("log", _) => {
+                let base = degree.unwrap_or_else(|| Content::cn("10"));
+                Content::apply(head, std::iter::once(base).chain(args))
+            }
+            _ => Content::apply(head, args),
+        };
+    }
+    let body = match args.len() {
+        1 => args.pop().unwrap(),
+        _ => Content::apply(Content::csymbol("list1", "list"), args),
+    };
+    let lambda = || {
+        Content::bind(
+            Content::csymbol("fns1", "lambda"),
+            bvars.clone(),
+            body.clone(),
+        )
+    };
+    // Sums range over integers, as in the arith1 content dictionary.
+    let range = |name: &str| match (lower.clone(), upper.clone()) {
+        (Some(lower), Some(upper)) => Some(Content::apply(
+            Content::csymbol("interval1", name),
+            [lower, upper],
+        )),
+        _ => domain.clone(),
+    };
+    match name {
+        "int" => match range("oriented_interval") {
+            Some(range) => {
+                Content::apply(Content::csymbol("calculus1", "defint"), [range, lambda()])
+            }
+            None => Content::apply(head, [lambda()]),
+        },
+        "sum" | "product" => match range("integer_interval") {
+            Some(range) => Content::apply(head, [range, lambda()]),
+            None => Content::apply(head, [lambda()]),
+        },
+        "limit" => match lower {
+            Some(target) => Content::apply(
+                head,
+                [target, Content::csymbol("limit1", "both_sides"), lambda()],
+            ),
+            None => Content::apply(head, [lambda()]),
+        },
+        "diff" => Content::apply(head, [lambda()]),
+        _ => Content::bind(head, bvars, body),
+    }
+}
+
+impl Content {
+    /// Write the expression out as a Content MathML `<math>` element, using
+    /// the default [`WriteOptions`]. The output is always Strict Content
+    /// MathML.
+    pub fn to_content_mathml(&self) -> String {
+        self.to_content_mathml_with(&WriteOptions::default())
+    }
+
+    /// Write the expression out as a Content MathML `<math>` element. The
+    /// `data_attributes` option is ignored.
+    pub fn to_content_mathml_with(&self, opts: &WriteOptions) -> String {
+        let w = Writer { opts };
+        let mut root = w.node("math");
+        if opts.xmlns {
+            let attr = match &opts.prefix {
+                Some(p) => format!("xmlns:{}", p),
+                None => "xmlns".into(),
+            };
+            root = root.attr_add(attr, NAMESPACE);
+        }
+        let root = root.child(w.content(self));
+        let mut out = String::new();
+        root.write(&mut out, opts.indent.as_deref(), 0);
+        out
+    }
+}
+
+struct Writer<'a> {
+    opts: &'a WriteOptions,
+}
+
+impl Writer<'_> {
+    fn node(&self, name: &str) -> XmlNode {
+        match &self.opts.prefix {
+            Some(p) => XmlNode::new(format!("{}:{}", p, name)),
+            None => XmlNode::new(name),
+        }
+    }
+
+    fn content(&self, c: &Content) -> XmlNode {
+        match c {
+            Content::Ci(t) => self.node("ci").text_add(t),
+            Content::Cn { t, kind } => {
+                let n = self.node("cn");
+                match kind {
+                    Some(k) => n.attr_add("type", k),
+                    None => n,
+                }
+                .text_add(t)
+            }
+            Content::Cs(t) => self.node("cs").text_add(t),
+            Content::Csymbol { cd, name } => {
+                let n = self.node("csymbol");
+                match cd {
+                    Some(cd) => n.attr_add("cd", cd),
+                    None => n,
+                }
+                .text_add(name)
+            }
+            Content::Apply { head, args } => args
+                .iter()
+                .fold(self.node("apply").child(self.content(head)), |n, a| {
+                    n.child(self.content(a))
+                }),
+            Content::Bind { head, bvars, body } => {
+                let n = bvars
+                    .iter()
+                    .fold(self.node("bind").child(self.content(head)), |n, v| {
+                        n.child(self.node("bvar").child(self.node("ci").text_add(v)))
+                    });
+                n.child(self.content(body))
+            }
+            Content::Err(t) => self
+                .node("cerror")
+                .child(
+                    self.node("csymbol")
+                        .attr_add("cd", "moreerrors")
+                        .text_add("unexpected"),
+                )
+                .child(self.node("cs").text_add(t)),
+        }
+    }
+}
+
+impl Content {
+    /// Render the expression as presentation markup, using conventional
+    /// notation for the symbols in the common content dictionaries. Unknown
+    /// symbols are written as functions applied to their arguments, and keep
+    /// their content dictionary in a `cd` data attribute, as do rationals.
+    pub fn to_element(&self) -> Element {
+        self.render().0
+    }
+
+    /// Render the expression, wrapping it in parentheses if it binds looser
+    /// than `min`.
+    fn render_min(&self, min: u8) -> Element {
+        let (e, prec) = self.render();
+        if prec < min {
+            fenced(Some('('), into_vec(e), Some(')'))
+        } else {
+            e
+        }
+    }
+
+    /// Render the expression, along with its precedence.
+    fn render(&self) -> (Element, u8) {
+        match self {
+            Content::Ci(t) => (Element::id(t.as_str()), ATOM),
+            Content::Cn { t, .. } => match t.strip_prefix('-') {
+                Some(t) => (Element::row([Element::op('−'), Element::num(t)]), PREFIX),
+                None => (Element::num(t.as_str()), ATOM),
+            },
+            Content::Cs(t) => (Element::str(t.as_str()), ATOM),
+            Content::Err(t) => (Element::err(t.as_str()), ATOM),
+            Content::Csymbol { cd, name } => (
+                symbol(self).unwrap_or_else(|| {
+                    let cd = cd.clone().unwrap_or_default();
+                    Element::id(name.as_str()).data("cd", Value::Str(cd))
+                }),
+                ATOM,
+            ),
+            Content::Bind { head, bvars, body } => {
+                let vars = list(bvars.iter().map(|v| Element::id(v.as_str())));
+                let mut elems = Vec::new();
+                match notation(head) {
+                    Some(Notation::Quantifier(c)) => {
+                        elems.push(Element::op(c));
+                        elems.extend(vars);
+                        elems.push(Element::op(':'));
+                    }
+                    Some(Notation::Lambda) => {
+                        elems.extend(vars);
+                        elems.push(Element::op('↦'));
+                    }
+                    _ => {
+                        elems.push(head.render_min(ATOM));
+                        elems.extend(vars);
+                        elems.push(Element::op('.'));
+                    }
+                }
+                elems.extend(into_vec(body.render_min(LOWEST)));
+                (Element::row(elems), LOWEST)
+            }
+            Content::Apply { head, args } => notation(head)
+                .and_then(|n| apply_notation(n, args))
+                .unwrap_or_else(|| {
+                    let mut elems = vec![head.render_min(ATOM), Element::op('\u{2061}')];
+                    elems.push(fenced(
+                        Some('('),
+                        list(args.iter().map(|a| a.render_min(LOWEST))),
+                        Some(')'),
+                    ));
+                    (Element::row(elems), ATOM)
+                }),
+        }
+    }
+}
+
+/// Render a symbol on its own.
+fn symbol(c: &Content) -> Option<Element> {
+    let e = match notation(c)? {
+        Notation::Infix(c, ..) | Notation::Prefix(c, _) | Notation::Postfix(c) => Element::op(c),
+        Notation::Big(c) | Notation::Quantifier(c) => Element::op(c),
+        Notation::Minus => Element::op('−'),
+        Notation::Times => Element::op('×'),
+        Notation::Func(name) => Element::id(name),
+        Notation::Const(t) if t.chars().count() == 1 && t != "π" => Element::id_normal(t),
+        Notation::Const(t) => Element::id(t),
+        Notation::Int | Notation::DefInt => Element::op('∫'),
+        Notation::Lambda => Element::op('λ'),
+        _ => return None,
+    };
+    Some(e)
+}
+
+/// Render an application using its head's notation, if the arguments fit it.
+fn apply_notation(n: Notation, args: &[Content]) -> Option<(Element, u8)> {
+    let (e, prec) = match (n, args) {
+        (Notation::Infix(c, prec, assoc), [_, _, ..]) => (infix(args, c, prec, assoc), prec),
+        (Notation::Minus, [_, _]) => (infix(args, '−', SUM, false), SUM),
+        (Notation::Minus, [a]) | (Notation::Prefix(_, _), [a]) => {
+            let (c, prec) = match n {
+                Notation::Prefix(c, prec) => (c, prec),
+                _ => ('−', PREFIX),
+            };
+            let min = if is_signed(a) { ATOM } else { prec };
+            (Element::row([Element::op(c), a.render_min(min)]), prec)
+        }
+        (Notation::Times, [_, _, ..]) => {
+            // Numbers after the first factor need a visible sign.
+            let c = if args[1..].iter().any(|a| matches!(a, Content::Cn { .. })) {
+                '×'
+            } else {
+                '\u{2062}'
+            };
+            (infix(args, c, PRODUCT, true), PRODUCT)
+        }
+        (Notation::Postfix(c), [a]) => (Element::row([a.render_min(ATOM), Element::op(c)]), POWER),
+        (Notation::Frac, [n, d]) => (Element::frac(n.to_element(), d.to_element()), PREFIX),
+        (Notation::Rational, [n, d]) => (
+            Element::frac(n.to_element(), d.to_element()).data("cd", Value::Str("nums1".into())),
+            PREFIX,
+        ),
+        (Notation::Power, [b, e]) => (Element::sup(b.render_min(ATOM), e.to_element()), POWER),
+        (Notation::Root, [x]) => (Element::sqrt(x.to_element()), ATOM),
+        (Notation::Root, [x, n]) => match n {
+            Content::Cn { t, .. } if t == "2" => (Element::sqrt(x.to_element()), ATOM),
+            _ => (Element::root(x.to_element(), n.to_element()), ATOM),
+        },
+        (Notation::Fence(open, close), _) => (
+            fenced(
+                Some(open),
+                list(args.iter().map(Content::to_element)),
+                Some(close),
+            ),
+            ATOM,
+        ),
+        (Notation::Func(name), [a]) if a.render().1 == ATOM && !is_apply(a) => (
+            Element::row([Element::id(name), Element::op('\u{2061}'), a.to_element()]),
+            PREFIX,
+        ),
+        (Notation::Func(name), _) => (
+            Element::row([
+                Element::id(name),
+                Element::op('\u{2061}'),
+                fenced(
+                    Some('('),
+                    list(args.iter().map(Content::to_element)),
+                    Some(')'),
+                ),
+            ]),
+            ATOM,
+        ),
+        (Notation::Conjugate, [a]) => (Element::over(a.to_element(), Element::op('¯')), ATOM),
+        (Notation::Matrix, _) => {
+            let rows = args.iter().map(|r| match r {
+                Content::Apply { args, .. } => args
+                    .iter()
+                    .map(|c| TableCell::new([c.to_element()]))
+                    .collect(),
+                r => vec![TableCell::new([r.to_element()])],
+            });
+            let table = Element::table(rows.map(TableRow::new));
+            (fenced(Some('('), vec![table], Some(')')), ATOM)
+        }
+        (Notation::Big(c), [range, f]) => {
+            let (vars, body) = lambda(f)?;
+            let under = match interval(range) {
+                Some((lo, _)) => Element::row([vars.clone(), Element::op('='), lo.to_element()]),
+                None => Element::row([vars, Element::op('∈'), range.to_element()]),
+            };
+            let op = match interval(range) {
+                Some((_, hi)) => Element::under_over(Element::op(c), under, hi.to_element()),
+                None => Element::under(Element::op(c), under),
+            };
+            (Element::row([op, body.render_min(PRODUCT)]), SUM)
+        }
+        (Notation::Big(c), [f]) => {
+            let (vars, body) = lambda(f)?;
+            let op = Element::under(Element::op(c), vars);
+            (Element::row([op, body.render_min(PRODUCT)]), SUM)
+        }
+        (Notation::Int, [f]) => {
+            let (vars, body) = lambda(f)?;
+            (integral(Element::op('∫'), vars, body), SUM)
+        }
+        (Notation::DefInt, [range, f]) => {
+            let (vars, body) = lambda(f)?;
+            let op = match interval(range) {
+                Some((lo, hi)) => {
+                    Element::sub_sup(Element::op('∫'), lo.to_element(), hi.to_element())
+                }
+                None => Element::sub(Element::op('∫'), range.to_element()),
+            };
+            (integral(op, vars, body), SUM)
+        }
+        (Notation::Limit, [target, dir, f]) => {
+            let (vars, body) = lambda(f)?;
+            let target = match dir.symbol() {
+                Some((_, "above")) => Element::sup(target.render_min(ATOM), Element::op('+')),
+                Some((_, "below")) => Element::sup(target.render_min(ATOM), Element::op('−')),
+                _ => target.to_element(),
+            };
+            let under = Element::row([vars, Element::op('→'), target]);
+            let op = Element::under(Element::id("lim"), under);
+            (Element::row([op, body.render_min(PRODUCT)]), SUM)
+        }
+        (Notation::Diff, [f]) => {
+            let (vars, body) = lambda(f)?;
+            let d = Element::frac(
+                Element::id_normal("d"),
+                Element::row([Element::id_normal("d"), vars]),
+            );
+            (Element::row([d, body.render_min(PRODUCT)]), PRODUCT)
+        }
+        _ => return None,
+    };
+    Some((e, prec))
+}
+
+fn is_apply(c: &Content) -> bool {
+    matches!(c, Content::Apply { .. })
+}
+
+/// Render an infix operator between arguments. Arguments after the first need
+/// to bind tighter if the operator isn't associative.
+fn infix(args: &[Content], c: char, prec: u8, assoc: bool) -> Element {
+    let mut elems = Vec::new();
+    for (i, a) in args.iter().enumerate() {
+        if i > 0 {
+            elems.push(Element::op(c));
+        }
+        let min = match i {
+            0 => prec,
+            _ if is_signed(a) => ATOM,
+            _ if !assoc => prec + 1,
+            _ => prec,
+        };
+        operand(&mut elems, a, min);
+    }
+    Element::row(elems)
+}
+
+/// Check if an expression starts with a minus sign, which needs parentheses
+/// right after another operator: `x + (−y)`, not `x + −y`.
+fn is_signed(c: &Content) -> bool {
+    match c {
+        Content::Cn { t, .. } => t.starts_with('-'),
+        Content::Apply { head, args } => matches!(
+            (notation(head), args.as_slice()),
+            (Some(Notation::Minus | Notation::Prefix('−', _)), [_])
+        ),
+        _ => false,
+    }
+}
+
+/// Add an operand to a row, splicing it in unless it needs parentheses.
+fn operand(elems: &mut Vec<Element>, c: &Content, min: u8) {
+    match c.render() {
+        (e, prec) if prec >= min => elems.extend(into_vec(e)),
+        (e, _) => elems.push(fenced(Some('('), into_vec(e), Some(')'))),
+    }
+}
+
+/// Get the bound variables and body of a lambda.
+fn lambda(c: &Content) -> Option<(Element, &Content)> {
+    match c {
+        Content::Bind { head, bvars, body } if notation(head) == Some(Notation::Lambda) => {
+            let vars = list(bvars.iter().map(|v| Element::id(v.as_str())));
+            Some((into_elem(vars), body))
+        }
+        _ => None,
+    }
+}
+
+/// Get the ends of an interval.
+fn interval(c: &Content) -> Option<(&Content, &Content)> {
+    match c {
+        Content::Apply { head, args } => match (head.symbol(), args.as_slice()) {
+            (Some(("interval1", _)), [lo, hi]) => Some((lo, hi)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn integral(op: Element, vars: Element, body: &Content) -> Element {
+    let mut elems = vec![op];
+    operand(&mut elems, body, PRODUCT);
+    elems.push(Element::id_normal("d"));
+    elems.push(vars);
+    Element::row(elems)
+}
+
+impl Content {
+    /// Read presentation markup back into a content tree. This is the inverse
+    /// of [`Content::to_element`], and only succeeds if the meaning is
+    /// unambiguous: `[a, b]` could be a list or an interval, and `x y` has no
+    /// operator between the two, so both give `None`. Identifiers with a `cd`
+    /// data attribute are read as symbols from that content dictionary, and
+    /// fractions with `nums1` as rationals.
+    pub fn from_element(e: &Element) -> Option<Content> {
+        match e.elem() {
+            MathElement::Id { t, .. } => match unknown_cd(e) {
+                Some(cd) => Some(Content::Csymbol {
+                    cd: (!cd.is_empty()).then(|| cd.to_owned()),
+                    name: t.clone(),
+                }),
+                None => Some(constant(e).unwrap_or_else(|| Content::ci(t.as_str()))),
+            },
+            MathElement::Num(t) => Some(Content::cn(t.as_str())),
+            MathElement::Str(t) => Some(Content::cs(t.as_str())),
+            MathElement::Err(t) => Some(Content::err(t.as_str())),
+            MathElement::Row(elems) => read_seq(elems),
+            MathElement::Frac {
+                num,
+                den,
+                line_thickness: None,
+            } if unknown_cd(e) == Some("nums1") => Some(Content::apply(
+                Content::csymbol("nums1", "rational"),
+                [Content::from_element(num)?, Content::from_element(den)?],
+            )),
+            MathElement::Frac {
+                num,
+                den,
+                line_thickness: None,
+            } => Some(Content::apply(
+                Content::csymbol("arith1", "divide"),
+                [Content::from_element(num)?, Content::from_element(den)?],
+            )),
+            MathElement::Sup { base, sup } => Some(Content::apply(
+                Content::csymbol("arith1", "power"),
+                [Content::from_element(base)?, Content::from_element(sup)?],
+            )),
+            MathElement::Sqrt(base) => Some(Content::apply(
+                Content::csymbol("arith1", "root"),
+                [Content::from_element(base)?, Content::cn("2")],
+            )),
+            MathElement::Root { base, index } => Some(Content::apply(
+                Content::csymbol("arith1", "root"),
+                [Content::from_element(base)?, Content::from_element(index)?],
+            )),
+            MathElement::Over {
+                base,
+                over,
+                accent: false,
+            } if op_char(over) == Some('¯') => Some(Content::apply(
+                Content::csymbol("complex1", "conjugate"),
+                [Content::from_element(base)?],
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// Find the symbol for the first notation matching a test.
+fn notation_symbol(f: impl Fn(Notation) -> bool) -> Option<Content> {
+    NOTATIONS
+        .iter()
+        .find(|(.., n)| f(*n))
+        .map(|(cd, name, _)| Content::csymbol(*cd, *name))
+}
+
+/// Get the content dictionary kept in an element's `cd` data attribute, like
+/// on the identifier of an unknown symbol.
+fn unknown_cd(e: &Element) -> Option<&str> {
+    match e.attributes()?.data.as_ref()?.get("cd")? {
+        Value::Str(cd) => Some(cd.as_str()),
+        _ => None,
+    }
+}
+
+/// Find the constant that renders as this element.
+fn constant(e: &Element) -> Option<Content> {
+    NOTATIONS
+        .iter()
+        .filter(|(.., n)| matches!(n, Notation::Const(_)))
+        .map(|(cd, name, _)| Content::csymbol(*cd, *name))
+        .find(|c| symbol(c).as_ref() == Some(e))
+}
+
+/// Find the infix operator written with a character, along with its
+/// precedence and whether it's associative.
+fn infix_symbol(c: char) -> Option<(Content, u8, bool)> {
+    match c {
+        '−' => Some((Content::csymbol("arith1", "minus"), SUM, false)),
+        '×' | '⋅' | '\u{2062}' => Some((Content::csymbol("arith1", "times"), PRODUCT, true)),
+        c => NOTATIONS.iter().find_map(|(cd, name, n)| match n {
+            Notation::Infix(t, prec, assoc) if *t == c => {
+                Some((Content::csymbol(*cd, *name), *prec, *assoc))
+            }
+            _ => None,
+        }),
+    }
+}
+
+/// Read a sequence of elements as a single expression.
+fn read_seq(elems: &[Element]) -> Option<Content> {
+    let mut r = Reader { elems, pos: 0 };
+    let c = r.expr(LOWEST)?;
+    (r.pos == elems.len()).then_some(c)
+}
+
+/// Read a comma-separated list of expressions.
+fn read_list(elems: &[Element]) -> Option<Vec<Content>> {
+    if elems.is_empty() {
+        return Some(Vec::new());
+    }
+    elems
+        .split(|e| op_char(e) == Some(','))
+        .map(read_seq)
+        .collect()
+}
+
+/// Read the bound variables of a binder, which are identifiers separated by
+/// commas.
+fn read_vars(elems: &[Element]) -> Option<Vec<String>> {
+    let elems = match elems {
+        [e] => match e.elem() {
+            MathElement::Row(elems) => elems.as_slice(),
+            _ => elems,
+        },
+        elems => elems,
+    };
+    elems
+        .split(|e| op_char(e) == Some(','))
+        .map(|v| match v {
+            [v] => match v.elem() {
+                MathElement::Id { t, .. } => Some(t.clone()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+fn lambda_of(vars: Vec<String>, body: Content) -> Content {
+    Content::bind(Content::csymbol("fns1", "lambda"), vars, body)
+}
+
+/// Read the elements under a large operator, like `i = 1`. Returns the
+/// variables, the relation, and the elements after it.
+fn under_row(e: &Element) -> Option<(Vec<String>, char, &[Element])> {
+    let MathElement::Row(elems) = e.elem() else {
+        return None;
+    };
+    let (i, rel) = elems
+        .iter()
+        .enumerate()
+        .find_map(|(i, e)| op_char(e).filter(|c| "=∈→".contains(*c)).map(|c| (i, c)))?;
+    Some((read_vars(&elems[..i])?, rel, &elems[i + 1..]))
+}
+
+/// Check for the upright `d` of a differential.
+fn is_d(e: &Element) -> bool {
+    matches!(e.elem(), MathElement::Id { t, normal: true } if t == "d")
+}
+
+/// Read a bracketed sequence. Brackets that several symbols are written with
+/// are ambiguous, except for parentheses around a single expression, which
+/// only group.
+fn fence(open: char, inner: &[Element], close: char) -> Option<Content> {
+    if (open, close) == ('(', ')') {
+        if let [table] = inner {
+            if let MathElement::Table { rows } = table.elem() {
+                let rows = rows.iter().map(|r| {
+                    let cells = r.cells.iter().map(|c| read_seq(&c.elems));
+                    let head = Content::csymbol("linalg2", "matrixrow");
+                    Some(Content::apply(head, cells.collect::<Option<Vec<_>>>()?))
+                });
+                let rows = rows.collect::<Option<Vec<_>>>()?;
+                return Some(Content::apply(Content::csymbol("linalg2", "matrix"), rows));
+            }
+        }
+        if !inner.iter().any(|e| op_char(e) == Some(',')) {
+            return read_seq(inner);
+        }
+    }
+    let mut found = NOTATIONS
+        .iter()
+        .filter(|(.., n)| *n == Notation::Fence(open, close));
+    let (cd, name, _) = found.next()?;
+    if found.next().is_some() {
+        return None;
+    }
+    Some(Content::apply(
+        Content::csymbol(*cd, *name),
+        read_list(inner)?,
+    ))
+}
+
+/// Reads an expression from a row of elements, by operator precedence.
+struct Reader<'a> {
+    elems: &'a [Element],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn peek(&self) -> Option<&'a Element> {
+        self.elems.get(self.pos)
+    }
+
+    fn peek_op(&self) -> Option<char> {
+        self.peek().and_then(op_char)
+    }
+
+    fn next(&mut self) -> Option<&'a Element> {
+        let e = self.peek()?;
+        self.pos += 1;
+        Some(e)
+    }
+
+    /// Read an expression whose operators bind at least as tightly as `min`.
+    fn expr(&mut self, min: u8) -> Option<Content> {
+        let mut lhs = self.factor()?;
+        // Only flatten chains read here, not parenthesized operands.
+        let mut chained = false;
+        while let Some((sym, prec, assoc)) = self.peek_op().and_then(infix_symbol) {
+            if prec < min {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.expr(prec + 1)?;
+            lhs = match lhs {
+                Content::Apply { head, mut args }
+                    if chained && *head == sym && (assoc || prec == RELATION) =>
+                {
+                    args.push(rhs);
+                    Content::Apply { head, args }
+                }
+                lhs => Content::apply(sym, [lhs, rhs]),
+            };
+            chained = true;
+        }
+        Some(lhs)
+    }
+
+    /// Read a term and any factorials after it.
+    fn factor(&mut self) -> Option<Content> {
+        let mut c = self.term()?;
+        while self.peek_op() == Some('!') {
+            self.pos += 1;
+            c = Content::apply(Content::csymbol("integer1", "factorial"), [c]);
+        }
+        Some(c)
+    }
+
+    fn term(&mut self) -> Option<Content> {
+        let start = self.pos;
+        let e = self.next()?;
+        if let Some(c) = op_char(e) {
+            return self.operator(c);
+        }
+        match e.elem() {
+            MathElement::Id { t, .. } => {
+                // A lambda starts with its variables.
+                let rest = &self.elems[start..];
+                let arrow = rest.iter().position(|e| {
+                    !matches!(e.elem(), MathElement::Id { .. }) && op_char(e) != Some(',')
+                });
+                if let Some(i) = arrow.filter(|i| op_char(&rest[*i]) == Some('↦')) {
+                    let vars = read_vars(&rest[..i])?;
+                    self.pos = start + i + 1;
+                    return Some(lambda_of(vars, self.expr(LOWEST)?));
+                }
+                if self.peek_op() == Some('\u{2061}') {
+                    self.pos += 1;
+                    let head = match unknown_cd(e) {
+                        Some(_) => Content::from_element(e)?,
+                        None => notation_symbol(|n| matches!(n, Notation::Func(f) if f == t))
+                            .unwrap_or_else(|| Content::ci(t.as_str())),
+                    };
+                    return self.arguments(head);
+                }
+                Content::from_element(e)
+            }
+            MathElement::Sub { base, sub } if op_char(base) == Some('∫') => {
+                let range = Content::from_element(sub)?;
+                self.integral(Content::csymbol("calculus1", "defint"), Some(range))
+            }
+            MathElement::SubSup { base, sub, sup } if op_char(base) == Some('∫') => {
+                let range = Content::apply(
+                    Content::csymbol("interval1", "oriented_interval"),
+                    [Content::from_element(sub)?, Content::from_element(sup)?],
+                );
+                self.integral(Content::csymbol("calculus1", "defint"), Some(range))
+            }
+            MathElement::UnderOver {
+                base, under, over, ..
+            } => {
+                let head = notation_symbol(|n| Some(n) == op_char(base).map(Notation::Big))?;
+                let (vars, '=', lower) = under_row(under)? else {
+                    return None;
+                };
+                let range = Content::apply(
+                    Content::csymbol("interval1", "integer_interval"),
+                    [read_seq(lower)?, Content::from_element(over)?],
+                );
+                let body = self.expr(PRODUCT)?;
+                Some(Content::apply(head, [range, lambda_of(vars, body)]))
+            }
+            MathElement::Under { base, under, .. } if is_lim(base) => {
+                let (vars, '→', target) = under_row(under)? else {
+                    return None;
+                };
+                let (target, dir) = match target {
+                    [e] => match e.elem() {
+                        MathElement::Sup { base, sup } if op_char(sup) == Some('+') => {
+                            (Content::from_element(base)?, "above")
+                        }
+                        MathElement::Sup { base, sup } if op_char(sup) == Some('−') => {
+                            (Content::from_element(base)?, "below")
+                        }
+                        _ => (Content::from_element(e)?, "both_sides"),
+                    },
+                    target => (read_seq(target)?, "both_sides"),
+                };
+                let body = self.expr(PRODUCT)?;
+                Some(Content::apply(
+                    Content::csymbol("limit1", "limit"),
+                    [
+                        target,
+                        Content::csymbol("limit1", dir),
+                        lambda_of(vars, body),
+                    ],
+                ))
+            }
+            MathElement::Under { base, under, .. } => {
+                let head = notation_symbol(|n| Some(n) == op_char(base).map(Notation::Big))?;
+                let set = match under_row(under) {
+                    Some((vars, '∈', set)) => Some((vars, read_seq(set)?)),
+                    Some(_) => return None,
+                    None => None,
+                };
+                let body = self.expr(PRODUCT)?;
+                match set {
+                    Some((vars, set)) => Some(Content::apply(head, [set, lambda_of(vars, body)])),
+                    None => {
+                        let vars = read_vars(std::slice::from_ref(under.as_ref()))?;
+                        Some(Content::apply(head, [lambda_of(vars, body)]))
+                    }
+                }
+            }
+            MathElement::Frac {
+                num,
+                den,
+                line_thickness: None,
+            } if is_d(num) => {
+                let MathElement::Row(den) = den.elem() else {
+                    return Content::from_element(e);
+                };
+                let [d, vars] = den.as_slice() else {
+                    return Content::from_element(e);
+                };
+                if !is_d(d) {
+                    return Content::from_element(e);
+                }
+                let vars = read_vars(std::slice::from_ref(vars))?;
+                let body = self.expr(PRODUCT)?;
+                Some(Content::apply(
+                    Content::csymbol("calculus1", "diff"),
+                    [lambda_of(vars, body)],
+                ))
+            }
+            _ => {
+                let c = Content::from_element(e)?;
+                if self.peek_op() == Some('\u{2061}') {
+                    self.pos += 1;
+                    return self.arguments(c);
+                }
+                Some(c)
+            }
+        }
+    }
+
+    /// Read a term starting with an operator.
+    fn operator(&mut self, c: char) -> Option<Content> {
+        match c {
+            '−' => match self.peek().map(Element::elem) {
+                Some(MathElement::Num(t)) => {
+                    self.pos += 1;
+                    Some(Content::cn(format!("-{}", t)))
+                }
+                _ => Some(Content::apply(
+                    Content::csymbol("arith1", "unary_minus"),
+                    [self.expr(PREFIX)?],
+                )),
+            },
+            '¬' => Some(Content::apply(
+                Content::csymbol("logic1", "not"),
+                [self.expr(PREFIX)?],
+            )),
+            '∀' | '∃' => {
+                let head = notation_symbol(|n| n == Notation::Quantifier(c))?;
+                let rest = &self.elems[self.pos..];
+                let colon = rest.iter().position(|e| op_char(e) == Some(':'))?;
+                let vars = read_vars(&rest[..colon])?;
+                self.pos += colon + 1;
+                Some(Content::bind(head, vars, self.expr(LOWEST)?))
+            }
+            '∫' => self.integral(Content::csymbol("calculus1", "int"), None),
+            '(' | '[' | '{' | '⌊' | '⌈' | '|' => {
+                let start = self.pos;
+                let mut depth = 0;
+                let end = self.elems[start..].iter().position(|e| match op_char(e) {
+                    Some('|') if c == '|' => true,
+                    Some('(' | '[' | '{' | '⌊' | '⌈') => {
+                        depth += 1;
+                        false
+                    }
+                    Some(')' | ']' | '}' | '⌋' | '⌉') if depth > 0 => {
+                        depth -= 1;
+                        false
+                    }
+                    Some(')' | ']' | '}' | '⌋' | '⌉') => true,
+                    _ => false,
+                })?;
+                let close = op_char(&self.elems[start + end])?;
+                self.pos = start + end + 1;
+                fence(c, &self.elems[start..start + end], close)
+            }
+            _ => None,
+        }
+    }
+
+    /// Read the arguments of a function after the function application
+    /// operator.
+    fn arguments(&mut self, head: Content) -> Option<Content> {
+        let arg = self.next()?;
+        let args = match arg.elem() {
+            MathElement::Row(elems)
+                if elems.len() >= 2
+                    && op_char(&elems[0]) == Some('(')
+                    && op_char(&elems[elems.len() - 1]) == Some(')') =>
+            {
+                read_list(&elems[1..elems.len() - 1])?
+            }
+            _ => vec![Content::from_element(arg)?],
+        };
+        Some(Content::apply(head, args))
+    }
+
+    /// Read an integral's body and the `d x` after it.
+    fn integral(&mut self, head: Content, range: Option<Content>) -> Option<Content> {
+        let body = self.expr(PRODUCT)?;
+        if !self.next().is_some_and(is_d) {
+            return None;
+        }
+        let vars = read_vars(std::slice::from_ref(self.next()?))?;
+        let args = range.into_iter().chain([lambda_of(vars, body)]);
+        Some(Content::apply(head, args))
+    }
+}
+
+fn is_lim(e: &Element) -> bool {
+    matches!(e.elem(), MathElement::Id { t, .. } if t == "lim")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sym(cd: &str, name: &str) -> Content {
+        Content::csymbol(cd, name)
+    }
+
+    #[test]
+    fn parse_non_strict() {
+        let c = parse(
+            r#"<math><apply><eq/>
+                <apply><minus/><ci>x</ci></apply>
+                <apply><plus/><cn type="integer">2</cn><ci>y</ci></apply>
+            </apply></math>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            c,
+            Content::apply(
+                sym("relation1", "eq"),
+                [
+                    Content::apply(sym("arith1", "unary_minus"), [Content::ci("x")]),
+                    Content::apply(
+                        sym("arith1", "plus"),
+                        [
+                            Content::Cn {
+                                t: "2".into(),
+                                kind: Some("integer".into())
+                            },
+                            Content::ci("y")
+                        ]
+                    ),
+                ]
+            )
+        );
+        let c = parse(
+            r#"<apply><int/><bvar><ci>x</ci></bvar><lowlimit><cn>0</cn></lowlimit>
+                <uplimit><cn>1</cn></uplimit><apply><sin/><ci>x</ci></apply></apply>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            c,
+            Content::apply(
+                sym("calculus1", "defint"),
+                [
+                    Content::apply(
+                        sym("interval1", "oriented_interval"),
+                        [Content::cn("0"), Content::cn("1")]
+                    ),
+                    Content::bind(
+                        sym("fns1", "lambda"),
+                        ["x"],
+                        Content::apply(sym("transc1", "sin"), [Content::ci("x")])
+                    ),
+                ]
+            )
+        );
+        assert_eq!(
+            parse(r#"<cn type="rational">1<sep/>2</cn>"#).unwrap(),
+            Content::apply(
+                sym("nums1", "rational"),
+                [Content::cn("1"), Content::cn("2")]
+            )
+        );
+        assert_eq!(
+            parse("<foo/>").unwrap(),
+            Content::err("unsupported Content MathML element <foo>")
+        );
+    }
+
+    #[test]
+    fn rational() {
+        let c = parse(r#"<cn type="rational">1<sep/>2</cn>"#).unwrap();
+        let e = c.to_element();
+        let MathElement::Frac { num, den, .. } = e.elem() else {
+            panic!("expected a fraction, got {:?}", e);
+        };
+        assert_eq!((&**num, &**den), (&Element::num("1"), &Element::num("2")));
+        assert_eq!(Content::from_element(&e), Some(c));
+        let divide = Content::apply(
+            sym("arith1", "divide"),
+            [Content::cn("1"), Content::cn("2")],
+        );
+        assert_eq!(Content::from_element(&divide.to_element()), Some(divide));
+    }
+
+    #[test]
+    fn parse_strict() {
+        let c = parse(
+            r#"<math xmlns="http://www.w3.org/1998/Math/MathML"><bind>
+                <csymbol cd="quant1">forall</csymbol><bvar><ci>x</ci></bvar>
+                <apply><csymbol cd="relation1">geq</csymbol>
+                    <apply><csymbol cd="arith1">power</csymbol><ci>x</ci><cn>2</cn></apply>
+                    <cn>0</cn>
+                </apply>
+            </bind></math>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            c,
+            Content::bind(
+                sym("quant1", "forall"),
+                ["x"],
+                Content::apply(
+                    sym("relation1", "geq"),
+                    [
+                        Content::apply(
+                            sym("arith1", "power"),
+                            [Content::ci("x"), Content::cn("2")]
+                        ),
+                        Content::cn("0"),
+                    ]
+                )
+            )
+        );
+    }
+
+    #[test]
+    fn content_roundtrip() {
+        let c = Content::apply(
+            sym("arith1", "sum"),
+            [
+                Content::apply(
+                    sym("interval1", "integer_interval"),
+                    [Content::cn("1"), Content::ci("n")],
+                ),
+                Content::bind(
+                    sym("fns1", "lambda"),
+                    ["i"],
+                    Content::apply(Content::ci("f"), [Content::ci("i"), Content::cs("a<b")]),
+                ),
+            ],
+        );
+        for opts in [WriteOptions::default(), WriteOptions::pretty()] {
+            assert_eq!(parse(&c.to_content_mathml_with(&opts)).unwrap(), c);
+        }
+        let e = Content::err("bad input");
+        assert_eq!(parse(&e.to_content_mathml()).unwrap(), e);
+        assert_eq!(
+            Content::apply(sym("arith1", "plus"), [Content::ci("a"), Content::cn("1")])
+                .to_content_mathml(),
+            "<math xmlns=\"http://www.w3.org/1998/Math/MathML\"><apply><csymbol cd=\"arith1\">plus</csymbol><ci>a</ci><cn>1</cn></apply></math>"
+        );
+    }
+
+    #[test]
+    fn render_arithmetic() {
+        let a = || Content::ci("a");
+        let b = || Content::ci("b");
+        let plus = Content::apply(sym("arith1", "plus"), [a(), b()]);
+        // (a + b)^2 / (a − (a + b))
+        let c = Content::apply(
+            sym("arith1", "divide"),
+            [
+                Content::apply(sym("arith1", "power"), [plus.clone(), Content::cn("2")]),
+                Content::apply(sym("arith1", "minus"), [a(), plus.clone()]),
+            ],
+        );
+        let paren = || {
+            fenced(
+                Some('('),
+                vec![Element::id("a"), Element::op('+'), Element::id("b")],
+                Some(')'),
+            )
+        };
+        assert_eq!(
+            c.to_element(),
+            Element::frac(
+                Element::sup(paren(), Element::num("2")),
+                Element::row([Element::id("a"), Element::op('−'), paren()]),
+            )
+        );
+        let c = Content::apply(sym("arith1", "times"), [Content::cn("2"), a(), plus]);
+        assert_eq!(
+            c.to_element(),
+            Element::row([
+                Element::num("2"),
+                Element::op('\u{2062}'),
+                Element::id("a"),
+                Element::op('\u{2062}'),
+                paren(),
+            ])
+        );
+        let c = Content::apply(
+            sym("arith1", "root"),
+            [
+                Content::apply(sym("integer1", "factorial"), [a()]),
+                Content::cn("3"),
+            ],
+        );
+        assert_eq!(
+            c.to_element(),
+            Element::root(
+                Element::row([Element::id("a"), Element::op('!')]),
+                Element::num("3")
+            )
+        );
+    }
+
+    #[test]
+    fn render_signs() {
+        let neg = |n: &str| {
+            fenced(
+                Some('('),
+                vec![Element::op('−'), Element::num(n)],
+                Some(')'),
+            )
+        };
+        // −(−1)
+        let c = Content::apply(sym("arith1", "minus"), [Content::cn("-1")]);
+        assert_eq!(c.to_element(), Element::row([Element::op('−'), neg("1")]));
+        // x + 1 + (−y)
+        let c = Content::apply(
+            sym("arith1", "plus"),
+            [
+                Content::ci("x"),
+                Content::cn("1"),
+                Content::apply(sym("arith1", "unary_minus"), [Content::ci("y")]),
+            ],
+        );
+        let e = c.to_element();
+        assert_eq!(
+            e,
+            Element::row([
+                Element::id("x"),
+                Element::op('+'),
+                Element::num("1"),
+                Element::op('+'),
+                fenced(
+                    Some('('),
+                    vec![Element::op('−'), Element::id("y")],
+                    Some(')')
+                ),
+            ])
+        );
+        assert_eq!(Content::from_element(&e), Some(c));
+        // −2 × (−3): a leading sign stays bare.
+        let c = Content::apply(
+            sym("arith1", "times"),
+            [Content::cn("-2"), Content::cn("-3")],
+        );
+        assert_eq!(
+            c.to_element(),
+            Element::row([
+                Element::op('−'),
+                Element::num("2"),
+                Element::op('×'),
+                neg("3"),
+            ])
+        );
+    }
+
+    #[test]
+    fn render_binders() {
+        let c = parse(
+            r#"<apply><sum/><bvar><ci>i</ci></bvar><lowlimit><cn>1</cn></lowlimit>
+                <uplimit><ci>n</ci></uplimit><apply><power/><ci>i</ci><cn>2</cn></apply></apply>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            c.to_element(),
+            Element::row([
+                Element::under_over(
+                    Element::op('∑'),
+                    Element::row([Element::id("i"), Element::op('='), Element::num("1")]),
+                    Element::id("n")
+                ),
+                Element::sup(Element::id("i"), Element::num("2")),
+            ])
+        );
+        let c = parse(
+            r#"<apply><limit/><bvar><ci>x</ci></bvar><condition>
+                <apply><tendsto/><ci>x</ci><cn>0</cn></apply></condition>
+                <apply><sin/><ci>x</ci></apply></apply>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            c.to_element(),
+            Element::row([
+                Element::under(
+                    Element::id("lim"),
+                    Element::row([Element::id("x"), Element::op('→'), Element::num("0")])
+                ),
+                Element::row([
+                    Element::id("sin"),
+                    Element::op('\u{2061}'),
+                    Element::id("x")
+                ]),
+            ])
+        );
+        let c = Content::bind(
+            sym("quant1", "exists"),
+            ["x"],
+            Content::apply(sym("set1", "in"), [Content::ci("x"), sym("setname1", "R")]),
+        );
+        assert_eq!(
+            c.to_element(),
+            Element::row([
+                Element::op('∃'),
+                Element::id("x"),
+                Element::op(':'),
+                Element::id("x"),
+                Element::op('∈'),
+                Element::id_normal("ℝ"),
+            ])
+        );
+    }
+
+    #[test]
+    fn render_functions() {
+        let c = Content::apply(
+            Content::ci("f"),
+            [
+                Content::ci("x"),
+                Content::apply(sym("arith1", "abs"), [Content::ci("y")]),
+            ],
+        );
+        assert_eq!(
+            c.to_element(),
+            Element::row([
+                Element::id("f"),
+                Element::op('\u{2061}'),
+                fenced(
+                    Some('('),
+                    vec![
+                        Element::id("x"),
+                        Element::op(','),
+                        fenced(Some('|'), vec![Element::id("y")], Some('|')),
+                    ],
+                    Some(')')
+                ),
+            ])
+        );
+        let c = Content::apply(
+            sym("linalg2", "matrix"),
+            [
+                Content::apply(
+                    sym("linalg2", "matrixrow"),
+                    [Content::cn("1"), Content::cn("0")],
+                ),
+                Content::apply(
+                    sym("linalg2", "matrixrow"),
+                    [Content::cn("0"), Content::cn("1")],
+                ),
+            ],
+        );
+        assert_eq!(
+            c.to_element(),
+            fenced(
+                Some('('),
+                vec![Element::matrix([
+                    [Element::num("1"), Element::num("0")],
+                    [Element::num("0"), Element::num("1")],
+                ])],
+                Some(')')
+            )
+        );
+    }
+
+    #[test]
+    fn from_element() {
+        let x = || Content::ci("x");
+        let lambda = |v: &str, body| Content::bind(sym("fns1", "lambda"), [v.to_string()], body);
+        let cases = [
+            Content::apply(
+                sym("arith1", "plus"),
+                [
+                    Content::ci("a"),
+                    Content::apply(sym("arith1", "times"), [Content::cn("2"), x()]),
+                    Content::cn("-1"),
+                ],
+            ),
+            Content::apply(
+                sym("arith1", "minus"),
+                [
+                    Content::ci("a"),
+                    Content::apply(sym("arith1", "minus"), [Content::ci("b"), x()]),
+                ],
+            ),
+            Content::apply(
+                sym("relation1", "eq"),
+                [
+                    Content::apply(sym("arith1", "divide"), [x(), Content::ci("y")]),
+                    Content::apply(sym("arith1", "power"), [x(), Content::cn("2")]),
+                    Content::apply(sym("arith1", "root"), [x(), Content::cn("3")]),
+                ],
+            ),
+            Content::apply(
+                sym("logic1", "and"),
+                [
+                    Content::apply(sym("relation1", "lt"), [x(), sym("nums1", "pi")]),
+                    Content::apply(sym("logic1", "not"), [Content::ci("p")]),
+                ],
+            ),
+            Content::apply(
+                Content::ci("f"),
+                [
+                    Content::apply(sym("transc1", "sin"), [x()]),
+                    Content::apply(sym("arith1", "abs"), [Content::ci("y")]),
+                    Content::apply(sym("integer1", "factorial"), [Content::ci("n")]),
+                ],
+            ),
+            Content::apply(
+                sym("set1", "union"),
+                [
+                    Content::apply(sym("set1", "set"), [Content::ci("a"), Content::ci("b")]),
+                    Content::apply(sym("set1", "set"), [sym("set1", "emptyset")]),
+                ],
+            ),
+            Content::bind(
+                sym("quant1", "forall"),
+                ["x".to_string()],
+                Content::apply(
+                    sym("relation1", "geq"),
+                    [
+                        Content::apply(sym("arith1", "power"), [x(), Content::cn("2")]),
+                        Content::cn("0"),
+                    ],
+                ),
+            ),
+            lambda("x", Content::apply(sym("arith1", "unary_minus"), [x()])),
+            Content::apply(
+                sym("arith1", "sum"),
+                [
+                    Content::apply(
+                        sym("interval1", "integer_interval"),
+                        [Content::cn("1"), Content::ci("n")],
+                    ),
+                    lambda("i", Content::ci("i")),
+                ],
+            ),
+            Content::apply(
+                sym("arith1", "plus"),
+                [
+                    Content::apply(
+                        sym("calculus1", "defint"),
+                        [
+                            Content::apply(
+                                sym("interval1", "oriented_interval"),
+                                [Content::cn("0"), Content::cn("1")],
+                            ),
+                            lambda("x", x()),
+                        ],
+                    ),
+                    Content::apply(
+                        sym("calculus1", "int"),
+                        [lambda("x", Content::apply(sym("transc1", "cos"), [x()]))],
+                    ),
+                ],
+            ),
+            Content::apply(
+                sym("limit1", "limit"),
+                [
+                    Content::cn("0"),
+                    sym("limit1", "above"),
+                    lambda(
+                        "x",
+                        Content::apply(sym("arith1", "divide"), [Content::cn("1"), x()]),
+                    ),
+                ],
+            ),
+            Content::apply(
+                sym("calculus1", "diff"),
+                [lambda("x", Content::apply(sym("transc1", "exp"), [x()]))],
+            ),
+            Content::apply(
+                sym("linalg2", "matrix"),
+                [Content::apply(
+                    sym("linalg2", "matrixrow"),
+                    [Content::cn("1"), x()],
+                )],
+            ),
+        ];
+        for c in cases {
+            assert_eq!(Content::from_element(&c.to_element()), Some(c));
+        }
+
+        // Lists and closed intervals share brackets, and juxtaposition has no
+        // operator at all.
+        let list = Content::apply(sym("list1", "list"), [Content::cn("1"), Content::cn("2")]);
+        assert_eq!(Content::from_element(&list.to_element()), None);
+        let row = Element::row([Element::id("x"), Element::id("y")]);
+        assert_eq!(Content::from_element(&row), None);
+        let fence = Element::row([Element::op('('), Element::id("x"), Element::op(')')]);
+        assert_eq!(Content::from_element(&fence), Some(x()));
+    }
+}
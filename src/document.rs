@@ -0,0 +1,421 @@
+//! Encoding and decoding whole fog-math documents.
+
+use fog_pack::{
+    document::{Document, NewDocument},
+    schema::NoSchema,
+    types::Value,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    math::{Element, MathElement},
+    schema::schema,
+    Error,
+};
+
+/// Encode a list of equations as a document using the fog-math schema.
+///
+/// The document is checked against the schema before being returned, so
+/// passing it to [`Schema::validate_new_doc`] will succeed. Unknown elements
+/// and attributes, read from a newer schema version, fail with
+/// [`Error::Unknown`]; they can only be written with the schema they came
+/// from.
+///
+/// [`Schema::validate_new_doc`]: fog_pack::schema::Schema::validate_new_doc
+pub fn encode_document(elems: &[Element]) -> Result<NewDocument, Error> {
+    let doc = NewDocument::new(Some(schema().hash()), elems)?;
+    match schema().validate_new_doc(doc.clone()) {
+        Ok(_) => Ok(doc),
+        Err(source) => {
+            let Ok(raw) = raw(elems) else {
+                return Err(Error::Invalid {
+                    path: Vec::new(),
+                    source,
+                });
+            };
+            let (path, found) = find(&raw, &|e| validates(e));
+            match found.and_then(unknown_name) {
+                Some(name) => Err(Error::Unknown { path, name }),
+                None => Err(Error::Invalid { path, source }),
+            }
+        }
+    }
+}
+
+/// Decode a document written with the fog-math schema into its list of
+/// equations.
+///
+/// A [`Document`] has already passed validation against its schema, so
+/// this only checks that the schema is fog-math's. Documents from older
+/// versions should go through [`Migrations`](crate::migrate::Migrations),
+/// and ones from newer versions through [`decode_document_lenient`].
+pub fn decode_document(doc: &Document) -> Result<Vec<Element>, Error> {
+    let expected = schema().hash();
+    if doc.schema_hash() != Some(expected) {
+        return Err(Error::SchemaMismatch {
+            actual: doc.schema_hash().cloned(),
+            expected: expected.clone(),
+        });
+    }
+    doc.deserialize().map_err(|source| {
+        let path = doc
+            .deserialize::<Value>()
+            .map(|v| locate(&v, &|e| decode::<Element>(e).is_ok()))
+            .unwrap_or_default();
+        Error::Decode { path, source }
+    })
+}
+
+/// Decode a document that may use a newer version of the fog-math schema.
+///
+/// Documents with fog-math's schema are decoded as [`decode_document`]
+/// does. Documents with any other schema are read as far as possible:
+/// elements and attributes this version doesn't know become
+/// [`MathElement::Unknown`] and [`Attributes::unknown`]. Nothing checks that
+/// the other schema really is a version of fog-math's, so only use this for
+/// documents that are expected to hold math. If the contents don't fit, the
+/// document is rejected as using the wrong schema.
+///
+/// [`MathElement::Unknown`]: crate::math::MathElement::Unknown
+/// [`Attributes::unknown`]: crate::math::Attributes::unknown
+pub fn decode_document_lenient(doc: &Document) -> Result<Vec<Element>, Error> {
+    let expected = schema().hash();
+    match doc.schema_hash() {
+        Some(hash) if hash != expected => doc.deserialize().map_err(|_| Error::SchemaMismatch {
+            actual: Some(hash.clone()),
+            expected: expected.clone(),
+        }),
+        _ => decode_document(doc),
+    }
+}
+
+/// Convert anything serializable into a generic fog-pack value.
+fn raw<T: Serialize>(data: T) -> Result<Value, fog_pack::error::Error> {
+    decode(data)
+}
+
+/// Pass data through fog-pack without any schema, deserializing it as `T`.
+pub(crate) fn decode<T: DeserializeOwned>(
+    data: impl Serialize,
+) -> Result<T, fog_pack::error::Error> {
+    let doc = NoSchema::validate_new_doc(NewDocument::new(None, data)?)?;
+    doc.deserialize()
+}
+
+/// Check if a single element passes the schema.
+fn validates(elem: &Value) -> bool {
+    NewDocument::new(Some(schema().hash()), [elem])
+        .and_then(|doc| schema().validate_new_doc(doc))
+        .is_ok()
+}
+
+/// Name the first element or attribute from a newer schema version found
+/// directly on an element, including its table rows and cells.
+fn unknown_name(elem: &Value) -> Option<String> {
+    let elem: Element = decode(elem).ok()?;
+    if let MathElement::Unknown { tag, .. } = elem.elem() {
+        return Some(format!("element {}", tag));
+    }
+    let mut attrs = vec![elem.attributes()];
+    if let MathElement::Table { rows } = elem.elem() {
+        for row in rows {
+            attrs.push(row.a.as_deref());
+            attrs.extend(row.cells.iter().map(|c| c.a.as_deref()));
+        }
+    }
+    attrs
+        .into_iter()
+        .flatten()
+        .find_map(|a| a.unknown.keys().next())
+        .map(|k| format!("attribute {}", k))
+}
+
+/// Find the path to the innermost element in a document that fails
+/// `check`. Elements are maps with an `e` key. Paths are made of array
+/// indices and map keys, leaving out the `e` key for readability.
+pub(crate) fn locate(doc: &Value, check: &impl Fn(&Value) -> bool) -> Vec<String> {
+    find(doc, check).0
+}
+
+/// Like [`locate`], but also return the element found.
+fn find<'a>(doc: &'a Value, check: &impl Fn(&Value) -> bool) -> (Vec<String>, Option<&'a Value>) {
+    let mut path = Vec::new();
+    let mut current = None;
+    loop {
+        let mut found = Vec::new();
+        let v = current.unwrap_or(doc);
+        elements(v, &mut Vec::new(), &mut found, v);
+        match found.into_iter().find(|(_, e)| !check(e)) {
+            Some((steps, e)) => {
+                path.extend(steps);
+                current = Some(e);
+            }
+            None => return (path, current),
+        }
+    }
+}
+
+/// Collect the outermost elements below `v`, along with the path to them.
+/// Attributes are skipped, as their data can hold arbitrary values.
+fn elements<'a>(
+    v: &'a Value,
+    path: &mut Vec<String>,
+    out: &mut Vec<(Vec<String>, &'a Value)>,
+    root: &Value,
+) {
+    match v {
+        Value::Map(m) if m.contains_key("e") && !std::ptr::eq(v, root) => {
+            out.push((path.clone(), v));
+        }
+        Value::Map(m) => {
+            for (k, v) in m.iter().filter(|(k, _)| *k != "a") {
+                let step = k != "e";
+                if step {
+                    path.push(k.clone());
+                }
+                elements(v, path, out, root);
+                if step {
+                    path.pop();
+                }
+            }
+        }
+        Value::Array(a) => {
+            for (i, v) in a.iter().enumerate() {
+                path.push(i.to_string());
+                elements(v, path, out, root);
+                path.pop();
+            }
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{math::*, migrate::Migrations, schema::SchemaType, schema::VERSION};
+    use fog_pack::{
+        schema::{Schema, SchemaBuilder},
+        validator::*,
+    };
+    use std::collections::BTreeMap;
+
+    fn map<const N: usize>(entries: [(&str, Value); N]) -> Value {
+        Value::Map(
+            entries
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+        )
+    }
+
+    fn elem(tag: &str, v: Value) -> Value {
+        map([("e", map([(tag, v)]))])
+    }
+
+    #[test]
+    fn roundtrip() {
+        let elems = vec![
+            Element::frac(Element::num("1"), Element::id("x")),
+            Element::row([Element::id("a"), Element::op('+'), Element::id("b")])
+                .variant(Variant::Bold),
+        ];
+        let doc = encode_document(&elems).unwrap();
+        let doc = schema().validate_new_doc(doc).unwrap();
+        assert_eq!(decode_document(&doc).unwrap(), elems);
+        let (_, bytes) = schema().encode_doc(doc).unwrap();
+        let doc = schema().decode_doc(bytes).unwrap();
+        assert_eq!(decode_document(&doc).unwrap(), elems);
+        assert_eq!(decode_document_lenient(&doc).unwrap(), elems);
+        assert!(decode_document(
+            &schema()
+                .validate_new_doc(encode_document(&[]).unwrap())
+                .unwrap()
+        )
+        .unwrap()
+        .is_empty());
+    }
+
+    #[test]
+    fn schema_mismatch() {
+        let doc = NoSchema::validate_new_doc(NewDocument::new(None, [Element::id("x")]).unwrap())
+            .unwrap();
+        let err = decode_document(&doc).unwrap_err();
+        assert!(matches!(err, Error::SchemaMismatch { actual: None, .. }));
+        assert!(err.to_string().contains("no schema"));
+        let err = decode_document_lenient(&doc).unwrap_err();
+        assert!(matches!(err, Error::SchemaMismatch { actual: None, .. }));
+
+        // Even the lenient decoder rejects contents that aren't math.
+        let doc = SchemaBuilder::new(StrValidator::new().build())
+            .build()
+            .unwrap();
+        let other = Schema::from_doc(&doc).unwrap();
+        let doc = other
+            .validate_new_doc(NewDocument::new(Some(other.hash()), "x").unwrap())
+            .unwrap();
+        assert!(matches!(
+            decode_document_lenient(&doc),
+            Err(Error::SchemaMismatch {
+                actual: Some(_),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn decode_path() {
+        // An empty string isn't a character. The schema rejects it, so this
+        // can only come up for documents that skipped validation.
+        let bad = map([(
+            "e",
+            map([(
+                "Frac",
+                map([
+                    ("num", elem("Num", Value::from("1"))),
+                    (
+                        "den",
+                        elem(
+                            "Row",
+                            Value::Array(vec![
+                                elem("Id", map([("t", Value::from("x"))])),
+                                elem("Op", Value::from("")),
+                            ]),
+                        ),
+                    ),
+                ]),
+            )]),
+        )]);
+        let doc = Value::Array(vec![elem("Num", Value::from("2")), bad]);
+        assert!(schema()
+            .validate_new_doc(NewDocument::new(Some(schema().hash()), &doc).unwrap())
+            .is_err());
+        let path = locate(&doc, &|e| decode::<Element>(e).is_ok());
+        assert_eq!(path, &["1", "Frac", "den", "Row", "1"]);
+        let source = decode::<Vec<Element>>(&doc).unwrap_err();
+        let err = Error::Decode { path, source };
+        assert!(err
+            .to_string()
+            .starts_with("couldn't decode element at /1/Frac/den/Row/1: "));
+    }
+
+    #[test]
+    fn invalid_path() {
+        let mut attrs = BTreeMap::new();
+        attrs.insert("rtl".to_string(), Value::from("yes"));
+        let bad = map([
+            ("e", map([("Id", map([("t", Value::from("y"))]))])),
+            ("a", Value::Map(attrs)),
+        ]);
+        let doc = Value::Array(vec![
+            elem("Sqrt", elem("Num", Value::from("2"))),
+            elem(
+                "Sup",
+                map([
+                    ("base", elem("Id", map([("t", Value::from("x"))]))),
+                    ("sup", bad),
+                ]),
+            ),
+        ]);
+        assert_eq!(locate(&doc, &|e| validates(e)), ["1", "Sup", "sup"]);
+        // A valid document has nothing to point at.
+        let good = raw([Element::sqrt(Element::num("2"))]).unwrap();
+        assert!(locate(&good, &|e| validates(e)).is_empty());
+    }
+
+    /// The current schema, plus a `Glow` element, a `Spark` element with no
+    /// content, and a `sparkle` attribute.
+    fn newer_schema() -> Schema {
+        let mut types = BTreeMap::new();
+        let root = ArrayValidator::new()
+            .items(Element::validator(&mut types))
+            .build();
+        let Some(Validator::Enum(elems)) = types.get_mut("MathElement") else {
+            panic!("expected an enum");
+        };
+        elems.0.insert("Glow".into(), Some(Validator::new_any()));
+        elems.0.insert("Spark".into(), None);
+        let Some(Validator::Map(attrs)) = types.get_mut("Attributes") else {
+            panic!("expected a map");
+        };
+        attrs
+            .opt
+            .insert("sparkle".into(), StrValidator::new().build());
+        let doc = types
+            .into_iter()
+            .fold(SchemaBuilder::new(root), |b, (n, v)| b.type_add(&n, v))
+            .name("fog-math")
+            .version(VERSION + 1)
+            .build()
+            .unwrap();
+        Schema::from_doc(&doc).unwrap()
+    }
+
+    #[test]
+    fn newer_version() {
+        let newer = newer_schema();
+        let raw = Value::Array(vec![
+            map([
+                (
+                    "e",
+                    map([(
+                        "Row",
+                        Value::Array(vec![
+                            elem("Glow", map([("hue", Value::from(3u8))])),
+                            map([("e", Value::from("Spark"))]),
+                            elem("Num", Value::from("1")),
+                        ]),
+                    )]),
+                ),
+                ("a", map([("sparkle", Value::from("lots"))])),
+            ]),
+            elem("Id", map([("t", Value::from("x"))])),
+        ]);
+        let doc = newer
+            .validate_new_doc(NewDocument::new(Some(newer.hash()), &raw).unwrap())
+            .unwrap();
+        // Only the lenient decoder accepts a schema it doesn't know.
+        assert!(matches!(
+            decode_document(&doc),
+            Err(Error::SchemaMismatch { .. })
+        ));
+        assert!(matches!(
+            Migrations::new().decode(&doc),
+            Err(Error::SchemaMismatch { .. })
+        ));
+        let elems = decode_document_lenient(&doc).unwrap();
+        let MathElement::Row(row) = elems[0].elem() else {
+            panic!("expected a row");
+        };
+        assert!(matches!(
+            row[0].elem(),
+            MathElement::Unknown { tag, value: Some(_) } if tag == "Glow"
+        ));
+        assert!(matches!(
+            row[1].elem(),
+            MathElement::Unknown { tag, value: None } if tag == "Spark"
+        ));
+        assert_eq!(elems[1], Element::id("x"));
+
+        // The current schema can't hold them...
+        let err = encode_document(&elems).unwrap_err();
+        let Error::Unknown { path, name } = &err else {
+            panic!("expected an unknown element, got {:?}", err);
+        };
+        assert_eq!(path, &["0", "Row", "0"]);
+        assert_eq!(name, "element Glow");
+        let mut plain = elems.clone();
+        plain[0].set_elem(MathElement::Row(vec![Element::num("1")]));
+        let err = encode_document(&plain).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "can't encode /0: attribute sparkle is from a newer schema version"
+        );
+
+        // ...but the newer one gets back exactly what was read.
+        let doc = newer
+            .validate_new_doc(NewDocument::new(Some(newer.hash()), &elems).unwrap())
+            .unwrap();
+        assert_eq!(doc.deserialize::<Value>().unwrap(), raw);
+    }
+}
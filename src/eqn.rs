@@ -0,0 +1,1381 @@
+//! Conversion between the troff `eqn` preprocessor language and fog-math
+//! elements.
+//!
+//! Parsing follows the precedence of GNU eqn: `from` and `to` bind loosest,
+//! then `over`, then `sup` and `sub`, and diacritics like `bar` and `dot` are
+//! postfix. Macros made with `define` are expanded, `.EQ` and `.EN` lines are
+//! skipped, and layout commands like `size`, `up`, and `mark` are ignored.
+//! Unknown troff escapes become [`MathElement::Err`] nodes, and the rest of
+//! the input is still converted.
+//!
+//! eqn has no roots with an index, prescripts, or phantoms, so those are
+//! approximated when writing.
+
+use crate::math::*;
+use crate::tree::{into_elem, into_vec, op_char};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Sym {
+    /// An operator.
+    Op(char),
+    /// An identifier, using the default italics.
+    Id(char),
+    /// An upright identifier.
+    IdNormal(char),
+    /// A large operator.
+    Large(char),
+    /// A function name, which is set in roman.
+    Func,
+    Sup,
+    Sub,
+    Over,
+    Sqrt,
+    From,
+    To,
+    Left,
+    Right,
+    /// A pile, which is a table with one column.
+    Pile,
+    Matrix,
+    /// A matrix column.
+    Col,
+    /// The separator between rows of a pile or matrix column.
+    Above,
+    /// Diacritic placed over or under the previous term, and whether it's an
+    /// accent.
+    Mark(char, bool, bool),
+    /// `accent` or `uaccent`, which place the next term over or under.
+    Accent(bool),
+    /// A font for the next term.
+    Font(Variant),
+    /// `font`, followed by a troff font name.
+    FontName,
+    /// Moves the next term forward by hundredths of an em.
+    Fwd,
+    /// Commands that are skipped, with the number of parameters they take.
+    Ignore(usize),
+    Define,
+    Nothing,
+    Half,
+}
+
+/// The eqn keywords. The first entry for a given output is the one used when
+/// writing.
+const SYMBOLS: &[(&str, Sym)] = &[
+    // Operators
+    ("times", Sym::Op('×')),
+    ("cdot", Sym::Op('⋅')),
+    ("approx", Sym::Op('≈')),
+    ("union", Sym::Op('∪')),
+    ("inter", Sym::Op('∩')),
+    ("partial", Sym::Op('∂')),
+    ("del", Sym::Op('∇')),
+    ("grad", Sym::Op('∇')),
+    ("prime", Sym::Op('′')),
+    ("cdots", Sym::Op('⋯')),
+    ("ldots", Sym::Op('…')),
+    ("inf", Sym::IdNormal('∞')),
+    ("sum", Sym::Large('∑')),
+    ("prod", Sym::Large('∏')),
+    ("int", Sym::Large('∫')),
+    ("lim", Sym::Func),
+    ("sin", Sym::Func),
+    ("cos", Sym::Func),
+    ("tan", Sym::Func),
+    ("sinh", Sym::Func),
+    ("cosh", Sym::Func),
+    ("tanh", Sym::Func),
+    ("arc", Sym::Func),
+    ("max", Sym::Func),
+    ("min", Sym::Func),
+    ("det", Sym::Func),
+    ("exp", Sym::Func),
+    ("log", Sym::Func),
+    ("ln", Sym::Func),
+    ("Re", Sym::Func),
+    ("Im", Sym::Func),
+    ("and", Sym::Func),
+    ("if", Sym::Func),
+    ("for", Sym::Func),
+    // Greek
+    ("alpha", Sym::Id('α')),
+    ("beta", Sym::Id('β')),
+    ("gamma", Sym::Id('γ')),
+    ("delta", Sym::Id('δ')),
+    ("epsilon", Sym::Id('ε')),
+    ("zeta", Sym::Id('ζ')),
+    ("eta", Sym::Id('η')),
+    ("theta", Sym::Id('θ')),
+    ("iota", Sym::Id('ι')),
+    ("kappa", Sym::Id('κ')),
+    ("lambda", Sym::Id('λ')),
+    ("mu", Sym::Id('μ')),
+    ("nu", Sym::Id('ν')),
+    ("xi", Sym::Id('ξ')),
+    ("omicron", Sym::Id('ο')),
+    ("pi", Sym::Id('π')),
+    ("rho", Sym::Id('ρ')),
+    ("sigma", Sym::Id('σ')),
+    ("tau", Sym::Id('τ')),
+    ("upsilon", Sym::Id('υ')),
+    ("phi", Sym::Id('φ')),
+    ("chi", Sym::Id('χ')),
+    ("psi", Sym::Id('ψ')),
+    ("omega", Sym::Id('ω')),
+    ("GAMMA", Sym::IdNormal('Γ')),
+    ("DELTA", Sym::IdNormal('Δ')),
+    ("THETA", Sym::IdNormal('Θ')),
+    ("LAMBDA", Sym::IdNormal('Λ')),
+    ("XI", Sym::IdNormal('Ξ')),
+    ("PI", Sym::IdNormal('Π')),
+    ("SIGMA", Sym::IdNormal('Σ')),
+    ("UPSILON", Sym::IdNormal('Υ')),
+    ("PHI", Sym::IdNormal('Φ')),
+    ("PSI", Sym::IdNormal('Ψ')),
+    ("OMEGA", Sym::IdNormal('Ω')),
+    // Commands
+    ("sup", Sym::Sup),
+    ("sub", Sym::Sub),
+    ("over", Sym::Over),
+    ("smallover", Sym::Over),
+    ("sqrt", Sym::Sqrt),
+    ("from", Sym::From),
+    ("to", Sym::To),
+    ("left", Sym::Left),
+    ("right", Sym::Right),
+    ("pile", Sym::Pile),
+    ("lpile", Sym::Pile),
+    ("cpile", Sym::Pile),
+    ("rpile", Sym::Pile),
+    ("matrix", Sym::Matrix),
+    ("ccol", Sym::Col),
+    ("lcol", Sym::Col),
+    ("rcol", Sym::Col),
+    ("col", Sym::Col),
+    ("above", Sym::Above),
+    ("dot", Sym::Mark('˙', false, true)),
+    ("dotdot", Sym::Mark('¨', false, true)),
+    ("hat", Sym::Mark('^', false, true)),
+    ("tilde", Sym::Mark('~', false, true)),
+    ("vec", Sym::Mark('→', false, true)),
+    ("dyad", Sym::Mark('↔', false, true)),
+    ("bar", Sym::Mark('‾', false, false)),
+    ("under", Sym::Mark('_', true, false)),
+    ("utilde", Sym::Mark('~', true, true)),
+    ("accent", Sym::Accent(false)),
+    ("uaccent", Sym::Accent(true)),
+    ("roman", Sym::Font(Variant::Normal)),
+    ("italic", Sym::Font(Variant::Italic)),
+    ("bold", Sym::Font(Variant::Bold)),
+    ("fat", Sym::Font(Variant::Bold)),
+    ("font", Sym::FontName),
+    ("fwd", Sym::Fwd),
+    ("size", Sym::Ignore(1)),
+    ("back", Sym::Ignore(1)),
+    ("up", Sym::Ignore(1)),
+    ("down", Sym::Ignore(1)),
+    ("gsize", Sym::Ignore(1)),
+    ("gfont", Sym::Ignore(1)),
+    ("delim", Sym::Ignore(1)),
+    ("mark", Sym::Ignore(0)),
+    ("lineup", Sym::Ignore(0)),
+    ("define", Sym::Define),
+    ("ndefine", Sym::Define),
+    ("tdefine", Sym::Define),
+    ("nothing", Sym::Nothing),
+    ("half", Sym::Half),
+];
+
+/// Character sequences recognized inside words.
+const SEQUENCES: &[(&str, char)] = &[
+    (">=", '≥'),
+    ("<=", '≤'),
+    ("==", '≡'),
+    ("!=", '≠'),
+    ("+-", '±'),
+    ("->", '→'),
+    ("<-", '←'),
+    ("<<", '≪'),
+    (">>", '≫'),
+    ("...", '…'),
+    ("-", '−'),
+];
+
+/// troff special characters, written as `\(xx` or `\[xx]`.
+const SPECIAL: &[(&str, Sym)] = &[
+    ("lC", Sym::Op('{')),
+    ("rC", Sym::Op('}')),
+    ("ha", Sym::Op('^')),
+    ("ti", Sym::Op('~')),
+    ("dq", Sym::Op('"')),
+    ("mu", Sym::Op('×')),
+    ("di", Sym::Op('÷')),
+    ("+-", Sym::Op('±')),
+    ("mi", Sym::Op('−')),
+    ("**", Sym::Op('∗')),
+    ("<=", Sym::Op('≤')),
+    (">=", Sym::Op('≥')),
+    ("!=", Sym::Op('≠')),
+    ("==", Sym::Op('≡')),
+    ("~~", Sym::Op('≈')),
+    ("ap", Sym::Op('∼')),
+    ("pt", Sym::Op('∝')),
+    ("->", Sym::Op('→')),
+    ("<-", Sym::Op('←')),
+    ("ua", Sym::Op('↑')),
+    ("da", Sym::Op('↓')),
+    ("if", Sym::IdNormal('∞')),
+    ("pd", Sym::Op('∂')),
+    ("gr", Sym::Op('∇')),
+    ("no", Sym::Op('¬')),
+    ("is", Sym::Large('∫')),
+    ("sr", Sym::Op('√')),
+    ("ca", Sym::Op('∩')),
+    ("cu", Sym::Op('∪')),
+    ("sb", Sym::Op('⊂')),
+    ("sp", Sym::Op('⊃')),
+    ("ib", Sym::Op('⊆')),
+    ("ip", Sym::Op('⊇')),
+    ("mo", Sym::Op('∈')),
+    ("es", Sym::IdNormal('∅')),
+    ("fa", Sym::Op('∀')),
+    ("te", Sym::Op('∃')),
+    ("*a", Sym::Id('α')),
+    ("*b", Sym::Id('β')),
+    ("*g", Sym::Id('γ')),
+    ("*d", Sym::Id('δ')),
+    ("*e", Sym::Id('ε')),
+    ("*z", Sym::Id('ζ')),
+    ("*y", Sym::Id('η')),
+    ("*h", Sym::Id('θ')),
+    ("*i", Sym::Id('ι')),
+    ("*k", Sym::Id('κ')),
+    ("*l", Sym::Id('λ')),
+    ("*m", Sym::Id('μ')),
+    ("*n", Sym::Id('ν')),
+    ("*c", Sym::Id('ξ')),
+    ("*o", Sym::Id('ο')),
+    ("*p", Sym::Id('π')),
+    ("*r", Sym::Id('ρ')),
+    ("*s", Sym::Id('σ')),
+    ("ts", Sym::Id('ς')),
+    ("*t", Sym::Id('τ')),
+    ("*u", Sym::Id('υ')),
+    ("*f", Sym::Id('φ')),
+    ("*x", Sym::Id('χ')),
+    ("*q", Sym::Id('ψ')),
+    ("*w", Sym::Id('ω')),
+    ("*G", Sym::IdNormal('Γ')),
+    ("*D", Sym::IdNormal('Δ')),
+    ("*H", Sym::IdNormal('Θ')),
+    ("*L", Sym::IdNormal('Λ')),
+    ("*C", Sym::IdNormal('Ξ')),
+    ("*P", Sym::IdNormal('Π')),
+    ("*S", Sym::IdNormal('Σ')),
+    ("*U", Sym::IdNormal('Υ')),
+    ("*F", Sym::IdNormal('Φ')),
+    ("*Q", Sym::IdNormal('Ψ')),
+    ("*W", Sym::IdNormal('Ω')),
+];
+
+/// troff font names. The first entry for a variant is the one used when
+/// writing.
+const FONTS: &[(&str, Variant)] = &[
+    ("R", Variant::Normal),
+    ("I", Variant::Italic),
+    ("B", Variant::Bold),
+    ("BI", Variant::BoldItalic),
+    ("CW", Variant::Monospace),
+    ("CR", Variant::Monospace),
+    ("H", Variant::SansSerif),
+    ("HR", Variant::SansSerif),
+    ("HB", Variant::BoldSansSerif),
+    ("HI", Variant::SansSerifItalic),
+    ("HBI", Variant::SansSerifBoldItalic),
+];
+
+/// Widths of the `~` and `^` spaces, in em.
+const SPACE: f32 = 0.25;
+const THIN_SPACE: f32 = 1.0 / 6.0;
+
+/// Characters that end a word.
+const DELIMITERS: &str = "{}~^\"";
+
+/// Macros nested deeper than this become errors, which stops recursive
+/// definitions.
+const MAX_DEPTH: usize = 16;
+
+/// Parse an eqn equation into an element. The `.EQ` and `.EN` lines around it
+/// are optional.
+pub fn parse(src: &str) -> Element {
+    let src: String = src
+        .lines()
+        .filter(|l| !l.starts_with(".EQ") && !l.starts_with(".EN"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let src = expand(&src, &mut Vec::new(), 0);
+    let mut p = Parser { src: &src, pos: 0 };
+    let mut elems = Vec::new();
+    loop {
+        elems.extend(p.expr_list());
+        // Unmatched closing braces and separators are kept as they are.
+        match p.next() {
+            Tok::Eof => break,
+            Tok::Close => elems.push(Element::op('}')),
+            Tok::Word("right") => elems.extend(p.delim(false).map(Element::op)),
+            Tok::Word(w) => elems.push(Element::err(w)),
+            _ => (),
+        }
+    }
+    into_elem(elems)
+}
+
+fn symbol(name: &str) -> Option<Sym> {
+    SYMBOLS
+        .iter()
+        .find(|(s, _)| *s == name)
+        .map(|(_, sym)| *sym)
+}
+
+/// Make the element for a character symbol.
+fn char_elem(sym: Sym) -> Option<Element> {
+    match sym {
+        Sym::Op(c) | Sym::Large(c) => Some(Element::op(c)),
+        Sym::Id(c) => Some(Element::id(c.to_string())),
+        Sym::IdNormal(c) => Some(Element::id_normal(c.to_string())),
+        _ => None,
+    }
+}
+
+/// Apply a font to an element. Fonts set on an inner term take priority, as
+/// they do in eqn.
+fn restyle(v: Variant, e: Element) -> Element {
+    if e.attributes().is_some_and(|a| a.variant.is_some()) {
+        return e;
+    }
+    if v == Variant::Normal {
+        if let MathElement::Id { t, .. } = e.elem() {
+            if t.chars().count() == 1 {
+                return Element::id_normal(t.as_str());
+            }
+        }
+    }
+    e.variant(v)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Tok<'a> {
+    Word(&'a str),
+    Quoted(&'a str),
+    Open,
+    Close,
+    Space(f32),
+    Eof,
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Tok<'a> {
+        let rest = self.src[self.pos..].trim_start();
+        self.pos = self.src.len() - rest.len();
+        let Some(c) = rest.chars().next() else {
+            return Tok::Eof;
+        };
+        match c {
+            '{' => {
+                self.pos += 1;
+                Tok::Open
+            }
+            '}' => {
+                self.pos += 1;
+                Tok::Close
+            }
+            '"' => {
+                let len = rest[1..].find('"').map_or(rest.len(), |i| i + 2);
+                self.pos += len;
+                Tok::Quoted(rest[1..len].trim_end_matches('"'))
+            }
+            '~' | '^' => {
+                let len = rest.find(|c| c != '~' && c != '^').unwrap_or(rest.len());
+                self.pos += len;
+                Tok::Space(
+                    rest[..len]
+                        .chars()
+                        .map(|c| if c == '~' { SPACE } else { THIN_SPACE })
+                        .sum(),
+                )
+            }
+            _ => {
+                let len = rest
+                    .find(|c: char| c.is_whitespace() || DELIMITERS.contains(c))
+                    .unwrap_or(rest.len());
+                self.pos += len;
+                Tok::Word(&rest[..len])
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Tok<'a> {
+        let pos = self.pos;
+        let t = self.next();
+        self.pos = pos;
+        t
+    }
+
+    fn peek_sym(&mut self) -> Option<Sym> {
+        match self.peek() {
+            Tok::Word(w) => symbol(w),
+            _ => None,
+        }
+    }
+
+    /// Parse until a closing brace, `above`, `right`, or the end of input.
+    fn expr_list(&mut self) -> Vec<Element> {
+        let mut out = Vec::new();
+        loop {
+            let start = self.pos;
+            match self.next() {
+                Tok::Eof | Tok::Close => {
+                    self.pos = start;
+                    return out;
+                }
+                Tok::Word(w) if matches!(symbol(w), Some(Sym::Above | Sym::Right)) => {
+                    self.pos = start;
+                    return out;
+                }
+                // Words made of several parts are spliced into the list,
+                // unless something was attached to them.
+                Tok::Word(_) => {
+                    let end = self.pos;
+                    self.pos = start;
+                    let e = self.limits();
+                    if self.pos == end {
+                        out.extend(into_vec(e));
+                    } else {
+                        out.push(e);
+                    }
+                }
+                _ => {
+                    self.pos = start;
+                    out.push(self.limits());
+                }
+            }
+        }
+    }
+
+    /// Parse a term with any limits.
+    fn limits(&mut self) -> Element {
+        let base = self.sqrt_over();
+        match self.peek_sym() {
+            Some(Sym::From) => {
+                self.next();
+                let under = self.sqrt_over();
+                if self.peek_sym() == Some(Sym::To) {
+                    self.next();
+                    Element::under_over(base, under, self.limits())
+                } else {
+                    Element::under(base, under)
+                }
+            }
+            Some(Sym::To) => {
+                self.next();
+                Element::over(base, self.limits())
+            }
+            _ => base,
+        }
+    }
+
+    /// Parse a chain of fractions.
+    fn sqrt_over(&mut self) -> Element {
+        let mut e = self.sqrt();
+        while self.peek_sym() == Some(Sym::Over) {
+            self.next();
+            e = Element::frac(e, self.sqrt());
+        }
+        e
+    }
+
+    fn sqrt(&mut self) -> Element {
+        if self.peek_sym() == Some(Sym::Sqrt) {
+            self.next();
+            Element::sqrt(self.sqrt())
+        } else {
+            self.script()
+        }
+    }
+
+    /// Parse a term with any scripts. Scripts are right-associative.
+    fn script(&mut self) -> Element {
+        let base = self.simple();
+        self.scripts(base)
+    }
+
+    fn scripts(&mut self, base: Element) -> Element {
+        match self.peek_sym() {
+            Some(Sym::Sup) => {
+                self.next();
+                Element::sup(base, self.script())
+            }
+            Some(Sym::Sub) => {
+                self.next();
+                let sub = self.simple();
+                match self.peek_sym() {
+                    Some(Sym::Sup) => {
+                        self.next();
+                        Element::sub_sup(base, sub, self.script())
+                    }
+                    Some(Sym::Sub) => Element::sub(base, self.scripts(sub)),
+                    _ => Element::sub(base, sub),
+                }
+            }
+            _ => base,
+        }
+    }
+
+    /// Parse a single term with any diacritics.
+    fn simple(&mut self) -> Element {
+        let mut e = self.primary();
+        while let Some(sym) = self.peek_sym() {
+            e = match sym {
+                Sym::Mark(c, false, accent) => {
+                    self.next();
+                    match accent {
+                        true => Element::over_accent(e, Element::op(c)),
+                        false => Element::over(e, Element::op(c)),
+                    }
+                }
+                Sym::Mark(c, true, accent) => {
+                    self.next();
+                    match accent {
+                        true => Element::under_accent(e, Element::op(c)),
+                        false => Element::under(e, Element::op(c)),
+                    }
+                }
+                Sym::Accent(under) => {
+                    self.next();
+                    match under {
+                        false => Element::over_accent(e, self.simple()),
+                        true => Element::under_accent(e, self.simple()),
+                    }
+                }
+                _ => break,
+            };
+        }
+        e
+    }
+
+    fn primary(&mut self) -> Element {
+        let w = match self.next() {
+            Tok::Eof | Tok::Close => return Element::row([]),
+            Tok::Quoted(t) => return Element::text(special_text(t)),
+            Tok::Space(w) => return Element::space(Space::width(Length::Em(w))),
+            Tok::Open => {
+                let inner = self.expr_list();
+                if self.peek() == Tok::Close {
+                    self.next();
+                }
+                return into_elem(inner);
+            }
+            Tok::Word(w) => w,
+        };
+        let Some(sym) = symbol(w) else {
+            return into_elem(self.word(w));
+        };
+        match sym {
+            Sym::Func => Element::id(w),
+            Sym::Sqrt => Element::sqrt(self.simple()),
+            Sym::Left => {
+                let mut elems = Vec::new();
+                elems.extend(self.delim(true).map(Element::op));
+                elems.extend(self.expr_list());
+                if self.peek_sym() == Some(Sym::Right) {
+                    self.next();
+                    elems.extend(self.delim(false).map(Element::op));
+                }
+                Element::row(elems)
+            }
+            Sym::Pile => Element::matrix(self.column().into_iter().map(|e| [e])),
+            Sym::Matrix => self.matrix(),
+            Sym::Font(v) => restyle(v, self.simple()),
+            Sym::FontName => {
+                let v = match self.next() {
+                    Tok::Word(f) => FONTS.iter().find(|(n, _)| *n == f).map(|(_, v)| *v),
+                    _ => None,
+                };
+                let e = self.simple();
+                match v {
+                    Some(v) => restyle(v, e),
+                    None => e,
+                }
+            }
+            Sym::Fwd => {
+                let w = match self.next() {
+                    Tok::Word(n) => n.parse::<f32>().unwrap_or(0.0) / 100.0,
+                    _ => 0.0,
+                };
+                let mut elems = vec![Element::space(Space::width(Length::Em(w)))];
+                elems.extend(into_vec(self.simple()));
+                Element::row(elems)
+            }
+            Sym::Ignore(n) => {
+                for _ in 0..n {
+                    self.next();
+                }
+                self.primary()
+            }
+            Sym::Nothing => Element::row([]),
+            Sym::Half => Element::frac(Element::num("1"), Element::num("2")),
+            sym => char_elem(sym).unwrap_or_else(|| Element::err(w)),
+        }
+    }
+
+    /// Split a word into identifiers, numbers, and operators.
+    fn word(&self, w: &str) -> Vec<Element> {
+        let mut out = Vec::new();
+        let mut rest = w;
+        while let Some(c) = rest.chars().next() {
+            let len = if c.is_alphabetic() {
+                let len = rest
+                    .find(|c: char| !c.is_alphabetic())
+                    .unwrap_or(rest.len());
+                out.push(Element::id(&rest[..len]));
+                len
+            } else if c.is_ascii_digit() {
+                let mut len = rest
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len());
+                let frac = &rest[len..];
+                if frac.starts_with('.') && frac[1..].starts_with(|c: char| c.is_ascii_digit()) {
+                    len += 1 + frac[1..]
+                        .find(|c: char| !c.is_ascii_digit())
+                        .unwrap_or(frac.len() - 1);
+                }
+                out.push(Element::num(&rest[..len]));
+                len
+            } else if c == '\\' {
+                let (e, len) = escape(rest);
+                out.push(e);
+                len
+            } else if let Some((s, c)) = SEQUENCES.iter().find(|(s, _)| rest.starts_with(s)) {
+                out.push(Element::op(*c));
+                s.len()
+            } else {
+                out.push(Element::op(c));
+                c.len_utf8()
+            };
+            rest = &rest[len..];
+        }
+        out
+    }
+
+    /// Parse the delimiter after `left` or `right`.
+    fn delim(&mut self, left: bool) -> Option<char> {
+        match self.next() {
+            Tok::Open => Some('{'),
+            Tok::Close => Some('}'),
+            Tok::Word("floor") => Some(if left { '⌊' } else { '⌋' }),
+            Tok::Word("ceiling") => Some(if left { '⌈' } else { '⌉' }),
+            Tok::Word("<") => Some('⟨'),
+            Tok::Word(">") => Some('⟩'),
+            Tok::Word(w) if w.starts_with('\\') => op_char(&escape(w).0),
+            Tok::Word(w) => w.chars().next(),
+            _ => None,
+        }
+    }
+
+    /// Parse the braced body of a pile or matrix column, with rows separated
+    /// by `above`.
+    fn column(&mut self) -> Vec<Element> {
+        if self.peek() != Tok::Open {
+            return vec![self.simple()];
+        }
+        self.next();
+        let mut rows = Vec::new();
+        loop {
+            rows.push(into_elem(self.expr_list()));
+            match self.next() {
+                Tok::Word("above") => (),
+                _ => break,
+            }
+        }
+        rows
+    }
+
+    /// Parse a matrix, which is written as a list of columns.
+    fn matrix(&mut self) -> Element {
+        if self.peek() != Tok::Open {
+            return Element::matrix([[self.simple()]]);
+        }
+        self.next();
+        let mut cols = Vec::new();
+        loop {
+            match self.next() {
+                Tok::Word(w) if symbol(w) == Some(Sym::Col) => cols.push(self.column()),
+                Tok::Close | Tok::Eof => break,
+                _ => (),
+            }
+        }
+        let rows = cols.iter().map(Vec::len).max().unwrap_or(0);
+        Element::table((0..rows).map(|i| {
+            TableRow::new(
+                cols.iter().map(|col| {
+                    TableCell::new(col.get(i).cloned().map(into_vec).unwrap_or_default())
+                }),
+            )
+        }))
+    }
+}
+
+/// Expand the macros made with `define`, removing their definitions. A macro
+/// body is surrounded by any character that isn't in it, or by braces.
+fn expand(src: &str, defs: &mut Vec<(String, String)>, depth: usize) -> String {
+    let mut out = String::new();
+    let mut rest = src;
+    while let Some(c) = rest.chars().next() {
+        let len = if c == '"' {
+            rest[1..].find('"').map_or(rest.len(), |i| i + 2)
+        } else if c.is_whitespace() || DELIMITERS.contains(c) {
+            c.len_utf8()
+        } else {
+            rest.find(|c: char| c.is_whitespace() || DELIMITERS.contains(c))
+                .unwrap_or(rest.len())
+        };
+        let (word, after) = rest.split_at(len);
+        rest = after;
+        if symbol(word) == Some(Sym::Define) {
+            let after = rest.trim_start();
+            let name_len = after.find(char::is_whitespace).unwrap_or(after.len());
+            let (name, body) = after.split_at(name_len);
+            let body = body.trim_start();
+            let Some(open) = body.chars().next() else {
+                rest = body;
+                continue;
+            };
+            let close = if open == '{' { '}' } else { open };
+            let body = &body[open.len_utf8()..];
+            let end = body.find(close).unwrap_or(body.len());
+            defs.push((name.to_string(), body[..end].to_string()));
+            rest = body[end..].strip_prefix(close).unwrap_or("");
+        } else if let Some((_, body)) = defs.iter().rev().find(|(n, _)| n == word) {
+            if depth < MAX_DEPTH {
+                let body = body.clone();
+                out.push(' ');
+                out.push_str(&expand(&body, defs, depth + 1));
+                out.push(' ');
+            } else {
+                out.push_str(word);
+            }
+        } else {
+            out.push_str(word);
+        }
+    }
+    out
+}
+
+/// Parse a troff escape at the start of a string, returning the element and
+/// the length of the escape.
+fn escape(s: &str) -> (Element, usize) {
+    let (name, len) = if let Some(rest) = s.strip_prefix("\\(") {
+        let len = rest.char_indices().nth(2).map_or(rest.len(), |(i, _)| i);
+        (&rest[..len], 2 + len)
+    } else if let Some(rest) = s.strip_prefix("\\[") {
+        let len = rest.find(']').unwrap_or(rest.len());
+        (&rest[..len], (3 + len).min(s.len()))
+    } else {
+        let len = s[1..].chars().next().map_or(1, |c| 1 + c.len_utf8());
+        return (Element::err(&s[..len]), len);
+    };
+    let e = SPECIAL
+        .iter()
+        .find(|(n, _)| *n == name)
+        .and_then(|(_, sym)| char_elem(*sym))
+        .unwrap_or_else(|| Element::err(&s[..len]));
+    (e, len)
+}
+
+/// Replace troff special characters in quoted text.
+fn special_text(t: &str) -> String {
+    let mut out = String::new();
+    let mut rest = t;
+    while let Some(i) = rest.find('\\') {
+        out.push_str(&rest[..i]);
+        let (e, len) = escape(&rest[i..]);
+        match e.elem() {
+            MathElement::Op(c) => out.push(*c),
+            MathElement::Id { t, .. } => out.push_str(t),
+            _ => out.push_str(&rest[i..i + len]),
+        }
+        rest = &rest[i + len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+impl Element {
+    /// Write the element out as eqn. Elements with no eqn equivalent, like
+    /// roots with an index, prescripts, and phantoms, are approximated.
+    pub fn to_eqn(&self) -> String {
+        let mut w = Writer::default();
+        w.items(self);
+        w.out
+    }
+}
+
+/// Find the eqn keyword for a character.
+fn keyword(c: char) -> Option<&'static str> {
+    SYMBOLS
+        .iter()
+        .find(|(_, sym)| {
+            matches!(sym, Sym::Op(t) | Sym::Id(t) | Sym::IdNormal(t) | Sym::Large(t) if *t == c)
+        })
+        .map(|(s, _)| *s)
+}
+
+/// Get the keyword for a font.
+fn font(e: &Element) -> Option<String> {
+    let v = e.attributes()?.variant?;
+    match SYMBOLS.iter().find(|(_, sym)| *sym == Sym::Font(v)) {
+        Some((s, _)) => Some(s.to_string()),
+        None => FONTS
+            .iter()
+            .find(|(_, f)| *f == v)
+            .map(|(n, _)| format!("font {}", n)),
+    }
+}
+
+/// Get the brackets around a row, if it's written with `left` and `right`.
+/// The closing bracket may be missing.
+fn brackets(elems: &[Element]) -> Option<(char, Option<char>)> {
+    let open = op_char(elems.first()?)?;
+    if !"([{|⌊⌈⟨‖".contains(open) || elems.len() < 2 {
+        return None;
+    }
+    let close = op_char(elems.last()?).filter(|c| ")]}|⌋⌉⟩‖".contains(*c));
+    Some((open, close))
+}
+
+/// Get the diacritic keyword for an over- or underscript.
+fn diacritic(e: &Element) -> Option<&'static str> {
+    let (mark, under, accent) = match e.elem() {
+        MathElement::Over { over, accent, .. } => (over, false, *accent),
+        MathElement::Under {
+            under,
+            accent_under,
+            ..
+        } => (under, true, *accent_under),
+        _ => return None,
+    };
+    let c = op_char(mark)?;
+    SYMBOLS
+        .iter()
+        .find(|(_, sym)| *sym == Sym::Mark(c, under, accent))
+        .map(|(s, _)| *s)
+}
+
+/// Check if the element is written as a single eqn term.
+fn is_simple(e: &Element) -> bool {
+    if font(e).is_some() {
+        return true;
+    }
+    match e.elem() {
+        MathElement::Frac { .. } | MathElement::UnderOver { .. } => false,
+        MathElement::Sup { .. } | MathElement::Sub { .. } | MathElement::SubSup { .. } => false,
+        MathElement::Root { .. } | MathElement::MultiScript { .. } => false,
+        MathElement::Over { accent, .. } => *accent || diacritic(e).is_some(),
+        MathElement::Under { .. } => diacritic(e).is_some(),
+        MathElement::Row(elems) => brackets(elems).is_some(),
+        MathElement::Padding(_) | MathElement::Phantom(_) => false,
+        _ => true,
+    }
+}
+
+/// Check if the element is a term with scripts, which can be a fraction
+/// part without braces.
+fn is_script(e: &Element) -> bool {
+    match e.elem() {
+        MathElement::Sup { base, .. } | MathElement::Sub { base, .. } => is_simple(base),
+        MathElement::SubSup { base, sub, .. } => is_simple(base) && is_simple(sub),
+        MathElement::Sqrt(_) => true,
+        _ => is_simple(e),
+    }
+}
+
+#[derive(Default)]
+struct Writer {
+    out: String,
+    /// Set if the last token opened a group.
+    open: bool,
+}
+
+impl Writer {
+    /// Write a token. Tokens are separated by spaces, except just inside
+    /// braces.
+    fn push(&mut self, s: &str) {
+        if !self.out.is_empty() && !self.open && s != "}" {
+            self.out.push(' ');
+        }
+        self.out.push_str(s);
+        self.open = s == "{";
+    }
+
+    /// Write the contents of an element, without any grouping.
+    fn items(&mut self, e: &Element) {
+        match e.elem() {
+            MathElement::Row(elems) if font(e).is_none() && brackets(elems).is_none() => {
+                elems.iter().for_each(|e| self.element(e))
+            }
+            _ => self.element(e),
+        }
+    }
+
+    /// Write a term, grouping it in braces if needed. Fraction parts may also
+    /// have scripts.
+    fn term(&mut self, e: &Element, scripts: bool) {
+        if is_simple(e) || (scripts && is_script(e)) {
+            self.element(e);
+        } else {
+            self.push("{");
+            self.items(e);
+            self.push("}");
+        }
+    }
+
+    fn element(&mut self, e: &Element) {
+        if let Some(f) = font(e) {
+            let mut inner = e.clone();
+            inner.attributes_mut().variant = None;
+            self.push(&f);
+            self.term(&inner, false);
+            return;
+        }
+        match e.elem() {
+            MathElement::Op(_) | MathElement::Oper(_) | MathElement::ResolvedOper(_) => {
+                let c = op_char(e).unwrap();
+                if let Some((s, _)) = SEQUENCES.iter().find(|(_, t)| *t == c) {
+                    self.push(s);
+                } else if let Some(s) = keyword(c) {
+                    self.push(s);
+                } else {
+                    self.push(&special(c));
+                }
+            }
+            MathElement::Id { t, normal } => {
+                let mut chars = t.chars();
+                let single = match (chars.next(), chars.next()) {
+                    (Some(c), None) => Some(c),
+                    _ => None,
+                };
+                if let Some(k) = single.and_then(keyword) {
+                    self.push(k);
+                } else if symbol(t).is_some_and(|s| s != Sym::Func)
+                    || !t.chars().all(char::is_alphabetic)
+                {
+                    self.text(t);
+                } else if single.is_some() && *normal {
+                    self.push("roman");
+                    self.push(t);
+                } else {
+                    self.push(t);
+                }
+            }
+            MathElement::Num(t) => self.push(t),
+            MathElement::Text(t) | MathElement::Str(t) => self.text(t),
+            // eqn has no way to mark an error, so it's set off with brackets.
+            MathElement::Err(t) | MathElement::Unknown { tag: t, .. } => {
+                self.text(&format!("[[{}]]", t))
+            }
+            MathElement::Space(s) => {
+                let w = match s.width {
+                    Some(Length::Em(w)) => w,
+                    _ => THIN_SPACE,
+                };
+                let spaces = (w / SPACE + 0.01).floor() as usize;
+                let thin = ((w - spaces as f32 * SPACE) / THIN_SPACE).round() as usize;
+                let thin = if spaces + thin == 0 { 1 } else { thin };
+                self.push(&format!("{}{}", "~".repeat(spaces), "^".repeat(thin)));
+            }
+            MathElement::Row(elems) => {
+                if let Some((open, close)) = brackets(elems) {
+                    self.push("left");
+                    self.delim(open, true);
+                    let inner = &elems[1..elems.len() - close.is_some() as usize];
+                    inner.iter().for_each(|e| self.element(e));
+                    self.push("right");
+                    match close {
+                        Some(c) => self.delim(c, false),
+                        None => self.push("\"\""),
+                    }
+                } else {
+                    self.push("{");
+                    elems.iter().for_each(|e| self.element(e));
+                    self.push("}");
+                }
+            }
+            MathElement::Phantom(_) => self.push("{}"),
+            MathElement::Padding(Padding { elems, .. }) => {
+                self.push("{");
+                elems.iter().for_each(|e| self.element(e));
+                self.push("}");
+            }
+            MathElement::Frac {
+                num,
+                den,
+                line_thickness,
+            } => {
+                if *line_thickness == Some(0.0) {
+                    self.push("pile");
+                    self.push("{");
+                    self.items(num);
+                    self.push("above");
+                    self.items(den);
+                    self.push("}");
+                } else {
+                    self.term(num, true);
+                    self.push("over");
+                    self.term(den, true);
+                }
+            }
+            MathElement::Sqrt(base) => {
+                self.push("sqrt");
+                self.term(base, false);
+            }
+            MathElement::Root { base, index } => {
+                self.push("{}");
+                self.script("sup", index);
+                self.push("sqrt");
+                self.term(base, false);
+            }
+            MathElement::Sup { base, sup } => {
+                self.term(base, false);
+                self.script("sup", sup);
+            }
+            MathElement::Sub { base, sub } => {
+                self.term(base, false);
+                self.script("sub", sub);
+            }
+            MathElement::SubSup { base, sub, sup } => {
+                self.term(base, false);
+                self.script("sub", sub);
+                self.script("sup", sup);
+            }
+            MathElement::Over { base, over, accent } => {
+                self.term(base, false);
+                match diacritic(e) {
+                    Some(d) => self.push(d),
+                    None if *accent => self.script("accent", over),
+                    None => self.script("to", over),
+                }
+            }
+            MathElement::Under {
+                base,
+                under,
+                accent_under,
+            } => {
+                self.term(base, false);
+                match diacritic(e) {
+                    Some(d) => self.push(d),
+                    None if *accent_under => self.script("uaccent", under),
+                    None => self.script("from", under),
+                }
+            }
+            MathElement::UnderOver {
+                base, under, over, ..
+            } => {
+                self.term(base, false);
+                self.script("from", under);
+                self.script("to", over);
+            }
+            MathElement::MultiScript { base, post, pre } => {
+                for p in pre {
+                    self.push("{}");
+                    self.script("sub", &p.sub);
+                    self.script("sup", &p.sup);
+                }
+                // Extra pairs of scripts are attached to a braced group.
+                (1..post.len()).for_each(|_| self.push("{"));
+                self.term(base, false);
+                for (i, p) in post.iter().enumerate() {
+                    if i > 0 {
+                        self.push("}");
+                    }
+                    self.script("sub", &p.sub);
+                    self.script("sup", &p.sup);
+                }
+            }
+            MathElement::Table { rows } => {
+                let cols = rows.iter().map(|r| r.cells.len()).max().unwrap_or(0);
+                if cols == 1 {
+                    self.push("pile");
+                    self.column(rows.iter().map(|r| &r.cells[0]));
+                } else {
+                    self.push("matrix");
+                    self.push("{");
+                    for i in 0..cols {
+                        self.push("ccol");
+                        self.column(rows.iter().filter_map(|r| r.cells.get(i)));
+                    }
+                    self.push("}");
+                }
+            }
+        }
+    }
+
+    fn script(&mut self, name: &str, e: &Element) {
+        self.push(name);
+        self.term(e, false);
+    }
+
+    fn column<'a>(&mut self, cells: impl Iterator<Item = &'a TableCell>) {
+        self.push("{");
+        for (i, cell) in cells.enumerate() {
+            if i > 0 {
+                self.push("above");
+            }
+            if cell.elems.is_empty() {
+                self.push("{}");
+            }
+            cell.elems.iter().for_each(|e| self.items(e));
+        }
+        self.push("}");
+    }
+
+    fn delim(&mut self, c: char, left: bool) {
+        match c {
+            // Braces are delimiters here, not groups.
+            '{' => {
+                self.push("{");
+                self.open = false;
+            }
+            '}' => self.out.push_str(" }"),
+            '⌊' | '⌋' => self.push("floor"),
+            '⌈' | '⌉' => self.push("ceiling"),
+            '⟨' if left => self.push("<"),
+            '⟩' if !left => self.push(">"),
+            c => self.push(&special(c)),
+        }
+    }
+
+    fn text(&mut self, t: &str) {
+        let t: String = t
+            .chars()
+            .map(|c| match c {
+                '"' | '\\' => special(c),
+                c => c.to_string(),
+            })
+            .collect();
+        self.push(&format!("\"{}\"", t));
+    }
+}
+
+/// Write a character, escaping it if it can't appear in a word.
+fn special(c: char) -> String {
+    if !DELIMITERS.contains(c) && c != '\\' && !c.is_whitespace() {
+        return c.to_string();
+    }
+    match SPECIAL
+        .iter()
+        .find(|(_, sym)| char_elem(*sym).is_some_and(|e| op_char(&e) == Some(c)))
+    {
+        Some((n, _)) => format!("\\[{}]", n),
+        None => format!("\\[u{:04X}]", c as u32),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words_and_symbols() {
+        assert_eq!(
+            parse("x=2y+1 >= alpha times \\(*b"),
+            Element::row([
+                Element::id("x"),
+                Element::op('='),
+                Element::num("2"),
+                Element::id("y"),
+                Element::op('+'),
+                Element::num("1"),
+                Element::op('≥'),
+                Element::id("α"),
+                Element::op('×'),
+                Element::id("β"),
+            ])
+        );
+        assert_eq!(
+            parse("f(x) ~ \"if\" ^ GAMMA \\(zz"),
+            Element::row([
+                Element::id("f"),
+                Element::op('('),
+                Element::id("x"),
+                Element::op(')'),
+                Element::space(Space::width(Length::Em(SPACE))),
+                Element::text("if"),
+                Element::space(Space::width(Length::Em(THIN_SPACE))),
+                Element::id_normal("Γ"),
+                Element::err("\\(zz"),
+            ])
+        );
+    }
+
+    #[test]
+    fn scripts_and_limits() {
+        assert_eq!(
+            parse("sum from i=0 to inf x sub i sup 2"),
+            Element::row([
+                Element::under_over(
+                    Element::op('∑'),
+                    Element::row([Element::id("i"), Element::op('='), Element::num("0")]),
+                    Element::id_normal("∞")
+                ),
+                Element::sub_sup(Element::id("x"), Element::id("i"), Element::num("2")),
+            ])
+        );
+        assert_eq!(
+            parse("e sup x sup 2 over 2"),
+            Element::frac(
+                Element::sup(
+                    Element::id("e"),
+                    Element::sup(Element::id("x"), Element::num("2"))
+                ),
+                Element::num("2")
+            )
+        );
+        assert_eq!(
+            parse("lim from {n -> inf} a sub n"),
+            Element::row([
+                Element::under(
+                    Element::id("lim"),
+                    Element::row([Element::id("n"), Element::op('→'), Element::id_normal("∞")])
+                ),
+                Element::sub(Element::id("a"), Element::id("n")),
+            ])
+        );
+    }
+
+    #[test]
+    fn fractions_and_brackets() {
+        assert_eq!(
+            parse("{a + b} over c"),
+            Element::frac(
+                Element::row([Element::id("a"), Element::op('+'), Element::id("b")]),
+                Element::id("c")
+            )
+        );
+        assert_eq!(
+            parse("sqrt x over 2"),
+            Element::frac(Element::sqrt(Element::id("x")), Element::num("2"))
+        );
+        assert_eq!(
+            parse("left ( a over b right ) left floor x right floor left { y right \"\""),
+            Element::row([
+                Element::row([
+                    Element::op('('),
+                    Element::frac(Element::id("a"), Element::id("b")),
+                    Element::op(')'),
+                ]),
+                Element::row([Element::op('⌊'), Element::id("x"), Element::op('⌋')]),
+                Element::row([Element::op('{'), Element::id("y")]),
+            ])
+        );
+    }
+
+    #[test]
+    fn piles_and_matrices() {
+        assert_eq!(
+            parse("pile { a above b + c }"),
+            Element::matrix([
+                [Element::id("a")],
+                [Element::row([
+                    Element::id("b"),
+                    Element::op('+'),
+                    Element::id("c")
+                ])],
+            ])
+        );
+        assert_eq!(
+            parse("matrix { ccol { a above c } rcol { b above d } }"),
+            Element::matrix([
+                [Element::id("a"), Element::id("b")],
+                [Element::id("c"), Element::id("d")],
+            ])
+        );
+    }
+
+    #[test]
+    fn fonts_and_macros() {
+        assert_eq!(
+            parse(".EQ\ndefine sq 'sup 2'\nbold x sq + roman d + font HB y + x bar + y dot\n.EN"),
+            Element::row([
+                Element::sup(Element::id("x").variant(Variant::Bold), Element::num("2")),
+                Element::op('+'),
+                Element::id_normal("d"),
+                Element::op('+'),
+                Element::id("y").variant(Variant::BoldSansSerif),
+                Element::op('+'),
+                Element::over(Element::id("x"), Element::op('‾')),
+                Element::op('+'),
+                Element::over_accent(Element::id("y"), Element::op('˙')),
+            ])
+        );
+        assert_eq!(
+            parse("define half '1/2' x half"),
+            Element::row([
+                Element::id("x"),
+                Element::num("1"),
+                Element::op('/'),
+                Element::num("2"),
+            ])
+        );
+    }
+
+    #[test]
+    fn write() {
+        let e = Element::row([
+            Element::under_over(
+                Element::op('∑'),
+                Element::row([Element::id("i"), Element::op('='), Element::num("0")]),
+                Element::id("n"),
+            ),
+            Element::frac(
+                Element::sup(Element::id("x"), Element::num("2")),
+                Element::row([Element::id("y"), Element::op('−'), Element::num("1")]),
+            ),
+            Element::op('≤'),
+            Element::op('{'),
+        ]);
+        assert_eq!(
+            e.to_eqn(),
+            "sum from {i = 0} to n x sup 2 over {y - 1} <= \\[lC]"
+        );
+        assert_eq!(
+            Element::root(Element::id("x"), Element::num("3")).to_eqn(),
+            "{} sup 3 sqrt x"
+        );
+    }
+
+    #[test]
+    fn eqn_roundtrip() {
+        for src in [
+            "sum from i=0 to inf x sub i sup 2",
+            "e sup {x sup 2} over {2 sigma sup 2}",
+            "x = {-b +- sqrt {b sup 2 - 4ac}} over 2a",
+            "lim from {n -> inf} left ( 1 + 1 over n right ) sup n = e",
+            "int from 0 to 1 f(x) ~ dx ^ + half",
+            "left [ matrix { ccol { 1 above 0 } ccol { 0 above 1 } } right ]",
+            "f(x) = left { pile { 1 above 0 } right \"\"",
+            "left { x right } + left | y right |",
+            "bold x + italic y + roman d + font CW z + \"text \\(dq\"",
+            "x bar + y dot + z vec + u under + v utilde + w accent *",
+            "a sub {i sub j} sup 2 + {x sup 2} bar + b to c + d from e",
+            "alpha + OMEGA + \\(*p + 3.14 cdot partial times del",
+        ] {
+            let e = parse(src);
+            assert_eq!(parse(&e.to_eqn()), e, "{} -> {}", src, e.to_eqn());
+        }
+    }
+}
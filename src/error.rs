@@ -0,0 +1,108 @@
+use std::fmt;
+
+use fog_pack::types::Hash;
+
+/// An error encountered while encoding or decoding a fog-math document.
+#[derive(Debug)]
+pub enum Error {
+    /// The document wasn't written with the fog-math schema.
+    SchemaMismatch {
+        /// The schema the document uses, if any.
+        actual: Option<Hash>,
+        /// The fog-math schema's hash.
+        expected: Hash,
+    },
+    /// An element failed validation against the schema.
+    Invalid {
+        /// Path from the document root to the innermost failing element.
+        path: Vec<String>,
+        source: fog_pack::error::Error,
+    },
+    /// An element or attribute from a newer schema version can't be written
+    /// with this one.
+    Unknown {
+        /// Path from the document root to the element.
+        path: Vec<String>,
+        /// The unknown element's tag, or the unknown attribute's key.
+        name: String,
+    },
+    /// An element couldn't be deserialized into the fog-math types.
+    Decode {
+        /// Path from the document root to the innermost failing element.
+        path: Vec<String>,
+        source: fog_pack::error::Error,
+    },
+    /// A document couldn't be moved between schema versions.
+    Migrate {
+        /// The schema version being migrated from.
+        version: u32,
+        /// Description of the failure.
+        msg: String,
+    },
+    /// Any other fog-pack failure, like a document exceeding the size limit.
+    FogPack(fog_pack::error::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::SchemaMismatch {
+                actual: Some(actual),
+                expected,
+            } => write!(
+                f,
+                "expected the fog-math schema {}, but the document uses {}",
+                expected, actual
+            ),
+            Error::SchemaMismatch {
+                actual: None,
+                expected,
+            } => write!(
+                f,
+                "expected the fog-math schema {}, but the document has no schema",
+                expected
+            ),
+            Error::Invalid { path, source } => {
+                write!(f, "invalid element at /{}: {}", path.join("/"), source)
+            }
+            Error::Unknown { path, name } => write!(
+                f,
+                "can't encode /{}: {} is from a newer schema version",
+                path.join("/"),
+                name
+            ),
+            Error::Decode { path, source } => {
+                write!(
+                    f,
+                    "couldn't decode element at /{}: {}",
+                    path.join("/"),
+                    source
+                )
+            }
+            Error::Migrate { version, msg } => {
+                write!(
+                    f,
+                    "couldn't migrate from schema version {}: {}",
+                    version, msg
+                )
+            }
+            Error::FogPack(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::SchemaMismatch { .. } | Error::Unknown { .. } | Error::Migrate { .. } => None,
+            Error::Invalid { source, .. } | Error::Decode { source, .. } => Some(source),
+            Error::FogPack(e) => Some(e),
+        }
+    }
+}
+
+impl From<fog_pack::error::Error> for Error {
+    fn from(e: fog_pack::error::Error) -> Self {
+        Error::FogPack(e)
+    }
+}
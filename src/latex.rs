@@ -0,0 +1,2126 @@
+//! Conversion between LaTeX math-mode syntax and fog-math elements.
+//!
+//! Parsing is forgiving: unknown macros, unbalanced delimiters, and other
+//! problems become [`MathElement::Err`] nodes holding the offending source,
+//! and the rest of the input is still converted.
+
+use crate::math::*;
+use crate::tree::{into_elem, into_vec};
+
+/// How a control sequence for a single symbol should be converted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    /// An operator.
+    Op,
+    /// An identifier, using the default italics.
+    Id,
+    /// An upright identifier, like the uppercase Greek letters.
+    IdNormal,
+    /// A large operator. Scripts become limits if set.
+    Large(bool),
+}
+
+/// Control sequences that produce a single symbol.
+const SYMBOLS: &[(&str, char, Kind)] = &[
+    ("alpha", 'α', Kind::Id),
+    ("beta", 'β', Kind::Id),
+    ("gamma", 'γ', Kind::Id),
+    ("delta", 'δ', Kind::Id),
+    ("epsilon", 'ϵ', Kind::Id),
+    ("varepsilon", 'ε', Kind::Id),
+    ("zeta", 'ζ', Kind::Id),
+    ("eta", 'η', Kind::Id),
+    ("theta", 'θ', Kind::Id),
+    ("vartheta", 'ϑ', Kind::Id),
+    ("iota", 'ι', Kind::Id),
+    ("kappa", 'κ', Kind::Id),
+    ("varkappa", 'ϰ', Kind::Id),
+    ("lambda", 'λ', Kind::Id),
+    ("mu", 'μ', Kind::Id),
+    ("nu", 'ν', Kind::Id),
+    ("xi", 'ξ', Kind::Id),
+    ("omicron", 'ο', Kind::Id),
+    ("pi", 'π', Kind::Id),
+    ("varpi", 'ϖ', Kind::Id),
+    ("rho", 'ρ', Kind::Id),
+    ("varrho", 'ϱ', Kind::Id),
+    ("sigma", 'σ', Kind::Id),
+    ("varsigma", 'ς', Kind::Id),
+    ("tau", 'τ', Kind::Id),
+    ("upsilon", 'υ', Kind::Id),
+    ("phi", 'ϕ', Kind::Id),
+    ("varphi", 'φ', Kind::Id),
+    ("chi", 'χ', Kind::Id),
+    ("psi", 'ψ', Kind::Id),
+    ("omega", 'ω', Kind::Id),
+    ("Gamma", 'Γ', Kind::IdNormal),
+    ("Delta", 'Δ', Kind::IdNormal),
+    ("Theta", 'Θ', Kind::IdNormal),
+    ("Lambda", 'Λ', Kind::IdNormal),
+    ("Xi", 'Ξ', Kind::IdNormal),
+    ("Pi", 'Π', Kind::IdNormal),
+    ("Sigma", 'Σ', Kind::IdNormal),
+    ("Upsilon", 'Υ', Kind::IdNormal),
+    ("Phi", 'Φ', Kind::IdNormal),
+    ("Psi", 'Ψ', Kind::IdNormal),
+    ("Omega", 'Ω', Kind::IdNormal),
+    ("infty", '∞', Kind::IdNormal),
+    ("emptyset", '∅', Kind::IdNormal),
+    ("varnothing", '⌀', Kind::IdNormal),
+    ("hbar", 'ℏ', Kind::Id),
+    ("ell", 'ℓ', Kind::Id),
+    ("wp", '℘', Kind::Id),
+    ("Re", 'ℜ', Kind::IdNormal),
+    ("Im", 'ℑ', Kind::IdNormal),
+    ("aleph", 'ℵ', Kind::IdNormal),
+    ("beth", 'ℶ', Kind::IdNormal),
+    ("imath", 'ı', Kind::Id),
+    ("jmath", 'ȷ', Kind::Id),
+    ("partial", '∂', Kind::Op),
+    ("nabla", '∇', Kind::Op),
+    ("prime", '′', Kind::Op),
+    ("top", '⊤', Kind::IdNormal),
+    ("bot", '⊥', Kind::IdNormal),
+    ("angle", '∠', Kind::Op),
+    ("triangle", '△', Kind::IdNormal),
+    ("forall", '∀', Kind::Op),
+    ("exists", '∃', Kind::Op),
+    ("nexists", '∄', Kind::Op),
+    ("neg", '¬', Kind::Op),
+    ("lnot", '¬', Kind::Op),
+    ("pm", '±', Kind::Op),
+    ("mp", '∓', Kind::Op),
+    ("times", '×', Kind::Op),
+    ("div", '÷', Kind::Op),
+    ("cdot", '⋅', Kind::Op),
+    ("ast", '∗', Kind::Op),
+    ("star", '⋆', Kind::Op),
+    ("circ", '∘', Kind::Op),
+    ("bullet", '∙', Kind::Op),
+    ("oplus", '⊕', Kind::Op),
+    ("ominus", '⊖', Kind::Op),
+    ("otimes", '⊗', Kind::Op),
+    ("oslash", '⊘', Kind::Op),
+    ("odot", '⊙', Kind::Op),
+    ("dagger", '†', Kind::Op),
+    ("ddagger", '‡', Kind::Op),
+    ("cap", '∩', Kind::Op),
+    ("cup", '∪', Kind::Op),
+    ("uplus", '⊎', Kind::Op),
+    ("sqcap", '⊓', Kind::Op),
+    ("sqcup", '⊔', Kind::Op),
+    ("wedge", '∧', Kind::Op),
+    ("land", '∧', Kind::Op),
+    ("vee", '∨', Kind::Op),
+    ("lor", '∨', Kind::Op),
+    ("setminus", '∖', Kind::Op),
+    ("wr", '≀', Kind::Op),
+    ("amalg", '⨿', Kind::Op),
+    ("leq", '≤', Kind::Op),
+    ("le", '≤', Kind::Op),
+    ("geq", '≥', Kind::Op),
+    ("ge", '≥', Kind::Op),
+    ("neq", '≠', Kind::Op),
+    ("ne", '≠', Kind::Op),
+    ("ll", '≪', Kind::Op),
+    ("gg", '≫', Kind::Op),
+    ("leqslant", '⩽', Kind::Op),
+    ("geqslant", '⩾', Kind::Op),
+    ("prec", '≺', Kind::Op),
+    ("succ", '≻', Kind::Op),
+    ("preceq", '⪯', Kind::Op),
+    ("succeq", '⪰', Kind::Op),
+    ("equiv", '≡', Kind::Op),
+    ("sim", '∼', Kind::Op),
+    ("simeq", '≃', Kind::Op),
+    ("approx", '≈', Kind::Op),
+    ("cong", '≅', Kind::Op),
+    ("asymp", '≍', Kind::Op),
+    ("doteq", '≐', Kind::Op),
+    ("propto", '∝', Kind::Op),
+    ("models", '⊨', Kind::Op),
+    ("vdash", '⊢', Kind::Op),
+    ("dashv", '⊣', Kind::Op),
+    ("perp", '⊥', Kind::Op),
+    ("mid", '∣', Kind::Op),
+    ("nmid", '∤', Kind::Op),
+    ("parallel", '∥', Kind::Op),
+    ("nparallel", '∦', Kind::Op),
+    ("in", '∈', Kind::Op),
+    ("notin", '∉', Kind::Op),
+    ("ni", '∋', Kind::Op),
+    ("subset", '⊂', Kind::Op),
+    ("supset", '⊃', Kind::Op),
+    ("subseteq", '⊆', Kind::Op),
+    ("supseteq", '⊇', Kind::Op),
+    ("subsetneq", '⊊', Kind::Op),
+    ("supsetneq", '⊋', Kind::Op),
+    ("sqsubseteq", '⊑', Kind::Op),
+    ("sqsupseteq", '⊒', Kind::Op),
+    ("to", '→', Kind::Op),
+    ("rightarrow", '→', Kind::Op),
+    ("leftarrow", '←', Kind::Op),
+    ("gets", '←', Kind::Op),
+    ("leftrightarrow", '↔', Kind::Op),
+    ("Rightarrow", '⇒', Kind::Op),
+    ("Leftarrow", '⇐', Kind::Op),
+    ("Leftrightarrow", '⇔', Kind::Op),
+    ("implies", '⟹', Kind::Op),
+    ("impliedby", '⟸', Kind::Op),
+    ("iff", '⟺', Kind::Op),
+    ("longrightarrow", '⟶', Kind::Op),
+    ("longleftarrow", '⟵', Kind::Op),
+    ("longleftrightarrow", '⟷', Kind::Op),
+    ("Longrightarrow", '⟹', Kind::Op),
+    ("Longleftarrow", '⟸', Kind::Op),
+    ("Longleftrightarrow", '⟺', Kind::Op),
+    ("mapsto", '↦', Kind::Op),
+    ("longmapsto", '⟼', Kind::Op),
+    ("hookrightarrow", '↪', Kind::Op),
+    ("hookleftarrow", '↩', Kind::Op),
+    ("uparrow", '↑', Kind::Op),
+    ("downarrow", '↓', Kind::Op),
+    ("updownarrow", '↕', Kind::Op),
+    ("Uparrow", '⇑', Kind::Op),
+    ("Downarrow", '⇓', Kind::Op),
+    ("nearrow", '↗', Kind::Op),
+    ("searrow", '↘', Kind::Op),
+    ("rightleftharpoons", '⇌', Kind::Op),
+    ("ldots", '…', Kind::Op),
+    ("dots", '…', Kind::Op),
+    ("cdots", '⋯', Kind::Op),
+    ("vdots", '⋮', Kind::Op),
+    ("ddots", '⋱', Kind::Op),
+    ("therefore", '∴', Kind::Op),
+    ("because", '∵', Kind::Op),
+    ("colon", ':', Kind::Op),
+    ("lbrace", '{', Kind::Op),
+    ("rbrace", '}', Kind::Op),
+    ("{", '{', Kind::Op),
+    ("}", '}', Kind::Op),
+    ("langle", '⟨', Kind::Op),
+    ("rangle", '⟩', Kind::Op),
+    ("lfloor", '⌊', Kind::Op),
+    ("rfloor", '⌋', Kind::Op),
+    ("lceil", '⌈', Kind::Op),
+    ("rceil", '⌉', Kind::Op),
+    ("vert", '|', Kind::Op),
+    ("lvert", '|', Kind::Op),
+    ("rvert", '|', Kind::Op),
+    ("Vert", '‖', Kind::Op),
+    ("lVert", '‖', Kind::Op),
+    ("rVert", '‖', Kind::Op),
+    ("|", '‖', Kind::Op),
+    ("backslash", '\\', Kind::Op),
+    ("sum", '∑', Kind::Large(true)),
+    ("prod", '∏', Kind::Large(true)),
+    ("coprod", '∐', Kind::Large(true)),
+    ("bigcup", '⋃', Kind::Large(true)),
+    ("bigcap", '⋂', Kind::Large(true)),
+    ("bigvee", '⋁', Kind::Large(true)),
+    ("bigwedge", '⋀', Kind::Large(true)),
+    ("bigoplus", '⨁', Kind::Large(true)),
+    ("bigotimes", '⨂', Kind::Large(true)),
+    ("bigodot", '⨀', Kind::Large(true)),
+    ("biguplus", '⨄', Kind::Large(true)),
+    ("bigsqcup", '⨆', Kind::Large(true)),
+    ("int", '∫', Kind::Large(false)),
+    ("iint", '∬', Kind::Large(false)),
+    ("iiint", '∭', Kind::Large(false)),
+    ("oint", '∮', Kind::Large(false)),
+];
+
+/// Function names, which are typeset upright. Scripts become limits if set.
+const FUNCTIONS: &[(&str, bool)] = &[
+    ("arccos", false),
+    ("arcsin", false),
+    ("arctan", false),
+    ("arg", false),
+    ("cos", false),
+    ("cosh", false),
+    ("cot", false),
+    ("coth", false),
+    ("csc", false),
+    ("deg", false),
+    ("det", true),
+    ("dim", false),
+    ("exp", false),
+    ("gcd", true),
+    ("hom", false),
+    ("inf", true),
+    ("ker", false),
+    ("lg", false),
+    ("lim", true),
+    ("liminf", true),
+    ("limsup", true),
+    ("ln", false),
+    ("log", false),
+    ("max", true),
+    ("min", true),
+    ("Pr", true),
+    ("sec", false),
+    ("sin", false),
+    ("sinh", false),
+    ("sup", true),
+    ("tan", false),
+    ("tanh", false),
+];
+
+/// Font commands that take a single argument.
+const FONTS: &[(&str, Variant)] = &[
+    ("mathrm", Variant::Normal),
+    ("mathup", Variant::Normal),
+    ("mathit", Variant::Italic),
+    ("mathbf", Variant::Bold),
+    ("boldsymbol", Variant::BoldItalic),
+    ("bm", Variant::BoldItalic),
+    ("mathbb", Variant::DoubleStruck),
+    ("mathcal", Variant::Script),
+    ("mathscr", Variant::Script),
+    ("mathfrak", Variant::Fraktur),
+    ("mathsf", Variant::SansSerif),
+    ("mathtt", Variant::Monospace),
+];
+
+/// Text commands, and the variant they apply.
+const TEXT: &[(&str, Option<Variant>)] = &[
+    ("text", None),
+    ("textrm", None),
+    ("textnormal", None),
+    ("mbox", None),
+    ("hbox", None),
+    ("textit", Some(Variant::Italic)),
+    ("textbf", Some(Variant::Bold)),
+    ("textsf", Some(Variant::SansSerif)),
+    ("texttt", Some(Variant::Monospace)),
+];
+
+/// Accents and other decorations placed over or under their argument:
+/// command, character, whether it's an accent, and whether it goes under.
+const ACCENTS: &[(&str, char, bool, bool)] = &[
+    ("hat", '^', true, false),
+    ("widehat", '^', true, false),
+    ("check", 'ˇ', true, false),
+    ("tilde", '~', true, false),
+    ("widetilde", '~', true, false),
+    ("acute", '´', true, false),
+    ("grave", '`', true, false),
+    ("dot", '˙', true, false),
+    ("ddot", '¨', true, false),
+    ("breve", '˘', true, false),
+    ("bar", '¯', true, false),
+    ("vec", '→', true, false),
+    ("mathring", '˚', true, false),
+    ("overline", '‾', false, false),
+    ("overrightarrow", '→', false, false),
+    ("overleftarrow", '←', false, false),
+    ("overleftrightarrow", '↔', false, false),
+    ("overbrace", '⏞', false, false),
+    ("underline", '_', false, true),
+    ("underbrace", '⏟', false, true),
+    ("underrightarrow", '→', false, true),
+    ("underleftarrow", '←', false, true),
+];
+
+/// Horizontal spacing commands and their widths in em.
+const SPACES: &[(&str, f32)] = &[
+    (",", 3.0 / 18.0),
+    ("thinspace", 3.0 / 18.0),
+    (":", 4.0 / 18.0),
+    (">", 4.0 / 18.0),
+    ("medspace", 4.0 / 18.0),
+    (";", 5.0 / 18.0),
+    ("thickspace", 5.0 / 18.0),
+    ("enspace", 0.5),
+    ("quad", 1.0),
+    ("qquad", 2.0),
+    ("!", -3.0 / 18.0),
+    ("negthinspace", -3.0 / 18.0),
+    ("negmedspace", -4.0 / 18.0),
+    ("negthickspace", -5.0 / 18.0),
+];
+
+/// Explicitly sized delimiters and their size in em.
+const BIG: &[(&str, f32)] = &[
+    ("big", 1.2),
+    ("bigl", 1.2),
+    ("bigr", 1.2),
+    ("bigm", 1.2),
+    ("Big", 1.623),
+    ("Bigl", 1.623),
+    ("Bigr", 1.623),
+    ("Bigm", 1.623),
+    ("bigg", 2.047),
+    ("biggl", 2.047),
+    ("biggr", 2.047),
+    ("biggm", 2.047),
+    ("Bigg", 2.470),
+    ("Biggl", 2.470),
+    ("Biggr", 2.470),
+    ("Biggm", 2.470),
+];
+
+/// Matrix environments and the fences placed around them.
+const MATRICES: &[(&str, Option<char>, Option<char>)] = &[
+    ("matrix", None, None),
+    ("smallmatrix", None, None),
+    ("pmatrix", Some('('), Some(')')),
+    ("bmatrix", Some('['), Some(']')),
+    ("Bmatrix", Some('{'), Some('}')),
+    ("vmatrix", Some('|'), Some('|')),
+    ("Vmatrix", Some('‖'), Some('‖')),
+    ("cases", Some('{'), None),
+    ("dcases", Some('{'), None),
+    ("rcases", None, Some('}')),
+    ("array", None, None),
+    ("subarray", None, None),
+    ("aligned", None, None),
+    ("alignedat", None, None),
+    ("align", None, None),
+    ("align*", None, None),
+    ("alignat", None, None),
+    ("alignat*", None, None),
+    ("gather", None, None),
+    ("gather*", None, None),
+    ("gathered", None, None),
+    ("split", None, None),
+    ("eqnarray", None, None),
+    ("eqnarray*", None, None),
+];
+
+/// Default fraction rule thickness, in em.
+const RULE: f32 = 0.04;
+
+/// Negated forms of relations, for use with `\not`.
+const NEGATIONS: &[(char, char)] = &[
+    ('=', '≠'),
+    ('<', '≮'),
+    ('>', '≯'),
+    ('≤', '≰'),
+    ('≥', '≱'),
+    ('∈', '∉'),
+    ('∋', '∌'),
+    ('⊂', '⊄'),
+    ('⊃', '⊅'),
+    ('⊆', '⊈'),
+    ('⊇', '⊉'),
+    ('≡', '≢'),
+    ('∼', '≁'),
+    ('≃', '≄'),
+    ('≈', '≉'),
+    ('≅', '≇'),
+    ('∣', '∤'),
+    ('∥', '∦'),
+    ('∃', '∄'),
+];
+
+/// Parse LaTeX math-mode source into an element.
+///
+/// The source may optionally be wrapped in math-mode delimiters (`$...$`,
+/// `\(...\)`, `$$...$$`, or `\[...\]`). Display math delimiters set the
+/// display style on the returned element.
+pub fn parse(src: &str) -> Element {
+    let src = src.trim();
+    let (src, display) = if let Some(s) = src
+        .strip_prefix("$$")
+        .and_then(|s| s.strip_suffix("$$"))
+        .or_else(|| src.strip_prefix("\\[").and_then(|s| s.strip_suffix("\\]")))
+    {
+        (s, true)
+    } else if let Some(s) = src
+        .strip_prefix('$')
+        .and_then(|s| s.strip_suffix('$'))
+        .or_else(|| src.strip_prefix("\\(").and_then(|s| s.strip_suffix("\\)")))
+    {
+        (s, false)
+    } else {
+        (src, false)
+    };
+    let e = parse_fragment(src);
+    if display {
+        e.display_style(true)
+    } else {
+        e
+    }
+}
+
+fn parse_fragment(src: &str) -> Element {
+    let mut p = Parser { src, pos: 0 };
+    into_elem(p.group_body(false))
+}
+
+fn rebuild(e: MathElement, a: Option<Attributes>) -> Element {
+    match a {
+        Some(a) => Element::with_attributes(e, a),
+        None => Element::new(e),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Tok<'a> {
+    Cmd(&'a str),
+    Char(char),
+    Open,
+    Close,
+    Sup,
+    Sub,
+    Amp,
+    Newline,
+    Prime,
+    Eof,
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    /// Skip whitespace and comments.
+    fn skip_ws(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            if trimmed.starts_with('%') {
+                self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn next(&mut self) -> Tok<'a> {
+        self.skip_ws();
+        let rest = self.rest();
+        let Some(c) = rest.chars().next() else {
+            return Tok::Eof;
+        };
+        self.pos += c.len_utf8();
+        match c {
+            '\\' => {
+                let rest = self.rest();
+                let len = rest
+                    .find(|c: char| !c.is_ascii_alphabetic())
+                    .unwrap_or(rest.len());
+                if len > 0 {
+                    self.pos += len;
+                    return Tok::Cmd(&rest[..len]);
+                }
+                match rest.chars().next() {
+                    Some('\\') => {
+                        self.pos += 1;
+                        Tok::Newline
+                    }
+                    Some(c) => {
+                        self.pos += c.len_utf8();
+                        Tok::Cmd(&rest[..c.len_utf8()])
+                    }
+                    None => Tok::Cmd(""),
+                }
+            }
+            '{' => Tok::Open,
+            '}' => Tok::Close,
+            '^' => Tok::Sup,
+            '_' => Tok::Sub,
+            '&' => Tok::Amp,
+            '\'' => Tok::Prime,
+            c => Tok::Char(c),
+        }
+    }
+
+    fn peek(&mut self) -> Tok<'a> {
+        let pos = self.pos;
+        let t = self.next();
+        self.pos = pos;
+        t
+    }
+
+    /// Check if the next non-whitespace character is `c`, consuming it if so.
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_ws();
+        if self.rest().starts_with(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Read a brace-delimited argument as raw text.
+    fn raw_group(&mut self) -> Option<&'a str> {
+        if !self.eat('{') {
+            return None;
+        }
+        let start = self.pos;
+        let mut depth = 0;
+        let mut escaped = false;
+        for (i, c) in self.rest().char_indices() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '{' => depth += 1,
+                '}' if depth == 0 => {
+                    self.pos = start + i + 1;
+                    return Some(&self.src[start..start + i]);
+                }
+                '}' => depth -= 1,
+                _ => (),
+            }
+        }
+        self.pos = self.src.len();
+        Some(&self.src[start..])
+    }
+
+    /// Read an optional bracket-delimited argument as raw text.
+    fn raw_optional(&mut self) -> Option<&'a str> {
+        if !self.eat('[') {
+            return None;
+        }
+        let start = self.pos;
+        let mut depth = 0;
+        for (i, c) in self.rest().char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                ']' if depth == 0 => {
+                    self.pos = start + i + 1;
+                    return Some(&self.src[start..start + i]);
+                }
+                _ => (),
+            }
+        }
+        self.pos = self.src.len();
+        Some(&self.src[start..])
+    }
+
+    /// Parse elements until the closing brace of a group, or the end of
+    /// input. Stray tokens that can't appear here become errors.
+    fn group_body(&mut self, in_group: bool) -> Vec<Element> {
+        let mut out = Vec::new();
+        loop {
+            out.extend(self.row());
+            let start = self.pos;
+            match self.next() {
+                Tok::Eof => break,
+                Tok::Close if in_group => break,
+                Tok::Cmd("end") => {
+                    let name = self.raw_group().unwrap_or("");
+                    out.push(Element::err(format!("\\end{{{}}}", name)));
+                }
+                _ => out.push(Element::err(self.src[start..self.pos].trim())),
+            }
+        }
+        out
+    }
+
+    /// Parse a row of elements, stopping at anything that ends a row.
+    fn row(&mut self) -> Vec<Element> {
+        let mut out = Vec::new();
+        loop {
+            match self.peek() {
+                Tok::Eof
+                | Tok::Close
+                | Tok::Amp
+                | Tok::Newline
+                | Tok::Cmd("right")
+                | Tok::Cmd("end") => return out,
+                Tok::Cmd(
+                    style @ ("displaystyle" | "textstyle" | "scriptstyle" | "scriptscriptstyle"),
+                ) => {
+                    self.next();
+                    let rest = Element::row(self.row());
+                    out.push(match style {
+                        "displaystyle" => rest.display_style(true),
+                        "textstyle" => rest.display_style(false),
+                        "scriptstyle" => {
+                            rest.display_style(false).script_level(ScriptLevel::Set(1))
+                        }
+                        _ => rest.display_style(false).script_level(ScriptLevel::Set(2)),
+                    });
+                    return out;
+                }
+                _ => (),
+            }
+            if let Some(e) = self.scripted() {
+                out.push(e);
+            }
+        }
+    }
+
+    /// Parse an atom and any scripts attached to it.
+    fn scripted(&mut self) -> Option<Element> {
+        let (base, mut limits) = match self.peek() {
+            Tok::Sup | Tok::Sub | Tok::Prime => (Element::row([]), false),
+            _ => self.atom()?,
+        };
+        let mut sub = None;
+        let mut sup = None;
+        let mut primes = 0;
+        let mut err = None;
+        loop {
+            let start = self.pos;
+            match self.peek() {
+                Tok::Cmd("limits") => limits = true,
+                Tok::Cmd("nolimits") => limits = false,
+                Tok::Sup => {
+                    self.next();
+                    let arg = self.arg();
+                    if sup.is_some() {
+                        err = Some(Element::err(self.src[start..self.pos].trim()));
+                        break;
+                    }
+                    sup = Some(arg);
+                    continue;
+                }
+                Tok::Sub => {
+                    self.next();
+                    let arg = self.arg();
+                    if sub.is_some() {
+                        err = Some(Element::err(self.src[start..self.pos].trim()));
+                        break;
+                    }
+                    sub = Some(arg);
+                    continue;
+                }
+                Tok::Prime if sup.is_none() => primes += 1,
+                _ => break,
+            }
+            self.next();
+        }
+        if primes > 0 {
+            let prime = Element::op(match primes {
+                1 => '′',
+                2 => '″',
+                3 => '‴',
+                _ => '⁗',
+            });
+            sup = Some(match sup {
+                Some(s) => {
+                    let mut elems = vec![prime];
+                    elems.extend(into_vec(s));
+                    Element::row(elems)
+                }
+                None => prime,
+            });
+        }
+        let e = match (sub, sup, limits) {
+            (None, None, _) => base,
+            (Some(sub), None, false) => Element::sub(base, sub),
+            (None, Some(sup), false) => Element::sup(base, sup),
+            (Some(sub), Some(sup), false) => Element::sub_sup(base, sub, sup),
+            (Some(sub), None, true) => Element::under(base, sub),
+            (None, Some(sup), true) => Element::over(base, sup),
+            (Some(sub), Some(sup), true) => Element::under_over(base, sub, sup),
+        };
+        // A doubled script is kept as an error after the scripted element.
+        Some(match err {
+            Some(err) => Element::row([e, err]),
+            None => e,
+        })
+    }
+
+    /// Parse a single argument to a command or script: a group or a single
+    /// token.
+    fn arg(&mut self) -> Element {
+        let start = self.pos;
+        match self.next() {
+            Tok::Open => into_elem(self.group_body(true)),
+            Tok::Char(c) => char_elem(c),
+            Tok::Cmd(name) => match self.command(name) {
+                Some((e, _)) => e,
+                None => Element::row([]),
+            },
+            Tok::Prime => Element::op('′'),
+            Tok::Eof => Element::err("missing argument"),
+            _ => {
+                self.pos = start;
+                Element::err("missing argument")
+            }
+        }
+    }
+
+    /// Parse a single element. Returns the element and whether scripts
+    /// attached to it should be placed as limits. `None` is returned for
+    /// tokens that produce no element.
+    fn atom(&mut self) -> Option<(Element, bool)> {
+        let start = self.pos;
+        match self.next() {
+            Tok::Open => Some((into_elem(self.group_body(true)), false)),
+            Tok::Char(c) if c.is_ascii_digit() || c == '.' => {
+                let rest = self.rest();
+                let len = rest
+                    .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                    .unwrap_or(rest.len());
+                let num = rest[..len].trim_end_matches('.');
+                if c == '.' && num.is_empty() {
+                    return Some((Element::op('.'), false));
+                }
+                self.pos += num.len();
+                let mut t = c.to_string();
+                t.push_str(num);
+                Some((Element::num(t), false))
+            }
+            Tok::Char(c) => Some((char_elem(c), false)),
+            Tok::Cmd(name) => self.command(name),
+            _ => {
+                self.pos = start;
+                Some((Element::err("missing argument"), false))
+            }
+        }
+    }
+
+    /// Parse a delimiter following `\left`, `\right`, `\big`, and so on.
+    /// `Ok(None)` is returned for the empty `.` delimiter.
+    fn delim(&mut self) -> Result<Option<char>, Element> {
+        let start = self.pos;
+        match self.next() {
+            Tok::Char('.') => Ok(None),
+            Tok::Char(c) => Ok(Some(c)),
+            Tok::Cmd(name) => match SYMBOLS.iter().find(|s| s.0 == name) {
+                Some(&(_, c, Kind::Op)) => Ok(Some(c)),
+                _ => Err(Element::err(self.src[start..self.pos].trim())),
+            },
+            _ => {
+                self.pos = start;
+                Err(Element::err("missing delimiter"))
+            }
+        }
+    }
+
+    fn command(&mut self, name: &'a str) -> Option<(Element, bool)> {
+        if let Some(&(_, c, kind)) = SYMBOLS.iter().find(|s| s.0 == name) {
+            return Some(match kind {
+                Kind::Op if is_fence(c) => (fixed(c), false),
+                Kind::Op => (Element::op(c), false),
+                Kind::Id => (Element::id(c.to_string()), false),
+                Kind::IdNormal => (Element::id_normal(c.to_string()), false),
+                Kind::Large(limits) => (Element::op(c), limits),
+            });
+        }
+        if let Some(&(f, limits)) = FUNCTIONS.iter().find(|s| s.0 == name) {
+            return Some((Element::id(f), limits));
+        }
+        if let Some(&(_, v)) = FONTS.iter().find(|s| s.0 == name) {
+            let arg = self.arg();
+            return Some((font(arg, v), false));
+        }
+        if let Some(&(_, v)) = TEXT.iter().find(|s| s.0 == name) {
+            let t = Element::text(unescape_text(self.raw_group().unwrap_or("")));
+            return Some((if let Some(v) = v { t.variant(v) } else { t }, false));
+        }
+        if let Some(&(_, c, accent, under)) = ACCENTS.iter().find(|s| s.0 == name) {
+            let base = self.arg();
+            let mark = Element::op(c);
+            // Braces take limits just like large operators.
+            let limits = matches!(c, '⏞' | '⏟');
+            return Some(match (accent, under) {
+                (true, false) => (Element::over_accent(base, mark), limits),
+                (false, false) => (Element::over(base, mark), limits),
+                (true, true) => (Element::under_accent(base, mark), limits),
+                (false, true) => (Element::under(base, mark), limits),
+            });
+        }
+        if let Some(&(_, w)) = SPACES.iter().find(|s| s.0 == name) {
+            return Some((Element::space(Space::width(Length::Em(w))), false));
+        }
+        if let Some(&(_, size)) = BIG.iter().find(|s| s.0 == name) {
+            let e = match self.delim() {
+                Ok(Some(c)) => Element::oper(Operator {
+                    min_size: Some(LengthOrFraction::Em(size)),
+                    max_size: Some(LengthOrFraction::Em(size)),
+                    ..Operator::new(c)
+                }),
+                Ok(None) => Element::row([]),
+                Err(e) => e,
+            };
+            return Some((e, false));
+        }
+        let e = match name {
+            "frac" | "dfrac" | "tfrac" | "cfrac" => {
+                let num = self.arg();
+                let den = self.arg();
+                let f = Element::frac(num, den);
+                match name {
+                    "dfrac" | "cfrac" => f.display_style(true),
+                    "tfrac" => f.display_style(false),
+                    _ => f,
+                }
+            }
+            "binom" | "dbinom" | "tbinom" => {
+                let n = self.arg();
+                let k = self.arg();
+                let b = Element::row([
+                    Element::op('('),
+                    Element::frac_thickness(n, k, 0.0),
+                    Element::op(')'),
+                ]);
+                match name {
+                    "dbinom" => b.display_style(true),
+                    "tbinom" => b.display_style(false),
+                    _ => b,
+                }
+            }
+            "genfrac" => {
+                let open = self.raw_group().and_then(raw_delim);
+                let close = self.raw_group().and_then(raw_delim);
+                let thickness = self.raw_group().unwrap_or("").trim();
+                let style = self.raw_group().unwrap_or("").trim();
+                let num = self.arg();
+                let den = self.arg();
+                let f = match thickness {
+                    "" => Element::frac(num, den),
+                    t => match parse_length(t) {
+                        Some(Length::Em(v)) => Element::frac_thickness(num, den, v / RULE),
+                        _ => Element::frac(num, den),
+                    },
+                };
+                let mut out = Vec::new();
+                out.extend(open.map(Element::op));
+                out.push(f);
+                out.extend(close.map(Element::op));
+                let e = into_elem(out);
+                match style {
+                    "0" => e.display_style(true),
+                    "1" => e.display_style(false),
+                    _ => e,
+                }
+            }
+            "sqrt" => match self.raw_optional() {
+                Some(index) => {
+                    let index = parse_fragment(index);
+                    Element::root(self.arg(), index)
+                }
+                None => Element::sqrt(self.arg()),
+            },
+            "left" => return Some((self.fenced(), false)),
+            "middle" => match self.delim() {
+                Ok(Some(c)) => Element::op(c),
+                Ok(None) => Element::row([]),
+                Err(e) => e,
+            },
+            "operatorname" | "operatornamewithlimits" => {
+                let limits = name == "operatornamewithlimits" || self.eat('*');
+                let t = self.raw_group().unwrap_or("");
+                return Some((Element::id(t.trim()), limits));
+            }
+            "mathop" => return Some((self.arg(), true)),
+            "overset" | "stackrel" => {
+                let over = self.arg();
+                Element::over(self.arg(), over)
+            }
+            "underset" => {
+                let under = self.arg();
+                Element::under(self.arg(), under)
+            }
+            "xrightarrow" | "xleftarrow" => {
+                let under = self.raw_optional().map(parse_fragment);
+                let over = self.arg();
+                let arrow = Element::op(if name == "xrightarrow" { '→' } else { '←' });
+                match under {
+                    Some(under) => Element::under_over(arrow, under, over),
+                    None => Element::over(arrow, over),
+                }
+            }
+            "phantom" | "hphantom" | "vphantom" => Element::phantom(into_vec(self.arg())),
+            "hspace" | "mspace" => {
+                self.eat('*');
+                let len = self.raw_group().unwrap_or("");
+                match parse_length(len) {
+                    Some(l) => Element::space(Space::width(l)),
+                    None => Element::err(format!("\\{}{{{}}}", name, len)),
+                }
+            }
+            "kern" | "mkern" | "hskip" | "mskip" => {
+                let start = self.pos;
+                self.skip_ws();
+                let rest = self.rest();
+                let len = rest
+                    .find(|c: char| c.is_whitespace() || c == '\\' || c == '{')
+                    .unwrap_or(rest.len());
+                self.pos += len;
+                match parse_length(&rest[..len]) {
+                    Some(l) => Element::space(Space::width(l)),
+                    None => Element::err(format!("\\{}{}", name, &self.src[start..self.pos])),
+                }
+            }
+            " " | "~" => Element::text("\u{a0}"),
+            "#" | "$" | "%" | "&" | "_" => Element::text(name),
+            "not" => {
+                let start = self.pos;
+                match self.scripted().map(|e| e.into_parts()) {
+                    Some((MathElement::Op(c), None)) => match NEGATIONS.iter().find(|n| n.0 == c) {
+                        Some(&(_, n)) => Element::op(n),
+                        None => Element::text(format!("{}\u{338}", c)),
+                    },
+                    _ => Element::err(format!("\\not{}", &self.src[start..self.pos])),
+                }
+            }
+            "begin" => self.environment(),
+            "hline" | "nonumber" | "notag" | "limits" | "nolimits" => return None,
+            _ => Element::err(format!("\\{}", name)),
+        };
+        Some((e, false))
+    }
+
+    /// Parse the remainder of a `\left ... \right` pair.
+    fn fenced(&mut self) -> Element {
+        let mut out = Vec::new();
+        match self.delim() {
+            Ok(Some(c)) => out.push(Element::op(c)),
+            Ok(None) => (),
+            Err(e) => out.push(e),
+        }
+        out.extend(self.row());
+        if self.peek() == Tok::Cmd("right") {
+            self.next();
+            match self.delim() {
+                Ok(Some(c)) => out.push(Element::op(c)),
+                Ok(None) => (),
+                Err(e) => out.push(e),
+            }
+        } else {
+            out.push(Element::err("\\left without \\right"));
+        }
+        Element::row(out)
+    }
+
+    /// Parse an environment, after the `\begin`.
+    fn environment(&mut self) -> Element {
+        let name = self.raw_group().unwrap_or("").trim();
+        let fences = MATRICES.iter().find(|m| m.0 == name);
+        if matches!(
+            name,
+            "array" | "subarray" | "alignedat" | "alignat" | "alignat*"
+        ) {
+            self.raw_group();
+        }
+        let mut rows = Vec::new();
+        let mut cells = Vec::new();
+        let mut err = None;
+        loop {
+            let mut cell = self.cell();
+            cell.elems.extend(self.row());
+            cells.push(cell);
+            let start = self.pos;
+            match self.next() {
+                Tok::Amp => (),
+                Tok::Newline => {
+                    self.raw_optional();
+                    rows.push(TableRow::new(cells.drain(..)));
+                }
+                Tok::Cmd("end") => {
+                    let end = self.raw_group().unwrap_or("").trim();
+                    if end != name {
+                        err = Some(format!("\\end{{{}}}", end));
+                    }
+                    break;
+                }
+                Tok::Eof => {
+                    err = Some(format!("\\begin{{{}}} without \\end", name));
+                    break;
+                }
+                _ => cells.push(TableCell::new([Element::err(
+                    self.src[start..self.pos].trim(),
+                )])),
+            }
+        }
+        // A trailing `\\` leaves a single empty cell behind.
+        if !(cells.len() == 1 && cells[0].elems.is_empty()) {
+            rows.push(TableRow::new(cells));
+        }
+        let mut table = Element::table(rows);
+        if name == "smallmatrix" || name == "subarray" {
+            table = table.script_level(ScriptLevel::Add(1));
+        }
+        let mut out = Vec::new();
+        match fences {
+            Some(&(_, open, close)) => {
+                out.extend(open.map(Element::op));
+                out.push(table);
+                out.extend(close.map(Element::op));
+            }
+            None => {
+                out.push(Element::err(format!("\\begin{{{}}}", name)));
+                out.push(table);
+            }
+        }
+        out.extend(err.map(Element::err));
+        into_elem(out)
+    }
+
+    /// Start a table cell, handling `\multicolumn`.
+    fn cell(&mut self) -> TableCell {
+        if self.peek() != Tok::Cmd("multicolumn") {
+            return TableCell::new([]);
+        }
+        self.next();
+        let span = self
+            .raw_group()
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(1);
+        self.raw_group();
+        TableCell::new(into_vec(self.arg())).col_span(span)
+    }
+}
+
+/// Convert a single character in math mode.
+fn char_elem(c: char) -> Element {
+    match c {
+        '-' => Element::op('−'),
+        '*' => Element::op('∗'),
+        c if is_fence(c) => fixed(c),
+        '~' => Element::text("\u{a0}"),
+        c if c.is_ascii_digit() => Element::num(c.to_string()),
+        c if c.is_alphabetic() => Element::id(c.to_string()),
+        c => Element::op(c),
+    }
+}
+
+/// A delimiter written without `\left` or `\right`, which keeps its size.
+fn fixed(c: char) -> Element {
+    Element::oper(Operator {
+        stretchy: Some(false),
+        ..Operator::new(c)
+    })
+}
+
+/// Parse a delimiter given as a raw argument, like in `\genfrac`.
+fn raw_delim(s: &str) -> Option<char> {
+    let s = s.trim();
+    match s.strip_prefix('\\') {
+        Some(name) => SYMBOLS
+            .iter()
+            .find(|s| s.0 == name && s.2 == Kind::Op)
+            .map(|s| s.1),
+        None if s == "." => None,
+        None => s.chars().next(),
+    }
+}
+
+/// Apply a font command to its argument.
+fn font(e: Element, v: Variant) -> Element {
+    // Runs of letters in upright and bold fonts are words rather than
+    // products of single-letter variables.
+    let merge = matches!(
+        v,
+        Variant::Normal | Variant::Bold | Variant::SansSerif | Variant::Monospace
+    );
+    let e = if merge { merge_letters(e) } else { e };
+    // Single upright letters are marked as such. Longer words keep the
+    // variant so they aren't mistaken for operator names.
+    if v == Variant::Normal {
+        if let (MathElement::Id { t, .. }, None) = (e.elem(), e.attributes()) {
+            if t.chars().count() == 1 {
+                return Element::id_normal(t.clone());
+            }
+        }
+    }
+    e.variant(v)
+}
+
+/// Merge runs of identifiers in a row into single multi-letter identifiers.
+fn merge_letters(e: Element) -> Element {
+    let elems = into_vec(e);
+    let mut out: Vec<Element> = Vec::with_capacity(elems.len());
+    for e in elems {
+        let (e, a) = e.into_parts();
+        match (e, a) {
+            (MathElement::Id { t, normal: false }, None) => {
+                if let Some(MathElement::Id {
+                    t: prev,
+                    normal: false,
+                }) = out
+                    .last_mut()
+                    .filter(|p| p.attributes().is_none())
+                    .map(|p| p.elem_mut())
+                {
+                    prev.push_str(&t);
+                } else {
+                    out.push(Element::id(t));
+                }
+            }
+            (e, a) => out.push(rebuild(e, a)),
+        }
+    }
+    into_elem(out)
+}
+
+/// Unescape the contents of a text command.
+fn unescape_text(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(c) = rest.chars().next() {
+        rest = &rest[c.len_utf8()..];
+        match c {
+            '\\' => {
+                let len = rest
+                    .find(|c: char| !c.is_ascii_alphabetic())
+                    .unwrap_or(rest.len());
+                let (word, after) = rest.split_at(len);
+                match word {
+                    "textbackslash" => out.push('\\'),
+                    "textasciitilde" => out.push('~'),
+                    "textasciicircum" => out.push('^'),
+                    "" => match after.chars().next() {
+                        Some(c) => {
+                            if !matches!(c, '{' | '}' | '%' | '&' | '_' | '#' | '$' | '\\' | ' ') {
+                                out.push('\\');
+                            }
+                            out.push(c);
+                            rest = &after[c.len_utf8()..];
+                            continue;
+                        }
+                        None => out.push('\\'),
+                    },
+                    word => {
+                        out.push('\\');
+                        out.push_str(word);
+                    }
+                }
+                rest = after;
+            }
+            '~' => out.push('\u{a0}'),
+            '{' | '}' => (),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Parse a TeX length. Only font-relative units can be represented, so
+/// points are converted assuming a 10pt font.
+fn parse_length(s: &str) -> Option<Length> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | '-' | '+')))
+        .unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let v: f32 = num.parse().ok()?;
+    match unit.trim() {
+        "em" => Some(Length::Em(v)),
+        "ex" => Some(Length::Ex(v)),
+        "mu" => Some(Length::Em(v / 18.0)),
+        "pt" => Some(Length::Em(v / 10.0)),
+        _ => None,
+    }
+}
+
+impl Element {
+    /// Write the element out as LaTeX math-mode source, without any math-mode
+    /// delimiters. Some commands require the `amsmath` package.
+    pub fn to_latex(&self) -> String {
+        let mut w = Writer::default();
+        w.element(self);
+        w.out
+    }
+}
+
+#[derive(Default)]
+struct Writer {
+    out: String,
+    /// Set after writing a control word, which must be separated from any
+    /// letter that follows it.
+    after_word: bool,
+}
+
+impl Writer {
+    fn push(&mut self, s: &str) {
+        if self.after_word && s.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            self.out.push(' ');
+        }
+        self.out.push_str(s);
+        self.after_word =
+            s.starts_with('\\') && s.len() > 1 && s[1..].bytes().all(|b| b.is_ascii_alphabetic());
+    }
+
+    fn cmd(&mut self, name: &str) {
+        self.push(&format!("\\{}", name));
+    }
+
+    fn group(&mut self, e: &Element) {
+        self.push("{");
+        self.element(e);
+        self.push("}");
+    }
+
+    fn group_all(&mut self, elems: &[Element]) {
+        self.push("{");
+        elems.iter().for_each(|e| self.element(e));
+        self.push("}");
+    }
+
+    /// Write a script, only using braces when needed.
+    fn script(&mut self, e: &Element) {
+        let simple = e.attributes().is_none()
+            && match e.elem() {
+                MathElement::Id { t, normal: false } | MathElement::Num(t) => {
+                    t.len() == 1 && t.chars().all(|c| c.is_ascii_alphanumeric())
+                }
+                _ => false,
+            };
+        if simple {
+            self.element(e)
+        } else {
+            self.group(e)
+        }
+    }
+
+    /// Write the base of a script, adding braces if it isn't a single token.
+    fn base(&mut self, e: &Element) {
+        let single = match e.elem() {
+            MathElement::Op(_) | MathElement::Oper(_) | MathElement::ResolvedOper(_) => true,
+            MathElement::Id { .. } => true,
+            MathElement::Num(t) => t.chars().count() == 1,
+            MathElement::Text(_) | MathElement::Str(_) | MathElement::Err(_) => true,
+            MathElement::Over { .. } | MathElement::Under { .. } => {
+                accent(e).is_some() && e.attributes().is_none()
+            }
+            _ => false,
+        };
+        if single {
+            self.element(e)
+        } else {
+            self.group(e)
+        }
+    }
+
+    fn element(&mut self, e: &Element) {
+        let a = e.attributes();
+        let variant = a.and_then(|a| a.variant);
+        let style = a.and_then(|a| match (&a.script_level, a.display_style) {
+            (Some(ScriptLevel::Set(1)), _) => Some("scriptstyle"),
+            (Some(ScriptLevel::Set(l)), _) if *l >= 2 => Some("scriptscriptstyle"),
+            (_, Some(true)) => Some("displaystyle"),
+            (_, Some(false)) => Some("textstyle"),
+            _ => None,
+        });
+        let text = matches!(e.elem(), MathElement::Text(_) | MathElement::Str(_));
+        let frac = matches!(e.elem(), MathElement::Frac { .. });
+        let style = style.filter(|s| !(frac && matches!(*s, "displaystyle" | "textstyle")));
+        if let Some(style) = style {
+            self.push("{");
+            self.cmd(style);
+        }
+        let fonts = match variant {
+            Some(v) if !text => font_cmds(v),
+            _ => &[],
+        };
+        for f in fonts.iter() {
+            self.cmd(f);
+            self.push("{");
+        }
+        self.inner(e, variant);
+        for _ in fonts.iter() {
+            self.push("}");
+        }
+        if style.is_some() {
+            self.push("}");
+        }
+    }
+
+    fn inner(&mut self, e: &Element, variant: Option<Variant>) {
+        match e.elem() {
+            MathElement::Op(c) => self.op(*c),
+            MathElement::Oper(op) => {
+                let big = match (&op.min_size, &op.max_size) {
+                    (Some(LengthOrFraction::Em(min)), Some(LengthOrFraction::Em(max)))
+                        if min == max =>
+                    {
+                        BIG.iter().find(|b| (b.1 - min).abs() < 1e-3).map(|b| b.0)
+                    }
+                    _ => None,
+                };
+                if let Some(big) = big {
+                    self.cmd(big);
+                }
+                self.op(op.t)
+            }
+            MathElement::ResolvedOper(op) => self.op(op.t),
+            MathElement::Text(t) => {
+                let cmd = match variant {
+                    Some(Variant::Bold) => "textbf",
+                    Some(Variant::Italic) => "textit",
+                    Some(Variant::SansSerif) => "textsf",
+                    Some(Variant::Monospace) => "texttt",
+                    _ => "text",
+                };
+                self.cmd(cmd);
+                self.push(&format!("{{{}}}", escape_text(t)));
+            }
+            MathElement::Str(t) => {
+                self.cmd("texttt");
+                self.push(&format!("{{\"{}\"}}", escape_text(t)));
+            }
+            MathElement::Err(t) | MathElement::Unknown { tag: t, .. } => {
+                self.cmd("text");
+                self.push(&format!("{{{}}}", escape_text(t)));
+            }
+            // A word inside a font command is written out, not as `\max`.
+            MathElement::Id { t, .. }
+                if t.chars().count() > 1 && variant.is_some_and(|v| !font_cmds(v).is_empty()) =>
+            {
+                self.push(&escape_math(t))
+            }
+            MathElement::Id { t, normal } => self.id(t, *normal),
+            MathElement::Num(t) => self.push(&escape_math(t)),
+            MathElement::Space(s) => match &s.width {
+                Some(Length::Em(w)) => match SPACES.iter().find(|s| (s.1 - w).abs() < 1e-4) {
+                    Some((name, _)) => self.cmd(name),
+                    None => {
+                        self.cmd("hspace");
+                        self.push(&format!("{{{}em}}", w));
+                    }
+                },
+                Some(Length::Ex(w)) => {
+                    self.cmd("hspace");
+                    self.push(&format!("{{{}ex}}", w));
+                }
+                None => self.push("{}"),
+            },
+            MathElement::Phantom(elems) => {
+                self.cmd("phantom");
+                self.group_all(elems);
+            }
+            MathElement::Row(elems) => self.row(elems),
+            MathElement::Padding(p) => self.group_all(&p.elems),
+            MathElement::Frac {
+                line_thickness,
+                num,
+                den,
+            } => {
+                let style = e.attributes().and_then(|a| a.display_style);
+                match line_thickness {
+                    None => {
+                        self.cmd(match style {
+                            Some(true) => "dfrac",
+                            Some(false) => "tfrac",
+                            None => "frac",
+                        });
+                    }
+                    Some(t) => {
+                        self.cmd("genfrac");
+                        let style = match style {
+                            Some(true) => "0",
+                            Some(false) => "1",
+                            None => "",
+                        };
+                        self.push(&format!("{{}}{{}}{{{}em}}{{{}}}", t * RULE, style));
+                    }
+                }
+                self.group(num);
+                self.group(den);
+            }
+            MathElement::Sqrt(base) => {
+                self.cmd("sqrt");
+                self.group(base);
+            }
+            MathElement::Root { base, index } => {
+                self.cmd("sqrt");
+                self.push("[");
+                self.element(index);
+                self.push("]");
+                self.group(base);
+            }
+            MathElement::Sup { base, sup } => {
+                self.base(base);
+                if let MathElement::Op('′') = sup.elem() {
+                    self.push("'");
+                } else {
+                    self.push("^");
+                    self.script(sup);
+                }
+            }
+            MathElement::Sub { base, sub } => {
+                self.base(base);
+                self.push("_");
+                self.script(sub);
+            }
+            MathElement::SubSup { base, sub, sup } => {
+                self.base(base);
+                self.push("_");
+                self.script(sub);
+                self.push("^");
+                self.script(sup);
+            }
+            MathElement::Over { base, over, .. } => {
+                if let Some(name) = accent(e) {
+                    self.cmd(name);
+                    self.group(base);
+                } else {
+                    self.limits(base, None, Some(over));
+                }
+            }
+            MathElement::Under { base, under, .. } => {
+                if let Some(name) = accent(e) {
+                    self.cmd(name);
+                    self.group(base);
+                } else {
+                    self.limits(base, Some(under), None);
+                }
+            }
+            MathElement::UnderOver {
+                base, under, over, ..
+            } => self.limits(base, Some(under), Some(over)),
+            MathElement::MultiScript { base, post, pre } => {
+                for p in pre.iter() {
+                    self.push("{}");
+                    self.pair(p);
+                }
+                self.base(base);
+                for (i, p) in post.iter().enumerate() {
+                    if i > 0 {
+                        self.push("{}");
+                    }
+                    self.pair(p);
+                }
+            }
+            MathElement::Table { rows } => self.table(rows, e.attributes(), None),
+        }
+    }
+
+    fn op(&mut self, c: char) {
+        match c {
+            '−' => self.push("-"),
+            '∗' => self.push("*"),
+            '{' => self.push("\\{"),
+            '}' => self.push("\\}"),
+            '#' | '$' | '%' | '&' | '_' => self.push(&format!("\\{}", c)),
+            '~' => self.cmd("sim"),
+            '^' => self.cmd("wedge"),
+            c if c.is_ascii() && c != '\\' => self.push(&c.to_string()),
+            c => match SYMBOLS
+                .iter()
+                .find(|s| s.1 == c && matches!(s.2, Kind::Op | Kind::Large(_)))
+            {
+                Some((name, ..)) => self.cmd(name),
+                None => self.push(&c.to_string()),
+            },
+        }
+    }
+
+    fn id(&mut self, t: &str, normal: bool) {
+        let mut chars = t.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => {
+                if normal && c.is_ascii_alphabetic() {
+                    self.cmd("mathrm");
+                    self.push(&format!("{{{}}}", c));
+                } else if c.is_ascii_alphanumeric() {
+                    self.push(t);
+                } else {
+                    match SYMBOLS
+                        .iter()
+                        .find(|s| s.1 == c && matches!(s.2, Kind::Id | Kind::IdNormal))
+                    {
+                        Some((name, ..)) => self.cmd(name),
+                        None => self.push(&escape_math(t)),
+                    }
+                }
+            }
+            (None, _) => self.push("{}"),
+            _ => match FUNCTIONS.iter().find(|f| f.0 == t) {
+                Some((name, _)) => self.cmd(name),
+                None => {
+                    self.cmd("operatorname");
+                    self.push(&format!("{{{}}}", escape_math(t)));
+                }
+            },
+        }
+    }
+
+    fn pair(&mut self, p: &Pair) {
+        if !is_empty(&p.sub) {
+            self.push("_");
+            self.script(&p.sub);
+        }
+        if !is_empty(&p.sup) {
+            self.push("^");
+            self.script(&p.sup);
+        }
+    }
+
+    /// Write a base element with limits above and below it.
+    fn limits(&mut self, base: &Element, under: Option<&Element>, over: Option<&Element>) {
+        match takes_limits(base) {
+            Some(default) => {
+                self.base(base);
+                if !default {
+                    self.cmd("limits");
+                }
+                if let Some(under) = under {
+                    self.push("_");
+                    self.script(under);
+                }
+                if let Some(over) = over {
+                    self.push("^");
+                    self.script(over);
+                }
+            }
+            None => {
+                if let Some(under) = under {
+                    self.cmd("underset");
+                    self.group(under);
+                    self.push("{");
+                }
+                if let Some(over) = over {
+                    self.cmd("overset");
+                    self.group(over);
+                    self.group(base);
+                } else {
+                    self.group(base);
+                }
+                if under.is_some() {
+                    self.push("}");
+                }
+            }
+        }
+    }
+
+    fn row(&mut self, elems: &[Element]) {
+        // Binomials
+        if let [open, f, close] = elems {
+            if let (
+                MathElement::Op('('),
+                MathElement::Frac {
+                    line_thickness: Some(t),
+                    num,
+                    den,
+                },
+                MathElement::Op(')'),
+            ) = (open.elem(), f.elem(), close.elem())
+            {
+                if *t == 0.0 && f.attributes().is_none() {
+                    self.cmd("binom");
+                    self.group(num);
+                    self.group(den);
+                    return;
+                }
+            }
+        }
+        // Fenced matrices
+        if let Some(i) = elems
+            .iter()
+            .position(|e| matches!(e.elem(), MathElement::Table { .. }))
+        {
+            let open = elems[..i].iter().map(fence).collect::<Option<Vec<_>>>();
+            let close = elems[i + 1..].iter().map(fence).collect::<Option<Vec<_>>>();
+            if let (Some(open), Some(close)) = (open, close) {
+                if open.len() <= 1 && close.len() <= 1 {
+                    let (open, close) = (open.first().copied(), close.first().copied());
+                    let env = MATRICES
+                        .iter()
+                        .take_while(|m| m.0 != "array")
+                        .find(|m| m.1 == open && m.2 == close && (m.1.is_some() || m.2.is_some()));
+                    if let (Some(env), MathElement::Table { rows }) = (env, elems[i].elem()) {
+                        if !rows.iter().any(|r| r.cells.iter().any(|c| c.col_span != 1)) {
+                            self.table(rows, elems[i].attributes(), Some(env.0));
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+        // Stretchy fences around the row
+        let open = elems.first().and_then(fence).filter(|c| is_open(*c));
+        let close = elems.last().and_then(fence).filter(|c| !is_open(*c));
+        let (open, close) = match (open, close) {
+            (Some(o), Some(c)) if elems.len() >= 2 => (Some(o), Some(c)),
+            (Some(o), None) if elems.len() >= 2 => (Some(o), None),
+            (None, Some(c)) if elems.len() >= 2 => (None, Some(c)),
+            _ => (None, None),
+        };
+        if open.is_none() && close.is_none() {
+            elems.iter().for_each(|e| self.element(e));
+            return;
+        }
+        let start = usize::from(open.is_some());
+        let end = elems.len() - usize::from(close.is_some());
+        self.cmd("left");
+        self.delim(open);
+        elems[start..end].iter().for_each(|e| self.element(e));
+        self.cmd("right");
+        self.delim(close);
+    }
+
+    fn delim(&mut self, c: Option<char>) {
+        match c {
+            None => self.push("."),
+            Some('⟨') => self.cmd("langle"),
+            Some('⟩') => self.cmd("rangle"),
+            Some('‖') => self.cmd("|"),
+            Some(c) => self.op(c),
+        }
+    }
+
+    fn table(&mut self, rows: &[TableRow], a: Option<&Attributes>, env: Option<&str>) {
+        let spans = rows.iter().any(|r| r.cells.iter().any(|c| c.col_span != 1));
+        let small = matches!(
+            a.and_then(|a| a.script_level.as_ref()),
+            Some(ScriptLevel::Add(1))
+        );
+        let env = match env {
+            Some(env) => env,
+            None if spans => "array",
+            None if small => "smallmatrix",
+            None => "matrix",
+        };
+        self.push(&format!("\\begin{{{}}}", env));
+        if env == "array" {
+            let cols = rows
+                .iter()
+                .map(|r| r.cells.iter().map(|c| c.col_span.max(1) as usize).sum())
+                .max()
+                .unwrap_or(1);
+            self.push(&format!("{{{}}}", "c".repeat(cols)));
+        }
+        for (i, row) in rows.iter().enumerate() {
+            if i > 0 {
+                self.push(" \\\\ ");
+            }
+            for (j, cell) in row.cells.iter().enumerate() {
+                if j > 0 {
+                    self.push(" & ");
+                }
+                if cell.col_span != 1 {
+                    self.push(&format!("\\multicolumn{{{}}}{{c}}", cell.col_span));
+                    self.group_all(&cell.elems);
+                } else {
+                    cell.elems.iter().for_each(|e| self.element(e));
+                }
+            }
+        }
+        self.push(&format!("\\end{{{}}}", env));
+    }
+}
+
+/// Get the command for an accent-like overscript or underscript.
+fn accent(e: &Element) -> Option<&'static str> {
+    let (mark, accent, under) = match e.elem() {
+        MathElement::Over { over, accent, .. } => (over, *accent, false),
+        MathElement::Under {
+            under,
+            accent_under,
+            ..
+        } => (under, *accent_under, true),
+        _ => return None,
+    };
+    let c = match (mark.elem(), mark.attributes()) {
+        (MathElement::Op(c), None) => *c,
+        _ => return None,
+    };
+    ACCENTS
+        .iter()
+        .find(|a| a.1 == c && a.2 == accent && a.3 == under)
+        .or_else(|| ACCENTS.iter().find(|a| a.1 == c && a.3 == under))
+        .map(|a| a.0)
+}
+
+/// Check if scripts on an element can be written as limits, and if so, whether
+/// limits are the default for it.
+fn takes_limits(e: &Element) -> Option<bool> {
+    match e.elem() {
+        MathElement::Op(c) => SYMBOLS.iter().find_map(|s| match s.2 {
+            Kind::Large(limits) if s.1 == *c => Some(limits),
+            _ => None,
+        }),
+        MathElement::Id { t, .. } => FUNCTIONS.iter().find(|f| f.0 == t).map(|f| f.1),
+        MathElement::Over { over, .. } => {
+            (matches!(over.elem(), MathElement::Op('⏞')) && accent(e).is_some()).then_some(true)
+        }
+        MathElement::Under { under, .. } => {
+            (matches!(under.elem(), MathElement::Op('⏟')) && accent(e).is_some()).then_some(true)
+        }
+        _ => None,
+    }
+}
+
+/// Get the character of a stretchy fence operator.
+fn fence(e: &Element) -> Option<char> {
+    let c = match (e.elem(), e.attributes()) {
+        (MathElement::Op(c), None) => *c,
+        _ => return None,
+    };
+    is_fence(c).then_some(c)
+}
+
+fn is_fence(c: char) -> bool {
+    matches!(
+        c,
+        '(' | ')' | '[' | ']' | '{' | '}' | '|' | '‖' | '⟨' | '⟩' | '⌊' | '⌋' | '⌈' | '⌉'
+    )
+}
+
+fn is_open(c: char) -> bool {
+    matches!(c, '(' | '[' | '{' | '⟨' | '⌊' | '⌈' | '|' | '‖')
+}
+
+fn is_empty(e: &Element) -> bool {
+    matches!(e.elem(), MathElement::Row(r) if r.is_empty())
+}
+
+/// Font commands that reproduce a variant, outermost first.
+fn font_cmds(v: Variant) -> &'static [&'static str] {
+    match v {
+        Variant::Normal => &["mathrm"],
+        Variant::Bold => &["mathbf"],
+        Variant::Italic => &["mathit"],
+        Variant::BoldItalic => &["boldsymbol"],
+        Variant::DoubleStruck => &["mathbb"],
+        Variant::BoldFraktur => &["boldsymbol", "mathfrak"],
+        Variant::Script => &["mathcal"],
+        Variant::BoldScript => &["boldsymbol", "mathcal"],
+        Variant::Fraktur => &["mathfrak"],
+        Variant::SansSerif => &["mathsf"],
+        Variant::BoldSansSerif => &["mathbf", "mathsf"],
+        Variant::SansSerifItalic => &["mathsf"],
+        Variant::SansSerifBoldItalic => &["boldsymbol", "mathsf"],
+        Variant::Monospace => &["mathtt"],
+        Variant::Initial | Variant::Tailed | Variant::Looped | Variant::Stretched => &[],
+    }
+}
+
+/// Escape TeX special characters in math mode.
+fn escape_math(t: &str) -> String {
+    let mut out = String::with_capacity(t.len());
+    for c in t.chars() {
+        match c {
+            '#' | '$' | '%' | '&' | '_' | '{' | '}' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\\' => out.push_str("\\backslash "),
+            '^' => out.push_str("\\wedge "),
+            '~' => out.push_str("\\sim "),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Escape TeX special characters in text mode.
+fn escape_text(t: &str) -> String {
+    let mut out = String::with_capacity(t.len());
+    for c in t.chars() {
+        match c {
+            '#' | '$' | '%' | '&' | '_' | '{' | '}' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\\' => out.push_str("\\textbackslash{}"),
+            '^' => out.push_str("\\textasciicircum{}"),
+            '~' => out.push_str("\\textasciitilde{}"),
+            '\u{a0}' => out.push('~'),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens() {
+        assert_eq!(
+            parse("x + 3.14 - \\alpha"),
+            Element::row([
+                Element::id("x"),
+                Element::op('+'),
+                Element::num("3.14"),
+                Element::op('−'),
+                Element::id("α"),
+            ])
+        );
+        assert_eq!(
+            parse("\\sin(\\Omega)"),
+            Element::row([
+                Element::id("sin"),
+                fixed('('),
+                Element::id_normal("Ω"),
+                fixed(')'),
+            ])
+        );
+    }
+
+    #[test]
+    fn fractions_and_roots() {
+        assert_eq!(
+            parse("\\frac{a}{b}"),
+            Element::frac(Element::id("a"), Element::id("b"))
+        );
+        assert_eq!(
+            parse("\\frac12"),
+            Element::frac(Element::num("1"), Element::num("2"))
+        );
+        assert_eq!(
+            parse("\\sqrt[3]{x+1}"),
+            Element::root(
+                Element::row([Element::id("x"), Element::op('+'), Element::num("1")]),
+                Element::num("3")
+            )
+        );
+        assert_eq!(parse("\\sqrt x"), Element::sqrt(Element::id("x")));
+    }
+
+    #[test]
+    fn scripts() {
+        assert_eq!(
+            parse("x_i^2"),
+            Element::sub_sup(Element::id("x"), Element::id("i"), Element::num("2"))
+        );
+        assert_eq!(
+            parse("f'"),
+            Element::sup(Element::id("f"), Element::op('′'))
+        );
+        assert_eq!(
+            parse("\\sum_{i=1}^n"),
+            Element::under_over(
+                Element::op('∑'),
+                Element::row([Element::id("i"), Element::op('='), Element::num("1")]),
+                Element::id("n")
+            )
+        );
+        assert_eq!(
+            parse("\\int\\limits_0^1"),
+            Element::under_over(Element::op('∫'), Element::num("0"), Element::num("1"))
+        );
+        assert_eq!(
+            parse("\\underbrace{a}_{n}"),
+            Element::under(
+                Element::under(Element::id("a"), Element::op('⏟')),
+                Element::id("n")
+            )
+        );
+    }
+
+    #[test]
+    fn fences() {
+        assert_eq!(
+            parse("\\left( x \\right."),
+            Element::row([Element::op('('), Element::id("x")])
+        );
+        assert_eq!(
+            parse("\\left\\langle x \\middle| y \\right\\rangle"),
+            Element::row([
+                Element::op('⟨'),
+                Element::id("x"),
+                Element::op('|'),
+                Element::id("y"),
+                Element::op('⟩'),
+            ])
+        );
+    }
+
+    #[test]
+    fn fonts() {
+        assert_eq!(
+            parse("\\mathbb{R}"),
+            Element::id("R").variant(Variant::DoubleStruck)
+        );
+        assert_eq!(parse("\\mathrm{d}"), Element::id_normal("d"));
+        assert_eq!(
+            parse("\\mathrm{max}"),
+            Element::id("max").variant(Variant::Normal)
+        );
+        assert_eq!(
+            parse("\\mathbf{AB}"),
+            Element::id("AB").variant(Variant::Bold)
+        );
+        assert_eq!(
+            parse("\\mathcal{AB}"),
+            Element::row([Element::id("A"), Element::id("B")]).variant(Variant::Script)
+        );
+        assert_eq!(
+            parse("\\text{if } x"),
+            Element::row([Element::text("if "), Element::id("x")])
+        );
+    }
+
+    #[test]
+    fn accents() {
+        assert_eq!(
+            parse("\\hat{x}"),
+            Element::over_accent(Element::id("x"), Element::op('^'))
+        );
+        assert_eq!(
+            parse("\\overline{z}"),
+            Element::over(Element::id("z"), Element::op('‾'))
+        );
+    }
+
+    #[test]
+    fn spacing() {
+        assert_eq!(
+            parse("a\\quad b\\,c"),
+            Element::row([
+                Element::id("a"),
+                Element::space(Space::width(Length::Em(1.0))),
+                Element::id("b"),
+                Element::space(Space::width(Length::Em(3.0 / 18.0))),
+                Element::id("c"),
+            ])
+        );
+    }
+
+    #[test]
+    fn environments() {
+        assert_eq!(
+            parse("\\begin{pmatrix} a & b \\\\ c & d \\end{pmatrix}"),
+            Element::row([
+                Element::op('('),
+                Element::matrix([
+                    [Element::id("a"), Element::id("b")],
+                    [Element::id("c"), Element::id("d")],
+                ]),
+                Element::op(')'),
+            ])
+        );
+        assert_eq!(
+            parse("\\begin{cases} 1 & x > 0 \\\\ 0 & \\text{otherwise} \\\\ \\end{cases}"),
+            Element::row([
+                Element::op('{'),
+                Element::table([
+                    TableRow::new([
+                        TableCell::new([Element::num("1")]),
+                        TableCell::new([Element::id("x"), Element::op('>'), Element::num("0")]),
+                    ]),
+                    TableRow::new([
+                        TableCell::new([Element::num("0")]),
+                        TableCell::new([Element::text("otherwise")]),
+                    ]),
+                ]),
+            ])
+        );
+        assert_eq!(
+            parse("\\begin{array}{cc} \\multicolumn{2}{c}{x} \\end{array}"),
+            Element::table([TableRow::new([
+                TableCell::new([Element::id("x")]).col_span(2)
+            ])])
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            parse("a \\foo b"),
+            Element::row([Element::id("a"), Element::err("\\foo"), Element::id("b")])
+        );
+        assert_eq!(
+            parse("a } b"),
+            Element::row([Element::id("a"), Element::err("}"), Element::id("b")])
+        );
+        assert_eq!(
+            parse("\\left( a"),
+            Element::row([
+                Element::op('('),
+                Element::id("a"),
+                Element::err("\\left without \\right")
+            ])
+        );
+    }
+
+    #[test]
+    fn display_delimiters() {
+        assert_eq!(parse("$$x$$"), Element::id("x").display_style(true));
+        assert_eq!(parse("\\(x\\)"), Element::id("x"));
+    }
+
+    fn roundtrip(src: &str) {
+        let e = parse(src);
+        let out = e.to_latex();
+        assert_eq!(parse(&out), e, "{} => {}", src, out);
+    }
+
+    #[test]
+    fn write() {
+        let e = Element::row([
+            Element::sup(Element::num("10"), Element::id("x")),
+            Element::op('≤'),
+            Element::frac(
+                Element::id("α"),
+                Element::id("b").variant(Variant::DoubleStruck),
+            ),
+        ]);
+        assert_eq!(e.to_latex(), "{10}^x\\leq\\frac{\\alpha}{\\mathbb{b}}");
+        assert_eq!(
+            Element::text("50% & {x}").to_latex(),
+            "\\text{50\\% \\& \\{x\\}}"
+        );
+        assert_eq!(
+            Element::row([Element::id("sin"), Element::id("x")]).to_latex(),
+            "\\sin x"
+        );
+    }
+
+    #[test]
+    fn write_scripts() {
+        let e = Element::multiscript(
+            Element::id("X"),
+            [Pair::new(Element::id("c"), Element::id("d"))],
+            [Pair::new(Element::id("b"), Element::id("a"))],
+        );
+        assert_eq!(e.to_latex(), "{}_b^aX_c^d");
+        let e = Element::under_over(Element::op('∫'), Element::num("0"), Element::num("1"));
+        assert_eq!(e.to_latex(), "\\int\\limits_0^1");
+        let e = Element::under_over(Element::id("x"), Element::num("0"), Element::num("1"));
+        assert_eq!(e.to_latex(), "\\underset{0}{\\overset{1}{x}}");
+    }
+
+    #[test]
+    fn write_tables() {
+        let e = Element::table([
+            TableRow::new([TableCell::new([Element::num("1")]).col_span(2)]),
+            TableRow::new([
+                TableCell::new([Element::num("2")]),
+                TableCell::new([Element::num("3")]),
+            ]),
+        ]);
+        assert_eq!(
+            e.to_latex(),
+            "\\begin{array}{cc}\\multicolumn{2}{c}{1} \\\\ 2 & 3\\end{array}"
+        );
+    }
+
+    #[test]
+    fn delimiters() {
+        assert_eq!(
+            parse("\\{a\\}"),
+            Element::row([fixed('{'), Element::id("a"), fixed('}')])
+        );
+        for src in [
+            "(a]",
+            "\\{a\\}",
+            "\\langle a\\rangle",
+            "\\lfloor x\\rfloor",
+            "\\Vert v\\Vert",
+        ] {
+            assert_eq!(parse(src).to_latex(), src);
+        }
+        assert_eq!(parse("\\mathrm{max}").to_latex(), "\\mathrm{max}");
+        assert_eq!(parse("\\mathbf{max}").to_latex(), "\\mathbf{max}");
+    }
+
+    #[test]
+    fn latex_roundtrip() {
+        roundtrip("x_i^2 + \\frac{a}{b} - \\sqrt[3]{y}");
+        roundtrip("\\sum_{i=1}^{n} i^2 = \\frac{n(n+1)(2n+1)}{6}");
+        roundtrip("\\int_0^\\infty e^{-x^2}\\,dx");
+        roundtrip("\\left( \\frac{1}{2} \\right]");
+        roundtrip("\\left\\{ x \\right.");
+        roundtrip("\\mathbf{v} \\cdot \\mathbb{R}^n \\mathrm{d}x");
+        roundtrip("\\hat{x} + \\overline{y} + \\underbrace{a+b}_{n}");
+        roundtrip("\\begin{pmatrix} a & b \\\\ c & d \\end{pmatrix}");
+        roundtrip("\\begin{cases} 1 & x > 0 \\\\ 0 & \\text{else} \\end{cases}");
+        roundtrip("\\binom{n}{k} \\quad \\dfrac{1}{x} \\quad f''(x)");
+        roundtrip("\\text{a\\_b \\textbackslash{} c}");
+        roundtrip("\\genfrac{}{}{0.8pt}{}{a}{b} \\Bigl( \\lim_{x \\to 0} \\Bigr)");
+        roundtrip("\\operatorname{sgn} x");
+        roundtrip("\\{a\\} \\langle b\\rangle \\mathrm{max}");
+    }
+}
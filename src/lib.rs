@@ -1,2 +1,31 @@
+extern crate self as fog_math;
+
 pub mod schema;
 pub mod math;
+pub mod latex;
+pub mod asciimath;
+pub mod unicodemath;
+pub mod mathml;
+pub mod omml;
+pub mod typst;
+pub mod starmath;
+pub mod eqn;
+pub mod content;
+pub mod openmath;
+pub mod mathjson;
+pub mod mtef;
+pub mod wolfram;
+pub mod pretty;
+pub mod plaintext;
+pub mod operator;
+pub mod migrate;
+mod document;
+mod error;
+mod json;
+mod tree;
+mod xml;
+
+pub use document::{decode_document, decode_document_lenient, encode_document};
+pub use error::Error;
+pub use json::JsonError;
+pub use xml::XmlError;
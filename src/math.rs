@@ -1,5 +1,12 @@
-use std::collections::BTreeMap;
-use serde::{Deserialize, Serialize};
+use fog_pack::types::Value;
+use serde::{
+    de::{self, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, VariantAccess, Visitor},
+    ser::SerializeMap,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::{collections::BTreeMap, fmt};
+
+use crate::schema::SchemaType;
 
 #[inline]
 fn is_false(b: &bool) -> bool {
@@ -18,7 +25,7 @@ fn u32_one() -> u32 {
 
 /// Character Variant types. In general, prefer using Normal and including the
 /// actual Unicode character.
-#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, SchemaType)]
 pub enum Variant {
     #[default]
     Normal,
@@ -43,7 +50,7 @@ pub enum Variant {
 
 /// Adjust the script level of an element, either by setting it to a specific
 /// value or changing the value by some amount.
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, SchemaType)]
 pub enum ScriptLevel {
     /// Increment/decrement the script level.
     Add(i32),
@@ -52,18 +59,24 @@ pub enum ScriptLevel {
 }
 
 /// A Math element, including any global attributes.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, SchemaType)]
 pub struct Element {
     /// The actual element.
     e: MathElement,
     /// Optional attributes for the element.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     a: Option<Box<Attributes>>,
 }
 
 /// A Math element. Mirrors the elements in MathML.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+///
+/// Elements this version doesn't recognize are read as
+/// [`Unknown`](MathElement::Unknown) instead of failing the whole document.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, SchemaType)]
+#[serde(remote = "Self")]
 pub enum MathElement {
-    /// A single-character operator with default properties.
+    /// A single-character operator with default properties, as given by the
+    /// [operator dictionary](crate::operator).
     Op(char),
     /// Full operator. Some additional properties may have been overridden.
     Oper(Operator),
@@ -162,13 +175,184 @@ pub enum MathElement {
         pre: Vec<Pair>,
     },
     /// A table
-    Table {
-        rows: Vec<TableRow>,
+    Table { rows: Vec<TableRow> },
+    /// An element from a newer version of the schema. It's kept exactly as
+    /// it was read, so it survives decoding and re-encoding, and renderers
+    /// show it as an error.
+    #[serde(skip)]
+    Unknown {
+        tag: String,
+        /// The element's content, or `None` if it had none.
+        value: Option<Box<Value>>,
     },
 }
 
+impl Serialize for MathElement {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            MathElement::Unknown { tag, value: None } => serializer.serialize_str(tag),
+            MathElement::Unknown {
+                tag,
+                value: Some(value),
+            } => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(tag, value)?;
+                map.end()
+            }
+            known => MathElement::serialize(known, serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for MathElement {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Going through the data model directly, rather than as an enum,
+        // lets an unknown element be read whatever shape its content has.
+        deserializer.deserialize_any(ElementVisitor)
+    }
+}
+
+/// The tags [`MathElement`] knows how to read.
+const VARIANTS: &[&str] = &[
+    "Op",
+    "Oper",
+    "ResolvedOper",
+    "Text",
+    "Id",
+    "Num",
+    "Err",
+    "Space",
+    "Str",
+    "Phantom",
+    "Row",
+    "Padding",
+    "Frac",
+    "Sqrt",
+    "Root",
+    "Sup",
+    "Sub",
+    "SubSup",
+    "Over",
+    "Under",
+    "UnderOver",
+    "MultiScript",
+    "Table",
+];
+
+struct ElementVisitor;
+
+impl<'de> Visitor<'de> for ElementVisitor {
+    type Value = MathElement;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a math element")
+    }
+
+    /// An element without content, which is only ever an unknown one.
+    fn visit_str<E: de::Error>(self, tag: &str) -> Result<Self::Value, E> {
+        if VARIANTS.contains(&tag) {
+            return MathElement::deserialize(tag.into_deserializer());
+        }
+        Ok(MathElement::Unknown {
+            tag: tag.to_string(),
+            value: None,
+        })
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let tag: String = map
+            .next_key()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let e = if VARIANTS.contains(&tag.as_str()) {
+            map.next_value_seed(KnownElement(tag))?
+        } else {
+            MathElement::Unknown {
+                tag,
+                value: Some(map.next_value()?),
+            }
+        };
+        if map.next_key::<de::IgnoredAny>()?.is_some() {
+            return Err(de::Error::invalid_length(2, &self));
+        }
+        Ok(e)
+    }
+}
+
+/// Reads the payload of a recognized element, by handing the derived
+/// deserializer an enum with only that one variant in it.
+struct KnownElement(String);
+
+impl<'de> DeserializeSeed<'de> for KnownElement {
+    type Value = MathElement;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        MathElement::deserialize(OneVariant {
+            tag: self.0,
+            payload: deserializer,
+        })
+    }
+}
+
+struct OneVariant<D> {
+    tag: String,
+    payload: D,
+}
+
+impl<'de, D: Deserializer<'de>> Deserializer<'de> for OneVariant<D> {
+    type Error = D::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+        visitor.visit_enum(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'de, D: Deserializer<'de>> EnumAccess<'de> for OneVariant<D> {
+    type Error = D::Error;
+    type Variant = Payload<D>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Payload<D>), D::Error> {
+        let tag = seed.deserialize(self.tag.into_deserializer())?;
+        Ok((tag, Payload(self.payload)))
+    }
+}
+
+struct Payload<D>(D);
+
+impl<'de, D: Deserializer<'de>> VariantAccess<'de> for Payload<D> {
+    type Error = D::Error;
+
+    fn unit_variant(self) -> Result<(), D::Error> {
+        Deserialize::deserialize(self.0)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, D::Error> {
+        seed.deserialize(self.0)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, D::Error> {
+        self.0.deserialize_tuple(len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, D::Error> {
+        self.0.deserialize_struct("", fields, visitor)
+    }
+}
+
 /// A row in a table.
-#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize, SchemaType)]
 #[serde(default)]
 pub struct TableRow {
     #[serde(skip_serializing_if = "Vec::is_empty")]
@@ -178,7 +362,7 @@ pub struct TableRow {
 }
 
 /// A cell in a table.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, SchemaType)]
 pub struct TableCell {
     #[serde(default = "u32_one", skip_serializing_if = "u32_is_one")]
     pub col_span: u32,
@@ -191,13 +375,13 @@ pub struct TableCell {
 }
 
 /// A pair of superscript and subscript, used by the Multiscript element.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, SchemaType)]
 pub struct Pair {
     pub sup: Box<Element>,
     pub sub: Box<Element>,
 }
 
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, SchemaType)]
 #[serde(default)]
 pub struct Padding {
     #[serde(skip_serializing_if = "Vec::is_empty")]
@@ -214,7 +398,7 @@ pub struct Padding {
     pub voffset: Option<Length>,
 }
 
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, SchemaType)]
 #[serde(default)]
 pub struct Space {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -225,15 +409,16 @@ pub struct Space {
     pub depth: Option<Length>,
 }
 
-/// Form of the operation. Normally derived from the operator's base character.
-#[derive(Clone, Debug, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// Form of the operation. Normally inferred from the operator's position in
+/// its row; see [`crate::operator::infer_forms`].
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Serialize, Deserialize, SchemaType)]
 pub enum OpForm {
     Prefix,
     Postfix,
     Infix,
 }
 
-#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize, SchemaType)]
 #[serde(default)]
 pub struct Operator {
     /// The operator's text, which should be a single character.
@@ -242,7 +427,7 @@ pub struct Operator {
     /// plain [`MathElement::Text`] instead, dropping any other
     /// operator-specific attributes in the process.
     pub t: char,
-    /// Operator form.
+    /// Operator form. Overrides the form inferred from position.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub form: Option<OpForm>,
     /// Maximum size
@@ -278,11 +463,13 @@ pub struct Operator {
 }
 
 /// An operator whose properties have been completely resolved.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, SchemaType)]
 pub struct ResolvedOperator {
     pub t: char,
     pub form: OpForm,
-    pub max_size: Length,
+    /// Maximum size, or `None` if the operator can stretch without limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<Length>,
     pub min_size: Length,
     pub lspace: Length,
     pub rspace: Length,
@@ -295,7 +482,7 @@ pub struct ResolvedOperator {
 }
 
 /// A font-relative length or a specified fraction of another length.
-#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, SchemaType)]
 pub enum LengthOrFraction {
     /// Font-relative unit, usually used for widths
     Em(f32),
@@ -307,7 +494,7 @@ pub enum LengthOrFraction {
 }
 
 /// A font-relative length.
-#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, SchemaType)]
 pub enum Length {
     /// Font-relative unit, usually used for widths
     Em(f32),
@@ -317,7 +504,7 @@ pub enum Length {
 
 /// Global Element attributes. Mostly contains styling information, but also
 /// includes the option to contain arbitrary additional data.
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, SchemaType)]
 #[serde(default)]
 pub struct Attributes {
     #[serde(skip_serializing_if = "Vec::is_empty")]
@@ -332,13 +519,435 @@ pub struct Attributes {
     pub script_level: Option<ScriptLevel>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub data: Option<BTreeMap<String, fog_pack::types::Value>>,
+    /// Attributes from a newer version of the schema, kept so they survive
+    /// decoding and re-encoding.
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, Value>,
+}
+
+impl Element {
+    /// Create a new element with no attributes.
+    pub fn new(e: MathElement) -> Self {
+        Self { e, a: None }
+    }
+
+    /// Create a new element with the given attributes.
+    pub fn with_attributes(e: MathElement, a: Attributes) -> Self {
+        Self {
+            e,
+            a: Some(Box::new(a)),
+        }
+    }
+
+    /// Get the inner math element.
+    pub fn elem(&self) -> &MathElement {
+        &self.e
+    }
+
+    /// Get a mutable reference to the inner math element.
+    pub fn elem_mut(&mut self) -> &mut MathElement {
+        &mut self.e
+    }
+
+    /// Replace the inner math element, returning the old one.
+    pub fn set_elem(&mut self, e: MathElement) -> MathElement {
+        std::mem::replace(&mut self.e, e)
+    }
+
+    /// Get the element's attributes, if any have been set.
+    pub fn attributes(&self) -> Option<&Attributes> {
+        self.a.as_deref()
+    }
+
+    /// Get a mutable reference to the element's attributes, creating a default
+    /// set of attributes if there were none.
+    pub fn attributes_mut(&mut self) -> &mut Attributes {
+        self.a.get_or_insert_with(Box::default)
+    }
+
+    /// Replace the element's attributes, returning the old ones.
+    pub fn set_attributes(&mut self, a: Option<Attributes>) -> Option<Attributes> {
+        std::mem::replace(&mut self.a, a.map(Box::new)).map(|a| *a)
+    }
+
+    /// Split the element into its inner math element and attributes.
+    pub fn into_parts(self) -> (MathElement, Option<Attributes>) {
+        (self.e, self.a.map(|a| *a))
+    }
+
+    /// Set the character variant.
+    pub fn variant(mut self, variant: Variant) -> Self {
+        self.attributes_mut().variant = Some(variant);
+        self
+    }
+
+    /// Add a class to the element.
+    pub fn class(mut self, class: impl Into<String>) -> Self {
+        self.attributes_mut().class.push(class.into());
+        self
+    }
+
+    /// Set right-to-left directionality.
+    pub fn rtl(mut self, rtl: bool) -> Self {
+        self.attributes_mut().rtl = rtl;
+        self
+    }
+
+    /// Set the display style.
+    pub fn display_style(mut self, display_style: bool) -> Self {
+        self.attributes_mut().display_style = Some(display_style);
+        self
+    }
+
+    /// Set the script level adjustment.
+    pub fn script_level(mut self, script_level: ScriptLevel) -> Self {
+        self.attributes_mut().script_level = Some(script_level);
+        self
+    }
+
+    /// Attach an arbitrary data entry to the element.
+    pub fn data(mut self, key: impl Into<String>, value: fog_pack::types::Value) -> Self {
+        self.attributes_mut()
+            .data
+            .get_or_insert_with(BTreeMap::new)
+            .insert(key.into(), value);
+        self
+    }
+
+    /// A single-character operator with default properties.
+    pub fn op(t: char) -> Self {
+        Self::new(MathElement::Op(t))
+    }
+
+    /// An operator with some properties overridden.
+    pub fn oper(op: Operator) -> Self {
+        Self::new(MathElement::Oper(op))
+    }
+
+    /// An operator with all properties resolved.
+    pub fn resolved_oper(op: ResolvedOperator) -> Self {
+        Self::new(MathElement::ResolvedOper(op))
+    }
+
+    /// Raw text.
+    pub fn text(t: impl Into<String>) -> Self {
+        Self::new(MathElement::Text(t.into()))
+    }
+
+    /// An identifier, using the default italics rules.
+    pub fn id(t: impl Into<String>) -> Self {
+        Self::new(MathElement::Id {
+            t: t.into(),
+            normal: false,
+        })
+    }
+
+    /// An identifier that is never italicized by default.
+    pub fn id_normal(t: impl Into<String>) -> Self {
+        Self::new(MathElement::Id {
+            t: t.into(),
+            normal: true,
+        })
+    }
+
+    /// A numeric value.
+    pub fn num(t: impl Into<String>) -> Self {
+        Self::new(MathElement::Num(t.into()))
+    }
+
+    /// An error message.
+    pub fn err(t: impl Into<String>) -> Self {
+        Self::new(MathElement::Err(t.into()))
+    }
+
+    /// A blank space.
+    pub fn space(space: Space) -> Self {
+        Self::new(MathElement::Space(space))
+    }
+
+    /// A string literal.
+    pub fn str(t: impl Into<String>) -> Self {
+        Self::new(MathElement::Str(t.into()))
+    }
+
+    /// Invisible elements that still affect layout.
+    pub fn phantom(elems: impl IntoIterator<Item = Element>) -> Self {
+        Self::new(MathElement::Phantom(elems.into_iter().collect()))
+    }
+
+    /// A row of elements.
+    pub fn row(elems: impl IntoIterator<Item = Element>) -> Self {
+        Self::new(MathElement::Row(elems.into_iter().collect()))
+    }
+
+    /// Padding around elements.
+    pub fn padding(padding: Padding) -> Self {
+        Self::new(MathElement::Padding(padding))
+    }
+
+    /// A fraction with the standard line thickness.
+    pub fn frac(num: impl Into<Element>, den: impl Into<Element>) -> Self {
+        Self::new(MathElement::Frac {
+            line_thickness: None,
+            num: Box::new(num.into()),
+            den: Box::new(den.into()),
+        })
+    }
+
+    /// A fraction with a line thickness, as a fraction of the standard
+    /// thickness. A thickness of 0 is commonly used for binomials.
+    pub fn frac_thickness(
+        num: impl Into<Element>,
+        den: impl Into<Element>,
+        line_thickness: f32,
+    ) -> Self {
+        Self::new(MathElement::Frac {
+            line_thickness: Some(line_thickness),
+            num: Box::new(num.into()),
+            den: Box::new(den.into()),
+        })
+    }
+
+    /// A square root.
+    pub fn sqrt(base: impl Into<Element>) -> Self {
+        Self::new(MathElement::Sqrt(Box::new(base.into())))
+    }
+
+    /// A root with an explicit index.
+    pub fn root(base: impl Into<Element>, index: impl Into<Element>) -> Self {
+        Self::new(MathElement::Root {
+            base: Box::new(base.into()),
+            index: Box::new(index.into()),
+        })
+    }
+
+    /// A superscript.
+    pub fn sup(base: impl Into<Element>, sup: impl Into<Element>) -> Self {
+        Self::new(MathElement::Sup {
+            base: Box::new(base.into()),
+            sup: Box::new(sup.into()),
+        })
+    }
+
+    /// A subscript.
+    pub fn sub(base: impl Into<Element>, sub: impl Into<Element>) -> Self {
+        Self::new(MathElement::Sub {
+            base: Box::new(base.into()),
+            sub: Box::new(sub.into()),
+        })
+    }
+
+    /// Both a subscript and a superscript.
+    pub fn sub_sup(
+        base: impl Into<Element>,
+        sub: impl Into<Element>,
+        sup: impl Into<Element>,
+    ) -> Self {
+        Self::new(MathElement::SubSup {
+            base: Box::new(base.into()),
+            sub: Box::new(sub.into()),
+            sup: Box::new(sup.into()),
+        })
+    }
+
+    /// An overscript.
+    pub fn over(base: impl Into<Element>, over: impl Into<Element>) -> Self {
+        Self::new(MathElement::Over {
+            base: Box::new(base.into()),
+            over: Box::new(over.into()),
+            accent: false,
+        })
+    }
+
+    /// An overscript, treated as an accent.
+    pub fn over_accent(base: impl Into<Element>, over: impl Into<Element>) -> Self {
+        Self::new(MathElement::Over {
+            base: Box::new(base.into()),
+            over: Box::new(over.into()),
+            accent: true,
+        })
+    }
+
+    /// An underscript.
+    pub fn under(base: impl Into<Element>, under: impl Into<Element>) -> Self {
+        Self::new(MathElement::Under {
+            base: Box::new(base.into()),
+            under: Box::new(under.into()),
+            accent_under: false,
+        })
+    }
+
+    /// An underscript, treated as an accent.
+    pub fn under_accent(base: impl Into<Element>, under: impl Into<Element>) -> Self {
+        Self::new(MathElement::Under {
+            base: Box::new(base.into()),
+            under: Box::new(under.into()),
+            accent_under: true,
+        })
+    }
+
+    /// Both an underscript and an overscript.
+    pub fn under_over(
+        base: impl Into<Element>,
+        under: impl Into<Element>,
+        over: impl Into<Element>,
+    ) -> Self {
+        Self::new(MathElement::UnderOver {
+            base: Box::new(base.into()),
+            under: Box::new(under.into()),
+            over: Box::new(over.into()),
+            accent: false,
+            accent_under: false,
+        })
+    }
+
+    /// Both an underscript and an overscript, with each one optionally
+    /// treated as an accent.
+    pub fn under_over_accent(
+        base: impl Into<Element>,
+        under: impl Into<Element>,
+        over: impl Into<Element>,
+        accent_under: bool,
+        accent: bool,
+    ) -> Self {
+        Self::new(MathElement::UnderOver {
+            base: Box::new(base.into()),
+            under: Box::new(under.into()),
+            over: Box::new(over.into()),
+            accent,
+            accent_under,
+        })
+    }
+
+    /// Scripts attached both after (`post`) and before (`pre`) a base element.
+    pub fn multiscript(
+        base: impl Into<Element>,
+        post: impl IntoIterator<Item = Pair>,
+        pre: impl IntoIterator<Item = Pair>,
+    ) -> Self {
+        Self::new(MathElement::MultiScript {
+            base: Box::new(base.into()),
+            post: post.into_iter().collect(),
+            pre: pre.into_iter().collect(),
+        })
+    }
+
+    /// A table.
+    pub fn table(rows: impl IntoIterator<Item = TableRow>) -> Self {
+        Self::new(MathElement::Table {
+            rows: rows.into_iter().collect(),
+        })
+    }
+
+    /// A table where every cell holds a single element and spans one row and
+    /// column.
+    pub fn matrix<R>(rows: impl IntoIterator<Item = R>) -> Self
+    where
+        R: IntoIterator<Item = Element>,
+    {
+        Self::table(
+            rows.into_iter()
+                .map(|r| TableRow::new(r.into_iter().map(TableCell::from))),
+        )
+    }
+}
+
+impl From<MathElement> for Element {
+    fn from(e: MathElement) -> Self {
+        Self::new(e)
+    }
+}
+
+impl TableRow {
+    /// Create a row from a set of cells.
+    pub fn new(cells: impl IntoIterator<Item = TableCell>) -> Self {
+        Self {
+            cells: cells.into_iter().collect(),
+            a: None,
+        }
+    }
+}
+
+impl TableCell {
+    /// Create a cell spanning one row and one column.
+    pub fn new(elems: impl IntoIterator<Item = Element>) -> Self {
+        Self {
+            col_span: 1,
+            row_span: 1,
+            elems: elems.into_iter().collect(),
+            a: None,
+        }
+    }
+
+    /// Set the number of columns this cell spans.
+    pub fn col_span(mut self, col_span: u32) -> Self {
+        self.col_span = col_span;
+        self
+    }
+
+    /// Set the number of rows this cell spans.
+    pub fn row_span(mut self, row_span: u32) -> Self {
+        self.row_span = row_span;
+        self
+    }
+}
+
+impl Default for TableCell {
+    fn default() -> Self {
+        Self::new([])
+    }
+}
+
+impl From<Element> for TableCell {
+    fn from(e: Element) -> Self {
+        Self::new([e])
+    }
+}
+
+impl Pair {
+    /// Create a subscript/superscript pair.
+    pub fn new(sub: impl Into<Element>, sup: impl Into<Element>) -> Self {
+        Self {
+            sup: Box::new(sup.into()),
+            sub: Box::new(sub.into()),
+        }
+    }
+}
+
+impl Padding {
+    /// Create padding around a set of elements, with no adjustments.
+    pub fn new(elems: impl IntoIterator<Item = Element>) -> Self {
+        Self {
+            elems: elems.into_iter().collect(),
+            ..Self::default()
+        }
+    }
+}
+
+impl Space {
+    /// Create a space with only a width.
+    pub fn width(width: Length) -> Self {
+        Self {
+            width: Some(width),
+            ..Self::default()
+        }
+    }
+}
+
+impl Operator {
+    /// Create an operator with no overridden properties.
+    pub fn new(t: char) -> Self {
+        Self {
+            t,
+            ..Self::default()
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-
     #[test]
     fn sizes() {
         let elem_size = std::mem::size_of::<Element>();
@@ -351,4 +960,111 @@ mod tests {
         dbg!(rop_size);
         panic!();
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn builder() {
+        let e = Element::frac(
+            Element::id("a"),
+            Element::sup(Element::id("b"), Element::num("2")),
+        )
+        .variant(Variant::Bold)
+        .class("eq");
+        let a = e.attributes().unwrap();
+        assert_eq!(a.variant, Some(Variant::Bold));
+        assert_eq!(a.class, vec!["eq".to_string()]);
+        let MathElement::Frac {
+            num,
+            den,
+            line_thickness: None,
+        } = e.elem()
+        else {
+            panic!("expected a fraction");
+        };
+        assert_eq!(**num, Element::id("a"));
+        assert!(matches!(den.elem(), MathElement::Sup { .. }));
+    }
+
+    #[test]
+    fn matrix() {
+        let m = Element::matrix([
+            [Element::num("1"), Element::num("0")],
+            [Element::num("0"), Element::num("1")],
+        ]);
+        let MathElement::Table { rows } = m.elem() else {
+            panic!("expected a table");
+        };
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].cells[1], TableCell::new([Element::num("1")]));
+        assert_eq!(rows[1].cells[1].col_span, 1);
+    }
+
+    #[test]
+    fn attribute_accessors() {
+        let mut e = Element::new(MathElement::Op('+'));
+        assert!(e.attributes().is_none());
+        e.attributes_mut().rtl = true;
+        assert!(e.attributes().unwrap().rtl);
+        let old = e.set_attributes(None).unwrap();
+        assert!(old.rtl);
+        assert!(e.attributes().is_none());
+        let old = e.set_elem(MathElement::Op('-'));
+        assert_eq!(old, MathElement::Op('+'));
+    }
+
+    #[test]
+    fn unknown_elements() {
+        use fog_pack::{document::NewDocument, schema::NoSchema};
+        fn map<const N: usize>(entries: [(&str, Value); N]) -> Value {
+            Value::Map(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (k.to_string(), v))
+                    .collect(),
+            )
+        }
+        let glow = map([("e", map([("Glow", map([("hue", Value::from(3u8))]))]))]);
+        let raw = Value::Array(vec![map([
+            (
+                "e",
+                map([(
+                    "Row",
+                    Value::Array(vec![
+                        glow,
+                        map([("e", map([("Num", Value::from("1"))]))]),
+                        map([("e", Value::from("Spark"))]),
+                    ]),
+                )]),
+            ),
+            (
+                "a",
+                map([("rtl", Value::from(true)), ("sparkle", Value::from("lots"))]),
+            ),
+        ])]);
+        let doc = NoSchema::validate_new_doc(NewDocument::new(None, &raw).unwrap()).unwrap();
+        let elems: Vec<Element> = doc.deserialize().unwrap();
+        let MathElement::Row(row) = elems[0].elem() else {
+            panic!("expected a row");
+        };
+        assert_eq!(
+            *row[0].elem(),
+            MathElement::Unknown {
+                tag: "Glow".into(),
+                value: Some(Box::new(map([("hue", Value::from(3u8))]))),
+            }
+        );
+        assert_eq!(row[1], Element::num("1"));
+        assert_eq!(
+            *row[2].elem(),
+            MathElement::Unknown {
+                tag: "Spark".into(),
+                value: None,
+            }
+        );
+        let a = elems[0].attributes().unwrap();
+        assert!(a.rtl);
+        assert_eq!(a.unknown.get("sparkle"), Some(&Value::from("lots")));
+
+        let doc = NoSchema::validate_new_doc(NewDocument::new(None, &elems).unwrap()).unwrap();
+        assert_eq!(doc.deserialize::<Value>().unwrap(), raw);
+    }
+}
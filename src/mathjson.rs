@@ -0,0 +1,910 @@
+//! Conversion between MathJSON expressions, as used by the Cortex Compute
+//! Engine, and fog-math elements.
+//!
+//! Reading maps the layout heads, like `Add`, `Divide`, `Power`, `Subscript`,
+//! `Matrix`, and `Delimiter`, onto the matching elements, and writes any other
+//! head as a function applied to a fenced argument list. Only JSON syntax
+//! errors are returned as errors: malformed expressions become
+//! [`MathElement::Err`] nodes, and the rest of the input is still converted.
+//!
+//! Writing reads operator precedence back out of rows, so `2 x + 1` becomes
+//! `["Add", ["Multiply", 2, "x"], 1]`. Parentheses around a single expression
+//! only group, and other brackets become `Delimiter` expressions.
+
+use crate::json::{self, Json, JsonError};
+use crate::math::*;
+use crate::tree::{fenced, into_vec, list, op_char, splice};
+
+const LOWEST: u8 = 0;
+const RELATION: u8 = 1;
+const SUM: u8 = 2;
+const PRODUCT: u8 = 3;
+const PREFIX: u8 = 4;
+const POSTFIX: u8 = 5;
+const ATOM: u8 = 6;
+
+/// Infix operators, with their heads and precedence.
+const INFIX: &[(char, &str, u8)] = &[
+    ('+', "Add", SUM),
+    ('−', "Subtract", SUM),
+    ('-', "Subtract", SUM),
+    ('×', "Multiply", PRODUCT),
+    ('⋅', "Multiply", PRODUCT),
+    ('*', "Multiply", PRODUCT),
+    ('\u{2062}', "Multiply", PRODUCT),
+    ('/', "Divide", PRODUCT),
+    ('÷', "Divide", PRODUCT),
+    ('=', "Equal", RELATION),
+    ('≠', "NotEqual", RELATION),
+    ('<', "Less", RELATION),
+    ('≤', "LessEqual", RELATION),
+    ('>', "Greater", RELATION),
+    ('≥', "GreaterEqual", RELATION),
+    ('≈', "Approx", RELATION),
+];
+
+/// Symbols, with the identifier they're written as, and whether it's upright.
+const SYMBOLS: &[(&str, &str, bool)] = &[
+    ("Pi", "π", false),
+    ("ExponentialE", "e", true),
+    ("ImaginaryUnit", "i", true),
+    ("Infinity", "∞", true),
+    ("NaN", "NaN", true),
+    ("True", "True", true),
+    ("False", "False", true),
+    ("alpha", "α", false),
+    ("beta", "β", false),
+    ("gamma", "γ", false),
+    ("delta", "δ", false),
+    ("epsilon", "ϵ", false),
+    ("varepsilon", "ε", false),
+    ("zeta", "ζ", false),
+    ("eta", "η", false),
+    ("theta", "θ", false),
+    ("iota", "ι", false),
+    ("kappa", "κ", false),
+    ("lambda", "λ", false),
+    ("mu", "μ", false),
+    ("nu", "ν", false),
+    ("xi", "ξ", false),
+    ("rho", "ρ", false),
+    ("sigma", "σ", false),
+    ("tau", "τ", false),
+    ("upsilon", "υ", false),
+    ("phi", "ϕ", false),
+    ("varphi", "φ", false),
+    ("chi", "χ", false),
+    ("psi", "ψ", false),
+    ("omega", "ω", false),
+    ("Gamma", "Γ", true),
+    ("Delta", "Δ", true),
+    ("Theta", "Θ", true),
+    ("Lambda", "Λ", true),
+    ("Xi", "Ξ", true),
+    ("Sigma", "Σ", true),
+    ("Phi", "Φ", true),
+    ("Psi", "Ψ", true),
+    ("Omega", "Ω", true),
+];
+
+/// Functions, with the names they're written as.
+const FUNCTIONS: &[(&str, &str)] = &[
+    ("Sin", "sin"),
+    ("Cos", "cos"),
+    ("Tan", "tan"),
+    ("Cot", "cot"),
+    ("Sec", "sec"),
+    ("Csc", "csc"),
+    ("Arcsin", "arcsin"),
+    ("Arccos", "arccos"),
+    ("Arctan", "arctan"),
+    ("Sinh", "sinh"),
+    ("Cosh", "cosh"),
+    ("Tanh", "tanh"),
+    ("Exp", "exp"),
+    ("Ln", "ln"),
+    ("Log", "log"),
+    ("Max", "max"),
+    ("Min", "min"),
+    ("Gcd", "gcd"),
+    ("Lcm", "lcm"),
+    ("Det", "det"),
+];
+
+/// Parse a MathJSON expression.
+pub fn parse(src: &str) -> Result<Element, JsonError> {
+    let v = json::parse(src)?;
+    Ok(render(&v).0)
+}
+
+/// Render an expression, along with its precedence.
+fn render(v: &Json) -> (Element, u8) {
+    match v {
+        Json::Num(t) => number(t),
+        Json::Str(s) => (string(s), ATOM),
+        Json::Bool(b) => (symbol(if *b { "True" } else { "False" }), ATOM),
+        Json::Null => (Element::err("unexpected null"), ATOM),
+        Json::Array(items) => match items.split_first() {
+            Some((head, args)) => apply(head, args),
+            None => (Element::err("empty MathJSON expression"), ATOM),
+        },
+        Json::Object(_) => {
+            if let Some(n) = v.get("num") {
+                match n.as_str().or_else(|| n.as_num()) {
+                    Some(t) => number(t),
+                    None => (Element::err("invalid MathJSON number"), ATOM),
+                }
+            } else if let Some(s) = v.get("sym").and_then(Json::as_str) {
+                (symbol(s), ATOM)
+            } else if let Some(s) = v.get("str").and_then(Json::as_str) {
+                (Element::str(s), ATOM)
+            } else if let Some(f) = v.get("fn") {
+                render(f)
+            } else {
+                (Element::err("unsupported MathJSON object"), ATOM)
+            }
+        }
+    }
+}
+
+fn number(t: &str) -> (Element, u8) {
+    let (neg, t) = match t.strip_prefix('-') {
+        Some(t) => (true, t),
+        None => (false, t.strip_prefix('+').unwrap_or(t)),
+    };
+    let e = match t {
+        "Infinity" => Element::id_normal("∞"),
+        "NaN" => Element::id_normal("NaN"),
+        t => Element::num(t),
+    };
+    if neg {
+        (Element::row([Element::op('−'), e]), PREFIX)
+    } else {
+        (e, ATOM)
+    }
+}
+
+/// Render a string, which is a symbol unless it's quoted.
+fn string(s: &str) -> Element {
+    match s.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')) {
+        Some(s) => Element::str(s),
+        None => symbol(s.trim_matches('`')),
+    }
+}
+
+fn symbol(name: &str) -> Element {
+    match SYMBOLS.iter().find(|(n, ..)| *n == name) {
+        Some((_, t, true)) => Element::id_normal(*t),
+        Some((_, t, false)) => Element::id(*t),
+        None => Element::id(name),
+    }
+}
+
+/// Get the text of a quoted string.
+fn quoted(v: &Json) -> Option<&str> {
+    match v.get("str").and_then(Json::as_str) {
+        Some(s) => Some(s),
+        None => v.as_str()?.strip_prefix('\'')?.strip_suffix('\''),
+    }
+}
+
+/// Get the arguments of an expression with the given head.
+fn args_of<'a>(v: &'a Json, head: &str) -> Option<&'a [Json]> {
+    let items = v.as_array()?;
+    (items.first()?.as_str() == Some(head)).then(|| &items[1..])
+}
+
+fn apply(head: &Json, args: &[Json]) -> (Element, u8) {
+    let Some(name) = head.as_str().filter(|s| !s.starts_with('\'')) else {
+        return func(wrap(head, ATOM), args);
+    };
+    match (name, args) {
+        ("Add", [_, _, ..]) => {
+            let mut elems = Vec::new();
+            for (i, a) in args.iter().enumerate() {
+                if i == 0 {
+                    operand(&mut elems, a, SUM);
+                    continue;
+                }
+                // Negative terms are written as subtraction.
+                let neg = match (args_of(a, "Negate"), a.as_num()) {
+                    (Some([x]), _) => Some(render(x)),
+                    (_, Some(t)) if t.starts_with('-') => Some(number(&t[1..])),
+                    _ => None,
+                };
+                match neg {
+                    Some(neg) => {
+                        elems.push(Element::op('−'));
+                        let (e, prec) = after_op(neg);
+                        splice(&mut elems, e, prec, PRODUCT);
+                    }
+                    None => {
+                        elems.push(Element::op('+'));
+                        let (e, prec) = after_op(render(a));
+                        splice(&mut elems, e, prec, SUM);
+                    }
+                }
+            }
+            (Element::row(elems), SUM)
+        }
+        ("Subtract", [a, b]) => {
+            let mut elems = Vec::new();
+            operand(&mut elems, a, SUM);
+            elems.push(Element::op('−'));
+            let (e, prec) = after_op(render(b));
+            splice(&mut elems, e, prec, PRODUCT);
+            (Element::row(elems), SUM)
+        }
+        ("Negate" | "Subtract", [x]) => {
+            let mut elems = vec![Element::op('−')];
+            let (e, prec) = after_op(render(x));
+            splice(&mut elems, e, prec, PREFIX);
+            (Element::row(elems), PREFIX)
+        }
+        ("Multiply", [_, _, ..]) => {
+            let mut elems = Vec::new();
+            for (i, a) in args.iter().enumerate() {
+                let mut factor = render(a);
+                if i > 0 {
+                    // Numbers after the first factor need a visible sign.
+                    let c = if a.as_num().is_some() || a.get("num").is_some() {
+                        '×'
+                    } else {
+                        '\u{2062}'
+                    };
+                    elems.push(Element::op(c));
+                    factor = after_op(factor);
+                }
+                splice(&mut elems, factor.0, factor.1, PRODUCT);
+            }
+            (Element::row(elems), PRODUCT)
+        }
+        ("Divide" | "Rational", [n, d]) => (Element::frac(render(n).0, render(d).0), ATOM),
+        ("Power", [b, e]) => (Element::sup(wrap(b, ATOM), render(e).0), ATOM),
+        ("Sqrt", [x]) => (Element::sqrt(render(x).0), ATOM),
+        ("Root", [x, n]) => (Element::root(render(x).0, render(n).0), ATOM),
+        ("Subscript", [b, s]) => (Element::sub(wrap(b, ATOM), render(s).0), ATOM),
+        ("Factorial", [x]) => (Element::row([wrap(x, ATOM), Element::op('!')]), POSTFIX),
+        ("Sequence", _) => (Element::row(list(args.iter().map(|a| render(a).0))), LOWEST),
+        ("Delimiter", [body, ..]) => {
+            let (open, sep, close) = match args.get(1).and_then(quoted) {
+                Some(d) => delimiters(d),
+                None => (Some('('), ',', Some(')')),
+            };
+            let items = match args_of(body, "Sequence") {
+                Some(items) => items,
+                None => std::slice::from_ref(body),
+            };
+            let mut elems = Vec::new();
+            for (i, a) in items.iter().enumerate() {
+                if i > 0 {
+                    elems.push(Element::op(sep));
+                }
+                elems.push(render(a).0);
+            }
+            (fenced(open, elems, close), ATOM)
+        }
+        ("Matrix", [rows, ..]) => {
+            let (open, _, close) = match args.get(1).and_then(quoted) {
+                Some(d) => delimiters(d),
+                None => (Some('('), ',', Some(')')),
+            };
+            let rows = args_of(rows, "List").and_then(|rows| {
+                rows.iter()
+                    .map(|r| {
+                        let cells = args_of(r, "List")?;
+                        Some(cells.iter().map(|c| render(c).0).collect::<Vec<_>>())
+                    })
+                    .collect::<Option<Vec<_>>>()
+            });
+            match rows {
+                Some(rows) => (fenced(open, vec![Element::matrix(rows)], close), ATOM),
+                None => (Element::err("Matrix needs a list of lists"), ATOM),
+            }
+        }
+        ("Error", _) => {
+            let msg = args.first().and_then(quoted).unwrap_or("MathJSON error");
+            (Element::err(msg), ATOM)
+        }
+        _ => {
+            if let Some((c, _, prec)) = INFIX.iter().find(|(_, h, p)| *h == name && *p == RELATION)
+            {
+                if args.len() >= 2 {
+                    let mut elems = Vec::new();
+                    for (i, a) in args.iter().enumerate() {
+                        if i > 0 {
+                            elems.push(Element::op(*c));
+                        }
+                        operand(&mut elems, a, SUM);
+                    }
+                    return (Element::row(elems), *prec);
+                }
+            }
+            let name = match FUNCTIONS.iter().find(|(h, _)| *h == name) {
+                Some((_, n)) => n,
+                None => name,
+            };
+            func(Element::id(name), args)
+        }
+    }
+}
+
+/// Render a function applied to a fenced argument list.
+fn func(head: Element, args: &[Json]) -> (Element, u8) {
+    let args = list(args.iter().map(|a| render(a).0));
+    (
+        Element::row([
+            head,
+            Element::op('\u{2061}'),
+            fenced(Some('('), args, Some(')')),
+        ]),
+        ATOM,
+    )
+}
+
+/// Split a delimiter string into the open, separator, and close characters. A
+/// `.` means there's no delimiter.
+fn delimiters(d: &str) -> (Option<char>, char, Option<char>) {
+    let c: Vec<char> = d.chars().collect();
+    let (open, sep, close) = match c.as_slice() {
+        [o, c] => (*o, ',', *c),
+        [o, s, c] => (*o, *s, *c),
+        [o] => (*o, ',', '.'),
+        _ => ('(', ',', ')'),
+    };
+    let some = |c: char| (c != '.').then_some(c);
+    (some(open), sep, some(close))
+}
+
+/// Render an expression, wrapping it in parentheses if it binds looser than
+/// `min`.
+fn wrap(v: &Json, min: u8) -> Element {
+    let (e, prec) = render(v);
+    if prec < min {
+        fenced(Some('('), into_vec(e), Some(')'))
+    } else {
+        e
+    }
+}
+
+/// Add an operand to a row, splicing it in unless it needs parentheses.
+fn operand(elems: &mut Vec<Element>, v: &Json, min: u8) {
+    let (e, prec) = render(v);
+    splice(elems, e, prec, min);
+}
+
+/// Adjust a rendered operand that follows another operator. A leading minus
+/// sign would run into that operator, as in `a−−b`, so such operands are
+/// treated as binding loosest to get parentheses.
+fn after_op((e, prec): (Element, u8)) -> (Element, u8) {
+    match e.elem() {
+        MathElement::Row(elems) if elems.first().and_then(op_char) == Some('−') => (e, LOWEST),
+        _ => (e, prec),
+    }
+}
+
+impl Element {
+    /// Write the element out as a MathJSON expression. Elements with no
+    /// MathJSON equivalent, like spaces and phantoms, are dropped.
+    pub fn to_mathjson(&self) -> String {
+        expr(self).to_string()
+    }
+}
+
+fn call(head: &str, args: impl IntoIterator<Item = Json>) -> Json {
+    Json::Array(std::iter::once(Json::str(head)).chain(args).collect())
+}
+
+fn error(msg: &str) -> Json {
+    call("Error", [Json::str(format!("'{}'", msg))])
+}
+
+/// Write a number, using the object form if it isn't a valid JSON number.
+fn number_json(t: &str) -> Json {
+    match json::parse(t) {
+        Ok(Json::Num(_)) => Json::Num(t.into()),
+        _ => Json::object([("num", Json::str(t))]),
+    }
+}
+
+fn matrix(rows: &[TableRow], open: Option<char>, close: Option<char>) -> Json {
+    let rows = rows.iter().map(|r| {
+        let cells = r.cells.iter().map(|c| read_row(&c.elems));
+        call("List", cells)
+    });
+    let mut args = vec![call("List", rows)];
+    if (open, close) != (Some('('), Some(')')) {
+        let d: String = [open.unwrap_or('.'), close.unwrap_or('.')].iter().collect();
+        args.push(Json::str(format!("'{}'", d)));
+    }
+    call("Matrix", args)
+}
+
+fn expr(e: &Element) -> Json {
+    match e.elem() {
+        MathElement::Num(t) => number_json(t),
+        // Upright symbols like `ExponentialE` are only meant when the
+        // identifier is upright too; an italic `e` is just a variable.
+        MathElement::Id { t, normal } => match SYMBOLS
+            .iter()
+            .find(|(_, s, upright)| s == t && (*normal || !upright))
+        {
+            Some((name, ..)) => Json::str(*name),
+            None => Json::str(t.as_str()),
+        },
+        MathElement::Text(t) | MathElement::Str(t) => Json::str(format!("'{}'", t)),
+        MathElement::Err(t) | MathElement::Unknown { tag: t, .. } => error(t),
+        MathElement::Op(_) | MathElement::Oper(_) | MathElement::ResolvedOper(_) => {
+            read_row(std::slice::from_ref(e))
+        }
+        MathElement::Row(elems) => read_row(elems),
+        MathElement::Padding(p) => read_row(&p.elems),
+        MathElement::Space(_) | MathElement::Phantom(_) => call("Sequence", []),
+        MathElement::Frac { num, den, .. } => call("Divide", [expr(num), expr(den)]),
+        MathElement::Sqrt(x) => call("Sqrt", [expr(x)]),
+        MathElement::Root { base, index } => call("Root", [expr(base), expr(index)]),
+        MathElement::Sup { base, sup } => call("Power", [expr(base), expr(sup)]),
+        MathElement::Sub { base, sub } => call("Subscript", [expr(base), expr(sub)]),
+        MathElement::SubSup { base, sub, sup } => call(
+            "Power",
+            [call("Subscript", [expr(base), expr(sub)]), expr(sup)],
+        ),
+        MathElement::Over { base, over, .. } => call("Overscript", [expr(base), expr(over)]),
+        MathElement::Under { base, under, .. } => call("Underscript", [expr(base), expr(under)]),
+        MathElement::UnderOver {
+            base, under, over, ..
+        } => call(
+            "Overscript",
+            [call("Underscript", [expr(base), expr(under)]), expr(over)],
+        ),
+        MathElement::MultiScript { base, post, pre } => match (post.as_slice(), pre.is_empty()) {
+            ([p], true) => call(
+                "Power",
+                [call("Subscript", [expr(base), expr(&p.sub)]), expr(&p.sup)],
+            ),
+            _ => error("multiscripts are not supported"),
+        },
+        MathElement::Table { rows } => matrix(rows, None, None),
+    }
+}
+
+/// Read a row as a single expression, or a `Sequence` if it has several
+/// comma-separated items.
+fn read_row(elems: &[Element]) -> Json {
+    let mut items = read_items(elems);
+    if items.len() == 1 {
+        items.pop().unwrap()
+    } else {
+        call("Sequence", items)
+    }
+}
+
+/// Read comma-separated expressions.
+fn read_items(elems: &[Element]) -> Vec<Json> {
+    let elems: Vec<&Element> = elems
+        .iter()
+        .filter(|e| !matches!(e.elem(), MathElement::Space(_) | MathElement::Phantom(_)))
+        .collect();
+    let mut r = Reader {
+        elems: &elems,
+        pos: 0,
+    };
+    let mut items = Vec::new();
+    while r.pos < elems.len() {
+        if r.peek_op() == Some(',') {
+            r.pos += 1;
+            continue;
+        }
+        match r.expr(LOWEST) {
+            Some(v) => items.push(v),
+            None => {
+                // Skip anything that can't start an expression.
+                let e = elems[r.pos];
+                let msg = match op_char(e) {
+                    Some(c) => format!("unexpected `{}`", c),
+                    None => "unexpected element".into(),
+                };
+                items.push(error(&msg));
+                r.pos += 1;
+            }
+        }
+    }
+    items
+}
+
+fn is_open(c: char) -> bool {
+    "([{⌊⌈⟨|".contains(c)
+}
+
+fn is_close(c: char) -> bool {
+    ")]}⌋⌉⟩|".contains(c)
+}
+
+/// Reads expressions from a row of elements, by operator precedence.
+struct Reader<'a, 'b> {
+    elems: &'b [&'a Element],
+    pos: usize,
+}
+
+impl<'a> Reader<'a, '_> {
+    fn peek(&self) -> Option<&'a Element> {
+        self.elems.get(self.pos).copied()
+    }
+
+    fn peek_op(&self) -> Option<char> {
+        self.peek().and_then(op_char)
+    }
+
+    fn expr(&mut self, min: u8) -> Option<Json> {
+        let mut lhs = self.term()?;
+        // Only flatten chains read here, not parenthesized operands.
+        let mut chained = false;
+        loop {
+            let (head, prec) = match self.peek_op() {
+                Some('!') => {
+                    self.pos += 1;
+                    lhs = call("Factorial", [lhs]);
+                    continue;
+                }
+                Some(c) => match INFIX.iter().find(|(t, ..)| *t == c) {
+                    Some((_, head, prec)) => {
+                        if *prec < min {
+                            break;
+                        }
+                        self.pos += 1;
+                        (*head, *prec)
+                    }
+                    // Brackets next to an expression multiply it.
+                    None if is_open(c) && c != '|' && PRODUCT >= min => ("Multiply", PRODUCT),
+                    None => break,
+                },
+                None if self.peek().is_some() && PRODUCT >= min => ("Multiply", PRODUCT),
+                _ => break,
+            };
+            let rhs = self
+                .expr(prec + 1)
+                .unwrap_or_else(|| error("missing operand"));
+            lhs = match lhs {
+                Json::Array(mut items)
+                    if chained
+                        && head != "Subtract"
+                        && head != "Divide"
+                        && items.first().and_then(Json::as_str) == Some(head) =>
+                {
+                    items.push(rhs);
+                    Json::Array(items)
+                }
+                lhs => call(head, [lhs, rhs]),
+            };
+            chained = true;
+        }
+        Some(lhs)
+    }
+
+    fn term(&mut self) -> Option<Json> {
+        let e = self.peek()?;
+        if let Some(c) = op_char(e) {
+            if c == ',' || c == '!' || (is_close(c) && c != '|') {
+                return None;
+            }
+            if INFIX.iter().any(|(t, ..)| *t == c) && !"−-+".contains(c) {
+                return None;
+            }
+            self.pos += 1;
+            return Some(match c {
+                '−' | '-' => match self.peek().map(Element::elem) {
+                    Some(MathElement::Num(t)) => {
+                        self.pos += 1;
+                        number_json(&format!("-{}", t))
+                    }
+                    _ => {
+                        let x = self
+                            .expr(PREFIX)
+                            .unwrap_or_else(|| error("missing operand"));
+                        call("Negate", [x])
+                    }
+                },
+                '+' => self
+                    .expr(PREFIX)
+                    .unwrap_or_else(|| error("missing operand")),
+                c if is_open(c) => self.fence(c),
+                c => Json::str(c.to_string()),
+            });
+        }
+        self.pos += 1;
+        if self.peek_op() != Some('\u{2061}') {
+            return Some(expr(e));
+        }
+        // Function application.
+        self.pos += 1;
+        let head = match e.elem() {
+            MathElement::Id { t, .. } => match FUNCTIONS.iter().find(|(_, n)| n == t) {
+                Some((h, _)) => Json::str(*h),
+                None => expr(e),
+            },
+            _ => expr(e),
+        };
+        let args = match self.peek().map(|a| (a, a.elem())) {
+            Some((_, MathElement::Row(elems)))
+                if elems.len() >= 2
+                    && op_char(&elems[0]) == Some('(')
+                    && op_char(&elems[elems.len() - 1]) == Some(')') =>
+            {
+                self.pos += 1;
+                read_items(&elems[1..elems.len() - 1])
+            }
+            Some(_) if self.peek_op() == Some('(') => {
+                self.pos += 1;
+                match self.close('(') {
+                    Some((inner, _)) => {
+                        read_items(&inner.iter().map(|e| (*e).clone()).collect::<Vec<_>>())
+                    }
+                    None => Vec::new(),
+                }
+            }
+            _ => self.term().into_iter().collect(),
+        };
+        match head {
+            Json::Str(h) => Some(call(&h, args)),
+            head => Some(Json::Array(std::iter::once(head).chain(args).collect())),
+        }
+    }
+
+    /// Find the bracket closing one that was just read, returning the
+    /// elements between them and the closing character.
+    fn close(&mut self, open: char) -> Option<(&'_ [&'a Element], char)> {
+        let start = self.pos;
+        let mut depth = 0;
+        let end = self.elems[start..].iter().position(|e| match op_char(e) {
+            Some('|') if open == '|' => true,
+            Some(c) if is_open(c) && c != '|' => {
+                depth += 1;
+                false
+            }
+            Some(c) if is_close(c) && c != '|' && depth > 0 => {
+                depth -= 1;
+                false
+            }
+            Some(c) => is_close(c) && c != '|',
+            None => false,
+        })?;
+        let close = op_char(self.elems[start + end])?;
+        self.pos = start + end + 1;
+        Some((&self.elems[start..start + end], close))
+    }
+
+    /// Read a bracketed expression after its opening bracket.
+    fn fence(&mut self, open: char) -> Json {
+        let Some((inner, close)) = self.close(open) else {
+            return Json::str(open.to_string());
+        };
+        if let [table] = inner {
+            if let MathElement::Table { rows } = table.elem() {
+                return matrix(rows, Some(open), Some(close));
+            }
+        }
+        let inner: Vec<Element> = inner.iter().map(|e| (*e).clone()).collect();
+        let mut items = read_items(&inner);
+        let parens = (open, close) == ('(', ')');
+        if parens && items.len() == 1 {
+            return items.pop().unwrap();
+        }
+        let body = if items.len() == 1 {
+            items.pop().unwrap()
+        } else {
+            call("Sequence", items)
+        };
+        let mut args = vec![body];
+        if !parens {
+            args.push(Json::str(format!("'{}{}'", open, close)));
+        }
+        call("Delimiter", args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(src: &str) {
+        let e = parse(src).unwrap();
+        assert_eq!(e.to_mathjson(), src, "{:?}", e);
+    }
+
+    #[test]
+    fn parse_layout_heads() {
+        let e = parse(r#"["Add", "x", ["Power", "y", 2]]"#).unwrap();
+        assert_eq!(
+            e,
+            Element::row([
+                Element::id("x"),
+                Element::op('+'),
+                Element::sup(Element::id("y"), Element::num("2")),
+            ])
+        );
+        let e = parse(r#"["Divide", ["Sqrt", "Pi"], ["Subscript", "a", 1]]"#).unwrap();
+        assert_eq!(
+            e,
+            Element::frac(
+                Element::sqrt(Element::id("π")),
+                Element::sub(Element::id("a"), Element::num("1")),
+            )
+        );
+        let e = parse(r#"["Add", "x", ["Negate", "y"], -2]"#).unwrap();
+        assert_eq!(
+            e,
+            Element::row([
+                Element::id("x"),
+                Element::op('−'),
+                Element::id("y"),
+                Element::op('−'),
+                Element::num("2"),
+            ])
+        );
+        let neg = |e| fenced(Some('('), vec![Element::op('−'), e], Some(')'));
+        let e = parse(r#"["Subtract", "a", ["Negate", "b"]]"#).unwrap();
+        assert_eq!(
+            e,
+            Element::row([Element::id("a"), Element::op('−'), neg(Element::id("b"))])
+        );
+        let e = parse(r#"["Negate", ["Negate", "b"]]"#).unwrap();
+        assert_eq!(e, Element::row([Element::op('−'), neg(Element::id("b"))]));
+        let e = parse(r#"["Multiply", -2, "a", -3]"#).unwrap();
+        assert_eq!(
+            e,
+            Element::row([
+                Element::op('−'),
+                Element::num("2"),
+                Element::op('\u{2062}'),
+                Element::id("a"),
+                Element::op('×'),
+                neg(Element::num("3")),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_fences() {
+        let e = parse(r#"["Delimiter", ["Sequence", "a", "b"], "'[;]'"]"#).unwrap();
+        assert_eq!(
+            e,
+            Element::row([
+                Element::op('['),
+                Element::id("a"),
+                Element::op(';'),
+                Element::id("b"),
+                Element::op(']'),
+            ])
+        );
+        let e = parse(r#"["Matrix", ["List", ["List", 1, 0], ["List", 0, 1]], "'[]'"]"#).unwrap();
+        assert_eq!(
+            e,
+            Element::row([
+                Element::op('['),
+                Element::matrix([
+                    [Element::num("1"), Element::num("0")],
+                    [Element::num("0"), Element::num("1")],
+                ]),
+                Element::op(']'),
+            ])
+        );
+        let e = parse(r#"["Multiply", 2, ["Add", "a", "b"]]"#).unwrap();
+        assert_eq!(
+            e,
+            Element::row([
+                Element::num("2"),
+                Element::op('\u{2062}'),
+                Element::row([
+                    Element::op('('),
+                    Element::id("a"),
+                    Element::op('+'),
+                    Element::id("b"),
+                    Element::op(')'),
+                ]),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_unknown_heads() {
+        let e = parse(r#"["Foo", "x", {"num": "1.5"}]"#).unwrap();
+        assert_eq!(
+            e,
+            Element::row([
+                Element::id("Foo"),
+                Element::op('\u{2061}'),
+                Element::row([
+                    Element::op('('),
+                    Element::id("x"),
+                    Element::op(','),
+                    Element::num("1.5"),
+                    Element::op(')'),
+                ]),
+            ])
+        );
+        let e = parse(r#"[["Derivative", "f"], "x"]"#).unwrap();
+        assert!(matches!(e.elem(), MathElement::Row(elems) if elems.len() == 3));
+        assert_eq!(
+            parse("[]").unwrap(),
+            Element::err("empty MathJSON expression")
+        );
+        assert_eq!(
+            parse(r#"["Error", "'oops'"]"#).unwrap(),
+            Element::err("oops")
+        );
+        assert!(parse(r#"["Add", 1"#).is_err());
+    }
+
+    #[test]
+    fn write_precedence() {
+        let e = Element::row([
+            Element::num("2"),
+            Element::id("x"),
+            Element::op('+'),
+            Element::num("1"),
+            Element::op('='),
+            Element::op('−'),
+            Element::id("y"),
+            Element::op('!'),
+        ]);
+        assert_eq!(
+            e.to_mathjson(),
+            r#"["Equal",["Add",["Multiply",2,"x"],1],["Negate",["Factorial","y"]]]"#
+        );
+        let e = Element::row([
+            Element::id("a"),
+            Element::op('−'),
+            Element::id("b"),
+            Element::op('−'),
+            Element::num("3"),
+            Element::op(','),
+            Element::op('|'),
+            Element::id("z"),
+            Element::op('|'),
+        ]);
+        assert_eq!(
+            e.to_mathjson(),
+            r#"["Sequence",["Subtract",["Subtract","a","b"],3],["Delimiter","z","'||'"]]"#
+        );
+    }
+
+    #[test]
+    fn write_functions() {
+        let e = Element::row([
+            Element::id("sin"),
+            Element::op('\u{2061}'),
+            Element::id("θ"),
+            Element::op('⋅'),
+            Element::id("f"),
+            Element::op('\u{2061}'),
+            Element::op('('),
+            Element::id("x"),
+            Element::op(','),
+            Element::num("0x1F"),
+            Element::op(')'),
+        ]);
+        assert_eq!(
+            e.to_mathjson(),
+            r#"["Multiply",["Sin","theta"],["f","x",{"num":"0x1F"}]]"#
+        );
+    }
+
+    #[test]
+    fn mathjson_roundtrip() {
+        roundtrip(r#"["Add","x",["Power","y",2]]"#);
+        roundtrip(r#"["Divide",["Subtract","a","b"],["Root","x",3]]"#);
+        roundtrip(r#"["Multiply",2,"x",["Add","x",1],["Power",["Add","a","b"],2]]"#);
+        roundtrip(r#"["Equal",["Subscript","a","n"],["Sqrt","ExponentialE"]]"#);
+        roundtrip(r#"["Delimiter",["Sequence","a","b"],"'[)'"]"#);
+        roundtrip(r#"["Matrix",["List",["List",1,"x"],["List",{"num":"1e"},"'s'"]]]"#);
+        roundtrip(r#"["Foo",["Factorial","n"],["Max","a","b"]]"#);
+    }
+
+    #[test]
+    fn upright_symbols() {
+        roundtrip(r#"["Subscript","x","i"]"#);
+        roundtrip(r#"["Multiply","e","ImaginaryUnit"]"#);
+        let e = Element::sup(Element::id("e"), Element::id_normal("i"));
+        assert_eq!(e.to_mathjson(), r#"["Power","e","ImaginaryUnit"]"#);
+    }
+}
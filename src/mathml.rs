@@ -0,0 +1,1076 @@
+//! Conversion between MathML and fog-math elements.
+//!
+//! Parsing and writing both follow MathML Core. Elements that have no fog-math
+//! equivalent are turned into [`MathElement::Err`] nodes so the rest of the
+//! equation is still usable. Styling attributes like `mathcolor` and `style`
+//! are dropped, as fog-math doesn't carry CSS information.
+
+use std::collections::BTreeMap;
+
+use fog_pack::types::Value;
+
+use crate::math::*;
+use crate::xml::{self, XmlError, XmlNode};
+
+/// The MathML namespace.
+pub const NAMESPACE: &str = "http://www.w3.org/1998/Math/MathML";
+
+/// Parse a MathML document, which should have a `<math>` element at its root.
+///
+/// Only XML syntax errors are returned as errors. Unsupported or malformed
+/// MathML becomes [`MathElement::Err`] nodes within the returned tree.
+pub fn parse(src: &str) -> Result<Element, XmlError> {
+    let root = xml::parse(src)?;
+    Ok(convert(&root))
+}
+
+/// Convert a single MathML element.
+fn convert(node: &XmlNode) -> Element {
+    let name = node.local();
+    let e = match name {
+        "math" => {
+            let mut e = row(node);
+            if node.attr("display") == Some("block") {
+                e.attributes_mut().display_style = Some(true);
+            }
+            return with_attributes(e, node);
+        }
+        "mrow" | "mstyle" => MathElement::Row(children(node)),
+        "mi" => {
+            let t = token_text(node);
+            let normal = node.attr("mathvariant") == Some("normal");
+            MathElement::Id { t, normal }
+        }
+        "mn" => MathElement::Num(token_text(node)),
+        "mo" => operator(node),
+        "mtext" => MathElement::Text(token_text(node)),
+        "ms" => MathElement::Str(token_text(node)),
+        "merror" => MathElement::Err(token_text(node)),
+        "mspace" => MathElement::Space(Space {
+            width: node.attr("width").and_then(parse_length),
+            height: node.attr("height").and_then(parse_length),
+            depth: node.attr("depth").and_then(parse_length),
+        }),
+        "mpadded" => MathElement::Padding(Padding {
+            elems: children(node),
+            width: node.attr("width").and_then(parse_length),
+            height: node.attr("height").and_then(parse_length),
+            depth: node.attr("depth").and_then(parse_length),
+            lspace: node.attr("lspace").and_then(parse_length),
+            voffset: node.attr("voffset").and_then(parse_length),
+        }),
+        "mphantom" => MathElement::Phantom(children(node)),
+        "msqrt" => MathElement::Sqrt(Box::new(row(node))),
+        "mfrac" => match args::<2>(node) {
+            Ok([num, den]) => MathElement::Frac {
+                line_thickness: node.attr("linethickness").and_then(parse_thickness),
+                num,
+                den,
+            },
+            Err(e) => e,
+        },
+        "mroot" => match args::<2>(node) {
+            Ok([base, index]) => MathElement::Root { base, index },
+            Err(e) => e,
+        },
+        "msub" => match args::<2>(node) {
+            Ok([base, sub]) => MathElement::Sub { base, sub },
+            Err(e) => e,
+        },
+        "msup" => match args::<2>(node) {
+            Ok([base, sup]) => MathElement::Sup { base, sup },
+            Err(e) => e,
+        },
+        "msubsup" => match args::<3>(node) {
+            Ok([base, sub, sup]) => MathElement::SubSup { base, sub, sup },
+            Err(e) => e,
+        },
+        "munder" => match args::<2>(node) {
+            Ok([base, under]) => MathElement::Under {
+                base,
+                under,
+                accent_under: is_true(node, "accentunder"),
+            },
+            Err(e) => e,
+        },
+        "mover" => match args::<2>(node) {
+            Ok([base, over]) => MathElement::Over {
+                base,
+                over,
+                accent: is_true(node, "accent"),
+            },
+            Err(e) => e,
+        },
+        "munderover" => match args::<3>(node) {
+            Ok([base, under, over]) => MathElement::UnderOver {
+                base,
+                under,
+                over,
+                accent: is_true(node, "accent"),
+                accent_under: is_true(node, "accentunder"),
+            },
+            Err(e) => e,
+        },
+        "mmultiscripts" => multiscripts(node),
+        "mtable" => MathElement::Table {
+            rows: node.elems().map(table_row).collect(),
+        },
+        "none" => MathElement::Row(Vec::new()),
+        "semantics" => match node.elems().next() {
+            Some(first) => return with_attributes(convert(first), node),
+            None => MathElement::Row(Vec::new()),
+        },
+        "maction" => {
+            let selection = node
+                .attr("selection")
+                .and_then(|s| s.trim().parse::<usize>().ok())
+                .unwrap_or(1);
+            match node.elems().nth(selection.saturating_sub(1)) {
+                Some(child) => return with_attributes(convert(child), node),
+                None => MathElement::Err("<maction> has no selected child".into()),
+            }
+        }
+        "mfenced" => fenced(node),
+        _ => MathElement::Err(format!("unsupported MathML element <{}>", name)),
+    };
+    with_attributes(Element::new(e), node)
+}
+
+/// Convert all child elements.
+fn children(node: &XmlNode) -> Vec<Element> {
+    node.elems().map(convert).collect()
+}
+
+/// Convert the children of an element with an inferred `<mrow>`.
+fn row(node: &XmlNode) -> Element {
+    let mut elems = children(node);
+    if elems.len() == 1 {
+        elems.pop().unwrap()
+    } else {
+        Element::row(elems)
+    }
+}
+
+/// Convert the children of an element that requires exactly `N` of them,
+/// producing an error element if the count is wrong.
+fn args<const N: usize>(node: &XmlNode) -> Result<[Box<Element>; N], MathElement> {
+    let elems: Vec<Box<Element>> = node.elems().map(|e| Box::new(convert(e))).collect();
+    let len = elems.len();
+    elems.try_into().map_err(|_| {
+        MathElement::Err(format!(
+            "<{}> requires {} children, but has {}",
+            node.local(),
+            N,
+            len
+        ))
+    })
+}
+
+/// Get the text of a token element, with whitespace trimmed and collapsed.
+fn token_text(node: &XmlNode) -> String {
+    node.text().split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn is_true(node: &XmlNode, attr: &str) -> bool {
+    node.attr(attr).map(str::trim) == Some("true")
+}
+
+fn parse_bool(s: &str) -> Option<bool> {
+    match s.trim() {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// Convert an `<mo>` element. Multi-character operators become plain text.
+fn operator(node: &XmlNode) -> MathElement {
+    let text = token_text(node);
+    let mut chars = text.chars();
+    let (Some(t), None) = (chars.next(), chars.next()) else {
+        return MathElement::Text(text);
+    };
+    let lof = |attr| node.attr(attr).and_then(parse_length_or_frac);
+    let flag = |attr| node.attr(attr).and_then(parse_bool);
+    let op = Operator {
+        t,
+        form: node.attr("form").and_then(|f| match f.trim() {
+            "prefix" => Some(OpForm::Prefix),
+            "postfix" => Some(OpForm::Postfix),
+            "infix" => Some(OpForm::Infix),
+            _ => None,
+        }),
+        max_size: lof("maxsize"),
+        min_size: lof("minsize"),
+        lspace: lof("lspace"),
+        rspace: lof("rspace"),
+        stretchy: flag("stretchy"),
+        symmetric: flag("symmetric"),
+        large_op: flag("largeop"),
+        movable_limits: flag("movablelimits"),
+        separator: flag("separator"),
+        fence: flag("fence"),
+    };
+    if op == Operator::new(t) {
+        MathElement::Op(t)
+    } else {
+        MathElement::Oper(op)
+    }
+}
+
+fn multiscripts(node: &XmlNode) -> MathElement {
+    let mut elems = node.elems();
+    let Some(base) = elems.next() else {
+        return MathElement::Err("<mmultiscripts> requires a base".into());
+    };
+    let mut post = Vec::new();
+    let mut pre = Vec::new();
+    let mut in_pre = false;
+    let mut pending: Option<Element> = None;
+    for e in elems {
+        if e.local() == "mprescripts" {
+            if pending.is_some() {
+                return MathElement::Err("<mmultiscripts> has an unpaired script".into());
+            }
+            in_pre = true;
+            continue;
+        }
+        let e = convert(e);
+        match pending.take() {
+            None => pending = Some(e),
+            Some(sub) => {
+                let pair = Pair::new(sub, e);
+                if in_pre {
+                    pre.push(pair)
+                } else {
+                    post.push(pair)
+                }
+            }
+        }
+    }
+    if pending.is_some() {
+        return MathElement::Err("<mmultiscripts> has an unpaired script".into());
+    }
+    MathElement::MultiScript {
+        base: Box::new(convert(base)),
+        post,
+        pre,
+    }
+}
+
+fn table_row(node: &XmlNode) -> TableRow {
+    let cells = match node.local() {
+        "mtr" => node.elems().map(table_cell).collect(),
+        // Equation labels can't be represented, so they're dropped.
+        "mlabeledtr" => node.elems().skip(1).map(table_cell).collect(),
+        name => vec![TableCell::new([Element::err(format!(
+            "expected <mtr> in <mtable>, found <{}>",
+            name
+        ))])],
+    };
+    TableRow {
+        cells,
+        a: attributes(node).map(Box::new),
+    }
+}
+
+fn table_cell(node: &XmlNode) -> TableCell {
+    if node.local() != "mtd" {
+        return TableCell::new([Element::err(format!(
+            "expected <mtd> in <mtr>, found <{}>",
+            node.local()
+        ))]);
+    }
+    let span = |attr| {
+        node.attr(attr)
+            .and_then(|s| s.trim().parse::<u32>().ok())
+            .unwrap_or(1)
+    };
+    TableCell {
+        col_span: span("columnspan"),
+        row_span: span("rowspan"),
+        elems: children(node),
+        a: attributes(node).map(Box::new),
+    }
+}
+
+/// Expand the deprecated `<mfenced>` element into a row of operators.
+fn fenced(node: &XmlNode) -> MathElement {
+    let fence = |t: &str| {
+        let mut chars = t.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(Element::oper(Operator {
+                fence: Some(true),
+                ..Operator::new(c)
+            })),
+            (None, _) => None,
+            _ => Some(Element::text(t)),
+        }
+    };
+    let seps: Vec<char> = node
+        .attr("separators")
+        .unwrap_or(",")
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    let mut out = Vec::new();
+    out.extend(fence(node.attr("open").unwrap_or("(")));
+    for (i, child) in node.elems().enumerate() {
+        if i > 0 {
+            if let Some(&sep) = seps.get(i - 1).or(seps.last()) {
+                out.push(Element::oper(Operator {
+                    separator: Some(true),
+                    ..Operator::new(sep)
+                }));
+            }
+        }
+        out.push(convert(child));
+    }
+    out.extend(fence(node.attr("close").unwrap_or(")")));
+    MathElement::Row(out)
+}
+
+/// Apply the global attributes of a MathML element.
+fn with_attributes(mut e: Element, node: &XmlNode) -> Element {
+    let Some(a) = attributes(node) else {
+        return e;
+    };
+    let dst = e.attributes_mut();
+    dst.class.extend(a.class);
+    dst.rtl |= a.rtl;
+    if a.display_style.is_some() {
+        dst.display_style = a.display_style;
+    }
+    if a.variant.is_some() {
+        dst.variant = a.variant;
+    }
+    if a.script_level.is_some() {
+        dst.script_level = a.script_level;
+    }
+    if let Some(data) = a.data {
+        dst.data.get_or_insert_with(BTreeMap::new).extend(data);
+    }
+    e
+}
+
+/// Parse the global attributes of a MathML element.
+fn attributes(node: &XmlNode) -> Option<Attributes> {
+    let mut a = Attributes::default();
+    for (k, v) in node.attrs.iter() {
+        let v = v.trim();
+        match k.as_str() {
+            "class" => a.class = v.split_whitespace().map(String::from).collect(),
+            "dir" => a.rtl = v == "rtl",
+            "displaystyle" => a.display_style = parse_bool(v),
+            "scriptlevel" => a.script_level = parse_script_level(v),
+            "mathvariant" => {
+                // Core MathML only allows "normal" on `<mi>`, which is
+                // handled as part of the identifier itself.
+                if node.local() != "mi" || v != "normal" {
+                    a.variant = parse_variant(v)
+                }
+            }
+            k => {
+                if let Some(key) = k.strip_prefix("data-") {
+                    a.data
+                        .get_or_insert_with(BTreeMap::new)
+                        .insert(key.to_string(), Value::Str(v.to_string()));
+                }
+            }
+        }
+    }
+    let empty = a.class.is_empty()
+        && !a.rtl
+        && a.display_style.is_none()
+        && a.script_level.is_none()
+        && a.variant.is_none()
+        && a.data.is_none();
+    (!empty).then_some(a)
+}
+
+fn parse_script_level(s: &str) -> Option<ScriptLevel> {
+    if let Some(v) = s.strip_prefix('+') {
+        v.parse().ok().map(ScriptLevel::Add)
+    } else if s.starts_with('-') {
+        s.parse().ok().map(ScriptLevel::Add)
+    } else {
+        s.parse().ok().map(ScriptLevel::Set)
+    }
+}
+
+/// Parse a `mathvariant` value.
+pub(crate) fn parse_variant(s: &str) -> Option<Variant> {
+    Some(match s {
+        "normal" => Variant::Normal,
+        "bold" => Variant::Bold,
+        "italic" => Variant::Italic,
+        "bold-italic" => Variant::BoldItalic,
+        "double-struck" => Variant::DoubleStruck,
+        "bold-fraktur" => Variant::BoldFraktur,
+        "script" => Variant::Script,
+        "bold-script" => Variant::BoldScript,
+        "fraktur" => Variant::Fraktur,
+        "sans-serif" => Variant::SansSerif,
+        "bold-sans-serif" => Variant::BoldSansSerif,
+        "sans-serif-italic" => Variant::SansSerifItalic,
+        "sans-serif-bold-italic" => Variant::SansSerifBoldItalic,
+        "monospace" => Variant::Monospace,
+        "initial" => Variant::Initial,
+        "tailed" => Variant::Tailed,
+        "looped" => Variant::Looped,
+        "stretched" => Variant::Stretched,
+        _ => return None,
+    })
+}
+
+/// Size of the MathML named spaces, in eighteenths of an em.
+fn named_space(s: &str) -> Option<f32> {
+    let (neg, s) = match s.strip_prefix("negative") {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let v = match s {
+        "veryverythinmathspace" => 1.0,
+        "verythinmathspace" => 2.0,
+        "thinmathspace" => 3.0,
+        "mediummathspace" => 4.0,
+        "thickmathspace" => 5.0,
+        "verythickmathspace" => 6.0,
+        "veryverythickmathspace" => 7.0,
+        _ => return None,
+    };
+    Some(if neg { -v } else { v })
+}
+
+/// Parse a MathML length. Only font-relative units can be represented, so
+/// absolute units are ignored, with the exception of zero lengths.
+pub(crate) fn parse_length(s: &str) -> Option<Length> {
+    let s = s.trim();
+    if let Some(v) = named_space(s) {
+        return Some(Length::Em(v / 18.0));
+    }
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | '-' | '+')))
+        .unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let v: f32 = num.parse().ok()?;
+    match unit {
+        "em" => Some(Length::Em(v)),
+        "ex" => Some(Length::Ex(v)),
+        _ if v == 0.0 => Some(Length::Em(0.0)),
+        _ => None,
+    }
+}
+
+/// Parse a length that may also be a percentage or a unitless multiple.
+pub(crate) fn parse_length_or_frac(s: &str) -> Option<LengthOrFraction> {
+    let s = s.trim();
+    if let Some(p) = s.strip_suffix('%') {
+        return p
+            .trim()
+            .parse::<f32>()
+            .ok()
+            .map(|v| LengthOrFraction::Frac(v / 100.0));
+    }
+    if let Ok(v) = s.parse::<f32>() {
+        return Some(LengthOrFraction::Frac(v));
+    }
+    parse_length(s).map(|l| match l {
+        Length::Em(v) => LengthOrFraction::Em(v),
+        Length::Ex(v) => LengthOrFraction::Ex(v),
+    })
+}
+
+/// Parse a fraction's `linethickness`, relative to the default thickness.
+fn parse_thickness(s: &str) -> Option<f32> {
+    match s.trim() {
+        "thin" => Some(0.5),
+        "medium" => Some(1.0),
+        "thick" => Some(2.0),
+        s => match parse_length_or_frac(s)? {
+            LengthOrFraction::Frac(v) => Some(v),
+            LengthOrFraction::Em(v) | LengthOrFraction::Ex(v) if v == 0.0 => Some(0.0),
+            _ => None,
+        },
+    }
+}
+
+/// Options for writing MathML.
+#[derive(Clone, Debug)]
+pub struct WriteOptions {
+    /// Indentation to use for each nesting level. If not set, the output is
+    /// written without any added whitespace.
+    pub indent: Option<String>,
+    /// Namespace prefix to put on every element, like `m` for `<m:mi>`. This
+    /// is mostly useful in XHTML and other XML documents.
+    pub prefix: Option<String>,
+    /// Whether to declare the MathML namespace on the root `<math>` element.
+    /// HTML5 doesn't require it, but XML documents do.
+    pub xmlns: bool,
+    /// Whether to write an element's `data` entries as `data-*` attributes.
+    /// Only strings, numbers, and booleans can be written out.
+    pub data_attributes: bool,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            indent: None,
+            prefix: None,
+            xmlns: true,
+            data_attributes: true,
+        }
+    }
+}
+
+impl WriteOptions {
+    /// Options for pretty-printed output, indenting with two spaces.
+    pub fn pretty() -> Self {
+        Self {
+            indent: Some("  ".into()),
+            ..Self::default()
+        }
+    }
+}
+
+impl Element {
+    /// Write the element out as a MathML `<math>` element, using the default
+    /// [`WriteOptions`].
+    pub fn to_mathml(&self) -> String {
+        self.to_mathml_with(&WriteOptions::default())
+    }
+
+    /// Write the element out as a MathML `<math>` element.
+    pub fn to_mathml_with(&self, opts: &WriteOptions) -> String {
+        let w = Writer { opts };
+        let mut root = w.node("math");
+        if opts.xmlns {
+            let attr = match &opts.prefix {
+                Some(p) => format!("xmlns:{}", p),
+                None => "xmlns".into(),
+            };
+            root = root.attr_add(attr, NAMESPACE);
+        }
+        if self.attributes().and_then(|a| a.display_style) == Some(true) {
+            root = root.attr_add("display", "block");
+        }
+        let root = root.child(w.element(self));
+        let mut out = String::new();
+        root.write(&mut out, opts.indent.as_deref(), 0);
+        out
+    }
+}
+
+struct Writer<'a> {
+    opts: &'a WriteOptions,
+}
+
+impl Writer<'_> {
+    fn node(&self, name: &str) -> XmlNode {
+        match &self.opts.prefix {
+            Some(p) => XmlNode::new(format!("{}:{}", p, name)),
+            None => XmlNode::new(name),
+        }
+    }
+
+    fn token(&self, name: &str, text: &str) -> XmlNode {
+        self.node(name).text_add(text)
+    }
+
+    fn parent<'e>(&self, name: &str, elems: impl IntoIterator<Item = &'e Element>) -> XmlNode {
+        elems
+            .into_iter()
+            .fold(self.node(name), |n, e| n.child(self.element(e)))
+    }
+
+    fn element(&self, elem: &Element) -> XmlNode {
+        let node = match elem.elem() {
+            MathElement::Op(t) => self.token("mo", &t.to_string()),
+            MathElement::Oper(op) => {
+                let mut n = self.token("mo", &op.t.to_string());
+                if let Some(form) = op.form {
+                    n = n.attr_add("form", form_str(form));
+                }
+                let lofs = [
+                    ("lspace", &op.lspace),
+                    ("rspace", &op.rspace),
+                    ("minsize", &op.min_size),
+                    ("maxsize", &op.max_size),
+                ];
+                for (k, v) in lofs {
+                    if let Some(v) = v {
+                        n = n.attr_add(k, length_or_frac_str(v));
+                    }
+                }
+                let flags = [
+                    ("stretchy", op.stretchy),
+                    ("symmetric", op.symmetric),
+                    ("largeop", op.large_op),
+                    ("movablelimits", op.movable_limits),
+                    ("separator", op.separator),
+                    ("fence", op.fence),
+                ];
+                for (k, v) in flags {
+                    if let Some(v) = v {
+                        n = n.attr_add(k, v.to_string());
+                    }
+                }
+                n
+            }
+            MathElement::ResolvedOper(op) => {
+                let mut n = self
+                    .token("mo", &op.t.to_string())
+                    .attr_add("form", form_str(op.form))
+                    .attr_add("lspace", length_str(&op.lspace))
+                    .attr_add("rspace", length_str(&op.rspace))
+                    .attr_add("minsize", length_str(&op.min_size));
+                // An unlimited size is MathML's default, and has no value
+                // to write.
+                if let Some(max) = &op.max_size {
+                    n = n.attr_add("maxsize", length_str(max));
+                }
+                n.attr_add("stretchy", op.stretchy.to_string())
+                    .attr_add("symmetric", op.symmetric.to_string())
+                    .attr_add("largeop", op.large_op.to_string())
+                    .attr_add("movablelimits", op.movable_limits.to_string())
+                    .attr_add("separator", op.separator.to_string())
+                    .attr_add("fence", op.fence.to_string())
+            }
+            MathElement::Text(t) => self.token("mtext", t),
+            MathElement::Id { t, normal } => {
+                let n = self.token("mi", t);
+                if *normal {
+                    n.attr_add("mathvariant", "normal")
+                } else {
+                    n
+                }
+            }
+            MathElement::Num(t) => self.token("mn", t),
+            MathElement::Err(t) | MathElement::Unknown { tag: t, .. } => {
+                self.node("merror").child(self.token("mtext", t))
+            }
+            MathElement::Space(s) => {
+                let mut n = self.node("mspace");
+                for (k, v) in [
+                    ("width", &s.width),
+                    ("height", &s.height),
+                    ("depth", &s.depth),
+                ] {
+                    if let Some(v) = v {
+                        n = n.attr_add(k, length_str(v));
+                    }
+                }
+                n
+            }
+            MathElement::Str(t) => self.token("ms", t),
+            MathElement::Phantom(elems) => self.parent("mphantom", elems),
+            MathElement::Row(elems) => self.parent("mrow", elems),
+            MathElement::Padding(p) => {
+                let mut n = self.parent("mpadded", &p.elems);
+                let lengths = [
+                    ("width", &p.width),
+                    ("height", &p.height),
+                    ("depth", &p.depth),
+                    ("lspace", &p.lspace),
+                    ("voffset", &p.voffset),
+                ];
+                for (k, v) in lengths {
+                    if let Some(v) = v {
+                        n = n.attr_add(k, length_str(v));
+                    }
+                }
+                n
+            }
+            MathElement::Frac {
+                line_thickness,
+                num,
+                den,
+            } => {
+                let n = self.parent("mfrac", [&**num, &**den]);
+                match line_thickness {
+                    Some(t) => n.attr_add("linethickness", format!("{}%", t * 100.0)),
+                    None => n,
+                }
+            }
+            MathElement::Sqrt(base) => self.parent("msqrt", [&**base]),
+            MathElement::Root { base, index } => self.parent("mroot", [&**base, &**index]),
+            MathElement::Sup { base, sup } => self.parent("msup", [&**base, &**sup]),
+            MathElement::Sub { base, sub } => self.parent("msub", [&**base, &**sub]),
+            MathElement::SubSup { base, sub, sup } => {
+                self.parent("msubsup", [&**base, &**sub, &**sup])
+            }
+            MathElement::Over { base, over, accent } => {
+                let n = self.parent("mover", [&**base, &**over]);
+                if *accent {
+                    n.attr_add("accent", "true")
+                } else {
+                    n
+                }
+            }
+            MathElement::Under {
+                base,
+                under,
+                accent_under,
+            } => {
+                let n = self.parent("munder", [&**base, &**under]);
+                if *accent_under {
+                    n.attr_add("accentunder", "true")
+                } else {
+                    n
+                }
+            }
+            MathElement::UnderOver {
+                base,
+                under,
+                over,
+                accent,
+                accent_under,
+            } => {
+                let mut n = self.parent("munderover", [&**base, &**under, &**over]);
+                if *accent {
+                    n = n.attr_add("accent", "true");
+                }
+                if *accent_under {
+                    n = n.attr_add("accentunder", "true");
+                }
+                n
+            }
+            MathElement::MultiScript { base, post, pre } => {
+                let pairs = |n: XmlNode, pairs: &[Pair]| {
+                    pairs.iter().fold(n, |n, p| {
+                        n.child(self.element(&p.sub)).child(self.element(&p.sup))
+                    })
+                };
+                let mut n = pairs(self.parent("mmultiscripts", [&**base]), post);
+                if !pre.is_empty() {
+                    n = pairs(n.child(self.node("mprescripts")), pre);
+                }
+                n
+            }
+            MathElement::Table { rows } => rows.iter().fold(self.node("mtable"), |n, row| {
+                let tr = row.cells.iter().fold(self.node("mtr"), |tr, cell| {
+                    let mut td = self.parent("mtd", &cell.elems);
+                    if cell.col_span != 1 {
+                        td = td.attr_add("columnspan", cell.col_span.to_string());
+                    }
+                    if cell.row_span != 1 {
+                        td = td.attr_add("rowspan", cell.row_span.to_string());
+                    }
+                    tr.child(self.attributes(td, cell.a.as_deref()))
+                });
+                n.child(self.attributes(tr, row.a.as_deref()))
+            }),
+        };
+        self.attributes(node, elem.attributes())
+    }
+
+    /// Write out the global attributes.
+    fn attributes(&self, mut n: XmlNode, a: Option<&Attributes>) -> XmlNode {
+        let Some(a) = a else {
+            return n;
+        };
+        if !a.class.is_empty() {
+            n = n.attr_add("class", a.class.join(" "));
+        }
+        if a.rtl {
+            n = n.attr_add("dir", "rtl");
+        }
+        if let Some(d) = a.display_style {
+            n = n.attr_add("displaystyle", d.to_string());
+        }
+        if let Some(s) = &a.script_level {
+            let s = match s {
+                ScriptLevel::Add(v) if *v >= 0 => format!("+{}", v),
+                ScriptLevel::Add(v) => v.to_string(),
+                ScriptLevel::Set(v) => v.to_string(),
+            };
+            n = n.attr_add("scriptlevel", s);
+        }
+        if let Some(v) = a.variant {
+            n = n.attr_add("mathvariant", variant_str(v));
+        }
+        if let (true, Some(data)) = (self.opts.data_attributes, &a.data) {
+            for (k, v) in data.iter() {
+                let v = match v {
+                    Value::Str(s) => s.clone(),
+                    Value::Bool(b) => b.to_string(),
+                    Value::Int(i) => i.to_string(),
+                    Value::F32(f) => f.to_string(),
+                    Value::F64(f) => f.to_string(),
+                    _ => continue,
+                };
+                n = n.attr_add(format!("data-{}", k), v);
+            }
+        }
+        n
+    }
+}
+
+fn form_str(form: OpForm) -> &'static str {
+    match form {
+        OpForm::Prefix => "prefix",
+        OpForm::Postfix => "postfix",
+        OpForm::Infix => "infix",
+    }
+}
+
+fn length_str(l: &Length) -> String {
+    match l {
+        Length::Em(v) => format!("{}em", v),
+        Length::Ex(v) => format!("{}ex", v),
+    }
+}
+
+fn length_or_frac_str(l: &LengthOrFraction) -> String {
+    match l {
+        LengthOrFraction::Em(v) => format!("{}em", v),
+        LengthOrFraction::Ex(v) => format!("{}ex", v),
+        LengthOrFraction::Frac(v) => format!("{}%", v * 100.0),
+    }
+}
+
+/// Get the `mathvariant` value for a variant.
+pub(crate) fn variant_str(v: Variant) -> &'static str {
+    match v {
+        Variant::Normal => "normal",
+        Variant::Bold => "bold",
+        Variant::Italic => "italic",
+        Variant::BoldItalic => "bold-italic",
+        Variant::DoubleStruck => "double-struck",
+        Variant::BoldFraktur => "bold-fraktur",
+        Variant::Script => "script",
+        Variant::BoldScript => "bold-script",
+        Variant::Fraktur => "fraktur",
+        Variant::SansSerif => "sans-serif",
+        Variant::BoldSansSerif => "bold-sans-serif",
+        Variant::SansSerifItalic => "sans-serif-italic",
+        Variant::SansSerifBoldItalic => "sans-serif-bold-italic",
+        Variant::Monospace => "monospace",
+        Variant::Initial => "initial",
+        Variant::Tailed => "tailed",
+        Variant::Looped => "looped",
+        Variant::Stretched => "stretched",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens() {
+        let e = parse(
+            r#"<math><mi>x</mi><mo>+</mo><mn>2</mn><mo>sin</mo><mi mathvariant="normal">d</mi></math>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            e,
+            Element::row([
+                Element::id("x"),
+                Element::op('+'),
+                Element::num("2"),
+                Element::text("sin"),
+                Element::id_normal("d"),
+            ])
+        );
+    }
+
+    #[test]
+    fn operator_properties() {
+        let e = parse(r#"<math><mo stretchy="false" lspace="0.2em" form="prefix">(</mo></math>"#)
+            .unwrap();
+        let MathElement::Oper(op) = e.elem() else {
+            panic!("expected an operator, got {:?}", e);
+        };
+        assert_eq!(op.t, '(');
+        assert_eq!(op.stretchy, Some(false));
+        assert_eq!(op.lspace, Some(LengthOrFraction::Em(0.2)));
+        assert_eq!(op.form, Some(OpForm::Prefix));
+    }
+
+    #[test]
+    fn layout() {
+        let e = parse(
+            r#"<math display="block">
+                <mfrac linethickness="0"><mi>n</mi><mi>k</mi></mfrac>
+                <msubsup><mi>x</mi><mi>i</mi><mn>2</mn></msubsup>
+                <mroot><mi>y</mi><mn>3</mn></mroot>
+            </math>"#,
+        )
+        .unwrap();
+        let expected = Element::row([
+            Element::frac_thickness(Element::id("n"), Element::id("k"), 0.0),
+            Element::sub_sup(Element::id("x"), Element::id("i"), Element::num("2")),
+            Element::root(Element::id("y"), Element::num("3")),
+        ])
+        .display_style(true);
+        assert_eq!(e, expected);
+    }
+
+    #[test]
+    fn multiscripts() {
+        let e = parse(
+            r#"<math><mmultiscripts><mi>X</mi><mi>a</mi><none/><mprescripts/><mi>b</mi><mi>c</mi></mmultiscripts></math>"#,
+        )
+        .unwrap();
+        let expected = Element::multiscript(
+            Element::id("X"),
+            [Pair::new(Element::id("a"), Element::row([]))],
+            [Pair::new(Element::id("b"), Element::id("c"))],
+        );
+        assert_eq!(e, expected);
+    }
+
+    #[test]
+    fn table() {
+        let e = parse(
+            r#"<math><mtable><mtr><mtd columnspan="2"><mn>1</mn></mtd></mtr><mtr><mtd><mn>2</mn></mtd><mtd><mn>3</mn></mtd></mtr></mtable></math>"#,
+        )
+        .unwrap();
+        let expected = Element::table([
+            TableRow::new([TableCell::new([Element::num("1")]).col_span(2)]),
+            TableRow::new([
+                TableCell::new([Element::num("2")]),
+                TableCell::new([Element::num("3")]),
+            ]),
+        ]);
+        assert_eq!(e, expected);
+    }
+
+    #[test]
+    fn attributes() {
+        let e = parse(
+            r#"<math><mrow class="a b" dir="rtl" scriptlevel="+1" mathvariant="bold" data-id="7" mathcolor="red"><mi>x</mi></mrow></math>"#,
+        )
+        .unwrap();
+        let a = e.attributes().unwrap();
+        assert_eq!(a.class, ["a", "b"]);
+        assert!(a.rtl);
+        assert_eq!(a.script_level, Some(ScriptLevel::Add(1)));
+        assert_eq!(a.variant, Some(Variant::Bold));
+        assert_eq!(a.data.as_ref().unwrap()["id"], Value::Str("7".into()));
+    }
+
+    #[test]
+    fn unsupported() {
+        let e = parse(
+            r#"<math><mi>x</mi><menclose><mi>y</mi></menclose><mfrac><mi>z</mi></mfrac></math>"#,
+        )
+        .unwrap();
+        let MathElement::Row(elems) = e.elem() else {
+            panic!("expected a row");
+        };
+        assert_eq!(elems[0], Element::id("x"));
+        assert!(matches!(elems[1].elem(), MathElement::Err(_)));
+        assert!(matches!(elems[2].elem(), MathElement::Err(_)));
+        assert!(parse("<math><mi>x</math>").is_err());
+    }
+
+    #[test]
+    fn write_tokens() {
+        let e = Element::row([Element::id("x"), Element::op('<'), Element::num("3")]);
+        assert_eq!(
+            e.to_mathml(),
+            r#"<math xmlns="http://www.w3.org/1998/Math/MathML"><mrow><mi>x</mi><mo>&lt;</mo><mn>3</mn></mrow></math>"#
+        );
+    }
+
+    #[test]
+    fn write_unknown() {
+        let e = Element::row([
+            Element::id("x"),
+            Element::new(MathElement::Unknown {
+                tag: "Glow".into(),
+                value: None,
+            }),
+        ]);
+        assert_eq!(
+            e.to_mathml(),
+            r#"<math xmlns="http://www.w3.org/1998/Math/MathML"><mrow><mi>x</mi><merror><mtext>Glow</mtext></merror></mrow></math>"#
+        );
+    }
+
+    #[test]
+    fn write_options() {
+        let e = Element::sup(Element::id("x"), Element::num("2"))
+            .class("eq")
+            .data("n", Value::Str("1".into()));
+        let opts = WriteOptions {
+            indent: Some(" ".into()),
+            prefix: Some("m".into()),
+            xmlns: true,
+            data_attributes: false,
+        };
+        assert_eq!(
+            e.to_mathml_with(&opts),
+            "<m:math xmlns:m=\"http://www.w3.org/1998/Math/MathML\">\n <m:msup class=\"eq\">\n  <m:mi>x</m:mi>\n  <m:mn>2</m:mn>\n </m:msup>\n</m:math>"
+        );
+    }
+
+    #[test]
+    fn write_operator() {
+        let e = Element::oper(Operator {
+            stretchy: Some(false),
+            max_size: Some(LengthOrFraction::Frac(2.0)),
+            ..Operator::new('(')
+        });
+        assert_eq!(
+            e.to_mathml_with(&WriteOptions {
+                xmlns: false,
+                ..WriteOptions::default()
+            }),
+            r#"<math><mo maxsize="200%" stretchy="false">(</mo></math>"#
+        );
+    }
+
+    #[test]
+    fn resolved_roundtrip() {
+        let mut e = Element::row([Element::op('('), Element::id("x"), Element::op(')')]);
+        e.resolve_operators();
+        let out = e.to_mathml();
+        assert!(out.contains(r#"minsize="1em" stretchy="true""#), "{}", out);
+        let mut back = parse(&out).unwrap();
+        back.resolve_operators();
+        assert_eq!(back, e);
+    }
+
+    #[test]
+    fn roundtrip() {
+        let e = Element::row([
+            Element::frac_thickness(Element::id("n"), Element::id("k"), 0.0),
+            Element::multiscript(
+                Element::id("X"),
+                [Pair::new(Element::id("a"), Element::row([]))],
+                [Pair::new(Element::id("b"), Element::id("c"))],
+            ),
+            Element::under_over_accent(
+                Element::op('∑'),
+                Element::id("i"),
+                Element::id("n"),
+                false,
+                true,
+            ),
+            Element::space(Space::width(Length::Em(0.5))),
+            Element::padding(Padding {
+                voffset: Some(Length::Ex(-1.0)),
+                ..Padding::new([Element::str("s")])
+            }),
+            Element::phantom([Element::sqrt(Element::id("y"))]),
+            Element::err("oops"),
+            Element::table([
+                TableRow::new([TableCell::new([Element::num("1")]).col_span(2)]),
+                TableRow::new([
+                    TableCell::new([Element::num("2")]),
+                    TableCell::new([Element::num("3")]).row_span(3),
+                ]),
+            ]),
+        ])
+        .display_style(true)
+        .rtl(true)
+        .script_level(ScriptLevel::Add(-1))
+        .variant(Variant::BoldScript)
+        .data("k", Value::Str("v".into()));
+        let out = e.to_mathml_with(&WriteOptions::pretty());
+        assert_eq!(parse(&out).unwrap(), e, "{}", out);
+    }
+}
@@ -0,0 +1,842 @@
+//! Reading and writing documents from other versions of the fog-math schema.
+//!
+//! Each past schema version is registered with [`Migrations::register`],
+//! along with a function that upgrades a document's contents to the next
+//! version. [`Migrations::new`] starts out with every version this crate has
+//! published. Upgrades work on the raw fog-pack [`Value`] of the document,
+//! since older documents may not fit the current [`math`](crate::math)
+//! types. Decoding an old document applies each upgrade in turn, from the
+//! document's version up to [`VERSION`].
+//!
+//! Going the other way, [`Migrations::downgrade`] writes a document for
+//! readers of an older version. Elements the older schema rejects are
+//! lowered to [`MathElement::Err`] holding their plain-text rendering.
+
+use std::collections::BTreeMap;
+
+use fog_pack::{
+    document::{Document, NewDocument},
+    schema::{Schema, SchemaBuilder},
+    types::{Hash, Value},
+    validator::*,
+};
+
+use crate::{
+    decode_document,
+    document::{decode, locate},
+    encode_document,
+    math::{Element, MathElement},
+    schema::{schema, VERSION},
+    Error,
+};
+
+/// Upgrade a document's contents from one schema version to the next.
+pub type Upgrade = fn(Value) -> Result<Value, String>;
+
+struct PastVersion {
+    version: u32,
+    schema: Schema,
+    upgrade: Upgrade,
+}
+
+/// The past schema versions a reader knows how to migrate.
+pub struct Migrations {
+    past: Vec<PastVersion>,
+}
+
+impl Default for Migrations {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Migrations {
+    /// Create a set of migrations holding every past version published by
+    /// this crate.
+    pub fn new() -> Self {
+        Self {
+            past: vec![PastVersion {
+                version: 1,
+                schema: version_1(),
+                upgrade: upgrade_1,
+            }],
+        }
+    }
+
+    /// Register a past schema version. `upgrade` turns the contents of a
+    /// document written with it into those of version `version + 1`.
+    ///
+    /// Registering a version that is already present replaces it. Fails if
+    /// `version` isn't older than [`VERSION`].
+    pub fn register(
+        &mut self,
+        version: u32,
+        schema: Schema,
+        upgrade: Upgrade,
+    ) -> Result<&mut Self, Error> {
+        if version >= VERSION {
+            return Err(Error::Migrate {
+                version,
+                msg: "only past schema versions can be registered".into(),
+            });
+        }
+        self.past.retain(|p| p.version != version);
+        self.past.push(PastVersion {
+            version,
+            schema,
+            upgrade,
+        });
+        self.past.sort_by_key(|p| p.version);
+        Ok(self)
+    }
+
+    /// Get the schema used for a version, if it's the current one or has
+    /// been registered.
+    pub fn schema(&self, version: u32) -> Option<&Schema> {
+        if version == VERSION {
+            return Some(schema());
+        }
+        self.past
+            .iter()
+            .find(|p| p.version == version)
+            .map(|p| &p.schema)
+    }
+
+    /// Get the schema version a schema hash belongs to.
+    pub fn version_of_hash(&self, hash: &Hash) -> Option<u32> {
+        if hash == schema().hash() {
+            return Some(VERSION);
+        }
+        self.past
+            .iter()
+            .find(|p| p.schema.hash() == hash)
+            .map(|p| p.version)
+    }
+
+    /// Get the schema version a document was written with, if known.
+    pub fn version_of(&self, doc: &Document) -> Option<u32> {
+        doc.schema_hash().and_then(|h| self.version_of_hash(h))
+    }
+
+    /// Decode a document written with the current schema or any registered
+    /// past version. Documents with any other schema are rejected, as
+    /// [`decode_document`] rejects them.
+    pub fn decode(&self, doc: &Document) -> Result<Vec<Element>, Error> {
+        let version = match self.version_of(doc) {
+            Some(VERSION) | None => return decode_document(doc),
+            Some(v) => v,
+        };
+        let mut value: Value = doc.deserialize()?;
+        for v in version..VERSION {
+            let step = self
+                .past
+                .iter()
+                .find(|p| p.version == v)
+                .ok_or_else(|| Error::Migrate {
+                    version: v,
+                    msg: format!("no upgrade to version {} is registered", v + 1),
+                })?;
+            value = (step.upgrade)(value).map_err(|msg| Error::Migrate { version: v, msg })?;
+        }
+        let elems: Vec<Element> = decode(&value).map_err(|source| Error::Decode {
+            path: locate(&value, &|e| decode::<Element>(e).is_ok()),
+            source,
+        })?;
+        // Make sure the upgrades produced something the current schema
+        // accepts.
+        encode_document(&elems)?;
+        Ok(elems)
+    }
+
+    /// Encode a list of equations for readers of an older schema version.
+    ///
+    /// Elements the older schema rejects are lowered: first their children
+    /// are lowered, then their attributes are dropped, and if the element
+    /// still doesn't pass, it's replaced with an error element holding its
+    /// plain-text rendering. Past schemas are expected to take
+    /// a list of equations, like the current one.
+    pub fn downgrade(&self, elems: &[Element], version: u32) -> Result<NewDocument, Error> {
+        if version == VERSION {
+            return encode_document(elems);
+        }
+        let schema = self.schema(version).ok_or_else(|| Error::Migrate {
+            version,
+            msg: "the schema version isn't registered".into(),
+        })?;
+        let elems: Vec<Element> = elems.iter().map(|e| lower(e, schema)).collect();
+        let doc = NewDocument::new(Some(schema.hash()), &elems)?;
+        schema.validate_new_doc(doc.clone())?;
+        Ok(doc)
+    }
+}
+
+/// Version 1 of the schema, as first published. Its root only accepted an
+/// empty list, so no other version 1 document can exist, but it already
+/// described tables as a bare array of rows.
+fn version_1() -> Schema {
+    let doc = SchemaBuilder::new(
+        ArrayValidator::new()
+            .items(EnumValidator::new().build())
+            .build(),
+    )
+    .type_add(
+        "OpForm",
+        EnumValidator::new()
+            .insert("Prefix", None)
+            .insert("Postfix", None)
+            .insert("Infix", None)
+            .build(),
+    )
+    .type_add(
+        "Attributes",
+        MapValidator::new()
+            .opt_add(
+                "class",
+                ArrayValidator::new()
+                    .items(StrValidator::new().build())
+                    .build(),
+            )
+            .opt_add(
+                "rtl",
+                BoolValidator::new()
+                    .comment("Set for right-to-left directionality")
+                    .build(),
+            )
+            .opt_add("display_style", BoolValidator::new().build())
+            .opt_add(
+                "variant",
+                StrValidator::new()
+                    .in_add("Normal")
+                    .in_add("Bold")
+                    .in_add("Italic")
+                    .in_add("BoldItalic")
+                    .in_add("DoubleStruck")
+                    .in_add("BoldFraktur")
+                    .in_add("Script")
+                    .in_add("BoldScript")
+                    .in_add("Fraktur")
+                    .in_add("SansSerif")
+                    .in_add("BoldSansSerif")
+                    .in_add("SansSerifItalic")
+                    .in_add("SansSerifBoldItalic")
+                    .in_add("Monospace")
+                    .in_add("Initial")
+                    .in_add("Tailed")
+                    .in_add("Looped")
+                    .in_add("Stretched")
+                    .build(),
+            )
+            .opt_add(
+                "script_level",
+                EnumValidator::new()
+                    .insert(
+                        "Set",
+                        Some(IntValidator::new().min(u32::MIN).max(u32::MAX).build()),
+                    )
+                    .insert(
+                        "Add",
+                        Some(IntValidator::new().min(i32::MIN).max(i32::MAX).build()),
+                    )
+                    .build(),
+            )
+            .opt_add(
+                "data",
+                MapValidator::new().values(Validator::new_any()).build(),
+            )
+            .build(),
+    )
+    .type_add(
+        "Length",
+        EnumValidator::new()
+            .insert("Em", Some(Validator::F32(F32Validator::new())))
+            .insert("Ex", Some(Validator::F32(F32Validator::new())))
+            .build(),
+    )
+    .type_add(
+        "LengthOrFraction",
+        EnumValidator::new()
+            .insert("Em", Some(Validator::F32(F32Validator::new())))
+            .insert("Ex", Some(Validator::F32(F32Validator::new())))
+            .insert("Frac", Some(Validator::F32(F32Validator::new())))
+            .build(),
+    )
+    .type_add(
+        "Pair",
+        MapValidator::new()
+            .req_add("sup", Validator::new_ref("Element"))
+            .req_add("sub", Validator::new_ref("Element"))
+            .build(),
+    )
+    .type_add(
+        "Element",
+        MapValidator::new()
+            .opt_add("a", Validator::new_ref("Attributes"))
+            .req_add(
+                "e",
+                EnumValidator::new()
+                    .insert("Op", Some(StrValidator::new().max_char(1).build()))
+                    .insert(
+                        "Oper",
+                        Some(
+                            MapValidator::new()
+                                .req_add("t", StrValidator::new().max_char(1).build())
+                                .opt_add(
+                                    "form",
+                                    EnumValidator::new()
+                                        .insert("Prefix", None)
+                                        .insert("Postfix", None)
+                                        .insert("Infix", None)
+                                        .build(),
+                                )
+                                .opt_add("max_size", Validator::new_ref("LengthOrFraction"))
+                                .opt_add("min_size", Validator::new_ref("LengthOrFraction"))
+                                .opt_add("lspace", Validator::new_ref("LengthOrFraction"))
+                                .opt_add("rspace", Validator::new_ref("LengthOrFraction"))
+                                .opt_add("stretchy", BoolValidator::new().build())
+                                .opt_add("symmetric", BoolValidator::new().build())
+                                .opt_add("large_op", BoolValidator::new().build())
+                                .opt_add("movable_limits", BoolValidator::new().build())
+                                .opt_add("separator", BoolValidator::new().build())
+                                .opt_add("fence", BoolValidator::new().build())
+                                .build(),
+                        ),
+                    )
+                    .insert(
+                        "ResolvedOper",
+                        Some(
+                            MapValidator::new()
+                                .req_add("t", StrValidator::new().max_char(1).build())
+                                .req_add(
+                                    "form",
+                                    EnumValidator::new()
+                                        .insert("Prefix", None)
+                                        .insert("Postfix", None)
+                                        .insert("Infix", None)
+                                        .build(),
+                                )
+                                .req_add("max_size", Validator::new_ref("Length"))
+                                .req_add("min_size", Validator::new_ref("Length"))
+                                .req_add("lspace", Validator::new_ref("Length"))
+                                .req_add("rspace", Validator::new_ref("Length"))
+                                .req_add("stretchy", BoolValidator::new().build())
+                                .req_add("symmetric", BoolValidator::new().build())
+                                .req_add("large_op", BoolValidator::new().build())
+                                .req_add("movable_limits", BoolValidator::new().build())
+                                .req_add("separator", BoolValidator::new().build())
+                                .req_add("fence", BoolValidator::new().build())
+                                .build(),
+                        ),
+                    )
+                    .insert("Text", Some(StrValidator::new().build()))
+                    .insert(
+                        "Id",
+                        Some(
+                            MapValidator::new()
+                                .req_add("t", StrValidator::new().build())
+                                .opt_add("normal", BoolValidator::new().build())
+                                .build(),
+                        ),
+                    )
+                    .insert("Num", Some(StrValidator::new().build()))
+                    .insert("Err", Some(StrValidator::new().build()))
+                    .insert(
+                        "Space",
+                        Some(
+                            MapValidator::new()
+                                .opt_add("width", Validator::new_ref("Length"))
+                                .opt_add("height", Validator::new_ref("Length"))
+                                .opt_add("depth", Validator::new_ref("Length"))
+                                .build(),
+                        ),
+                    )
+                    .insert("Str", Some(StrValidator::new().build()))
+                    .insert(
+                        "Phantom",
+                        Some(
+                            ArrayValidator::new()
+                                .items(Validator::new_ref("Element"))
+                                .build(),
+                        ),
+                    )
+                    .insert(
+                        "Row",
+                        Some(
+                            ArrayValidator::new()
+                                .items(Validator::new_ref("Element"))
+                                .build(),
+                        ),
+                    )
+                    .insert(
+                        "Padding",
+                        Some(
+                            MapValidator::new()
+                                .opt_add(
+                                    "elems",
+                                    ArrayValidator::new()
+                                        .items(Validator::new_ref("Element"))
+                                        .build(),
+                                )
+                                .opt_add("width", Validator::new_ref("Length"))
+                                .opt_add("height", Validator::new_ref("Length"))
+                                .opt_add("depth", Validator::new_ref("Length"))
+                                .opt_add("lspace", Validator::new_ref("Length"))
+                                .opt_add("voffset", Validator::new_ref("Length"))
+                                .build(),
+                        ),
+                    )
+                    .insert(
+                        "Frac",
+                        Some(
+                            MapValidator::new()
+                                .req_add("num", Validator::new_ref("Element"))
+                                .req_add("den", Validator::new_ref("Element"))
+                                .opt_add("line_thickness", Validator::F32(F32Validator::new()))
+                                .build(),
+                        ),
+                    )
+                    .insert("Sqrt", Some(Validator::new_ref("Element")))
+                    .insert(
+                        "Root",
+                        Some(
+                            MapValidator::new()
+                                .req_add("base", Validator::new_ref("Element"))
+                                .req_add("index", Validator::new_ref("Element"))
+                                .build(),
+                        ),
+                    )
+                    .insert(
+                        "Sup",
+                        Some(
+                            MapValidator::new()
+                                .req_add("base", Validator::new_ref("Element"))
+                                .req_add("sup", Validator::new_ref("Element"))
+                                .build(),
+                        ),
+                    )
+                    .insert(
+                        "Sub",
+                        Some(
+                            MapValidator::new()
+                                .req_add("base", Validator::new_ref("Element"))
+                                .req_add("sub", Validator::new_ref("Element"))
+                                .build(),
+                        ),
+                    )
+                    .insert(
+                        "SubSup",
+                        Some(
+                            MapValidator::new()
+                                .req_add("base", Validator::new_ref("Element"))
+                                .req_add("sup", Validator::new_ref("Element"))
+                                .req_add("sub", Validator::new_ref("Element"))
+                                .build(),
+                        ),
+                    )
+                    .insert(
+                        "Over",
+                        Some(
+                            MapValidator::new()
+                                .req_add("base", Validator::new_ref("Element"))
+                                .req_add("over", Validator::new_ref("Element"))
+                                .opt_add("accent", BoolValidator::new().build())
+                                .build(),
+                        ),
+                    )
+                    .insert(
+                        "Under",
+                        Some(
+                            MapValidator::new()
+                                .req_add("base", Validator::new_ref("Element"))
+                                .req_add("under", Validator::new_ref("Element"))
+                                .opt_add("accent_under", BoolValidator::new().build())
+                                .build(),
+                        ),
+                    )
+                    .insert(
+                        "UnderOver",
+                        Some(
+                            MapValidator::new()
+                                .req_add("base", Validator::new_ref("Element"))
+                                .req_add("under", Validator::new_ref("Element"))
+                                .req_add("over", Validator::new_ref("Element"))
+                                .opt_add("accent", BoolValidator::new().build())
+                                .opt_add("accent_under", BoolValidator::new().build())
+                                .build(),
+                        ),
+                    )
+                    .insert(
+                        "MultiScript",
+                        Some(
+                            MapValidator::new()
+                                .req_add("base", Validator::new_ref("Element"))
+                                .opt_add(
+                                    "post",
+                                    ArrayValidator::new()
+                                        .items(Validator::new_ref("Pair"))
+                                        .build(),
+                                )
+                                .opt_add(
+                                    "pre",
+                                    ArrayValidator::new()
+                                        .items(Validator::new_ref("Pair"))
+                                        .build(),
+                                )
+                                .build(),
+                        ),
+                    )
+                    .insert(
+                        "Table",
+                        Some(
+                            ArrayValidator::new()
+                                .items(
+                                    MapValidator::new()
+                                        .opt_add("a", Validator::new_ref("Attributes"))
+                                        .opt_add(
+                                            "cells",
+                                            ArrayValidator::new()
+                                                .items(Validator::new_ref("TableCell"))
+                                                .build(),
+                                        )
+                                        .build(),
+                                )
+                                .build(),
+                        ),
+                    )
+                    .build(),
+            )
+            .build(),
+    )
+    .type_add(
+        "TableCell",
+        MapValidator::new()
+            .opt_add("col_span", IntValidator::new().min(0).max(u32::MAX).build())
+            .opt_add("row_span", IntValidator::new().min(0).max(u32::MAX).build())
+            .opt_add(
+                "elems",
+                ArrayValidator::new()
+                    .items(Validator::new_ref("Element"))
+                    .build(),
+            )
+            .opt_add("a", Validator::new_ref("Attributes"))
+            .build(),
+    )
+    .name("fog-math")
+    .version(1)
+    .description("Formatted math, closely matching MathML.")
+    .build()
+    .expect("version 1 schema is valid");
+    Schema::from_doc(&doc).expect("version 1 schema is valid")
+}
+
+/// Upgrade from version 1: wrap every table's rows in a map, and drop the
+/// unset attributes that version 1 wrote out as null.
+fn upgrade_1(v: Value) -> Result<Value, String> {
+    Ok(match v {
+        Value::Map(m) => Value::Map(
+            m.into_iter()
+                .filter(|(k, v)| !(k == "a" && *v == Value::Null))
+                .map(|(k, v)| {
+                    let v = upgrade_1(v)?;
+                    let v = match (k.as_str(), v) {
+                        ("Table", Value::Array(rows)) => {
+                            let mut m = BTreeMap::new();
+                            m.insert("rows".to_string(), Value::Array(rows));
+                            Value::Map(m)
+                        }
+                        ("Table", _) => return Err("table isn't an array".into()),
+                        (_, v) => v,
+                    };
+                    Ok((k, v))
+                })
+                .collect::<Result<_, String>>()?,
+        ),
+        Value::Array(a) => Value::Array(a.into_iter().map(upgrade_1).collect::<Result<_, _>>()?),
+        v => v,
+    })
+}
+
+fn passes(e: &Element, schema: &Schema) -> bool {
+    NewDocument::new(Some(schema.hash()), [e])
+        .and_then(|doc| schema.validate_new_doc(doc))
+        .is_ok()
+}
+
+fn lower(e: &Element, schema: &Schema) -> Element {
+    if passes(e, schema) {
+        return e.clone();
+    }
+    let mut lowered = e.clone();
+    for child in children_mut(&mut lowered) {
+        *child = lower(child, schema);
+    }
+    if passes(&lowered, schema) {
+        return lowered;
+    }
+    let (m, _) = lowered.into_parts();
+    let bare = Element::new(m);
+    if passes(&bare, schema) {
+        bare
+    } else {
+        Element::err(e.to_plain_text())
+    }
+}
+
+fn children_mut(e: &mut Element) -> Vec<&mut Element> {
+    use MathElement::*;
+    match e.elem_mut() {
+        Op(_)
+        | Oper(_)
+        | ResolvedOper(_)
+        | Text(_)
+        | Id { .. }
+        | Num(_)
+        | Err(_)
+        | Space(_)
+        | Str(_)
+        | Unknown { .. } => Vec::new(),
+        Phantom(v) | Row(v) => v.iter_mut().collect(),
+        Padding(p) => p.elems.iter_mut().collect(),
+        Sqrt(b) => vec![&mut **b],
+        Frac { num: a, den: b, .. }
+        | Root { base: a, index: b }
+        | Sup { base: a, sup: b }
+        | Sub { base: a, sub: b }
+        | Over {
+            base: a, over: b, ..
+        }
+        | Under {
+            base: a, under: b, ..
+        } => vec![&mut **a, &mut **b],
+        SubSup { base, sub, sup } => vec![&mut **base, &mut **sub, &mut **sup],
+        UnderOver {
+            base, under, over, ..
+        } => vec![&mut **base, &mut **under, &mut **over],
+        MultiScript { base, post, pre } => {
+            let mut out = vec![&mut **base];
+            for p in post.iter_mut().chain(pre.iter_mut()) {
+                out.push(&mut *p.sub);
+                out.push(&mut *p.sup);
+            }
+            out
+        }
+        Table { rows } => rows
+            .iter_mut()
+            .flat_map(|r| r.cells.iter_mut())
+            .flat_map(|c| c.elems.iter_mut())
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::*;
+    use crate::schema::SchemaType;
+
+    /// A made-up version 0 of the schema, which had no `Str` element and,
+    /// like version 1, wrote tables as a bare array of rows.
+    fn version_0() -> Schema {
+        let mut types = BTreeMap::new();
+        let root = ArrayValidator::new()
+            .items(Element::validator(&mut types))
+            .build();
+        let Some(Validator::Enum(elems)) = types.get_mut("MathElement") else {
+            panic!("expected an enum");
+        };
+        elems.0.remove("Str");
+        elems.0.insert(
+            "Table".into(),
+            Some(
+                ArrayValidator::new()
+                    .items(Validator::new_ref("TableRow"))
+                    .build(),
+            ),
+        );
+        let doc = types
+            .into_iter()
+            .fold(SchemaBuilder::new(root), |b, (n, v)| b.type_add(&n, v))
+            .name("fog-math")
+            .version(0)
+            .build()
+            .unwrap();
+        Schema::from_doc(&doc).unwrap()
+    }
+
+    /// Version 0 documents are already valid version 1 documents.
+    fn upgrade_0(v: Value) -> Result<Value, String> {
+        Ok(v)
+    }
+
+    fn migrations() -> Migrations {
+        let mut m = Migrations::new();
+        m.register(0, version_0(), upgrade_0).unwrap();
+        m
+    }
+
+    fn old_doc(schema: &Schema) -> Document {
+        #[derive(serde::Serialize)]
+        struct Raw {
+            e: Old,
+        }
+        #[derive(serde::Serialize)]
+        enum Old {
+            Table(Vec<TableRow>),
+        }
+        let rows = vec![TableRow::new([
+            TableCell::new([Element::num("1")]),
+            TableCell::new([Element::id("x")]),
+        ])];
+        let doc = NewDocument::new(
+            Some(schema.hash()),
+            [Raw {
+                e: Old::Table(rows),
+            }],
+        )
+        .unwrap();
+        schema.validate_new_doc(doc).unwrap()
+    }
+
+    #[test]
+    fn versions() {
+        let m = migrations();
+        let current = schema()
+            .validate_new_doc(encode_document(&[Element::id("x")]).unwrap())
+            .unwrap();
+        assert_eq!(m.version_of(&current), Some(VERSION));
+        let old = old_doc(m.schema(0).unwrap());
+        assert_eq!(m.version_of(&old), Some(0));
+        assert_eq!(Migrations::new().version_of(&old), None);
+        assert_eq!(m.decode(&current).unwrap(), vec![Element::id("x")]);
+    }
+
+    #[test]
+    fn upgrade() {
+        let m = migrations();
+        let old = old_doc(m.schema(0).unwrap());
+        assert!(decode_document(&old).is_err());
+        assert_eq!(
+            m.decode(&old).unwrap(),
+            vec![Element::table([TableRow::new([
+                TableCell::new([Element::num("1")]),
+                TableCell::new([Element::id("x")]),
+            ])])]
+        );
+        let err = Migrations::new().decode(&old).unwrap_err();
+        assert!(matches!(err, Error::SchemaMismatch { .. }));
+    }
+
+    #[test]
+    fn failed_upgrade() {
+        let mut m = Migrations::new();
+        m.register(0, version_0(), |_| Err("unsupported".into()))
+            .unwrap();
+        let old = old_doc(m.schema(0).unwrap());
+        let err = m.decode(&old).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "couldn't migrate from schema version 0: unsupported"
+        );
+    }
+
+    #[test]
+    fn downgrade() {
+        let m = migrations();
+        let elems = vec![
+            Element::row([Element::id("s"), Element::op('='), Element::str("ab")])
+                .variant(Variant::Bold),
+            Element::matrix([[Element::num("1"), Element::num("0")]]),
+        ];
+        let doc = m.downgrade(&elems, 0).unwrap();
+        let doc = m.schema(0).unwrap().validate_new_doc(doc).unwrap();
+        let lowered: Vec<Element> = doc.deserialize().unwrap();
+        assert_eq!(
+            lowered,
+            vec![
+                Element::row([Element::id("s"), Element::op('='), Element::err("\"ab\"")])
+                    .variant(Variant::Bold),
+                Element::err("[1, 0]"),
+            ]
+        );
+        // The current version needs no lowering.
+        let doc = m.downgrade(&elems, VERSION).unwrap();
+        let doc = schema().validate_new_doc(doc).unwrap();
+        assert_eq!(decode_document(&doc).unwrap(), elems);
+        assert!(matches!(
+            m.downgrade(&elems, 7),
+            Err(Error::Migrate { version: 7, .. })
+        ));
+    }
+
+    #[test]
+    fn register() {
+        let mut m = Migrations::new();
+        assert!(matches!(
+            m.register(VERSION, version_0(), upgrade_0),
+            Err(Error::Migrate {
+                version: VERSION,
+                ..
+            })
+        ));
+        assert!(m.schema(VERSION + 1).is_none());
+        assert_eq!(m.version_of_hash(version_1().hash()), Some(1));
+    }
+
+    #[test]
+    fn baseline() {
+        // The hash of the schema as first published.
+        assert_eq!(
+            version_1().hash().to_string(),
+            "KPRTx5HqpT9bm6HsdwLAXFeK3dH8mr1jj8pL9LjpczcC"
+        );
+        let m = Migrations::new();
+        let schema = m.schema(1).unwrap();
+        let doc = NewDocument::new(Some(schema.hash()), Vec::<Element>::new()).unwrap();
+        let doc = schema.validate_new_doc(doc).unwrap();
+        assert_eq!(m.version_of(&doc), Some(1));
+        assert!(m.decode(&doc).unwrap().is_empty());
+
+        // Elements as version 1 wrote them, with null attributes and bare
+        // arrays of table rows.
+        let cell = Value::Map(
+            [(
+                "elems".to_string(),
+                Value::Array(vec![Value::Map(
+                    [
+                        (
+                            "e".to_string(),
+                            Value::Map([("Num".to_string(), Value::from("1"))].into()),
+                        ),
+                        ("a".to_string(), Value::Null),
+                    ]
+                    .into(),
+                )]),
+            )]
+            .into(),
+        );
+        let table = Value::Map(
+            [
+                (
+                    "e".to_string(),
+                    Value::Map(
+                        [(
+                            "Table".to_string(),
+                            Value::Array(vec![Value::Map(
+                                [("cells".to_string(), Value::Array(vec![cell]))].into(),
+                            )]),
+                        )]
+                        .into(),
+                    ),
+                ),
+                ("a".to_string(), Value::Null),
+            ]
+            .into(),
+        );
+        let upgraded = upgrade_1(Value::Array(vec![table])).unwrap();
+        assert_eq!(
+            decode::<Vec<Element>>(&upgraded).unwrap(),
+            vec![Element::table([TableRow::new([TableCell::new([
+                Element::num("1")
+            ])])])]
+        );
+    }
+}
@@ -0,0 +1,1095 @@
+//! Decoding of MathType equations stored as MTEF version 5 binary data.
+//!
+//! This is the format inside the `Equation Native` stream of MathType and
+//! Equation Editor OLE objects. The stream's 28-byte header is skipped if
+//! present, so either the whole stream or the bare MTEF data can be passed to
+//! [`parse`].
+//!
+//! Templates become the matching elements: fractions, roots, scripts, big
+//! operators with limits, fences, and so on. Character embellishments become
+//! accents. Sizes, colors, fonts, rulers, and nudges only affect layout, so
+//! they're read and then ignored. Boxes and strikes keep only their contents,
+//! and unknown templates become [`MathElement::Err`] nodes.
+
+use std::fmt;
+
+use crate::math::*;
+use crate::tree::into_elem;
+
+/// An error encountered while decoding MTEF data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MtefError {
+    /// Byte offset into the data where the error was found.
+    pub pos: usize,
+    /// Description of the error.
+    pub msg: String,
+}
+
+impl fmt::Display for MtefError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MTEF error at byte {}: {}", self.pos, self.msg)
+    }
+}
+
+impl std::error::Error for MtefError {}
+
+// Record types.
+const END: u8 = 0;
+const LINE: u8 = 1;
+const CHAR: u8 = 2;
+const TMPL: u8 = 3;
+const PILE: u8 = 4;
+const MATRIX: u8 = 5;
+const EMBELL: u8 = 6;
+const RULER: u8 = 7;
+const FONT_STYLE_DEF: u8 = 8;
+const SIZE: u8 = 9;
+const FULL: u8 = 10;
+const SUB: u8 = 11;
+const SUB2: u8 = 12;
+const SYM: u8 = 13;
+const SUBSYM: u8 = 14;
+const COLOR: u8 = 15;
+const COLOR_DEF: u8 = 16;
+const FONT_DEF: u8 = 17;
+const EQN_PREFS: u8 = 18;
+const ENCODING_DEF: u8 = 19;
+/// Record types from this on are followed by their length, so they can be
+/// skipped.
+const FUTURE: u8 = 100;
+
+// Record options.
+const OPT_NUDGE: u8 = 0x08;
+const OPT_CHAR_EMBELL: u8 = 0x01;
+const OPT_CHAR_ENC_CHAR_8: u8 = 0x04;
+const OPT_CHAR_ENC_CHAR_16: u8 = 0x10;
+const OPT_CHAR_ENC_NO_MTCODE: u8 = 0x20;
+const OPT_LINE_NULL: u8 = 0x01;
+const OPT_LP_RULER: u8 = 0x02;
+const OPT_LINE_LSPACE: u8 = 0x04;
+const OPT_COLOR_CMYK: u8 = 0x01;
+const OPT_COLOR_NAME: u8 = 0x04;
+
+// Template variations.
+const FENCE_L: u16 = 0x0001;
+const FENCE_R: u16 = 0x0002;
+const ROOT_NTH: u16 = 0x0001;
+const FR_SLASH: u16 = 0x0002;
+const FR_BASE: u16 = 0x0004;
+const BAR_DOUBLE: u16 = 0x0001;
+const AR_DOUBLE: u16 = 0x0001;
+const AR_LEFT: u16 = 0x0002;
+const AR_RIGHT: u16 = 0x0004;
+const INT_LOOP: u16 = 0x0004;
+const BO_SUM: u16 = 0x0040;
+const HB_TOP: u16 = 0x0001;
+const SU_PRECEDES: u16 = 0x0001;
+const DI_LEFT: u16 = 0x0001;
+const DI_RIGHT: u16 = 0x0002;
+const VE_LEFT: u16 = 0x0001;
+const VE_RIGHT: u16 = 0x0002;
+const VE_UNDER: u16 = 0x0004;
+const VE_HARPOON: u16 = 0x0008;
+
+/// Fence templates, by selector, with their default brackets.
+const FENCES: &[(u8, char, char)] = &[
+    (0, '⟨', '⟩'),
+    (1, '(', ')'),
+    (2, '{', '}'),
+    (3, '[', ']'),
+    (4, '|', '|'),
+    (5, '‖', '‖'),
+    (6, '⌊', '⌋'),
+    (7, '⌈', '⌉'),
+    (8, '⟦', '⟧'),
+    (9, '(', ')'),
+];
+
+/// Big operator templates, by selector, with their default operator.
+const BIG_OPS: &[(u8, char)] = &[
+    (15, '∫'),
+    (16, '∑'),
+    (17, '∏'),
+    (18, '∐'),
+    (19, '⋃'),
+    (20, '⋂'),
+    (21, '∫'),
+    (22, '∑'),
+];
+
+/// Embellishments, with their mark, and whether it goes under the character.
+const EMBELLS: &[(u8, char, bool)] = &[
+    (2, '˙', false),
+    (3, '¨', false),
+    (4, '\u{20DB}', false),
+    (8, '~', false),
+    (9, '^', false),
+    (11, '→', false),
+    (12, '←', false),
+    (13, '↔', false),
+    (14, '⇀', false),
+    (15, '↼', false),
+    (17, '¯', false),
+    (19, '⌢', false),
+    (20, '⌣', false),
+    (24, '\u{20DC}', false),
+    (25, '˙', true),
+    (26, '¨', true),
+    (29, '_', true),
+    (30, '~', true),
+    (33, '→', true),
+    (34, '←', true),
+    (35, '↔', true),
+];
+
+/// Prime embellishments, which become superscripts.
+const PRIMES: &[(u8, char)] = &[(5, '′'), (6, '″'), (7, '‵'), (18, '‴')];
+
+/// Embellishments drawn through the character, with the combining character
+/// that overlays it.
+const OVERLAYS: &[(u8, char)] = &[(10, '\u{338}'), (16, '\u{335}')];
+
+/// Spacing characters, with their width in em.
+const SPACES: &[(char, f32)] = &[
+    ('\u{EF00}', 0.0),
+    ('\u{EF01}', 1.0 / 6.0),
+    ('\u{EF02}', 2.0 / 9.0),
+    ('\u{EF03}', 5.0 / 18.0),
+    ('\u{EF04}', 1.0),
+    ('\u{EF05}', 2.0),
+    ('\u{EF08}', 1.0 / 18.0),
+    ('\u{2002}', 0.5),
+    ('\u{2003}', 1.0),
+    ('\u{2005}', 0.25),
+    ('\u{2009}', 1.0 / 6.0),
+    ('\u{200A}', 1.0 / 12.0),
+];
+
+/// Symbols usually written as identifiers.
+const SYMBOL_IDS: &str = "∞∅∂ℏℓ℘ℵ∇";
+
+/// How a character is written, from its typeface.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Style {
+    Text,
+    Function,
+    Variable,
+    LowerGreek,
+    UpperGreek,
+    Symbol,
+    Vector,
+    Number,
+    Space,
+    Marker,
+    /// An explicit font, or one this decoder doesn't know.
+    Other,
+}
+
+fn style(typeface: u8) -> Style {
+    match typeface.wrapping_sub(128) {
+        1 | 12 => Style::Text,
+        2 => Style::Function,
+        3 | 9 | 10 => Style::Variable,
+        4 => Style::LowerGreek,
+        5 => Style::UpperGreek,
+        6 | 11 | 22 => Style::Symbol,
+        7 => Style::Vector,
+        8 => Style::Number,
+        24 => Style::Space,
+        23 => Style::Marker,
+        _ => Style::Other,
+    }
+}
+
+/// Decode MTEF version 5 data, or an `Equation Native` stream holding it.
+pub fn parse(data: &[u8]) -> Result<Element, MtefError> {
+    let mut d = Decoder { data, pos: 0 };
+    // The OLE stream header starts with its own length.
+    if data.len() > 28 && data[..2] == [28, 0] {
+        d.pos = 28;
+    }
+    let version = d.u8()?;
+    if version != 5 {
+        return Err(MtefError {
+            pos: d.pos - 1,
+            msg: format!("unsupported MTEF version {}", version),
+        });
+    }
+    // Platform, product, and product version.
+    d.skip(4)?;
+    d.cstr()?;
+    // Equation options.
+    d.u8()?;
+    let elems = d.objects(true)?;
+    Ok(into_elem(elems))
+}
+
+/// A template's selector, variation, and contents.
+struct Template {
+    selector: u8,
+    variation: u16,
+    /// The slots, which are `None` if the line is null.
+    slots: Vec<Option<Vec<Element>>>,
+    /// Characters in the template, like fences and operators.
+    chars: Vec<char>,
+}
+
+impl Template {
+    fn slot(&self, i: usize) -> Option<Element> {
+        self.slots
+            .get(i)
+            .cloned()
+            .flatten()
+            .filter(|s| !s.is_empty())
+            .map(into_elem)
+    }
+
+    /// Get a slot, using an empty row if it's missing.
+    fn slot_or_empty(&self, i: usize) -> Element {
+        self.slot(i).unwrap_or_else(|| Element::row([]))
+    }
+
+    fn char_or(&self, i: usize, default: char) -> char {
+        self.chars.get(i).copied().unwrap_or(default)
+    }
+
+    fn has(&self, flag: u16) -> bool {
+        self.variation & flag != 0
+    }
+}
+
+/// A decoded record.
+enum Record {
+    End,
+    Line(Option<Vec<Element>>),
+    Char {
+        c: char,
+        style: Style,
+        embells: Vec<u8>,
+    },
+    Tmpl(Template),
+    Pile(Vec<Option<Vec<Element>>>),
+    Matrix(Element),
+    Embell(u8),
+    /// A record that only affects layout.
+    Skip,
+}
+
+/// A run of characters that are joined into one element.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Run {
+    Num,
+    Func,
+    Text,
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Decoder<'_> {
+    fn err(&self, msg: impl Into<String>) -> MtefError {
+        MtefError {
+            pos: self.pos,
+            msg: msg.into(),
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8, MtefError> {
+        let b = *self
+            .data
+            .get(self.pos)
+            .ok_or_else(|| self.err("unexpected end of data"))?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn u16(&mut self) -> Result<u16, MtefError> {
+        Ok(u16::from_le_bytes([self.u8()?, self.u8()?]))
+    }
+
+    /// Read an unsigned integer, which takes 3 bytes if it doesn't fit in
+    /// one.
+    fn uint(&mut self) -> Result<u16, MtefError> {
+        match self.u8()? {
+            255 => self.u16(),
+            b => Ok(b.into()),
+        }
+    }
+
+    fn skip(&mut self, n: usize) -> Result<(), MtefError> {
+        if self.pos + n > self.data.len() {
+            self.pos = self.data.len();
+            return Err(self.err("unexpected end of data"));
+        }
+        self.pos += n;
+        Ok(())
+    }
+
+    /// Read a null-terminated string.
+    fn cstr(&mut self) -> Result<String, MtefError> {
+        let rest = &self.data[self.pos..];
+        let len = rest
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or_else(|| self.err("unterminated string"))?;
+        self.pos += len + 1;
+        Ok(String::from_utf8_lossy(&rest[..len]).into_owned())
+    }
+
+    /// Skip the nudge offsets, if there are any.
+    fn nudge(&mut self, options: u8) -> Result<(), MtefError> {
+        if options & OPT_NUDGE != 0 {
+            let (dx, dy) = (self.u8()?, self.u8()?);
+            if (dx, dy) == (128, 128) {
+                self.skip(4)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Skip a dimension array in the equation preferences, which is packed
+    /// into nibbles.
+    fn dimensions(&mut self) -> Result<(), MtefError> {
+        let count = self.u8()?;
+        let mut nibbles = Vec::new();
+        let mut next = || -> Result<u8, MtefError> {
+            if nibbles.is_empty() {
+                let b = self.u8()?;
+                nibbles.extend([b & 0x0f, b >> 4]);
+            }
+            Ok(nibbles.pop().unwrap())
+        };
+        for _ in 0..count {
+            // The unit, then digits up to the terminator.
+            next()?;
+            while next()? != 0x0f {}
+        }
+        Ok(())
+    }
+
+    /// Read records up to an `END` record. At the top level, the data may
+    /// also just end.
+    fn objects(&mut self, top: bool) -> Result<Vec<Element>, MtefError> {
+        let mut line = Line::default();
+        loop {
+            if top && self.pos >= self.data.len() {
+                break;
+            }
+            match self.record()? {
+                Record::End => break,
+                Record::Line(Some(elems)) => line.extend(elems),
+                Record::Line(None) | Record::Skip | Record::Embell(_) => (),
+                Record::Char { c, style, embells } => line.char(c, style, &embells),
+                Record::Tmpl(t) => line.template(t),
+                Record::Pile(lines) => match <[_; 1]>::try_from(lines) {
+                    Ok([line_elems]) => line.extend(line_elems.unwrap_or_default()),
+                    Err(lines) => line.push(pile(lines)),
+                },
+                Record::Matrix(e) => line.push(e),
+            }
+        }
+        Ok(line.finish())
+    }
+
+    fn record(&mut self) -> Result<Record, MtefError> {
+        let tag = self.u8()?;
+        let rec = match tag {
+            END => Record::End,
+            LINE => {
+                let options = self.u8()?;
+                self.nudge(options)?;
+                if options & OPT_LINE_LSPACE != 0 {
+                    self.u16()?;
+                }
+                if options & OPT_LP_RULER != 0 {
+                    self.ruler()?;
+                }
+                if options & OPT_LINE_NULL != 0 {
+                    Record::Line(None)
+                } else {
+                    Record::Line(Some(self.objects(false)?))
+                }
+            }
+            CHAR => {
+                let options = self.u8()?;
+                self.nudge(options)?;
+                let style = style(self.u8()?);
+                let mut code = None;
+                if options & OPT_CHAR_ENC_NO_MTCODE == 0 {
+                    code = Some(u32::from(self.u16()?));
+                }
+                if options & OPT_CHAR_ENC_CHAR_8 != 0 {
+                    let b = self.u8()?;
+                    code = code.or(Some(b.into()));
+                }
+                if options & OPT_CHAR_ENC_CHAR_16 != 0 {
+                    let c = self.u16()?;
+                    code = code.or(Some(c.into()));
+                }
+                let c = code
+                    .and_then(char::from_u32)
+                    .unwrap_or(char::REPLACEMENT_CHARACTER);
+                let mut embells = Vec::new();
+                if options & OPT_CHAR_EMBELL != 0 {
+                    loop {
+                        match self.record()? {
+                            Record::End => break,
+                            Record::Embell(e) => embells.push(e),
+                            _ => return Err(self.err("expected an EMBELL record")),
+                        }
+                    }
+                }
+                Record::Char { c, style, embells }
+            }
+            TMPL => {
+                let options = self.u8()?;
+                self.nudge(options)?;
+                let selector = self.u8()?;
+                let mut variation = u16::from(self.u8()?);
+                if variation & 0x80 != 0 {
+                    variation = (variation & 0x7f) | (u16::from(self.u8()?) << 8);
+                }
+                // Template-specific options.
+                self.u8()?;
+                let mut t = Template {
+                    selector,
+                    variation,
+                    slots: Vec::new(),
+                    chars: Vec::new(),
+                };
+                loop {
+                    match self.record()? {
+                        Record::End => break,
+                        Record::Line(slot) => t.slots.push(slot),
+                        Record::Char { c, .. } => t.chars.push(c),
+                        Record::Pile(lines) => t.slots.push(Some(vec![pile(lines)])),
+                        Record::Matrix(e) => t.slots.push(Some(vec![e])),
+                        Record::Tmpl(_) | Record::Embell(_) | Record::Skip => (),
+                    }
+                }
+                Record::Tmpl(t)
+            }
+            PILE => {
+                let options = self.u8()?;
+                self.nudge(options)?;
+                // Horizontal and vertical alignment.
+                self.skip(2)?;
+                if options & OPT_LP_RULER != 0 {
+                    self.ruler()?;
+                }
+                let mut lines = Vec::new();
+                loop {
+                    match self.record()? {
+                        Record::End => break,
+                        Record::Line(line) => lines.push(line),
+                        _ => return Err(self.err("expected a LINE record in a pile")),
+                    }
+                }
+                Record::Pile(lines)
+            }
+            MATRIX => {
+                let options = self.u8()?;
+                self.nudge(options)?;
+                // Vertical alignment and justification.
+                self.skip(3)?;
+                let rows = usize::from(self.u8()?);
+                let cols = usize::from(self.u8()?);
+                // Partition line styles, two bits for each.
+                self.skip(((rows + 1) * 2).div_ceil(8))?;
+                self.skip(((cols + 1) * 2).div_ceil(8))?;
+                let mut cells = Vec::new();
+                loop {
+                    match self.record()? {
+                        Record::End => break,
+                        Record::Line(line) => cells.push(line.unwrap_or_default()),
+                        _ => return Err(self.err("expected a LINE record in a matrix")),
+                    }
+                }
+                let mut cells = cells.into_iter();
+                let table = Element::table((0..rows).map(|_| {
+                    TableRow::new(
+                        (0..cols).map(|_| TableCell::new(cells.next().unwrap_or_default())),
+                    )
+                }));
+                Record::Matrix(table)
+            }
+            EMBELL => {
+                let options = self.u8()?;
+                self.nudge(options)?;
+                Record::Embell(self.u8()?)
+            }
+            RULER => {
+                self.pos -= 1;
+                self.ruler()?;
+                Record::Skip
+            }
+            FONT_STYLE_DEF => {
+                self.skip(2)?;
+                Record::Skip
+            }
+            SIZE => {
+                match self.u8()? {
+                    101 => self.skip(2)?,
+                    100 => self.skip(3)?,
+                    _ => self.skip(1)?,
+                }
+                Record::Skip
+            }
+            FULL | SUB | SUB2 | SYM | SUBSYM => Record::Skip,
+            COLOR => {
+                self.uint()?;
+                Record::Skip
+            }
+            COLOR_DEF => {
+                let options = self.u8()?;
+                let values = if options & OPT_COLOR_CMYK != 0 { 4 } else { 3 };
+                self.skip(values * 2)?;
+                if options & OPT_COLOR_NAME != 0 {
+                    self.cstr()?;
+                }
+                Record::Skip
+            }
+            FONT_DEF => {
+                self.uint()?;
+                self.cstr()?;
+                Record::Skip
+            }
+            EQN_PREFS => {
+                self.u8()?;
+                // Sizes and spaces.
+                self.dimensions()?;
+                self.dimensions()?;
+                for _ in 0..self.u8()? {
+                    if self.u8()? != 0 {
+                        self.u8()?;
+                    }
+                }
+                Record::Skip
+            }
+            ENCODING_DEF => {
+                self.cstr()?;
+                Record::Skip
+            }
+            tag if tag >= FUTURE => {
+                let len = self.uint()?;
+                self.skip(len.into())?;
+                Record::Skip
+            }
+            tag => {
+                self.pos -= 1;
+                return Err(self.err(format!("unknown record type {}", tag)));
+            }
+        };
+        Ok(rec)
+    }
+
+    /// Skip a `RULER` record, including its tag.
+    fn ruler(&mut self) -> Result<(), MtefError> {
+        if self.u8()? != RULER {
+            self.pos -= 1;
+            return Err(self.err("expected a RULER record"));
+        }
+        let stops = usize::from(self.u8()?);
+        // Each tab stop has a type and an offset.
+        self.skip(stops * 3)
+    }
+}
+
+/// Build a line's elements, joining runs of characters and attaching
+/// scripts to what they follow.
+#[derive(Default)]
+struct Line {
+    elems: Vec<Element>,
+    run: Option<(Run, String)>,
+    /// Prescripts waiting for the element they go before.
+    pre: Option<Pair>,
+}
+
+impl Line {
+    fn flush(&mut self) {
+        if let Some((run, t)) = self.run.take() {
+            self.push_now(match run {
+                Run::Num => Element::num(t),
+                Run::Func => Element::id(t),
+                Run::Text => Element::text(t),
+            });
+        }
+    }
+
+    fn push_now(&mut self, e: Element) {
+        let e = match self.pre.take() {
+            Some(pair) => Element::multiscript(e, [], [pair]),
+            None => e,
+        };
+        self.elems.push(e);
+    }
+
+    fn push(&mut self, e: Element) {
+        self.flush();
+        self.push_now(e);
+    }
+
+    fn extend(&mut self, elems: Vec<Element>) {
+        for e in elems {
+            self.push(e);
+        }
+    }
+
+    fn char(&mut self, c: char, style: Style, embells: &[u8]) {
+        let run = match style {
+            Style::Marker => return,
+            Style::Text => Some(Run::Text),
+            Style::Function if c.is_alphanumeric() => Some(Run::Func),
+            Style::Number if c.is_ascii_digit() || c == '.' => Some(Run::Num),
+            _ if c.is_ascii_digit() => Some(Run::Num),
+            _ => None,
+        };
+        if let (Some(run), true) = (run, embells.is_empty()) {
+            match &mut self.run {
+                Some((r, t)) if *r == run => t.push(c),
+                _ => {
+                    self.flush();
+                    self.run = Some((run, c.to_string()));
+                }
+            }
+            return;
+        }
+        let e = match run {
+            Some(Run::Num) => Element::num(c),
+            Some(Run::Func) => Element::id(c),
+            Some(Run::Text) => Element::text(c),
+            None => char_elem(c, style),
+        };
+        let e = embells.iter().fold(e, |e, &emb| embellish(e, c, emb));
+        self.push(e);
+    }
+
+    fn template(&mut self, t: Template) {
+        match t.selector {
+            27..=29 => {
+                let sub = if t.selector == 28 { None } else { t.slot(0) };
+                let sup = if t.selector == 27 { None } else { t.slot(1) };
+                if t.has(SU_PRECEDES) {
+                    self.flush();
+                    let empty = || Element::row([]);
+                    self.pre = Some(Pair::new(
+                        sub.unwrap_or_else(empty),
+                        sup.unwrap_or_else(empty),
+                    ));
+                    return;
+                }
+                self.flush();
+                let base = self.elems.pop().unwrap_or_else(|| Element::row([]));
+                let e = match (sub, sup) {
+                    (Some(sub), Some(sup)) => Element::sub_sup(base, sub, sup),
+                    (Some(sub), None) => Element::sub(base, sub),
+                    (None, Some(sup)) => Element::sup(base, sup),
+                    (None, None) => base,
+                };
+                self.elems.push(e);
+            }
+            _ => {
+                for e in template(&t) {
+                    self.push(e);
+                }
+            }
+        }
+    }
+
+    fn finish(mut self) -> Vec<Element> {
+        self.flush();
+        if let Some(pair) = self.pre.take() {
+            self.elems
+                .push(Element::multiscript(Element::row([]), [], [pair]));
+        }
+        self.elems
+    }
+}
+
+/// Convert a character that isn't part of a run.
+fn char_elem(c: char, style: Style) -> Element {
+    if let Some((_, w)) = SPACES.iter().find(|(s, _)| *s == c) {
+        return Element::space(Space::width(Length::Em(*w)));
+    }
+    match style {
+        Style::Space => Element::space(Space::width(Length::Em(0.0))),
+        Style::UpperGreek => Element::id_normal(c),
+        Style::Vector if c.is_alphabetic() => Element::id(c).variant(Variant::Bold),
+        Style::Variable | Style::LowerGreek | Style::Function | Style::Other
+            if c.is_alphabetic() =>
+        {
+            Element::id(c)
+        }
+        _ if SYMBOL_IDS.contains(c) => Element::id_normal(c),
+        _ => Element::op(c),
+    }
+}
+
+/// Add an embellishment to a character.
+fn embellish(e: Element, c: char, emb: u8) -> Element {
+    if let Some((_, mark, under)) = EMBELLS.iter().find(|(n, ..)| *n == emb) {
+        return if *under {
+            Element::under_accent(e, Element::op(*mark))
+        } else {
+            Element::over_accent(e, Element::op(*mark))
+        };
+    }
+    if let Some((_, p)) = PRIMES.iter().find(|(n, _)| *n == emb) {
+        return Element::sup(e, Element::op(*p));
+    }
+    if let Some((_, o)) = OVERLAYS.iter().find(|(n, _)| *n == emb) {
+        return Element::text(format!("{}{}", c, o));
+    }
+    e
+}
+
+/// Convert a pile to a single-column table.
+fn pile(lines: Vec<Option<Vec<Element>>>) -> Element {
+    Element::table(
+        lines
+            .into_iter()
+            .map(|l| TableRow::new([TableCell::new(l.unwrap_or_default())])),
+    )
+}
+
+/// Convert a template, other than scripts, to the elements it stands for.
+fn template(t: &Template) -> Vec<Element> {
+    let e = match t.selector {
+        0..=9 => {
+            let (_, l, r) = FENCES.iter().find(|(s, ..)| *s == t.selector).unwrap();
+            // Fences have no flags for which sides are present when both are.
+            let both = t.selector == 9 || !t.has(FENCE_L | FENCE_R);
+            let mut chars = t.chars.iter().copied();
+            let mut elems = Vec::new();
+            if both || t.has(FENCE_L) {
+                elems.push(Element::op(chars.next().unwrap_or(*l)));
+            }
+            elems.extend(t.slots.first().cloned().flatten().unwrap_or_default());
+            if both || t.has(FENCE_R) {
+                elems.push(Element::op(chars.next().unwrap_or(*r)));
+            }
+            Element::row(elems)
+        }
+        10 if t.has(ROOT_NTH) => Element::root(t.slot_or_empty(0), t.slot_or_empty(1)),
+        10 => Element::sqrt(t.slot_or_empty(0)),
+        11 if t.has(FR_SLASH | FR_BASE) => {
+            Element::row([t.slot_or_empty(0), Element::op('/'), t.slot_or_empty(1)])
+        }
+        11 => Element::frac(t.slot_or_empty(0), t.slot_or_empty(1)),
+        12 => Element::under_accent(t.slot_or_empty(0), Element::op('_')),
+        13 if t.has(BAR_DOUBLE) => Element::over_accent(t.slot_or_empty(0), Element::op('═')),
+        13 => Element::over_accent(t.slot_or_empty(0), Element::op('‾')),
+        14 => {
+            let arrow = match (t.has(AR_DOUBLE), t.has(AR_LEFT), t.has(AR_RIGHT)) {
+                (false, true, true) => '↔',
+                (false, true, false) => '←',
+                (false, ..) => '→',
+                (true, true, true) => '⇔',
+                (true, true, false) => '⇐',
+                (true, ..) => '⇒',
+            };
+            scripts(Element::op(arrow), t.slot(0), t.slot(1), true)
+        }
+        15..=20 => {
+            let (_, default) = BIG_OPS.iter().find(|(s, _)| *s == t.selector).unwrap();
+            let default = match (t.selector, t.variation & 0x03, t.has(INT_LOOP)) {
+                (15, 2, false) => '∬',
+                (15, 3, false) => '∭',
+                (15, 1, true) => '∮',
+                (15, 2, true) => '∯',
+                (15, 3, true) => '∰',
+                _ => *default,
+            };
+            let op = Element::op(t.char_or(0, default));
+            let op = scripts(op, t.slot(2), t.slot(1), t.has(BO_SUM));
+            return vec![op, t.slot_or_empty(0)];
+        }
+        21 | 22 => {
+            let (_, default) = BIG_OPS.iter().find(|(s, _)| *s == t.selector).unwrap();
+            let op = Element::op(t.char_or(0, *default));
+            scripts(op, t.slot(0), t.slot(1), t.has(BO_SUM))
+        }
+        23 => scripts(t.slot_or_empty(0), t.slot(2), t.slot(1), true),
+        24 | 25 => {
+            let top = t.has(HB_TOP);
+            let mark = match (t.selector, top) {
+                (24, true) => '⏞',
+                (24, false) => '⏟',
+                (_, true) => '⎴',
+                (_, false) => '⎵',
+            };
+            let mark = Element::op(t.char_or(0, mark));
+            let base = t.slot_or_empty(0);
+            let label = t.slot(1);
+            match (top, label) {
+                (true, Some(l)) => Element::over(Element::over(base, mark), l),
+                (true, None) => Element::over(base, mark),
+                (false, Some(l)) => Element::under(Element::under(base, mark), l),
+                (false, None) => Element::under(base, mark),
+            }
+        }
+        26 => {
+            let e = Element::row([Element::op('⟌'), t.slot_or_empty(0)]);
+            match t.slot(1) {
+                Some(q) => Element::over(e, q),
+                None => e,
+            }
+        }
+        30 => {
+            let mut elems = Vec::new();
+            if t.has(DI_LEFT) {
+                elems.extend([Element::op('⟨'), t.slot_or_empty(0)]);
+            }
+            elems.push(Element::op('|'));
+            if t.has(DI_RIGHT) {
+                elems.extend([t.slot_or_empty(1), Element::op('⟩')]);
+            }
+            Element::row(elems)
+        }
+        31 => {
+            let arrow = match (t.has(VE_HARPOON), t.has(VE_LEFT), t.has(VE_RIGHT)) {
+                (_, true, true) => '↔',
+                (false, true, false) => '←',
+                (false, ..) => '→',
+                (true, true, false) => '↼',
+                (true, ..) => '⇀',
+            };
+            if t.has(VE_UNDER) {
+                Element::under_accent(t.slot_or_empty(0), Element::op(arrow))
+            } else {
+                Element::over_accent(t.slot_or_empty(0), Element::op(arrow))
+            }
+        }
+        32 => Element::over_accent(t.slot_or_empty(0), Element::op('~')),
+        33 => Element::over_accent(t.slot_or_empty(0), Element::op('^')),
+        34 => Element::over_accent(t.slot_or_empty(0), Element::op('⌒')),
+        35..=37 => t.slot_or_empty(0),
+        s => Element::err(format!("unknown MTEF template {}", s)),
+    };
+    vec![e]
+}
+
+/// Attach limits to a base, either as scripts or under and over it.
+fn scripts(base: Element, over: Option<Element>, under: Option<Element>, limits: bool) -> Element {
+    match (under, over, limits) {
+        (Some(u), Some(o), true) => Element::under_over(base, u, o),
+        (Some(u), None, true) => Element::under(base, u),
+        (None, Some(o), true) => Element::over(base, o),
+        (Some(u), Some(o), false) => Element::sub_sup(base, u, o),
+        (Some(u), None, false) => Element::sub(base, u),
+        (None, Some(o), false) => Element::sup(base, o),
+        (None, None, _) => base,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VARIABLE: u8 = 131;
+    const NUMBER: u8 = 136;
+    const FUNCTION: u8 = 130;
+    const SYMBOL: u8 = 134;
+
+    /// The MTEF header, followed by a size record.
+    fn header() -> Vec<u8> {
+        let mut d = vec![5, 1, 0, 6, 9];
+        d.extend(b"DSMT6\0");
+        d.push(0);
+        d.extend([SIZE, 100, 3, 0x40, 0x01, FULL]);
+        d
+    }
+
+    fn ch(typeface: u8, c: char) -> Vec<u8> {
+        let mut d = vec![CHAR, 0, typeface];
+        d.extend((c as u16).to_le_bytes());
+        d
+    }
+
+    fn line(objects: &[Vec<u8>]) -> Vec<u8> {
+        let mut d = vec![LINE, 0];
+        d.extend(objects.concat());
+        d.push(END);
+        d
+    }
+
+    fn tmpl(selector: u8, variation: u8, contents: &[Vec<u8>]) -> Vec<u8> {
+        let mut d = vec![TMPL, 0, selector, variation, 0];
+        d.extend(contents.concat());
+        d.push(END);
+        d
+    }
+
+    const NULL: [u8; 2] = [LINE, OPT_LINE_NULL];
+
+    fn equation(objects: &[Vec<u8>]) -> Vec<u8> {
+        let mut d = header();
+        d.extend(line(objects));
+        d.push(END);
+        d
+    }
+
+    #[test]
+    fn chars_and_runs() {
+        let data = equation(&[
+            ch(FUNCTION, 's'),
+            ch(FUNCTION, 'i'),
+            ch(FUNCTION, 'n'),
+            ch(VARIABLE, 'x'),
+            ch(SYMBOL, '+'),
+            ch(NUMBER, '1'),
+            ch(NUMBER, '2'),
+            ch(NUMBER, '.'),
+            ch(NUMBER, '5'),
+            ch(SYMBOL, '∞'),
+            ch(152, '\u{EF04}'),
+        ]);
+        assert_eq!(
+            parse(&data).unwrap(),
+            Element::row([
+                Element::id("sin"),
+                Element::id("x"),
+                Element::op('+'),
+                Element::num("12.5"),
+                Element::id_normal("∞"),
+                Element::space(Space::width(Length::Em(1.0))),
+            ])
+        );
+    }
+
+    #[test]
+    fn fractions_roots_and_scripts() {
+        let data = equation(&[
+            tmpl(
+                11,
+                0,
+                &[line(&[ch(NUMBER, '1')]), line(&[ch(VARIABLE, 'x')])],
+            ),
+            ch(SYMBOL, '='),
+            ch(VARIABLE, 'y'),
+            tmpl(
+                29,
+                0,
+                &[line(&[ch(VARIABLE, 'i')]), line(&[ch(NUMBER, '2')])],
+            ),
+            tmpl(
+                10,
+                1,
+                &[line(&[ch(VARIABLE, 'a')]), line(&[ch(NUMBER, '3')])],
+            ),
+            tmpl(10, 0, &[line(&[ch(VARIABLE, 'b')]), NULL.to_vec()]),
+        ]);
+        assert_eq!(
+            parse(&data).unwrap(),
+            Element::row([
+                Element::frac(Element::num("1"), Element::id("x")),
+                Element::op('='),
+                Element::sub_sup(Element::id("y"), Element::id("i"), Element::num("2")),
+                Element::root(Element::id("a"), Element::num("3")),
+                Element::sqrt(Element::id("b")),
+            ])
+        );
+    }
+
+    #[test]
+    fn big_operators_and_fences() {
+        let data = equation(&[
+            tmpl(
+                16,
+                0x70,
+                &[
+                    line(&[ch(VARIABLE, 'i')]),
+                    line(&[ch(VARIABLE, 'i'), ch(SYMBOL, '='), ch(NUMBER, '1')]),
+                    line(&[ch(VARIABLE, 'n')]),
+                    ch(SYMBOL, '∑'),
+                ],
+            ),
+            tmpl(
+                1,
+                FENCE_L as u8 | FENCE_R as u8,
+                &[line(&[ch(VARIABLE, 'z')]), ch(SYMBOL, '('), ch(SYMBOL, ']')],
+            ),
+            tmpl(
+                15,
+                0x01,
+                &[line(&[ch(VARIABLE, 'f')]), NULL.to_vec(), NULL.to_vec()],
+            ),
+        ]);
+        assert_eq!(
+            parse(&data).unwrap(),
+            Element::row([
+                Element::under_over(
+                    Element::op('∑'),
+                    Element::row([Element::id("i"), Element::op('='), Element::num("1")]),
+                    Element::id("n"),
+                ),
+                Element::id("i"),
+                Element::row([Element::op('('), Element::id("z"), Element::op(']')]),
+                Element::op('∫'),
+                Element::id("f"),
+            ])
+        );
+    }
+
+    #[test]
+    fn piles_and_matrices() {
+        let mut data = header();
+        data.extend([PILE, 0, 1, 1]);
+        data.extend(line(&[ch(VARIABLE, 'a')]));
+        data.extend(line(&[ch(VARIABLE, 'b')]));
+        data.push(END);
+        data.extend([MATRIX, 0, 0, 0, 0, 2, 2, 0, 0]);
+        for c in ['1', '0', '0', '1'] {
+            data.extend(line(&[ch(NUMBER, c)]));
+        }
+        data.extend([END, END]);
+        assert_eq!(
+            parse(&data).unwrap(),
+            Element::row([
+                Element::table([
+                    TableRow::new([TableCell::new([Element::id("a")])]),
+                    TableRow::new([TableCell::new([Element::id("b")])]),
+                ]),
+                Element::matrix([
+                    [Element::num("1"), Element::num("0")],
+                    [Element::num("0"), Element::num("1")],
+                ]),
+            ])
+        );
+    }
+
+    #[test]
+    fn embellishments() {
+        let mut x = vec![CHAR, OPT_CHAR_EMBELL, VARIABLE, b'x', 0];
+        x.extend([EMBELL, 0, 9, EMBELL, 0, 5, END]);
+        let mut data = vec![28, 0];
+        data.resize(28, 0);
+        data.extend(equation(&[x, tmpl(13, 0, &[line(&[ch(VARIABLE, 'v')])])]));
+        assert_eq!(
+            parse(&data).unwrap(),
+            Element::row([
+                Element::sup(
+                    Element::over_accent(Element::id("x"), Element::op('^')),
+                    Element::op('′'),
+                ),
+                Element::over_accent(Element::id("v"), Element::op('‾')),
+            ])
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let mut data = equation(&[ch(VARIABLE, 'x')]);
+        data[0] = 3;
+        assert_eq!(parse(&data).unwrap_err().msg, "unsupported MTEF version 3");
+        let data = equation(&[ch(VARIABLE, 'x')]);
+        assert!(parse(&data[..data.len() - 3]).is_err());
+        let data = equation(&[tmpl(99, 0, &[line(&[ch(VARIABLE, 'x')])])]);
+        assert_eq!(
+            parse(&data).unwrap(),
+            Element::err("unknown MTEF template 99")
+        );
+    }
+}
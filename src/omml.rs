@@ -0,0 +1,944 @@
+//! Conversion between Office Math Markup Language (OMML) and fog-math
+//! elements.
+//!
+//! OMML is the equation format stored inside `.docx` files. Its math objects
+//! map fairly directly onto fog-math elements, with a few exceptions: n-ary
+//! operators and functions carry their operand inside them, so they are
+//! spliced into the surrounding row when parsing, and text runs are split
+//! into individual identifier, number, and operator tokens. Objects with no
+//! fog-math equivalent become [`MathElement::Err`] nodes.
+
+use crate::math::*;
+use crate::tree::{into_elem, op_char};
+use crate::xml::{self, XmlError, XmlNode};
+
+/// The OMML namespace.
+pub const NAMESPACE: &str = "http://schemas.openxmlformats.org/officeDocument/2006/math";
+
+/// Combining accent characters, and the spacing characters used for them in
+/// fog-math elements.
+const ACCENTS: &[(char, char)] = &[
+    ('\u{302}', '^'),
+    ('\u{30C}', 'ˇ'),
+    ('\u{303}', '~'),
+    ('\u{301}', '´'),
+    ('\u{300}', '`'),
+    ('\u{307}', '˙'),
+    ('\u{308}', '¨'),
+    ('\u{306}', '˘'),
+    ('\u{304}', '¯'),
+    ('\u{305}', '¯'),
+    ('\u{20D7}', '→'),
+    ('\u{20D6}', '←'),
+    ('\u{20E1}', '↔'),
+];
+
+/// N-ary operators, which become `<m:nary>` when written.
+const NARY: &[char] = &[
+    '∑', '∏', '∐', '∫', '∬', '∭', '∮', '∯', '∰', '⋀', '⋁', '⋂', '⋃', '⨀', '⨁', '⨂', '⨄', '⨆',
+];
+
+/// N-ary operators whose limits go to the side by default.
+const INTEGRALS: &[char] = &['∫', '∬', '∭', '∮', '∯', '∰'];
+
+/// Grouping characters, which become `<m:groupChr>` when written.
+const GROUP_CHARS: &[char] = &['⏞', '⏟', '⏜', '⏝', '⎴', '⎵', '←', '→', '↔', '⇐', '⇒', '⇔'];
+
+const OPEN: &[char] = &['(', '[', '{', '⟨', '⌊', '⌈', '|', '‖'];
+const CLOSE: &[char] = &[')', ']', '}', '⟩', '⌋', '⌉', '|', '‖'];
+
+/// Parse an OMML document. The first `<m:oMathPara>` or `<m:oMath>` element
+/// is converted, so this also accepts a larger WordprocessingML fragment.
+///
+/// Only XML syntax errors are returned as errors. Unsupported or malformed
+/// OMML becomes [`MathElement::Err`] nodes within the returned tree.
+pub fn parse(src: &str) -> Result<Element, XmlError> {
+    let root = xml::parse(src)?;
+    let para = root.find("oMathPara");
+    let Some(math) = para.or_else(|| root.find("oMath")) else {
+        return Ok(Element::err("no <m:oMath> element found"));
+    };
+    if math.local() == "oMath" {
+        return Ok(arg(math));
+    }
+    let mut eqs: Vec<Element> = math
+        .elems()
+        .filter(|e| e.local() == "oMath")
+        .map(arg)
+        .collect();
+    let e = if eqs.len() == 1 {
+        eqs.pop().unwrap()
+    } else {
+        Element::table(eqs.into_iter().map(|e| TableRow::new([TableCell::from(e)])))
+    };
+    Ok(e.display_style(true))
+}
+
+/// Convert the contents of an argument element like `<m:e>` or `<m:num>`.
+fn arg(node: &XmlNode) -> Element {
+    into_elem(items(node))
+}
+
+/// Convert the contents of an element into a list of elements.
+fn items(node: &XmlNode) -> Vec<Element> {
+    let mut out = Vec::new();
+    node.elems().for_each(|e| convert(e, &mut out));
+    out
+}
+
+/// Find a named argument element and convert it. A missing argument is
+/// treated as empty.
+fn child(node: &XmlNode, name: &str) -> Element {
+    match node.elems().find(|e| e.local() == name) {
+        Some(c) => arg(c),
+        None => Element::row([]),
+    }
+}
+
+/// Get the `m:val` of a property within an object's property element.
+fn prop<'a>(node: &'a XmlNode, name: &str) -> Option<&'a str> {
+    let pr = node.elems().find(|e| e.local().ends_with("Pr"))?;
+    let p = pr.elems().find(|e| e.local() == name)?;
+    Some(p.attr("val").unwrap_or(""))
+}
+
+/// Get an on/off property. A property with no value is on.
+fn flag(node: &XmlNode, name: &str) -> bool {
+    matches!(prop(node, name), Some("" | "1" | "on" | "true"))
+}
+
+/// Get a single-character property, where an empty value means no character.
+fn char_prop(node: &XmlNode, name: &str, default: Option<char>) -> Option<char> {
+    match prop(node, name) {
+        Some(v) => v.chars().next(),
+        None => default,
+    }
+}
+
+fn fence(c: char) -> Element {
+    Element::oper(Operator {
+        fence: Some(true),
+        ..Operator::new(c)
+    })
+}
+
+fn large_op(c: char) -> Element {
+    Element::oper(Operator {
+        large_op: Some(true),
+        ..Operator::new(c)
+    })
+}
+
+/// Check if an element is in the math namespace, assuming the usual `m`
+/// prefix is used for it.
+fn is_math(node: &XmlNode) -> bool {
+    matches!(node.name.split_once(':'), None | Some(("m", _)))
+}
+
+/// Convert a single OMML object, adding the result to `out`.
+fn convert(node: &XmlNode, out: &mut Vec<Element>) {
+    let name = node.local();
+    if !is_math(node) {
+        // WordprocessingML markup that can turn up inside equations. Tracked
+        // insertions are kept, everything else is dropped.
+        if name == "ins" || name == "smartTag" {
+            node.elems().for_each(|e| convert(e, out));
+        }
+        return;
+    }
+    if name.ends_with("Pr") {
+        return;
+    }
+    let e = match name {
+        "r" => return run(node, out),
+        "oMath" | "box" | "borderBox" => return node.elems().for_each(|e| convert(e, out)),
+        "f" => {
+            let num = child(node, "num");
+            let den = child(node, "den");
+            if prop(node, "type") == Some("noBar") {
+                Element::frac_thickness(num, den, 0.0)
+            } else {
+                Element::frac(num, den)
+            }
+        }
+        "rad" => {
+            let base = child(node, "e");
+            let index = child(node, "deg");
+            let empty = matches!(index.elem(), MathElement::Row(r) if r.is_empty());
+            if flag(node, "degHide") || empty {
+                Element::sqrt(base)
+            } else {
+                Element::root(base, index)
+            }
+        }
+        "sSub" => Element::sub(child(node, "e"), child(node, "sub")),
+        "sSup" => Element::sup(child(node, "e"), child(node, "sup")),
+        "sSubSup" => Element::sub_sup(child(node, "e"), child(node, "sub"), child(node, "sup")),
+        "sPre" => {
+            let pair = Pair::new(child(node, "sub"), child(node, "sup"));
+            match child(node, "e").into_parts() {
+                (MathElement::SubSup { base, sub, sup }, None) => {
+                    Element::new(MathElement::MultiScript {
+                        base,
+                        post: vec![Pair { sub, sup }],
+                        pre: vec![pair],
+                    })
+                }
+                (
+                    MathElement::MultiScript {
+                        base,
+                        post,
+                        mut pre,
+                    },
+                    None,
+                ) => {
+                    pre.insert(0, pair);
+                    Element::new(MathElement::MultiScript { base, post, pre })
+                }
+                (e, a) => {
+                    let base = match a {
+                        Some(a) => Element::with_attributes(e, a),
+                        None => Element::new(e),
+                    };
+                    Element::multiscript(base, [], [pair])
+                }
+            }
+        }
+        "nary" => {
+            let chr = char_prop(node, "chr", Some('∫')).unwrap_or('∫');
+            let op = large_op(chr);
+            let sub = (!flag(node, "subHide")).then(|| child(node, "sub"));
+            let sup = (!flag(node, "supHide")).then(|| child(node, "sup"));
+            let limits = match prop(node, "limLoc") {
+                Some("undOvr") => true,
+                Some("subSup") => false,
+                _ => !INTEGRALS.contains(&chr),
+            };
+            out.push(match (sub, sup, limits) {
+                (None, None, _) => op,
+                (Some(sub), None, true) => Element::under(op, sub),
+                (None, Some(sup), true) => Element::over(op, sup),
+                (Some(sub), Some(sup), true) => Element::under_over(op, sub, sup),
+                (Some(sub), None, false) => Element::sub(op, sub),
+                (None, Some(sup), false) => Element::sup(op, sup),
+                (Some(sub), Some(sup), false) => Element::sub_sup(op, sub, sup),
+            });
+            // The operand is spliced into the surrounding row.
+            if let Some(e) = node.elems().find(|e| e.local() == "e") {
+                out.extend(items(e));
+            }
+            return;
+        }
+        "func" => {
+            if let Some(f) = node.elems().find(|e| e.local() == "fName") {
+                out.extend(items(f));
+            }
+            out.push(Element::op('\u{2061}'));
+            if let Some(e) = node.elems().find(|e| e.local() == "e") {
+                out.extend(items(e));
+            }
+            return;
+        }
+        "d" => {
+            let open = char_prop(node, "begChr", Some('('));
+            let close = char_prop(node, "endChr", Some(')'));
+            let sep = char_prop(node, "sepChr", Some('|'));
+            let mut elems: Vec<Element> = open.map(fence).into_iter().collect();
+            for (i, e) in node.elems().filter(|e| e.local() == "e").enumerate() {
+                if let (true, Some(sep)) = (i > 0, sep) {
+                    elems.push(Element::oper(Operator {
+                        separator: Some(true),
+                        ..Operator::new(sep)
+                    }));
+                }
+                elems.extend(items(e));
+            }
+            elems.extend(close.map(fence));
+            Element::row(elems)
+        }
+        "m" => Element::table(
+            node.elems()
+                .filter(|r| r.local() == "mr")
+                .map(|r| {
+                    TableRow::new(
+                        r.elems()
+                            .filter(|e| e.local() == "e")
+                            .map(|e| TableCell::new(items(e))),
+                    )
+                })
+                .collect::<Vec<_>>(),
+        ),
+        "eqArr" => Element::table(
+            node.elems()
+                .filter(|e| e.local() == "e")
+                .map(|e| TableRow::new([TableCell::new(items(e))]))
+                .collect::<Vec<_>>(),
+        ),
+        "acc" => {
+            let c = char_prop(node, "chr", Some('\u{302}')).unwrap_or('\u{302}');
+            let c = ACCENTS.iter().find(|a| a.0 == c).map_or(c, |a| a.1);
+            Element::over_accent(child(node, "e"), Element::op(c))
+        }
+        "bar" => {
+            let base = child(node, "e");
+            if prop(node, "pos") == Some("top") {
+                Element::over(base, Element::op('‾'))
+            } else {
+                Element::under(base, Element::op('_'))
+            }
+        }
+        "groupChr" => {
+            let c = char_prop(node, "chr", Some('⏟')).unwrap_or('⏟');
+            let base = child(node, "e");
+            if prop(node, "pos") == Some("top") {
+                Element::over(base, Element::op(c))
+            } else {
+                Element::under(base, Element::op(c))
+            }
+        }
+        "limUpp" => Element::over(child(node, "e"), child(node, "lim")),
+        "limLow" => {
+            let under = child(node, "lim");
+            match child(node, "e").into_parts() {
+                // Written out for an under-over with no operator.
+                (
+                    MathElement::Over {
+                        base,
+                        over,
+                        accent: false,
+                    },
+                    None,
+                ) => Element::under_over(*base, under, *over),
+                (e, None) => Element::under(Element::new(e), under),
+                (e, Some(a)) => Element::under(Element::with_attributes(e, a), under),
+            }
+        }
+        "phant" => Element::phantom(items(child_node(node, "e"))),
+        _ => Element::err(format!("unsupported OMML element <m:{}>", name)),
+    };
+    out.push(e);
+}
+
+fn child_node<'a>(node: &'a XmlNode, name: &str) -> &'a XmlNode {
+    node.elems().find(|e| e.local() == name).unwrap_or(node)
+}
+
+/// Get the variant given by a run's `m:scr` and `m:sty` properties. Plain
+/// roman text has no variant, and is marked as normal on identifiers instead.
+fn run_variant(scr: Option<&str>, sty: Option<&str>) -> Option<Variant> {
+    let bold = matches!(sty, Some("b" | "bi"));
+    Some(match (scr.unwrap_or("roman"), sty) {
+        ("roman", Some("b")) => Variant::Bold,
+        ("roman", Some("bi")) => Variant::BoldItalic,
+        ("roman", Some("i")) => Variant::Italic,
+        ("script", _) if bold => Variant::BoldScript,
+        ("script", _) => Variant::Script,
+        ("fraktur", _) if bold => Variant::BoldFraktur,
+        ("fraktur", _) => Variant::Fraktur,
+        ("double-struck", _) => Variant::DoubleStruck,
+        ("sans-serif", Some("p")) => Variant::SansSerif,
+        ("sans-serif", Some("b")) => Variant::BoldSansSerif,
+        ("sans-serif", Some("bi")) => Variant::SansSerifBoldItalic,
+        ("sans-serif", _) => Variant::SansSerifItalic,
+        ("monospace", _) => Variant::Monospace,
+        _ => return None,
+    })
+}
+
+/// Split a run into identifier, number, and operator tokens.
+fn run(node: &XmlNode, out: &mut Vec<Element>) {
+    let text: String = node
+        .elems()
+        .filter(|e| e.local() == "t")
+        .map(|e| e.text())
+        .collect();
+    let pr = node.elems().find(|e| e.local() == "rPr" && is_math(e));
+    let val = |name: &str| {
+        pr.and_then(|pr| pr.elems().find(|e| e.local() == name))
+            .map(|e| e.attr("val").unwrap_or(""))
+    };
+    let sty = val("sty");
+    let scr = val("scr");
+    let variant = run_variant(scr, sty);
+    let with_variant = |e: Element| match variant {
+        Some(v) => e.variant(v),
+        None => e,
+    };
+    if val("nor").is_some_and(|v| !matches!(v, "0" | "off" | "false")) {
+        out.push(with_variant(Element::text(text)));
+        return;
+    }
+    let upright = sty == Some("p");
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let e =
+            if c.is_ascii_digit() || (c == '.' && chars.peek().is_some_and(char::is_ascii_digit)) {
+                let mut num = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_ascii_digit() || (c == '.' && !num.contains('.'))) {
+                        break;
+                    }
+                    num.push(c);
+                    chars.next();
+                }
+                Element::num(num)
+            } else if c.is_alphabetic() && upright {
+                let mut t = c.to_string();
+                while let Some(&c) = chars.peek().filter(|c| c.is_alphabetic()) {
+                    t.push(c);
+                    chars.next();
+                }
+                if t.chars().count() == 1 && variant.is_none() {
+                    Element::id_normal(t)
+                } else {
+                    Element::id(t)
+                }
+            } else if c.is_alphabetic() {
+                Element::id(c.to_string())
+            } else if c.is_whitespace() {
+                continue;
+            } else {
+                Element::op(c)
+            };
+        out.push(with_variant(e));
+    }
+}
+
+impl Element {
+    /// Write the element out as an OMML `<m:oMath>` element. If the element is
+    /// set to display style, it is wrapped in an `<m:oMathPara>` instead.
+    pub fn to_omml(&self) -> String {
+        let mut math = XmlNode::new("m:oMath");
+        for n in items_out(std::slice::from_ref(self)) {
+            math = math.child(n);
+        }
+        let root = if self.attributes().and_then(|a| a.display_style) == Some(true) {
+            XmlNode::new("m:oMathPara").child(math)
+        } else {
+            math
+        };
+        let mut out = String::new();
+        root.attr_add("xmlns:m", NAMESPACE).write(&mut out, None, 0);
+        out
+    }
+}
+
+/// Check if an element can be written as a delimiter of `<m:d>`. Operators
+/// explicitly marked as fences always can, and brackets otherwise can.
+fn is_fence(e: &Element, open: bool) -> (bool, bool) {
+    let explicit = match e.elem() {
+        MathElement::Oper(op) => op.fence == Some(true),
+        MathElement::ResolvedOper(op) => op.fence,
+        _ => false,
+    };
+    let bracket = match e.elem() {
+        MathElement::Oper(op) if op.stretchy == Some(false) => false,
+        _ => op_char(e).is_some_and(|c| if open { OPEN } else { CLOSE }.contains(&c)),
+    };
+    (explicit || bracket, explicit)
+}
+
+/// Get the delimiters of a row that should be written as `<m:d>`.
+fn delimiters(elems: &[Element]) -> Option<(Option<char>, Option<char>)> {
+    let (first, last) = (elems.first()?, elems.last()?);
+    let (open, open_explicit) = is_fence(first, true);
+    let (close, close_explicit) = is_fence(last, false);
+    match (open && elems.len() >= 2, close && elems.len() >= 2) {
+        (true, true) => Some((op_char(first), op_char(last))),
+        (true, false) if open_explicit => Some((op_char(first), None)),
+        (false, true) if close_explicit => Some((None, op_char(last))),
+        _ => None,
+    }
+}
+
+fn is_large_op(e: &Element) -> bool {
+    match e.elem() {
+        MathElement::Oper(op) if op.large_op == Some(true) => true,
+        _ => op_char(e).is_some_and(|c| NARY.contains(&c)),
+    }
+}
+
+/// Get the n-ary operator and scripts of an element, if it is one.
+fn nary(e: &Element) -> Option<(char, Option<&Element>, Option<&Element>, bool)> {
+    let (base, sub, sup, limits) = match e.elem() {
+        MathElement::UnderOver {
+            base, under, over, ..
+        } => (base, Some(under), Some(over), true),
+        MathElement::Under { base, under, .. } => (base, Some(under), None, true),
+        MathElement::Over { base, over, .. } => (base, None, Some(over), true),
+        MathElement::SubSup { base, sub, sup } => (base, Some(sub), Some(sup), false),
+        MathElement::Sub { base, sub } => (base, Some(sub), None, false),
+        MathElement::Sup { base, sup } => (base, None, Some(sup), false),
+        _ => return is_large_op(e).then(|| (op_char(e).unwrap(), None, None, false)),
+    };
+    if !is_large_op(base) || e.attributes().is_some() {
+        return None;
+    }
+    Some((op_char(base)?, sub.map(|b| &**b), sup.map(|b| &**b), limits))
+}
+
+fn pr(name: &str, props: &[(&str, &str)]) -> XmlNode {
+    props.iter().fold(XmlNode::new(name), |n, (k, v)| {
+        n.child(XmlNode::new(format!("m:{}", k)).attr_add("m:val", *v))
+    })
+}
+
+/// Write an argument element.
+fn arg_out(name: &str, e: &Element) -> XmlNode {
+    let elems = match e.elem() {
+        MathElement::Row(elems) if e.attributes().is_none() && delimiters(elems).is_none() => {
+            items_out(elems)
+        }
+        _ => items_out(std::slice::from_ref(e)),
+    };
+    elems
+        .into_iter()
+        .fold(XmlNode::new(name), |n, c| n.child(c))
+}
+
+fn arg_items(name: &str, elems: &[Element]) -> XmlNode {
+    items_out(elems)
+        .into_iter()
+        .fold(XmlNode::new(name), |n, c| n.child(c))
+}
+
+/// Write a list of elements, combining n-ary operators and functions with
+/// the element following them.
+fn items_out(elems: &[Element]) -> Vec<XmlNode> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < elems.len() {
+        let e = &elems[i];
+        let next = elems.get(i + 1);
+        if let Some((chr, sub, sup, limits)) = nary(e) {
+            let mut props = vec![("chr", chr.to_string())];
+            props.push(("limLoc", if limits { "undOvr" } else { "subSup" }.into()));
+            if sub.is_none() {
+                props.push(("subHide", "1".into()));
+            }
+            if sup.is_none() {
+                props.push(("supHide", "1".into()));
+            }
+            let props: Vec<(&str, &str)> = props.iter().map(|(k, v)| (*k, v.as_str())).collect();
+            let empty = Element::row([]);
+            out.push(
+                XmlNode::new("m:nary")
+                    .child(pr("m:naryPr", &props))
+                    .child(arg_out("m:sub", sub.unwrap_or(&empty)))
+                    .child(arg_out("m:sup", sup.unwrap_or(&empty)))
+                    .child(arg_items(
+                        "m:e",
+                        next.map(std::slice::from_ref).unwrap_or(&[]),
+                    )),
+            );
+            i += 2;
+            continue;
+        }
+        if next.and_then(op_char) == Some('\u{2061}') {
+            let operand = elems.get(i + 2).map(std::slice::from_ref).unwrap_or(&[]);
+            out.push(
+                XmlNode::new("m:func")
+                    .child(arg_out("m:fName", e))
+                    .child(arg_items("m:e", operand)),
+            );
+            i += 3;
+            continue;
+        }
+        match e.elem() {
+            MathElement::Row(elems) if e.attributes().is_none() && delimiters(elems).is_none() => {
+                out.extend(items_out(elems))
+            }
+            _ => out.push(element_out(e)),
+        }
+        i += 1;
+    }
+    out
+}
+
+/// Get the `m:scr` and `m:sty` values for a variant.
+fn variant_props(v: Variant) -> (Option<&'static str>, Option<&'static str>) {
+    match v {
+        Variant::Normal => (None, Some("p")),
+        Variant::Bold => (None, Some("b")),
+        Variant::Italic => (None, Some("i")),
+        Variant::BoldItalic => (None, Some("bi")),
+        Variant::DoubleStruck => (Some("double-struck"), None),
+        Variant::BoldFraktur => (Some("fraktur"), Some("b")),
+        Variant::Script => (Some("script"), None),
+        Variant::BoldScript => (Some("script"), Some("b")),
+        Variant::Fraktur => (Some("fraktur"), None),
+        Variant::SansSerif => (Some("sans-serif"), Some("p")),
+        Variant::BoldSansSerif => (Some("sans-serif"), Some("b")),
+        Variant::SansSerifItalic => (Some("sans-serif"), None),
+        Variant::SansSerifBoldItalic => (Some("sans-serif"), Some("bi")),
+        Variant::Monospace => (Some("monospace"), None),
+        Variant::Initial | Variant::Tailed | Variant::Looped | Variant::Stretched => (None, None),
+    }
+}
+
+/// Write a text run.
+fn run_out(text: &str, variant: Option<Variant>, upright: bool, nor: bool) -> XmlNode {
+    let (scr, mut sty) = variant.map_or((None, None), variant_props);
+    if upright && sty.is_none() && scr.is_none() {
+        sty = Some("p");
+    }
+    let mut rpr = XmlNode::new("m:rPr");
+    if nor {
+        rpr = rpr.child(XmlNode::new("m:nor"));
+    }
+    if let Some(scr) = scr {
+        rpr = rpr.child(XmlNode::new("m:scr").attr_add("m:val", scr));
+    }
+    if let Some(sty) = sty {
+        rpr = rpr.child(XmlNode::new("m:sty").attr_add("m:val", sty));
+    }
+    let mut r = XmlNode::new("m:r");
+    if !rpr.children.is_empty() {
+        r = r.child(rpr);
+    }
+    let mut t = XmlNode::new("m:t");
+    if text.starts_with(' ') || text.ends_with(' ') {
+        t = t.attr_add("xml:space", "preserve");
+    }
+    r.child(t.text_add(text))
+}
+
+fn element_out(e: &Element) -> XmlNode {
+    let variant = e.attributes().and_then(|a| a.variant);
+    match e.elem() {
+        MathElement::Op(_) | MathElement::Oper(_) | MathElement::ResolvedOper(_) => {
+            run_out(&op_char(e).unwrap().to_string(), variant, false, false)
+        }
+        MathElement::Text(t) | MathElement::Str(t) => run_out(t, variant, false, true),
+        MathElement::Err(t) | MathElement::Unknown { tag: t, .. } => XmlNode::new("m:borderBox")
+            .child(XmlNode::new("m:e").child(run_out(t, None, false, true))),
+        MathElement::Id { t, normal } => {
+            let upright = *normal || t.chars().count() > 1;
+            run_out(t, variant, upright, false)
+        }
+        MathElement::Num(t) => run_out(t, variant, false, false),
+        // Spacing has no direct equivalent, so it becomes an em space.
+        MathElement::Space(_) => run_out("\u{2003}", None, false, false),
+        MathElement::Row(elems) => match delimiters(elems) {
+            Some((open, close)) => {
+                let mut inner = elems.as_slice();
+                if open.is_some() {
+                    inner = &inner[1..];
+                }
+                if close.is_some() {
+                    inner = &inner[..inner.len() - 1];
+                }
+                let is_sep = |e: &Element| match e.elem() {
+                    MathElement::Oper(op) => op.separator == Some(true),
+                    MathElement::ResolvedOper(op) => op.separator,
+                    _ => false,
+                };
+                let sep = inner.iter().find(|e| is_sep(e)).and_then(op_char);
+                let open = open.map_or(String::new(), String::from);
+                let close = close.map_or(String::new(), String::from);
+                let mut props = vec![("begChr", open.as_str()), ("endChr", close.as_str())];
+                let sep_str = sep.map(String::from);
+                if let Some(s) = &sep_str {
+                    props.push(("sepChr", s.as_str()));
+                }
+                let mut d = XmlNode::new("m:d").child(pr("m:dPr", &props));
+                for part in inner.split(|e| sep.is_some() && is_sep(e)) {
+                    d = d.child(arg_items("m:e", part));
+                }
+                d
+            }
+            None => arg_items("m:box", elems),
+        },
+        MathElement::Phantom(elems) => XmlNode::new("m:phant").child(arg_items("m:e", elems)),
+        MathElement::Padding(p) => arg_items("m:box", &p.elems),
+        MathElement::Frac {
+            line_thickness,
+            num,
+            den,
+        } => {
+            let mut f = XmlNode::new("m:f");
+            if *line_thickness == Some(0.0) {
+                f = f.child(pr("m:fPr", &[("type", "noBar")]));
+            }
+            f.child(arg_out("m:num", num)).child(arg_out("m:den", den))
+        }
+        MathElement::Sqrt(base) => XmlNode::new("m:rad")
+            .child(pr("m:radPr", &[("degHide", "1")]))
+            .child(XmlNode::new("m:deg"))
+            .child(arg_out("m:e", base)),
+        MathElement::Root { base, index } => XmlNode::new("m:rad")
+            .child(arg_out("m:deg", index))
+            .child(arg_out("m:e", base)),
+        MathElement::Sub { base, sub } => XmlNode::new("m:sSub")
+            .child(arg_out("m:e", base))
+            .child(arg_out("m:sub", sub)),
+        MathElement::Sup { base, sup } => XmlNode::new("m:sSup")
+            .child(arg_out("m:e", base))
+            .child(arg_out("m:sup", sup)),
+        MathElement::SubSup { base, sub, sup } => XmlNode::new("m:sSubSup")
+            .child(arg_out("m:e", base))
+            .child(arg_out("m:sub", sub))
+            .child(arg_out("m:sup", sup)),
+        MathElement::Over { base, over, accent } => {
+            let c = op_char(over);
+            if let Some(c) = c.filter(|_| *accent) {
+                let c = ACCENTS.iter().find(|a| a.1 == c).map_or(c, |a| a.0);
+                XmlNode::new("m:acc")
+                    .child(pr("m:accPr", &[("chr", &c.to_string())]))
+                    .child(arg_out("m:e", base))
+            } else if c == Some('‾') {
+                XmlNode::new("m:bar")
+                    .child(pr("m:barPr", &[("pos", "top")]))
+                    .child(arg_out("m:e", base))
+            } else if let Some(c) = c.filter(|c| GROUP_CHARS.contains(c)) {
+                XmlNode::new("m:groupChr")
+                    .child(pr(
+                        "m:groupChrPr",
+                        &[("chr", &c.to_string()), ("pos", "top")],
+                    ))
+                    .child(arg_out("m:e", base))
+            } else {
+                XmlNode::new("m:limUpp")
+                    .child(arg_out("m:e", base))
+                    .child(arg_out("m:lim", over))
+            }
+        }
+        MathElement::Under { base, under, .. } => match op_char(under) {
+            Some('_') => XmlNode::new("m:bar")
+                .child(pr("m:barPr", &[("pos", "bot")]))
+                .child(arg_out("m:e", base)),
+            Some(c) if GROUP_CHARS.contains(&c) => XmlNode::new("m:groupChr")
+                .child(pr(
+                    "m:groupChrPr",
+                    &[("chr", &c.to_string()), ("pos", "bot")],
+                ))
+                .child(arg_out("m:e", base)),
+            _ => XmlNode::new("m:limLow")
+                .child(arg_out("m:e", base))
+                .child(arg_out("m:lim", under)),
+        },
+        MathElement::UnderOver {
+            base, under, over, ..
+        } => {
+            let upp = XmlNode::new("m:limUpp")
+                .child(arg_out("m:e", base))
+                .child(arg_out("m:lim", over));
+            XmlNode::new("m:limLow")
+                .child(XmlNode::new("m:e").child(upp))
+                .child(arg_out("m:lim", under))
+        }
+        MathElement::MultiScript { base, post, pre } => {
+            // Each pair of scripts wraps the element built so far.
+            let mut e = arg_out("m:e", base);
+            let mut n = None;
+            for p in post.iter() {
+                let s = XmlNode::new("m:sSubSup")
+                    .child(e)
+                    .child(arg_out("m:sub", &p.sub))
+                    .child(arg_out("m:sup", &p.sup));
+                e = XmlNode::new("m:e").child(s.clone());
+                n = Some(s);
+            }
+            for p in pre.iter().rev() {
+                let s = XmlNode::new("m:sPre")
+                    .child(arg_out("m:sub", &p.sub))
+                    .child(arg_out("m:sup", &p.sup))
+                    .child(e);
+                e = XmlNode::new("m:e").child(s.clone());
+                n = Some(s);
+            }
+            n.unwrap_or_else(|| element_out(base))
+        }
+        MathElement::Table { rows } => rows.iter().fold(XmlNode::new("m:m"), |m, row| {
+            m.child(row.cells.iter().fold(XmlNode::new("m:mr"), |mr, cell| {
+                mr.child(arg_items("m:e", &cell.elems))
+            }))
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn omath(body: &str) -> String {
+        format!(
+            r#"<m:oMath xmlns:m="{}" xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">{}</m:oMath>"#,
+            NAMESPACE, body
+        )
+    }
+
+    #[test]
+    fn runs() {
+        let e = parse(&omath(
+            r#"<m:r><m:t>x+12.5</m:t></m:r>
+            <m:r><w:rPr><w:rFonts w:ascii="Cambria Math"/></w:rPr><m:rPr><m:sty m:val="p"/></m:rPr><m:t>sin d</m:t></m:r>
+            <m:r><m:rPr><m:scr m:val="double-struck"/></m:rPr><m:t>R</m:t></m:r>
+            <m:r><m:rPr><m:nor/></m:rPr><m:t xml:space="preserve"> if </m:t></m:r>"#,
+        ))
+        .unwrap();
+        assert_eq!(
+            e,
+            Element::row([
+                Element::id("x"),
+                Element::op('+'),
+                Element::num("12.5"),
+                Element::id("sin"),
+                Element::id_normal("d"),
+                Element::id("R").variant(Variant::DoubleStruck),
+                Element::text(" if "),
+            ])
+        );
+    }
+
+    #[test]
+    fn objects() {
+        let e = parse(&omath(
+            r#"<m:f><m:fPr><m:type m:val="noBar"/></m:fPr><m:num><m:r><m:t>n</m:t></m:r></m:num><m:den><m:r><m:t>k</m:t></m:r></m:den></m:f>
+            <m:rad><m:radPr><m:degHide m:val="1"/></m:radPr><m:deg/><m:e><m:r><m:t>x</m:t></m:r></m:e></m:rad>
+            <m:rad><m:deg><m:r><m:t>3</m:t></m:r></m:deg><m:e><m:r><m:t>y</m:t></m:r></m:e></m:rad>
+            <m:sPre><m:sub><m:r><m:t>a</m:t></m:r></m:sub><m:sup><m:r><m:t>b</m:t></m:r></m:sup><m:e><m:r><m:t>X</m:t></m:r></m:e></m:sPre>
+            <m:acc><m:e><m:r><m:t>v</m:t></m:r></m:e></m:acc>"#,
+        ))
+        .unwrap();
+        assert_eq!(
+            e,
+            Element::row([
+                Element::frac_thickness(Element::id("n"), Element::id("k"), 0.0),
+                Element::sqrt(Element::id("x")),
+                Element::root(Element::id("y"), Element::num("3")),
+                Element::multiscript(
+                    Element::id("X"),
+                    [],
+                    [Pair::new(Element::id("a"), Element::id("b"))]
+                ),
+                Element::over_accent(Element::id("v"), Element::op('^')),
+            ])
+        );
+    }
+
+    #[test]
+    fn nary_and_func() {
+        let e = parse(&omath(
+            r#"<m:nary><m:naryPr><m:chr m:val="∑"/><m:supHide m:val="on"/></m:naryPr><m:sub><m:r><m:t>i</m:t></m:r></m:sub><m:sup/><m:e><m:r><m:t>i</m:t></m:r></m:e></m:nary>
+            <m:func><m:fName><m:r><m:rPr><m:sty m:val="p"/></m:rPr><m:t>cos</m:t></m:r></m:fName><m:e><m:r><m:t>θ</m:t></m:r></m:e></m:func>"#,
+        ))
+        .unwrap();
+        let sum = Element::oper(Operator {
+            large_op: Some(true),
+            ..Operator::new('∑')
+        });
+        assert_eq!(
+            e,
+            Element::row([
+                Element::under(sum, Element::id("i")),
+                Element::id("i"),
+                Element::id("cos"),
+                Element::op('\u{2061}'),
+                Element::id("θ"),
+            ])
+        );
+    }
+
+    #[test]
+    fn delimiters_and_matrices() {
+        let e = parse(&omath(
+            r#"<m:d><m:dPr><m:begChr m:val="["/><m:endChr m:val=""/></m:dPr>
+            <m:e><m:m><m:mr><m:e><m:r><m:t>1</m:t></m:r></m:e><m:e><m:r><m:t>0</m:t></m:r></m:e></m:mr></m:m></m:e></m:d>
+            <m:d><m:e><m:r><m:t>a</m:t></m:r></m:e><m:e><m:r><m:t>b</m:t></m:r></m:e></m:d>"#,
+        ))
+        .unwrap();
+        let fence = |c| {
+            Element::oper(Operator {
+                fence: Some(true),
+                ..Operator::new(c)
+            })
+        };
+        assert_eq!(
+            e,
+            Element::row([
+                Element::row([
+                    fence('['),
+                    Element::matrix([[Element::num("1"), Element::num("0")]]),
+                ]),
+                Element::row([
+                    fence('('),
+                    Element::id("a"),
+                    Element::oper(Operator {
+                        separator: Some(true),
+                        ..Operator::new('|')
+                    }),
+                    Element::id("b"),
+                    fence(')'),
+                ]),
+            ])
+        );
+    }
+
+    #[test]
+    fn unsupported() {
+        let e = parse(&omath("<m:r><m:t>x</m:t></m:r><m:bogus/>")).unwrap();
+        assert_eq!(
+            e,
+            Element::row([
+                Element::id("x"),
+                Element::err("unsupported OMML element <m:bogus>"),
+            ])
+        );
+        assert_eq!(
+            parse("<w:p/>").unwrap(),
+            Element::err("no <m:oMath> element found")
+        );
+    }
+
+    #[test]
+    fn roundtrip() {
+        let sum = Element::oper(Operator {
+            large_op: Some(true),
+            ..Operator::new('∑')
+        });
+        let int = Element::oper(Operator {
+            large_op: Some(true),
+            ..Operator::new('∫')
+        });
+        let fence = |c| {
+            Element::oper(Operator {
+                fence: Some(true),
+                ..Operator::new(c)
+            })
+        };
+        let e = Element::row([
+            Element::under_over(sum, Element::id("i"), Element::id("n")),
+            Element::sup(Element::id("x"), Element::num("2")),
+            Element::sub_sup(int, Element::num("0"), Element::num("1")),
+            Element::frac(
+                Element::id("a"),
+                Element::row([Element::id("b"), Element::op('+'), Element::num("1")]),
+            ),
+            Element::id("lim"),
+            Element::op('\u{2061}'),
+            Element::under(Element::id("x"), Element::id("y")),
+            Element::over(Element::id("x"), Element::op('⏞')),
+            Element::under(Element::id("x"), Element::op('_')),
+            Element::under_over(Element::id("A"), Element::id("b"), Element::id("c")),
+            Element::multiscript(
+                Element::id("X"),
+                [Pair::new(Element::id("a"), Element::id("b"))],
+                [Pair::new(Element::id("c"), Element::id("d"))],
+            ),
+            Element::row([
+                fence('('),
+                Element::matrix([[Element::id("a")], [Element::id("b")]]),
+                fence(')'),
+            ]),
+            Element::id("x").variant(Variant::BoldFraktur),
+            Element::id("y").variant(Variant::SansSerif),
+            Element::text("text").variant(Variant::Bold),
+            Element::phantom([Element::id("z")]),
+        ])
+        .display_style(true);
+        let out = e.to_omml();
+        assert!(out.starts_with("<m:oMathPara xmlns:m="));
+        assert_eq!(parse(&out).unwrap(), e, "{}", out);
+    }
+}
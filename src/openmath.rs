@@ -0,0 +1,548 @@
+//! OpenMath objects, in the XML and JSON encodings.
+//!
+//! OpenMath is built from the same pieces as Strict Content MathML, so objects
+//! are read into [`Content`] trees: `OMV` becomes [`Content::Ci`], `OMS`
+//! becomes [`Content::Csymbol`], `OMA` and `OMBIND` become applications and
+//! bindings, and so on. Use [`Content::to_element`] to present them, which
+//! picks a notation for each symbol by its content dictionary, and
+//! [`Content::from_element`] to go back when the presentation is unambiguous.
+//!
+//! Attributions are dropped in favor of the object they attribute, and
+//! references and byte arrays become [`Content::Err`] nodes.
+
+use crate::content::Content;
+use crate::json::{self, Json, JsonError};
+use crate::math::Element;
+use crate::xml::{self, XmlError, XmlNode};
+
+/// The OpenMath XML namespace.
+pub const NAMESPACE: &str = "http://www.openmath.org/OpenMath";
+
+/// Parse an OpenMath object in the XML encoding. The root may be an `OMOBJ`
+/// or a bare object.
+///
+/// Only XML syntax errors are returned as errors. Unsupported or malformed
+/// objects become [`Content::Err`] nodes within the returned tree.
+pub fn parse_xml(src: &str) -> Result<Content, XmlError> {
+    let root = xml::parse(src)?;
+    Ok(from_xml(&root))
+}
+
+fn from_xml(node: &XmlNode) -> Content {
+    let name = node.local();
+    let mut elems = node.elems();
+    match name {
+        "OMOBJ" => match elems.next() {
+            Some(e) => from_xml(e),
+            None => Content::err("<OMOBJ> has no object"),
+        },
+        "OMI" => integer(node.text().trim()),
+        "OMF" => match (node.attr("dec"), node.attr("hex")) {
+            (Some(dec), _) => double(dec.trim()),
+            (None, Some(hex)) => match u64::from_str_radix(hex.trim(), 16) {
+                Ok(bits) => double(&f64::from_bits(bits).to_string()),
+                Err(_) => Content::err(format!("invalid OMF hex value `{}`", hex)),
+            },
+            (None, None) => Content::err("<OMF> has no value"),
+        },
+        "OMSTR" => Content::cs(node.text()),
+        "OMV" => Content::ci(node.attr("name").unwrap_or_default()),
+        "OMS" => symbol(node.attr("cd"), node.attr("name").unwrap_or_default()),
+        "OMA" => match elems.next() {
+            Some(head) => Content::apply(from_xml(head), elems.map(from_xml)),
+            None => Content::err("<OMA> has no applicant"),
+        },
+        "OMBIND" => {
+            let (Some(head), Some(vars), Some(body)) = (elems.next(), elems.next(), elems.next())
+            else {
+                return Content::err("<OMBIND> needs a binder, variables, and a body");
+            };
+            let vars = vars.elems().map(|v| {
+                // Variables may be attributed.
+                let v = match v.local() {
+                    "OMATTR" => v.elems().find(|e| e.local() == "OMV").unwrap_or(v),
+                    _ => v,
+                };
+                v.attr("name").unwrap_or_default().to_string()
+            });
+            Content::bind(from_xml(head), vars, from_xml(body))
+        }
+        "OMATTR" => match elems.find(|e| e.local() != "OMATP") {
+            Some(e) => from_xml(e),
+            None => Content::err("<OMATTR> has no object"),
+        },
+        "OME" => {
+            let head = elems.next();
+            let args: Vec<_> = elems.collect();
+            error(
+                head.map(|h| {
+                    (
+                        h.attr("cd").unwrap_or_default(),
+                        h.attr("name").unwrap_or_default(),
+                    )
+                }),
+                args.first()
+                    .filter(|a| a.local() == "OMSTR")
+                    .map(|a| a.text()),
+            )
+        }
+        "OMR" => Content::err("OpenMath references are not supported"),
+        _ => Content::err(format!("unsupported OpenMath element <{}>", name)),
+    }
+}
+
+/// Parse an OpenMath object in the JSON encoding. The root may be an `OMOBJ`
+/// or a bare object.
+///
+/// Only JSON syntax errors are returned as errors. Unsupported or malformed
+/// objects become [`Content::Err`] nodes within the returned tree.
+pub fn parse_json(src: &str) -> Result<Content, JsonError> {
+    let root = json::parse(src)?;
+    Ok(from_json(&root))
+}
+
+fn from_json(v: &Json) -> Content {
+    let field = |key| v.get(key).and_then(Json::as_str);
+    let number = |key| v.get(key).and_then(|n| n.as_num().or_else(|| n.as_str()));
+    let Some(kind) = field("kind") else {
+        return Content::err("OpenMath JSON object has no kind");
+    };
+    match kind {
+        "OMOBJ" => match v.get("object") {
+            Some(o) => from_json(o),
+            None => Content::err("OMOBJ has no object"),
+        },
+        "OMI" => match (number("integer").or(field("decimal")), field("hexadecimal")) {
+            (Some(t), _) => integer(t),
+            (None, Some(hex)) => integer(&hex.replacen("0x", "x", 1)),
+            (None, None) => Content::err("OMI has no value"),
+        },
+        "OMF" => match number("float").or(field("decimal")) {
+            Some(t) => double(t),
+            None => Content::err("OMF has no value"),
+        },
+        "OMSTR" => Content::cs(field("string").unwrap_or_default()),
+        "OMV" => Content::ci(field("name").unwrap_or_default()),
+        "OMS" => symbol(field("cd"), field("name").unwrap_or_default()),
+        "OMA" => match v.get("applicant") {
+            Some(head) => Content::apply(from_json(head), list(v, "arguments")),
+            None => Content::err("OMA has no applicant"),
+        },
+        "OMBIND" => {
+            let (Some(head), Some(vars), Some(body)) = (
+                v.get("binder"),
+                v.get("variables").and_then(Json::as_array),
+                v.get("object"),
+            ) else {
+                return Content::err("OMBIND needs a binder, variables, and an object");
+            };
+            let vars = vars.iter().map(|v| {
+                let v = match v.get("kind").and_then(Json::as_str) {
+                    Some("OMATTR") => v.get("object").unwrap_or(v),
+                    _ => v,
+                };
+                v.get("name").and_then(Json::as_str).unwrap_or_default()
+            });
+            Content::bind(from_json(head), vars, from_json(body))
+        }
+        "OMATTR" => match v.get("object") {
+            Some(o) => from_json(o),
+            None => Content::err("OMATTR has no object"),
+        },
+        "OME" => {
+            let head = v.get("error").map(|e| {
+                let f = |key| e.get(key).and_then(Json::as_str).unwrap_or_default();
+                (f("cd"), f("name"))
+            });
+            let msg = v
+                .get("arguments")
+                .and_then(Json::as_array)
+                .and_then(|a| a.first())
+                .filter(|a| a.get("kind").and_then(Json::as_str) == Some("OMSTR"))
+                .and_then(|a| a.get("string").and_then(Json::as_str))
+                .map(String::from);
+            error(head, msg)
+        }
+        "OMR" => Content::err("OpenMath references are not supported"),
+        "OMB" => Content::err("OpenMath byte arrays are not supported"),
+        kind => Content::err(format!("unsupported OpenMath kind `{}`", kind)),
+    }
+}
+
+fn list(v: &Json, key: &str) -> Vec<Content> {
+    v.get(key)
+        .and_then(Json::as_array)
+        .unwrap_or_default()
+        .iter()
+        .map(from_json)
+        .collect()
+}
+
+/// Read an integer, which may be written in hexadecimal like `-x1F`.
+fn integer(t: &str) -> Content {
+    let (sign, digits) = match t.strip_prefix('-') {
+        Some(d) => ("-", d),
+        None => ("", t),
+    };
+    match digits.strip_prefix('x') {
+        Some(hex) => match u128::from_str_radix(hex, 16) {
+            Ok(n) => Content::cn(format!("{}{}", sign, n)),
+            Err(_) => Content::err(format!("invalid OpenMath integer `{}`", t)),
+        },
+        None => Content::cn(t),
+    }
+}
+
+fn double(t: &str) -> Content {
+    Content::Cn {
+        t: t.into(),
+        kind: Some("double".into()),
+    }
+}
+
+fn symbol(cd: Option<&str>, name: &str) -> Content {
+    Content::Csymbol {
+        cd: cd.map(String::from),
+        name: name.into(),
+    }
+}
+
+/// Convert an error, keeping the message of the standard `unexpected` error.
+fn error(head: Option<(&str, &str)>, msg: Option<String>) -> Content {
+    match (head, msg) {
+        (Some(("moreerrors", "unexpected")), Some(msg)) => Content::err(msg),
+        (Some((cd, name)), _) => Content::err(format!("OpenMath error {}.{}", cd, name)),
+        (None, _) => Content::err("OpenMath error"),
+    }
+}
+
+/// Check if a number has to be written as a float.
+fn is_float(t: &str, kind: Option<&str>) -> bool {
+    matches!(kind, Some("double" | "real")) || t.parse::<i128>().is_err()
+}
+
+impl Content {
+    /// Write the expression out as an OpenMath `OMOBJ` in the XML encoding.
+    pub fn to_openmath_xml(&self) -> String {
+        let root = XmlNode::new("OMOBJ")
+            .attr_add("xmlns", NAMESPACE)
+            .attr_add("version", "2.0")
+            .child(to_xml(self));
+        let mut out = String::new();
+        root.write(&mut out, None, 0);
+        out
+    }
+
+    /// Write the expression out as an OpenMath `OMOBJ` in the JSON encoding.
+    pub fn to_openmath_json(&self) -> String {
+        Json::object([
+            ("kind", Json::str("OMOBJ")),
+            ("openmath", Json::str("2.0")),
+            ("object", to_json(self)),
+        ])
+        .to_string()
+    }
+}
+
+impl Element {
+    /// Write the element out as an OpenMath object in the XML encoding, if its
+    /// meaning can be read from the presentation. See
+    /// [`Content::from_element`].
+    pub fn to_openmath_xml(&self) -> Option<String> {
+        Content::from_element(self).map(|c| c.to_openmath_xml())
+    }
+
+    /// Write the element out as an OpenMath object in the JSON encoding, if
+    /// its meaning can be read from the presentation. See
+    /// [`Content::from_element`].
+    pub fn to_openmath_json(&self) -> Option<String> {
+        Content::from_element(self).map(|c| c.to_openmath_json())
+    }
+}
+
+fn to_xml(c: &Content) -> XmlNode {
+    match c {
+        Content::Ci(t) => XmlNode::new("OMV").attr_add("name", t),
+        Content::Cn { t, kind } if is_float(t, kind.as_deref()) => {
+            XmlNode::new("OMF").attr_add("dec", t)
+        }
+        Content::Cn { t, .. } => XmlNode::new("OMI").text_add(t),
+        Content::Cs(t) => XmlNode::new("OMSTR").text_add(t),
+        Content::Csymbol { cd, name } => {
+            let n = XmlNode::new("OMS");
+            match cd {
+                Some(cd) => n.attr_add("cd", cd),
+                None => n,
+            }
+            .attr_add("name", name)
+        }
+        Content::Apply { head, args } => args
+            .iter()
+            .fold(XmlNode::new("OMA").child(to_xml(head)), |n, a| {
+                n.child(to_xml(a))
+            }),
+        Content::Bind { head, bvars, body } => {
+            let vars = bvars.iter().fold(XmlNode::new("OMBVAR"), |n, v| {
+                n.child(XmlNode::new("OMV").attr_add("name", v))
+            });
+            XmlNode::new("OMBIND")
+                .child(to_xml(head))
+                .child(vars)
+                .child(to_xml(body))
+        }
+        Content::Err(t) => XmlNode::new("OME")
+            .child(
+                XmlNode::new("OMS")
+                    .attr_add("cd", "moreerrors")
+                    .attr_add("name", "unexpected"),
+            )
+            .child(XmlNode::new("OMSTR").text_add(t)),
+    }
+}
+
+fn to_json(c: &Content) -> Json {
+    match c {
+        Content::Ci(t) => Json::object([("kind", Json::str("OMV")), ("name", Json::str(t))]),
+        Content::Cn { t, kind } if is_float(t, kind.as_deref()) => {
+            // JSON numbers can't hold infinities or NaN.
+            let value = match t.parse::<f64>() {
+                Ok(f) if f.is_finite() => ("float", Json::Num(t.clone())),
+                _ => ("decimal", Json::str(t)),
+            };
+            Json::object([("kind", Json::str("OMF")), value])
+        }
+        Content::Cn { t, .. } => Json::object([
+            ("kind", Json::str("OMI")),
+            ("integer", Json::Num(t.clone())),
+        ]),
+        Content::Cs(t) => Json::object([("kind", Json::str("OMSTR")), ("string", Json::str(t))]),
+        Content::Csymbol { cd, name } => Json::object(
+            [("kind", Json::str("OMS"))]
+                .into_iter()
+                .chain(cd.as_ref().map(|cd| ("cd", Json::str(cd))))
+                .chain([("name", Json::str(name))]),
+        ),
+        Content::Apply { head, args } => Json::object([
+            ("kind", Json::str("OMA")),
+            ("applicant", to_json(head)),
+            ("arguments", Json::Array(args.iter().map(to_json).collect())),
+        ]),
+        Content::Bind { head, bvars, body } => {
+            let vars = bvars.iter().map(|v| to_json(&Content::ci(v.as_str())));
+            Json::object([
+                ("kind", Json::str("OMBIND")),
+                ("binder", to_json(head)),
+                ("variables", Json::Array(vars.collect())),
+                ("object", to_json(body)),
+            ])
+        }
+        Content::Err(t) => Json::object([
+            ("kind", Json::str("OME")),
+            (
+                "error",
+                to_json(&Content::csymbol("moreerrors", "unexpected")),
+            ),
+            (
+                "arguments",
+                Json::Array(vec![to_json(&Content::cs(t.as_str()))]),
+            ),
+        ]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::MathElement;
+
+    fn sym(cd: &str, name: &str) -> Content {
+        Content::csymbol(cd, name)
+    }
+
+    fn sin_plus() -> Content {
+        Content::apply(
+            sym("arith1", "plus"),
+            [
+                Content::apply(sym("transc1", "sin"), [Content::ci("x")]),
+                Content::cn("-26"),
+                Content::Cn {
+                    t: "0.5".into(),
+                    kind: Some("double".into()),
+                },
+            ],
+        )
+    }
+
+    #[test]
+    fn parse_xml_objects() {
+        let c = parse_xml(
+            r#"<OMOBJ xmlns="http://www.openmath.org/OpenMath" version="2.0">
+                <OMA>
+                    <OMS cd="arith1" name="plus"/>
+                    <OMA><OMS cd="transc1" name="sin"/><OMV name="x"/></OMA>
+                    <OMI>-x1A</OMI>
+                    <OMF dec="0.5"/>
+                </OMA>
+            </OMOBJ>"#,
+        )
+        .unwrap();
+        assert_eq!(c, sin_plus());
+
+        let c = parse_xml(
+            r#"<OMBIND>
+                <OMS cd="quant1" name="forall"/>
+                <OMBVAR><OMATTR><OMATP/><OMV name="x"/></OMATTR></OMBVAR>
+                <OMSTR>a &amp; b</OMSTR>
+            </OMBIND>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            c,
+            Content::bind(sym("quant1", "forall"), ["x"], Content::cs("a & b"))
+        );
+        let c = parse_xml(r#"<OMF hex="3FF8000000000000"/>"#).unwrap();
+        assert!(matches!(c, Content::Cn { t, .. } if t == "1.5"));
+        let c =
+            parse_xml(r#"<OME><OMS cd="moreerrors" name="unexpected"/><OMSTR>oops</OMSTR></OME>"#);
+        assert_eq!(c.unwrap(), Content::err("oops"));
+        assert!(matches!(
+            parse_xml("<OMR href=\"#a\"/>"),
+            Ok(Content::Err(_))
+        ));
+        assert!(parse_xml("<OMA>").is_err());
+    }
+
+    #[test]
+    fn parse_json_objects() {
+        let c = parse_json(
+            r#"{"kind": "OMOBJ", "openmath": "2.0", "object": {
+                "kind": "OMA",
+                "applicant": {"kind": "OMS", "cd": "arith1", "name": "plus"},
+                "arguments": [
+                    {"kind": "OMA",
+                     "applicant": {"kind": "OMS", "cd": "transc1", "name": "sin"},
+                     "arguments": [{"kind": "OMV", "name": "x"}]},
+                    {"kind": "OMI", "hexadecimal": "-0x1A"},
+                    {"kind": "OMF", "float": 0.5}
+                ]
+            }}"#,
+        )
+        .unwrap();
+        assert_eq!(c, sin_plus());
+
+        let c = parse_json(
+            r#"{"kind": "OMBIND",
+                "binder": {"kind": "OMS", "cd": "fns1", "name": "lambda"},
+                "variables": [{"kind": "OMV", "name": "y"}],
+                "object": {"kind": "OMI", "integer": 12345678901234567890}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            c,
+            Content::bind(
+                sym("fns1", "lambda"),
+                ["y"],
+                Content::cn("12345678901234567890")
+            )
+        );
+        assert!(matches!(
+            parse_json(r#"{"kind": "OMX"}"#),
+            Ok(Content::Err(_))
+        ));
+        assert!(parse_json(r#"{"kind": "#).is_err());
+    }
+
+    #[test]
+    fn xml_roundtrip() {
+        let c = Content::apply(
+            sym("relation1", "eq"),
+            [
+                sin_plus(),
+                Content::bind(sym("fns1", "lambda"), ["x", "y"], Content::cs("<s>")),
+                Content::err("bad"),
+            ],
+        );
+        let xml = c.to_openmath_xml();
+        assert!(
+            xml.starts_with(r#"<OMOBJ xmlns="http://www.openmath.org/OpenMath" version="2.0">"#)
+        );
+        assert!(xml.contains(r#"<OMI>-26</OMI><OMF dec="0.5"/>"#));
+        assert_eq!(parse_xml(&xml).unwrap(), c);
+    }
+
+    #[test]
+    fn json_roundtrip() {
+        let c = Content::apply(
+            sym("relation1", "eq"),
+            [
+                sin_plus(),
+                Content::bind(sym("fns1", "lambda"), ["x", "y"], Content::cs("\"s\"")),
+                Content::err("bad"),
+            ],
+        );
+        let json = c.to_openmath_json();
+        assert!(json.starts_with(r#"{"kind":"OMOBJ","openmath":"2.0","object":"#));
+        assert!(json.contains(r#"{"kind":"OMI","integer":-26}"#));
+        assert_eq!(parse_json(&json).unwrap(), c);
+    }
+
+    #[test]
+    fn presentation() {
+        let c = parse_xml(
+            r#"<OMA>
+                <OMS cd="arith1" name="divide"/>
+                <OMA><OMS cd="arith1" name="unary_minus"/><OMV name="b"/></OMA>
+                <OMA>
+                    <OMS cd="arith1" name="times"/>
+                    <OMI>2</OMI>
+                    <OMV name="a"/>
+                </OMA>
+            </OMA>"#,
+        )
+        .unwrap();
+        let e = c.to_element();
+        let MathElement::Frac { num, den, .. } = e.elem() else {
+            panic!("expected a fraction, got {:?}", e);
+        };
+        assert_eq!(**num, Element::row([Element::op('−'), Element::id("b")]));
+        assert_eq!(
+            **den,
+            Element::row([Element::num("2"), Element::op('\u{2062}'), Element::id("a")])
+        );
+        assert_eq!(Content::from_element(&e), Some(c.clone()));
+        assert_eq!(e.to_openmath_xml(), Some(c.to_openmath_xml()));
+    }
+
+    #[test]
+    fn unknown_symbols() {
+        let c = parse_xml(r#"<OMA><OMS cd="mycd" name="frob"/><OMV name="x"/></OMA>"#).unwrap();
+        let e = c.to_element();
+        assert_eq!(Content::from_element(&e), Some(c.clone()));
+        assert_eq!(e.to_openmath_xml(), Some(c.to_openmath_xml()));
+        let c = parse_xml(r#"<OMS cd="mycd" name="frob"/>"#).unwrap();
+        let xml = c.to_element().to_openmath_xml().unwrap();
+        assert!(xml.contains(r#"<OMS cd="mycd" name="frob"/>"#), "{}", xml);
+    }
+
+    #[test]
+    fn ambiguous_presentation() {
+        // Closed intervals and lists are both written with square brackets.
+        let e = Element::row([
+            Element::op('['),
+            Element::num("0"),
+            Element::op(','),
+            Element::num("1"),
+            Element::op(']'),
+        ]);
+        assert_eq!(e.to_openmath_json(), None);
+        let e = Element::row([Element::op('{'), Element::num("0"), Element::op('}')]);
+        assert_eq!(
+            e.to_openmath_json().as_deref(),
+            Some(concat!(
+                r#"{"kind":"OMOBJ","openmath":"2.0","object":{"kind":"OMA","#,
+                r#""applicant":{"kind":"OMS","cd":"set1","name":"set"},"#,
+                r#""arguments":[{"kind":"OMI","integer":0}]}}"#
+            ))
+        );
+    }
+}
@@ -0,0 +1,874 @@
+//! The MathML Core operator dictionary and operator resolution.
+//!
+//! The dictionary follows the compact form used by MathML Core: every entry
+//! maps a range of characters in a given form to a category, and the
+//! category supplies the spacing and properties. Characters that aren't
+//! found in any form get the default spacing of 5/18 em on each side and no
+//! properties, which is what most relations want.
+//!
+//! Operator forms are inferred from position, as described in
+//! [`infer_forms`]. [`Element::resolve_operators`] rewrites every
+//! [`MathElement::Op`] and [`MathElement::Oper`] in a tree into a
+//! [`MathElement::ResolvedOper`]. Properties set on an [`Operator`] always
+//! win over the dictionary.
+
+use crate::math::*;
+
+/// Properties shared by a group of dictionary entries. Spacing is in
+/// eighteenths of an em, the "math unit" used by the dictionary.
+#[derive(Clone, Copy, Debug)]
+struct Category {
+    form: OpForm,
+    lspace: u8,
+    rspace: u8,
+    stretchy: bool,
+    symmetric: bool,
+    large_op: bool,
+    movable_limits: bool,
+    fence: bool,
+}
+
+const fn category(form: OpForm, lspace: u8, rspace: u8) -> Category {
+    Category {
+        form,
+        lspace,
+        rspace,
+        stretchy: false,
+        symmetric: false,
+        large_op: false,
+        movable_limits: false,
+        fence: false,
+    }
+}
+
+/// Stretchy infix arrows.
+const ARROW: Category = Category {
+    stretchy: true,
+    ..category(OpForm::Infix, 5, 5)
+};
+/// Additive and set operators.
+const ADDITIVE: Category = category(OpForm::Infix, 4, 4);
+/// Multiplicative operators.
+const MULTIPLICATIVE: Category = category(OpForm::Infix, 3, 3);
+/// Invisible operators, like function application.
+const INVISIBLE: Category = category(OpForm::Infix, 0, 0);
+/// List separators.
+const SEPARATOR: Category = category(OpForm::Infix, 0, 3);
+/// Vertical bars used between terms, as in set-builder notation.
+const DIVIDER: Category = Category {
+    stretchy: true,
+    symmetric: true,
+    fence: true,
+    ..category(OpForm::Infix, 2, 2)
+};
+/// Tight prefix operators.
+const PREFIX: Category = category(OpForm::Prefix, 0, 0);
+/// Tight postfix operators.
+const POSTFIX: Category = category(OpForm::Postfix, 0, 0);
+/// Opening fences.
+const OPEN: Category = Category {
+    stretchy: true,
+    symmetric: true,
+    fence: true,
+    ..category(OpForm::Prefix, 0, 0)
+};
+/// Closing fences.
+const CLOSE: Category = Category {
+    stretchy: true,
+    symmetric: true,
+    fence: true,
+    ..category(OpForm::Postfix, 0, 0)
+};
+/// Large operators whose limits move to scripts in inline math.
+const LARGE: Category = Category {
+    symmetric: true,
+    large_op: true,
+    movable_limits: true,
+    ..category(OpForm::Prefix, 3, 3)
+};
+/// Integrals, which are large but keep their limits in place.
+const INTEGRAL: Category = Category {
+    symmetric: true,
+    large_op: true,
+    ..category(OpForm::Prefix, 3, 3)
+};
+/// Stretchy accents placed over or under a base.
+const ACCENT: Category = Category {
+    stretchy: true,
+    ..category(OpForm::Postfix, 0, 0)
+};
+
+/// Inclusive character ranges and their category. A character may appear
+/// once per form.
+#[rustfmt::skip]
+const DICTIONARY: &[(char, char, Category)] = &[
+    // Infix
+    ('\u{2190}', '\u{2195}', ARROW),
+    ('\u{219A}', '\u{21AE}', ARROW),
+    ('\u{21B0}', '\u{21B5}', ARROW),
+    ('\u{21B9}', '\u{21B9}', ARROW),
+    ('\u{21BC}', '\u{21D5}', ARROW),
+    ('\u{21DA}', '\u{21F0}', ARROW),
+    ('\u{21F3}', '\u{21FF}', ARROW),
+    ('\u{2794}', '\u{2794}', ARROW),
+    ('\u{2799}', '\u{2799}', ARROW),
+    ('\u{279B}', '\u{27A1}', ARROW),
+    ('\u{27A5}', '\u{27A6}', ARROW),
+    ('\u{27A8}', '\u{27AF}', ARROW),
+    ('\u{27F0}', '\u{27F1}', ARROW),
+    ('\u{27F5}', '\u{27FF}', ARROW),
+    ('\u{2900}', '\u{2920}', ARROW),
+    ('\u{2934}', '\u{2937}', ARROW),
+    ('\u{2942}', '\u{2975}', ARROW),
+    ('\u{297C}', '\u{297F}', ARROW),
+    ('\u{2B04}', '\u{2B07}', ARROW),
+    ('\u{2B0C}', '\u{2B11}', ARROW),
+    ('\u{2B30}', '\u{2B3E}', ARROW),
+    ('\u{2B40}', '\u{2B4C}', ARROW),
+    ('+', '+', ADDITIVE),
+    ('-', '-', ADDITIVE),
+    ('/', '/', ADDITIVE),
+    ('\u{B1}', '\u{B1}', ADDITIVE),
+    ('\u{F7}', '\u{F7}', ADDITIVE),
+    ('\u{2044}', '\u{2044}', ADDITIVE),
+    ('\u{2212}', '\u{2216}', ADDITIVE),
+    ('\u{2227}', '\u{222A}', ADDITIVE),
+    ('\u{2238}', '\u{2238}', ADDITIVE),
+    ('\u{228C}', '\u{228E}', ADDITIVE),
+    ('\u{2293}', '\u{2296}', ADDITIVE),
+    ('\u{2298}', '\u{2298}', ADDITIVE),
+    ('\u{229D}', '\u{229F}', ADDITIVE),
+    ('\u{22BB}', '\u{22BD}', ADDITIVE),
+    ('\u{22CE}', '\u{22CF}', ADDITIVE),
+    ('\u{22D2}', '\u{22D3}', ADDITIVE),
+    ('\u{2795}', '\u{2797}', ADDITIVE),
+    ('\u{29B8}', '\u{29B8}', ADDITIVE),
+    ('\u{29BC}', '\u{29BC}', ADDITIVE),
+    ('\u{29C4}', '\u{29C5}', ADDITIVE),
+    ('\u{29F5}', '\u{29FB}', ADDITIVE),
+    ('\u{2A22}', '\u{2A2E}', ADDITIVE),
+    ('\u{2A40}', '\u{2A4F}', ADDITIVE),
+    ('\u{2A51}', '\u{2A63}', ADDITIVE),
+    ('%', '%', MULTIPLICATIVE),
+    ('*', '*', MULTIPLICATIVE),
+    ('.', '.', MULTIPLICATIVE),
+    ('?', '?', MULTIPLICATIVE),
+    ('@', '@', MULTIPLICATIVE),
+    ('^', '^', MULTIPLICATIVE),
+    ('\u{B7}', '\u{B7}', MULTIPLICATIVE),
+    ('\u{D7}', '\u{D7}', MULTIPLICATIVE),
+    ('\u{2022}', '\u{2022}', MULTIPLICATIVE),
+    ('\u{2043}', '\u{2043}', MULTIPLICATIVE),
+    ('\u{2217}', '\u{2219}', MULTIPLICATIVE),
+    ('\u{2240}', '\u{2240}', MULTIPLICATIVE),
+    ('\u{2297}', '\u{2297}', MULTIPLICATIVE),
+    ('\u{2299}', '\u{229B}', MULTIPLICATIVE),
+    ('\u{22A0}', '\u{22A1}', MULTIPLICATIVE),
+    ('\u{22BA}', '\u{22BA}', MULTIPLICATIVE),
+    ('\u{22C4}', '\u{22C7}', MULTIPLICATIVE),
+    ('\u{22C9}', '\u{22CC}', MULTIPLICATIVE),
+    ('\u{2305}', '\u{2306}', MULTIPLICATIVE),
+    ('\u{27CB}', '\u{27CB}', MULTIPLICATIVE),
+    ('\u{27CD}', '\u{27CD}', MULTIPLICATIVE),
+    ('\u{29C6}', '\u{29C8}', MULTIPLICATIVE),
+    ('\u{29D4}', '\u{29D7}', MULTIPLICATIVE),
+    ('\u{29E2}', '\u{29E2}', MULTIPLICATIVE),
+    ('\u{2A1D}', '\u{2A1E}', MULTIPLICATIVE),
+    ('\u{2A2F}', '\u{2A3D}', MULTIPLICATIVE),
+    ('\u{2A3F}', '\u{2A3F}', MULTIPLICATIVE),
+    ('\u{2A50}', '\u{2A50}', MULTIPLICATIVE),
+    ('\u{2A64}', '\u{2A65}', MULTIPLICATIVE),
+    ('\u{2061}', '\u{2064}', INVISIBLE),
+    (',', ',', SEPARATOR),
+    (';', ';', SEPARATOR),
+    ('|', '|', DIVIDER),
+    ('\u{2016}', '\u{2016}', DIVIDER),
+    // Prefix
+    ('!', '!', PREFIX),
+    ('+', '+', PREFIX),
+    ('-', '-', PREFIX),
+    ('\u{AC}', '\u{AC}', PREFIX),
+    ('\u{B1}', '\u{B1}', PREFIX),
+    ('\u{2018}', '\u{2018}', PREFIX),
+    ('\u{201C}', '\u{201C}', PREFIX),
+    ('\u{2200}', '\u{2204}', PREFIX),
+    ('\u{2207}', '\u{2207}', PREFIX),
+    ('\u{2212}', '\u{2213}', PREFIX),
+    ('\u{221A}', '\u{221C}', PREFIX),
+    ('\u{2220}', '\u{2222}', PREFIX),
+    ('\u{2310}', '\u{2310}', PREFIX),
+    ('\u{2319}', '\u{2319}', PREFIX),
+    ('\u{2AEC}', '\u{2AED}', PREFIX),
+    ('(', '(', OPEN),
+    ('[', '[', OPEN),
+    ('{', '{', OPEN),
+    ('|', '|', OPEN),
+    ('\u{2016}', '\u{2016}', OPEN),
+    ('\u{2308}', '\u{2308}', OPEN),
+    ('\u{230A}', '\u{230A}', OPEN),
+    ('\u{2329}', '\u{2329}', OPEN),
+    ('\u{2772}', '\u{2772}', OPEN),
+    ('\u{27E6}', '\u{27E6}', OPEN),
+    ('\u{27E8}', '\u{27E8}', OPEN),
+    ('\u{27EA}', '\u{27EA}', OPEN),
+    ('\u{27EC}', '\u{27EC}', OPEN),
+    ('\u{27EE}', '\u{27EE}', OPEN),
+    ('\u{2980}', '\u{2980}', OPEN),
+    ('\u{2983}', '\u{2983}', OPEN),
+    ('\u{2985}', '\u{2985}', OPEN),
+    ('\u{2987}', '\u{2987}', OPEN),
+    ('\u{2989}', '\u{2989}', OPEN),
+    ('\u{298B}', '\u{298B}', OPEN),
+    ('\u{298D}', '\u{298D}', OPEN),
+    ('\u{298F}', '\u{298F}', OPEN),
+    ('\u{2991}', '\u{2991}', OPEN),
+    ('\u{2993}', '\u{2993}', OPEN),
+    ('\u{2995}', '\u{2995}', OPEN),
+    ('\u{2997}', '\u{2997}', OPEN),
+    ('\u{29FC}', '\u{29FC}', OPEN),
+    ('\u{220F}', '\u{2211}', LARGE),
+    ('\u{22C0}', '\u{22C3}', LARGE),
+    ('\u{2A00}', '\u{2A0A}', LARGE),
+    ('\u{2AFC}', '\u{2AFC}', LARGE),
+    ('\u{2AFF}', '\u{2AFF}', LARGE),
+    ('\u{222B}', '\u{2233}', INTEGRAL),
+    ('\u{2A0B}', '\u{2A1C}', INTEGRAL),
+    // Postfix
+    ('!', '!', POSTFIX),
+    ('"', '"', POSTFIX),
+    ('%', '%', POSTFIX),
+    ('&', '\'', POSTFIX),
+    ('`', '`', POSTFIX),
+    ('\u{A8}', '\u{A8}', POSTFIX),
+    ('\u{B0}', '\u{B0}', POSTFIX),
+    ('\u{B2}', '\u{B4}', POSTFIX),
+    ('\u{B8}', '\u{B9}', POSTFIX),
+    ('\u{2CA}', '\u{2CB}', POSTFIX),
+    ('\u{2D8}', '\u{2DA}', POSTFIX),
+    ('\u{2DD}', '\u{2DD}', POSTFIX),
+    ('\u{311}', '\u{311}', POSTFIX),
+    ('\u{2019}', '\u{2019}', POSTFIX),
+    ('\u{201D}', '\u{201D}', POSTFIX),
+    ('\u{2032}', '\u{2037}', POSTFIX),
+    ('\u{2057}', '\u{2057}', POSTFIX),
+    ('\u{20DB}', '\u{20DC}', POSTFIX),
+    (')', ')', CLOSE),
+    (']', ']', CLOSE),
+    ('}', '}', CLOSE),
+    ('|', '|', CLOSE),
+    ('\u{2016}', '\u{2016}', CLOSE),
+    ('\u{2309}', '\u{2309}', CLOSE),
+    ('\u{230B}', '\u{230B}', CLOSE),
+    ('\u{232A}', '\u{232A}', CLOSE),
+    ('\u{2773}', '\u{2773}', CLOSE),
+    ('\u{27E7}', '\u{27E7}', CLOSE),
+    ('\u{27E9}', '\u{27E9}', CLOSE),
+    ('\u{27EB}', '\u{27EB}', CLOSE),
+    ('\u{27ED}', '\u{27ED}', CLOSE),
+    ('\u{27EF}', '\u{27EF}', CLOSE),
+    ('\u{2980}', '\u{2980}', CLOSE),
+    ('\u{2984}', '\u{2984}', CLOSE),
+    ('\u{2986}', '\u{2986}', CLOSE),
+    ('\u{2988}', '\u{2988}', CLOSE),
+    ('\u{298A}', '\u{298A}', CLOSE),
+    ('\u{298C}', '\u{298C}', CLOSE),
+    ('\u{298E}', '\u{298E}', CLOSE),
+    ('\u{2990}', '\u{2990}', CLOSE),
+    ('\u{2992}', '\u{2992}', CLOSE),
+    ('\u{2994}', '\u{2994}', CLOSE),
+    ('\u{2996}', '\u{2996}', CLOSE),
+    ('\u{2998}', '\u{2998}', CLOSE),
+    ('\u{29FD}', '\u{29FD}', CLOSE),
+    ('^', '_', ACCENT),
+    ('~', '~', ACCENT),
+    ('\u{AF}', '\u{AF}', ACCENT),
+    ('\u{2C6}', '\u{2C7}', ACCENT),
+    ('\u{2C9}', '\u{2C9}', ACCENT),
+    ('\u{2CD}', '\u{2CD}', ACCENT),
+    ('\u{2DC}', '\u{2DC}', ACCENT),
+    ('\u{2F7}', '\u{2F7}', ACCENT),
+    ('\u{302}', '\u{302}', ACCENT),
+    ('\u{203E}', '\u{203E}', ACCENT),
+    ('\u{2322}', '\u{2323}', ACCENT),
+    ('\u{23B4}', '\u{23B5}', ACCENT),
+    ('\u{23DC}', '\u{23E1}', ACCENT),
+];
+
+/// Characters that separate items in a list.
+const SEPARATORS: &[char] = &[',', ';', '\u{2063}'];
+
+/// The default spacing for characters not in the dictionary, in eighteenths
+/// of an em.
+const DEFAULT_SPACE: u8 = 5;
+
+/// Order in which other forms are tried when a character has no entry for
+/// the requested form.
+const FALLBACK: [OpForm; 3] = [OpForm::Infix, OpForm::Postfix, OpForm::Prefix];
+
+fn entry(c: char, form: OpForm) -> Option<Category> {
+    DICTIONARY
+        .iter()
+        .find(|(lo, hi, cat)| cat.form == form && (*lo..=*hi).contains(&c))
+        .map(|(_, _, cat)| *cat)
+}
+
+fn mu(v: u8) -> Length {
+    Length::Em(v as f32 / 18.0)
+}
+
+/// Check if the dictionary has an entry for a character in the given form.
+pub fn contains(c: char, form: OpForm) -> bool {
+    entry(c, form).is_some()
+}
+
+/// The forms a character has entries for in the dictionary, in the order
+/// they're tried when the requested form isn't present.
+pub fn forms(c: char) -> impl Iterator<Item = OpForm> {
+    FALLBACK.into_iter().filter(move |f| contains(c, *f))
+}
+
+/// Look up a character in the operator dictionary.
+///
+/// If there is no entry for the requested form, the other forms are tried
+/// in the order infix, postfix, prefix. If none match, the operator gets
+/// the dictionary defaults. The returned operator always keeps the
+/// requested form.
+pub fn lookup(c: char, form: OpForm) -> ResolvedOperator {
+    let cat = std::iter::once(form)
+        .chain(FALLBACK)
+        .find_map(|f| entry(c, f))
+        .unwrap_or(category(form, DEFAULT_SPACE, DEFAULT_SPACE));
+    ResolvedOperator {
+        t: c,
+        form,
+        max_size: None,
+        min_size: Length::Em(1.0),
+        lspace: mu(cat.lspace),
+        rspace: mu(cat.rspace),
+        stretchy: cat.stretchy,
+        symmetric: cat.symmetric,
+        large_op: cat.large_op,
+        movable_limits: cat.movable_limits,
+        separator: SEPARATORS.contains(&c),
+        fence: cat.fence,
+    }
+}
+
+/// Resolve a length that may be a fraction of the dictionary value.
+fn length(v: &Option<LengthOrFraction>, default: Length) -> Length {
+    match (v, default) {
+        (None, d) => d,
+        (Some(LengthOrFraction::Em(v)), _) => Length::Em(*v),
+        (Some(LengthOrFraction::Ex(v)), _) => Length::Ex(*v),
+        (Some(LengthOrFraction::Frac(f)), Length::Em(d)) => Length::Em(f * d),
+        (Some(LengthOrFraction::Frac(f)), Length::Ex(d)) => Length::Ex(f * d),
+    }
+}
+
+/// Resolve a size that may be a fraction of the unstretched size.
+fn size(v: &Option<LengthOrFraction>, default: Length) -> Length {
+    match v {
+        Some(LengthOrFraction::Frac(f)) => Length::Em(*f),
+        v => length(v, default),
+    }
+}
+
+impl Operator {
+    /// Resolve every property of this operator, using the dictionary entry
+    /// for any property that isn't set. If the operator has no form, `form`
+    /// is used instead.
+    ///
+    /// Fractional sizes are relative to the operator's unstretched size,
+    /// which is taken to be 1em, and fractional spacing is relative to the
+    /// dictionary spacing.
+    pub fn resolve(&self, form: OpForm) -> ResolvedOperator {
+        let d = lookup(self.t, self.form.unwrap_or(form));
+        ResolvedOperator {
+            t: self.t,
+            form: d.form,
+            max_size: match &self.max_size {
+                None => d.max_size,
+                v => Some(size(v, Length::Em(1.0))),
+            },
+            min_size: size(&self.min_size, d.min_size),
+            lspace: length(&self.lspace, d.lspace),
+            rspace: length(&self.rspace, d.rspace),
+            stretchy: self.stretchy.unwrap_or(d.stretchy),
+            symmetric: self.symmetric.unwrap_or(d.symmetric),
+            large_op: self.large_op.unwrap_or(d.large_op),
+            movable_limits: self.movable_limits.unwrap_or(d.movable_limits),
+            separator: self.separator.unwrap_or(d.separator),
+            fence: self.fence.unwrap_or(d.fence),
+        }
+    }
+}
+
+impl ResolvedOperator {
+    /// Look up an operator in the dictionary. See [`lookup`].
+    pub fn from_dictionary(t: char, form: OpForm) -> Self {
+        lookup(t, form)
+    }
+}
+
+impl Element {
+    /// Rewrite every [`MathElement::Op`] and [`MathElement::Oper`] in this
+    /// tree into a fully populated [`MathElement::ResolvedOper`]. Operators
+    /// without an explicit form get the one given by [`infer_forms`].
+    pub fn resolve_operators(&mut self) {
+        let forms = infer_forms(self);
+        resolve(self, &mut forms.into_iter());
+    }
+}
+
+/// If `e` is an embellished operator, return its core operator.
+///
+/// Following MathML, an operator is embellished by scripts, limits, and
+/// fraction numerators, and by rows, padding, and phantoms holding it along
+/// with nothing but spaces. Renderers should treat the whole embellished
+/// operator as the operator when deciding its form and spacing.
+pub fn core_operator(e: &Element) -> Option<&Element> {
+    use MathElement::*;
+    match e.elem() {
+        Op(_) | Oper(_) | ResolvedOper(_) => Some(e),
+        Sup { base, .. }
+        | Sub { base, .. }
+        | SubSup { base, .. }
+        | Over { base, .. }
+        | Under { base, .. }
+        | UnderOver { base, .. }
+        | MultiScript { base, .. }
+        | Frac { num: base, .. } => core_operator(base),
+        Row(v) | Phantom(v) => group_core(v),
+        Padding(p) => group_core(&p.elems),
+        _ => None,
+    }
+}
+
+fn group_core(elems: &[Element]) -> Option<&Element> {
+    let mut core = None;
+    for e in elems.iter().filter(|e| !is_space_like(e)) {
+        if core.is_some() {
+            return None;
+        }
+        core = Some(core_operator(e)?);
+    }
+    core
+}
+
+fn is_space_like(e: &Element) -> bool {
+    match e.elem() {
+        MathElement::Space(_) => true,
+        MathElement::Row(v) | MathElement::Phantom(v) => v.iter().all(is_space_like),
+        MathElement::Padding(p) => p.elems.iter().all(is_space_like),
+        _ => false,
+    }
+}
+
+fn explicit_form(op: &Element) -> Option<OpForm> {
+    match op.elem() {
+        MathElement::Oper(op) => op.form,
+        MathElement::ResolvedOper(op) => Some(op.form),
+        _ => None,
+    }
+}
+
+/// The form of each element of a row that is an embellished operator, or
+/// `None` for the other elements.
+///
+/// An operator's own form always wins. Otherwise it is prefix when first in
+/// a row of several elements, postfix when last, and infix in any other
+/// case, including when it's alone. Space-like elements are ignored when
+/// deciding this, as in MathML Core.
+pub fn row_forms(elems: &[Element]) -> Vec<Option<OpForm>> {
+    let visible: Vec<usize> = (0..elems.len())
+        .filter(|i| !is_space_like(&elems[*i]))
+        .collect();
+    let several = visible.len() > 1;
+    elems
+        .iter()
+        .enumerate()
+        .map(|(i, e)| {
+            let core = core_operator(e)?;
+            Some(
+                explicit_form(core).unwrap_or(if several && visible.first() == Some(&i) {
+                    OpForm::Prefix
+                } else if several && visible.last() == Some(&i) {
+                    OpForm::Postfix
+                } else {
+                    OpForm::Infix
+                }),
+            )
+        })
+        .collect()
+}
+
+/// Infer the form of every operator in a tree, in document order.
+///
+/// Each embellished operator gets its form from [`row_forms`], and passes
+/// it on to its core operator. Operators that aren't in a row, such as a
+/// lone operator in a script, are infix. The contents of table cells,
+/// padding, phantoms, and square roots are treated as rows.
+pub fn infer_forms(e: &Element) -> Vec<OpForm> {
+    let mut out = Vec::new();
+    infer(e, None, &mut out);
+    out
+}
+
+/// `form` is set when `e` is an embellished operator whose form has already
+/// been decided.
+fn infer(e: &Element, form: Option<OpForm>, out: &mut Vec<OpForm>) {
+    use MathElement::*;
+    match e.elem() {
+        Op(_) => out.push(form.unwrap_or(OpForm::Infix)),
+        Oper(op) => out.push(op.form.or(form).unwrap_or(OpForm::Infix)),
+        ResolvedOper(op) => out.push(op.form),
+        Text(_) | Id { .. } | Num(_) | Err(_) | Space(_) | Str(_) | Unknown { .. } => (),
+        Phantom(v) | Row(v) => infer_row(v, form, out),
+        Padding(p) => infer_row(&p.elems, form, out),
+        Sqrt(b) => infer(b, None, out),
+        Root { base, index } => {
+            infer(base, None, out);
+            infer(index, None, out);
+        }
+        Frac { num: a, den: b, .. }
+        | Sup { base: a, sup: b }
+        | Sub { base: a, sub: b }
+        | Over {
+            base: a, over: b, ..
+        }
+        | Under {
+            base: a, under: b, ..
+        } => {
+            infer(a, form, out);
+            infer(b, None, out);
+        }
+        SubSup { base, sub, sup } => {
+            infer(base, form, out);
+            infer(sub, None, out);
+            infer(sup, None, out);
+        }
+        UnderOver {
+            base, under, over, ..
+        } => {
+            infer(base, form, out);
+            infer(under, None, out);
+            infer(over, None, out);
+        }
+        MultiScript { base, post, pre } => {
+            infer(base, form, out);
+            for p in post.iter().chain(pre.iter()) {
+                infer(&p.sub, None, out);
+                infer(&p.sup, None, out);
+            }
+        }
+        Table { rows } => rows
+            .iter()
+            .flat_map(|r| r.cells.iter())
+            .for_each(|c| infer_row(&c.elems, None, out)),
+    }
+}
+
+fn infer_row(elems: &[Element], form: Option<OpForm>, out: &mut Vec<OpForm>) {
+    if form.is_some() {
+        // The row is itself embellishing an operator.
+        for e in elems {
+            infer(e, form.filter(|_| !is_space_like(e)), out);
+        }
+    } else {
+        for (e, f) in elems.iter().zip(row_forms(elems)) {
+            infer(e, f, out);
+        }
+    }
+}
+
+/// Resolve operators in the same order [`infer`] visits them.
+fn resolve(elem: &mut Element, forms: &mut impl Iterator<Item = OpForm>) {
+    use MathElement::*;
+    let e = elem.elem_mut();
+    match e {
+        Op(c) => {
+            let form = forms.next().unwrap_or(OpForm::Infix);
+            *e = ResolvedOper(lookup(*c, form));
+        }
+        Oper(op) => {
+            let form = forms.next().unwrap_or(OpForm::Infix);
+            *e = ResolvedOper(op.resolve(form));
+        }
+        ResolvedOper(_) => {
+            forms.next();
+        }
+        Text(_) | Id { .. } | Num(_) | Err(_) | Space(_) | Str(_) | Unknown { .. } => (),
+        Phantom(v) | Row(v) => v.iter_mut().for_each(|e| resolve(e, forms)),
+        Padding(p) => p.elems.iter_mut().for_each(|e| resolve(e, forms)),
+        Sqrt(b) => resolve(b, forms),
+        Frac { num: a, den: b, .. }
+        | Root { base: a, index: b }
+        | Sup { base: a, sup: b }
+        | Sub { base: a, sub: b }
+        | Over {
+            base: a, over: b, ..
+        }
+        | Under {
+            base: a, under: b, ..
+        } => {
+            resolve(a, forms);
+            resolve(b, forms);
+        }
+        SubSup { base, sub, sup } => {
+            resolve(base, forms);
+            resolve(sub, forms);
+            resolve(sup, forms);
+        }
+        UnderOver {
+            base, under, over, ..
+        } => {
+            resolve(base, forms);
+            resolve(under, forms);
+            resolve(over, forms);
+        }
+        MultiScript { base, post, pre } => {
+            resolve(base, forms);
+            for p in post.iter_mut().chain(pre.iter_mut()) {
+                resolve(&mut p.sub, forms);
+                resolve(&mut p.sup, forms);
+            }
+        }
+        Table { rows } => rows
+            .iter_mut()
+            .flat_map(|r| r.cells.iter_mut())
+            .flat_map(|c| c.elems.iter_mut())
+            .for_each(|e| resolve(e, forms)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn em(v: f32) -> Length {
+        Length::Em(v)
+    }
+
+    #[test]
+    fn spacing() {
+        let plus = lookup('+', OpForm::Infix);
+        assert_eq!(plus.lspace, em(4.0 / 18.0));
+        assert_eq!(plus.rspace, em(4.0 / 18.0));
+        let times = lookup('×', OpForm::Infix);
+        assert_eq!(times.lspace, em(3.0 / 18.0));
+        let minus = lookup('−', OpForm::Prefix);
+        assert_eq!(minus.lspace, em(0.0));
+        assert_eq!(minus.rspace, em(0.0));
+        let eq = lookup('=', OpForm::Infix);
+        assert_eq!(eq.lspace, em(5.0 / 18.0));
+        assert!(!eq.stretchy && !eq.fence && !eq.large_op);
+        let comma = lookup(',', OpForm::Infix);
+        assert_eq!((comma.lspace, comma.rspace), (em(0.0), em(3.0 / 18.0)));
+        assert!(comma.separator);
+    }
+
+    #[test]
+    fn properties() {
+        let sum = lookup('∑', OpForm::Prefix);
+        assert!(sum.large_op && sum.movable_limits && sum.symmetric);
+        let int = lookup('∫', OpForm::Prefix);
+        assert!(int.large_op && !int.movable_limits);
+        let open = lookup('(', OpForm::Prefix);
+        assert!(open.fence && open.stretchy && open.symmetric);
+        let arrow = lookup('→', OpForm::Infix);
+        assert!(arrow.stretchy && !arrow.fence);
+        let bar = lookup('‾', OpForm::Postfix);
+        assert!(bar.stretchy);
+    }
+
+    #[test]
+    fn fallback() {
+        // No infix entry, so the postfix one is used but the form is kept.
+        let paren = lookup(')', OpForm::Infix);
+        assert_eq!(paren.form, OpForm::Infix);
+        assert!(paren.fence);
+        assert_eq!(paren.lspace, em(0.0));
+        // Bars have an entry in every form.
+        assert_eq!(forms('|').count(), 3);
+        assert_eq!(lookup('|', OpForm::Infix).lspace, em(2.0 / 18.0));
+        assert_eq!(lookup('|', OpForm::Prefix).lspace, em(0.0));
+        assert_eq!(forms('∑').next(), Some(OpForm::Prefix));
+        assert_eq!(forms('≤').next(), None);
+    }
+
+    #[test]
+    fn overrides() {
+        let op = Operator {
+            stretchy: Some(false),
+            lspace: Some(LengthOrFraction::Frac(0.5)),
+            rspace: Some(LengthOrFraction::Ex(1.0)),
+            max_size: Some(LengthOrFraction::Frac(2.0)),
+            ..Operator::new('(')
+        };
+        let r = op.resolve(OpForm::Infix);
+        assert_eq!(r.form, OpForm::Infix);
+        assert!(!r.stretchy);
+        assert!(r.fence && r.symmetric);
+        assert_eq!(r.lspace, em(0.0));
+        assert_eq!(r.rspace, Length::Ex(1.0));
+        assert_eq!(r.max_size, Some(em(2.0)));
+        assert_eq!(r.min_size, em(1.0));
+
+        let op = Operator {
+            form: Some(OpForm::Infix),
+            lspace: Some(LengthOrFraction::Frac(0.5)),
+            ..Operator::new('+')
+        };
+        let r = op.resolve(OpForm::Prefix);
+        assert_eq!(r.form, OpForm::Infix);
+        assert_eq!(r.lspace, em(2.0 / 18.0));
+        assert_eq!(r.rspace, em(4.0 / 18.0));
+    }
+
+    #[test]
+    fn resolve_tree() {
+        let mut e = Element::row([
+            Element::under_over(Element::op('∑'), Element::id("i"), Element::id("n")),
+            Element::frac(Element::id("a"), Element::op('+')),
+            Element::oper(Operator {
+                large_op: Some(true),
+                ..Operator::new('=')
+            }),
+        ]);
+        e.resolve_operators();
+        let MathElement::Row(v) = e.elem() else {
+            panic!("expected a row");
+        };
+        let MathElement::UnderOver { base, .. } = v[0].elem() else {
+            panic!("expected limits");
+        };
+        assert_eq!(
+            *base.elem(),
+            MathElement::ResolvedOper(lookup('∑', OpForm::Prefix))
+        );
+        let MathElement::Frac { den, .. } = v[1].elem() else {
+            panic!("expected a fraction");
+        };
+        assert_eq!(
+            *den.elem(),
+            MathElement::ResolvedOper(lookup('+', OpForm::Infix))
+        );
+        let MathElement::ResolvedOper(eq) = v[2].elem() else {
+            panic!("expected an operator");
+        };
+        assert!(eq.large_op);
+        assert_eq!(eq.lspace, em(5.0 / 18.0));
+    }
+
+    #[test]
+    fn attributes_kept() {
+        let mut e = Element::op('+').variant(Variant::Bold);
+        e.resolve_operators();
+        assert_eq!(e.attributes().unwrap().variant, Some(Variant::Bold));
+        assert!(matches!(e.elem(), MathElement::ResolvedOper(_)));
+    }
+
+    #[test]
+    fn positions() {
+        use OpForm::*;
+        let e = Element::row([
+            Element::op('-'),
+            Element::id("a"),
+            Element::op('+'),
+            Element::id("b"),
+            Element::op('!'),
+        ]);
+        assert_eq!(infer_forms(&e), vec![Prefix, Infix, Postfix]);
+        // Alone in a row, in a script, or at the top.
+        assert_eq!(infer_forms(&Element::row([Element::op('+')])), vec![Infix]);
+        assert_eq!(
+            infer_forms(&Element::sup(Element::id("x"), Element::op('+'))),
+            vec![Infix]
+        );
+        // An explicit form wins over the position.
+        let e = Element::row([
+            Element::oper(Operator {
+                form: Some(Postfix),
+                ..Operator::new('+')
+            }),
+            Element::id("a"),
+        ]);
+        assert_eq!(infer_forms(&e), vec![Postfix]);
+        assert_eq!(row_forms(e_row(&e)), vec![Some(Postfix), None]);
+    }
+
+    #[test]
+    fn space_like_siblings() {
+        use OpForm::*;
+        let space = || Element::space(Space::width(Length::Em(0.5)));
+        let e = Element::row([space(), Element::op('-'), Element::id("x")]);
+        assert_eq!(infer_forms(&e), vec![Prefix]);
+        let e = Element::row([Element::id("x"), Element::op('!'), space()]);
+        assert_eq!(infer_forms(&e), vec![Postfix]);
+        let e = Element::row([Element::op('-'), space()]);
+        assert_eq!(infer_forms(&e), vec![Infix]);
+        assert_eq!(row_forms(e_row(&e)), vec![Some(Infix), None]);
+    }
+
+    fn e_row(e: &Element) -> &[Element] {
+        match e.elem() {
+            MathElement::Row(v) => v,
+            _ => panic!("expected a row"),
+        }
+    }
+
+    #[test]
+    fn embellished() {
+        use OpForm::*;
+        let sum = Element::under_over(Element::op('∑'), Element::op('='), Element::id("n"));
+        assert_eq!(core_operator(&sum), Some(&Element::op('∑')));
+        let padded = Element::row([
+            Element::space(Space::width(Length::Em(0.5))),
+            Element::sup(Element::op('-'), Element::num("1")),
+        ]);
+        assert_eq!(core_operator(&padded), Some(&Element::op('-')));
+        assert_eq!(
+            core_operator(&Element::sup(Element::id("x"), Element::op('+'))),
+            None
+        );
+        assert_eq!(
+            core_operator(&Element::row([Element::op('+'), Element::op('-')])),
+            None
+        );
+        // The embellished operator's position decides the core's form,
+        // while operators in its scripts are on their own.
+        let e = Element::row([Element::id("a"), sum, Element::id("i"), padded]);
+        assert_eq!(infer_forms(&e), vec![Infix, Infix, Postfix]);
+    }
+
+    #[test]
+    fn resolve_forms() {
+        let mut e = Element::row([
+            Element::op('('),
+            Element::id("a"),
+            Element::op('|'),
+            Element::id("b"),
+            Element::op('|'),
+        ]);
+        e.resolve_operators();
+        let forms: Vec<_> = e_row(&e)
+            .iter()
+            .filter_map(|e| match e.elem() {
+                MathElement::ResolvedOper(op) => Some((op.form, op.lspace.clone())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            forms,
+            vec![
+                (OpForm::Prefix, Length::Em(0.0)),
+                (OpForm::Infix, Length::Em(2.0 / 18.0)),
+                (OpForm::Postfix, Length::Em(0.0)),
+            ]
+        );
+        assert_eq!(
+            infer_forms(&e),
+            vec![OpForm::Prefix, OpForm::Infix, OpForm::Postfix]
+        );
+    }
+}
@@ -0,0 +1,849 @@
+//! Single-line plain-text rendering, for chat messages, logs, and CSV files.
+//!
+//! Scripts are written with Unicode superscript and subscript characters when
+//! every character in them has one, so `x²` and `aᵢ`, and otherwise fall back
+//! to `^(...)` and `_(...)`. Fractions become `(a)/(b)`, radicals `√(x+1)`,
+//! and styled letters are taken from the Mathematical Alphanumeric Symbols
+//! block. Everything can also be limited to plain ASCII.
+
+use crate::math::*;
+use crate::pretty::ASCII;
+use crate::tree::op_char;
+
+/// Options for plain-text rendering.
+#[derive(Clone, Debug)]
+pub struct PlainTextOptions {
+    /// Whether to use Unicode superscript and subscript characters for
+    /// scripts that can be written entirely with them.
+    pub script_chars: bool,
+    /// Whether to write letters and digits with a [`Variant`] as Mathematical
+    /// Alphanumeric Symbols, like `𝐱` for bold x.
+    pub styled_letters: bool,
+    /// Whether to write accents as combining characters, like `x̂`.
+    pub combining_accents: bool,
+    /// Whether to only use ASCII characters. Greek letters and operators are
+    /// spelled out, and the other options are ignored.
+    pub ascii: bool,
+}
+
+impl Default for PlainTextOptions {
+    fn default() -> Self {
+        Self {
+            script_chars: true,
+            styled_letters: true,
+            combining_accents: true,
+            ascii: false,
+        }
+    }
+}
+
+impl PlainTextOptions {
+    /// Options for ASCII-only output.
+    pub fn ascii() -> Self {
+        Self {
+            script_chars: false,
+            styled_letters: false,
+            combining_accents: false,
+            ascii: true,
+        }
+    }
+}
+
+impl Element {
+    /// Write the element out as one line of Unicode text, using the default
+    /// [`PlainTextOptions`].
+    pub fn to_plain_text(&self) -> String {
+        self.to_plain_text_with(&PlainTextOptions::default())
+    }
+
+    /// Write the element out as one line of text.
+    pub fn to_plain_text_with(&self, opts: &PlainTextOptions) -> String {
+        let w = Writer { opts };
+        w.elem(self, None, false)
+    }
+}
+
+const SUPERSCRIPTS: &[(char, char)] = &[
+    ('0', '⁰'),
+    ('1', '¹'),
+    ('2', '²'),
+    ('3', '³'),
+    ('4', '⁴'),
+    ('5', '⁵'),
+    ('6', '⁶'),
+    ('7', '⁷'),
+    ('8', '⁸'),
+    ('9', '⁹'),
+    ('+', '⁺'),
+    ('-', '⁻'),
+    ('−', '⁻'),
+    ('=', '⁼'),
+    ('(', '⁽'),
+    (')', '⁾'),
+    ('a', 'ᵃ'),
+    ('b', 'ᵇ'),
+    ('c', 'ᶜ'),
+    ('d', 'ᵈ'),
+    ('e', 'ᵉ'),
+    ('f', 'ᶠ'),
+    ('g', 'ᵍ'),
+    ('h', 'ʰ'),
+    ('i', 'ⁱ'),
+    ('j', 'ʲ'),
+    ('k', 'ᵏ'),
+    ('l', 'ˡ'),
+    ('m', 'ᵐ'),
+    ('n', 'ⁿ'),
+    ('o', 'ᵒ'),
+    ('p', 'ᵖ'),
+    ('r', 'ʳ'),
+    ('s', 'ˢ'),
+    ('t', 'ᵗ'),
+    ('u', 'ᵘ'),
+    ('v', 'ᵛ'),
+    ('w', 'ʷ'),
+    ('x', 'ˣ'),
+    ('y', 'ʸ'),
+    ('z', 'ᶻ'),
+    ('A', 'ᴬ'),
+    ('B', 'ᴮ'),
+    ('D', 'ᴰ'),
+    ('E', 'ᴱ'),
+    ('G', 'ᴳ'),
+    ('H', 'ᴴ'),
+    ('I', 'ᴵ'),
+    ('J', 'ᴶ'),
+    ('K', 'ᴷ'),
+    ('L', 'ᴸ'),
+    ('M', 'ᴹ'),
+    ('N', 'ᴺ'),
+    ('O', 'ᴼ'),
+    ('P', 'ᴾ'),
+    ('R', 'ᴿ'),
+    ('T', 'ᵀ'),
+    ('U', 'ᵁ'),
+    ('V', 'ⱽ'),
+    ('W', 'ᵂ'),
+    ('β', 'ᵝ'),
+    ('γ', 'ᵞ'),
+    ('δ', 'ᵟ'),
+    ('θ', 'ᶿ'),
+    ('φ', 'ᵠ'),
+    ('ϕ', 'ᵠ'),
+    ('χ', 'ᵡ'),
+];
+
+const SUBSCRIPTS: &[(char, char)] = &[
+    ('0', '₀'),
+    ('1', '₁'),
+    ('2', '₂'),
+    ('3', '₃'),
+    ('4', '₄'),
+    ('5', '₅'),
+    ('6', '₆'),
+    ('7', '₇'),
+    ('8', '₈'),
+    ('9', '₉'),
+    ('+', '₊'),
+    ('-', '₋'),
+    ('−', '₋'),
+    ('=', '₌'),
+    ('(', '₍'),
+    (')', '₎'),
+    ('a', 'ₐ'),
+    ('e', 'ₑ'),
+    ('h', 'ₕ'),
+    ('i', 'ᵢ'),
+    ('j', 'ⱼ'),
+    ('k', 'ₖ'),
+    ('l', 'ₗ'),
+    ('m', 'ₘ'),
+    ('n', 'ₙ'),
+    ('o', 'ₒ'),
+    ('p', 'ₚ'),
+    ('r', 'ᵣ'),
+    ('s', 'ₛ'),
+    ('t', 'ₜ'),
+    ('u', 'ᵤ'),
+    ('v', 'ᵥ'),
+    ('x', 'ₓ'),
+    ('β', 'ᵦ'),
+    ('γ', 'ᵧ'),
+    ('ρ', 'ᵨ'),
+    ('φ', 'ᵩ'),
+    ('ϕ', 'ᵩ'),
+    ('χ', 'ᵪ'),
+];
+
+/// Accent characters, with the combining mark they're written with and the
+/// function they're spelled out as.
+const ACCENTS: &[(char, char, &str)] = &[
+    ('^', '\u{302}', "hat"),
+    ('ˆ', '\u{302}', "hat"),
+    ('ˇ', '\u{30C}', "check"),
+    ('~', '\u{303}', "tilde"),
+    ('˜', '\u{303}', "tilde"),
+    ('´', '\u{301}', "acute"),
+    ('`', '\u{300}', "grave"),
+    ('˙', '\u{307}', "dot"),
+    ('¨', '\u{308}', "ddot"),
+    ('˘', '\u{306}', "breve"),
+    ('¯', '\u{305}', "bar"),
+    ('‾', '\u{305}', "bar"),
+    ('→', '\u{20D7}', "vec"),
+];
+
+/// Operators written with a space on either side.
+const SPACED: &[char] = &[
+    '=', '≠', '<', '>', '≤', '≥', '≈', '≡', '∼', '≅', '∝', '→', '←', '↔', '⇒', '⇐', '⇔', '↦', '∈',
+    '∉', '⊂', '⊆', '⊃', '⊇', '≔',
+];
+
+/// Operators that take limits.
+const LARGE: &[char] = &['∑', '∏', '∐', '∫', '∬', '∭', '∮', '⋃', '⋂', '⋀', '⋁'];
+
+/// Get the Mathematical Alphanumeric Symbol for a character in a variant.
+fn styled(c: char, v: Variant) -> Option<char> {
+    use Variant::*;
+    // Letters that were already in the Letterlike Symbols block.
+    let hole = match (v, c) {
+        (Italic, 'h') => Some('ℎ'),
+        (Script, 'B') => Some('ℬ'),
+        (Script, 'E') => Some('ℰ'),
+        (Script, 'F') => Some('ℱ'),
+        (Script, 'H') => Some('ℋ'),
+        (Script, 'I') => Some('ℐ'),
+        (Script, 'L') => Some('ℒ'),
+        (Script, 'M') => Some('ℳ'),
+        (Script, 'R') => Some('ℛ'),
+        (Script, 'e') => Some('ℯ'),
+        (Script, 'g') => Some('ℊ'),
+        (Script, 'o') => Some('ℴ'),
+        (Fraktur, 'C') => Some('ℭ'),
+        (Fraktur, 'H') => Some('ℌ'),
+        (Fraktur, 'I') => Some('ℑ'),
+        (Fraktur, 'R') => Some('ℜ'),
+        (Fraktur, 'Z') => Some('ℨ'),
+        (DoubleStruck, 'C') => Some('ℂ'),
+        (DoubleStruck, 'H') => Some('ℍ'),
+        (DoubleStruck, 'N') => Some('ℕ'),
+        (DoubleStruck, 'P') => Some('ℙ'),
+        (DoubleStruck, 'Q') => Some('ℚ'),
+        (DoubleStruck, 'R') => Some('ℝ'),
+        (DoubleStruck, 'Z') => Some('ℤ'),
+        _ => None,
+    };
+    if hole.is_some() {
+        return hole;
+    }
+    let latin = match v {
+        Bold => 0x1D400,
+        Italic => 0x1D434,
+        BoldItalic => 0x1D468,
+        Script => 0x1D49C,
+        BoldScript => 0x1D4D0,
+        Fraktur => 0x1D504,
+        DoubleStruck => 0x1D538,
+        BoldFraktur => 0x1D56C,
+        SansSerif => 0x1D5A0,
+        BoldSansSerif => 0x1D5D4,
+        SansSerifItalic => 0x1D608,
+        SansSerifBoldItalic => 0x1D63C,
+        Monospace => 0x1D670,
+        _ => return None,
+    };
+    let digits = match v {
+        Bold => Some(0x1D7CE),
+        DoubleStruck => Some(0x1D7D8),
+        SansSerif => Some(0x1D7E2),
+        BoldSansSerif => Some(0x1D7EC),
+        Monospace => Some(0x1D7F6),
+        _ => None,
+    };
+    let greek = match v {
+        Bold => Some(0x1D6A8),
+        Italic => Some(0x1D6E2),
+        BoldItalic => Some(0x1D71C),
+        BoldSansSerif => Some(0x1D756),
+        SansSerifBoldItalic => Some(0x1D790),
+        _ => None,
+    };
+    let n = c as u32;
+    let code = match c {
+        'A'..='Z' => latin + n - 'A' as u32,
+        'a'..='z' => latin + 26 + n - 'a' as u32,
+        '0'..='9' => digits? + n - '0' as u32,
+        // The capital theta symbol fills the gap left by the unused final
+        // capital sigma.
+        'Α'..='Ω' => greek? + n - 'Α' as u32,
+        'ϴ' => greek? + 17,
+        '∇' => greek? + 25,
+        'α'..='ω' => greek? + 26 + n - 'α' as u32,
+        '∂' => greek? + 51,
+        'ϵ' => greek? + 52,
+        'ϑ' => greek? + 53,
+        'ϰ' => greek? + 54,
+        'ϕ' => greek? + 55,
+        'ϱ' => greek? + 56,
+        'ϖ' => greek? + 57,
+        _ => return None,
+    };
+    char::from_u32(code)
+}
+
+/// Check if an element is a big operator, possibly with limits.
+fn is_large(e: &Element) -> bool {
+    match e.elem() {
+        MathElement::Sub { base, .. }
+        | MathElement::Sup { base, .. }
+        | MathElement::SubSup { base, .. }
+        | MathElement::Under { base, .. }
+        | MathElement::Over { base, .. }
+        | MathElement::UnderOver { base, .. } => is_large(base),
+        MathElement::Id { t, .. } => t == "lim",
+        _ => op_char(e).is_some_and(|c| LARGE.contains(&c)),
+    }
+}
+
+/// Check if an element is written as a single unit, which doesn't need
+/// parentheses around it.
+fn is_atom(e: &Element) -> bool {
+    match e.elem() {
+        MathElement::Row(elems) => match elems.as_slice() {
+            [e] => is_atom(e),
+            [first, .., last] => matches!(
+                (op_char(first), op_char(last)),
+                (Some('('), Some(')')) | (Some('['), Some(']')) | (Some('{'), Some('}'))
+            ),
+            [] => true,
+        },
+        MathElement::Frac { .. } => false,
+        MathElement::Sub { base, .. }
+        | MathElement::Sup { base, .. }
+        | MathElement::SubSup { base, .. } => is_atom(base),
+        _ => true,
+    }
+}
+
+/// Check if an element starts with an opening bracket.
+fn is_bracketed(e: &Element) -> bool {
+    let first = match e.elem() {
+        MathElement::Row(elems) => elems.first(),
+        _ => Some(e),
+    };
+    first
+        .and_then(op_char)
+        .is_some_and(|c| matches!(c, '(' | '[' | '{' | '|' | '⟨'))
+}
+
+/// Check if an element sits right next to its neighbor with no visible
+/// operator between them.
+fn is_juxtaposed(neighbor: Option<&Element>) -> bool {
+    neighbor.is_some_and(|n| op_char(n).is_none_or(|c| matches!(c, '\u{2061}'..='\u{2064}')))
+}
+
+fn is_empty(e: &Element) -> bool {
+    matches!(e.elem(), MathElement::Row(elems) if elems.is_empty())
+}
+
+struct Writer<'a> {
+    opts: &'a PlainTextOptions,
+}
+
+impl Writer<'_> {
+    /// Write an element, in the variant inherited from its parents. Inside
+    /// scripts, operators aren't spaced out.
+    fn elem(&self, e: &Element, variant: Option<Variant>, script: bool) -> String {
+        let variant = e.attributes().and_then(|a| a.variant).or(variant);
+        match e.elem() {
+            MathElement::Op(c) => self.op(*c, script),
+            MathElement::Oper(op) => self.op(op.t, script),
+            MathElement::ResolvedOper(op) => self.op(op.t, script),
+            MathElement::Id { t, .. } | MathElement::Num(t) | MathElement::Text(t) => {
+                self.token(t, variant)
+            }
+            MathElement::Err(t) | MathElement::Unknown { tag: t, .. } => {
+                let (open, close) = if self.opts.ascii {
+                    ("[[", "]]")
+                } else {
+                    ("⟦", "⟧")
+                };
+                format!("{}{}{}", open, self.token(t, None), close)
+            }
+            MathElement::Str(t) => format!("\"{}\"", self.token(t, variant)),
+            MathElement::Space(s) => match &s.width {
+                Some(Length::Em(w) | Length::Ex(w)) if *w > 0.2 => " ".into(),
+                _ => String::new(),
+            },
+            MathElement::Phantom(_) => String::new(),
+            MathElement::Padding(p) => self.row(&p.elems, variant, script),
+            MathElement::Row(elems) => match elems.as_slice() {
+                [open, table, close]
+                    if matches!(table.elem(), MathElement::Table { .. })
+                        && op_char(open).is_some()
+                        && op_char(close).is_some() =>
+                {
+                    let MathElement::Table { rows } = table.elem() else {
+                        unreachable!()
+                    };
+                    let mut out = self.op(op_char(open).unwrap(), script);
+                    out.push_str(&self.table(rows, variant));
+                    out.push_str(&self.op(op_char(close).unwrap(), script));
+                    out
+                }
+                _ => self.row(elems, variant, script),
+            },
+            MathElement::Frac {
+                line_thickness: Some(t),
+                num,
+                den,
+            } if *t == 0.0 => {
+                let sep = if self.opts.ascii { " choose " } else { "¦" };
+                let num_s = self.elem(num, variant, script);
+                let den_s = self.elem(den, variant, script);
+                format!(
+                    "{}{}{}",
+                    self.group(num, num_s),
+                    sep,
+                    self.group(den, den_s)
+                )
+            }
+            MathElement::Frac { num, den, .. } => {
+                let part = |e: &Element| {
+                    let s = self.elem(e, variant, script);
+                    if matches!(e.elem(), MathElement::Num(_)) {
+                        s
+                    } else {
+                        format!("({})", s)
+                    }
+                };
+                format!("{}/{}", part(num), part(den))
+            }
+            MathElement::Sqrt(base) => {
+                let base_s = self.elem(base, variant, script);
+                if self.opts.ascii {
+                    format!("sqrt({})", base_s)
+                } else {
+                    format!("√{}", self.group(base, base_s))
+                }
+            }
+            MathElement::Root { base, index } => {
+                let base_s = self.elem(base, variant, script);
+                let index_s = self.elem(index, variant, true);
+                let sign = match index_s.as_str() {
+                    _ if self.opts.ascii => None,
+                    "3" => Some("∛".to_string()),
+                    "4" => Some("∜".to_string()),
+                    s if self.opts.script_chars => map(s, SUPERSCRIPTS).map(|s| format!("{}√", s)),
+                    _ => None,
+                };
+                match sign {
+                    Some(sign) => format!("{}{}", sign, self.group(base, base_s)),
+                    None => format!("root({}, {})", index_s, base_s),
+                }
+            }
+            MathElement::Sup { base, sup } => self.scripted(base, None, Some(sup), variant),
+            MathElement::Sub { base, sub } => self.scripted(base, Some(sub), None, variant),
+            MathElement::SubSup { base, sub, sup } => {
+                self.scripted(base, Some(sub), Some(sup), variant)
+            }
+            MathElement::Over {
+                base, over, accent, ..
+            } => match self.accent(base, over, *accent, true, variant) {
+                Some(s) => s,
+                None => self.scripted(base, None, Some(over), variant),
+            },
+            MathElement::Under {
+                base,
+                under,
+                accent_under,
+            } => match self.accent(base, under, *accent_under, false, variant) {
+                Some(s) => s,
+                None => self.scripted(base, Some(under), None, variant),
+            },
+            MathElement::UnderOver {
+                base, under, over, ..
+            } => self.scripted(base, Some(under), Some(over), variant),
+            MathElement::MultiScript { base, post, pre } => {
+                let mut out = String::new();
+                for p in pre {
+                    out.push_str(&self.scripts(&p.sub, &p.sup, variant));
+                }
+                let base_s = self.elem(base, variant, script);
+                out.push_str(&self.group(base, base_s));
+                for p in post {
+                    out.push_str(&self.scripts(&p.sub, &p.sup, variant));
+                }
+                out
+            }
+            MathElement::Table { rows } => format!("[{}]", self.table(rows, variant)),
+        }
+    }
+
+    fn row(&self, elems: &[Element], variant: Option<Variant>, script: bool) -> String {
+        let mut out = String::new();
+        for (i, e) in elems.iter().enumerate() {
+            let prev = i.checked_sub(1).map(|p| &elems[p]);
+            let next = elems.get(i + 1);
+            if op_char(e) == Some('\u{2061}') {
+                // Keep a function's name apart from an argument without
+                // brackets, so `sin x` doesn't become `sinx`.
+                if next.is_some_and(|n| !is_bracketed(n)) {
+                    out.push(' ');
+                }
+                continue;
+            }
+            let s = self.elem(e, variant, script);
+            // `1/2x` would read as a fraction of a product.
+            if matches!(e.elem(), MathElement::Frac { .. })
+                && (is_juxtaposed(prev) || is_juxtaposed(next))
+            {
+                out.push_str(&format!("({})", s));
+            } else {
+                out.push_str(&s);
+            }
+            // Keep limits apart from the operand they apply to.
+            if is_large(e) && i + 1 < elems.len() && !matches!(e.elem(), MathElement::Op(_)) {
+                out.push(' ');
+            }
+        }
+        out
+    }
+
+    fn table(&self, rows: &[TableRow], variant: Option<Variant>) -> String {
+        let rows: Vec<String> = rows
+            .iter()
+            .map(|r| {
+                let cells: Vec<String> = r
+                    .cells
+                    .iter()
+                    .map(|c| self.row(&c.elems, variant, false))
+                    .collect();
+                cells.join(", ")
+            })
+            .collect();
+        rows.join("; ")
+    }
+
+    /// Wrap written-out text in parentheses unless the element is an atom.
+    fn group(&self, e: &Element, s: String) -> String {
+        if is_atom(e) {
+            s
+        } else {
+            format!("({})", s)
+        }
+    }
+
+    fn op(&self, c: char, script: bool) -> String {
+        let t = match c {
+            '\u{2061}' | '\u{2062}' | '\u{2064}' => return String::new(),
+            '\u{2063}' | ',' => return ", ".into(),
+            ';' => return "; ".into(),
+            c => self.token(&c.to_string(), None),
+        };
+        let spaced = SPACED.contains(&c) || t.starts_with(|c: char| c.is_ascii_alphabetic());
+        if spaced && !script {
+            format!(" {} ", t)
+        } else {
+            t
+        }
+    }
+
+    fn token(&self, t: &str, variant: Option<Variant>) -> String {
+        if self.opts.ascii {
+            let mut out = String::new();
+            for c in t.chars() {
+                match ASCII.iter().find(|(a, _)| *a == c) {
+                    Some((_, s)) => out.push_str(s),
+                    None if c.is_ascii() => out.push(c),
+                    None => out.push('?'),
+                }
+            }
+            return out;
+        }
+        match variant {
+            Some(v) if self.opts.styled_letters => {
+                t.chars().map(|c| styled(c, v).unwrap_or(c)).collect()
+            }
+            _ => t.into(),
+        }
+    }
+
+    fn scripted(
+        &self,
+        base: &Element,
+        sub: Option<&Element>,
+        sup: Option<&Element>,
+        variant: Option<Variant>,
+    ) -> String {
+        let base_s = self.elem(base, variant, false);
+        let mut out = self.group(base, base_s);
+        if let Some(sub) = sub.filter(|e| !is_empty(e)) {
+            out.push_str(&self.script(sub, SUBSCRIPTS, '_', variant));
+        }
+        if let Some(sup) = sup.filter(|e| !is_empty(e)) {
+            out.push_str(&self.script(sup, SUPERSCRIPTS, '^', variant));
+        }
+        out
+    }
+
+    /// Write a subscript and superscript pair, skipping empty ones.
+    fn scripts(&self, sub: &Element, sup: &Element, variant: Option<Variant>) -> String {
+        let mut out = String::new();
+        if !is_empty(sub) {
+            out.push_str(&self.script(sub, SUBSCRIPTS, '_', variant));
+        }
+        if !is_empty(sup) {
+            out.push_str(&self.script(sup, SUPERSCRIPTS, '^', variant));
+        }
+        out
+    }
+
+    fn script(
+        &self,
+        e: &Element,
+        chars: &[(char, char)],
+        mark: char,
+        variant: Option<Variant>,
+    ) -> String {
+        let s = self.elem(e, variant, true);
+        // Primes are already raised.
+        if mark == '^' && !s.is_empty() && s.chars().all(|c| matches!(c, '′' | '″' | '‴')) {
+            return s;
+        }
+        if self.opts.script_chars && !self.opts.ascii {
+            if let Some(mapped) = map(&s, chars) {
+                return mapped;
+            }
+        }
+        if s.chars().count() == 1 {
+            format!("{}{}", mark, s)
+        } else {
+            format!("{}({})", mark, s)
+        }
+    }
+
+    /// Write an accented element with a combining mark, or spelled out as a
+    /// function.
+    fn accent(
+        &self,
+        base: &Element,
+        accent: &Element,
+        is_accent: bool,
+        over: bool,
+        variant: Option<Variant>,
+    ) -> Option<String> {
+        let c = op_char(accent)?;
+        let (mark, name) = if over {
+            let (_, mark, name) = ACCENTS.iter().find(|(a, ..)| *a == c)?;
+            (*mark, *name)
+        } else if matches!(c, '_' | '¯' | '‾' | '\u{332}') {
+            ('\u{332}', "underline")
+        } else {
+            return None;
+        };
+        if !is_accent && !matches!(mark, '\u{305}' | '\u{332}' | '\u{20D7}') {
+            return None;
+        }
+        let s = self.elem(base, variant, false);
+        let single = s.chars().count() == 1;
+        if self.opts.combining_accents && !self.opts.ascii {
+            // Lines run along every character, but other marks only fit
+            // over one.
+            if matches!(mark, '\u{305}' | '\u{332}') {
+                return Some(s.chars().flat_map(|c| [c, mark]).collect());
+            }
+            if single {
+                return Some(format!("{}{}", s, mark));
+            }
+        }
+        Some(format!("{}({})", name, s))
+    }
+}
+
+/// Map every character of a string, or give up if any character has no
+/// mapping.
+fn map(s: &str, chars: &[(char, char)]) -> Option<String> {
+    if s.is_empty() {
+        return None;
+    }
+    s.chars()
+        .map(|c| chars.iter().find(|(a, _)| *a == c).map(|(_, m)| *m))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scripts() {
+        let e = Element::row([
+            Element::sup(Element::id("x"), Element::num("2")),
+            Element::op('+'),
+            Element::sub(Element::id("a"), Element::id("i")),
+        ]);
+        assert_eq!(e.to_plain_text(), "x²+aᵢ");
+        let e = Element::sub_sup(
+            Element::id("x"),
+            Element::id("q"),
+            Element::row([Element::id("n"), Element::op('+'), Element::num("1")]),
+        );
+        assert_eq!(e.to_plain_text(), "x_qⁿ⁺¹");
+        let e = Element::sup(
+            Element::row([Element::id("a"), Element::op('+'), Element::id("b")]),
+            Element::id("q"),
+        );
+        assert_eq!(e.to_plain_text(), "(a+b)^q");
+        let e = Element::sup(Element::id("f"), Element::op('′'));
+        assert_eq!(e.to_plain_text(), "f′");
+    }
+
+    #[test]
+    fn fractions_and_radicals() {
+        let e = Element::frac(Element::id("a"), Element::id("b"));
+        assert_eq!(e.to_plain_text(), "(a)/(b)");
+        let e = Element::frac(Element::num("1"), Element::num("2"));
+        assert_eq!(e.to_plain_text(), "1/2");
+        let half = || Element::frac(Element::num("1"), Element::num("2"));
+        let e = Element::row([half(), Element::id("x")]);
+        assert_eq!(e.to_plain_text(), "(1/2)x");
+        let e = Element::row([half(), Element::op('\u{2062}'), Element::id("x")]);
+        assert_eq!(e.to_plain_text(), "(1/2)x");
+        let e = Element::row([half(), Element::op('+'), Element::id("x")]);
+        assert_eq!(e.to_plain_text(), "1/2+x");
+        let e = Element::frac_thickness(
+            Element::row([Element::id("n"), Element::op('+'), Element::num("1")]),
+            Element::id("k"),
+            0.0,
+        );
+        assert_eq!(e.to_plain_text(), "(n+1)¦k");
+        let e = Element::row([e, Element::id("x")]);
+        assert_eq!(e.to_plain_text(), "((n+1)¦k)x");
+        let e = Element::sqrt(Element::row([
+            Element::id("x"),
+            Element::op('+'),
+            Element::num("1"),
+        ]));
+        assert_eq!(e.to_plain_text(), "√(x+1)");
+        assert_eq!(Element::sqrt(Element::id("x")).to_plain_text(), "√x");
+        let e = Element::root(Element::id("x"), Element::num("3"));
+        assert_eq!(e.to_plain_text(), "∛x");
+        let e = Element::root(Element::id("x"), Element::id("n"));
+        assert_eq!(e.to_plain_text(), "ⁿ√x");
+    }
+
+    #[test]
+    fn function_application() {
+        let e = Element::row([
+            Element::id("sin"),
+            Element::op('\u{2061}'),
+            Element::id("x"),
+        ]);
+        assert_eq!(e.to_plain_text(), "sin x");
+        let e = Element::row([
+            Element::id("f"),
+            Element::op('\u{2061}'),
+            Element::row([Element::op('('), Element::id("x"), Element::op(')')]),
+        ]);
+        assert_eq!(e.to_plain_text(), "f(x)");
+    }
+
+    #[test]
+    fn variants() {
+        let e = Element::row([
+            Element::id("x").variant(Variant::Bold),
+            Element::op('∈'),
+            Element::sup(
+                Element::id("R").variant(Variant::DoubleStruck),
+                Element::id("n"),
+            ),
+        ]);
+        assert_eq!(e.to_plain_text(), "𝐱 ∈ ℝⁿ");
+        let e = Element::row([Element::id("F"), Element::id("α")]).variant(Variant::BoldItalic);
+        assert_eq!(e.to_plain_text(), "𝑭𝜶");
+        let e = Element::num("12").variant(Variant::Monospace);
+        assert_eq!(e.to_plain_text(), "𝟷𝟸");
+    }
+
+    #[test]
+    fn limits_and_accents() {
+        let e = Element::row([
+            Element::under_over(
+                Element::op('∑'),
+                Element::row([Element::id("i"), Element::op('='), Element::num("1")]),
+                Element::id("n"),
+            ),
+            Element::sup(Element::id("i"), Element::num("2")),
+        ]);
+        assert_eq!(e.to_plain_text(), "∑ᵢ₌₁ⁿ i²");
+        let e = Element::row([
+            Element::under_over(Element::op('∫'), Element::row([]), Element::row([])),
+            Element::id("f"),
+        ]);
+        assert_eq!(e.to_plain_text(), "∫ f");
+        let e = Element::row([
+            Element::over_accent(Element::id("x"), Element::op('^')),
+            Element::op('+'),
+            Element::over(
+                Element::row([Element::id("a"), Element::id("b")]),
+                Element::op('‾'),
+            ),
+        ]);
+        assert_eq!(e.to_plain_text(), "x\u{302}+a\u{305}b\u{305}");
+    }
+
+    #[test]
+    fn tables() {
+        let e = Element::row([
+            Element::op('('),
+            Element::matrix([
+                [Element::num("1"), Element::num("0")],
+                [Element::num("0"), Element::num("1")],
+            ]),
+            Element::op(')'),
+        ]);
+        assert_eq!(e.to_plain_text(), "(1, 0; 0, 1)");
+        let e = Element::matrix([[Element::id("a")], [Element::id("b")]]);
+        assert_eq!(e.to_plain_text(), "[a; b]");
+    }
+
+    #[test]
+    fn errors() {
+        let e = Element::row([
+            Element::id("x"),
+            Element::op('='),
+            Element::new(MathElement::Unknown {
+                tag: "Glow".into(),
+                value: None,
+            })
+            .variant(Variant::Bold),
+        ]);
+        assert_eq!(e.to_plain_text(), "x = ⟦Glow⟧");
+        assert_eq!(
+            Element::err("bad").to_plain_text_with(&PlainTextOptions::ascii()),
+            "[[bad]]"
+        );
+    }
+
+    #[test]
+    fn ascii() {
+        let opts = PlainTextOptions::ascii();
+        let e = Element::row([
+            Element::sup(Element::id("α"), Element::num("2")),
+            Element::op('≤'),
+            Element::sqrt(Element::frac(Element::id("π"), Element::num("2"))),
+            Element::op('−'),
+            Element::sub(
+                Element::id("x").variant(Variant::Bold),
+                Element::row([Element::id("i"), Element::id("j")]),
+            ),
+        ]);
+        assert_eq!(
+            e.to_plain_text_with(&opts),
+            "alpha^2 <= sqrt((pi)/2)-x_(ij)"
+        );
+        let e = Element::over_accent(Element::id("v"), Element::op('→'));
+        assert_eq!(e.to_plain_text_with(&opts), "vec(v)");
+    }
+}
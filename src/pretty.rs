@@ -0,0 +1,1025 @@
+//! Two-dimensional text rendering, for terminals and logs.
+//!
+//! Elements are laid out as blocks of characters with a baseline, in the style
+//! of SymPy's pretty printer: fractions are stacked over a rule, radicals get
+//! a `√` and an overbar, scripts are raised and lowered, tables become aligned
+//! grids, and brackets around anything taller than a line are stretched to
+//! fit. Long rows are broken at their top-level operators to fit the
+//! configured width.
+
+use crate::math::*;
+use crate::operator::row_forms;
+use crate::tree::op_char;
+
+/// Options for pretty-printing.
+#[derive(Clone, Debug)]
+pub struct PrettyOptions {
+    /// Width to fit the output into. Long rows are broken before top-level
+    /// operators, like `+` and `=`, when possible. If not set, rows are never
+    /// broken.
+    pub width: Option<usize>,
+    /// Whether to only use ASCII characters. Greek letters are spelled out,
+    /// and operators and drawing characters are replaced with their closest
+    /// ASCII equivalents.
+    pub ascii: bool,
+}
+
+impl Default for PrettyOptions {
+    fn default() -> Self {
+        Self {
+            width: Some(80),
+            ascii: false,
+        }
+    }
+}
+
+impl PrettyOptions {
+    /// Options for ASCII-only output.
+    pub fn ascii() -> Self {
+        Self {
+            ascii: true,
+            ..Self::default()
+        }
+    }
+}
+
+impl Element {
+    /// Render the element as multi-line Unicode text, using the default
+    /// [`PrettyOptions`].
+    pub fn to_pretty(&self) -> String {
+        self.to_pretty_with(&PrettyOptions::default())
+    }
+
+    /// Render the element as multi-line text.
+    pub fn to_pretty_with(&self, opts: &PrettyOptions) -> String {
+        let l = Layout { opts };
+        let block = match (self.elem(), opts.width) {
+            (MathElement::Row(elems), Some(width)) => l.wrapped(elems, width),
+            _ => l.elem(self),
+        };
+        block.render()
+    }
+}
+
+/// ASCII spellings of characters.
+pub(crate) const ASCII: &[(char, &str)] = &[
+    ('α', "alpha"),
+    ('β', "beta"),
+    ('γ', "gamma"),
+    ('δ', "delta"),
+    ('ϵ', "epsilon"),
+    ('ε', "epsilon"),
+    ('ζ', "zeta"),
+    ('η', "eta"),
+    ('θ', "theta"),
+    ('ϑ', "theta"),
+    ('ι', "iota"),
+    ('κ', "kappa"),
+    ('λ', "lambda"),
+    ('μ', "mu"),
+    ('ν', "nu"),
+    ('ξ', "xi"),
+    ('ο', "o"),
+    ('π', "pi"),
+    ('ϖ', "pi"),
+    ('ρ', "rho"),
+    ('ϱ', "rho"),
+    ('σ', "sigma"),
+    ('ς', "sigma"),
+    ('τ', "tau"),
+    ('υ', "upsilon"),
+    ('ϕ', "phi"),
+    ('φ', "phi"),
+    ('χ', "chi"),
+    ('ψ', "psi"),
+    ('ω', "omega"),
+    ('Γ', "Gamma"),
+    ('Δ', "Delta"),
+    ('Θ', "Theta"),
+    ('Λ', "Lambda"),
+    ('Ξ', "Xi"),
+    ('Π', "Pi"),
+    ('Σ', "Sigma"),
+    ('Υ', "Upsilon"),
+    ('Φ', "Phi"),
+    ('Ψ', "Psi"),
+    ('Ω', "Omega"),
+    ('−', "-"),
+    ('×', "*"),
+    ('⋅', "*"),
+    ('·', "*"),
+    ('∗', "*"),
+    ('÷', "/"),
+    ('±', "+-"),
+    ('∓', "-+"),
+    ('≤', "<="),
+    ('≥', ">="),
+    ('≠', "!="),
+    ('≈', "~="),
+    ('≅', "~="),
+    ('∼', "~"),
+    ('≡', "=="),
+    ('≔', ":="),
+    ('∝', "propto"),
+    ('→', "->"),
+    ('←', "<-"),
+    ('↔', "<->"),
+    ('⇒', "=>"),
+    ('⇐', "<="),
+    ('⇔', "<=>"),
+    ('↦', "|->"),
+    ('⧴', ":>"),
+    ('∈', "in"),
+    ('∉', "not in"),
+    ('∋', "ni"),
+    ('⊂', "subset"),
+    ('⊆', "subseteq"),
+    ('⊃', "supset"),
+    ('⊇', "supseteq"),
+    ('∪', "U"),
+    ('⋃', "U"),
+    ('∩', "n"),
+    ('⋂', "n"),
+    ('∖', "\\"),
+    ('∧', "&"),
+    ('∨', "|"),
+    ('¬', "~"),
+    ('∀', "forall "),
+    ('∃', "exists "),
+    ('∅', "{}"),
+    ('∞', "oo"),
+    ('∂', "d"),
+    ('∇', "nabla"),
+    ('′', "'"),
+    ('″', "''"),
+    ('‴', "'''"),
+    ('°', "deg"),
+    ('…', "..."),
+    ('⋯', "..."),
+    ('⋮', ":"),
+    ('⋱', "\\"),
+    ('ⅆ', "d"),
+    ('ⅈ', "i"),
+    ('ⅇ', "e"),
+    ('ℏ', "hbar"),
+    ('ℵ', "aleph"),
+    ('ℂ', "C"),
+    ('ℕ', "N"),
+    ('ℚ', "Q"),
+    ('ℝ', "R"),
+    ('ℤ', "Z"),
+    ('⟨', "<"),
+    ('⟩', ">"),
+    ('⟦', "[["),
+    ('⟧', "]]"),
+    ('⌊', "|_"),
+    ('⌋', "_|"),
+    ('⌈', "|"),
+    ('⌉', "|"),
+    ('‖', "||"),
+    ('∣', "|"),
+    ('∘', "o"),
+    ('⊗', "(x)"),
+    ('⊕', "(+)"),
+    ('√', "sqrt"),
+    ('∑', "sum"),
+    ('∏', "prod"),
+    ('∫', "int"),
+    ('∮', "oint"),
+    ('˙', "."),
+    ('¨', "\""),
+    ('¯', "_"),
+    ('‾', "_"),
+    ('⏞', "^"),
+    ('⏟', "v"),
+];
+
+/// Operators that aren't spaced out, even between operands.
+const TIGHT: &[char] = &['/', '⋅', '.', '^', '_', '\u{2061}', '\u{2062}', '\u{2064}'];
+
+/// Operators written after their operand.
+const POSTFIX: &[char] = &['!', '′', '″', '‴', '\'', '%', '°'];
+
+/// Opening and closing brackets, which are stretched to fit their contents.
+const FENCES: &[(char, char)] = &[
+    ('(', ')'),
+    ('[', ']'),
+    ('{', '}'),
+    ('⟨', '⟩'),
+    ('⌊', '⌋'),
+    ('⌈', '⌉'),
+    ('⟦', '⟧'),
+    ('|', '|'),
+    ('‖', '‖'),
+];
+
+fn is_open(c: char) -> bool {
+    FENCES.iter().any(|(o, _)| *o == c)
+}
+
+/// Check if an element starts with an opening bracket.
+fn starts_open(e: &Element) -> bool {
+    let first = match e.elem() {
+        MathElement::Row(elems) => elems.first(),
+        _ => Some(e),
+    };
+    first.and_then(op_char).is_some_and(is_open)
+}
+
+fn is_close(c: char) -> bool {
+    FENCES.iter().any(|(_, cl)| *cl == c)
+}
+
+fn is_big(c: char) -> bool {
+    matches!(c, '∑' | '∏' | '∫' | '∮')
+}
+
+/// Check if an element is a big operator, possibly with limits.
+fn big_op(e: &Element) -> bool {
+    match e.elem() {
+        MathElement::Sub { base, .. }
+        | MathElement::Sup { base, .. }
+        | MathElement::SubSup { base, .. }
+        | MathElement::Under { base, .. }
+        | MathElement::Over { base, .. }
+        | MathElement::UnderOver { base, .. } => big_op(base),
+        _ => op_char(e).is_some_and(is_big),
+    }
+}
+
+fn is_empty(e: &Element) -> bool {
+    matches!(e.elem(), MathElement::Row(elems) if elems.is_empty())
+}
+
+fn em(l: &Length) -> usize {
+    let em = match l {
+        Length::Em(v) => *v,
+        Length::Ex(v) => *v / 2.0,
+    };
+    em.round().max(0.0) as usize
+}
+
+/// A rectangle of characters, with the row that lines up with the text around
+/// it.
+#[derive(Clone, Debug)]
+struct Block {
+    lines: Vec<Vec<char>>,
+    baseline: usize,
+}
+
+enum Align {
+    Left,
+    Center,
+}
+
+impl Block {
+    fn new(mut lines: Vec<Vec<char>>, baseline: usize) -> Self {
+        let width = lines.iter().map(Vec::len).max().unwrap_or(0);
+        for l in lines.iter_mut() {
+            l.resize(width, ' ');
+        }
+        Self { lines, baseline }
+    }
+
+    fn text(s: &str) -> Self {
+        Self::new(vec![s.chars().collect()], 0)
+    }
+
+    fn blank(width: usize, height: usize, baseline: usize) -> Self {
+        Self::new(vec![vec![' '; width]; height.max(1)], baseline)
+    }
+
+    fn width(&self) -> usize {
+        self.lines.first().map_or(0, Vec::len)
+    }
+
+    fn height(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Rows below the baseline.
+    fn depth(&self) -> usize {
+        self.height() - self.baseline - 1
+    }
+
+    /// Place blocks side by side, lining up their baselines.
+    fn hcat(blocks: impl IntoIterator<Item = Block>) -> Self {
+        let blocks: Vec<Block> = blocks.into_iter().collect();
+        let above = blocks.iter().map(|b| b.baseline).max().unwrap_or(0);
+        let below = blocks.iter().map(Block::depth).max().unwrap_or(0);
+        let mut lines = vec![Vec::new(); above + below + 1];
+        for b in blocks {
+            let top = above - b.baseline;
+            for (r, line) in lines.iter_mut().enumerate() {
+                match r.checked_sub(top).and_then(|r| b.lines.get(r)) {
+                    Some(l) => line.extend(l),
+                    None => line.resize(line.len() + b.width(), ' '),
+                }
+            }
+        }
+        Self::new(lines, above)
+    }
+
+    /// Stack blocks, taking the baseline from the block at `base`.
+    fn vstack(parts: Vec<Block>, base: usize, align: Align) -> Self {
+        let width = parts.iter().map(Block::width).max().unwrap_or(0);
+        let baseline =
+            parts[..base].iter().map(Block::height).sum::<usize>() + parts[base].baseline;
+        let mut lines = Vec::new();
+        for p in parts {
+            let pad = match align {
+                Align::Left => 0,
+                Align::Center => (width - p.width()) / 2,
+            };
+            for l in p.lines {
+                let mut line = vec![' '; pad];
+                line.extend(l);
+                lines.push(line);
+            }
+        }
+        Self::new(lines, baseline)
+    }
+
+    fn render(&self) -> String {
+        let lines: Vec<String> = self
+            .lines
+            .iter()
+            .map(|l| l.iter().collect::<String>().trim_end().to_string())
+            .collect();
+        lines.join("\n")
+    }
+}
+
+struct Layout<'a> {
+    opts: &'a PrettyOptions,
+}
+
+impl Layout<'_> {
+    /// Lay out a line of text, spelling it out in ASCII if needed.
+    fn text(&self, s: &str) -> Block {
+        Block::text(&self.str(s))
+    }
+
+    /// Draw a box around an error message.
+    fn error(&self, s: &str) -> Block {
+        let s: Vec<char> = self.str(s).chars().collect();
+        let (h, v) = (self.pick('─', '-'), self.pick('│', '|'));
+        let corner = |unicode| self.pick(unicode, '+');
+        let rule = |l, r| {
+            let mut line = vec![corner(l)];
+            line.extend(std::iter::repeat_n(h, s.len()));
+            line.push(corner(r));
+            line
+        };
+        let mut mid = vec![v];
+        mid.extend(&s);
+        mid.push(v);
+        Block::new(vec![rule('┌', '┐'), mid, rule('└', '┘')], 1)
+    }
+
+    fn str(&self, s: &str) -> String {
+        if !self.opts.ascii {
+            return s.into();
+        }
+        let mut out = String::new();
+        for c in s.chars() {
+            match ASCII.iter().find(|(a, _)| *a == c) {
+                Some((_, t)) => out.push_str(t),
+                None if c.is_ascii() => out.push(c),
+                None => out.push('?'),
+            }
+        }
+        out
+    }
+
+    /// Pick between the Unicode and ASCII forms of a drawing character.
+    fn pick(&self, unicode: char, ascii: char) -> char {
+        if self.opts.ascii {
+            ascii
+        } else {
+            unicode
+        }
+    }
+
+    fn elem(&self, e: &Element) -> Block {
+        match e.elem() {
+            MathElement::Op(c) => self.op(*c),
+            MathElement::Oper(op) => self.op(op.t),
+            MathElement::ResolvedOper(op) => self.op(op.t),
+            MathElement::Text(t) | MathElement::Num(t) => self.text(t),
+            MathElement::Err(t) | MathElement::Unknown { tag: t, .. } => self.error(t),
+            MathElement::Id { t, .. } => self.text(t),
+            MathElement::Str(t) => self.text(&format!("\"{}\"", t)),
+            MathElement::Space(s) => Block::blank(s.width.as_ref().map_or(0, em), 1, 0),
+            MathElement::Phantom(elems) => {
+                let b = self.row(elems);
+                Block::blank(b.width(), b.height(), b.baseline)
+            }
+            MathElement::Row(elems) => self.row(elems),
+            MathElement::Padding(p) => {
+                let lspace = p.lspace.as_ref().map_or(0, em);
+                let b = self.row(&p.elems);
+                let width = p.width.as_ref().map_or(0, em).max(lspace + b.width());
+                let right = width - lspace - b.width();
+                Block::hcat([Block::blank(lspace, 1, 0), b, Block::blank(right, 1, 0)])
+            }
+            MathElement::Frac {
+                line_thickness,
+                num,
+                den,
+            } => {
+                let (num, den) = (self.elem(num), self.elem(den));
+                let width = num.width().max(den.width());
+                let rule = if *line_thickness == Some(0.0) {
+                    Block::blank(width, 1, 0)
+                } else {
+                    Block::new(vec![vec![self.pick('─', '-'); width]], 0)
+                };
+                Block::vstack(vec![num, rule, den], 1, Align::Center)
+            }
+            MathElement::Sqrt(base) => self.sqrt(self.elem(base)),
+            MathElement::Root { base, index } => {
+                let root = self.sqrt(self.elem(base));
+                let index = self.elem(index);
+                // Sit the index just above the radical sign.
+                let mut col = Block::vstack(
+                    vec![index.clone(), Block::blank(index.width(), 1, 0)],
+                    0,
+                    Align::Left,
+                );
+                if col.height() < root.height() {
+                    let pad = Block::blank(col.width(), root.height() - col.height(), 0);
+                    col = Block::vstack(vec![pad, col], 0, Align::Left);
+                }
+                col.baseline = col.height() - root.height() + root.baseline;
+                Block::hcat([col, root])
+            }
+            MathElement::Sup { base, sup } => self.scripts(base, None, Some(sup)),
+            MathElement::Sub { base, sub } => self.scripts(base, Some(sub), None),
+            MathElement::SubSup { base, sub, sup } => self.scripts(base, Some(sub), Some(sup)),
+            MathElement::Over { base, over, accent } => {
+                let base = self.elem(base);
+                let over = self.over(over, *accent, base.width(), true);
+                Block::vstack(vec![over, base], 1, Align::Center)
+            }
+            MathElement::Under {
+                base,
+                under,
+                accent_under,
+            } => {
+                let base = self.elem(base);
+                let under = self.over(under, *accent_under, base.width(), false);
+                Block::vstack(vec![base, under], 0, Align::Center)
+            }
+            MathElement::UnderOver {
+                base,
+                under,
+                over,
+                accent,
+                accent_under,
+            } => {
+                let base = self.elem(base);
+                let over = self.over(over, *accent, base.width(), true);
+                let under = self.over(under, *accent_under, base.width(), false);
+                Block::vstack(vec![over, base, under], 1, Align::Center)
+            }
+            MathElement::MultiScript { base, post, pre } => {
+                let base = self.elem(base);
+                let column = |p: &Pair| {
+                    let sub = (!is_empty(&p.sub)).then(|| self.elem(&p.sub));
+                    let sup = (!is_empty(&p.sup)).then(|| self.elem(&p.sup));
+                    script_column(&base, sub, sup)
+                };
+                let mut blocks: Vec<Block> = pre.iter().map(column).collect();
+                let post: Vec<Block> = post.iter().map(column).collect();
+                blocks.push(base);
+                blocks.extend(post);
+                Block::hcat(blocks)
+            }
+            MathElement::Table { rows } => self.table(rows),
+        }
+    }
+
+    fn op(&self, c: char) -> Block {
+        match c {
+            '\u{2061}' => Block::text(&self.pick('\u{2009}', ' ').to_string()),
+            '\u{2064}' => Block::text(""),
+            '\u{2062}' => self.text("⋅"),
+            '\u{2063}' => self.text(","),
+            c if is_big(c) => self.big(c),
+            c => self.text(&c.to_string()),
+        }
+    }
+
+    /// Draw a big operator over several lines.
+    fn big(&self, c: char) -> Block {
+        let (lines, baseline): (&[&str], usize) = match (c, self.opts.ascii) {
+            ('∑', false) => (&["___", "╲", "╱", "‾‾‾"], 2),
+            ('∑', true) => (&["___", "\\", "/", "---"], 2),
+            ('∏', false) => (&["┬──┬", "│  │", "│  │"], 2),
+            ('∏', true) => (&["____", "|  |", "|  |"], 2),
+            ('∮', false) => (&["⌠", "∮", "⌡"], 1),
+            (_, false) => (&["⌠", "⎮", "⌡"], 1),
+            (_, true) => (&[" /", " |", "/"], 1),
+        };
+        Block::new(
+            lines.iter().map(|l| l.chars().collect()).collect(),
+            baseline,
+        )
+    }
+
+    fn sqrt(&self, body: Block) -> Block {
+        let (upper, last) = if self.opts.ascii {
+            (" |", "\\/")
+        } else {
+            ("│", "√")
+        };
+        let indent = last.chars().count();
+        let mut lines = vec![vec![' '; indent]];
+        lines[0].resize(indent + body.width(), '_');
+        let height = body.height();
+        for (r, l) in body.lines.into_iter().enumerate() {
+            let mut line: Vec<char> = if r + 1 == height { last } else { upper }.chars().collect();
+            line.extend(l);
+            lines.push(line);
+        }
+        Block::new(lines, body.baseline + 1)
+    }
+
+    fn scripts(&self, base: &Element, sub: Option<&Element>, sup: Option<&Element>) -> Block {
+        let base = self.elem(base);
+        let col = script_column(&base, sub.map(|e| self.elem(e)), sup.map(|e| self.elem(e)));
+        Block::hcat([base, col])
+    }
+
+    /// Lay out an overscript or underscript, drawing accents across the
+    /// width of the base.
+    fn over(&self, e: &Element, accent: bool, width: usize, over: bool) -> Block {
+        let line = |s: String| Block::text(&s);
+        let c = match op_char(e) {
+            Some(c) if accent || matches!(c, '⏞' | '⏟' | '‾' | '¯' | '→' | '←') => c,
+            _ => return self.elem(e),
+        };
+        let width = width.max(1);
+        let repeat = |c: char, n: usize| std::iter::repeat_n(c, n).collect::<String>();
+        match c {
+            '¯' | '‾' | '_' if over => line(repeat('_', width)),
+            '¯' | '‾' | '_' => line(repeat(self.pick('‾', '-'), width)),
+            '→' => {
+                let mut s = repeat(self.pick('─', '-'), width.saturating_sub(1));
+                s.push(self.pick('→', '>'));
+                line(s)
+            }
+            '←' => {
+                let mut s = self.pick('←', '<').to_string();
+                s.push_str(&repeat(self.pick('─', '-'), width.saturating_sub(1)));
+                line(s)
+            }
+            '⏞' | '⏟' if width >= 3 => {
+                let (l, mid, r) = match (c, self.opts.ascii) {
+                    ('⏞', false) => ('╭', '┴', '╮'),
+                    ('⏟', false) => ('╰', '┬', '╯'),
+                    ('⏞', true) => ('/', '^', '\\'),
+                    (_, true) => ('\\', 'v', '/'),
+                    _ => unreachable!(),
+                };
+                let fill = self.pick('─', '-');
+                let half = (width - 3) / 2;
+                let mut s = l.to_string();
+                s.push_str(&repeat(fill, half));
+                s.push(mid);
+                s.push_str(&repeat(fill, width - 3 - half));
+                s.push(r);
+                line(s)
+            }
+            c => self.text(&c.to_string()),
+        }
+    }
+
+    fn row(&self, elems: &[Element]) -> Block {
+        Block::hcat(self.row_blocks(elems))
+    }
+
+    /// Lay out the elements of a row, one block per element, with spacing
+    /// around operators and brackets stretched to fit their contents.
+    fn row_blocks(&self, elems: &[Element]) -> Vec<Block> {
+        let mut blocks = Vec::with_capacity(elems.len());
+        let mut open: Vec<(usize, char)> = Vec::new();
+        let mut pairs = Vec::new();
+        let forms = row_forms(elems);
+        let mut spaced = false;
+        for (i, e) in elems.iter().enumerate() {
+            let mut b = self.elem(e);
+            if big_op(e) && i + 1 < elems.len() {
+                b = Block::hcat([b, Block::blank(1, 1, 0)]);
+            }
+            let Some(c) = op_char(e) else {
+                blocks.push(b);
+                spaced = false;
+                continue;
+            };
+            // Brackets already set a function's argument apart.
+            if c == '\u{2061}' && elems.get(i + 1).is_some_and(starts_open) {
+                b = Block::text("");
+            }
+            // Operators carrying a form keep it. Otherwise an operator is
+            // also treated as prefix right after another operator, since
+            // parsed rows rarely group unary minus with its operand.
+            let prev = i.checked_sub(1).map(|j| &elems[j]);
+            let explicit = matches!(
+                e.elem(),
+                MathElement::Oper(Operator { form: Some(_), .. }) | MathElement::ResolvedOper(_)
+            );
+            let after_op = match (forms[i], prev.map(op_char)) {
+                (Some(OpForm::Prefix), _) => true,
+                (Some(OpForm::Postfix), _) if explicit => true,
+                _ if explicit => false,
+                (_, Some(Some(p))) => !is_close(p) && !POSTFIX.contains(&p),
+                (_, Some(None)) => false,
+                (_, None) => true,
+            };
+            // Avoid doubling the space between two spaced operators.
+            let spaced_before = std::mem::take(&mut spaced);
+            let fence = is_open(c) || is_close(c);
+            if fence {
+                // Bars close the nearest open bar, and open one otherwise.
+                let closes = match open.last() {
+                    Some((_, o)) => FENCES.iter().any(|(fo, fc)| fo == o && *fc == c),
+                    None => false,
+                };
+                if closes {
+                    let (o, _) = open.pop().unwrap();
+                    pairs.push((o, i));
+                } else if is_open(c) {
+                    open.push((i, c));
+                }
+            } else if matches!(c, ',' | ';' | '\u{2063}') {
+                b = Block::hcat([b, Block::blank(1, 1, 0)]);
+                spaced = true;
+            } else if !(after_op || is_big(c) || TIGHT.contains(&c) || POSTFIX.contains(&c)) {
+                let space = |n| Block::blank(n, 1, 0);
+                b = Block::hcat([space(usize::from(!spaced_before)), b, space(1)]);
+                spaced = true;
+            }
+            blocks.push(b);
+        }
+        // Pairs are closed innermost first, so outer brackets also cover any
+        // stretched inner ones.
+        for (o, c) in pairs {
+            let inner = &blocks[o + 1..c];
+            let above = inner.iter().map(|b| b.baseline).max().unwrap_or(0);
+            let below = inner.iter().map(Block::depth).max().unwrap_or(0);
+            if above + below > 0 {
+                let (oc, cc) = (op_char(&elems[o]).unwrap(), op_char(&elems[c]).unwrap());
+                blocks[o] = self.fence(oc, above, below);
+                blocks[c] = self.fence(cc, above, below);
+            }
+        }
+        blocks
+    }
+
+    /// Draw a bracket stretched over several lines.
+    fn fence(&self, c: char, above: usize, below: usize) -> Block {
+        let height = above + below + 1;
+        let (top, mid, bottom, center) = match (c, self.opts.ascii) {
+            ('(', false) => ('⎛', '⎜', '⎝', None),
+            (')', false) => ('⎞', '⎟', '⎠', None),
+            ('[', false) | ('⟦', false) => ('⎡', '⎢', '⎣', None),
+            (']', false) | ('⟧', false) => ('⎤', '⎥', '⎦', None),
+            ('{', false) => ('⎧', '⎪', '⎩', Some('⎨')),
+            ('}', false) => ('⎫', '⎪', '⎭', Some('⎬')),
+            ('⌊', false) => ('⎢', '⎢', '⎣', None),
+            ('⌋', false) => ('⎥', '⎥', '⎦', None),
+            ('⌈', false) => ('⎡', '⎢', '⎢', None),
+            ('⌉', false) => ('⎤', '⎥', '⎥', None),
+            ('⟨', _) => (
+                self.pick('╱', '/'),
+                self.pick('╱', '/'),
+                self.pick('╲', '\\'),
+                None,
+            ),
+            ('⟩', _) => (
+                self.pick('╲', '\\'),
+                self.pick('╲', '\\'),
+                self.pick('╱', '/'),
+                None,
+            ),
+            ('‖', false) => ('║', '║', '║', None),
+            ('(', true) => ('/', '|', '\\', None),
+            (')', true) => ('\\', '|', '/', None),
+            ('{', true) => ('/', '|', '\\', Some('<')),
+            ('}', true) => ('\\', '|', '/', Some('>')),
+            ('[', true) | ('⟦', true) | ('⌊', true) | ('⌈', true) => ('[', '[', '[', None),
+            (']', true) | ('⟧', true) | ('⌋', true) | ('⌉', true) => (']', ']', ']', None),
+            _ => (
+                self.pick('│', '|'),
+                self.pick('│', '|'),
+                self.pick('│', '|'),
+                None,
+            ),
+        };
+        let mut lines = Vec::with_capacity(height);
+        for r in 0..height {
+            let c = if r == 0 {
+                top
+            } else if r + 1 == height {
+                bottom
+            } else if Some(r) == center.map(|_| height / 2) {
+                center.unwrap()
+            } else if matches!(c, '⟨' | '⟩') {
+                // Angle brackets turn at the middle.
+                if r < height / 2 {
+                    top
+                } else {
+                    bottom
+                }
+            } else {
+                mid
+            };
+            let mut line = vec![c];
+            if c == '‖' && self.opts.ascii {
+                line = vec!['|', '|'];
+            }
+            lines.push(line);
+        }
+        Block::new(lines, above)
+    }
+
+    fn table(&self, rows: &[TableRow]) -> Block {
+        let cells: Vec<Vec<Block>> = rows
+            .iter()
+            .map(|r| r.cells.iter().map(|c| self.row(&c.elems)).collect())
+            .collect();
+        let cols = cells.iter().map(Vec::len).max().unwrap_or(0);
+        let mut widths = vec![0; cols];
+        for r in &cells {
+            for (i, c) in r.iter().enumerate() {
+                widths[i] = widths[i].max(c.width());
+            }
+        }
+        let mut lines = Vec::new();
+        for r in cells {
+            let mut blocks = Vec::new();
+            for (i, width) in widths.iter().enumerate() {
+                if i > 0 {
+                    blocks.push(Block::blank(2, 1, 0));
+                }
+                let cell = r.get(i).cloned().unwrap_or_else(|| Block::blank(0, 1, 0));
+                let left = (width - cell.width()) / 2;
+                let right = width - cell.width() - left;
+                blocks.push(Block::hcat([
+                    Block::blank(left, 1, 0),
+                    cell,
+                    Block::blank(right, 1, 0),
+                ]));
+            }
+            lines.extend(Block::hcat(blocks).lines);
+        }
+        let baseline = lines.len().saturating_sub(1) / 2;
+        if lines.is_empty() {
+            return Block::text("");
+        }
+        Block::new(lines, baseline)
+    }
+
+    /// Lay out a row, breaking it into several lines before top-level
+    /// operators so each line fits in `width`.
+    fn wrapped(&self, elems: &[Element], width: usize) -> Block {
+        let blocks = self.row_blocks(elems);
+        let mut depth = 0usize;
+        let mut breaks = vec![false; elems.len()];
+        for (i, e) in elems.iter().enumerate() {
+            match op_char(e) {
+                Some(c) if is_open(c) && !is_close(c) => depth += 1,
+                Some(c) if is_close(c) && !is_open(c) => depth = depth.saturating_sub(1),
+                Some(c) => {
+                    breaks[i] = depth == 0
+                        && i > 0
+                        && !TIGHT.contains(&c)
+                        && !POSTFIX.contains(&c)
+                        && !matches!(c, ',' | ';' | '|' | '‖')
+                }
+                None => (),
+            }
+        }
+        let mut lines = Vec::new();
+        let mut start = 0;
+        let mut used = 0;
+        let mut brk = None;
+        for (i, b) in blocks.iter().enumerate() {
+            if breaks[i] && i > start {
+                brk = Some(i);
+            }
+            used += b.width();
+            if used > width {
+                if let Some(at) = brk.take() {
+                    lines.push(start..at);
+                    start = at;
+                    used = blocks[at..=i].iter().map(Block::width).sum();
+                }
+            }
+        }
+        lines.push(start..blocks.len());
+        if lines.len() == 1 {
+            return Block::hcat(blocks);
+        }
+        let parts = lines
+            .into_iter()
+            .map(|r| Block::hcat(blocks[r].iter().cloned()))
+            .collect();
+        Block::vstack(parts, 0, Align::Left)
+    }
+}
+
+/// Stack scripts into a column to go beside a base, with the superscript
+/// above the base and the subscript below it.
+fn script_column(base: &Block, sub: Option<Block>, sup: Option<Block>) -> Block {
+    let mut parts = Vec::new();
+    let sup_rows = sup.as_ref().map_or(0, Block::height);
+    parts.extend(sup);
+    parts.push(Block::blank(0, base.height(), base.baseline));
+    parts.extend(sub);
+    let mid = usize::from(sup_rows > 0);
+    Block::vstack(parts, mid, Align::Left)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pretty(e: &Element) -> String {
+        e.to_pretty()
+    }
+
+    #[test]
+    fn fractions_and_radicals() {
+        let e = Element::row([
+            Element::frac(Element::num("1"), Element::id("x")),
+            Element::op('+'),
+            Element::sqrt(Element::id("y")),
+        ]);
+        assert_eq!(pretty(&e), "1    _\n─ + √y\nx");
+        let e = Element::root(
+            Element::frac(Element::id("a"), Element::id("b")),
+            Element::num("3"),
+        );
+        assert_eq!(pretty(&e), "  _\n │a\n3│─\n √b");
+    }
+
+    #[test]
+    fn function_application() {
+        let e = Element::row([
+            Element::id("sin"),
+            Element::op('\u{2061}'),
+            Element::id("x"),
+        ]);
+        assert_eq!(pretty(&e), "sin\u{2009}x");
+        assert_eq!(e.to_pretty_with(&PrettyOptions::ascii()), "sin x");
+        let e = Element::row([
+            Element::id("f"),
+            Element::op('\u{2061}'),
+            Element::row([Element::op('('), Element::id("x"), Element::op(')')]),
+        ]);
+        assert_eq!(pretty(&e), "f(x)");
+    }
+
+    #[test]
+    fn scripts() {
+        let e = Element::row([
+            Element::sup(Element::id("x"), Element::num("2")),
+            Element::op('−'),
+            Element::sub_sup(Element::id("a"), Element::id("i"), Element::id("n")),
+        ]);
+        assert_eq!(pretty(&e), " 2    n\nx  − a\n      i");
+        let e = Element::multiscript(
+            Element::id("C"),
+            [Pair::new(Element::row([]), Element::id("k"))],
+            [Pair::new(Element::id("n"), Element::row([]))],
+        );
+        assert_eq!(pretty(&e), "  k\n C\nn");
+    }
+
+    #[test]
+    fn matrices() {
+        let e = Element::row([
+            Element::op('('),
+            Element::matrix([
+                [Element::num("1"), Element::num("10")],
+                [Element::id("x"), Element::num("0")],
+            ]),
+            Element::op(')'),
+        ]);
+        assert_eq!(pretty(&e), "⎛1  10⎞\n⎝x  0 ⎠");
+        let e = Element::row([
+            Element::op('|'),
+            Element::frac(Element::num("1"), Element::num("2")),
+            Element::op('|'),
+        ]);
+        assert_eq!(pretty(&e), "│1│\n│─│\n│2│");
+    }
+
+    #[test]
+    fn big_operators() {
+        let e = Element::row([
+            Element::under_over(
+                Element::op('∑'),
+                Element::row([Element::id("i"), Element::op('='), Element::num("1")]),
+                Element::id("n"),
+            ),
+            Element::sup(Element::id("i"), Element::num("2")),
+        ]);
+        assert_eq!(
+            pretty(&e),
+            ["  n", " ___", " ╲     2", " ╱    i", " ‾‾‾", "i = 1"].join("\n")
+        );
+        let e = Element::row([
+            Element::sub_sup(Element::op('∫'), Element::num("0"), Element::num("1")),
+            Element::id("x"),
+            Element::id_normal("d"),
+            Element::id("x"),
+        ]);
+        assert_eq!(pretty(&e), " 1\n⌠\n⎮  xdx\n⌡\n 0");
+    }
+
+    #[test]
+    fn accents() {
+        let e = Element::row([
+            Element::over_accent(Element::id("v"), Element::op('→')),
+            Element::op('='),
+            Element::under(
+                Element::row([Element::id("a"), Element::op('+'), Element::id("b")]),
+                Element::op('⏟'),
+            ),
+        ]);
+        assert_eq!(pretty(&e), "→\nv = a + b\n    ╰─┬─╯");
+    }
+
+    #[test]
+    fn errors() {
+        let e = Element::row([
+            Element::id("x"),
+            Element::op('+'),
+            Element::new(MathElement::Unknown {
+                tag: "Glow".into(),
+                value: None,
+            }),
+        ]);
+        assert_eq!(e.to_pretty(), "    ┌────┐\nx + │Glow│\n    └────┘");
+        assert_eq!(
+            Element::err("bad").to_pretty_with(&PrettyOptions::ascii()),
+            "+---+\n|bad|\n+---+"
+        );
+    }
+
+    #[test]
+    fn ascii() {
+        let e = Element::row([
+            Element::id("α"),
+            Element::op('≤'),
+            Element::sqrt(Element::frac(Element::id("π"), Element::num("2"))),
+        ]);
+        assert_eq!(
+            e.to_pretty_with(&PrettyOptions::ascii()),
+            "           __\n          |pi\nalpha <=  |--\n         \\/2"
+        );
+    }
+
+    #[test]
+    fn width() {
+        let mut elems = vec![Element::id("a")];
+        for t in ["b", "c", "d"] {
+            elems.push(Element::op('+'));
+            elems.push(Element::id(t));
+        }
+        let e = Element::row(elems);
+        let opts = PrettyOptions {
+            width: Some(6),
+            ascii: false,
+        };
+        assert_eq!(e.to_pretty_with(&opts), "a + b\n + c\n + d");
+        assert_eq!(e.to_pretty(), "a + b + c + d");
+    }
+
+    #[test]
+    fn operator_forms() {
+        let e = Element::row([
+            Element::id("a"),
+            Element::op('='),
+            Element::op('-'),
+            Element::id("b"),
+        ]);
+        assert_eq!(e.to_pretty(), "a = -b");
+        // Resolved operators keep the form inferred from their position.
+        let mut resolved = e.clone();
+        resolved.resolve_operators();
+        assert_eq!(resolved.to_pretty(), "a = - b");
+        let e = Element::row([
+            Element::id("n"),
+            Element::oper(Operator {
+                form: Some(OpForm::Postfix),
+                ..Operator::new('+')
+            }),
+        ]);
+        assert_eq!(e.to_pretty(), "n+");
+    }
+}
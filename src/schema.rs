@@ -1,370 +1,567 @@
+//! The fog-pack schema for fog-math documents.
+//!
+//! The schema is derived from the types in [`math`](crate::math) through
+//! [`SchemaType`], so it follows their serde representation without being
+//! written out by hand. Doc comments on those types become validator
+//! comments, except on enums and on fields referencing another named type,
+//! as fog-pack has nowhere to put them.
+//!
+//! Documents refer to the schema by its hash, so any change to the schema,
+//! including to those doc comments, leaves documents written with the old
+//! one behind. Changes need a new
+//! [`VERSION`] and a registered [migration](crate::migrate).
+
+use std::collections::BTreeMap;
 use std::sync::OnceLock;
 
 use fog_pack::{
     document::Document,
     schema::{Schema, SchemaBuilder},
+    types::Value,
     validator::*,
 };
 
+use crate::math::Element;
+
+pub use fog_math_derive::SchemaType;
+
+/// The version of the schema built by this crate. Documents written with
+/// earlier versions can be read through [`Migrations`](crate::migrate::Migrations).
+pub const VERSION: u32 = 2;
+
 static SCHEMA_DOC: OnceLock<Document> = OnceLock::new();
 static SCHEMA: OnceLock<Schema> = OnceLock::new();
 
+/// The fog-math schema, for validating and encoding documents. See
+/// [`encode_document`](crate::encode_document) and
+/// [`decode_document`](crate::decode_document) for the usual way to use it.
+///
+/// The schema is built from the crate's own types, so building it can't
+/// fail short of a bug in this crate.
 pub fn schema() -> &'static Schema {
-    SCHEMA.get_or_init(|| Schema::from_doc(schema_doc()).unwrap())
+    SCHEMA.get_or_init(|| Schema::from_doc(schema_doc()).expect("fog-math schema is valid"))
 }
 
+/// The document describing the fog-math schema. A fog-math document is a
+/// list of equations, each of which is an [`Element`].
 pub fn schema_doc() -> &'static Document {
     SCHEMA_DOC.get_or_init(|| {
-        SchemaBuilder::new(
-            ArrayValidator::new()
-                .items(EnumValidator::new().build())
-                .build(),
-        )
-        .type_add(
-            "OpForm",
-            EnumValidator::new()
-                .insert("Prefix", None)
-                .insert("Postfix", None)
-                .insert("Infix", None)
-                .build(),
-        )
-        .type_add(
-            "Attributes",
-            MapValidator::new()
-                .opt_add(
-                    "class",
-                    ArrayValidator::new()
-                        .items(StrValidator::new().build())
-                        .build(),
-                )
-                .opt_add(
-                    "rtl",
-                    BoolValidator::new()
-                        .comment("Set for right-to-left directionality")
-                        .build(),
-                )
-                .opt_add("display_style", BoolValidator::new().build())
-                .opt_add(
-                    "variant",
-                    StrValidator::new()
-                        .in_add("Normal")
-                        .in_add("Bold")
-                        .in_add("Italic")
-                        .in_add("BoldItalic")
-                        .in_add("DoubleStruck")
-                        .in_add("BoldFraktur")
-                        .in_add("Script")
-                        .in_add("BoldScript")
-                        .in_add("Fraktur")
-                        .in_add("SansSerif")
-                        .in_add("BoldSansSerif")
-                        .in_add("SansSerifItalic")
-                        .in_add("SansSerifBoldItalic")
-                        .in_add("Monospace")
-                        .in_add("Initial")
-                        .in_add("Tailed")
-                        .in_add("Looped")
-                        .in_add("Stretched")
-                        .build(),
-                )
-                .opt_add(
-                    "script_level",
-                    EnumValidator::new()
-                        .insert(
-                            "Set",
-                            Some(IntValidator::new().min(u32::MIN).max(u32::MAX).build()),
-                        )
-                        .insert(
-                            "Add",
-                            Some(IntValidator::new().min(i32::MIN).max(i32::MAX).build()),
-                        )
-                        .build(),
-                )
-                .opt_add(
-                    "data",
-                    MapValidator::new().values(Validator::new_any()).build(),
-                )
-                .build(),
-        )
-        .type_add(
-            "Length",
-            EnumValidator::new()
-                .insert("Em", Some(Validator::F32(F32Validator::new())))
-                .insert("Ex", Some(Validator::F32(F32Validator::new())))
-                .build(),
-        )
-        .type_add(
-            "LengthOrFraction",
-            EnumValidator::new()
-                .insert("Em", Some(Validator::F32(F32Validator::new())))
-                .insert("Ex", Some(Validator::F32(F32Validator::new())))
-                .insert("Frac", Some(Validator::F32(F32Validator::new())))
-                .build(),
-        )
-        .type_add(
-            "Pair",
-            MapValidator::new()
-                .req_add("sup", Validator::new_ref("Element"))
-                .req_add("sub", Validator::new_ref("Element"))
-                .build(),
-        )
-        .type_add(
-            "Element",
-            MapValidator::new()
-                .opt_add("a", Validator::new_ref("Attributes"))
-                .req_add(
-                    "e",
-                    EnumValidator::new()
-                        .insert("Op", Some(StrValidator::new().max_char(1).build()))
-                        .insert(
-                            "Oper",
-                            Some(
-                                MapValidator::new()
-                                    .req_add("t", StrValidator::new().max_char(1).build())
-                                    .opt_add(
-                                        "form",
-                                        EnumValidator::new()
-                                            .insert("Prefix", None)
-                                            .insert("Postfix", None)
-                                            .insert("Infix", None)
-                                            .build(),
-                                    )
-                                    .opt_add("max_size", Validator::new_ref("LengthOrFraction"))
-                                    .opt_add("min_size", Validator::new_ref("LengthOrFraction"))
-                                    .opt_add("lspace", Validator::new_ref("LengthOrFraction"))
-                                    .opt_add("rspace", Validator::new_ref("LengthOrFraction"))
-                                    .opt_add("stretchy", BoolValidator::new().build())
-                                    .opt_add("symmetric", BoolValidator::new().build())
-                                    .opt_add("large_op", BoolValidator::new().build())
-                                    .opt_add("movable_limits", BoolValidator::new().build())
-                                    .opt_add("separator", BoolValidator::new().build())
-                                    .opt_add("fence", BoolValidator::new().build())
-                                    .build(),
-                            ),
-                        )
-                        .insert(
-                            "ResolvedOper",
-                            Some(
-                                MapValidator::new()
-                                    .req_add("t", StrValidator::new().max_char(1).build())
-                                    .req_add(
-                                        "form",
-                                        EnumValidator::new()
-                                            .insert("Prefix", None)
-                                            .insert("Postfix", None)
-                                            .insert("Infix", None)
-                                            .build(),
-                                    )
-                                    .req_add("max_size", Validator::new_ref("Length"))
-                                    .req_add("min_size", Validator::new_ref("Length"))
-                                    .req_add("lspace", Validator::new_ref("Length"))
-                                    .req_add("rspace", Validator::new_ref("Length"))
-                                    .req_add("stretchy", BoolValidator::new().build())
-                                    .req_add("symmetric", BoolValidator::new().build())
-                                    .req_add("large_op", BoolValidator::new().build())
-                                    .req_add("movable_limits", BoolValidator::new().build())
-                                    .req_add("separator", BoolValidator::new().build())
-                                    .req_add("fence", BoolValidator::new().build())
-                                    .build(),
-                            ),
-                        )
-                        .insert("Text", Some(StrValidator::new().build()))
-                        .insert(
-                            "Id",
-                            Some(
-                                MapValidator::new()
-                                    .req_add("t", StrValidator::new().build())
-                                    .opt_add("normal", BoolValidator::new().build())
-                                    .build(),
-                            ),
-                        )
-                        .insert("Num", Some(StrValidator::new().build()))
-                        .insert("Err", Some(StrValidator::new().build()))
-                        .insert(
-                            "Space",
-                            Some(
-                                MapValidator::new()
-                                    .opt_add("width", Validator::new_ref("Length"))
-                                    .opt_add("height", Validator::new_ref("Length"))
-                                    .opt_add("depth", Validator::new_ref("Length"))
-                                    .build(),
-                            ),
-                        )
-                        .insert("Str", Some(StrValidator::new().build()))
-                        .insert(
-                            "Phantom",
-                            Some(
-                                ArrayValidator::new()
-                                    .items(Validator::new_ref("Element"))
-                                    .build(),
-                            ),
-                        )
-                        .insert(
-                            "Row",
-                            Some(
-                                ArrayValidator::new()
-                                    .items(Validator::new_ref("Element"))
-                                    .build(),
-                            ),
-                        )
-                        .insert(
-                            "Padding",
-                            Some(
-                                MapValidator::new()
-                                    .opt_add(
-                                        "elems",
-                                        ArrayValidator::new()
-                                            .items(Validator::new_ref("Element"))
-                                            .build(),
-                                    )
-                                    .opt_add("width", Validator::new_ref("Length"))
-                                    .opt_add("height", Validator::new_ref("Length"))
-                                    .opt_add("depth", Validator::new_ref("Length"))
-                                    .opt_add("lspace", Validator::new_ref("Length"))
-                                    .opt_add("voffset", Validator::new_ref("Length"))
-                                    .build(),
-                            ),
-                        )
-                        .insert(
-                            "Frac",
-                            Some(
-                                MapValidator::new()
-                                    .req_add("num", Validator::new_ref("Element"))
-                                    .req_add("den", Validator::new_ref("Element"))
-                                    .opt_add("line_thickness", Validator::F32(F32Validator::new()))
-                                    .build(),
-                            ),
-                        )
-                        .insert("Sqrt", Some(Validator::new_ref("Element")))
-                        .insert(
-                            "Root",
-                            Some(
-                                MapValidator::new()
-                                    .req_add("base", Validator::new_ref("Element"))
-                                    .req_add("index", Validator::new_ref("Element"))
-                                    .build(),
-                            ),
-                        )
-                        .insert(
-                            "Sup",
-                            Some(
-                                MapValidator::new()
-                                    .req_add("base", Validator::new_ref("Element"))
-                                    .req_add("sup", Validator::new_ref("Element"))
-                                    .build(),
-                            ),
-                        )
-                        .insert(
-                            "Sub",
-                            Some(
-                                MapValidator::new()
-                                    .req_add("base", Validator::new_ref("Element"))
-                                    .req_add("sub", Validator::new_ref("Element"))
-                                    .build(),
-                            ),
-                        )
-                        .insert(
-                            "SubSup",
-                            Some(
-                                MapValidator::new()
-                                    .req_add("base", Validator::new_ref("Element"))
-                                    .req_add("sup", Validator::new_ref("Element"))
-                                    .req_add("sub", Validator::new_ref("Element"))
-                                    .build(),
-                            ),
-                        )
-                        .insert(
-                            "Over",
-                            Some(
-                                MapValidator::new()
-                                    .req_add("base", Validator::new_ref("Element"))
-                                    .req_add("over", Validator::new_ref("Element"))
-                                    .opt_add("accent", BoolValidator::new().build())
-                                    .build(),
-                            ),
-                        )
-                        .insert(
-                            "Under",
-                            Some(
-                                MapValidator::new()
-                                    .req_add("base", Validator::new_ref("Element"))
-                                    .req_add("under", Validator::new_ref("Element"))
-                                    .opt_add("accent_under", BoolValidator::new().build())
-                                    .build(),
-                            ),
-                        )
-                        .insert(
-                            "UnderOver",
-                            Some(
-                                MapValidator::new()
-                                    .req_add("base", Validator::new_ref("Element"))
-                                    .req_add("under", Validator::new_ref("Element"))
-                                    .req_add("over", Validator::new_ref("Element"))
-                                    .opt_add("accent", BoolValidator::new().build())
-                                    .opt_add("accent_under", BoolValidator::new().build())
-                                    .build(),
-                            ),
-                        )
-                        .insert(
-                            "MultiScript",
-                            Some(
-                                MapValidator::new()
-                                    .req_add("base", Validator::new_ref("Element"))
-                                    .opt_add(
-                                        "post",
-                                        ArrayValidator::new()
-                                            .items(Validator::new_ref("Pair"))
-                                            .build(),
-                                    )
-                                    .opt_add(
-                                        "pre",
-                                        ArrayValidator::new()
-                                            .items(Validator::new_ref("Pair"))
-                                            .build(),
-                                    )
-                                    .build(),
-                            ),
-                        )
-                        .insert(
-                            "Table",
-                            Some(
-                                ArrayValidator::new()
-                                    .items(
-                                        MapValidator::new()
-                                            .opt_add("a", Validator::new_ref("Attributes"))
-                                            .opt_add(
-                                                "cells",
-                                                ArrayValidator::new()
-                                                    .items(Validator::new_ref("TableCell"))
-                                                    .build(),
-                                            )
-                                            .build(),
-                                    )
-                                    .build(),
-                            ),
-                        )
-                        .build(),
-                )
-                .build(),
-        )
-        .type_add(
-            "TableCell",
-            MapValidator::new()
-                .opt_add("col_span", IntValidator::new().min(0).max(u32::MAX).build())
-                .opt_add("row_span", IntValidator::new().min(0).max(u32::MAX).build())
-                .opt_add(
-                    "elems",
-                    ArrayValidator::new()
-                        .items(Validator::new_ref("Element"))
-                        .build(),
-                )
-                .opt_add("a", Validator::new_ref("Attributes"))
-                .build(),
-        )
-        .name("fog-math")
-        .version(1)
-        .description("Formatted math, closely matching MathML.")
-        .build()
-        .unwrap()
+        let mut types = BTreeMap::new();
+        let root = ArrayValidator::new()
+            .comment("A list of equations")
+            .items(Element::validator(&mut types))
+            .build();
+        types
+            .into_iter()
+            .fold(SchemaBuilder::new(root), |b, (name, v)| {
+                b.type_add(&name, v)
+            })
+            .name("fog-math")
+            .version(VERSION)
+            .description("Formatted math, closely matching MathML.")
+            .build()
+            .expect("fog-math schema is valid")
     })
 }
+
+/// A type that can be described by a fog-pack [`Validator`]. Derive it
+/// with `#[derive(SchemaType)]` alongside serde's derives.
+pub trait SchemaType {
+    /// Get the validator to use wherever this type appears, adding any
+    /// named types it relies on to `types`.
+    fn validator(types: &mut BTreeMap<String, Validator>) -> Validator;
+}
+
+/// Register a named type, building its validator only the first time it's
+/// seen, and return a reference to it. Used by the derive macro.
+#[doc(hidden)]
+pub fn named(
+    types: &mut BTreeMap<String, Validator>,
+    name: &str,
+    build: impl FnOnce(&mut BTreeMap<String, Validator>) -> Validator,
+) -> Validator {
+    if !types.contains_key(name) {
+        // Hold the name while building, so recursive types terminate.
+        types.insert(name.to_string(), Validator::new_any());
+        let v = build(types);
+        types.insert(name.to_string(), v);
+    }
+    Validator::new_ref(name)
+}
+
+/// Attach a comment to a validator, if it has room for one. Used by the
+/// derive macro.
+#[doc(hidden)]
+pub fn commented(mut v: Validator, comment: &str) -> Validator {
+    if comment.is_empty() {
+        return v;
+    }
+    let slot = match &mut v {
+        Validator::Bool(v) => &mut v.comment,
+        Validator::Int(v) => &mut v.comment,
+        Validator::F32(v) => &mut v.comment,
+        Validator::F64(v) => &mut v.comment,
+        Validator::Str(v) => &mut v.comment,
+        Validator::Array(v) => &mut v.comment,
+        Validator::Map(v) => &mut v.comment,
+        _ => return v,
+    };
+    *slot = comment.to_string();
+    v
+}
+
+impl SchemaType for bool {
+    fn validator(_: &mut BTreeMap<String, Validator>) -> Validator {
+        BoolValidator::new().build()
+    }
+}
+
+impl SchemaType for char {
+    fn validator(_: &mut BTreeMap<String, Validator>) -> Validator {
+        StrValidator::new().min_char(1).max_char(1).build()
+    }
+}
+
+impl SchemaType for String {
+    fn validator(_: &mut BTreeMap<String, Validator>) -> Validator {
+        StrValidator::new().build()
+    }
+}
+
+impl SchemaType for f32 {
+    fn validator(_: &mut BTreeMap<String, Validator>) -> Validator {
+        Validator::F32(F32Validator::new())
+    }
+}
+
+impl SchemaType for f64 {
+    fn validator(_: &mut BTreeMap<String, Validator>) -> Validator {
+        Validator::F64(F64Validator::new())
+    }
+}
+
+macro_rules! int_schema {
+    ($($t:ty),*) => {
+        $(impl SchemaType for $t {
+            fn validator(_: &mut BTreeMap<String, Validator>) -> Validator {
+                IntValidator::new().min(<$t>::MIN).max(<$t>::MAX).build()
+            }
+        })*
+    };
+}
+
+int_schema!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+impl SchemaType for Value {
+    fn validator(_: &mut BTreeMap<String, Validator>) -> Validator {
+        Validator::new_any()
+    }
+}
+
+/// Optional values are left out when unset, so they validate as the inner
+/// type in an optional field.
+impl<T: SchemaType> SchemaType for Option<T> {
+    fn validator(types: &mut BTreeMap<String, Validator>) -> Validator {
+        T::validator(types)
+    }
+}
+
+impl<T: SchemaType> SchemaType for Box<T> {
+    fn validator(types: &mut BTreeMap<String, Validator>) -> Validator {
+        T::validator(types)
+    }
+}
+
+impl<T: SchemaType> SchemaType for Vec<T> {
+    fn validator(types: &mut BTreeMap<String, Validator>) -> Validator {
+        ArrayValidator::new().items(T::validator(types)).build()
+    }
+}
+
+impl<T: SchemaType> SchemaType for BTreeMap<String, T> {
+    fn validator(types: &mut BTreeMap<String, Validator>) -> Validator {
+        MapValidator::new().values(T::validator(types)).build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::*;
+    use fog_pack::{document::NewDocument, types::Value};
+    use serde::Serialize;
+
+    /// Every variant of `MathElement`. Adding a variant breaks
+    /// `variant_name`; add it here too, along with a sample in `samples`.
+    const VARIANTS: &[&str] = &[
+        "Op",
+        "Oper",
+        "ResolvedOper",
+        "Text",
+        "Id",
+        "Num",
+        "Err",
+        "Space",
+        "Str",
+        "Phantom",
+        "Row",
+        "Padding",
+        "Frac",
+        "Sqrt",
+        "Root",
+        "Sup",
+        "Sub",
+        "SubSup",
+        "Over",
+        "Under",
+        "UnderOver",
+        "MultiScript",
+        "Table",
+    ];
+
+    fn variant_name(e: &MathElement) -> &'static str {
+        use MathElement::*;
+        match e {
+            Op(_) => "Op",
+            Oper(_) => "Oper",
+            ResolvedOper(_) => "ResolvedOper",
+            Text(_) => "Text",
+            Id { .. } => "Id",
+            Num(_) => "Num",
+            Err(_) => "Err",
+            Space(_) => "Space",
+            Str(_) => "Str",
+            Phantom(_) => "Phantom",
+            Row(_) => "Row",
+            Padding(_) => "Padding",
+            Frac { .. } => "Frac",
+            Sqrt(_) => "Sqrt",
+            Root { .. } => "Root",
+            Sup { .. } => "Sup",
+            Sub { .. } => "Sub",
+            SubSup { .. } => "SubSup",
+            Over { .. } => "Over",
+            Under { .. } => "Under",
+            UnderOver { .. } => "UnderOver",
+            MultiScript { .. } => "MultiScript",
+            Table { .. } => "Table",
+            // Not part of the schema, so never among the samples.
+            Unknown { .. } => "Unknown",
+        }
+    }
+
+    const ALL_VARIANTS: &[Variant] = &[
+        Variant::Normal,
+        Variant::Bold,
+        Variant::Italic,
+        Variant::BoldItalic,
+        Variant::DoubleStruck,
+        Variant::BoldFraktur,
+        Variant::Script,
+        Variant::BoldScript,
+        Variant::Fraktur,
+        Variant::SansSerif,
+        Variant::BoldSansSerif,
+        Variant::SansSerifItalic,
+        Variant::SansSerifBoldItalic,
+        Variant::Monospace,
+        Variant::Initial,
+        Variant::Tailed,
+        Variant::Looped,
+        Variant::Stretched,
+    ];
+
+    fn x() -> Element {
+        Element::id("x")
+    }
+
+    /// Samples of every element, with and without their optional fields.
+    fn samples() -> Vec<Element> {
+        let em = |v| Length::Em(v);
+        let full_op = Operator {
+            t: '∑',
+            form: Some(OpForm::Prefix),
+            max_size: Some(LengthOrFraction::Frac(2.0)),
+            min_size: Some(LengthOrFraction::Em(1.0)),
+            lspace: Some(LengthOrFraction::Ex(0.5)),
+            rspace: Some(LengthOrFraction::Em(0.0)),
+            stretchy: Some(false),
+            symmetric: Some(true),
+            large_op: Some(true),
+            movable_limits: Some(true),
+            separator: Some(false),
+            fence: Some(false),
+        };
+        let mut cell = TableCell::new([x()]);
+        cell.col_span = 2;
+        cell.row_span = 3;
+        cell.a = Some(Box::new(Attributes {
+            class: vec!["cell".into()],
+            ..Attributes::default()
+        }));
+        let mut row = TableRow::new([cell, TableCell::new([])]);
+        row.a = Some(Box::new(Attributes {
+            rtl: true,
+            ..Attributes::default()
+        }));
+        vec![
+            Element::op('+'),
+            Element::oper(Operator::new('=')),
+            Element::oper(full_op.clone()),
+            Element::resolved_oper(full_op.resolve(OpForm::Prefix)),
+            Element::resolved_oper(crate::operator::lookup('(', OpForm::Prefix)),
+            Element::text("if"),
+            Element::id("x"),
+            Element::id_normal("sin"),
+            Element::num("1.5"),
+            Element::err("oops"),
+            Element::space(Space::default()),
+            Element::space(Space {
+                width: Some(em(1.0)),
+                height: Some(Length::Ex(1.0)),
+                depth: Some(em(-0.5)),
+            }),
+            Element::str("abc"),
+            Element::new(MathElement::Phantom(vec![x()])),
+            Element::row([]),
+            Element::row([x(), Element::op('+'), x()]),
+            Element::new(MathElement::Padding(Padding::default())),
+            Element::new(MathElement::Padding(Padding {
+                elems: vec![x()],
+                width: Some(em(1.0)),
+                height: Some(em(1.0)),
+                depth: Some(em(1.0)),
+                lspace: Some(em(1.0)),
+                voffset: Some(em(-1.0)),
+            })),
+            Element::frac(x(), x()),
+            Element::frac_thickness(x(), x(), 0.0),
+            Element::sqrt(x()),
+            Element::root(x(), Element::num("3")),
+            Element::sup(x(), x()),
+            Element::sub(x(), x()),
+            Element::sub_sup(x(), x(), x()),
+            Element::over(x(), x()),
+            Element::over_accent(x(), Element::op('^')),
+            Element::under(x(), x()),
+            Element::under_accent(x(), Element::op('_')),
+            Element::under_over(x(), x(), x()),
+            Element::under_over_accent(x(), x(), x(), true, true),
+            Element::multiscript(x(), [], []),
+            Element::multiscript(x(), [Pair::new(x(), x())], [Pair::new(x(), x())]),
+            Element::table([]),
+            Element::table([TableRow::default(), row]),
+            Element::matrix([[x(), x()], [x(), x()]]),
+        ]
+    }
+
+    fn encode<T: Serialize>(data: T) -> fog_pack::error::Result<Document> {
+        let doc = NewDocument::new(Some(schema().hash()), data)?;
+        schema().validate_new_doc(doc)
+    }
+
+    #[test]
+    fn every_element() {
+        let samples = samples();
+        for name in VARIANTS {
+            assert!(
+                samples.iter().any(|e| variant_name(e.elem()) == *name),
+                "no sample for {}",
+                name
+            );
+        }
+        for e in &samples {
+            if let Err(err) = encode([e]) {
+                panic!("{:?} failed validation: {}", e, err);
+            }
+        }
+        let doc = encode(&samples).unwrap();
+        let decoded: Vec<Element> = doc.deserialize().unwrap();
+        assert_eq!(decoded, samples);
+    }
+
+    #[test]
+    fn every_attribute() {
+        let mut data = std::collections::BTreeMap::new();
+        data.insert("source".to_string(), Value::from("x"));
+        data.insert("weight".to_string(), Value::from(3u8));
+        let full = Attributes {
+            class: vec!["a".into(), "b".into()],
+            rtl: true,
+            display_style: Some(false),
+            variant: Some(Variant::Bold),
+            script_level: Some(ScriptLevel::Add(-1)),
+            data: Some(data),
+            unknown: Default::default(),
+        };
+        let mut elems = vec![
+            Element::with_attributes(
+                MathElement::Id {
+                    t: "x".into(),
+                    normal: false,
+                },
+                full,
+            ),
+            Element::with_attributes(
+                MathElement::Num("1".into()),
+                Attributes {
+                    script_level: Some(ScriptLevel::Set(2)),
+                    ..Attributes::default()
+                },
+            ),
+            Element::with_attributes(MathElement::Op('+'), Attributes::default()),
+        ];
+        elems.extend(ALL_VARIANTS.iter().map(|v| x().variant(*v)));
+        let doc = encode(&elems).unwrap();
+        let decoded: Vec<Element> = doc.deserialize().unwrap();
+        assert_eq!(decoded, elems);
+    }
+
+    #[test]
+    fn root() {
+        assert!(encode(Vec::<Element>::new()).is_ok());
+        // A bare element isn't a list of equations.
+        assert!(encode(x()).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_shapes() {
+        #[derive(Serialize)]
+        struct Raw<T> {
+            e: T,
+        }
+        #[derive(Serialize)]
+        enum Old {
+            Table(Vec<TableRow>),
+        }
+        #[derive(Serialize)]
+        enum Future {
+            Hologram(String),
+        }
+        #[derive(Serialize)]
+        enum Multi {
+            Op(String),
+        }
+        // Tables used to be validated as a bare array of rows.
+        assert!(encode([Raw {
+            e: Old::Table(vec![TableRow::default()])
+        }])
+        .is_err());
+        assert!(encode([Raw {
+            e: Future::Hologram("x".into())
+        }])
+        .is_err());
+        assert!(encode([Raw {
+            e: Multi::Op("+-".into())
+        }])
+        .is_err());
+        assert!(encode([Raw {
+            e: Multi::Op("+".into())
+        }])
+        .is_ok());
+    }
+
+    #[test]
+    fn derived_types() {
+        let mut types = BTreeMap::new();
+        Element::validator(&mut types);
+        let names: Vec<&str> = types.keys().map(String::as_str).collect();
+        assert_eq!(
+            names,
+            [
+                "Attributes",
+                "Element",
+                "Length",
+                "LengthOrFraction",
+                "MathElement",
+                "OpForm",
+                "Operator",
+                "Padding",
+                "Pair",
+                "ResolvedOperator",
+                "ScriptLevel",
+                "Space",
+                "TableCell",
+                "TableRow",
+                "Variant",
+            ]
+        );
+
+        // Fields follow the serialized form, and a fully specified operator
+        // writes every one of them.
+        let Validator::Map(op) = &types["Operator"] else {
+            panic!("expected a map");
+        };
+        let mut keys: Vec<&str> = op
+            .req
+            .keys()
+            .chain(op.opt.keys())
+            .map(String::as_str)
+            .collect();
+        keys.sort_unstable();
+        let full = Operator {
+            form: Some(OpForm::Infix),
+            max_size: Some(LengthOrFraction::Em(1.0)),
+            min_size: Some(LengthOrFraction::Em(1.0)),
+            lspace: Some(LengthOrFraction::Em(1.0)),
+            rspace: Some(LengthOrFraction::Em(1.0)),
+            stretchy: Some(true),
+            symmetric: Some(true),
+            large_op: Some(true),
+            movable_limits: Some(true),
+            separator: Some(true),
+            fence: Some(true),
+            ..Operator::new('+')
+        };
+        let doc = NewDocument::new(None, &full).unwrap();
+        let doc = fog_pack::schema::NoSchema::validate_new_doc(doc).unwrap();
+        let Value::Map(written) = doc.deserialize().unwrap() else {
+            panic!("expected a map");
+        };
+        let written: Vec<&str> = written.keys().map(String::as_str).collect();
+        assert_eq!(keys, written);
+        // `Operator` has `#[serde(default)]`, so even `t` may be left out.
+        assert!(op.req.is_empty());
+
+        let Validator::Enum(variants) = &types["Variant"] else {
+            panic!("expected an enum");
+        };
+        assert_eq!(variants.0.len(), ALL_VARIANTS.len());
+    }
+
+    #[test]
+    fn comments() {
+        let mut types = BTreeMap::new();
+        Element::validator(&mut types);
+        let Validator::Map(element) = &types["Element"] else {
+            panic!("expected a map");
+        };
+        assert_eq!(
+            element.comment,
+            "A Math element, including any global attributes."
+        );
+        let Validator::Map(op) = &types["Operator"] else {
+            panic!("expected a map");
+        };
+        let Validator::Bool(stretchy) = &op.opt["stretchy"] else {
+            panic!("expected a bool");
+        };
+        assert_eq!(stretchy.comment, "If the operator should stretch");
+        let Validator::Str(t) = &op.opt["t"] else {
+            panic!("expected a string");
+        };
+        assert!(t.comment.starts_with("The operator's text"));
+        assert!(t.comment.contains('\n'));
+    }
+
+    #[test]
+    fn hash() {
+        // Changing the schema, even a doc comment on one of the math types,
+        // makes existing documents unreadable. If this fails, bump `VERSION`
+        // and register a migration from the old one.
+        assert_eq!(
+            schema().hash().to_string(),
+            "RqLWNicK1UXGTbsoCpBe49RPh7xEAU4S4q5Syg5Dn9jB"
+        );
+    }
+}
@@ -0,0 +1,1358 @@
+//! Conversion between StarMath, the formula language of LibreOffice Math and
+//! OpenDocument formulas, and fog-math elements.
+//!
+//! Parsing is forgiving: unknown `%` names become [`MathElement::Err`] nodes,
+//! unmatched brackets become plain operators, and the rest of the input is
+//! still converted. Unknown words are variables, as they are in LibreOffice.
+
+use crate::math::*;
+use crate::tree::{font_names, into_elem, into_vec, op_char};
+
+/// Where a script is placed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Script {
+    Sub,
+    Sup,
+    LSub,
+    LSup,
+    CSub,
+    CSup,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Sym {
+    /// An operator.
+    Op(char),
+    /// An identifier, using the default italics.
+    Id(char),
+    /// An upright identifier.
+    IdNormal(char),
+    /// A large operator.
+    Large(char),
+    /// A function name. Set if it's a limit function like `lim`.
+    Func(bool),
+    /// `func`, which makes the next word a function name.
+    FuncName,
+    Open(char),
+    Close(char),
+    /// Invisible grouping braces.
+    Group,
+    EndGroup,
+    Left,
+    Right,
+    /// The missing delimiter in `left none`.
+    NoDelim,
+    /// Accent placed over the next term.
+    Accent(char),
+    /// Line placed over or under the next term.
+    Line(char, bool),
+    /// Brace placed over or under the previous term, with a label.
+    Brace(char, bool),
+    /// Font attribute for the next term.
+    Font(&'static str),
+    /// `font`, followed by the font name.
+    FontName,
+    /// Attributes that are skipped, with the number of parameters they take.
+    Ignore(usize),
+    Sqrt,
+    NRoot,
+    Abs,
+    Fact,
+    Binom,
+    Stack,
+    Matrix,
+    Phantom,
+    Over,
+    Script(Script),
+    /// Cell separator in matrices.
+    Col,
+    /// Row separator in matrices.
+    RowSep,
+    Newline,
+}
+
+/// The StarMath keyword table. The first entry for a given output is the one
+/// used when writing.
+const SYMBOLS: &[(&str, Sym)] = &[
+    // Operators
+    ("+", Sym::Op('+')),
+    ("-", Sym::Op('−')),
+    ("*", Sym::Op('∗')),
+    ("/", Sym::Op('/')),
+    ("cdot", Sym::Op('⋅')),
+    ("times", Sym::Op('×')),
+    ("div", Sym::Op('÷')),
+    ("+-", Sym::Op('±')),
+    ("-+", Sym::Op('∓')),
+    ("neg", Sym::Op('¬')),
+    ("and", Sym::Op('∧')),
+    ("or", Sym::Op('∨')),
+    ("circ", Sym::Op('∘')),
+    ("oplus", Sym::Op('⊕')),
+    ("ominus", Sym::Op('⊖')),
+    ("otimes", Sym::Op('⊗')),
+    ("odot", Sym::Op('⊙')),
+    ("odivide", Sym::Op('⊘')),
+    ("setminus", Sym::Op('∖')),
+    ("bslash", Sym::Op('∖')),
+    ("intersection", Sym::Op('∩')),
+    ("union", Sym::Op('∪')),
+    // Relations
+    ("=", Sym::Op('=')),
+    ("<>", Sym::Op('≠')),
+    ("neq", Sym::Op('≠')),
+    ("<", Sym::Op('<')),
+    ("lt", Sym::Op('<')),
+    (">", Sym::Op('>')),
+    ("gt", Sym::Op('>')),
+    ("<=", Sym::Op('≤')),
+    ("le", Sym::Op('≤')),
+    (">=", Sym::Op('≥')),
+    ("ge", Sym::Op('≥')),
+    ("leslant", Sym::Op('⩽')),
+    ("geslant", Sym::Op('⩾')),
+    ("<<", Sym::Op('≪')),
+    ("ll", Sym::Op('≪')),
+    (">>", Sym::Op('≫')),
+    ("gg", Sym::Op('≫')),
+    ("approx", Sym::Op('≈')),
+    ("sim", Sym::Op('∼')),
+    ("simeq", Sym::Op('≃')),
+    ("equiv", Sym::Op('≡')),
+    ("cong", Sym::Op('≅')),
+    ("prop", Sym::Op('∝')),
+    ("parallel", Sym::Op('∥')),
+    ("ortho", Sym::Op('⊥')),
+    ("divides", Sym::Op('∣')),
+    ("ndivides", Sym::Op('∤')),
+    ("toward", Sym::Op('→')),
+    ("drarrow", Sym::Op('⇒')),
+    ("dlarrow", Sym::Op('⇐')),
+    ("dlrarrow", Sym::Op('⇔')),
+    ("def", Sym::Op('≝')),
+    ("in", Sym::Op('∈')),
+    ("notin", Sym::Op('∉')),
+    ("owns", Sym::Op('∋')),
+    ("subset", Sym::Op('⊂')),
+    ("subseteq", Sym::Op('⊆')),
+    ("supset", Sym::Op('⊃')),
+    ("supseteq", Sym::Op('⊇')),
+    ("nsubset", Sym::Op('⊄')),
+    ("nsupset", Sym::Op('⊅')),
+    ("prec", Sym::Op('≺')),
+    ("succ", Sym::Op('≻')),
+    // Other symbols
+    ("infinity", Sym::IdNormal('∞')),
+    ("infty", Sym::IdNormal('∞')),
+    ("partial", Sym::Op('∂')),
+    ("nabla", Sym::Op('∇')),
+    ("exists", Sym::Op('∃')),
+    ("notexists", Sym::Op('∄')),
+    ("forall", Sym::Op('∀')),
+    ("emptyset", Sym::IdNormal('∅')),
+    ("aleph", Sym::IdNormal('ℵ')),
+    ("Re", Sym::IdNormal('ℜ')),
+    ("Im", Sym::IdNormal('ℑ')),
+    ("wp", Sym::Id('℘')),
+    ("hbar", Sym::Id('ℏ')),
+    ("lambdabar", Sym::Id('ƛ')),
+    ("setN", Sym::IdNormal('ℕ')),
+    ("setZ", Sym::IdNormal('ℤ')),
+    ("setQ", Sym::IdNormal('ℚ')),
+    ("setR", Sym::IdNormal('ℝ')),
+    ("setC", Sym::IdNormal('ℂ')),
+    ("dotslow", Sym::Op('…')),
+    ("dotsaxis", Sym::Op('⋯')),
+    ("dotsvert", Sym::Op('⋮')),
+    ("dotsup", Sym::Op('⋰')),
+    ("dotsdown", Sym::Op('⋱')),
+    ("leftarrow", Sym::Op('←')),
+    ("rightarrow", Sym::Op('→')),
+    ("uparrow", Sym::Op('↑')),
+    ("downarrow", Sym::Op('↓')),
+    // Greek
+    ("%alpha", Sym::Id('α')),
+    ("%beta", Sym::Id('β')),
+    ("%gamma", Sym::Id('γ')),
+    ("%delta", Sym::Id('δ')),
+    ("%epsilon", Sym::Id('ε')),
+    ("%varepsilon", Sym::Id('ϵ')),
+    ("%zeta", Sym::Id('ζ')),
+    ("%eta", Sym::Id('η')),
+    ("%theta", Sym::Id('θ')),
+    ("%vartheta", Sym::Id('ϑ')),
+    ("%iota", Sym::Id('ι')),
+    ("%kappa", Sym::Id('κ')),
+    ("%lambda", Sym::Id('λ')),
+    ("%mu", Sym::Id('μ')),
+    ("%nu", Sym::Id('ν')),
+    ("%xi", Sym::Id('ξ')),
+    ("%omicron", Sym::Id('ο')),
+    ("%pi", Sym::Id('π')),
+    ("%varpi", Sym::Id('ϖ')),
+    ("%rho", Sym::Id('ρ')),
+    ("%varrho", Sym::Id('ϱ')),
+    ("%sigma", Sym::Id('σ')),
+    ("%varsigma", Sym::Id('ς')),
+    ("%tau", Sym::Id('τ')),
+    ("%upsilon", Sym::Id('υ')),
+    ("%phi", Sym::Id('φ')),
+    ("%varphi", Sym::Id('ϕ')),
+    ("%chi", Sym::Id('χ')),
+    ("%psi", Sym::Id('ψ')),
+    ("%omega", Sym::Id('ω')),
+    ("%GAMMA", Sym::IdNormal('Γ')),
+    ("%DELTA", Sym::IdNormal('Δ')),
+    ("%THETA", Sym::IdNormal('Θ')),
+    ("%LAMBDA", Sym::IdNormal('Λ')),
+    ("%XI", Sym::IdNormal('Ξ')),
+    ("%PI", Sym::IdNormal('Π')),
+    ("%SIGMA", Sym::IdNormal('Σ')),
+    ("%UPSILON", Sym::IdNormal('Υ')),
+    ("%PHI", Sym::IdNormal('Φ')),
+    ("%PSI", Sym::IdNormal('Ψ')),
+    ("%OMEGA", Sym::IdNormal('Ω')),
+    // Large operators and functions
+    ("sum", Sym::Large('∑')),
+    ("prod", Sym::Large('∏')),
+    ("coprod", Sym::Large('∐')),
+    ("int", Sym::Large('∫')),
+    ("iint", Sym::Large('∬')),
+    ("iiint", Sym::Large('∭')),
+    ("lint", Sym::Large('∮')),
+    ("llint", Sym::Large('∯')),
+    ("lllint", Sym::Large('∰')),
+    ("lim", Sym::Func(true)),
+    ("liminf", Sym::Func(true)),
+    ("limsup", Sym::Func(true)),
+    ("sin", Sym::Func(false)),
+    ("cos", Sym::Func(false)),
+    ("tan", Sym::Func(false)),
+    ("cot", Sym::Func(false)),
+    ("sinh", Sym::Func(false)),
+    ("cosh", Sym::Func(false)),
+    ("tanh", Sym::Func(false)),
+    ("coth", Sym::Func(false)),
+    ("arcsin", Sym::Func(false)),
+    ("arccos", Sym::Func(false)),
+    ("arctan", Sym::Func(false)),
+    ("arccot", Sym::Func(false)),
+    ("arsinh", Sym::Func(false)),
+    ("arcosh", Sym::Func(false)),
+    ("artanh", Sym::Func(false)),
+    ("arcoth", Sym::Func(false)),
+    ("ln", Sym::Func(false)),
+    ("log", Sym::Func(false)),
+    ("exp", Sym::Func(false)),
+    ("func", Sym::FuncName),
+    // Brackets
+    ("(", Sym::Open('(')),
+    (")", Sym::Close(')')),
+    ("[", Sym::Open('[')),
+    ("]", Sym::Close(']')),
+    ("lbrace", Sym::Open('{')),
+    ("rbrace", Sym::Close('}')),
+    ("langle", Sym::Open('⟨')),
+    ("rangle", Sym::Close('⟩')),
+    ("lceil", Sym::Open('⌈')),
+    ("rceil", Sym::Close('⌉')),
+    ("lfloor", Sym::Open('⌊')),
+    ("rfloor", Sym::Close('⌋')),
+    ("lline", Sym::Open('|')),
+    ("rline", Sym::Close('|')),
+    ("ldline", Sym::Open('‖')),
+    ("rdline", Sym::Close('‖')),
+    ("ldbracket", Sym::Open('⟦')),
+    ("rdbracket", Sym::Close('⟧')),
+    ("{", Sym::Group),
+    ("}", Sym::EndGroup),
+    ("left", Sym::Left),
+    ("right", Sym::Right),
+    ("none", Sym::NoDelim),
+    // Attributes
+    ("acute", Sym::Accent('´')),
+    ("grave", Sym::Accent('`')),
+    ("breve", Sym::Accent('˘')),
+    ("circle", Sym::Accent('˚')),
+    ("dot", Sym::Accent('˙')),
+    ("ddot", Sym::Accent('¨')),
+    ("bar", Sym::Accent('¯')),
+    ("vec", Sym::Accent('→')),
+    ("widevec", Sym::Accent('→')),
+    ("tilde", Sym::Accent('~')),
+    ("widetilde", Sym::Accent('~')),
+    ("hat", Sym::Accent('^')),
+    ("widehat", Sym::Accent('^')),
+    ("check", Sym::Accent('ˇ')),
+    ("overline", Sym::Line('‾', false)),
+    ("underline", Sym::Line('_', true)),
+    ("overbrace", Sym::Brace('⏞', false)),
+    ("underbrace", Sym::Brace('⏟', true)),
+    ("bold", Sym::Font("bold")),
+    ("ital", Sym::Font("ital")),
+    ("italic", Sym::Font("ital")),
+    ("nbold", Sym::Font("nbold")),
+    ("nitalic", Sym::Font("nitalic")),
+    ("font", Sym::FontName),
+    ("color", Sym::Ignore(1)),
+    ("size", Sym::Ignore(1)),
+    ("alignl", Sym::Ignore(0)),
+    ("alignc", Sym::Ignore(0)),
+    ("alignr", Sym::Ignore(0)),
+    ("nospace", Sym::Ignore(0)),
+    // Commands
+    ("sqrt", Sym::Sqrt),
+    ("nroot", Sym::NRoot),
+    ("abs", Sym::Abs),
+    ("fact", Sym::Fact),
+    ("binom", Sym::Binom),
+    ("stack", Sym::Stack),
+    ("matrix", Sym::Matrix),
+    ("phantom", Sym::Phantom),
+    ("over", Sym::Over),
+    ("^", Sym::Script(Script::Sup)),
+    ("sup", Sym::Script(Script::Sup)),
+    ("rsup", Sym::Script(Script::Sup)),
+    ("_", Sym::Script(Script::Sub)),
+    ("sub", Sym::Script(Script::Sub)),
+    ("rsub", Sym::Script(Script::Sub)),
+    ("lsup", Sym::Script(Script::LSup)),
+    ("lsub", Sym::Script(Script::LSub)),
+    ("to", Sym::Script(Script::CSup)),
+    ("csup", Sym::Script(Script::CSup)),
+    ("from", Sym::Script(Script::CSub)),
+    ("csub", Sym::Script(Script::CSub)),
+    ("##", Sym::RowSep),
+    ("#", Sym::Col),
+    ("newline", Sym::Newline),
+];
+
+/// Font attributes.
+const FONTS: &[(&str, Variant)] = &[
+    ("nitalic", Variant::Normal),
+    ("bold", Variant::Bold),
+    ("ital", Variant::Italic),
+    ("font sans", Variant::SansSerif),
+    ("font fixed", Variant::Monospace),
+];
+
+/// Font attributes applied to an already-styled term: the attribute, the
+/// term's variant, and the combined variant.
+const STYLED: &[(&str, Variant, Variant)] = &[
+    ("bold", Variant::Italic, Variant::BoldItalic),
+    ("bold", Variant::SansSerif, Variant::BoldSansSerif),
+    (
+        "bold",
+        Variant::SansSerifItalic,
+        Variant::SansSerifBoldItalic,
+    ),
+    ("ital", Variant::SansSerif, Variant::SansSerifItalic),
+    ("ital", Variant::Bold, Variant::BoldItalic),
+    ("ital", Variant::BoldSansSerif, Variant::SansSerifBoldItalic),
+];
+
+/// Widths of the `~` and `` ` `` spaces, in em.
+const BLANK: f32 = 0.5;
+const SMALL_BLANK: f32 = 0.25;
+
+/// Parse StarMath into an element. Formulas with several lines become a
+/// one-column table.
+pub fn parse(src: &str) -> Element {
+    let mut p = Parser { src, pos: 0 };
+    let mut lines = vec![Vec::new()];
+    loop {
+        let line = lines.last_mut().unwrap();
+        line.extend(p.expr_list());
+        // Unmatched closing brackets and separators are kept as operators.
+        match p.next() {
+            Tok::Eof => break,
+            Tok::Sym(_, Sym::Newline) => lines.push(Vec::new()),
+            Tok::Sym(_, Sym::Close(c)) => line.push(Element::op(c)),
+            Tok::Sym(_, Sym::EndGroup) => line.push(Element::op('}')),
+            Tok::Sym(_, Sym::Col | Sym::RowSep) => line.push(Element::op('#')),
+            Tok::Sym(_, Sym::Right) => line.extend(p.delim().map(Element::op)),
+            _ => (),
+        }
+    }
+    if lines.len() > 1 {
+        Element::matrix(lines.into_iter().map(|l| [into_elem(l)]))
+    } else {
+        into_elem(lines.pop().unwrap())
+    }
+}
+
+fn is_empty(e: &Element) -> bool {
+    matches!(e.elem(), MathElement::Row(elems) if elems.is_empty())
+}
+
+/// Apply a font attribute to an element, combining it with any existing
+/// variant.
+fn restyle(name: &str, e: Element) -> Element {
+    let current = e.attributes().and_then(|a| a.variant);
+    match name {
+        "nitalic" if current.is_none() => {
+            if let MathElement::Id { t, .. } = e.elem() {
+                if t.chars().count() == 1 {
+                    return Element::id_normal(t.as_str());
+                }
+            }
+        }
+        "nbold" => {
+            let mut e = e;
+            let v = STYLED
+                .iter()
+                .find(|(n, _, v)| *n == "bold" && Some(*v) == current)
+                .map(|(_, inner, _)| *inner);
+            if current == Some(Variant::Bold) || v.is_some() {
+                e.attributes_mut().variant = v;
+            }
+            return e;
+        }
+        "font serif" => return e,
+        _ => (),
+    }
+    let v = STYLED
+        .iter()
+        .find(|(n, inner, _)| *n == name && Some(*inner) == current)
+        .map(|(.., v)| *v)
+        .or_else(|| FONTS.iter().find(|(n, _)| *n == name).map(|(_, v)| *v));
+    match v {
+        Some(v) => e.variant(v),
+        None => e,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Tok<'a> {
+    Sym(&'a str, Sym),
+    Word(&'a str),
+    Num(&'a str),
+    Text(String),
+    /// A character escaped with a backslash.
+    Escaped(char),
+    Char(char),
+    Space(f32),
+    /// An unknown `%` name.
+    Unknown(&'a str),
+    Eof,
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+fn word_len(s: &str) -> usize {
+    s.find(|c: char| !c.is_alphanumeric()).unwrap_or(s.len())
+}
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Tok<'a> {
+        let rest = self.src[self.pos..].trim_start();
+        self.pos = self.src.len() - rest.len();
+        let Some(c) = rest.chars().next() else {
+            return Tok::Eof;
+        };
+        match c {
+            '"' => {
+                let mut t = String::new();
+                let mut len = rest.len();
+                let mut chars = rest.char_indices().skip(1);
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '"' => {
+                            len = i + 1;
+                            break;
+                        }
+                        '\\' => t.extend(chars.next().map(|(_, c)| c)),
+                        c => t.push(c),
+                    }
+                }
+                self.pos += len;
+                Tok::Text(t)
+            }
+            '~' | '`' => {
+                let len = rest.find(|c| c != '~' && c != '`').unwrap_or(rest.len());
+                self.pos += len;
+                Tok::Space(
+                    rest[..len]
+                        .chars()
+                        .map(|c| if c == '~' { BLANK } else { SMALL_BLANK })
+                        .sum(),
+                )
+            }
+            '\\' => {
+                // Escaped brackets may be written with their keyword.
+                let name = &rest[1..1 + word_len(&rest[1..])];
+                let bracket = SYMBOLS.iter().find_map(|(s, sym)| match sym {
+                    Sym::Open(c) | Sym::Close(c) if *s == name => Some(*c),
+                    _ => None,
+                });
+                if let Some(b) = bracket {
+                    self.pos += 1 + name.len();
+                    return Tok::Escaped(b);
+                }
+                match rest[1..].chars().next() {
+                    Some(c) => {
+                        self.pos += 1 + c.len_utf8();
+                        Tok::Escaped(c)
+                    }
+                    None => {
+                        self.pos += 1;
+                        Tok::Char('\\')
+                    }
+                }
+            }
+            '%' => {
+                let len = 1 + word_len(&rest[1..]);
+                self.pos += len;
+                let name = &rest[..len];
+                match SYMBOLS.iter().find(|(s, _)| *s == name) {
+                    Some(&(s, sym)) => Tok::Sym(s, sym),
+                    None => Tok::Unknown(name),
+                }
+            }
+            c if c.is_ascii_digit()
+                || (c == '.' && rest[1..].starts_with(|c: char| c.is_ascii_digit())) =>
+            {
+                let mut len = rest
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len());
+                let frac = &rest[len..];
+                if frac.starts_with('.') && frac[1..].starts_with(|c: char| c.is_ascii_digit()) {
+                    len += 1 + frac[1..]
+                        .find(|c: char| !c.is_ascii_digit())
+                        .unwrap_or(frac.len() - 1);
+                }
+                self.pos += len;
+                Tok::Num(&rest[..len])
+            }
+            c if c.is_alphabetic() => {
+                let len = word_len(rest);
+                self.pos += len;
+                let word = &rest[..len];
+                match SYMBOLS.iter().find(|(s, _)| *s == word) {
+                    Some(&(s, sym)) => Tok::Sym(s, sym),
+                    None => Tok::Word(word),
+                }
+            }
+            c => {
+                let sym = SYMBOLS
+                    .iter()
+                    .filter(|(s, _)| !s.starts_with(char::is_alphabetic) && rest.starts_with(s))
+                    .max_by_key(|(s, _)| s.len());
+                match sym {
+                    Some(&(s, sym)) => {
+                        self.pos += s.len();
+                        Tok::Sym(s, sym)
+                    }
+                    None => {
+                        self.pos += c.len_utf8();
+                        Tok::Char(c)
+                    }
+                }
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Tok<'a> {
+        let pos = self.pos;
+        let t = self.next();
+        self.pos = pos;
+        t
+    }
+
+    /// Parse terms until a closing bracket, separator, or the end of input.
+    fn expr_list(&mut self) -> Vec<Element> {
+        let mut out = Vec::new();
+        loop {
+            match self.peek() {
+                Tok::Eof => return out,
+                Tok::Sym(
+                    _,
+                    Sym::EndGroup
+                    | Sym::Close(_)
+                    | Sym::Right
+                    | Sym::Col
+                    | Sym::RowSep
+                    | Sym::Newline,
+                ) => return out,
+                Tok::Sym(_, Sym::Over) => {
+                    self.next();
+                    let num = out.pop().unwrap_or_else(|| Element::row([]));
+                    out.push(Element::frac(num, self.unit()));
+                }
+                Tok::Sym(_, Sym::Brace(c, under)) => {
+                    self.next();
+                    let base = out.pop().unwrap_or_else(|| Element::row([]));
+                    let label = self.unit();
+                    let e = match (under, is_empty(&label)) {
+                        (false, true) => Element::over(base, Element::op(c)),
+                        (false, false) => Element::over(Element::over(base, Element::op(c)), label),
+                        (true, true) => Element::under(base, Element::op(c)),
+                        (true, false) => {
+                            Element::under(Element::under(base, Element::op(c)), label)
+                        }
+                    };
+                    out.push(e);
+                }
+                _ => out.push(self.unit()),
+            }
+        }
+    }
+
+    /// Parse a term with any scripts.
+    fn unit(&mut self) -> Element {
+        let mut base = self.term();
+        let mut scripts: [Option<Element>; 6] = Default::default();
+        while let Tok::Sym(_, Sym::Script(s)) = self.peek() {
+            // Repeated scripts apply to everything before them.
+            if scripts[s as usize].is_some() {
+                base = attach(base, std::mem::take(&mut scripts));
+            }
+            self.next();
+            scripts[s as usize] = Some(self.term());
+        }
+        attach(base, scripts)
+    }
+
+    /// Parse a single term.
+    fn term(&mut self) -> Element {
+        match self.next() {
+            Tok::Eof => Element::row([]),
+            Tok::Num(n) => Element::num(n),
+            Tok::Text(t) => Element::text(t),
+            Tok::Word(w) => Element::id(w),
+            Tok::Char(c) if c.is_alphabetic() => Element::id(c.to_string()),
+            Tok::Char(c) | Tok::Escaped(c) => Element::op(c),
+            Tok::Space(w) => Element::space(Space::width(Length::Em(w))),
+            Tok::Unknown(name) => Element::err(name),
+            Tok::Sym(s, sym) => match sym {
+                Sym::Op(c) | Sym::Large(c) => Element::op(c),
+                Sym::Id(c) => Element::id(c.to_string()),
+                Sym::IdNormal(c) => Element::id_normal(c.to_string()),
+                Sym::Func(_) => Element::id(s),
+                Sym::FuncName => match self.next() {
+                    Tok::Word(w) | Tok::Sym(w, _) => Element::id(w),
+                    _ => Element::row([]),
+                },
+                Sym::Open(c) => {
+                    let mut elems = vec![Element::op(c)];
+                    elems.extend(self.expr_list());
+                    if let Tok::Sym(_, Sym::Close(c)) = self.peek() {
+                        self.next();
+                        elems.push(Element::op(c));
+                    }
+                    Element::row(elems)
+                }
+                Sym::Group => {
+                    let inner = self.expr_list();
+                    if self.peek() == Tok::Sym("}", Sym::EndGroup) {
+                        self.next();
+                    }
+                    into_elem(inner)
+                }
+                Sym::Left => {
+                    let mut elems = Vec::new();
+                    elems.extend(self.delim().map(Element::op));
+                    elems.extend(self.expr_list());
+                    if let Tok::Sym(_, Sym::Right) = self.peek() {
+                        self.next();
+                        elems.extend(self.delim().map(Element::op));
+                    }
+                    Element::row(elems)
+                }
+                Sym::Accent(c) => Element::over_accent(self.term(), Element::op(c)),
+                Sym::Line(c, false) => Element::over(self.term(), Element::op(c)),
+                Sym::Line(c, true) => Element::under(self.term(), Element::op(c)),
+                Sym::Font(name) => restyle(name, self.term()),
+                Sym::FontName => {
+                    let name = match self.next() {
+                        Tok::Word("sans") => "font sans",
+                        Tok::Word("fixed") => "font fixed",
+                        _ => "font serif",
+                    };
+                    restyle(name, self.term())
+                }
+                Sym::Ignore(n) => {
+                    for _ in 0..n {
+                        // Relative sizes like `size +4` have a sign.
+                        if let Tok::Sym(_, Sym::Op(_)) = self.next() {
+                            self.next();
+                        }
+                    }
+                    self.term()
+                }
+                Sym::Sqrt => Element::sqrt(self.term()),
+                Sym::NRoot => {
+                    let index = self.term();
+                    Element::root(self.term(), index)
+                }
+                Sym::Abs => {
+                    let mut elems = vec![Element::op('|')];
+                    elems.extend(into_vec(self.term()));
+                    elems.push(Element::op('|'));
+                    Element::row(elems)
+                }
+                Sym::Fact => {
+                    let mut elems = into_vec(self.term());
+                    elems.push(Element::op('!'));
+                    Element::row(elems)
+                }
+                Sym::Binom => {
+                    let top = self.term();
+                    Element::frac_thickness(top, self.term(), 0.0)
+                }
+                Sym::Stack => self.table(false),
+                Sym::Matrix => self.table(true),
+                Sym::Phantom => Element::phantom(into_vec(self.term())),
+                Sym::Script(Script::Sup) => Element::op('^'),
+                Sym::Script(Script::Sub) => Element::op('_'),
+                Sym::Close(c) => Element::op(c),
+                _ => Element::row([]),
+            },
+        }
+    }
+
+    /// Parse the delimiter after `left` or `right`.
+    fn delim(&mut self) -> Option<char> {
+        match self.next() {
+            Tok::Sym(_, Sym::Open(c) | Sym::Close(c) | Sym::Op(c)) => Some(c),
+            Tok::Sym(_, Sym::Group) => Some('{'),
+            Tok::Sym(_, Sym::EndGroup) => Some('}'),
+            Tok::Escaped(c) | Tok::Char(c) => Some(c),
+            _ => None,
+        }
+    }
+
+    /// Parse the body of a `stack` or `matrix`. Stacks separate rows with `#`,
+    /// while matrices separate cells with `#` and rows with `##`.
+    fn table(&mut self, matrix: bool) -> Element {
+        if self.peek() != Tok::Sym("{", Sym::Group) {
+            return Element::matrix([[self.term()]]);
+        }
+        self.next();
+        let mut rows = vec![Vec::new()];
+        loop {
+            let cell = TableCell::new(self.expr_list());
+            rows.last_mut().unwrap().push(cell);
+            match self.next() {
+                Tok::Sym(_, Sym::Col) if matrix => (),
+                Tok::Sym(_, Sym::Col | Sym::RowSep | Sym::Newline) => rows.push(Vec::new()),
+                _ => break,
+            }
+        }
+        Element::table(rows.into_iter().map(TableRow::new))
+    }
+}
+
+/// Attach scripts to a base element, in the order of the [`Script`] enum.
+fn attach(base: Element, scripts: [Option<Element>; 6]) -> Element {
+    let [sub, sup, lsub, lsup, csub, csup] = scripts;
+    let empty = || Element::row([]);
+    let e = match (sub, sup, lsub, lsup) {
+        (sub, sup, None, None) => match (sub, sup) {
+            (None, None) => base,
+            (Some(sub), None) => Element::sub(base, sub),
+            (None, Some(sup)) => Element::sup(base, sup),
+            (Some(sub), Some(sup)) => Element::sub_sup(base, sub, sup),
+        },
+        (sub, sup, lsub, lsup) => {
+            let post = (sub.is_some() || sup.is_some())
+                .then(|| Pair::new(sub.unwrap_or_else(empty), sup.unwrap_or_else(empty)));
+            let pre = Pair::new(lsub.unwrap_or_else(empty), lsup.unwrap_or_else(empty));
+            Element::multiscript(base, post, [pre])
+        }
+    };
+    match (csub, csup) {
+        (None, None) => e,
+        (Some(under), None) => Element::under(e, under),
+        (None, Some(over)) => Element::over(e, over),
+        (Some(under), Some(over)) => Element::under_over(e, under, over),
+    }
+}
+
+impl Element {
+    /// Write the element out as StarMath. Elements with no StarMath
+    /// equivalent, like padding and most variants, are approximated.
+    pub fn to_starmath(&self) -> String {
+        let mut w = Writer::default();
+        match self.elem() {
+            // A one-column table at the top level is a multi-line formula.
+            MathElement::Table { rows }
+                if self.attributes().is_none()
+                    && rows.len() > 1
+                    && rows.iter().all(|r| r.cells.len() == 1) =>
+            {
+                for (i, row) in rows.iter().enumerate() {
+                    if i > 0 {
+                        w.push("newline");
+                    }
+                    row.cells[0].elems.iter().for_each(|e| w.items(e));
+                }
+            }
+            _ => w.items(self),
+        }
+        w.out
+    }
+}
+
+/// Find the StarMath keyword for a symbol.
+fn lookup(f: impl Fn(Sym) -> bool) -> Option<&'static str> {
+    SYMBOLS.iter().find(|(_, sym)| f(*sym)).map(|(s, _)| *s)
+}
+
+fn font(e: &Element) -> Option<Vec<&'static str>> {
+    font_names(e.attributes()?.variant?, FONTS, STYLED)
+}
+
+/// Get the brackets around a row, if it starts and ends with them.
+fn brackets(elems: &[Element]) -> Option<(&'static str, &'static str)> {
+    let open = op_char(elems.first()?)?;
+    let close = op_char(elems.last()?)?;
+    let open = lookup(|s| s == Sym::Open(open))?;
+    let close = lookup(|s| s == Sym::Close(close))?;
+    (elems.len() >= 2).then_some((open, close))
+}
+
+/// Check if a row is `abs` applied to its contents.
+fn is_abs(elems: &[Element]) -> bool {
+    elems.len() >= 2
+        && op_char(&elems[0]) == Some('|')
+        && op_char(&elems[elems.len() - 1]) == Some('|')
+}
+
+/// Get the attribute keyword for an over- or underscript.
+fn attribute(e: &Element) -> Option<&'static str> {
+    let (mark, accent, under) = match e.elem() {
+        MathElement::Over { over, accent, .. } => (over, *accent, false),
+        MathElement::Under {
+            under,
+            accent_under,
+            ..
+        } => (under, *accent_under, true),
+        _ => return None,
+    };
+    let c = op_char(mark)?;
+    match (accent, under) {
+        (true, false) => lookup(|s| s == Sym::Accent(c)),
+        (false, _) => lookup(|s| s == Sym::Line(c, under)),
+        (true, true) => None,
+    }
+}
+
+/// Get the brace keyword and base for an over- or underscript, if it's a
+/// brace. The label is the outer script, if any.
+fn brace(e: &Element) -> Option<(&'static str, &Element, Option<&Element>)> {
+    let (base, mark, accent, under) = match e.elem() {
+        MathElement::Over { base, over, accent } => (base, over, *accent, false),
+        MathElement::Under {
+            base,
+            under,
+            accent_under,
+        } => (base, under, *accent_under, true),
+        _ => return None,
+    };
+    if accent {
+        return None;
+    }
+    if let Some(name) = op_char(mark).and_then(|c| lookup(|s| s == Sym::Brace(c, under))) {
+        return Some((name, base, None));
+    }
+    let (name, inner, None) = brace(base)? else {
+        return None;
+    };
+    (matches!(base.elem(), MathElement::Under { .. }) == under).then_some((name, inner, Some(mark)))
+}
+
+/// Check if scripts on this element are written with `from` and `to`.
+fn takes_limits(e: &Element) -> bool {
+    match e.elem() {
+        MathElement::Id { t, .. } => SYMBOLS.iter().any(|(n, s)| n == t && *s == Sym::Func(true)),
+        _ => op_char(e).is_some_and(|c| lookup(|s| s == Sym::Large(c)).is_some()),
+    }
+}
+
+/// Check if the element is written as a single StarMath term.
+fn is_term(e: &Element) -> bool {
+    if font(e).is_some() {
+        return true;
+    }
+    match e.elem() {
+        MathElement::Frac {
+            line_thickness: Some(t),
+            ..
+        } => *t == 0.0,
+        MathElement::Frac { .. } | MathElement::UnderOver { .. } => false,
+        MathElement::Sup { .. } | MathElement::Sub { .. } | MathElement::SubSup { .. } => false,
+        MathElement::MultiScript { .. } => false,
+        MathElement::Over { .. } | MathElement::Under { .. } => attribute(e).is_some(),
+        MathElement::Row(elems) => brackets(elems).is_some() || is_abs(elems),
+        MathElement::Padding(_) => false,
+        _ => true,
+    }
+}
+
+/// Check if the element is a term followed by scripts.
+fn is_scripted(e: &Element) -> bool {
+    matches!(
+        e.elem(),
+        MathElement::Sup { .. }
+            | MathElement::Sub { .. }
+            | MathElement::SubSup { .. }
+            | MathElement::MultiScript { .. }
+            | MathElement::Over { .. }
+            | MathElement::Under { .. }
+            | MathElement::UnderOver { .. }
+    ) && brace(e).is_none()
+}
+
+#[derive(Default)]
+struct Writer {
+    out: String,
+    /// The last token written.
+    last: String,
+}
+
+impl Writer {
+    /// Write a token. Tokens are separated by spaces, except around scripts
+    /// and inside brackets and braces.
+    fn push(&mut self, s: &str) {
+        let glued = self.out.is_empty()
+            || ["{", "(", "[", "^", "_"].contains(&self.last.as_str())
+            || ["}", ")", "]", "^", "_"].contains(&s)
+            || (s == "(" && self.last.chars().count() == 1 && self.last != "-");
+        if !glued {
+            self.out.push(' ');
+        }
+        self.out.push_str(s);
+        self.last = s.to_string();
+    }
+
+    /// Write the contents of an element, without any grouping.
+    fn items(&mut self, e: &Element) {
+        match e.elem() {
+            MathElement::Row(elems)
+                if font(e).is_none() && brackets(elems).is_none() && !is_abs(elems) =>
+            {
+                elems.iter().for_each(|e| self.element(e))
+            }
+            _ => self.element(e),
+        }
+    }
+
+    /// Write a term, grouping it in braces if needed. Fraction parts may also
+    /// have scripts.
+    fn term(&mut self, e: &Element, scripts: bool) {
+        if is_term(e) || (scripts && is_scripted(e)) {
+            self.element(e);
+        } else {
+            self.push("{");
+            self.items(e);
+            self.push("}");
+        }
+    }
+
+    fn script(&mut self, name: &str, e: &Element) {
+        if !is_empty(e) {
+            self.push(name);
+            self.term(e, false);
+        }
+    }
+
+    fn element(&mut self, e: &Element) {
+        if let Some(names) = font(e) {
+            let mut inner = e.clone();
+            inner.attributes_mut().variant = None;
+            names.iter().for_each(|n| self.push(n));
+            self.term(&inner, false);
+            return;
+        }
+        match e.elem() {
+            MathElement::Op(_) | MathElement::Oper(_) | MathElement::ResolvedOper(_) => {
+                let c = op_char(e).unwrap();
+                match lookup(|s| matches!(s, Sym::Op(t) | Sym::Large(t) if t == c)) {
+                    Some(s) => self.push(s),
+                    None if "(){}[]#^_%~`\"\\".contains(c) => self.push(&format!("\\{}", c)),
+                    None => self.push(&c.to_string()),
+                }
+            }
+            MathElement::Id { t, normal } => {
+                let mut chars = t.chars();
+                let single = match (chars.next(), chars.next()) {
+                    (Some(c), None) => Some(c),
+                    _ => None,
+                };
+                let name = single
+                    .and_then(|c| lookup(|s| matches!(s, Sym::Id(n) | Sym::IdNormal(n) if n == c)));
+                let is_word = t.starts_with(char::is_alphabetic) && word_len(t) == t.len();
+                if let Some(name) = name {
+                    self.push(name);
+                    return;
+                }
+                match SYMBOLS.iter().find(|(n, _)| n == t) {
+                    Some((_, Sym::Func(_))) => self.push(t),
+                    Some(_) => {
+                        self.push("func");
+                        self.push(t);
+                    }
+                    None if is_word && single.is_some() && *normal => {
+                        self.push("nitalic");
+                        self.push(t);
+                    }
+                    None if is_word => self.push(t),
+                    None => self.text(t),
+                }
+            }
+            MathElement::Num(t) => self.push(t),
+            MathElement::Text(t) | MathElement::Str(t) => self.text(t),
+            MathElement::Err(t) | MathElement::Unknown { tag: t, .. } => {
+                self.push("color red");
+                self.text(t);
+            }
+            MathElement::Space(s) => {
+                let w = match s.width {
+                    Some(Length::Em(w)) => w,
+                    _ => SMALL_BLANK,
+                };
+                let blanks = (w / BLANK).floor() as usize;
+                let small = ((w - blanks as f32 * BLANK) / SMALL_BLANK).round() as usize;
+                let small = if blanks + small == 0 { 1 } else { small };
+                self.push(&format!("{}{}", "~".repeat(blanks), "`".repeat(small)));
+            }
+            MathElement::Row(elems) => {
+                if is_abs(elems) {
+                    self.push("abs");
+                    self.term(&Element::row(elems[1..elems.len() - 1].to_vec()), false);
+                } else if let Some((open, close)) = brackets(elems) {
+                    self.push(open);
+                    elems[1..elems.len() - 1]
+                        .iter()
+                        .for_each(|e| self.element(e));
+                    self.push(close);
+                } else {
+                    self.push("{");
+                    elems.iter().for_each(|e| self.element(e));
+                    self.push("}");
+                }
+            }
+            MathElement::Phantom(elems) => {
+                self.push("phantom");
+                self.term(&Element::row(elems.clone()), false);
+            }
+            MathElement::Padding(Padding { elems, .. }) => {
+                self.push("{");
+                elems.iter().for_each(|e| self.element(e));
+                self.push("}");
+            }
+            MathElement::Frac {
+                num,
+                den,
+                line_thickness,
+            } => {
+                if *line_thickness == Some(0.0) {
+                    self.push("binom");
+                    self.term(num, false);
+                    self.term(den, false);
+                } else {
+                    self.term(num, true);
+                    self.push("over");
+                    self.term(den, true);
+                }
+            }
+            MathElement::Sqrt(base) => {
+                self.push("sqrt");
+                self.term(base, false);
+            }
+            MathElement::Root { base, index } => {
+                self.push("nroot");
+                self.term(index, false);
+                self.term(base, false);
+            }
+            MathElement::Sup { base, sup } => {
+                self.term(base, false);
+                self.script("^", sup);
+            }
+            MathElement::Sub { base, sub } => {
+                self.term(base, false);
+                self.script("_", sub);
+            }
+            MathElement::SubSup { base, sub, sup } => {
+                self.term(base, false);
+                self.script("_", sub);
+                self.script("^", sup);
+            }
+            MathElement::Over { base, over, .. } => {
+                if let Some((name, base, label)) = brace(e) {
+                    self.term(base, true);
+                    self.push(name);
+                    match label {
+                        Some(l) => self.term(l, false),
+                        None => self.push("{}"),
+                    }
+                } else if let Some(name) = attribute(e) {
+                    self.push(name);
+                    self.term(base, false);
+                } else {
+                    self.limits(base, None, Some(over));
+                }
+            }
+            MathElement::Under { base, under, .. } => {
+                if let Some((name, base, label)) = brace(e) {
+                    self.term(base, true);
+                    self.push(name);
+                    match label {
+                        Some(l) => self.term(l, false),
+                        None => self.push("{}"),
+                    }
+                } else if let Some(name) = attribute(e) {
+                    self.push(name);
+                    self.term(base, false);
+                } else {
+                    self.limits(base, Some(under), None);
+                }
+            }
+            MathElement::UnderOver {
+                base, under, over, ..
+            } => self.limits(base, Some(under), Some(over)),
+            MathElement::MultiScript { base, post, pre } => {
+                // Extra pairs of scripts are attached to a braced group.
+                let depth = post.len().max(pre.len()).max(1);
+                (1..depth).for_each(|_| self.push("{"));
+                self.term(base, false);
+                for i in 0..depth {
+                    if i > 0 {
+                        self.push("}");
+                    }
+                    if let Some(p) = pre.get(i) {
+                        self.script("lsub", &p.sub);
+                        self.script("lsup", &p.sup);
+                    }
+                    if let Some(p) = post.get(i) {
+                        self.script("_", &p.sub);
+                        self.script("^", &p.sup);
+                    }
+                }
+            }
+            MathElement::Table { rows } => {
+                let cols = rows.iter().map(|r| r.cells.len()).max().unwrap_or(0);
+                let stack = cols == 1;
+                self.push(if stack { "stack" } else { "matrix" });
+                self.push("{");
+                for (i, row) in rows.iter().enumerate() {
+                    if i > 0 {
+                        self.push(if stack { "#" } else { "##" });
+                    }
+                    for (j, cell) in row.cells.iter().enumerate() {
+                        if j > 0 {
+                            self.push("#");
+                        }
+                        cell.elems.iter().for_each(|e| self.element(e));
+                    }
+                }
+                self.push("}");
+            }
+        }
+    }
+
+    /// Write scripts placed above and below a base.
+    fn limits(&mut self, base: &Element, under: Option<&Element>, over: Option<&Element>) {
+        self.term(base, false);
+        let (from, to) = if takes_limits(base) {
+            ("from", "to")
+        } else {
+            ("csub", "csup")
+        };
+        if let Some(under) = under {
+            self.push(from);
+            self.term(under, false);
+        }
+        if let Some(over) = over {
+            self.push(to);
+            self.term(over, false);
+        }
+    }
+
+    fn text(&mut self, t: &str) {
+        self.push(&format!(
+            "\"{}\"",
+            t.replace('\\', "\\\\").replace('"', "\\\"")
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(s: &str) -> Element {
+        Element::row(s.chars().map(|c| match c {
+            '=' | '+' => Element::op(c),
+            c if c.is_ascii_digit() => Element::num(c.to_string()),
+            c => Element::id(c.to_string()),
+        }))
+    }
+
+    #[test]
+    fn fractions_and_roots() {
+        assert_eq!(
+            parse("{a + b} over c"),
+            Element::frac(ids("a+b"), Element::id("c"))
+        );
+        assert_eq!(
+            parse("x^2 over y"),
+            Element::frac(
+                Element::sup(Element::id("x"), Element::num("2")),
+                Element::id("y")
+            )
+        );
+        assert_eq!(
+            parse("nroot{3}{x + 1} sqrt x"),
+            Element::row([
+                Element::root(ids("x+1"), Element::num("3")),
+                Element::sqrt(Element::id("x")),
+            ])
+        );
+        assert_eq!(
+            parse("binom n k"),
+            Element::frac_thickness(Element::id("n"), Element::id("k"), 0.0)
+        );
+    }
+
+    #[test]
+    fn scripts_and_limits() {
+        assert_eq!(
+            parse("sum from{i=1} to{n} i^2"),
+            Element::row([
+                Element::under_over(Element::op('∑'), ids("i=1"), Element::id("n")),
+                Element::sup(Element::id("i"), Element::num("2")),
+            ])
+        );
+        assert_eq!(
+            parse("lim from {x toward 0} f(x)"),
+            Element::row([
+                Element::under(
+                    Element::id("lim"),
+                    Element::row([Element::id("x"), Element::op('→'), Element::num("0")])
+                ),
+                Element::id("f"),
+                Element::row([Element::op('('), Element::id("x"), Element::op(')')]),
+            ])
+        );
+        assert_eq!(
+            parse("x_1 sup 2 lsub a"),
+            Element::multiscript(
+                Element::id("x"),
+                [Pair::new(Element::num("1"), Element::num("2"))],
+                [Pair::new(Element::id("a"), Element::row([]))]
+            )
+        );
+    }
+
+    #[test]
+    fn attributes() {
+        assert_eq!(
+            parse("bold x ital y font sans z bold font sans w nitalic d"),
+            Element::row([
+                Element::id("x").variant(Variant::Bold),
+                Element::id("y").variant(Variant::Italic),
+                Element::id("z").variant(Variant::SansSerif),
+                Element::id("w").variant(Variant::BoldSansSerif),
+                Element::id_normal("d"),
+            ])
+        );
+        assert_eq!(
+            parse("hat x overline y color red z"),
+            Element::row([
+                Element::over_accent(Element::id("x"), Element::op('^')),
+                Element::over(Element::id("y"), Element::op('‾')),
+                Element::id("z"),
+            ])
+        );
+        assert_eq!(
+            parse("{a + b} underbrace {n}"),
+            Element::under(
+                Element::under(ids("a+b"), Element::op('⏟')),
+                Element::id("n")
+            )
+        );
+    }
+
+    #[test]
+    fn brackets_and_matrices() {
+        assert_eq!(
+            parse("left( matrix{a # b ## c # d} right)"),
+            Element::row([
+                Element::op('('),
+                Element::matrix([
+                    [Element::id("a"), Element::id("b")],
+                    [Element::id("c"), Element::id("d")],
+                ]),
+                Element::op(')'),
+            ])
+        );
+        assert_eq!(
+            parse("stack{1 # 2}"),
+            Element::matrix([[Element::num("1")], [Element::num("2")]])
+        );
+        assert_eq!(
+            parse("lbrace x rbrace abs{y} %alpha %GAMMA %foo )"),
+            Element::row([
+                Element::row([Element::op('{'), Element::id("x"), Element::op('}')]),
+                Element::row([Element::op('|'), Element::id("y"), Element::op('|')]),
+                Element::id("α"),
+                Element::id_normal("Γ"),
+                Element::err("%foo"),
+                Element::op(')'),
+            ])
+        );
+        assert_eq!(
+            parse("a = b newline c = d"),
+            Element::matrix([[ids("a=b")], [ids("c=d")]])
+        );
+    }
+
+    #[test]
+    fn write() {
+        let e = Element::row([
+            Element::op('∫'),
+            Element::frac(ids("a+b"), Element::id("c")),
+            Element::op('−'),
+            Element::sqrt(Element::id("α")),
+        ]);
+        assert_eq!(e.to_starmath(), "int {a + b} over c - sqrt %alpha");
+        assert_eq!(
+            Element::under_over(Element::op('∑'), ids("i=1"), Element::id("n")).to_starmath(),
+            "sum from {i = 1} to n"
+        );
+        assert_eq!(
+            Element::over(Element::id("x"), Element::id("y")).to_starmath(),
+            "x csup y"
+        );
+        assert_eq!(Element::id("over").to_starmath(), "func over");
+    }
+
+    #[test]
+    fn starmath_roundtrip() {
+        for src in [
+            "sum from{i=1} to{n} i^2",
+            "{a + b} over {c - d} = x^{2 n} over y_1",
+            "sqrt{x^2 + y^2} + nroot{3}{x + 1}",
+            "lim from{x toward 0} {sin x} over x = 1",
+            "int from 0 to 1 f(x) dx",
+            "left( matrix{a # b ## c # d} right) stack{1 # 2 # 3}",
+            "left[ matrix{1 # 0 ## 0 # 1} right]",
+            "bold x + ital y + font sans z + bold ital font sans w + nitalic e",
+            "hat x vec v {a + b} overbrace n overline z underline w",
+            "abs{x} <= 1 ~ forall x in setR",
+            "binom n k lsup a lsub b C _c ^d",
+            "%alpha + %GAMMA + infinity + \"text\" + 3.14",
+            "lbrace x rbrace langle a rangle \\{ \\( x csub y",
+            "a = b newline c = d",
+            "phantom x func sin x sin x fact n",
+        ] {
+            let e = parse(src);
+            assert_eq!(parse(&e.to_starmath()), e, "{} -> {}", src, e.to_starmath());
+        }
+    }
+}
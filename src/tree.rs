@@ -0,0 +1,81 @@
+//! Small helpers for building and taking apart element trees, shared by the
+//! format readers and writers.
+
+use crate::math::{Element, MathElement, Variant};
+
+/// Turn a list of elements into a single element, using a row if needed.
+pub(crate) fn into_elem(mut elems: Vec<Element>) -> Element {
+    if elems.len() == 1 {
+        elems.pop().unwrap()
+    } else {
+        Element::row(elems)
+    }
+}
+
+/// Unwrap a plain row into its elements.
+pub(crate) fn into_vec(e: Element) -> Vec<Element> {
+    match e.into_parts() {
+        (MathElement::Row(elems), None) => elems,
+        (e, Some(a)) => vec![Element::with_attributes(e, a)],
+        (e, None) => vec![Element::new(e)],
+    }
+}
+
+/// Get the character of an operator element.
+pub(crate) fn op_char(e: &Element) -> Option<char> {
+    match e.elem() {
+        MathElement::Op(c) => Some(*c),
+        MathElement::Oper(op) => Some(op.t),
+        MathElement::ResolvedOper(op) => Some(op.t),
+        _ => None,
+    }
+}
+
+/// Separate elements with commas.
+pub(crate) fn list(elems: impl IntoIterator<Item = Element>) -> Vec<Element> {
+    let mut out = Vec::new();
+    for (i, e) in elems.into_iter().enumerate() {
+        if i > 0 {
+            out.push(Element::op(','));
+        }
+        out.push(e);
+    }
+    out
+}
+
+/// Put elements in a row between a pair of delimiters, either of which may
+/// be left out.
+pub(crate) fn fenced(open: Option<char>, inner: Vec<Element>, close: Option<char>) -> Element {
+    let mut elems = Vec::new();
+    elems.extend(open.map(Element::op));
+    elems.extend(inner);
+    elems.extend(close.map(Element::op));
+    Element::row(elems)
+}
+
+/// Add an operand to a row, splicing it in unless it needs parentheses.
+pub(crate) fn splice(elems: &mut Vec<Element>, e: Element, prec: u8, min: u8) {
+    if prec >= min {
+        elems.extend(into_vec(e));
+    } else {
+        elems.push(fenced(Some('('), into_vec(e), Some(')')));
+    }
+}
+
+/// Get the font names for a variant, outermost first. `fonts` names the
+/// plain variants, and `styled` the ones made by applying a font to an
+/// already-styled term: the font, the term's variant, and the combined
+/// variant.
+pub(crate) fn font_names(
+    v: Variant,
+    fonts: &[(&'static str, Variant)],
+    styled: &[(&'static str, Variant, Variant)],
+) -> Option<Vec<&'static str>> {
+    if let Some((n, _)) = fonts.iter().find(|(_, f)| *f == v) {
+        return Some(vec![n]);
+    }
+    let (n, inner, _) = styled.iter().find(|(.., f)| *f == v)?;
+    let mut names = vec![*n];
+    names.extend(font_names(*inner, fonts, styled)?);
+    Some(names)
+}
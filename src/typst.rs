@@ -0,0 +1,1883 @@
+//! Conversion between Typst math markup and fog-math elements.
+//!
+//! Parsing is forgiving: unknown names and code expressions become
+//! [`MathElement::Err`] nodes, unmatched brackets become plain operators, and
+//! the rest of the input is still converted. The surrounding `$` delimiters are
+//! optional; a block equation like `$ x $` sets display style.
+
+use crate::math::*;
+use crate::tree::{font_names, into_elem, into_vec, op_char};
+
+/// How a symbol name should be converted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    /// An operator.
+    Op,
+    /// An identifier, using the default italics.
+    Id,
+    /// An upright identifier, like the uppercase Greek letters.
+    IdNormal,
+    /// A large operator. Scripts become limits if set.
+    Large(bool),
+}
+
+/// Symbol names, with their modifiers. The first entry for a character is the
+/// one used when writing.
+const SYMBOLS: &[(&str, char, Kind)] = &[
+    ("alpha", 'α', Kind::Id),
+    ("beta", 'β', Kind::Id),
+    ("gamma", 'γ', Kind::Id),
+    ("delta", 'δ', Kind::Id),
+    ("epsilon", 'ε', Kind::Id),
+    ("epsilon.alt", 'ϵ', Kind::Id),
+    ("zeta", 'ζ', Kind::Id),
+    ("eta", 'η', Kind::Id),
+    ("theta", 'θ', Kind::Id),
+    ("theta.alt", 'ϑ', Kind::Id),
+    ("iota", 'ι', Kind::Id),
+    ("kappa", 'κ', Kind::Id),
+    ("lambda", 'λ', Kind::Id),
+    ("mu", 'μ', Kind::Id),
+    ("nu", 'ν', Kind::Id),
+    ("xi", 'ξ', Kind::Id),
+    ("omicron", 'ο', Kind::Id),
+    ("pi", 'π', Kind::Id),
+    ("pi.alt", 'ϖ', Kind::Id),
+    ("rho", 'ρ', Kind::Id),
+    ("rho.alt", 'ϱ', Kind::Id),
+    ("sigma", 'σ', Kind::Id),
+    ("sigma.alt", 'ς', Kind::Id),
+    ("tau", 'τ', Kind::Id),
+    ("upsilon", 'υ', Kind::Id),
+    ("phi", 'φ', Kind::Id),
+    ("phi.alt", 'ϕ', Kind::Id),
+    ("chi", 'χ', Kind::Id),
+    ("psi", 'ψ', Kind::Id),
+    ("omega", 'ω', Kind::Id),
+    ("Gamma", 'Γ', Kind::IdNormal),
+    ("Delta", 'Δ', Kind::IdNormal),
+    ("Theta", 'Θ', Kind::IdNormal),
+    ("Lambda", 'Λ', Kind::IdNormal),
+    ("Xi", 'Ξ', Kind::IdNormal),
+    ("Pi", 'Π', Kind::IdNormal),
+    ("Sigma", 'Σ', Kind::IdNormal),
+    ("Upsilon", 'Υ', Kind::IdNormal),
+    ("Phi", 'Φ', Kind::IdNormal),
+    ("Psi", 'Ψ', Kind::IdNormal),
+    ("Omega", 'Ω', Kind::IdNormal),
+    ("NN", 'ℕ', Kind::IdNormal),
+    ("ZZ", 'ℤ', Kind::IdNormal),
+    ("QQ", 'ℚ', Kind::IdNormal),
+    ("RR", 'ℝ', Kind::IdNormal),
+    ("CC", 'ℂ', Kind::IdNormal),
+    ("infinity", '∞', Kind::IdNormal),
+    ("oo", '∞', Kind::IdNormal),
+    ("emptyset", '∅', Kind::IdNormal),
+    ("aleph", 'ℵ', Kind::IdNormal),
+    ("ell", 'ℓ', Kind::Id),
+    ("planck.reduce", 'ℏ', Kind::Id),
+    ("partial", '∂', Kind::Op),
+    ("nabla", '∇', Kind::Op),
+    ("prime", '′', Kind::Op),
+    ("prime.double", '″', Kind::Op),
+    ("plus.minus", '±', Kind::Op),
+    ("minus.plus", '∓', Kind::Op),
+    ("times", '×', Kind::Op),
+    ("div", '÷', Kind::Op),
+    ("dot.op", '⋅', Kind::Op),
+    ("dot.c", '·', Kind::Op),
+    ("star.op", '⋆', Kind::Op),
+    ("circle.small", '∘', Kind::Op),
+    ("plus.circle", '⊕', Kind::Op),
+    ("times.circle", '⊗', Kind::Op),
+    ("and", '∧', Kind::Op),
+    ("or", '∨', Kind::Op),
+    ("not", '¬', Kind::Op),
+    ("union", '∪', Kind::Op),
+    ("sect", '∩', Kind::Op),
+    ("without", '∖', Kind::Op),
+    ("eq.not", '≠', Kind::Op),
+    ("lt.eq", '≤', Kind::Op),
+    ("gt.eq", '≥', Kind::Op),
+    ("lt.double", '≪', Kind::Op),
+    ("gt.double", '≫', Kind::Op),
+    ("approx", '≈', Kind::Op),
+    ("equiv", '≡', Kind::Op),
+    ("tilde.op", '∼', Kind::Op),
+    ("tilde.equiv", '≅', Kind::Op),
+    ("prop", '∝', Kind::Op),
+    ("prec", '≺', Kind::Op),
+    ("succ", '≻', Kind::Op),
+    ("in", '∈', Kind::Op),
+    ("in.not", '∉', Kind::Op),
+    ("in.rev", '∋', Kind::Op),
+    ("subset", '⊂', Kind::Op),
+    ("supset", '⊃', Kind::Op),
+    ("subset.eq", '⊆', Kind::Op),
+    ("supset.eq", '⊇', Kind::Op),
+    ("perp", '⟂', Kind::Op),
+    ("parallel", '∥', Kind::Op),
+    ("divides", '∣', Kind::Op),
+    ("colon.eq", '≔', Kind::Op),
+    ("arrow.r", '→', Kind::Op),
+    ("arrow.l", '←', Kind::Op),
+    ("arrow.l.r", '↔', Kind::Op),
+    ("arrow.t", '↑', Kind::Op),
+    ("arrow.b", '↓', Kind::Op),
+    ("arrow.r.double", '⇒', Kind::Op),
+    ("arrow.l.double", '⇐', Kind::Op),
+    ("arrow.l.r.double", '⇔', Kind::Op),
+    ("arrow.r.bar", '↦', Kind::Op),
+    ("arrow.r.long", '⟶', Kind::Op),
+    ("arrow.r.squiggly", '⇝', Kind::Op),
+    ("forall", '∀', Kind::Op),
+    ("exists", '∃', Kind::Op),
+    ("exists.not", '∄', Kind::Op),
+    ("top", '⊤', Kind::Op),
+    ("bot", '⊥', Kind::Op),
+    ("tack.r", '⊢', Kind::Op),
+    ("therefore", '∴', Kind::Op),
+    ("because", '∵', Kind::Op),
+    ("dots.h", '…', Kind::Op),
+    ("dots.c", '⋯', Kind::Op),
+    ("dots.v", '⋮', Kind::Op),
+    ("dots.down", '⋱', Kind::Op),
+    ("angle", '∠', Kind::Op),
+    ("degree", '°', Kind::Op),
+    ("angle.l", '⟨', Kind::Op),
+    ("angle.r", '⟩', Kind::Op),
+    ("bar.v.double", '‖', Kind::Op),
+    ("floor.l", '⌊', Kind::Op),
+    ("floor.r", '⌋', Kind::Op),
+    ("ceil.l", '⌈', Kind::Op),
+    ("ceil.r", '⌉', Kind::Op),
+    ("sum", '∑', Kind::Large(true)),
+    ("product", '∏', Kind::Large(true)),
+    ("product.co", '∐', Kind::Large(true)),
+    ("union.big", '⋃', Kind::Large(true)),
+    ("sect.big", '⋂', Kind::Large(true)),
+    ("and.big", '⋀', Kind::Large(true)),
+    ("or.big", '⋁', Kind::Large(true)),
+    ("plus.circle.big", '⨁', Kind::Large(true)),
+    ("times.circle.big", '⨂', Kind::Large(true)),
+    ("integral", '∫', Kind::Large(false)),
+    ("integral.double", '∬', Kind::Large(false)),
+    ("integral.triple", '∭', Kind::Large(false)),
+    ("integral.cont", '∮', Kind::Large(false)),
+];
+
+/// Shorthand operator sequences. These are preferred over symbol names when
+/// writing.
+const SHORTHANDS: &[(&str, char)] = &[
+    ("-", '−'),
+    ("*", '∗'),
+    ("->", '→'),
+    ("<-", '←'),
+    ("<->", '↔'),
+    ("=>", '⇒'),
+    ("<=>", '⇔'),
+    ("|->", '↦'),
+    ("<=", '≤'),
+    (">=", '≥'),
+    ("!=", '≠'),
+    (":=", '≔'),
+    ("<<", '≪'),
+    (">>", '≫'),
+    ("...", '…'),
+];
+
+/// Text operators, and whether they take scripts as limits.
+const TEXT_OPS: &[(&str, bool)] = &[
+    ("arccos", false),
+    ("arcsin", false),
+    ("arctan", false),
+    ("arg", false),
+    ("cos", false),
+    ("cosh", false),
+    ("cot", false),
+    ("coth", false),
+    ("csc", false),
+    ("csch", false),
+    ("deg", false),
+    ("det", true),
+    ("dim", false),
+    ("exp", false),
+    ("gcd", true),
+    ("hom", false),
+    ("inf", true),
+    ("ker", false),
+    ("lg", false),
+    ("lim", true),
+    ("liminf", true),
+    ("limsup", true),
+    ("ln", false),
+    ("log", false),
+    ("max", true),
+    ("min", true),
+    ("mod", false),
+    ("Pr", true),
+    ("sec", false),
+    ("sech", false),
+    ("sin", false),
+    ("sinh", false),
+    ("sup", true),
+    ("tan", false),
+    ("tanh", false),
+    ("tr", false),
+];
+
+/// Named spaces, in em.
+const SPACES: &[(&str, f32)] = &[
+    ("thin", 1.0 / 6.0),
+    ("med", 2.0 / 9.0),
+    ("thick", 5.0 / 18.0),
+    ("quad", 1.0),
+    ("wide", 2.0),
+];
+
+/// Accent functions, with the accent character placed over the argument.
+const ACCENTS: &[(&str, char)] = &[
+    ("grave", '`'),
+    ("acute", '´'),
+    ("hat", '^'),
+    ("tilde", '~'),
+    ("macron", '¯'),
+    ("breve", '˘'),
+    ("dot", '˙'),
+    ("dot.double", '¨'),
+    ("diaer", '¨'),
+    ("circle", '˚'),
+    ("acute.double", '˝'),
+    ("caron", 'ˇ'),
+    ("arrow", '→'),
+    ("arrow.l", '←'),
+];
+
+/// Lines and braces placed over or under an argument: the mark, whether it
+/// goes under, and whether it takes an optional label as a limit.
+const MARKS: &[(&str, char, bool, bool)] = &[
+    ("overline", '‾', false, false),
+    ("underline", '_', true, false),
+    ("overbrace", '⏞', false, true),
+    ("underbrace", '⏟', true, true),
+    ("overbracket", '⎴', false, true),
+    ("underbracket", '⎵', true, true),
+    ("overparen", '⏜', false, true),
+    ("underparen", '⏝', true, true),
+];
+
+/// Functions placing delimiters around their argument.
+const FENCES: &[(&str, char, char)] = &[
+    ("abs", '|', '|'),
+    ("norm", '‖', '‖'),
+    ("floor", '⌊', '⌋'),
+    ("ceil", '⌈', '⌉'),
+];
+
+/// Font functions.
+const FONTS: &[(&str, Variant)] = &[
+    ("upright", Variant::Normal),
+    ("italic", Variant::Italic),
+    ("bold", Variant::Bold),
+    ("bb", Variant::DoubleStruck),
+    ("cal", Variant::Script),
+    ("frak", Variant::Fraktur),
+    ("sans", Variant::SansSerif),
+    ("mono", Variant::Monospace),
+];
+
+/// Font functions applied to an already-styled argument: the function, the
+/// argument's variant, and the combined variant.
+const STYLED: &[(&str, Variant, Variant)] = &[
+    ("bold", Variant::Italic, Variant::BoldItalic),
+    ("bold", Variant::Fraktur, Variant::BoldFraktur),
+    ("bold", Variant::Script, Variant::BoldScript),
+    ("bold", Variant::SansSerif, Variant::BoldSansSerif),
+    (
+        "bold",
+        Variant::SansSerifItalic,
+        Variant::SansSerifBoldItalic,
+    ),
+    ("italic", Variant::SansSerif, Variant::SansSerifItalic),
+    ("italic", Variant::Bold, Variant::BoldItalic),
+];
+
+/// Other supported functions.
+const FUNCTIONS: &[&str] = &[
+    "frac", "binom", "sqrt", "root", "attach", "limits", "scripts", "op", "lr", "accent",
+    "display", "inline", "script", "sscript", "mat", "vec", "cases", "hide",
+];
+
+fn is_function(name: &str) -> bool {
+    FUNCTIONS.contains(&name)
+        || ACCENTS.iter().any(|(n, _)| *n == name)
+        || MARKS.iter().any(|(n, ..)| *n == name)
+        || FENCES.iter().any(|(n, ..)| *n == name)
+        || FONTS.iter().any(|(n, _)| *n == name)
+}
+
+/// Check if a name, possibly with modifiers, means anything.
+fn is_known(name: &str) -> bool {
+    is_function(name)
+        || SYMBOLS.iter().any(|(n, ..)| *n == name)
+        || TEXT_OPS.iter().any(|(n, _)| *n == name)
+        || SPACES.iter().any(|(n, _)| *n == name)
+}
+
+/// Parse Typst math markup into an element.
+pub fn parse(src: &str) -> Element {
+    let mut src = src.trim();
+    let mut block = false;
+    if let Some(inner) = src.strip_prefix('$').and_then(|s| s.strip_suffix('$')) {
+        block = inner.starts_with(char::is_whitespace) && inner.ends_with(char::is_whitespace);
+        src = inner;
+    }
+    let mut p = Parser { src, pos: 0 };
+    let nodes = p.expr(&[]);
+    let e = if nodes.iter().any(|n| n.sep.is_some()) {
+        // Line breaks and alignment points make a table, with one row per line.
+        let mut lines = vec![Vec::new()];
+        for n in nodes {
+            if n.sep == Some('\n') {
+                lines.push(Vec::new());
+            } else {
+                lines.last_mut().unwrap().push(n);
+            }
+        }
+        Element::table(lines.into_iter().map(|l| TableRow::new(cells(l))))
+    } else {
+        into_elem(elems(nodes))
+    };
+    if block {
+        e.display_style(true)
+    } else {
+        e
+    }
+}
+
+/// A parsed expression. Parenthesized groups also hold their contents, which
+/// are used without the parentheses in fractions and scripts.
+struct Node {
+    e: Element,
+    group: Option<Vec<Node>>,
+    /// Set for line breaks (`\n`) and alignment points (`&`).
+    sep: Option<char>,
+    /// Whether scripts on this should be placed as limits.
+    limits: bool,
+}
+
+impl Node {
+    fn new(e: Element) -> Self {
+        Self {
+            e,
+            group: None,
+            sep: None,
+            limits: false,
+        }
+    }
+
+    fn sep(c: char) -> Self {
+        Self {
+            sep: Some(c),
+            ..Self::new(Element::row([]))
+        }
+    }
+
+    fn limits(e: Element, limits: bool) -> Self {
+        Self {
+            limits,
+            ..Self::new(e)
+        }
+    }
+
+    /// Get the element, with any outer parentheses removed.
+    fn arg(self) -> Element {
+        match self.group {
+            Some(inner) => into_elem(elems(inner)),
+            None => self.e,
+        }
+    }
+}
+
+/// Get the elements from parsed nodes, dropping separators.
+fn elems(nodes: Vec<Node>) -> Vec<Element> {
+    nodes
+        .into_iter()
+        .filter(|n| n.sep.is_none())
+        .map(|n| n.e)
+        .collect()
+}
+
+/// Split parsed nodes into table cells at each alignment point.
+fn cells(nodes: Vec<Node>) -> Vec<TableCell> {
+    let mut cells = vec![Vec::new()];
+    for n in nodes {
+        match n.sep {
+            Some('&') => cells.push(Vec::new()),
+            Some(_) => (),
+            None => cells.last_mut().unwrap().push(n.e),
+        }
+    }
+    cells.into_iter().map(TableCell::new).collect()
+}
+
+/// A function argument. Named arguments keep their source text, as their
+/// values are usually code rather than math.
+struct Arg<'a> {
+    name: Option<&'a str>,
+    raw: &'a str,
+    nodes: Vec<Node>,
+}
+
+impl Arg<'_> {
+    fn elem(self) -> Element {
+        into_elem(elems(self.nodes))
+    }
+
+    /// Check if this is a single expression that takes limits.
+    fn limits(&self) -> bool {
+        matches!(&self.nodes[..], [n] if n.limits)
+    }
+}
+
+/// Join positional arguments back together, for functions that take a single
+/// content argument.
+fn content(args: Vec<Arg>) -> Element {
+    let mut out = Vec::new();
+    for (i, a) in args.into_iter().enumerate() {
+        if i > 0 {
+            out.push(Element::op(','));
+        }
+        out.extend(elems(a.nodes));
+    }
+    into_elem(out)
+}
+
+/// Attach scripts to a base element.
+fn scripts(base: Element, sub: Option<Element>, sup: Option<Element>, limits: bool) -> Element {
+    match (sub, sup, limits) {
+        (None, None, _) => base,
+        (Some(sub), None, false) => Element::sub(base, sub),
+        (None, Some(sup), false) => Element::sup(base, sup),
+        (Some(sub), Some(sup), false) => Element::sub_sup(base, sub, sup),
+        (Some(sub), None, true) => Element::under(base, sub),
+        (None, Some(sup), true) => Element::over(base, sup),
+        (Some(sub), Some(sup), true) => Element::under_over(base, sub, sup),
+    }
+}
+
+/// Get the closing delimiter for an opening one.
+fn closing(c: char) -> char {
+    match c {
+        '(' => ')',
+        '[' => ']',
+        '{' => '}',
+        '⟨' => '⟩',
+        '⌊' => '⌋',
+        '⌈' => '⌉',
+        c => c,
+    }
+}
+
+/// Read a `delim` argument, which is a string or `#none`.
+fn delim(raw: &str) -> Option<char> {
+    match raw.trim_matches('"') {
+        "||" => Some('‖'),
+        s if raw.starts_with('"') => s.chars().next(),
+        _ => None,
+    }
+}
+
+/// Apply a font function to an element, combining it with any existing
+/// variant.
+fn restyle(name: &str, e: Element) -> Element {
+    if name == "upright" && e.attributes().is_none() {
+        if let MathElement::Id { t, .. } = e.elem() {
+            if t.chars().count() == 1 {
+                return Element::id_normal(t.as_str());
+            }
+        }
+    }
+    let current = e.attributes().and_then(|a| a.variant);
+    let v = STYLED
+        .iter()
+        .find(|(n, inner, _)| *n == name && Some(*inner) == current)
+        .map(|(.., v)| *v)
+        .or_else(|| FONTS.iter().find(|(n, _)| *n == name).map(|(_, v)| *v));
+    match v {
+        Some(v) => e.variant(v),
+        None => e,
+    }
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    /// Skip whitespace and comments.
+    fn skip(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            if let Some(line) = trimmed.strip_prefix("//") {
+                self.pos += 2 + line.find('\n').unwrap_or(line.len());
+            } else if let Some(block) = trimmed.strip_prefix("/*") {
+                self.pos += 2 + block.find("*/").map_or(block.len(), |i| i + 2);
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip();
+        self.rest().chars().next()
+    }
+
+    /// Parse expressions until one of the stop characters or the end of input.
+    fn expr(&mut self, stops: &[char]) -> Vec<Node> {
+        let mut out: Vec<Node> = Vec::new();
+        while let Some(c) = self.peek() {
+            if stops.contains(&c) {
+                break;
+            }
+            if c == '/' {
+                self.pos += 1;
+                let num = if out.last().is_some_and(|n| n.sep.is_none()) {
+                    out.pop().unwrap().arg()
+                } else {
+                    Element::row([])
+                };
+                let den = self.unit(stops).arg();
+                out.push(Node::new(Element::frac(num, den)));
+            } else {
+                out.push(self.unit(stops));
+            }
+        }
+        out
+    }
+
+    /// Parse an atom with any attachments.
+    fn unit(&mut self, stops: &[char]) -> Node {
+        let base = self.atom(stops);
+        if base.sep.is_some() {
+            return base;
+        }
+        let mut sub = None;
+        let mut sup = None;
+        let mut primes = 0;
+        loop {
+            match self.peek() {
+                Some('_') if sub.is_none() => {
+                    self.pos += 1;
+                    sub = Some(self.atom(stops).arg());
+                }
+                Some('^') if sup.is_none() => {
+                    self.pos += 1;
+                    sup = Some(self.atom(stops).arg());
+                }
+                Some('\'') if sup.is_none() => {
+                    self.pos += 1;
+                    primes += 1;
+                }
+                _ => break,
+            }
+        }
+        if primes > 0 {
+            let prime = Element::op(match primes {
+                1 => '′',
+                2 => '″',
+                3 => '‴',
+                _ => '⁗',
+            });
+            sup = Some(match sup {
+                Some(s) => {
+                    let mut elems = vec![prime];
+                    elems.extend(into_vec(s));
+                    Element::row(elems)
+                }
+                None => prime,
+            });
+        }
+        if sub.is_none() && sup.is_none() {
+            return base;
+        }
+        Node::new(scripts(base.e, sub, sup, base.limits))
+    }
+
+    /// Parse a single atom.
+    fn atom(&mut self, stops: &[char]) -> Node {
+        let Some(c) = self.peek().filter(|c| !stops.contains(c)) else {
+            return Node::new(Element::row([]));
+        };
+        let rest = self.rest();
+        match c {
+            '"' => {
+                let mut t = String::new();
+                let mut len = rest.len();
+                let mut chars = rest.char_indices().skip(1);
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '"' => {
+                            len = i + 1;
+                            break;
+                        }
+                        '\\' => t.extend(chars.next().map(|(_, c)| c)),
+                        c => t.push(c),
+                    }
+                }
+                self.pos += len;
+                Node::new(Element::text(t))
+            }
+            '0'..='9' => {
+                let mut len = rest
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len());
+                let frac = &rest[len..];
+                if frac.starts_with('.') && frac[1..].starts_with(|c: char| c.is_ascii_digit()) {
+                    len += 1 + frac[1..]
+                        .find(|c: char| !c.is_ascii_digit())
+                        .unwrap_or(frac.len() - 1);
+                }
+                self.pos += len;
+                Node::new(Element::num(&rest[..len]))
+            }
+            '\\' => match rest[1..].chars().next() {
+                Some(c) if !c.is_whitespace() => {
+                    self.pos += 1 + c.len_utf8();
+                    Node::new(Element::op(c))
+                }
+                _ => {
+                    self.pos += 1;
+                    Node::sep('\n')
+                }
+            },
+            '&' => {
+                self.pos += 1;
+                Node::sep('&')
+            }
+            '#' => {
+                // Embedded code isn't evaluated.
+                let mut len = 1 + rest[1..]
+                    .find(|c: char| !(c.is_alphanumeric() || "_-.".contains(c)))
+                    .unwrap_or(rest.len() - 1);
+                if rest[len..].starts_with('(') {
+                    let mut depth = 0;
+                    for (i, c) in rest[len..].char_indices() {
+                        match c {
+                            '(' => depth += 1,
+                            ')' => depth -= 1,
+                            _ => (),
+                        }
+                        if depth == 0 {
+                            len += i + 1;
+                            break;
+                        }
+                    }
+                }
+                self.pos += len;
+                Node::new(Element::err(&rest[..len]))
+            }
+            '(' | '[' | '{' => {
+                self.pos += 1;
+                self.group(c)
+            }
+            '\'' => {
+                self.pos += 1;
+                Node::new(Element::op('′'))
+            }
+            c if c.is_alphabetic() => self.word(),
+            c => {
+                if let Some(&(s, c)) = SHORTHANDS
+                    .iter()
+                    .filter(|(s, _)| rest.starts_with(s))
+                    .max_by_key(|(s, _)| s.len())
+                {
+                    self.pos += s.len();
+                    return Node::new(Element::op(c));
+                }
+                self.pos += c.len_utf8();
+                Node::new(Element::op(c))
+            }
+        }
+    }
+
+    /// Parse a bracketed group, after the opening bracket. Only parentheses
+    /// are removed when the group is used as an argument.
+    fn group(&mut self, open: char) -> Node {
+        let close = closing(open);
+        let inner = self.expr(&[close]);
+        let closed = self.peek() == Some(close);
+        let mut elems = vec![Element::op(open)];
+        elems.extend(
+            inner
+                .iter()
+                .filter(|n| n.sep.is_none())
+                .map(|n| n.e.clone()),
+        );
+        if closed {
+            self.pos += close.len_utf8();
+            elems.push(Element::op(close));
+        }
+        Node {
+            group: (open == '(' && closed).then_some(inner),
+            ..Node::new(Element::row(elems))
+        }
+    }
+
+    /// Parse a name: a single-letter variable, a symbol, or a function call.
+    fn word(&mut self) -> Node {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !c.is_alphabetic())
+            .unwrap_or(rest.len());
+        if rest[..len].chars().count() == 1 {
+            self.pos += len;
+            return Node::new(Element::id(&rest[..len]));
+        }
+        // Take the longest run of modifiers that still names something.
+        let mut name = &rest[..len];
+        let mut end = len;
+        while let Some(m) = rest[end..].strip_prefix('.') {
+            let m_len = m.find(|c: char| !c.is_alphabetic()).unwrap_or(m.len());
+            if m_len == 0 {
+                break;
+            }
+            end += 1 + m_len;
+            if is_known(&rest[..end]) {
+                name = &rest[..end];
+            }
+        }
+        self.pos += name.len();
+        if self.rest().starts_with('(') && is_function(name) {
+            self.pos += 1;
+            return self.call(name);
+        }
+        if let Some(&(_, c, kind)) = SYMBOLS.iter().find(|(n, ..)| *n == name) {
+            return match kind {
+                Kind::Op => Node::new(Element::op(c)),
+                Kind::Id => Node::new(Element::id(c.to_string())),
+                Kind::IdNormal => Node::new(Element::id_normal(c.to_string())),
+                Kind::Large(limits) => Node::limits(Element::op(c), limits),
+            };
+        }
+        if let Some(&(_, limits)) = TEXT_OPS.iter().find(|(n, _)| *n == name) {
+            return Node::limits(Element::id(name), limits);
+        }
+        if let Some(&(_, w)) = SPACES.iter().find(|(n, _)| *n == name) {
+            return Node::new(Element::space(Space::width(Length::Em(w))));
+        }
+        Node::new(Element::err(name))
+    }
+
+    /// Parse function arguments, after the opening parenthesis. Returns the
+    /// positional arguments split into rows at semicolons, and the named
+    /// arguments.
+    fn args(&mut self) -> (Vec<Vec<Arg<'a>>>, Vec<Arg<'a>>) {
+        let mut rows = vec![Vec::new()];
+        let mut named = Vec::new();
+        loop {
+            match self.peek() {
+                None => break,
+                Some(')') => {
+                    self.pos += 1;
+                    break;
+                }
+                _ => (),
+            }
+            let rest = self.rest();
+            let key_len = rest
+                .find(|c: char| !(c.is_ascii_alphabetic() || c == '-'))
+                .unwrap_or(rest.len());
+            let after = &rest[key_len..];
+            let name = (key_len > 0 && after.starts_with(':') && !after.starts_with(":="))
+                .then(|| &rest[..key_len]);
+            if let Some(name) = name {
+                self.pos += name.len() + 1;
+            }
+            self.skip();
+            let start = self.pos;
+            let nodes = self.expr(&[',', ';', ')']);
+            let arg = Arg {
+                name,
+                raw: self.src[start..self.pos].trim(),
+                nodes,
+            };
+            if name.is_some() {
+                named.push(arg);
+            } else {
+                rows.last_mut().unwrap().push(arg);
+            }
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(';') => {
+                    self.pos += 1;
+                    rows.push(Vec::new());
+                }
+                _ => (),
+            }
+        }
+        while rows.last().is_some_and(Vec::is_empty) {
+            rows.pop();
+        }
+        (rows, named)
+    }
+
+    /// Parse a function call, after the opening parenthesis.
+    fn call(&mut self, name: &str) -> Node {
+        let (rows, mut named) = self.args();
+        let mut get = |key: &str| {
+            named
+                .iter()
+                .position(|a| a.name == Some(key))
+                .map(|i| named.remove(i))
+        };
+        let delims = |get: &mut dyn FnMut(&str) -> Option<Arg<'a>>, default| match get("delim") {
+            Some(a) => delim(a.raw),
+            None => Some(default),
+        };
+        let e = match name {
+            "mat" => {
+                let open = delims(&mut get, '(');
+                let table =
+                    Element::table(rows.into_iter().map(|r| {
+                        TableRow::new(r.into_iter().map(|a| TableCell::new(elems(a.nodes))))
+                    }));
+                fenced(open, table, open.map(closing))
+            }
+            "vec" => {
+                let open = delims(&mut get, '(');
+                let table = Element::table(
+                    rows.into_iter()
+                        .flatten()
+                        .map(|a| TableRow::new([TableCell::new(elems(a.nodes))])),
+                );
+                fenced(open, table, open.map(closing))
+            }
+            "cases" => {
+                let open = delims(&mut get, '{');
+                let reverse = get("reverse").is_some_and(|a| a.raw == "#true");
+                let table = Element::table(
+                    rows.into_iter()
+                        .flatten()
+                        .map(|a| TableRow::new(cells(a.nodes))),
+                );
+                if reverse {
+                    fenced(None, table, open.map(closing))
+                } else {
+                    fenced(open, table, None)
+                }
+            }
+            "attach" => {
+                let mut args = rows.into_iter().flatten();
+                let base = args.next();
+                let limits = base.as_ref().is_some_and(Arg::limits);
+                let base = base.map_or_else(|| Element::row([]), Arg::elem);
+                let mut script = |key: &str| get(key).map(Arg::elem);
+                let (t, b) = (script("t"), script("b"));
+                let (mut tr, mut br) = (script("tr"), script("br"));
+                let (tl, bl) = (script("tl"), script("bl"));
+                let (mut over, mut under) = (None, None);
+                if limits {
+                    over = t;
+                    under = b;
+                } else {
+                    tr = tr.or(t);
+                    br = br.or(b);
+                }
+                let empty = || Element::row([]);
+                let e = if tl.is_some() || bl.is_some() {
+                    let post = (tr.is_some() || br.is_some())
+                        .then(|| Pair::new(br.unwrap_or_else(empty), tr.unwrap_or_else(empty)));
+                    let pre = Pair::new(bl.unwrap_or_else(empty), tl.unwrap_or_else(empty));
+                    Element::multiscript(base, post, [pre])
+                } else {
+                    scripts(base, br, tr, false)
+                };
+                scripts(e, under, over, true)
+            }
+            "op" => {
+                let limits = get("limits").is_some_and(|a| a.raw == "#true");
+                let e = match content(rows.into_iter().flatten().collect()).into_parts() {
+                    (MathElement::Text(t), None) => Element::id(t),
+                    (e, a) => Element::with_attributes(e, a.unwrap_or_default()),
+                };
+                return Node::limits(e, limits);
+            }
+            _ => {
+                let mut args = rows.into_iter().flatten();
+                let mut next = || args.next().map_or_else(|| Element::row([]), Arg::elem);
+                match name {
+                    "frac" => Element::frac(next(), next()),
+                    "binom" => Element::row([
+                        Element::op('('),
+                        Element::frac_thickness(next(), next(), 0.0),
+                        Element::op(')'),
+                    ]),
+                    "root" => {
+                        let index = next();
+                        Element::root(next(), index)
+                    }
+                    "accent" => {
+                        let base = next();
+                        let mark = next();
+                        let c = match mark.elem() {
+                            MathElement::Id { t, .. } if t.chars().count() == 1 => t.chars().next(),
+                            _ => op_char(&mark),
+                        };
+                        Element::over_accent(base, c.map_or(mark, Element::op))
+                    }
+                    _ if MARKS.iter().any(|(n, ..)| *n == name) => {
+                        let &(_, c, under, limits) =
+                            MARKS.iter().find(|(n, ..)| *n == name).unwrap();
+                        let base = next();
+                        let label = args.next().map(Arg::elem);
+                        let (e, label) = if under {
+                            (
+                                Element::under(base, Element::op(c)),
+                                label.map(|l| (l, true)),
+                            )
+                        } else {
+                            (
+                                Element::over(base, Element::op(c)),
+                                label.map(|l| (l, false)),
+                            )
+                        };
+                        return match label {
+                            Some((l, true)) => Node::new(Element::under(e, l)),
+                            Some((l, false)) => Node::new(Element::over(e, l)),
+                            None => Node::limits(e, limits),
+                        };
+                    }
+                    _ => {
+                        let e = content(args.collect());
+                        match name {
+                            "sqrt" => Element::sqrt(e),
+                            "lr" => e,
+                            "limits" => return Node::limits(e, true),
+                            "scripts" => return Node::limits(e, false),
+                            "display" => e.display_style(true),
+                            "inline" => e.display_style(false),
+                            "script" => e.script_level(ScriptLevel::Set(1)),
+                            "sscript" => e.script_level(ScriptLevel::Set(2)),
+                            "hide" => Element::phantom(into_vec(e)),
+                            _ => {
+                                if let Some((_, c)) = ACCENTS.iter().find(|(n, _)| *n == name) {
+                                    Element::over_accent(e, Element::op(*c))
+                                } else if let Some((_, open, close)) =
+                                    FENCES.iter().find(|(n, ..)| *n == name)
+                                {
+                                    let mut elems = vec![Element::op(*open)];
+                                    elems.extend(into_vec(e));
+                                    elems.push(Element::op(*close));
+                                    Element::row(elems)
+                                } else {
+                                    restyle(name, e)
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        };
+        Node::new(e)
+    }
+}
+
+/// Place delimiters around a table.
+fn fenced(open: Option<char>, table: Element, close: Option<char>) -> Element {
+    if open.is_none() && close.is_none() {
+        return table;
+    }
+    let mut elems = Vec::new();
+    elems.extend(open.map(Element::op));
+    elems.push(table);
+    elems.extend(close.map(Element::op));
+    Element::row(elems)
+}
+
+impl Element {
+    /// Write the element out as Typst math markup, without the surrounding `$`
+    /// delimiters. Elements with no Typst equivalent, like padding, are
+    /// approximated.
+    pub fn to_typst(&self) -> String {
+        let mut w = Writer::default();
+        match self.elem() {
+            MathElement::Table { rows }
+                if self.attributes().is_none()
+                    && (rows.len() > 1 || rows.iter().any(|r| r.cells.len() > 1)) =>
+            {
+                w.lines(rows)
+            }
+            _ => w.items(self),
+        }
+        w.out
+    }
+}
+
+/// Find the Typst input for an operator character.
+fn op_str(c: char) -> Option<&'static str> {
+    SHORTHANDS
+        .iter()
+        .find(|(_, s)| *s == c)
+        .map(|(s, _)| *s)
+        .or_else(|| {
+            SYMBOLS
+                .iter()
+                .find(|(_, s, k)| *s == c && matches!(k, Kind::Op | Kind::Large(_)))
+                .map(|(n, ..)| *n)
+        })
+}
+
+/// Get the functions needed to write an element's attributes, outermost
+/// first.
+fn wrappers(e: &Element) -> Vec<&'static str> {
+    let mut names = Vec::new();
+    let Some(a) = e.attributes() else {
+        return names;
+    };
+    match a.display_style {
+        Some(true) => names.push("display"),
+        Some(false) => names.push("inline"),
+        None => (),
+    }
+    match a.script_level {
+        Some(ScriptLevel::Set(1)) => names.push("script"),
+        Some(ScriptLevel::Set(2)) => names.push("sscript"),
+        _ => (),
+    }
+    if let Some(fonts) = a.variant.and_then(|v| font_names(v, FONTS, STYLED)) {
+        names.extend(fonts);
+    }
+    names
+}
+
+/// Get the function for an over- or underscript, along with its base and
+/// any second argument: the label of a brace, or the mark of a general
+/// accent.
+fn mark_fn(e: &Element) -> Option<(&'static str, &Element, Option<&Element>)> {
+    let (base, mark, accent, under) = match e.elem() {
+        MathElement::Over { base, over, accent } => (base, over, *accent, false),
+        MathElement::Under {
+            base,
+            under,
+            accent_under,
+        } => (base, under, *accent_under, true),
+        _ => return None,
+    };
+    let c = op_char(mark);
+    if accent {
+        if under {
+            return None;
+        }
+        return match ACCENTS.iter().find(|(_, a)| Some(*a) == c) {
+            Some((name, _)) => Some((name, base, None)),
+            None => c.map(|_| ("accent", &**base, Some(&**mark))),
+        };
+    }
+    if let Some((name, ..)) = MARKS
+        .iter()
+        .find(|(_, m, u, _)| Some(*m) == c && *u == under)
+    {
+        return Some((name, base, None));
+    }
+    // A brace with a label.
+    let (name, inner, None) = mark_fn(base)? else {
+        return None;
+    };
+    MARKS
+        .iter()
+        .any(|(n, _, u, limits)| *n == name && *u == under && *limits)
+        .then_some((name, inner, Some(&**mark)))
+}
+
+/// Check if scripts on this element are written as limits.
+fn takes_limits(e: &Element) -> bool {
+    match e.elem() {
+        MathElement::Id { t, .. } => TEXT_OPS.iter().any(|(n, l)| n == t && *l),
+        MathElement::Over { .. } | MathElement::Under { .. } => match mark_fn(e) {
+            Some((name, _, None)) => MARKS.iter().any(|(n, .., l)| *n == name && *l),
+            _ => false,
+        },
+        _ => op_char(e).is_some_and(|c| {
+            SYMBOLS
+                .iter()
+                .any(|(_, s, k)| *s == c && *k == Kind::Large(true))
+        }),
+    }
+}
+
+/// Get the function placing delimiters around a row, if any.
+fn fence_fn(elems: &[Element]) -> Option<&'static str> {
+    let (open, close) = (op_char(elems.first()?)?, op_char(elems.last()?)?);
+    FENCES
+        .iter()
+        .find(|(_, o, c)| *o == open && *c == close)
+        .filter(|_| elems.len() >= 2)
+        .map(|(n, ..)| *n)
+}
+
+/// Get the brackets around a row, if it's written as a bracketed group.
+fn brackets(elems: &[Element]) -> Option<char> {
+    let (open, close) = (op_char(elems.first()?)?, op_char(elems.last()?)?);
+    ("([{".contains(open) && closing(open) == close && elems.len() >= 2).then_some(open)
+}
+
+/// Get a binomial's top and bottom, if the row is one.
+fn binom(elems: &[Element]) -> Option<(&Element, &Element)> {
+    let [open, frac, close] = elems else {
+        return None;
+    };
+    match frac.elem() {
+        MathElement::Frac {
+            line_thickness: Some(t),
+            num,
+            den,
+        } if *t == 0.0 && op_char(open) == Some('(') && op_char(close) == Some(')') => {
+            Some((num, den))
+        }
+        _ => None,
+    }
+}
+
+/// Get a table and its delimiters, if the row is one that can be written as
+/// a `mat`, `vec`, or `cases` call.
+fn table(elems: &[Element]) -> Option<(&Element, Option<char>, Option<char>)> {
+    let is_table = |e: &Element| matches!(e.elem(), MathElement::Table { .. });
+    let (t, open, close) = match elems {
+        [o, t, c] if is_table(t) => (t, Some(op_char(o)?), Some(op_char(c)?)),
+        [o, t] if is_table(t) => (t, Some(op_char(o)?), None),
+        [t, c] if is_table(t) => (t, None, Some(op_char(c)?)),
+        _ => return None,
+    };
+    match (open, close) {
+        (Some(o), Some(c)) if closing(o) == c => Some((t, open, close)),
+        (Some('{'), None) | (None, Some('}')) => Some((t, open, close)),
+        _ => None,
+    }
+}
+
+/// Check if a row is written as something other than `lr`.
+fn special_row(elems: &[Element]) -> bool {
+    binom(elems).is_some()
+        || table(elems).is_some()
+        || fence_fn(elems).is_some()
+        || brackets(elems).is_some()
+}
+
+/// Get the number of primes in a superscript, and anything after them.
+fn primes(sup: &Element) -> Option<(usize, Option<Element>)> {
+    let count = |e: &Element| match op_char(e)? {
+        '′' => Some(1),
+        '″' => Some(2),
+        '‴' => Some(3),
+        '⁗' => Some(4),
+        _ => None,
+    };
+    match sup.elem() {
+        MathElement::Row(elems) if sup.attributes().is_none() && elems.len() > 1 => {
+            let n = count(&elems[0])?;
+            Some((n, Some(into_elem(elems[1..].to_vec()))))
+        }
+        _ => count(sup).map(|n| (n, None)),
+    }
+}
+
+/// Check if the element is written as a single Typst atom.
+fn is_atom(e: &Element) -> bool {
+    if !wrappers(e).is_empty() {
+        return true;
+    }
+    match e.elem() {
+        MathElement::Frac { .. } | MathElement::UnderOver { .. } => false,
+        MathElement::Sup { .. } | MathElement::Sub { .. } | MathElement::SubSup { .. } => false,
+        MathElement::Over { .. } | MathElement::Under { .. } => mark_fn(e).is_some(),
+        MathElement::Row(elems) => special_row(elems),
+        _ => true,
+    }
+}
+
+/// Check if the element is written as a parenthesized group.
+fn is_paren_group(e: &Element) -> bool {
+    match e.elem() {
+        MathElement::Row(elems) => {
+            wrappers(e).is_empty()
+                && brackets(elems) == Some('(')
+                && binom(elems).is_none()
+                && table(elems).is_none()
+        }
+        _ => false,
+    }
+}
+
+fn quote(t: &str) -> String {
+    format!("\"{}\"", t.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[derive(Default)]
+struct Writer {
+    out: String,
+    /// The last token written.
+    last: String,
+    /// Set inside function arguments, where commas and semicolons need to be
+    /// escaped.
+    in_args: bool,
+}
+
+impl Writer {
+    /// Write a token, separating it from the previous one unless it's a
+    /// bracket, script, or separator that reads better attached.
+    fn push(&mut self, s: &str) {
+        let glued = self.out.is_empty()
+            || ["(", "[", "{", "^", "_", "/"].contains(&self.last.as_str())
+            || (self.last.ends_with('(') && !self.last.starts_with('\\'))
+            || s.starts_with([')', ']', '}', '^', '_', '/', ',', ';', '\''])
+            || (s.starts_with('(') && self.call_like());
+        if !glued {
+            self.out.push(' ');
+        }
+        self.out.push_str(s);
+        self.last = s.to_string();
+    }
+
+    /// Check if the last token reads naturally with an argument list
+    /// attached, like `f(x)` or `sin(x)`.
+    fn call_like(&self) -> bool {
+        let mut chars = self.last.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => c.is_alphanumeric() || c == '\'',
+            _ if self.last.starts_with('\'') => true,
+            _ => TEXT_OPS.iter().any(|(n, _)| *n == self.last),
+        }
+    }
+
+    /// Start a function call.
+    fn call(&mut self, name: &str) {
+        self.push(&format!("{}(", name));
+    }
+
+    /// Write a function argument.
+    fn content(&mut self, elems: &[Element]) {
+        let outer = std::mem::replace(&mut self.in_args, true);
+        elems.iter().for_each(|e| self.element(e));
+        self.in_args = outer;
+    }
+
+    /// Write a function argument holding a single element.
+    fn arg(&mut self, e: &Element) {
+        let outer = std::mem::replace(&mut self.in_args, true);
+        self.items(e);
+        self.in_args = outer;
+    }
+
+    /// Write a named function argument, unless it's empty.
+    fn named(&mut self, name: &str, e: &Element) {
+        if matches!(e.elem(), MathElement::Row(elems) if elems.is_empty()) {
+            return;
+        }
+        self.push(",");
+        self.push(&format!("{}:", name));
+        self.arg(e);
+    }
+
+    /// Write the contents of an element, without any grouping.
+    fn items(&mut self, e: &Element) {
+        match e.elem() {
+            MathElement::Row(elems) if wrappers(e).is_empty() && !special_row(elems) => {
+                elems.iter().for_each(|e| self.element(e))
+            }
+            _ => self.element(e),
+        }
+    }
+
+    /// Write a script or fraction part, which loses its outer parentheses
+    /// when read back.
+    fn operand(&mut self, e: &Element, unit: bool) {
+        let scripted = matches!(
+            e.elem(),
+            MathElement::Sup { .. }
+                | MathElement::Sub { .. }
+                | MathElement::SubSup { .. }
+                | MathElement::Over { .. }
+                | MathElement::Under { .. }
+                | MathElement::UnderOver { .. }
+        );
+        if (is_atom(e) || (unit && scripted)) && !is_paren_group(e) {
+            self.element(e);
+        } else {
+            self.push("(");
+            self.items(e);
+            self.push(")");
+        }
+    }
+
+    /// Write the base of a script.
+    fn base(&mut self, e: &Element) {
+        if takes_limits(e) {
+            self.call("scripts");
+            self.arg(e);
+            self.push(")");
+        } else if is_atom(e) {
+            self.element(e);
+        } else {
+            self.call("lr");
+            self.arg(e);
+            self.push(")");
+        }
+    }
+
+    /// Write the base of a limit.
+    fn limit_base(&mut self, e: &Element) {
+        if takes_limits(e) {
+            self.element(e);
+        } else {
+            self.call("limits");
+            self.arg(e);
+            self.push(")");
+        }
+    }
+
+    fn sub(&mut self, sub: &Element) {
+        self.push("_");
+        self.operand(sub, false);
+    }
+
+    fn sup(&mut self, sup: &Element) {
+        match primes(sup) {
+            Some((n, rest)) => {
+                self.push(&"'".repeat(n));
+                if let Some(rest) = rest {
+                    self.push("^");
+                    self.operand(&rest, false);
+                }
+            }
+            None => {
+                self.push("^");
+                self.operand(sup, false);
+            }
+        }
+    }
+
+    fn op(&mut self, c: char) {
+        if let Some(s) = op_str(c) {
+            self.push(s);
+        } else if "\\/_^'\"#$&()[]{}*-".contains(c) || (self.in_args && ",;".contains(c)) {
+            self.push(&format!("\\{}", c));
+        } else {
+            self.push(&c.to_string());
+        }
+    }
+
+    fn element(&mut self, e: &Element) {
+        let names = wrappers(e);
+        if !names.is_empty() {
+            let mut inner = e.clone();
+            let a = inner.attributes_mut();
+            a.display_style = None;
+            if matches!(a.script_level, Some(ScriptLevel::Set(1 | 2))) {
+                a.script_level = None;
+            }
+            if a.variant
+                .is_some_and(|v| font_names(v, FONTS, STYLED).is_some())
+            {
+                a.variant = None;
+            }
+            names.iter().for_each(|n| self.call(n));
+            self.arg(&inner);
+            names.iter().for_each(|_| self.push(")"));
+            return;
+        }
+        match e.elem() {
+            MathElement::Op(_) | MathElement::Oper(_) | MathElement::ResolvedOper(_) => {
+                self.op(op_char(e).unwrap())
+            }
+            MathElement::Id { t, normal } => {
+                let mut chars = t.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => {
+                        let name = SYMBOLS
+                            .iter()
+                            .find(|(_, s, k)| *s == c && matches!(k, Kind::Id | Kind::IdNormal));
+                        match name {
+                            Some((name, ..)) => self.push(name),
+                            None if *normal => {
+                                self.call("upright");
+                                self.push(t);
+                                self.push(")");
+                            }
+                            None => self.push(t),
+                        }
+                    }
+                    _ if TEXT_OPS.iter().any(|(n, _)| n == t) => self.push(t),
+                    _ => {
+                        self.call("op");
+                        self.push(&quote(t));
+                        self.push(")");
+                    }
+                }
+            }
+            MathElement::Num(t) => {
+                let mut p = Parser { src: t, pos: 0 };
+                if matches!(p.atom(&[]).e.elem(), MathElement::Num(_)) && p.pos == t.len() {
+                    self.push(t);
+                } else {
+                    self.push(&quote(t));
+                }
+            }
+            MathElement::Text(t) | MathElement::Str(t) => self.push(&quote(t)),
+            MathElement::Err(t) | MathElement::Unknown { tag: t, .. } => {
+                self.push(&format!("text(fill: #red, {})", quote(t)))
+            }
+            MathElement::Space(s) => {
+                let w = match s.width {
+                    Some(Length::Em(w)) => w,
+                    _ => 0.25,
+                };
+                let (name, _) = SPACES
+                    .iter()
+                    .min_by(|a, b| (a.1 - w).abs().total_cmp(&(b.1 - w).abs()))
+                    .unwrap();
+                self.push(name);
+            }
+            MathElement::Row(elems) => self.row(elems),
+            MathElement::Phantom(elems) => {
+                self.call("hide");
+                self.content(elems);
+                self.push(")");
+            }
+            MathElement::Padding(Padding { elems, .. }) => {
+                self.call("lr");
+                self.content(elems);
+                self.push(")");
+            }
+            MathElement::Frac { num, den, .. } => {
+                self.operand(num, true);
+                self.push("/");
+                self.operand(den, true);
+            }
+            MathElement::Sqrt(base) => {
+                self.call("sqrt");
+                self.arg(base);
+                self.push(")");
+            }
+            MathElement::Root { base, index } => {
+                self.call("root");
+                self.arg(index);
+                self.push(",");
+                self.arg(base);
+                self.push(")");
+            }
+            MathElement::Sup { base, sup } => {
+                self.base(base);
+                self.sup(sup);
+            }
+            MathElement::Sub { base, sub } => {
+                self.base(base);
+                self.sub(sub);
+            }
+            MathElement::SubSup { base, sub, sup } => {
+                self.base(base);
+                self.sub(sub);
+                self.sup(sup);
+            }
+            MathElement::Over { base, over, .. } => match mark_fn(e) {
+                Some((name, base, second)) => {
+                    self.call(name);
+                    self.arg(base);
+                    if let Some(second) = second {
+                        self.push(",");
+                        self.arg(second);
+                    }
+                    self.push(")");
+                }
+                None => {
+                    self.limit_base(base);
+                    self.push("^");
+                    self.operand(over, false);
+                }
+            },
+            MathElement::Under { base, under, .. } => match mark_fn(e) {
+                Some((name, base, second)) => {
+                    self.call(name);
+                    self.arg(base);
+                    if let Some(second) = second {
+                        self.push(",");
+                        self.arg(second);
+                    }
+                    self.push(")");
+                }
+                None => {
+                    self.limit_base(base);
+                    self.sub(under);
+                }
+            },
+            MathElement::UnderOver {
+                base, under, over, ..
+            } => {
+                self.limit_base(base);
+                self.sub(under);
+                self.push("^");
+                self.operand(over, false);
+            }
+            MathElement::MultiScript { base, post, pre } => {
+                // Extra pairs of scripts become nested attachments.
+                let depth = post.len().max(pre.len()).max(1);
+                (0..depth).for_each(|_| self.call("attach"));
+                self.arg(base);
+                for i in 0..depth {
+                    if let Some(p) = pre.get(i) {
+                        self.named("tl", &p.sup);
+                        self.named("bl", &p.sub);
+                    }
+                    if let Some(p) = post.get(i) {
+                        self.named("tr", &p.sup);
+                        self.named("br", &p.sub);
+                    }
+                    self.push(")");
+                }
+            }
+            MathElement::Table { .. } => self.table(e, None, None),
+        }
+    }
+
+    fn row(&mut self, elems: &[Element]) {
+        if let Some((num, den)) = binom(elems) {
+            self.call("binom");
+            self.arg(num);
+            self.push(",");
+            self.arg(den);
+            self.push(")");
+        } else if let Some((t, open, close)) = table(elems) {
+            self.table(t, open, close);
+        } else if let Some(name) = fence_fn(elems) {
+            self.call(name);
+            self.content(&elems[1..elems.len() - 1]);
+            self.push(")");
+        } else if let Some(open) = brackets(elems) {
+            self.push(&open.to_string());
+            elems[1..elems.len() - 1]
+                .iter()
+                .for_each(|e| self.element(e));
+            self.push(&closing(open).to_string());
+        } else {
+            self.call("lr");
+            self.content(elems);
+            self.push(")");
+        }
+    }
+
+    /// Write a table as lines of equations, with cells split at alignment
+    /// points.
+    fn lines(&mut self, rows: &[TableRow]) {
+        for (i, row) in rows.iter().enumerate() {
+            if i > 0 {
+                self.push("\\");
+            }
+            for (j, cell) in row.cells.iter().enumerate() {
+                if j > 0 {
+                    self.push("&");
+                }
+                cell.elems.iter().for_each(|e| self.element(e));
+            }
+        }
+    }
+
+    /// Write a table as a `mat`, `vec`, or `cases` call with the given
+    /// delimiters.
+    fn table(&mut self, e: &Element, open: Option<char>, close: Option<char>) {
+        let MathElement::Table { rows } = e.elem() else {
+            return;
+        };
+        let cols = rows.iter().map(|r| r.cells.len()).max().unwrap_or(0);
+        let delim = |c: Option<char>| match c {
+            Some('‖') => "\"||\"".to_string(),
+            Some(c) => quote(&c.to_string()),
+            None => "#none".to_string(),
+        };
+        let cases = matches!((open, close), (Some('{'), None) | (None, Some('}')));
+        if cases {
+            self.call("cases");
+            if close.is_some() {
+                self.push("reverse:");
+                self.push("#true");
+                self.push(",");
+            }
+        } else if open == Some('(') && cols == 1 {
+            self.call("vec");
+        } else {
+            self.call("mat");
+            if open != Some('(') {
+                self.push("delim:");
+                self.push(&delim(open));
+                self.push(",");
+            }
+        }
+        for (i, row) in rows.iter().enumerate() {
+            if i > 0 {
+                self.push(if cases || cols == 1 && open == Some('(') {
+                    ","
+                } else {
+                    ";"
+                });
+            }
+            for (j, cell) in row.cells.iter().enumerate() {
+                if j > 0 {
+                    self.push(if cases { "&" } else { "," });
+                }
+                self.content(&cell.elems);
+            }
+        }
+        self.push(")");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(s: &str) -> Element {
+        Element::row(s.chars().map(|c| match c {
+            '=' | '+' => Element::op(c),
+            '-' => Element::op('−'),
+            c if c.is_ascii_digit() => Element::num(c.to_string()),
+            c => Element::id(c.to_string()),
+        }))
+    }
+
+    #[test]
+    fn scripts_and_fractions() {
+        assert_eq!(
+            parse("sum_(i=1)^n i^2"),
+            Element::row([
+                Element::under_over(Element::op('∑'), ids("i=1"), Element::id("n")),
+                Element::sup(Element::id("i"), Element::num("2")),
+            ])
+        );
+        assert_eq!(
+            parse("(a+b)/c^2"),
+            Element::frac(
+                ids("a+b"),
+                Element::sup(Element::id("c"), Element::num("2"))
+            )
+        );
+        assert_eq!(
+            parse("x a/b"),
+            Element::row([
+                Element::id("x"),
+                Element::frac(Element::id("a"), Element::id("b")),
+            ])
+        );
+        assert_eq!(
+            parse("f'(x)"),
+            Element::row([
+                Element::sup(Element::id("f"), Element::op('′')),
+                Element::row([Element::op('('), Element::id("x"), Element::op(')')]),
+            ])
+        );
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(
+            parse("frac(a, b)"),
+            Element::frac(Element::id("a"), Element::id("b"))
+        );
+        assert_eq!(
+            parse("root(3, x+1)"),
+            Element::root(ids("x+1"), Element::num("3"))
+        );
+        assert_eq!(
+            parse("attach(x, t: 2, b: 1)"),
+            Element::sub_sup(Element::id("x"), Element::num("1"), Element::num("2"))
+        );
+        assert_eq!(
+            parse("attach(sum, t: n)"),
+            Element::over(Element::op('∑'), Element::id("n"))
+        );
+        assert_eq!(
+            parse("attach(C, tl: n, bl: k)"),
+            Element::multiscript(
+                Element::id("C"),
+                [],
+                [Pair::new(Element::id("k"), Element::id("n"))]
+            )
+        );
+        assert_eq!(
+            parse("abs(x) <= norm(y)"),
+            Element::row([
+                Element::row([Element::op('|'), Element::id("x"), Element::op('|')]),
+                Element::op('≤'),
+                Element::row([Element::op('‖'), Element::id("y"), Element::op('‖')]),
+            ])
+        );
+    }
+
+    #[test]
+    fn fonts_and_accents() {
+        assert_eq!(
+            parse("cal(A) bb(R) bold(frak(g)) upright(d)"),
+            Element::row([
+                Element::id("A").variant(Variant::Script),
+                Element::id("R").variant(Variant::DoubleStruck),
+                Element::id("g").variant(Variant::BoldFraktur),
+                Element::id_normal("d"),
+            ])
+        );
+        assert_eq!(
+            parse("hat(x) overline(y)"),
+            Element::row([
+                Element::over_accent(Element::id("x"), Element::op('^')),
+                Element::over(Element::id("y"), Element::op('‾')),
+            ])
+        );
+        assert_eq!(
+            parse("underbrace(a+b, n)"),
+            Element::under(
+                Element::under(ids("a+b"), Element::op('⏟')),
+                Element::id("n")
+            )
+        );
+    }
+
+    #[test]
+    fn tables() {
+        assert_eq!(
+            parse("mat(1, 2; 3, 4)"),
+            Element::row([
+                Element::op('('),
+                Element::matrix([
+                    [Element::num("1"), Element::num("2")],
+                    [Element::num("3"), Element::num("4")],
+                ]),
+                Element::op(')'),
+            ])
+        );
+        assert_eq!(
+            parse("mat(delim: \"[\", a; b)"),
+            Element::row([
+                Element::op('['),
+                Element::matrix([[Element::id("a")], [Element::id("b")]]),
+                Element::op(']'),
+            ])
+        );
+        assert_eq!(
+            parse("cases(x & \"if\" x >= 0, -x & \"otherwise\")"),
+            Element::row([
+                Element::op('{'),
+                Element::table([
+                    TableRow::new([
+                        TableCell::new([Element::id("x")]),
+                        TableCell::new([
+                            Element::text("if"),
+                            Element::id("x"),
+                            Element::op('≥'),
+                            Element::num("0"),
+                        ]),
+                    ]),
+                    TableRow::new([
+                        TableCell::new([Element::op('−'), Element::id("x")]),
+                        TableCell::new([Element::text("otherwise")]),
+                    ]),
+                ]),
+            ])
+        );
+        assert_eq!(
+            parse("a &= b \\ &= c"),
+            Element::table([
+                TableRow::new([
+                    TableCell::new([Element::id("a")]),
+                    TableCell::new([Element::op('='), Element::id("b")]),
+                ]),
+                TableRow::new([
+                    TableCell::new([]),
+                    TableCell::new([Element::op('='), Element::id("c")]),
+                ]),
+            ])
+        );
+    }
+
+    #[test]
+    fn symbols_and_errors() {
+        assert_eq!(
+            parse("alpha -> arrow.r.double oo"),
+            Element::row([
+                Element::id("α"),
+                Element::op('→'),
+                Element::op('⇒'),
+                Element::id_normal("∞"),
+            ])
+        );
+        assert_eq!(
+            parse("foo + #x \\/ y)"),
+            Element::row([
+                Element::err("foo"),
+                Element::op('+'),
+                Element::err("#x"),
+                Element::op('/'),
+                Element::id("y"),
+                Element::op(')'),
+            ])
+        );
+        assert_eq!(parse("$ x $"), Element::id("x").display_style(true));
+    }
+
+    #[test]
+    fn write() {
+        let e = Element::row([
+            Element::op('∫'),
+            Element::frac(ids("a+b"), Element::id("c")),
+            Element::op('−'),
+            Element::sqrt(Element::id("α")),
+        ]);
+        assert_eq!(e.to_typst(), "integral (a + b)/c - sqrt(alpha)");
+        assert_eq!(
+            Element::sub_sup(Element::op('∑'), ids("i=1"), Element::id("n")).to_typst(),
+            "scripts(sum)_(i = 1)^n"
+        );
+        assert_eq!(
+            Element::row([Element::op('<'), Element::op('=')]).to_typst(),
+            "< ="
+        );
+        assert_eq!(
+            Element::id("X").variant(Variant::BoldScript).to_typst(),
+            "bold(cal(X))"
+        );
+        assert_eq!(parse("a &= b \\ &= c").to_typst(), "a & = b \\ & = c");
+        assert_eq!(parse("f'(x)").to_typst(), "f'(x)");
+    }
+
+    #[test]
+    fn typst_roundtrip() {
+        for src in [
+            "sum_(i=1)^n i^2",
+            "a/b + frac(1, x+1)",
+            "sqrt(x) root(3, x+1)",
+            "mat(a, b; c, d) vec(1, 2, 3)",
+            "mat(delim: \"[\", 1, 0; 0, 1) mat(delim: #none, a; b)",
+            "cases(x & \"if\" x >= 0, -x & \"otherwise\")",
+            "cases(reverse: #true, a, b)",
+            "(a+b)/(c-d) = x^(2n)/y_1",
+            "lim_(x -> 0) (sin x)/x = 1",
+            "integral_0^1 f(x) d x",
+            "bold(x) + cal(F) + bb(R) + italic(sans(v)) + upright(e)",
+            "hat(x) arrow(v) underbrace(a+b, n) overline(z)",
+            "abs(x) <= 1 quad forall x in RR",
+            "attach(x, tl: 1, br: 2) attach(A, t: 3, b: 4) limits(X)_a",
+            "f''(x) + g'^2 + (x^2)^3",
+            "binom(n, k) display(a/b) hide(x) accent(x, star.op)",
+            "op(\"foo\") \"text\" 3.14 a \\, b, c \\/ d",
+            "lr(angle.l a, b angle.r) floor(x) ceil(y) norm(z)",
+            "a &= b \\ &= c",
+        ] {
+            let e = parse(src);
+            assert_eq!(parse(&e.to_typst()), e, "{} -> {}", src, e.to_typst());
+        }
+    }
+}
@@ -0,0 +1,1262 @@
+//! Conversion between UnicodeMath, the linear format used by Microsoft Office,
+//! and fog-math elements.
+//!
+//! Parsing follows the "build-up" rules of Unicode Technical Note 28: operands
+//! of fractions, scripts, and radicals are single factors or bracketed
+//! expressions, and brackets around an operand are dropped once it has been
+//! built up. Like AsciiMath, the format has no notion of a syntax error;
+//! anything that isn't understood is kept as a literal operator.
+
+use crate::math::*;
+use crate::tree::{into_elem, into_vec, op_char};
+
+/// Control words, which are replaced by their character before parsing.
+const CONTROL_WORDS: &[(&str, char)] = &[
+    // Greek
+    ("alpha", 'α'),
+    ("beta", 'β'),
+    ("gamma", 'γ'),
+    ("Gamma", 'Γ'),
+    ("delta", 'δ'),
+    ("Delta", 'Δ'),
+    ("epsilon", 'ϵ'),
+    ("varepsilon", 'ε'),
+    ("zeta", 'ζ'),
+    ("eta", 'η'),
+    ("theta", 'θ'),
+    ("Theta", 'Θ'),
+    ("vartheta", 'ϑ'),
+    ("iota", 'ι'),
+    ("kappa", 'κ'),
+    ("lambda", 'λ'),
+    ("Lambda", 'Λ'),
+    ("mu", 'μ'),
+    ("nu", 'ν'),
+    ("xi", 'ξ'),
+    ("Xi", 'Ξ'),
+    ("pi", 'π'),
+    ("Pi", 'Π'),
+    ("rho", 'ρ'),
+    ("sigma", 'σ'),
+    ("Sigma", 'Σ'),
+    ("tau", 'τ'),
+    ("upsilon", 'υ'),
+    ("phi", 'ϕ'),
+    ("varphi", 'φ'),
+    ("Phi", 'Φ'),
+    ("chi", 'χ'),
+    ("psi", 'ψ'),
+    ("Psi", 'Ψ'),
+    ("omega", 'ω'),
+    ("Omega", 'Ω'),
+    // Operators and relations
+    ("times", '×'),
+    ("cdot", '⋅'),
+    ("div", '÷'),
+    ("pm", '±'),
+    ("mp", '∓'),
+    ("ast", '∗'),
+    ("circ", '∘'),
+    ("le", '≤'),
+    ("ge", '≥'),
+    ("ne", '≠'),
+    ("approx", '≈'),
+    ("equiv", '≡'),
+    ("sim", '∼'),
+    ("propto", '∝'),
+    ("in", '∈'),
+    ("notin", '∉'),
+    ("subset", '⊂'),
+    ("supset", '⊃'),
+    ("subseteq", '⊆'),
+    ("supseteq", '⊇'),
+    ("cup", '∪'),
+    ("cap", '∩'),
+    ("wedge", '∧'),
+    ("vee", '∨'),
+    ("neg", '¬'),
+    ("forall", '∀'),
+    ("exists", '∃'),
+    ("partial", '∂'),
+    ("nabla", '∇'),
+    ("infty", '∞'),
+    ("emptyset", '∅'),
+    ("to", '→'),
+    ("rightarrow", '→'),
+    ("leftarrow", '←'),
+    ("Rightarrow", '⇒'),
+    ("Leftarrow", '⇐'),
+    ("iff", '⇔'),
+    ("mapsto", '↦'),
+    ("cdots", '⋯'),
+    ("ldots", '…'),
+    ("vdots", '⋮'),
+    ("ddots", '⋱'),
+    ("sum", '∑'),
+    ("prod", '∏'),
+    ("coprod", '∐'),
+    ("bigcup", '⋃'),
+    ("bigcap", '⋂'),
+    ("int", '∫'),
+    ("iint", '∬'),
+    ("iiint", '∭'),
+    ("oint", '∮'),
+    // Build-up operators
+    ("sqrt", '√'),
+    ("cbrt", '∛'),
+    ("qdrt", '∜'),
+    ("matrix", '■'),
+    ("eqarray", '█'),
+    ("phantom", '⟡'),
+    ("overbrace", '⏞'),
+    ("underbrace", '⏟'),
+    ("overbar", '¯'),
+    ("underbar", '▁'),
+    ("above", '┴'),
+    ("below", '┬'),
+    ("atop", '¦'),
+    ("of", '▒'),
+    ("naryand", '▒'),
+    ("begin", '〖'),
+    ("end", '〗'),
+    ("open", '├'),
+    ("close", '┤'),
+    ("langle", '⟨'),
+    ("rangle", '⟩'),
+    ("lfloor", '⌊'),
+    ("rfloor", '⌋'),
+    ("lceil", '⌈'),
+    ("rceil", '⌉'),
+    ("funcapply", '\u{2061}'),
+    // Accents
+    ("hat", '\u{302}'),
+    ("check", '\u{30C}'),
+    ("tilde", '\u{303}'),
+    ("acute", '\u{301}'),
+    ("grave", '\u{300}'),
+    ("dot", '\u{307}'),
+    ("ddot", '\u{308}'),
+    ("breve", '\u{306}'),
+    ("bar", '\u{305}'),
+    ("vec", '\u{20D7}'),
+    // Spaces
+    ("emsp", '\u{2003}'),
+    ("ensp", '\u{2002}'),
+    ("thicksp", '\u{2004}'),
+    ("medsp", '\u{205F}'),
+    ("thinsp", '\u{2009}'),
+    ("hairsp", '\u{200A}'),
+];
+
+/// Combining marks and the accent characters they become.
+const ACCENTS: &[(char, char)] = &[
+    ('\u{302}', '^'),
+    ('\u{30C}', 'ˇ'),
+    ('\u{303}', '~'),
+    ('\u{301}', '´'),
+    ('\u{300}', '`'),
+    ('\u{307}', '˙'),
+    ('\u{308}', '¨'),
+    ('\u{306}', '˘'),
+    ('\u{305}', '¯'),
+    ('\u{304}', '¯'),
+    ('\u{20D7}', '→'),
+];
+
+/// Spacing characters and their widths in em.
+const SPACES: &[(char, f32)] = &[
+    ('\u{2003}', 1.0),
+    ('\u{2002}', 0.5),
+    ('\u{2004}', 5.0 / 18.0),
+    ('\u{205F}', 4.0 / 18.0),
+    ('\u{2009}', 3.0 / 18.0),
+    ('\u{200A}', 1.0 / 18.0),
+];
+
+/// Function names, and whether they take their scripts as limits.
+const FUNCTIONS: &[(&str, bool)] = &[
+    ("arccos", false),
+    ("arcsin", false),
+    ("arctan", false),
+    ("arg", false),
+    ("cos", false),
+    ("cosh", false),
+    ("cot", false),
+    ("coth", false),
+    ("csc", false),
+    ("deg", false),
+    ("det", false),
+    ("dim", false),
+    ("exp", false),
+    ("gcd", false),
+    ("inf", true),
+    ("ker", false),
+    ("lg", false),
+    ("lim", true),
+    ("liminf", true),
+    ("limsup", true),
+    ("ln", false),
+    ("log", false),
+    ("max", true),
+    ("min", true),
+    ("Pr", false),
+    ("sec", false),
+    ("sin", false),
+    ("sinh", false),
+    ("sup", true),
+    ("tan", false),
+    ("tanh", false),
+];
+
+/// N-ary operators that take their scripts as limits. Integrals aren't
+/// included, as their scripts are placed to the side.
+const LARGE: &[char] = &['∑', '∏', '∐', '⋀', '⋁', '⋂', '⋃', '⨀', '⨁', '⨂', '⨄', '⨆'];
+
+const OPEN: &[char] = &['(', '[', '{', '⟨', '⌊', '⌈', '|', '‖'];
+const CLOSE: &[char] = &[')', ']', '}', '⟩', '⌋', '⌉', '|', '‖', '〗', '┤'];
+
+/// Characters that must be escaped with a backslash to be read literally.
+const SPECIAL: &[char] = &[
+    '(', ')', '[', ']', '{', '}', '⟨', '⟩', '⌊', '⌋', '⌈', '⌉', '|', '‖', '〖', '〗', '├', '┤',
+    '/', '¦', '_', '^', '┬', '┴', '&', '@', '■', '█', '√', '∛', '∜', '⟡', '⏞', '⏟', '¯', '▁', '▒',
+    '"', '\\', '\'', '′', '-', '*',
+];
+
+/// Parse UnicodeMath into an element. Control words like `\alpha` are
+/// accepted in place of the characters they stand for.
+pub fn parse(src: &str) -> Element {
+    let mut p = Parser {
+        src: replace_control_words(src).chars().collect(),
+        pos: 0,
+    };
+    let mut out = Vec::new();
+    while p.peek().is_some() {
+        out.extend(p.expr(false));
+    }
+    into_elem(out.into_iter().map(Node::into_elem).collect())
+}
+
+/// Replace known control words with their characters, leaving quoted text and
+/// escaped characters alone.
+fn replace_control_words(src: &str) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut quoted = false;
+    let mut rest = src;
+    while let Some(c) = rest.chars().next() {
+        rest = &rest[c.len_utf8()..];
+        if c == '"' {
+            quoted = !quoted;
+        }
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        let len = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let word = CONTROL_WORDS.iter().find(|w| w.0 == &rest[..len]);
+        match word {
+            Some((_, c)) if !quoted && len > 0 => {
+                out.push(*c);
+                rest = &rest[len..];
+                // A space after a control word only ends it.
+                if let Some(r) = rest.strip_prefix(' ') {
+                    rest = r;
+                }
+            }
+            _ => {
+                out.push('\\');
+                if let Some(c) = rest.chars().next().filter(|_| len == 0) {
+                    out.push(c);
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+    }
+    out
+}
+
+fn fence(c: char) -> Element {
+    Element::oper(Operator {
+        stretchy: Some(true),
+        ..Operator::new(c)
+    })
+}
+
+/// A parsed piece of an expression.
+struct Node {
+    e: Element,
+    /// The contents of a bracketed expression, used when it is an operand.
+    group: Option<Vec<Node>>,
+    /// Set for the `&` and `@` separators.
+    sep: Option<char>,
+}
+
+impl Node {
+    fn new(e: Element) -> Self {
+        Self {
+            e,
+            group: None,
+            sep: None,
+        }
+    }
+
+    fn into_elem(self) -> Element {
+        match self.sep {
+            Some(c) => Element::op(c),
+            None => self.e,
+        }
+    }
+
+    fn to_elem(&self) -> Element {
+        match self.sep {
+            Some(c) => Element::op(c),
+            None => self.e.clone(),
+        }
+    }
+
+    /// Get the element as an operand, with any outer brackets removed.
+    fn arg(self) -> Element {
+        match self.group {
+            Some(g) => into_elem(g.into_iter().map(Node::into_elem).collect()),
+            None => self.into_elem(),
+        }
+    }
+
+    /// Get the nodes making up an operand.
+    fn arg_nodes(self) -> Vec<Node> {
+        match self.group {
+            Some(g) => g,
+            None => vec![self],
+        }
+    }
+}
+
+fn nodes_elem(nodes: Vec<Node>) -> Element {
+    into_elem(nodes.into_iter().map(Node::into_elem).collect())
+}
+
+struct Parser {
+    src: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.src.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, n: usize) -> Option<char> {
+        self.src.get(self.pos + n).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    /// Check if the next character starts a factor: something that can be an
+    /// operand.
+    fn at_factor(&self) -> bool {
+        let Some(c) = self.peek() else {
+            return false;
+        };
+        c.is_alphanumeric()
+            || (c == '.' && self.peek_at(1).is_some_and(|c| c.is_ascii_digit()))
+            || (c == '\\' && self.peek_at(1).is_some_and(|c| c.is_ascii_alphabetic()))
+            || OPEN.contains(&c)
+            || "〖├\"√∛∜■█⟡⏞⏟¯▁∞∅".contains(c)
+    }
+
+    /// Parse an expression, stopping at a closing bracket if inside a group.
+    fn expr(&mut self, in_group: bool) -> Vec<Node> {
+        let mut out: Vec<Node> = Vec::new();
+        // Start of the current run of factors, which is the numerator if a
+        // fraction is found.
+        let mut run = 0;
+        while let Some(c) = self.peek() {
+            if CLOSE.contains(&c) && (in_group || !OPEN.contains(&c)) {
+                if in_group {
+                    break;
+                }
+                self.bump();
+                out.push(Node::new(Element::op(c)));
+                run = out.len();
+                continue;
+            }
+            if c.is_whitespace() {
+                self.bump();
+                if let Some(&(_, w)) = SPACES.iter().find(|s| s.0 == c) {
+                    out.push(Node::new(Element::space(Space::width(Length::Em(w)))));
+                }
+                run = out.len();
+                continue;
+            }
+            match c {
+                '&' | '@' => {
+                    self.bump();
+                    out.push(Node {
+                        sep: Some(c),
+                        ..Node::new(Element::row([]))
+                    });
+                    run = out.len();
+                }
+                '/' | '¦' => {
+                    self.bump();
+                    let num: Vec<Node> = out.drain(run..).collect();
+                    let den = self.factors();
+                    if num.is_empty() && den.is_empty() {
+                        out.push(Node::new(Element::op(c)));
+                        run = out.len();
+                        continue;
+                    }
+                    let num = run_elem(num);
+                    let den = run_elem(den);
+                    out.push(Node::new(if c == '¦' {
+                        Element::frac_thickness(num, den, 0.0)
+                    } else {
+                        Element::frac(num, den)
+                    }));
+                }
+                '▒' => {
+                    self.bump();
+                }
+                _ if self.at_factor() => out.push(self.scripted()),
+                _ => {
+                    // Operators can have scripts too, like n-ary operators.
+                    let op = Node::new(self.operator());
+                    out.push(self.scripts(op));
+                    run = out.len();
+                }
+            }
+        }
+        out
+    }
+
+    /// Parse an operator character.
+    fn operator(&mut self) -> Element {
+        match self.bump() {
+            Some('\\') => match self.bump() {
+                Some(c) => Element::op(c),
+                None => Element::op('\\'),
+            },
+            Some('-') => Element::op('−'),
+            Some('*') => Element::op('∗'),
+            Some(c) => Element::op(c),
+            None => Element::row([]),
+        }
+    }
+
+    /// Parse a run of factors with no operators or spaces between them.
+    fn factors(&mut self) -> Vec<Node> {
+        let mut out = Vec::new();
+        while self.at_factor() {
+            out.push(self.scripted());
+        }
+        out
+    }
+
+    /// Parse a factor along with any accents and scripts on it.
+    fn scripted(&mut self) -> Node {
+        let node = self.factor();
+        self.scripts(node)
+    }
+
+    /// Parse any accents and scripts following a node.
+    fn scripts(&mut self, mut node: Node) -> Node {
+        let mut limits = takes_limits(&node.e);
+        while let Some(&(_, a)) = self.peek().and_then(|c| ACCENTS.iter().find(|a| a.0 == c)) {
+            self.bump();
+            node = Node::new(Element::over_accent(node.e, Element::op(a)));
+            limits = false;
+        }
+        let mut sub = None;
+        let mut sup = None;
+        let mut under = None;
+        let mut over = None;
+        loop {
+            let slot = match self.peek() {
+                Some('_') if limits => &mut under,
+                Some('^') if limits => &mut over,
+                Some('_') => &mut sub,
+                Some('^') => &mut sup,
+                Some('┬') => &mut under,
+                Some('┴') => &mut over,
+                Some('\'' | '′') => {
+                    if sup.is_some() {
+                        break;
+                    }
+                    let mut n = 0;
+                    while matches!(self.peek(), Some('\'' | '′')) {
+                        self.bump();
+                        n += 1;
+                    }
+                    sup = Some(Element::op(match n {
+                        1 => '′',
+                        2 => '″',
+                        3 => '‴',
+                        _ => '⁗',
+                    }));
+                    continue;
+                }
+                Some(c) if superscript_digit(c).is_some() || subscript_digit(c).is_some() => {
+                    let (slot, f): (_, fn(char) -> Option<char>) = if superscript_digit(c).is_some()
+                    {
+                        (&mut sup, superscript_digit)
+                    } else {
+                        (&mut sub, subscript_digit)
+                    };
+                    if slot.is_some() {
+                        break;
+                    }
+                    let mut num = String::new();
+                    while let Some(d) = self.peek().and_then(f) {
+                        self.bump();
+                        num.push(d);
+                    }
+                    *slot = Some(Element::num(num));
+                    continue;
+                }
+                _ => break,
+            };
+            if slot.is_some() {
+                break;
+            }
+            self.bump();
+            *slot = Some(self.script());
+        }
+        let mut e = node.e;
+        let scripted = sub.is_some() || sup.is_some() || under.is_some() || over.is_some();
+        e = match (sub, sup) {
+            (Some(sub), Some(sup)) => Element::sub_sup(e, sub, sup),
+            (Some(sub), None) => Element::sub(e, sub),
+            (None, Some(sup)) => Element::sup(e, sup),
+            (None, None) => e,
+        };
+        e = match (under, over) {
+            (Some(under), Some(over)) => Element::under_over(e, under, over),
+            (Some(under), None) => Element::under(e, under),
+            (None, Some(over)) => Element::over(e, over),
+            (None, None) => e,
+        };
+        if scripted {
+            Node::new(e)
+        } else {
+            Node { e, ..node }
+        }
+    }
+
+    /// Parse the operand of a script.
+    fn script(&mut self) -> Element {
+        if matches!(self.peek(), Some('-' | '+' | '−' | '±' | '∓')) {
+            let sign = self.operator();
+            if self.at_factor() {
+                return Element::row([sign, self.factor().arg()]);
+            }
+            return sign;
+        }
+        if self.at_factor() {
+            return self.factor().arg();
+        }
+        match self.peek() {
+            Some(c) if !c.is_whitespace() && !CLOSE.contains(&c) && !"&@/¦".contains(c) => {
+                self.operator()
+            }
+            _ => Element::row([]),
+        }
+    }
+
+    /// Parse the operand of a prefix operator like a radical.
+    fn operand(&mut self) -> Node {
+        if self.at_factor() {
+            self.factor()
+        } else {
+            Node::new(Element::row([]))
+        }
+    }
+
+    fn factor(&mut self) -> Node {
+        let Some(c) = self.bump() else {
+            return Node::new(Element::row([]));
+        };
+        match c {
+            '0'..='9' | '.' => {
+                let mut num = c.to_string();
+                while let Some(c) = self.peek() {
+                    let decimal = c == '.'
+                        && !num.contains('.')
+                        && self.peek_at(1).is_some_and(|c| c.is_ascii_digit());
+                    if !c.is_ascii_digit() && !decimal {
+                        break;
+                    }
+                    num.push(c);
+                    self.bump();
+                }
+                Node::new(Element::num(num))
+            }
+            '∞' | '∅' => Node::new(Element::id_normal(c.to_string())),
+            c if c.is_ascii_alphabetic() => {
+                let word: String = self.src[self.pos - 1..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_alphabetic())
+                    .collect();
+                let func = FUNCTIONS
+                    .iter()
+                    .map(|f| f.0)
+                    .filter(|f| word.starts_with(f))
+                    .max_by_key(|f| f.len())
+                    .filter(|f| f.len() == word.len());
+                match func {
+                    Some(f) => {
+                        self.pos += f.len() - 1;
+                        Node::new(Element::id(f))
+                    }
+                    None => Node::new(Element::id(c.to_string())),
+                }
+            }
+            c if c.is_alphanumeric() => Node::new(Element::id(c.to_string())),
+            '〖' => self.group(None, true),
+            '├' => {
+                let open = self.peek().filter(|c| !c.is_whitespace());
+                if open.is_some() {
+                    self.bump();
+                }
+                self.group(open, false)
+            }
+            c if OPEN.contains(&c) => self.group(Some(c), false),
+            '"' => {
+                let mut text = String::new();
+                while let Some(c) = self.bump() {
+                    match c {
+                        '"' => break,
+                        '\\' if self.peek() == Some('"') => {
+                            self.bump();
+                            text.push('"');
+                        }
+                        c => text.push(c),
+                    }
+                }
+                Node::new(Element::text(text))
+            }
+            '√' | '∛' | '∜' => {
+                let mut nodes = self.operand().arg_nodes();
+                let e = match c {
+                    '∛' => Element::root(nodes_elem(nodes), Element::num("3")),
+                    '∜' => Element::root(nodes_elem(nodes), Element::num("4")),
+                    _ => match nodes.iter().position(|n| n.sep == Some('&')) {
+                        Some(i) => {
+                            let base = nodes.split_off(i + 1);
+                            nodes.pop();
+                            Element::root(nodes_elem(base), nodes_elem(nodes))
+                        }
+                        None => Element::sqrt(nodes_elem(nodes)),
+                    },
+                };
+                Node::new(e)
+            }
+            '■' | '█' => {
+                let nodes = self.operand().arg_nodes();
+                let rows = nodes.split(|n| n.sep == Some('@')).map(|row| {
+                    TableRow::new(
+                        row.split(|n| n.sep == Some('&'))
+                            .map(|cell| TableCell::new(cell.iter().map(Node::to_elem))),
+                    )
+                });
+                Node::new(Element::table(rows.collect::<Vec<_>>()))
+            }
+            '⟡' => Node::new(Element::phantom(into_vec(self.operand().arg()))),
+            '⏞' | '¯' => {
+                let mark = if c == '¯' { '‾' } else { c };
+                Node::new(Element::over(self.operand().arg(), Element::op(mark)))
+            }
+            '⏟' | '▁' => {
+                let mark = if c == '▁' { '_' } else { c };
+                Node::new(Element::under(self.operand().arg(), Element::op(mark)))
+            }
+            '\\' => {
+                let word: String = self.src[self.pos..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_alphabetic())
+                    .collect();
+                self.pos += word.len();
+                Node::new(Element::err(format!("\\{}", word)))
+            }
+            c => Node::new(Element::op(c)),
+        }
+    }
+
+    /// Parse a bracketed group, after the opening bracket. Invisible brackets
+    /// only group their contents.
+    fn group(&mut self, open: Option<char>, invisible: bool) -> Node {
+        let inner = self.expr(true);
+        let close = match self.bump() {
+            Some('┤') => {
+                let close = self.peek().filter(|c| !c.is_whitespace());
+                if close.is_some() {
+                    self.bump();
+                }
+                Some(close)
+            }
+            Some('〗') => Some(None),
+            Some(c) => Some(Some(c)),
+            None => None,
+        };
+        let Some(close) = close else {
+            // Unmatched brackets are kept as literal characters.
+            let mut elems: Vec<Element> = open.map(Element::op).into_iter().collect();
+            elems.extend(inner.into_iter().map(Node::into_elem));
+            return Node::new(Element::row(elems));
+        };
+        let e = if invisible && close.is_none() {
+            into_elem(inner.iter().map(Node::to_elem).collect())
+        } else {
+            let mut elems: Vec<Element> = open.map(fence).into_iter().collect();
+            elems.extend(inner.iter().map(Node::to_elem));
+            elems.extend(close.map(fence));
+            Element::row(elems)
+        };
+        Node {
+            e,
+            group: Some(inner),
+            sep: None,
+        }
+    }
+}
+
+/// Turn a run of factors into a fraction operand.
+fn run_elem(mut run: Vec<Node>) -> Element {
+    if run.len() == 1 {
+        run.pop().unwrap().arg()
+    } else {
+        nodes_elem(run)
+    }
+}
+
+fn superscript_digit(c: char) -> Option<char> {
+    match c {
+        '⁰' => Some('0'),
+        '¹' => Some('1'),
+        '²' => Some('2'),
+        '³' => Some('3'),
+        '⁴'..='⁹' => char::from_u32(c as u32 - '⁴' as u32 + '4' as u32),
+        _ => None,
+    }
+}
+
+fn subscript_digit(c: char) -> Option<char> {
+    match c {
+        '₀'..='₉' => char::from_u32(c as u32 - '₀' as u32 + '0' as u32),
+        _ => None,
+    }
+}
+
+/// Check if scripts on this element are placed as limits.
+fn takes_limits(e: &Element) -> bool {
+    match e.elem() {
+        MathElement::Id { t, .. } => FUNCTIONS.iter().any(|f| f.0 == t && f.1),
+        _ => op_char(e).is_some_and(|c| LARGE.contains(&c)),
+    }
+}
+
+impl Element {
+    /// Write the element out as UnicodeMath. Elements with no UnicodeMath
+    /// equivalent, like prescripts, are approximated.
+    pub fn to_unicodemath(&self) -> String {
+        let mut w = Writer::default();
+        match self.elem() {
+            MathElement::Row(elems) if fences(elems).is_none() => w.items(elems),
+            _ => w.element(self),
+        }
+        w.out
+    }
+}
+
+/// Get the stretchy fences around a row, if it has them.
+fn fences(elems: &[Element]) -> Option<(char, char)> {
+    let stretchy = |e: &Element| match e.elem() {
+        MathElement::Oper(op) if op.stretchy == Some(true) => Some(op.t),
+        _ => None,
+    };
+    if elems.len() < 2 {
+        return None;
+    }
+    Some((stretchy(elems.first()?)?, stretchy(elems.last()?)?))
+}
+
+fn is_op(e: &Element) -> bool {
+    op_char(e).is_some()
+}
+
+fn is_frac(e: &Element) -> bool {
+    matches!(e.elem(), MathElement::Frac { .. })
+}
+
+/// Get the combining mark for an accent.
+fn accent(e: &Element) -> Option<char> {
+    match e.elem() {
+        MathElement::Over {
+            over, accent: true, ..
+        } => {
+            let c = op_char(over)?;
+            ACCENTS.iter().find(|a| a.1 == c).map(|a| a.0)
+        }
+        _ => None,
+    }
+}
+
+/// Get the prefix operator for an over- or underscript.
+fn prefix(e: &Element) -> Option<char> {
+    match e.elem() {
+        MathElement::Over {
+            over,
+            accent: false,
+            ..
+        } => match op_char(over)? {
+            '⏞' => Some('⏞'),
+            '‾' => Some('¯'),
+            _ => None,
+        },
+        MathElement::Under {
+            under,
+            accent_under: false,
+            ..
+        } => match op_char(under)? {
+            '⏟' => Some('⏟'),
+            '_' => Some('▁'),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Check if the element is written as a single factor.
+fn is_factor(e: &Element) -> bool {
+    match e.elem() {
+        MathElement::Id { .. } | MathElement::Num(_) => true,
+        MathElement::Text(_) | MathElement::Str(_) | MathElement::Err(_) => true,
+        MathElement::Sqrt(_) | MathElement::Root { .. } | MathElement::Table { .. } => true,
+        MathElement::Row(_) | MathElement::Phantom(_) | MathElement::Padding(_) => true,
+        MathElement::Over { .. } | MathElement::Under { .. } => prefix(e).is_some(),
+        _ => false,
+    }
+}
+
+/// Check if the element is written as a factor or operator with accents or
+/// scripts on it.
+fn is_scripted(e: &Element) -> bool {
+    matches!(
+        e.elem(),
+        MathElement::Sub { .. }
+            | MathElement::Sup { .. }
+            | MathElement::SubSup { .. }
+            | MathElement::Over { .. }
+            | MathElement::Under { .. }
+            | MathElement::UnderOver { .. }
+    )
+}
+
+#[derive(Default)]
+struct Writer {
+    out: String,
+}
+
+impl Writer {
+    /// Write some text, keeping it from running together with a previous
+    /// number or function name.
+    fn push(&mut self, s: &str) {
+        let (Some(a), Some(b)) = (self.out.chars().last(), s.chars().next()) else {
+            self.out.push_str(s);
+            return;
+        };
+        if (a.is_ascii_digit() && b.is_ascii_digit())
+            || (a.is_ascii_alphabetic() && b.is_ascii_alphabetic())
+        {
+            self.out.push(' ');
+        }
+        self.out.push_str(s);
+    }
+
+    fn push_char(&mut self, c: char) {
+        self.push(c.encode_utf8(&mut [0; 4]));
+    }
+
+    /// Write the elements of a row. Fractions are set off with spaces so
+    /// their operands don't pick up neighboring factors.
+    fn items(&mut self, elems: &[Element]) {
+        for (i, e) in elems.iter().enumerate() {
+            if i > 0 {
+                let prev = &elems[i - 1];
+                if (is_frac(e) || is_frac(prev)) && !is_op(e) && !is_op(prev) {
+                    self.out.push(' ');
+                }
+            }
+            self.element(e);
+        }
+    }
+
+    /// Write the contents of an element, putting it in parentheses if it
+    /// isn't a single factor.
+    fn operand(&mut self, e: &Element, scripts: bool) {
+        let fenced = matches!(e.elem(), MathElement::Row(_));
+        if (is_factor(e) || (scripts && is_scripted(e))) && !fenced {
+            self.element(e);
+            return;
+        }
+        if !scripts && !fenced {
+            if let Some(c) = op_char(e).filter(|c| !"-+−±∓".contains(*c)) {
+                self.op(c);
+                return;
+            }
+        }
+        self.push("(");
+        match e.elem() {
+            MathElement::Row(elems) => self.items(elems),
+            _ => self.element(e),
+        }
+        self.push(")");
+    }
+
+    /// Write the base of an accent or script.
+    fn base(&mut self, e: &Element) {
+        if is_factor(e) || is_op(e) {
+            self.element(e);
+        } else {
+            self.push("〖");
+            self.element(e);
+            self.push("〗");
+        }
+    }
+
+    fn op(&mut self, c: char) {
+        if SPECIAL.contains(&c) {
+            self.push_char('\\');
+        }
+        self.push_char(c);
+    }
+
+    fn text(&mut self, t: &str) {
+        self.push(&format!("\"{}\"", t.replace('"', "\\\"")));
+    }
+
+    fn element(&mut self, e: &Element) {
+        match e.elem() {
+            MathElement::Op(_) | MathElement::Oper(_) | MathElement::ResolvedOper(_) => {
+                self.op(op_char(e).unwrap())
+            }
+            MathElement::Text(t) | MathElement::Str(t) => self.text(t),
+            // UnicodeMath has no way to mark an error, so it's set off with
+            // brackets.
+            MathElement::Err(t) | MathElement::Unknown { tag: t, .. } => {
+                self.text(&format!("⟦{}⟧", t))
+            }
+            MathElement::Id { t, .. } | MathElement::Num(t) => self.push(t),
+            MathElement::Space(s) => {
+                let w = match s.width {
+                    Some(Length::Em(w)) => w,
+                    Some(Length::Ex(w)) => w / 2.0,
+                    None => 0.0,
+                };
+                if w > 1.0 {
+                    (0..w.round() as usize).for_each(|_| self.out.push('\u{2003}'));
+                } else if let Some(&(c, _)) = SPACES
+                    .iter()
+                    .min_by(|a, b| (a.1 - w).abs().total_cmp(&(b.1 - w).abs()))
+                {
+                    self.out.push(c);
+                }
+            }
+            MathElement::Row(elems) => match fences(elems) {
+                Some((open, close)) => {
+                    if !OPEN.contains(&open) {
+                        self.push_char('├');
+                    }
+                    self.push_char(open);
+                    self.items(&elems[1..elems.len() - 1]);
+                    if !CLOSE.contains(&close) || matches!(close, '〗' | '┤') {
+                        self.push_char('┤');
+                    }
+                    self.push_char(close);
+                }
+                None => {
+                    self.push("〖");
+                    self.items(elems);
+                    self.push("〗");
+                }
+            },
+            MathElement::Phantom(elems) => {
+                self.push("⟡");
+                self.operand(&into_elem(elems.clone()), false);
+            }
+            MathElement::Padding(p) => {
+                self.push("〖");
+                self.items(&p.elems);
+                self.push("〗");
+            }
+            MathElement::Frac {
+                line_thickness,
+                num,
+                den,
+            } => {
+                self.operand(num, true);
+                self.push(if *line_thickness == Some(0.0) {
+                    "¦"
+                } else {
+                    "/"
+                });
+                self.operand(den, true);
+            }
+            MathElement::Sqrt(base) => {
+                self.push("√");
+                self.operand(base, false);
+            }
+            MathElement::Root { base, index } => match index.elem() {
+                MathElement::Num(n) if n == "3" || n == "4" => {
+                    self.push(if n == "3" { "∛" } else { "∜" });
+                    self.operand(base, false);
+                }
+                _ => {
+                    self.push("√(");
+                    self.element(index);
+                    self.push("&");
+                    self.element(base);
+                    self.push(")");
+                }
+            },
+            MathElement::Sub { base, sub } => {
+                self.base(base);
+                self.push("_");
+                self.operand(sub, false);
+            }
+            MathElement::Sup { base, sup } => {
+                self.base(base);
+                match op_char(sup).filter(|c| "′″‴⁗".contains(*c)) {
+                    Some(c) => self.push_char(c),
+                    None => {
+                        self.push("^");
+                        self.operand(sup, false);
+                    }
+                }
+            }
+            MathElement::SubSup { base, sub, sup } => {
+                self.base(base);
+                self.push("_");
+                self.operand(sub, false);
+                self.push("^");
+                self.operand(sup, false);
+            }
+            MathElement::Over { base, over, .. } => {
+                if let Some(mark) = accent(e) {
+                    self.base(base);
+                    self.out.push(mark);
+                } else if let Some(c) = prefix(e) {
+                    self.push_char(c);
+                    self.operand(base, false);
+                } else {
+                    self.base(base);
+                    self.push(if takes_limits(base) { "^" } else { "┴" });
+                    self.operand(over, false);
+                }
+            }
+            MathElement::Under { base, under, .. } => {
+                if let Some(c) = prefix(e) {
+                    self.push_char(c);
+                    self.operand(base, false);
+                } else {
+                    self.base(base);
+                    self.push(if takes_limits(base) { "_" } else { "┬" });
+                    self.operand(under, false);
+                }
+            }
+            MathElement::UnderOver {
+                base, under, over, ..
+            } => {
+                let limits = takes_limits(base);
+                self.base(base);
+                self.push(if limits { "_" } else { "┬" });
+                self.operand(under, false);
+                self.push(if limits { "^" } else { "┴" });
+                self.operand(over, false);
+            }
+            MathElement::MultiScript { base, post, pre } => {
+                for p in pre.iter() {
+                    self.push("〖〗_");
+                    self.operand(&p.sub, false);
+                    self.push("^");
+                    self.operand(&p.sup, false);
+                }
+                self.base(base);
+                for p in post.iter() {
+                    self.push("_");
+                    self.operand(&p.sub, false);
+                    self.push("^");
+                    self.operand(&p.sup, false);
+                }
+            }
+            MathElement::Table { rows } => {
+                self.push("■(");
+                for (i, row) in rows.iter().enumerate() {
+                    if i > 0 {
+                        self.push("@");
+                    }
+                    for (j, cell) in row.cells.iter().enumerate() {
+                        if j > 0 {
+                            self.push("&");
+                        }
+                        self.items(&cell.elems);
+                    }
+                }
+                self.push(")");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(s: &str) -> Element {
+        Element::row(s.chars().map(|c| match c {
+            '=' | '+' => Element::op(c),
+            c if c.is_ascii_digit() => Element::num(c.to_string()),
+            c => Element::id(c.to_string()),
+        }))
+    }
+
+    #[test]
+    fn fractions() {
+        assert_eq!(
+            parse("(a+b)/c"),
+            Element::frac(row("a+b"), Element::id("c"))
+        );
+        // Fraction operands are runs of factors, and spaces end them.
+        assert_eq!(
+            parse("2πr/3 + a/b c"),
+            Element::row([
+                Element::frac(row("2πr"), Element::num("3")),
+                Element::op('+'),
+                Element::frac(Element::id("a"), Element::id("b")),
+                Element::id("c"),
+            ])
+        );
+        assert_eq!(
+            parse("n¦k"),
+            Element::frac_thickness(Element::id("n"), Element::id("k"), 0.0)
+        );
+    }
+
+    #[test]
+    fn scripts() {
+        assert_eq!(
+            parse("∑_(i=1)^n▒i^2"),
+            Element::row([
+                Element::under_over(Element::op('∑'), row("i=1"), Element::id("n")),
+                Element::sup(Element::id("i"), Element::num("2")),
+            ])
+        );
+        assert_eq!(
+            parse("x_i^2 e^-x x²"),
+            Element::row([
+                Element::sub_sup(Element::id("x"), Element::id("i"), Element::num("2")),
+                Element::sup(
+                    Element::id("e"),
+                    Element::row([Element::op('−'), Element::id("x")])
+                ),
+                Element::sup(Element::id("x"), Element::num("2")),
+            ])
+        );
+        assert_eq!(
+            parse("lim┬(n→∞) x̂"),
+            Element::row([
+                Element::under(
+                    Element::id("lim"),
+                    Element::row([Element::id("n"), Element::op('→'), Element::id_normal("∞")])
+                ),
+                Element::over_accent(Element::id("x"), Element::op('^')),
+            ])
+        );
+    }
+
+    #[test]
+    fn radicals() {
+        assert_eq!(
+            parse("√(x^2)"),
+            Element::sqrt(Element::sup(Element::id("x"), Element::num("2")))
+        );
+        assert_eq!(
+            parse("∛x"),
+            Element::root(Element::id("x"), Element::num("3"))
+        );
+        assert_eq!(
+            parse("\\sqrt(n&a+b)"),
+            Element::root(row("a+b"), Element::id("n"))
+        );
+    }
+
+    #[test]
+    fn brackets() {
+        assert_eq!(
+            parse("(■(a&b@c&d))"),
+            Element::row([
+                fence('('),
+                Element::matrix([
+                    [Element::id("a"), Element::id("b")],
+                    [Element::id("c"), Element::id("d")],
+                ]),
+                fence(')'),
+            ])
+        );
+        assert_eq!(
+            parse("├[a┤) 〖b〗 \\("),
+            Element::row([
+                Element::row([fence('['), Element::id("a"), fence(')')]),
+                Element::id("b"),
+                Element::op('('),
+            ])
+        );
+    }
+
+    #[test]
+    fn write() {
+        let e = Element::row([
+            Element::frac(row("a+b"), Element::id("c")),
+            Element::op('−'),
+            Element::sup(
+                Element::frac(Element::num("1"), Element::num("2")),
+                Element::id("n"),
+            ),
+            Element::id("sin"),
+            Element::id("x"),
+        ]);
+        assert_eq!(e.to_unicodemath(), "(a+b)/c−〖1/2〗^n sin x");
+        assert_eq!(
+            Element::row([Element::id("x"), Element::op('/'), Element::op('-')]).to_unicodemath(),
+            "x\\/\\-"
+        );
+    }
+
+    #[test]
+    fn unicodemath_roundtrip() {
+        for src in [
+            "(a+b)/c",
+            "√(x^2) + ∛x + √(n&x)",
+            "■(a&b@c&d)",
+            "(■(1&0@0&1))",
+            "∑_(i=1)^n▒i^2",
+            "∫_0^1 f(x) dx",
+            "2πr/3 + a/b c + n¦k",
+            "x_i^2 + e^-x + f'(x) + x²",
+            "lim┬(n→∞) a_n",
+            "x̂ + 〖a+b〗̂ + ⏞(a+b)┴n + ¯x",
+            "\\alpha/\\beta \"if \\\"x\\\"\" x≥0",
+            "|x| ≤ ‖y‖ ├[a,b┤)",
+            "〖x^2〗^3 〖a/b〗_i",
+            "⟡(x+y) a\u{2003}b",
+        ] {
+            let e = parse(src);
+            let w = e.to_unicodemath();
+            assert_eq!(parse(&w), e, "{} -> {}", src, w);
+        }
+    }
+}
@@ -0,0 +1,1565 @@
+//! Parsing of Wolfram Language expressions, as copied out of Mathematica.
+//!
+//! Two forms are accepted. InputForm, like `Sum[i^2, {i, 1, n}]`, is laid out
+//! the way Mathematica's StandardForm displays it: `Power` becomes a
+//! superscript, `Divide` a fraction, `Sum` a big operator with limits, and
+//! other heads are applied to a fenced argument list. Box structures, like
+//! `RowBox[{"x", "+", "1"}]`, map directly onto the matching elements, so
+//! `FractionBox` becomes [`MathElement::Frac`], `GridBox` becomes
+//! [`MathElement::Table`], and so on.
+//!
+//! Named characters, like `\[Alpha]`, are understood in both forms. Parsing
+//! never fails: anything that can't be read becomes a [`MathElement::Err`]
+//! node, and the rest of the input is still converted.
+
+use crate::math::*;
+use crate::tree::{fenced, into_elem, into_vec, list, splice};
+
+const LOWEST: u8 = 0;
+const SET: u8 = 1;
+const LOGIC: u8 = 2;
+const RELATION: u8 = 3;
+const SUM: u8 = 4;
+const PRODUCT: u8 = 5;
+const PREFIX: u8 = 6;
+const POSTFIX: u8 = 7;
+const ATOM: u8 = 8;
+
+/// Named characters, with the text they stand for. Characters that Mathematica
+/// places in the private use area are replaced with their standard
+/// equivalents.
+const NAMED: &[(&str, &str)] = &[
+    ("Alpha", "α"),
+    ("Beta", "β"),
+    ("Gamma", "γ"),
+    ("Delta", "δ"),
+    ("Epsilon", "ϵ"),
+    ("CurlyEpsilon", "ε"),
+    ("Zeta", "ζ"),
+    ("Eta", "η"),
+    ("Theta", "θ"),
+    ("CurlyTheta", "ϑ"),
+    ("Iota", "ι"),
+    ("Kappa", "κ"),
+    ("Lambda", "λ"),
+    ("Mu", "μ"),
+    ("Nu", "ν"),
+    ("Xi", "ξ"),
+    ("Omicron", "ο"),
+    ("Pi", "π"),
+    ("CurlyPi", "ϖ"),
+    ("Rho", "ρ"),
+    ("Sigma", "σ"),
+    ("FinalSigma", "ς"),
+    ("Tau", "τ"),
+    ("Upsilon", "υ"),
+    ("Phi", "ϕ"),
+    ("CurlyPhi", "φ"),
+    ("Chi", "χ"),
+    ("Psi", "ψ"),
+    ("Omega", "ω"),
+    ("CapitalGamma", "Γ"),
+    ("CapitalDelta", "Δ"),
+    ("CapitalTheta", "Θ"),
+    ("CapitalLambda", "Λ"),
+    ("CapitalXi", "Ξ"),
+    ("CapitalPi", "Π"),
+    ("CapitalSigma", "Σ"),
+    ("CapitalUpsilon", "Υ"),
+    ("CapitalPhi", "Φ"),
+    ("CapitalPsi", "Ψ"),
+    ("CapitalOmega", "Ω"),
+    ("Aleph", "ℵ"),
+    ("HBar", "ℏ"),
+    ("Infinity", "∞"),
+    ("Degree", "°"),
+    ("ImaginaryI", "ⅈ"),
+    ("ExponentialE", "ⅇ"),
+    ("DifferentialD", "ⅆ"),
+    ("PartialD", "∂"),
+    ("Del", "∇"),
+    ("EmptySet", "∅"),
+    ("DoubleStruckCapitalC", "ℂ"),
+    ("DoubleStruckCapitalN", "ℕ"),
+    ("DoubleStruckCapitalQ", "ℚ"),
+    ("DoubleStruckCapitalR", "ℝ"),
+    ("DoubleStruckCapitalZ", "ℤ"),
+    ("Integral", "∫"),
+    ("Sum", "∑"),
+    ("Product", "∏"),
+    ("Sqrt", "√"),
+    ("Equal", "=="),
+    ("LongEqual", "=="),
+    ("NotEqual", "≠"),
+    ("LessEqual", "≤"),
+    ("GreaterEqual", "≥"),
+    ("TildeTilde", "≈"),
+    ("Congruent", "≡"),
+    ("Proportional", "∝"),
+    ("Rule", "->"),
+    ("RuleDelayed", ":>"),
+    ("RightArrow", "→"),
+    ("RightVector", "⇀"),
+    ("LeftArrow", "←"),
+    ("DoubleRightArrow", "⇒"),
+    ("Implies", "⇒"),
+    ("Equivalent", "⇔"),
+    ("Element", "∈"),
+    ("NotElement", "∉"),
+    ("Subset", "⊂"),
+    ("SubsetEqual", "⊆"),
+    ("Union", "⋃"),
+    ("Intersection", "⋂"),
+    ("And", "∧"),
+    ("Or", "∨"),
+    ("Not", "¬"),
+    ("ForAll", "∀"),
+    ("Exists", "∃"),
+    ("Times", "×"),
+    ("Cross", "×"),
+    ("CenterDot", "·"),
+    ("Divide", "÷"),
+    ("PlusMinus", "±"),
+    ("MinusPlus", "∓"),
+    ("Minus", "−"),
+    ("CircleTimes", "⊗"),
+    ("CirclePlus", "⊕"),
+    ("Star", "⋆"),
+    ("Bullet", "•"),
+    ("Dagger", "†"),
+    ("Angle", "∠"),
+    ("Perpendicular", "⟂"),
+    ("Prime", "′"),
+    ("DoublePrime", "″"),
+    ("Ellipsis", "…"),
+    ("CenterEllipsis", "⋯"),
+    ("Transpose", "ᵀ"),
+    ("LeftDoubleBracket", "⟦"),
+    ("RightDoubleBracket", "⟧"),
+    ("LeftAngleBracket", "⟨"),
+    ("RightAngleBracket", "⟩"),
+    ("LeftFloor", "⌊"),
+    ("RightFloor", "⌋"),
+    ("LeftCeiling", "⌈"),
+    ("RightCeiling", "⌉"),
+    ("InvisibleTimes", "\u{2062}"),
+    ("InvisibleApplication", "\u{2061}"),
+    ("InvisibleComma", "\u{2063}"),
+    ("InvisibleSpace", "\u{200b}"),
+    ("ThinSpace", "\u{2009}"),
+    ("MediumSpace", "\u{205f}"),
+    ("ThickSpace", "\u{2005}"),
+    ("NegativeThinSpace", ""),
+    ("SpaceIndicator", " "),
+];
+
+/// Operators, longest first so they're matched greedily.
+const OPS: &[&str] = &[
+    "===", "=!=", ":=", "->", ":>", "==", "!=", "<=", ">=", "&&", "||", "//", "/.", "+", "-", "*",
+    "/", "^", "=", "<", ">", "!", "'", "@", ".", ";", ",", "(", ")", "[", "]", "{", "}", "&", "#",
+    "%", "∈", "∉", "⊂", "⊆", "⋃", "⋂", "·", "±", "≈", "≡", "⇒", "⇔",
+];
+
+/// Characters that are another spelling of an operator.
+const ALIASES: &[(char, &str)] = &[
+    ('≤', "<="),
+    ('≥', ">="),
+    ('≠', "!="),
+    ('→', "->"),
+    ('×', "*"),
+    ('÷', "/"),
+    ('−', "-"),
+    ('∧', "&&"),
+    ('∨', "||"),
+    ('¬', "!"),
+    ('∪', "⋃"),
+    ('∩', "⋂"),
+    ('\u{2062}', "*"),
+];
+
+/// Binary operators, with their heads, Wolfram precedence, and whether they're
+/// right associative.
+const BINARY: &[(&str, &str, u16, bool)] = &[
+    (";", "CompoundExpression", 10, false),
+    ("=", "Set", 40, true),
+    (":=", "SetDelayed", 40, true),
+    ("/.", "ReplaceAll", 110, false),
+    ("->", "Rule", 120, true),
+    (":>", "RuleDelayed", 120, true),
+    ("⇒", "Implies", 200, true),
+    ("⇔", "Equivalent", 205, false),
+    ("||", "Or", 215, false),
+    ("&&", "And", 216, false),
+    ("∈", "Element", 250, false),
+    ("∉", "NotElement", 250, false),
+    ("⊂", "Subset", 250, false),
+    ("⊆", "SubsetEqual", 250, false),
+    ("==", "Equal", 290, false),
+    ("!=", "Unequal", 290, false),
+    ("<", "Less", 290, false),
+    ("<=", "LessEqual", 290, false),
+    (">", "Greater", 290, false),
+    (">=", "GreaterEqual", 290, false),
+    ("===", "SameQ", 290, false),
+    ("=!=", "UnsameQ", 290, false),
+    ("≈", "TildeTilde", 290, false),
+    ("≡", "Congruent", 290, false),
+    ("⋃", "Union", 300, false),
+    ("⋂", "Intersection", 305, false),
+    ("+", "Plus", 310, false),
+    ("-", "Plus", 310, false),
+    ("±", "PlusMinus", 310, false),
+    ("*", "Times", 400, false),
+    ("·", "CenterDot", 410, false),
+    ("/", "Divide", 470, false),
+    (".", "Dot", 490, false),
+    ("^", "Power", 590, true),
+];
+
+const TIMES: u16 = 400;
+const MINUS: u16 = 480;
+const NOT: u16 = 230;
+const FACTORIAL: u16 = 610;
+const PREFIX_APPLY: u16 = 640;
+const POSTFIX_APPLY: u16 = 70;
+
+/// Heads whose nested uses are flattened into one list of arguments.
+const FLAT: &[&str] = &[
+    "CompoundExpression",
+    "Or",
+    "And",
+    "Equal",
+    "Union",
+    "Intersection",
+    "Plus",
+    "Times",
+    "Dot",
+];
+
+/// Infix heads, with the operator they're written with and its precedence.
+const INFIX: &[(&str, char, u8)] = &[
+    ("CompoundExpression", ';', LOWEST),
+    ("Set", '=', SET),
+    ("SetDelayed", '≔', SET),
+    ("Rule", '→', SET),
+    ("RuleDelayed", '⧴', SET),
+    ("Implies", '⇒', SET),
+    ("Equivalent", '⇔', SET),
+    ("Or", '∨', LOGIC),
+    ("And", '∧', LOGIC),
+    ("Element", '∈', RELATION),
+    ("NotElement", '∉', RELATION),
+    ("Subset", '⊂', RELATION),
+    ("SubsetEqual", '⊆', RELATION),
+    ("Equal", '=', RELATION),
+    ("Unequal", '≠', RELATION),
+    ("Less", '<', RELATION),
+    ("LessEqual", '≤', RELATION),
+    ("Greater", '>', RELATION),
+    ("GreaterEqual", '≥', RELATION),
+    ("SameQ", '≡', RELATION),
+    ("UnsameQ", '≢', RELATION),
+    ("TildeTilde", '≈', RELATION),
+    ("Congruent", '≡', RELATION),
+    ("Union", '⋃', SUM),
+    ("PlusMinus", '±', SUM),
+    ("Intersection", '⋂', PRODUCT),
+    ("CenterDot", '·', PRODUCT),
+    ("Cross", '×', PRODUCT),
+    ("Dot", '.', PRODUCT),
+];
+
+/// Symbols, with the identifier they're written as, and whether it's upright.
+const SYMBOLS: &[(&str, &str, bool)] = &[
+    ("Pi", "π", false),
+    ("E", "e", true),
+    ("ⅇ", "e", true),
+    ("I", "i", true),
+    ("ⅈ", "i", true),
+    ("ⅆ", "d", true),
+    ("Infinity", "∞", true),
+    ("∞", "∞", true),
+    ("Degree", "°", true),
+    ("GoldenRatio", "ϕ", false),
+    ("EulerGamma", "γ", false),
+    ("True", "True", true),
+    ("False", "False", true),
+    ("Null", "", true),
+];
+
+/// Functions, with the names they're written as.
+const FUNCTIONS: &[(&str, &str)] = &[
+    ("Sin", "sin"),
+    ("Cos", "cos"),
+    ("Tan", "tan"),
+    ("Cot", "cot"),
+    ("Sec", "sec"),
+    ("Csc", "csc"),
+    ("ArcSin", "arcsin"),
+    ("ArcCos", "arccos"),
+    ("ArcTan", "arctan"),
+    ("Sinh", "sinh"),
+    ("Cosh", "cosh"),
+    ("Tanh", "tanh"),
+    ("Coth", "coth"),
+    ("Log", "log"),
+    ("Max", "max"),
+    ("Min", "min"),
+    ("GCD", "gcd"),
+    ("LCM", "lcm"),
+    ("Det", "det"),
+    ("Tr", "tr"),
+    ("Sign", "sgn"),
+    ("Arg", "arg"),
+    ("Re", "Re"),
+    ("Im", "Im"),
+];
+
+/// Brackets that a single argument is written between.
+const BRACKETS: &[(&str, char, char)] = &[
+    ("Abs", '|', '|'),
+    ("Norm", '‖', '‖'),
+    ("Floor", '⌊', '⌋'),
+    ("Ceiling", '⌈', '⌉'),
+    ("AngleBracket", '⟨', '⟩'),
+];
+
+/// Heads that only change how their first argument is displayed.
+const WRAPPERS: &[&str] = &[
+    "HoldForm",
+    "Hold",
+    "Defer",
+    "TraditionalForm",
+    "StandardForm",
+    "InputForm",
+    "TeXForm",
+    "DisplayForm",
+    "Unevaluated",
+];
+
+/// Boxes that are displayed as their first argument.
+const BOX_WRAPPERS: &[&str] = &[
+    "BoxData",
+    "Cell",
+    "FormBox",
+    "TagBox",
+    "InterpretationBox",
+    "TooltipBox",
+    "AdjustmentBox",
+    "FrameBox",
+    "ErrorBox",
+    "ButtonBox",
+    "PaneBox",
+    "ItemBox",
+    "PanelBox",
+];
+
+/// Operator strings in boxes, with the character they're displayed as.
+const BOX_OPS: &[(&str, char)] = &[
+    ("->", '→'),
+    (":>", '⧴'),
+    ("==", '='),
+    ("!=", '≠'),
+    ("<=", '≤'),
+    (">=", '≥'),
+    (":=", '≔'),
+    ("&&", '∧'),
+    ("||", '∨'),
+    ("===", '≡'),
+    ("-", '−'),
+    ("*", '×'),
+    (" ", '\u{2062}'),
+];
+
+/// Accent strings for overscripts and underscripts.
+const ACCENTS: &[(&str, char)] = &[
+    ("^", '^'),
+    ("~", '~'),
+    (".", '˙'),
+    ("..", '¨'),
+    ("_", '¯'),
+    ("¯", '¯'),
+    ("→", '→'),
+    ("⇀", '→'),
+    ("⏞", '⏞'),
+    ("⏟", '⏟'),
+];
+
+/// Parse a Wolfram Language expression, in InputForm or as boxes.
+pub fn parse(src: &str) -> Element {
+    let src = named(src);
+    let mut p = Parser {
+        toks: lex(&src),
+        pos: 0,
+    };
+    let mut elems = Vec::new();
+    while p.peek().is_some() {
+        let x = p.expr(0);
+        if is_box(&x) {
+            elems.extend(into_vec(boxes(&x)));
+        } else {
+            elems.extend(into_vec(render(&x).0));
+        }
+    }
+    into_elem(elems)
+}
+
+/// Replace named characters, like `\[Alpha]`, and hexadecimal escapes, like
+/// `\:03b1`. Unknown names are left as they are.
+fn named(src: &str) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut rest = src;
+    while let Some(i) = rest.find('\\') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if let Some((name, _)) = rest.strip_prefix("\\[").and_then(|r| r.split_once(']')) {
+            if let Some((_, t)) = NAMED.iter().find(|(n, _)| *n == name) {
+                out.push_str(t);
+                rest = &rest[name.len() + 3..];
+                continue;
+            }
+        }
+        let hex = rest.get(2..6).filter(|_| rest.starts_with("\\:"));
+        if let Some(c) = hex
+            .and_then(|h| u32::from_str_radix(h, 16).ok())
+            .and_then(char::from_u32)
+        {
+            out.push(c);
+            rest = &rest[6..];
+            continue;
+        }
+        // Keep escaped characters together, so `\\` and `\"` stay as they are.
+        let len = rest[1..].chars().next().map_or(0, char::len_utf8);
+        out.push_str(&rest[..1 + len]);
+        rest = &rest[1 + len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Tok {
+    Num {
+        t: String,
+        base: Option<String>,
+        exp: Option<String>,
+    },
+    Sym(String),
+    Str(String),
+    Op(&'static str),
+    Err(String),
+}
+
+fn lex(src: &str) -> Vec<Tok> {
+    let mut toks = Vec::new();
+    let mut rest = src;
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+        } else if rest.starts_with("(*") {
+            // Comments nest.
+            let mut depth = 0;
+            let mut end = rest.len();
+            let mut i = 0;
+            while i < rest.len() {
+                if rest[i..].starts_with("(*") {
+                    depth += 1;
+                    i += 2;
+                } else if rest[i..].starts_with("*)") {
+                    depth -= 1;
+                    i += 2;
+                    if depth == 0 {
+                        end = i;
+                        break;
+                    }
+                } else {
+                    i += rest[i..].chars().next().map_or(1, char::len_utf8);
+                }
+            }
+            rest = &rest[end..];
+        } else if c.is_ascii_digit()
+            || (c == '.' && rest[1..].starts_with(|c: char| c.is_ascii_digit()))
+        {
+            let (tok, len) = number(rest);
+            toks.push(tok);
+            rest = &rest[len..];
+        } else if c.is_alphabetic() || c == '$' {
+            let len = rest
+                .find(|c: char| !(c.is_alphanumeric() || matches!(c, '$' | '`' | '_')))
+                .unwrap_or(rest.len());
+            toks.push(Tok::Sym(rest[..len].to_string()));
+            rest = &rest[len..];
+        } else if c == '"' {
+            let mut s = String::new();
+            let mut chars = rest[1..].char_indices();
+            let mut end = None;
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '"' => {
+                        end = Some(i + 2);
+                        break;
+                    }
+                    '\\' => match chars.next() {
+                        Some((_, 'n')) => s.push('\n'),
+                        Some((_, 't')) => s.push('\t'),
+                        Some((_, c @ ('"' | '\\'))) => s.push(c),
+                        Some((_, c)) => {
+                            s.push('\\');
+                            s.push(c);
+                        }
+                        None => s.push('\\'),
+                    },
+                    c => s.push(c),
+                }
+            }
+            match end {
+                Some(end) => {
+                    toks.push(Tok::Str(s));
+                    rest = &rest[end..];
+                }
+                None => {
+                    toks.push(Tok::Err("unterminated string".into()));
+                    rest = "";
+                }
+            }
+        } else if let Some(op) = OPS.iter().find(|op| rest.starts_with(**op)) {
+            toks.push(Tok::Op(op));
+            rest = &rest[op.len()..];
+        } else {
+            toks.push(match ALIASES.iter().find(|(a, _)| *a == c) {
+                Some((_, op)) => Tok::Op(op),
+                None if !c.is_ascii() => Tok::Sym(c.to_string()),
+                None => Tok::Err(format!("unexpected `{}`", c)),
+            });
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    toks
+}
+
+/// Lex a number, with an optional base (`16^^ff`), precision marks (`1.5`20`),
+/// and exponent (`1.5*^-3`).
+fn number(src: &str) -> (Tok, usize) {
+    let digits = |s: &str, radix: u32| {
+        s.find(|c: char| !(c.is_digit(radix) || c == '.'))
+            .unwrap_or(s.len())
+    };
+    let mut len = digits(src, 10);
+    let mut t = src[..len].to_string();
+    let mut base = None;
+    if src[len..].starts_with("^^") {
+        let n = digits(&src[len + 2..], 36);
+        base = Some(std::mem::replace(
+            &mut t,
+            src[len + 2..len + 2 + n].to_string(),
+        ));
+        len += 2 + n;
+    }
+    if src[len..].starts_with('`') {
+        len += src[len..]
+            .find(|c: char| !(c == '`' || c.is_ascii_digit() || c == '.'))
+            .unwrap_or(src.len() - len);
+    }
+    let mut exp = None;
+    if let Some(rest) = src[len..].strip_prefix("*^") {
+        let sign = usize::from(rest.starts_with('-'));
+        let n = rest[sign..]
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len() - sign);
+        if n > 0 {
+            exp = Some(rest[..sign + n].to_string());
+            len += 2 + sign + n;
+        }
+    }
+    (Tok::Num { t, base, exp }, len)
+}
+
+/// A parsed expression, in the shape of its FullForm.
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Sym(String),
+    Num(String),
+    Str(String),
+    Call(Box<Expr>, Vec<Expr>),
+    Err(String),
+}
+
+impl Expr {
+    fn call(head: &str, args: Vec<Expr>) -> Self {
+        Expr::Call(Box::new(Expr::Sym(head.into())), args)
+    }
+
+    fn num(t: &str) -> Self {
+        Expr::Num(t.into())
+    }
+
+    /// Get the head name and arguments, if this is a call with a symbol head.
+    fn head(&self) -> Option<(&str, &[Expr])> {
+        match self {
+            Expr::Call(head, args) => match head.as_ref() {
+                Expr::Sym(name) => Some((name.as_str(), args.as_slice())),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Get the arguments of a call to the given head.
+    fn args_of(&self, name: &str) -> Option<&[Expr]> {
+        self.head().filter(|(h, _)| *h == name).map(|(_, a)| a)
+    }
+}
+
+/// Combine two operands, flattening nested uses of associative heads.
+fn join(head: &str, lhs: Expr, rhs: Expr) -> Expr {
+    match lhs {
+        Expr::Call(h, mut args) if FLAT.contains(&head) && *h == Expr::Sym(head.into()) => {
+            args.push(rhs);
+            Expr::Call(h, args)
+        }
+        lhs => Expr::call(head, vec![lhs, rhs]),
+    }
+}
+
+fn negate(x: Expr) -> Expr {
+    match x {
+        Expr::Num(t) => match t.strip_prefix('-') {
+            Some(t) => Expr::num(t),
+            None => Expr::Num(format!("-{}", t)),
+        },
+        x => Expr::call("Minus", vec![x]),
+    }
+}
+
+/// Get the positive form of a negative term, like `-x` or `-2 a`.
+fn negative(x: &Expr) -> Option<Expr> {
+    match x {
+        Expr::Num(t) => t.strip_prefix('-').map(Expr::num),
+        _ => {
+            if let Some([x]) = x.args_of("Minus") {
+                return Some(x.clone());
+            }
+            let [first, rest @ ..] = x.args_of("Times")? else {
+                return None;
+            };
+            let first = negative(first)?;
+            let mut args = Vec::new();
+            if first != Expr::num("1") || rest.is_empty() {
+                args.push(first);
+            }
+            args.extend(rest.iter().cloned());
+            Some(match args.len() {
+                1 => args.pop().unwrap(),
+                _ => Expr::call("Times", args),
+            })
+        }
+    }
+}
+
+struct Parser {
+    toks: Vec<Tok>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Tok> {
+        self.toks.get(self.pos)
+    }
+
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.peek() {
+            Some(Tok::Op(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        let found = self.peek_op() == Some(op);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    /// Parse an expression whose operators bind at least as tightly as `min`.
+    fn expr(&mut self, min: u16) -> Expr {
+        let mut lhs = self.prefix();
+        loop {
+            let op = match self.peek() {
+                Some(Tok::Op(op)) => *op,
+                Some(Tok::Num { .. } | Tok::Sym(_) | Tok::Str(_)) if TIMES >= min => {
+                    // Juxtaposition is multiplication.
+                    let rhs = self.expr(TIMES + 1);
+                    lhs = join("Times", lhs, rhs);
+                    continue;
+                }
+                _ => break,
+            };
+            match op {
+                "[" => lhs = self.call(lhs),
+                "'" => {
+                    let mut n = 0;
+                    while self.eat("'") {
+                        n += 1;
+                    }
+                    let d = Expr::call("Derivative", vec![Expr::Num(n.to_string())]);
+                    lhs = Expr::Call(Box::new(d), vec![lhs]);
+                }
+                "!" if FACTORIAL >= min => {
+                    self.pos += 1;
+                    let head = if self.eat("!") {
+                        "Factorial2"
+                    } else {
+                        "Factorial"
+                    };
+                    lhs = Expr::call(head, vec![lhs]);
+                }
+                "@" if PREFIX_APPLY >= min => {
+                    self.pos += 1;
+                    let rhs = self.expr(PREFIX_APPLY);
+                    lhs = Expr::Call(Box::new(lhs), vec![rhs]);
+                }
+                "//" if POSTFIX_APPLY >= min => {
+                    self.pos += 1;
+                    let f = self.expr(POSTFIX_APPLY + 1);
+                    lhs = Expr::Call(Box::new(f), vec![lhs]);
+                }
+                "(" | "{" if TIMES >= min => {
+                    let rhs = self.expr(TIMES + 1);
+                    lhs = join("Times", lhs, rhs);
+                }
+                op => {
+                    let Some(&(_, head, prec, right)) = BINARY.iter().find(|(o, ..)| *o == op)
+                    else {
+                        break;
+                    };
+                    if prec < min {
+                        break;
+                    }
+                    self.pos += 1;
+                    // A trailing `;` discards the result.
+                    if op == ";" && matches!(self.peek_op(), None | Some(")" | "]" | "}" | ",")) {
+                        lhs = join(head, lhs, Expr::Sym("Null".into()));
+                        continue;
+                    }
+                    let rhs = self.expr(if right { prec } else { prec + 1 });
+                    lhs = match op {
+                        "-" => join(head, lhs, negate(rhs)),
+                        _ => join(head, lhs, rhs),
+                    };
+                }
+            }
+        }
+        lhs
+    }
+
+    fn prefix(&mut self) -> Expr {
+        let Some(tok) = self.peek().cloned() else {
+            return Expr::Err("unexpected end of input".into());
+        };
+        self.pos += 1;
+        match tok {
+            Tok::Num { t, base, exp } => {
+                let mut x = Expr::Num(t);
+                if let Some(base) = base {
+                    x = Expr::call("Subscript", vec![x, Expr::Num(base)]);
+                }
+                if let Some(exp) = exp {
+                    let pow = Expr::call("Power", vec![Expr::num("10"), Expr::Num(exp)]);
+                    x = Expr::call("Times", vec![x, pow]);
+                }
+                x
+            }
+            Tok::Sym(s) => Expr::Sym(s),
+            Tok::Str(s) => Expr::Str(s),
+            Tok::Err(msg) => Expr::Err(msg),
+            Tok::Op("(") => {
+                if self.eat(")") {
+                    return Expr::Err("empty parentheses".into());
+                }
+                let x = self.expr(0);
+                if self.eat(")") {
+                    x
+                } else {
+                    Expr::Err("expected `)`".into())
+                }
+            }
+            Tok::Op("{") => Expr::call("List", self.args("}")),
+            Tok::Op("-") => negate(self.expr(MINUS)),
+            Tok::Op("+") => self.expr(MINUS),
+            Tok::Op("!") => Expr::call("Not", vec![self.expr(NOT)]),
+            Tok::Op(op @ ("#" | "%")) => Expr::Sym(op.into()),
+            Tok::Op(op) => Expr::Err(format!("unexpected `{}`", op)),
+        }
+    }
+
+    /// Parse the arguments of a call, or the `[[...]]` of a part.
+    fn call(&mut self, head: Expr) -> Expr {
+        self.pos += 1;
+        if self.eat("[") {
+            let mut args = vec![head];
+            args.extend(self.args("]"));
+            if !self.eat("]") {
+                args.push(Expr::Err("expected `]]`".into()));
+            }
+            return Expr::call("Part", args);
+        }
+        Expr::Call(Box::new(head), self.args("]"))
+    }
+
+    /// Parse comma-separated arguments, up to and including the closing
+    /// bracket. Missing arguments are `Null`.
+    fn args(&mut self, close: &str) -> Vec<Expr> {
+        let mut args = Vec::new();
+        if self.eat(close) {
+            return args;
+        }
+        loop {
+            let at_end = matches!(self.peek_op(), Some(op) if op == "," || op == close);
+            args.push(if at_end {
+                Expr::Sym("Null".into())
+            } else {
+                self.expr(0)
+            });
+            if self.eat(",") {
+                continue;
+            }
+            if !self.eat(close) {
+                args.push(Expr::Err(format!("expected `{}`", close)));
+            }
+            return args;
+        }
+    }
+}
+
+fn symbol(name: &str) -> Element {
+    // Drop any context, like `Global``.
+    let name = name.rsplit('`').next().unwrap_or(name);
+    match SYMBOLS.iter().find(|(n, ..)| *n == name) {
+        Some((_, t, true)) => Element::id_normal(*t),
+        Some((_, t, false)) => Element::id(*t),
+        None => Element::id(name),
+    }
+}
+
+/// Render an InputForm expression, along with its precedence.
+fn render(x: &Expr) -> (Element, u8) {
+    match x {
+        Expr::Sym(s) => (symbol(s), ATOM),
+        Expr::Num(t) => match t.strip_prefix('-') {
+            Some(t) => (Element::row([Element::op('−'), Element::num(t)]), PREFIX),
+            None => (Element::num(t), ATOM),
+        },
+        Expr::Str(s) => (Element::text(s), ATOM),
+        Expr::Err(msg) => (Element::err(msg), ATOM),
+        Expr::Call(head, args) => match head.as_ref() {
+            Expr::Sym(name) => apply(name, args),
+            head => {
+                let f = match head.head() {
+                    Some(("Derivative", [Expr::Num(n)])) if args.len() == 1 => {
+                        // `f'` is `Derivative[1][f]`.
+                        let prime = match n.as_str() {
+                            "1" => Element::op('′'),
+                            "2" => Element::op('″'),
+                            "3" => Element::op('‴'),
+                            n => fenced(Some('('), vec![Element::num(n)], Some(')')),
+                        };
+                        return (Element::sup(wrap(&args[0], ATOM), prime), ATOM);
+                    }
+                    _ => wrap(head, ATOM),
+                };
+                func(f, args)
+            }
+        },
+    }
+}
+
+fn apply(name: &str, args: &[Expr]) -> (Element, u8) {
+    match (name, args) {
+        ("Plus", [_, _, ..]) => {
+            let mut elems = Vec::new();
+            for (i, a) in args.iter().enumerate() {
+                if i == 0 {
+                    operand(&mut elems, a, SUM);
+                    continue;
+                }
+                // Negative terms are written as subtraction.
+                match negative(a) {
+                    Some(a) => {
+                        elems.push(Element::op('−'));
+                        operand(&mut elems, &a, PRODUCT);
+                    }
+                    None => {
+                        elems.push(Element::op('+'));
+                        operand(&mut elems, a, SUM);
+                    }
+                }
+            }
+            (Element::row(elems), SUM)
+        }
+        ("Times", [_, _, ..]) => {
+            if let Some(x) = negative(&Expr::call("Times", args.to_vec())) {
+                let (e, prec) = render(&x);
+                let mut elems = vec![Element::op('−')];
+                splice(&mut elems, e, prec, PRODUCT);
+                return (Element::row(elems), PRODUCT);
+            }
+            // Factors with negative powers go in the denominator.
+            let mut num = Vec::new();
+            let mut den = Vec::new();
+            for a in args {
+                match a.args_of("Power") {
+                    Some([b, Expr::Num(e)]) if e.starts_with('-') => match &e[1..] {
+                        "1" => den.push(b.clone()),
+                        e => den.push(Expr::call("Power", vec![b.clone(), Expr::num(e)])),
+                    },
+                    _ => num.push(a.clone()),
+                }
+            }
+            if den.is_empty() {
+                times(&num)
+            } else {
+                (Element::frac(times(&num).0, times(&den).0), ATOM)
+            }
+        }
+        ("Minus", [x]) => {
+            let (e, prec) = render(x);
+            let mut elems = vec![Element::op('−')];
+            splice(&mut elems, e, prec, PRODUCT);
+            (Element::row(elems), prec.clamp(PRODUCT, PREFIX))
+        }
+        ("Divide" | "Rational", [n, d]) => (Element::frac(render(n).0, render(d).0), ATOM),
+        ("Power", [b, e]) => {
+            let root = e.args_of("Rational").or_else(|| e.args_of("Divide"));
+            match root {
+                Some([Expr::Num(one), n]) if one == "1" => {
+                    if *n == Expr::num("2") {
+                        (Element::sqrt(render(b).0), ATOM)
+                    } else {
+                        (Element::root(render(b).0, render(n).0), ATOM)
+                    }
+                }
+                _ => match b.args_of("Subscript") {
+                    Some([base, sub]) => (
+                        Element::sub_sup(wrap(base, ATOM), render(sub).0, render(e).0),
+                        ATOM,
+                    ),
+                    _ => (Element::sup(wrap(b, ATOM), render(e).0), ATOM),
+                },
+            }
+        }
+        ("Sqrt", [x]) => (Element::sqrt(render(x).0), ATOM),
+        ("Surd", [x, n]) => (Element::root(render(x).0, render(n).0), ATOM),
+        ("CubeRoot", [x]) => (Element::root(render(x).0, Element::num("3")), ATOM),
+        ("Exp", [x]) => (Element::sup(symbol("E"), render(x).0), ATOM),
+        ("Subscript", [b, subs @ ..]) if !subs.is_empty() => {
+            let sub = into_elem(list(subs.iter().map(|s| render(s).0)));
+            (Element::sub(wrap(b, ATOM), sub), ATOM)
+        }
+        ("Superscript", [b, s]) => (Element::sup(wrap(b, ATOM), render(s).0), ATOM),
+        ("Subsuperscript", [b, sub, sup]) => (
+            Element::sub_sup(wrap(b, ATOM), render(sub).0, render(sup).0),
+            ATOM,
+        ),
+        ("Part", [x, parts @ ..]) => {
+            let parts = list(parts.iter().map(|p| render(p).0));
+            let mut elems = vec![wrap(x, ATOM)];
+            elems.push(fenced(Some('⟦'), parts, Some('⟧')));
+            (Element::row(elems), POSTFIX)
+        }
+        ("Factorial", [x]) => (Element::row([wrap(x, ATOM), Element::op('!')]), POSTFIX),
+        ("Factorial2", [x]) => (
+            Element::row([wrap(x, ATOM), Element::op('!'), Element::op('!')]),
+            POSTFIX,
+        ),
+        ("Not", [x]) => (Element::row([Element::op('¬'), wrap(x, PREFIX)]), PREFIX),
+        ("Binomial", [n, k]) => {
+            let frac = Element::frac_thickness(render(n).0, render(k).0, 0.0);
+            (fenced(Some('('), vec![frac], Some(')')), ATOM)
+        }
+        ("List", _) => {
+            let items = list(args.iter().map(|a| render(a).0));
+            (fenced(Some('{'), items, Some('}')), ATOM)
+        }
+        ("MatrixForm" | "TableForm", [m, ..]) => match matrix(m) {
+            Some(table) if name == "MatrixForm" => {
+                (fenced(Some('('), vec![table], Some(')')), ATOM)
+            }
+            Some(table) => (table, ATOM),
+            None => render(m),
+        },
+        ("Sum", [body, iters @ ..]) if !iters.is_empty() => big_op('∑', body, iters),
+        ("Product", [body, iters @ ..]) if !iters.is_empty() => big_op('∏', body, iters),
+        ("Integrate", [body, vars @ ..]) if !vars.is_empty() => {
+            let mut elems = Vec::new();
+            for v in vars {
+                let op = match v.args_of("List") {
+                    Some([_, lo, hi]) => {
+                        Element::sub_sup(Element::op('∫'), render(lo).0, render(hi).0)
+                    }
+                    _ => Element::op('∫'),
+                };
+                elems.push(op);
+            }
+            operand(&mut elems, body, PRODUCT);
+            for v in vars.iter().rev() {
+                let v = match v.args_of("List") {
+                    Some([v, ..]) => v,
+                    _ => v,
+                };
+                elems.push(Element::op('\u{2062}'));
+                elems.push(Element::id_normal("d"));
+                elems.push(wrap(v, ATOM));
+            }
+            (Element::row(elems), SUM)
+        }
+        ("Limit", [body, rule, ..]) => {
+            let under = match rule.args_of("Rule") {
+                Some([v, to]) => Element::row([render(v).0, Element::op('→'), render(to).0]),
+                _ => render(rule).0,
+            };
+            let mut elems = vec![Element::under(Element::id_normal("lim"), under)];
+            operand(&mut elems, body, PRODUCT);
+            (Element::row(elems), SUM)
+        }
+        ("D", [body, vars @ ..]) if !vars.is_empty() => {
+            let mut elems = Vec::new();
+            for v in vars {
+                let (v, n) = match v.args_of("List") {
+                    Some([v, n]) => (v, Some(n)),
+                    _ => (v, None),
+                };
+                let frac = match n {
+                    Some(n) => Element::frac(
+                        Element::sup(Element::op('∂'), render(n).0),
+                        Element::row([Element::op('∂'), Element::sup(wrap(v, ATOM), render(n).0)]),
+                    ),
+                    None => Element::frac(
+                        Element::op('∂'),
+                        Element::row([Element::op('∂'), wrap(v, ATOM)]),
+                    ),
+                };
+                elems.push(frac);
+            }
+            operand(&mut elems, body, PRODUCT);
+            (Element::row(elems), PRODUCT)
+        }
+        ("Log", [b, x]) => {
+            let f = Element::sub(Element::id("log"), render(b).0);
+            func(f, std::slice::from_ref(x))
+        }
+        (name, [x]) if WRAPPERS.contains(&name) => render(x),
+        _ => {
+            if let Some((_, c, prec)) = INFIX.iter().find(|(h, ..)| *h == name) {
+                if args.len() >= 2 {
+                    let mut elems = Vec::new();
+                    for (i, a) in args.iter().enumerate() {
+                        if i > 0 {
+                            elems.push(Element::op(*c));
+                        }
+                        operand(&mut elems, a, prec + 1);
+                    }
+                    return (Element::row(elems), *prec);
+                }
+            }
+            if let Some((_, open, close)) = BRACKETS.iter().find(|(h, ..)| *h == name) {
+                let items = list(args.iter().map(|a| render(a).0));
+                return (fenced(Some(*open), items, Some(*close)), ATOM);
+            }
+            let f = match FUNCTIONS.iter().find(|(h, _)| *h == name) {
+                Some((_, n)) => Element::id(*n),
+                None => symbol(name),
+            };
+            func(f, args)
+        }
+    }
+}
+
+/// Render a product, with visible signs before numbers.
+fn times(args: &[Expr]) -> (Element, u8) {
+    match args {
+        [] => (Element::num("1"), ATOM),
+        [x] => render(x),
+        _ => {
+            let mut elems = Vec::new();
+            for (i, a) in args.iter().enumerate() {
+                if i == 0 {
+                    operand(&mut elems, a, PRODUCT);
+                    continue;
+                }
+                let c = if starts_with_number(a) {
+                    '×'
+                } else {
+                    '\u{2062}'
+                };
+                elems.push(Element::op(c));
+                operand(&mut elems, a, POSTFIX);
+            }
+            (Element::row(elems), PRODUCT)
+        }
+    }
+}
+
+fn starts_with_number(x: &Expr) -> bool {
+    match x {
+        Expr::Num(_) => true,
+        _ => match x.head() {
+            Some(("Power" | "Times" | "Factorial", [first, ..])) => starts_with_number(first),
+            _ => false,
+        },
+    }
+}
+
+/// Render a sum or product over a list of iterators, like `{i, 1, n}`.
+fn big_op(c: char, body: &Expr, iters: &[Expr]) -> (Element, u8) {
+    let mut elems = Vec::new();
+    for it in iters {
+        let eq = |v: &Expr, lo: Element| Element::row([render(v).0, Element::op('='), lo]);
+        let op = match it.args_of("List") {
+            Some([v, lo, hi, ..]) => {
+                Element::under_over(Element::op(c), eq(v, render(lo).0), render(hi).0)
+            }
+            Some([v, set]) if set.args_of("List").is_some() => Element::under(
+                Element::op(c),
+                Element::row([render(v).0, Element::op('∈'), render(set).0]),
+            ),
+            Some([v, hi]) => {
+                Element::under_over(Element::op(c), eq(v, Element::num("1")), render(hi).0)
+            }
+            Some([hi]) => Element::over(Element::op(c), render(hi).0),
+            _ => Element::under(Element::op(c), render(it).0),
+        };
+        elems.push(op);
+    }
+    operand(&mut elems, body, PRODUCT);
+    (Element::row(elems), SUM)
+}
+
+/// Get a table from a list of lists.
+fn matrix(m: &Expr) -> Option<Element> {
+    let rows = m.args_of("List")?;
+    let rows = rows
+        .iter()
+        .map(|r| {
+            Some(
+                r.args_of("List")?
+                    .iter()
+                    .map(|c| render(c).0)
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(Element::matrix(rows))
+}
+
+/// Render a function applied to a fenced argument list.
+fn func(head: Element, args: &[Expr]) -> (Element, u8) {
+    let args = list(args.iter().map(|a| render(a).0));
+    (
+        Element::row([
+            head,
+            Element::op('\u{2061}'),
+            fenced(Some('('), args, Some(')')),
+        ]),
+        ATOM,
+    )
+}
+
+/// Render an expression, wrapping it in parentheses if it binds looser than
+/// `min`.
+fn wrap(x: &Expr, min: u8) -> Element {
+    let (e, prec) = render(x);
+    if prec < min {
+        fenced(Some('('), into_vec(e), Some(')'))
+    } else {
+        e
+    }
+}
+
+/// Add an operand to a row, splicing it in unless it needs parentheses.
+fn operand(elems: &mut Vec<Element>, x: &Expr, min: u8) {
+    let (e, prec) = render(x);
+    splice(elems, e, prec, min);
+}
+
+/// Check if an expression is a box structure rather than InputForm.
+fn is_box(x: &Expr) -> bool {
+    match x.head() {
+        Some((name, _)) => name.ends_with("Box") || BOX_WRAPPERS.contains(&name),
+        None => false,
+    }
+}
+
+/// Convert a box structure.
+fn boxes(x: &Expr) -> Element {
+    let (name, args) = match x {
+        Expr::Str(s) => return token(s),
+        Expr::Num(t) => return Element::num(t),
+        Expr::Sym(s) => return symbol(s),
+        Expr::Err(msg) => return Element::err(msg),
+        Expr::Call(..) => match x.head() {
+            Some(h) => h,
+            None => return Element::err("unsupported box"),
+        },
+    };
+    match (name, args) {
+        ("RowBox", [items]) => match items.args_of("List") {
+            Some(items) => into_elem(items.iter().map(boxes).collect()),
+            None => boxes(items),
+        },
+        ("List", items) => into_elem(items.iter().map(boxes).collect()),
+        ("FractionBox", [n, d, ..]) => Element::frac(boxes(n), boxes(d)),
+        ("SuperscriptBox", [b, s, ..]) => Element::sup(boxes(b), boxes(s)),
+        ("SubscriptBox", [b, s, ..]) => Element::sub(boxes(b), boxes(s)),
+        ("SubsuperscriptBox", [b, sub, sup, ..]) => {
+            Element::sub_sup(boxes(b), boxes(sub), boxes(sup))
+        }
+        ("SqrtBox", [x, ..]) => Element::sqrt(boxes(x)),
+        ("RadicalBox", [x, n, ..]) => Element::root(boxes(x), boxes(n)),
+        ("OverscriptBox", [b, o, ..]) => match accent(o) {
+            Some(c) => Element::over_accent(boxes(b), Element::op(c)),
+            None => Element::over(boxes(b), boxes(o)),
+        },
+        ("UnderscriptBox", [b, u, ..]) => match accent(u) {
+            Some(c) => Element::under_accent(boxes(b), Element::op(c)),
+            None => Element::under(boxes(b), boxes(u)),
+        },
+        ("UnderoverscriptBox", [b, u, o, ..]) => Element::under_over(boxes(b), boxes(u), boxes(o)),
+        ("GridBox", [rows, ..]) => {
+            let rows = rows.args_of("List").map(|rows| {
+                rows.iter()
+                    .map(|r| {
+                        let cells = r.args_of("List").unwrap_or(std::slice::from_ref(r));
+                        TableRow::new(cells.iter().map(|c| TableCell::new(into_vec(boxes(c)))))
+                    })
+                    .collect::<Vec<_>>()
+            });
+            match rows {
+                Some(rows) => Element::table(rows),
+                None => Element::err("GridBox needs a list of rows"),
+            }
+        }
+        ("StyleBox", [b, opts @ ..]) => style(boxes(b), opts),
+        (name, [b, ..]) if BOX_WRAPPERS.contains(&name) => boxes(b),
+        (name, _) => Element::err(format!("unsupported box {}", name)),
+    }
+}
+
+/// Convert a string in a box structure.
+fn token(s: &str) -> Element {
+    if let Some(t) = s.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        return Element::text(t);
+    }
+    if s.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+        && s.chars().all(|c| c.is_ascii_digit() || c == '.')
+    {
+        return Element::num(s);
+    }
+    if SYMBOLS.iter().any(|(n, ..)| *n == s) {
+        return symbol(s);
+    }
+    if s.starts_with(|c: char| c.is_alphabetic() || c == '$') {
+        return Element::id(s);
+    }
+    if let Some((_, c)) = BOX_OPS.iter().find(|(o, _)| *o == s) {
+        return Element::op(*c);
+    }
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if !c.is_alphanumeric() && !c.is_ascii() && !is_op_char(c) => {
+            Element::id(s)
+        }
+        (Some(c), None) => Element::op(c),
+        _ => Element::text(s),
+    }
+}
+
+/// Check for characters that are displayed as symbols rather than operators.
+fn is_op_char(c: char) -> bool {
+    !matches!(c, '∞' | '°' | '∅' | 'ℵ' | 'ℏ' | 'ℂ' | 'ℕ' | 'ℚ' | 'ℝ' | 'ℤ')
+}
+
+/// Get the accent character for an overscript or underscript.
+fn accent(x: &Expr) -> Option<char> {
+    match x {
+        Expr::Str(s) => ACCENTS.iter().find(|(a, _)| a == s).map(|(_, c)| *c),
+        _ => None,
+    }
+}
+
+/// Apply the font options of a `StyleBox`.
+fn style(e: Element, opts: &[Expr]) -> Element {
+    let mut bold = false;
+    let mut italic = false;
+    for o in opts {
+        match o {
+            Expr::Sym(s) if s == "Bold" => bold = true,
+            Expr::Sym(s) if s == "Italic" => italic = true,
+            Expr::Str(s) if s == "TI" => italic = true,
+            Expr::Str(s) if s == "TB" => bold = true,
+            _ => match o.args_of("Rule") {
+                Some([Expr::Sym(k), v]) if k == "FontWeight" => {
+                    bold = matches!(v, Expr::Str(s) | Expr::Sym(s) if s == "Bold");
+                }
+                Some([Expr::Sym(k), v]) if k == "FontSlant" => {
+                    italic = matches!(v, Expr::Str(s) | Expr::Sym(s) if s == "Italic");
+                }
+                _ => (),
+            },
+        }
+    }
+    match (bold, italic) {
+        (true, true) => e.variant(Variant::BoldItalic),
+        (true, false) => e.variant(Variant::Bold),
+        (false, true) => e.variant(Variant::Italic),
+        (false, false) => e,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(
+            parse("a + b - 2 c"),
+            Element::row([
+                Element::id("a"),
+                Element::op('+'),
+                Element::id("b"),
+                Element::op('−'),
+                Element::num("2"),
+                Element::op('\u{2062}'),
+                Element::id("c"),
+            ])
+        );
+        assert_eq!(
+            parse("(x + 1)^2/y"),
+            Element::frac(
+                Element::sup(
+                    Element::row([
+                        Element::op('('),
+                        Element::id("x"),
+                        Element::op('+'),
+                        Element::num("1"),
+                        Element::op(')'),
+                    ]),
+                    Element::num("2"),
+                ),
+                Element::id("y"),
+            )
+        );
+        assert_eq!(
+            parse("-x^2 == 3*^5"),
+            Element::row([
+                Element::op('−'),
+                Element::sup(Element::id("x"), Element::num("2")),
+                Element::op('='),
+                Element::num("3"),
+                Element::op('×'),
+                Element::sup(Element::num("10"), Element::num("5")),
+            ])
+        );
+        assert_eq!(
+            parse("Times[a, Power[b, -1]]"),
+            Element::frac(Element::id("a"), Element::id("b"))
+        );
+        assert_eq!(
+            parse("n! (* comment *)"),
+            Element::row([Element::id("n"), Element::op('!')])
+        );
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(
+            parse("Sin[\\[Alpha]] + Sqrt[x]"),
+            Element::row([
+                Element::id("sin"),
+                Element::op('\u{2061}'),
+                Element::row([Element::op('('), Element::id("α"), Element::op(')')]),
+                Element::op('+'),
+                Element::sqrt(Element::id("x")),
+            ])
+        );
+        assert_eq!(
+            parse("f'[x]"),
+            Element::row([
+                Element::sup(Element::id("f"), Element::op('′')),
+                Element::op('\u{2061}'),
+                Element::row([Element::op('('), Element::id("x"), Element::op(')')]),
+            ])
+        );
+        assert_eq!(
+            parse("Subscript[x, i]^2"),
+            Element::sub_sup(Element::id("x"), Element::id("i"), Element::num("2"))
+        );
+        assert_eq!(
+            parse("Power[x, 1/3]"),
+            Element::root(Element::id("x"), Element::num("3"))
+        );
+        assert_eq!(
+            parse("Abs[E^(I Pi)]"),
+            Element::row([
+                Element::op('|'),
+                Element::sup(
+                    Element::id_normal("e"),
+                    Element::row([
+                        Element::id_normal("i"),
+                        Element::op('\u{2062}'),
+                        Element::id("π"),
+                    ]),
+                ),
+                Element::op('|'),
+            ])
+        );
+    }
+
+    #[test]
+    fn calculus() {
+        let square = Element::sup(Element::id("i"), Element::num("2"));
+        assert_eq!(
+            parse("Sum[i^2, {i, 1, n}]"),
+            Element::row([
+                Element::under_over(
+                    Element::op('∑'),
+                    Element::row([Element::id("i"), Element::op('='), Element::num("1")]),
+                    Element::id("n"),
+                ),
+                square,
+            ])
+        );
+        assert_eq!(
+            parse("Integrate[x^2, {x, 0, 1}]"),
+            Element::row([
+                Element::sub_sup(Element::op('∫'), Element::num("0"), Element::num("1")),
+                Element::sup(Element::id("x"), Element::num("2")),
+                Element::op('\u{2062}'),
+                Element::id_normal("d"),
+                Element::id("x"),
+            ])
+        );
+        assert_eq!(
+            parse("Limit[1/x, x -> Infinity]"),
+            Element::row([
+                Element::under(
+                    Element::id_normal("lim"),
+                    Element::row([Element::id("x"), Element::op('→'), Element::id_normal("∞"),]),
+                ),
+                Element::frac(Element::num("1"), Element::id("x")),
+            ])
+        );
+    }
+
+    #[test]
+    fn lists_and_matrices() {
+        assert_eq!(
+            parse("{a, b}"),
+            Element::row([
+                Element::op('{'),
+                Element::id("a"),
+                Element::op(','),
+                Element::id("b"),
+                Element::op('}'),
+            ])
+        );
+        assert_eq!(
+            parse("MatrixForm[{{1, 0}, {0, 1}}]"),
+            Element::row([
+                Element::op('('),
+                Element::matrix([
+                    [Element::num("1"), Element::num("0")],
+                    [Element::num("0"), Element::num("1")],
+                ]),
+                Element::op(')'),
+            ])
+        );
+    }
+
+    #[test]
+    fn boxes() {
+        assert_eq!(
+            parse(r#"RowBox[{"x", "+", FractionBox["1", SuperscriptBox["y", "2"]]}]"#),
+            Element::row([
+                Element::id("x"),
+                Element::op('+'),
+                Element::frac(
+                    Element::num("1"),
+                    Element::sup(Element::id("y"), Element::num("2")),
+                ),
+            ])
+        );
+        assert_eq!(
+            parse(r#"SubsuperscriptBox["\[Integral]", "0", "1"]"#),
+            Element::sub_sup(Element::op('∫'), Element::num("0"), Element::num("1"))
+        );
+        assert_eq!(
+            parse(r#"RowBox[{SqrtBox["x"], "\[LessEqual]", RadicalBox["y", "3"]}]"#),
+            Element::row([
+                Element::sqrt(Element::id("x")),
+                Element::op('≤'),
+                Element::root(Element::id("y"), Element::num("3")),
+            ])
+        );
+        assert_eq!(
+            parse(r#"RowBox[{"(", GridBox[{{"a", "b"}, {"c", "d"}}], ")"}]"#),
+            Element::row([
+                Element::op('('),
+                Element::matrix([
+                    [Element::id("a"), Element::id("b")],
+                    [Element::id("c"), Element::id("d")],
+                ]),
+                Element::op(')'),
+            ])
+        );
+        assert_eq!(
+            parse(
+                r#"BoxData[StyleBox[OverscriptBox["v", "\[RightVector]"], FontWeight -> "Bold"]]"#
+            ),
+            Element::over_accent(Element::id("v"), Element::op('→')).variant(Variant::Bold)
+        );
+        assert_eq!(
+            parse(r#"RowBox[{"\"if\"", " ", "x"}]"#),
+            Element::row([
+                Element::text("if"),
+                Element::op('\u{2062}'),
+                Element::id("x"),
+            ])
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            parse("f[x"),
+            Element::row([
+                Element::id("f"),
+                Element::op('\u{2061}'),
+                Element::row([
+                    Element::op('('),
+                    Element::id("x"),
+                    Element::op(','),
+                    Element::err("expected `]`"),
+                    Element::op(')'),
+                ]),
+            ])
+        );
+        assert_eq!(
+            parse("TemplateBox[{}, \"Spacer1\"]"),
+            Element::err("unsupported box TemplateBox")
+        );
+        assert_eq!(
+            parse("a + )"),
+            Element::row([
+                Element::id("a"),
+                Element::op('+'),
+                Element::err("unexpected `)`"),
+            ])
+        );
+        // An unterminated named character is left as it is.
+        assert_eq!(named(r"x + \[Alpha"), r"x + \[Alpha");
+        assert_eq!(named(r"\[Alpha] + \[Be"), r"α + \[Be");
+        parse(r"\[Alpha");
+    }
+}
@@ -0,0 +1,587 @@
+//! A minimal XML reader, just capable enough for the XML-based math formats.
+//!
+//! Namespaces are not resolved; elements and attributes keep their prefixed
+//! names, and converters match on the local part of a name instead.
+
+use std::fmt;
+
+/// An error encountered while reading an XML document.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct XmlError {
+    /// Byte offset into the source where the error was found.
+    pub pos: usize,
+    /// Description of the error.
+    pub msg: String,
+}
+
+impl fmt::Display for XmlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "XML error at byte {}: {}", self.pos, self.msg)
+    }
+}
+
+impl std::error::Error for XmlError {}
+
+/// An XML element, holding its attributes and child nodes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct XmlNode {
+    pub name: String,
+    pub attrs: Vec<(String, String)>,
+    pub children: Vec<XmlChild>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum XmlChild {
+    Elem(XmlNode),
+    Text(String),
+}
+
+/// Strip any namespace prefix from a name.
+pub(crate) fn local_name(name: &str) -> &str {
+    name.rsplit_once(':').map_or(name, |(_, l)| l)
+}
+
+impl XmlNode {
+    /// Create an element with no attributes or children.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Add an attribute to the element.
+    pub fn attr_add(mut self, name: impl Into<String>, val: impl Into<String>) -> Self {
+        self.attrs.push((name.into(), val.into()));
+        self
+    }
+
+    /// Add a child element.
+    pub fn child(mut self, child: XmlNode) -> Self {
+        self.children.push(XmlChild::Elem(child));
+        self
+    }
+
+    /// Add a child text node.
+    pub fn text_add(mut self, text: impl Into<String>) -> Self {
+        self.children.push(XmlChild::Text(text.into()));
+        self
+    }
+
+    /// The element's name without any namespace prefix.
+    pub fn local(&self) -> &str {
+        local_name(&self.name)
+    }
+
+    /// Look up an attribute by its local name.
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(k, _)| local_name(k) == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Iterate over all child elements, skipping text.
+    pub fn elems(&self) -> impl Iterator<Item = &XmlNode> {
+        self.children.iter().filter_map(|c| match c {
+            XmlChild::Elem(e) => Some(e),
+            XmlChild::Text(_) => None,
+        })
+    }
+
+    /// Find the first element, in document order, with the given local name.
+    /// This element is included in the search.
+    pub fn find(&self, name: &str) -> Option<&XmlNode> {
+        if self.local() == name {
+            return Some(self);
+        }
+        self.elems().find_map(|e| e.find(name))
+    }
+
+    /// Concatenate all text within this element and its descendants.
+    pub fn text(&self) -> String {
+        let mut out = String::new();
+        self.text_into(&mut out);
+        out
+    }
+
+    fn text_into(&self, out: &mut String) {
+        for c in self.children.iter() {
+            match c {
+                XmlChild::Elem(e) => e.text_into(out),
+                XmlChild::Text(t) => out.push_str(t),
+            }
+        }
+    }
+
+    /// Write the element out as XML. If `indent` is set, child elements are
+    /// placed on their own lines, unless the element directly contains text.
+    pub fn write(&self, out: &mut String, indent: Option<&str>, depth: usize) {
+        out.push('<');
+        out.push_str(&self.name);
+        for (k, v) in self.attrs.iter() {
+            out.push(' ');
+            out.push_str(k);
+            out.push_str("=\"");
+            escape_into(out, v, true);
+            out.push('"');
+        }
+        if self.children.is_empty() {
+            out.push_str("/>");
+            return;
+        }
+        out.push('>');
+        let has_text = self.children.iter().any(|c| matches!(c, XmlChild::Text(_)));
+        let indent = if has_text { None } else { indent };
+        for c in self.children.iter() {
+            if let Some(indent) = indent {
+                out.push('\n');
+                out.push_str(&indent.repeat(depth + 1));
+            }
+            match c {
+                XmlChild::Elem(e) => e.write(out, indent, depth + 1),
+                XmlChild::Text(t) => escape_into(out, t, false),
+            }
+        }
+        if let Some(indent) = indent {
+            out.push('\n');
+            out.push_str(&indent.repeat(depth));
+        }
+        out.push_str("</");
+        out.push_str(&self.name);
+        out.push('>');
+    }
+}
+
+/// Escape text for inclusion in XML content or a quoted attribute value.
+fn escape_into(out: &mut String, s: &str, attr: bool) {
+    for c in s.chars() {
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' => out.push_str("&amp;"),
+            '"' if attr => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+}
+
+/// Parse an XML document and return its root element.
+pub(crate) fn parse(src: &str) -> Result<XmlNode, XmlError> {
+    let mut p = Parser { src, pos: 0 };
+    p.skip_misc()?;
+    if !p.rest().starts_with('<') {
+        return Err(p.err("expected a root element"));
+    }
+    let root = p.element()?;
+    p.skip_misc()?;
+    if p.pos < src.len() {
+        return Err(p.err("unexpected content after the root element"));
+    }
+    Ok(root)
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn err(&self, msg: impl Into<String>) -> XmlError {
+        XmlError {
+            pos: self.pos,
+            msg: msg.into(),
+        }
+    }
+
+    fn skip_ws(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn skip_past(&mut self, end: &str) -> Result<(), XmlError> {
+        match self.rest().find(end) {
+            Some(i) => {
+                self.pos += i + end.len();
+                Ok(())
+            }
+            None => Err(self.err(format!("missing closing `{}`", end))),
+        }
+    }
+
+    /// Skip the prolog, comments, processing instructions, and doctypes.
+    fn skip_misc(&mut self) -> Result<(), XmlError> {
+        loop {
+            self.skip_ws();
+            let rest = self.rest();
+            if rest.starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if rest.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if rest.starts_with("<!DOCTYPE") {
+                self.skip_doctype()?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn skip_doctype(&mut self) -> Result<(), XmlError> {
+        let mut depth = 0;
+        for (i, c) in self.rest().char_indices() {
+            match c {
+                '[' => depth += 1,
+                ']' => depth -= 1,
+                '>' if depth == 0 => {
+                    self.pos += i + 1;
+                    return Ok(());
+                }
+                _ => (),
+            }
+        }
+        Err(self.err("unterminated DOCTYPE"))
+    }
+
+    fn name(&mut self) -> Result<&'a str, XmlError> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '/' | '>' | '=' | '<'))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(self.err("expected a name"));
+        }
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    fn element(&mut self) -> Result<XmlNode, XmlError> {
+        self.pos += 1; // '<'
+        let mut node = XmlNode::new(self.name()?);
+        loop {
+            self.skip_ws();
+            let rest = self.rest();
+            if rest.starts_with("/>") {
+                self.pos += 2;
+                return Ok(node);
+            }
+            if rest.starts_with('>') {
+                self.pos += 1;
+                break;
+            }
+            let key = self.name()?;
+            self.skip_ws();
+            if !self.rest().starts_with('=') {
+                return Err(self.err(format!("expected `=` after attribute `{}`", key)));
+            }
+            self.pos += 1;
+            self.skip_ws();
+            let quote = match self.rest().chars().next() {
+                Some(q @ ('"' | '\'')) => q,
+                _ => return Err(self.err("expected a quoted attribute value")),
+            };
+            self.pos += 1;
+            let Some(len) = self.rest().find(quote) else {
+                return Err(self.err("unterminated attribute value"));
+            };
+            let val = unescape(&self.rest()[..len], self.pos)?;
+            self.pos += len + 1;
+            node.attrs.push((key.to_string(), val));
+        }
+        loop {
+            let rest = self.rest();
+            if rest.is_empty() {
+                return Err(self.err(format!("missing closing tag for `{}`", node.name)));
+            }
+            if rest.starts_with("</") {
+                self.pos += 2;
+                let name = self.name()?;
+                if name != node.name {
+                    return Err(self.err(format!(
+                        "closing tag `{}` doesn't match `{}`",
+                        name, node.name
+                    )));
+                }
+                self.skip_ws();
+                if !self.rest().starts_with('>') {
+                    return Err(self.err("expected `>`"));
+                }
+                self.pos += 1;
+                // Whitespace between child elements is insignificant
+                if node.children.iter().any(|c| matches!(c, XmlChild::Elem(_))) {
+                    node.children.retain(|c| match c {
+                        XmlChild::Text(t) => !t.trim().is_empty(),
+                        XmlChild::Elem(_) => true,
+                    });
+                }
+                return Ok(node);
+            } else if rest.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if rest.starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if let Some(r) = rest.strip_prefix("<![CDATA[") {
+                let Some(len) = r.find("]]>") else {
+                    return Err(self.err("unterminated CDATA section"));
+                };
+                push_text(&mut node, &r[..len]);
+                self.pos += 9 + len + 3;
+            } else if rest.starts_with('<') {
+                let child = self.element()?;
+                node.children.push(XmlChild::Elem(child));
+            } else {
+                let len = rest.find('<').unwrap_or(rest.len());
+                let text = unescape(&rest[..len], self.pos)?;
+                push_text(&mut node, &text);
+                self.pos += len;
+            }
+        }
+    }
+}
+
+fn push_text(node: &mut XmlNode, text: &str) {
+    if let Some(XmlChild::Text(t)) = node.children.last_mut() {
+        t.push_str(text);
+    } else {
+        node.children.push(XmlChild::Text(text.to_string()));
+    }
+}
+
+fn unescape(s: &str, pos: usize) -> Result<String, XmlError> {
+    if !s.contains('&') {
+        return Ok(s.to_string());
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('&') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+        let err = |msg: String| XmlError {
+            pos: pos + (s.len() - rest.len()),
+            msg,
+        };
+        let Some(end) = rest.find(';') else {
+            return Err(err("unterminated entity reference".into()));
+        };
+        let ent = &rest[..end];
+        let c = if let Some(num) = ent.strip_prefix('#') {
+            let code = match num.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16),
+                None => num.parse(),
+            };
+            code.ok().and_then(char::from_u32)
+        } else {
+            entity(ent)
+        };
+        match c {
+            Some(c) => out.push(c),
+            None => return Err(err(format!("unknown entity `&{};`", ent))),
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Named entities. Covers the XML builtins plus the MathML entities that
+/// commonly turn up in published documents.
+fn entity(name: &str) -> Option<char> {
+    Some(match name {
+        "lt" => '<',
+        "gt" => '>',
+        "amp" => '&',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" | "NonBreakingSpace" => '\u{a0}',
+        "ApplyFunction" | "af" => '\u{2061}',
+        "InvisibleTimes" | "it" => '\u{2062}',
+        "InvisibleComma" | "ic" => '\u{2063}',
+        "InvisiblePlus" => '\u{2064}',
+        "minus" => '−',
+        "times" => '×',
+        "divide" | "div" => '÷',
+        "plusmn" | "PlusMinus" | "pm" => '±',
+        "mnplus" | "MinusPlus" | "mp" => '∓',
+        "middot" | "centerdot" | "CenterDot" => '·',
+        "sdot" => '⋅',
+        "compfn" | "SmallCircle" => '∘',
+        "sum" | "Sum" => '∑',
+        "prod" | "Product" => '∏',
+        "coprod" | "Coproduct" => '∐',
+        "int" | "Integral" => '∫',
+        "iint" | "Int" => '∬',
+        "iiint" | "tint" => '∭',
+        "conint" | "oint" | "ContourIntegral" => '∮',
+        "infin" => '∞',
+        "part" | "PartialD" => '∂',
+        "nabla" | "Del" => '∇',
+        "Sqrt" | "radic" => '√',
+        "prop" | "propto" | "Proportional" => '∝',
+        "ne" | "NotEqual" => '≠',
+        "le" | "leq" => '≤',
+        "ge" | "geq" | "GreaterEqual" => '≥',
+        "ll" | "Lt" => '≪',
+        "gg" | "Gt" => '≫',
+        "asymp" | "approx" | "ap" => '≈',
+        "sim" | "Tilde" => '∼',
+        "simeq" | "TildeEqual" => '≃',
+        "cong" | "TildeFullEqual" => '≅',
+        "equiv" | "Congruent" => '≡',
+        "isin" | "in" | "Element" => '∈',
+        "notin" | "NotElement" => '∉',
+        "ni" | "ReverseElement" => '∋',
+        "sub" | "subset" => '⊂',
+        "sup" | "supset" | "Superset" => '⊃',
+        "sube" | "subseteq" | "SubsetEqual" => '⊆',
+        "supe" | "supseteq" | "SupersetEqual" => '⊇',
+        "cap" => '∩',
+        "cup" => '∪',
+        "empty" | "emptyset" | "emptyv" => '∅',
+        "forall" | "ForAll" => '∀',
+        "exist" | "Exists" => '∃',
+        "nexist" | "NotExists" => '∄',
+        "and" | "wedge" => '∧',
+        "or" | "vee" => '∨',
+        "not" => '¬',
+        "rarr" | "rightarrow" | "RightArrow" => '→',
+        "larr" | "leftarrow" | "LeftArrow" => '←',
+        "harr" | "leftrightarrow" | "LeftRightArrow" => '↔',
+        "rArr" | "Rightarrow" | "Implies" => '⇒',
+        "lArr" | "Leftarrow" => '⇐',
+        "hArr" | "iff" | "Leftrightarrow" => '⇔',
+        "uarr" | "uparrow" => '↑',
+        "darr" | "downarrow" => '↓',
+        "mapsto" | "map" => '↦',
+        "deg" => '°',
+        "prime" => '′',
+        "Prime" => '″',
+        "hellip" | "mldr" => '…',
+        "ctdot" => '⋯',
+        "vellip" => '⋮',
+        "dtdot" => '⋱',
+        "lang" | "langle" | "LeftAngleBracket" => '⟨',
+        "rang" | "rangle" | "RightAngleBracket" => '⟩',
+        "lceil" | "LeftCeiling" => '⌈',
+        "rceil" | "RightCeiling" => '⌉',
+        "lfloor" | "LeftFloor" => '⌊',
+        "rfloor" | "RightFloor" => '⌋',
+        "verbar" | "vert" | "VerticalLine" => '|',
+        "Verbar" | "Vert" | "parallel" => '‖',
+        "perp" | "bottom" => '⊥',
+        "top" => '⊤',
+        "angle" | "ang" => '∠',
+        "there4" | "therefore" => '∴',
+        "because" => '∵',
+        "oplus" | "CirclePlus" => '⊕',
+        "otimes" | "CircleTimes" => '⊗',
+        "hbar" | "planck" => 'ℏ',
+        "ell" => 'ℓ',
+        "weierp" | "wp" => '℘',
+        "Re" | "real" => 'ℜ',
+        "Im" | "image" => 'ℑ',
+        "aleph" => 'ℵ',
+        "Copf" | "complexes" => 'ℂ',
+        "Nopf" | "naturals" => 'ℕ',
+        "Qopf" | "rationals" => 'ℚ',
+        "Ropf" | "reals" => 'ℝ',
+        "Zopf" | "integers" => 'ℤ',
+        "OverBar" => '‾',
+        "UnderBar" => '_',
+        "Hat" => '^',
+        "OverBrace" => '⏞',
+        "UnderBrace" => '⏟',
+        "alpha" => 'α',
+        "beta" => 'β',
+        "gamma" => 'γ',
+        "delta" => 'δ',
+        "epsi" | "epsilon" => 'ε',
+        "epsiv" | "varepsilon" => 'ϵ',
+        "zeta" => 'ζ',
+        "eta" => 'η',
+        "theta" => 'θ',
+        "thetav" | "vartheta" => 'ϑ',
+        "iota" => 'ι',
+        "kappa" => 'κ',
+        "lambda" => 'λ',
+        "mu" => 'μ',
+        "nu" => 'ν',
+        "xi" => 'ξ',
+        "omicron" => 'ο',
+        "pi" => 'π',
+        "piv" | "varpi" => 'ϖ',
+        "rho" => 'ρ',
+        "rhov" | "varrho" => 'ϱ',
+        "sigma" => 'σ',
+        "sigmav" | "varsigma" => 'ς',
+        "tau" => 'τ',
+        "upsi" | "upsilon" => 'υ',
+        "phi" => 'φ',
+        "phiv" | "varphi" => 'ϕ',
+        "chi" => 'χ',
+        "psi" => 'ψ',
+        "omega" => 'ω',
+        "Gamma" => 'Γ',
+        "Delta" => 'Δ',
+        "Theta" => 'Θ',
+        "Lambda" => 'Λ',
+        "Xi" => 'Ξ',
+        "Pi" => 'Π',
+        "Sigma" => 'Σ',
+        "Upsi" | "Upsilon" => 'Υ',
+        "Phi" => 'Φ',
+        "Psi" => 'Ψ',
+        "Omega" => 'Ω',
+        "ThinSpace" | "thinsp" => '\u{2009}',
+        "MediumSpace" => '\u{205f}',
+        "ZeroWidthSpace" => '\u{200b}',
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_nested() {
+        let root = parse(
+            r#"<?xml version="1.0"?>
+            <!-- comment -->
+            <math xmlns="http://www.w3.org/1998/Math/MathML" display='block'>
+              <mi>x</mi><mo>&lt;</mo><mn>&#x33;</mn><mtext><![CDATA[a<b]]></mtext>
+            </math>"#,
+        )
+        .unwrap();
+        assert_eq!(root.local(), "math");
+        assert_eq!(root.attr("display"), Some("block"));
+        let names: Vec<_> = root.elems().map(|e| e.local()).collect();
+        assert_eq!(names, ["mi", "mo", "mn", "mtext"]);
+        let texts: Vec<_> = root.elems().map(|e| e.text()).collect();
+        assert_eq!(texts, ["x", "<", "3", "a<b"]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse("<a><b></a>").is_err());
+        assert!(parse("<a>&bogus;</a>").is_err());
+        assert!(parse("<a></a><b/>").is_err());
+        assert!(parse("<a x=1/>").is_err());
+    }
+
+    #[test]
+    fn write_roundtrip() {
+        let node = XmlNode::new("m:f")
+            .attr_add("q", "\"")
+            .child(XmlNode::new("m:r").text_add("a<b&c"))
+            .child(XmlNode::new("m:e"));
+        let mut out = String::new();
+        node.write(&mut out, Some("  "), 0);
+        assert_eq!(
+            out,
+            "<m:f q=\"&quot;\">\n  <m:r>a&lt;b&amp;c</m:r>\n  <m:e/>\n</m:f>"
+        );
+        assert_eq!(parse(&out).unwrap(), node);
+    }
+}